@@ -10,7 +10,10 @@ use rama::{
         IntoResponse, Request, Response, StatusCode,
         client::{
             HttpClient,
-            proxy::layer::{HttpProxyAddressLayer, SetProxyAuthHttpHeaderLayer},
+            proxy::{
+                NoProxy, ProxyEnv,
+                layer::{HttpProxyEnvLayer, SetProxyAuthHttpHeaderLayer},
+            },
         },
         layer::{
             auth::AddAuthorizationLayer,
@@ -69,7 +72,8 @@ pub struct CliCommandHttp {
     max_redirects: usize,
 
     #[arg(long, short = 'P')]
-    /// upstream proxy to use (can also be specified using PROXY env variable)
+    /// upstream proxy to use (can also be specified using the
+    /// HTTP_PROXY, HTTPS_PROXY and ALL_PROXY env variables, respecting NO_PROXY)
     proxy: Option<String>,
 
     #[arg(long, short = 'U')]
@@ -400,8 +404,8 @@ where
             .unwrap_or_else(AddAuthorizationLayer::none),
         AddRequiredRequestHeadersLayer::default(),
         request_writer,
-        match cfg.proxy {
-            None => HttpProxyAddressLayer::try_from_env_default()?,
+        HttpProxyEnvLayer::new(match cfg.proxy {
+            None => ProxyEnv::try_from_env()?,
             Some(proxy) => {
                 let mut proxy_address: ProxyAddress =
                     proxy.parse().context("parse proxy address")?;
@@ -410,9 +414,12 @@ where
                         .context("parse proxy credentials")?;
                     proxy_address.credential = Some(credential);
                 }
-                HttpProxyAddressLayer::maybe(Some(proxy_address))
+                // same as curl, the NO_PROXY env var is respected for explicit proxies as well
+                ProxyEnv::new()
+                    .with_all_proxy(proxy_address)
+                    .with_no_proxy(NoProxy::from_env())
             }
-        },
+        }),
         SetProxyAuthHttpHeaderLayer::default(),
         HijackLayer::new(cfg.offline, service_fn(dummy_response)),
     );
@@ -0,0 +1,130 @@
+//! A [`Policy`] that records the rejections of another [`Policy`].
+//!
+//! See [`MetricsPolicy`].
+
+use super::{Policy, PolicyOutput, PolicyResult};
+use crate::Context;
+use crate::telemetry::prometheus::{Counter, Registry};
+use std::{borrow::Cow, fmt};
+
+const LIMIT_REJECTIONS: &str = "rama_limit_rejections";
+
+/// A [`Policy`] which counts the requests aborted by the wrapped [`Policy`]
+/// in the `rama_limit_rejections` Prometheus counter,
+/// labeled with the name of the limit.
+///
+/// # Example
+///
+/// ```
+/// use rama_core::layer::limit::{Limit, policy::{ConcurrentPolicy, MetricsPolicy}};
+/// use rama_core::service::service_fn;
+/// # use std::convert::Infallible;
+///
+/// let service = service_fn(|_: ()| async {
+///     Ok::<_, Infallible>(())
+/// });
+/// let service = Limit::new(
+///     service,
+///     MetricsPolicy::new("concurrency", ConcurrentPolicy::max(2)),
+/// );
+/// ```
+pub struct MetricsPolicy<P> {
+    name: Cow<'static, str>,
+    policy: P,
+    rejections: Counter,
+}
+
+impl<P: fmt::Debug> fmt::Debug for MetricsPolicy<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsPolicy")
+            .field("name", &self.name)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl<P: Clone> Clone for MetricsPolicy<P> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            policy: self.policy.clone(),
+            rejections: self.rejections.clone(),
+        }
+    }
+}
+
+impl<P> MetricsPolicy<P> {
+    /// Wrap the given [`Policy`], recording its rejections
+    /// under the given limit name in the [global `Registry`].
+    ///
+    /// [global `Registry`]: Registry::global
+    pub fn new(name: impl Into<Cow<'static, str>>, policy: P) -> Self {
+        Self::with_registry(Registry::global(), name, policy)
+    }
+
+    /// Wrap the given [`Policy`], recording its rejections
+    /// under the given limit name in the given [`Registry`].
+    pub fn with_registry(
+        registry: &Registry,
+        name: impl Into<Cow<'static, str>>,
+        policy: P,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            policy,
+            rejections: registry.counter(
+                LIMIT_REJECTIONS,
+                "Amount of requests rejected by a limit policy.",
+                &["limit"],
+            ),
+        }
+    }
+}
+
+impl<State, Request, P> Policy<State, Request> for MetricsPolicy<P>
+where
+    P: Policy<State, Request>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = P::Guard;
+    type Error = P::Error;
+
+    async fn check(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let result = self.policy.check(ctx, request).await;
+        if let PolicyOutput::Abort(_) = result.output {
+            self.rejections.inc(&[&self.name]);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::limit::policy::ConcurrentPolicy;
+    use crate::telemetry::prometheus::ExpositionFormat;
+
+    #[tokio::test]
+    async fn test_metrics_policy_counts_rejections() {
+        let registry = Registry::new();
+        let policy = MetricsPolicy::with_registry(&registry, "test", ConcurrentPolicy::max(1));
+
+        let first = policy.check(Context::default(), ()).await;
+        assert!(matches!(first.output, PolicyOutput::Ready(_)));
+        for _ in 0..2 {
+            let result = policy.check(Context::default(), ()).await;
+            assert!(matches!(result.output, PolicyOutput::Abort(_)));
+        }
+
+        let output = registry.encode(ExpositionFormat::Prometheus);
+        assert!(
+            output.contains(r#"rama_limit_rejections_total{limit="test"} 2"#),
+            "{output}"
+        );
+    }
+}
@@ -37,6 +37,10 @@ pub use concurrent::{ConcurrentCounter, ConcurrentPolicy, ConcurrentTracker, Lim
 
 mod matcher;
 
+mod metrics;
+#[doc(inline)]
+pub use metrics::MetricsPolicy;
+
 /// The full result of a limit policy.
 pub struct PolicyResult<State, Request, Guard, Error> {
     /// The input context
@@ -43,5 +43,4 @@ pub mod matcher;
 
 pub mod username;
 
-#[cfg(feature = "telemetry")]
 pub mod telemetry;
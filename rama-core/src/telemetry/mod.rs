@@ -1,3 +1,6 @@
 //! Rama telemetry modules.
 
+#[cfg(feature = "telemetry")]
 pub mod opentelemetry;
+
+pub mod prometheus;
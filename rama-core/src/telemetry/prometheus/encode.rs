@@ -0,0 +1,207 @@
+use super::{Family, FamilyKind, Registry, Value};
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+
+/// The format in which a [`Registry`] is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExpositionFormat {
+    /// The Prometheus text-based exposition format (version 0.0.4).
+    #[default]
+    Prometheus,
+    /// The [OpenMetrics](https://openmetrics.io) text format (version 1.0.0).
+    OpenMetrics,
+}
+
+impl ExpositionFormat {
+    /// The (http) content type of this [`ExpositionFormat`].
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Self::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+impl Registry {
+    /// Encode all metric families of this [`Registry`] in the given [`ExpositionFormat`].
+    pub fn encode(&self, format: ExpositionFormat) -> String {
+        let families: Vec<_> = self.families.lock().clone();
+
+        let mut output = String::new();
+        for family in families {
+            encode_family(&mut output, &family, format);
+        }
+        if format == ExpositionFormat::OpenMetrics {
+            output.push_str("# EOF\n");
+        }
+        output
+    }
+}
+
+fn encode_family(output: &mut String, family: &Family, format: ExpositionFormat) {
+    let (type_name, family_name) = match (&family.kind, format) {
+        (FamilyKind::Counter, ExpositionFormat::Prometheus) => {
+            ("counter", format!("{}_total", family.name))
+        }
+        (FamilyKind::Counter, ExpositionFormat::OpenMetrics) => ("counter", family.name.clone()),
+        (FamilyKind::Gauge, _) => ("gauge", family.name.clone()),
+        (FamilyKind::Histogram(_), _) => ("histogram", family.name.clone()),
+    };
+
+    let _ = write!(output, "# HELP {family_name} ");
+    write_escaped(
+        output,
+        &family.help,
+        format == ExpositionFormat::OpenMetrics,
+    );
+    let _ = writeln!(output, "\n# TYPE {family_name} {type_name}");
+
+    let series: Vec<_> = family
+        .series
+        .lock()
+        .iter()
+        .map(|(labels, value)| (labels.clone(), value.clone()))
+        .collect();
+
+    for (label_values, value) in series {
+        match (value.as_ref(), &family.kind) {
+            (Value::Counter(counter), _) => {
+                let _ = write!(output, "{}_total", family.name);
+                write_labels(output, &family.label_names, &label_values, None);
+                let _ = writeln!(output, " {}", counter.load(Ordering::Relaxed));
+            }
+            (Value::Gauge(gauge), _) => {
+                output.push_str(&family.name);
+                write_labels(output, &family.label_names, &label_values, None);
+                let _ = writeln!(output, " {}", gauge.load(Ordering::Relaxed));
+            }
+            (Value::Histogram(histogram), FamilyKind::Histogram(buckets)) => {
+                let mut cumulative = 0;
+                for (bound, count) in buckets.iter().zip(histogram.buckets.iter()) {
+                    cumulative += count.load(Ordering::Relaxed);
+                    let _ = write!(output, "{}_bucket", family.name);
+                    write_labels(
+                        output,
+                        &family.label_names,
+                        &label_values,
+                        Some(&format_float(*bound)),
+                    );
+                    let _ = writeln!(output, " {cumulative}");
+                }
+
+                let count = histogram.count.load(Ordering::Relaxed);
+                let _ = write!(output, "{}_bucket", family.name);
+                write_labels(output, &family.label_names, &label_values, Some("+Inf"));
+                let _ = writeln!(output, " {count}");
+
+                let sum = f64::from_bits(histogram.sum.load(Ordering::Relaxed));
+                let _ = write!(output, "{}_sum", family.name);
+                write_labels(output, &family.label_names, &label_values, None);
+                let _ = writeln!(output, " {}", format_float(sum));
+
+                let _ = write!(output, "{}_count", family.name);
+                write_labels(output, &family.label_names, &label_values, None);
+                let _ = writeln!(output, " {count}");
+            }
+            (Value::Histogram(_), _) => (),
+        }
+    }
+}
+
+fn write_labels(output: &mut String, names: &[String], values: &[String], le: Option<&str>) {
+    if names.is_empty() && le.is_none() {
+        return;
+    }
+
+    output.push('{');
+    let mut first = true;
+    for (name, value) in names.iter().zip(values) {
+        if !first {
+            output.push(',');
+        }
+        first = false;
+        let _ = write!(output, "{name}=\"");
+        write_escaped(output, value, true);
+        output.push('"');
+    }
+    if let Some(le) = le {
+        if !first {
+            output.push(',');
+        }
+        let _ = write!(output, "le=\"{le}\"");
+    }
+    output.push('}');
+}
+
+fn write_escaped(output: &mut String, value: &str, escape_quotes: bool) {
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '"' if escape_quotes => output.push_str("\\\""),
+            c => output.push(c),
+        }
+    }
+}
+
+fn format_float(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else if value.is_nan() {
+        "NaN".to_owned()
+    } else {
+        format!("{value:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        let registry = Registry::new();
+        registry
+            .counter("http_requests", "Total \"requests\".", &["method"])
+            .add(&["GET"], 3);
+        registry
+            .gauge("active", "Active\nrequests.", &[])
+            .set(&[], 2);
+        let histogram = registry.histogram("latency_seconds", "Latency.", &["path"], &[0.1, 1.0]);
+        histogram.observe(&["/a\"b"], 0.05);
+        histogram.observe(&["/a\"b"], 0.5);
+        histogram.observe(&["/a\"b"], 5.0);
+        registry
+    }
+
+    #[test]
+    fn test_encode_prometheus() {
+        assert_eq!(
+            registry().encode(ExpositionFormat::Prometheus),
+            r#"# HELP http_requests_total Total "requests".
+# TYPE http_requests_total counter
+http_requests_total{method="GET"} 3
+# HELP active Active\nrequests.
+# TYPE active gauge
+active 2
+# HELP latency_seconds Latency.
+# TYPE latency_seconds histogram
+latency_seconds_bucket{path="/a\"b",le="0.1"} 1
+latency_seconds_bucket{path="/a\"b",le="1.0"} 2
+latency_seconds_bucket{path="/a\"b",le="+Inf"} 3
+latency_seconds_sum{path="/a\"b"} 5.55
+latency_seconds_count{path="/a\"b"} 3
+"#
+        );
+    }
+
+    #[test]
+    fn test_encode_openmetrics() {
+        let output = registry().encode(ExpositionFormat::OpenMetrics);
+        assert!(output.starts_with(
+            "# HELP http_requests Total \\\"requests\\\".\n# TYPE http_requests counter\nhttp_requests_total{method=\"GET\"} 3\n"
+        ));
+        assert!(output.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,468 @@
+//! Prometheus metrics, exposed without the need of an (OTLP) collector.
+//!
+//! A [`Registry`] holds metric families ([`Counter`], [`Gauge`] and [`Histogram`]),
+//! which can be encoded in the Prometheus text or OpenMetrics format
+//! using [`Registry::encode`], e.g. to be served by an http endpoint.
+//!
+//! The amount of label sets (series) kept per family is bounded by
+//! [`Registry::with_max_series`], such that user input cannot cause the
+//! registry to grow unbounded. Once this limit is reached, observations for new
+//! label sets are recorded in a single series where all labels have
+//! the value [`OVERFLOW_LABEL_VALUE`].
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::telemetry::prometheus::{ExpositionFormat, Registry};
+//!
+//! let registry = Registry::new();
+//! let requests = registry.counter("app_requests", "Amount of requests served.", &["kind"]);
+//! requests.inc(&["web"]);
+//!
+//! let output = registry.encode(ExpositionFormat::Prometheus);
+//! assert!(output.contains(r#"app_requests_total{kind="web"} 1"#));
+//! ```
+
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+};
+
+mod encode;
+#[doc(inline)]
+pub use encode::ExpositionFormat;
+
+/// The default amount of series kept per metric family,
+/// see [`Registry::with_max_series`].
+pub const DEFAULT_MAX_SERIES: usize = 1000;
+
+/// The label value used for the series in which all observations are recorded
+/// once the max amount of series of a family is reached.
+pub const OVERFLOW_LABEL_VALUE: &str = "__overflow__";
+
+/// The default [`Histogram`] buckets, in seconds,
+/// tailored to measure the latency of network services.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static GLOBAL_REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// A registry of Prometheus metric families.
+///
+/// Cloning a [`Registry`] is cheap, all clones share the same metric families.
+#[derive(Clone)]
+pub struct Registry {
+    families: Arc<Mutex<Vec<Arc<Family>>>>,
+    max_series: usize,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let families = self.families.lock();
+        f.debug_struct("Registry")
+            .field(
+                "families",
+                &families
+                    .iter()
+                    .map(|family| &family.name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("max_series", &self.max_series)
+            .finish()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    /// Create a new empty [`Registry`].
+    pub fn new() -> Self {
+        Self {
+            families: Default::default(),
+            max_series: DEFAULT_MAX_SERIES,
+        }
+    }
+
+    /// The global [`Registry`], used by the rama metric layers
+    /// when no custom [`Registry`] is provided.
+    pub fn global() -> &'static Self {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Set the maximum amount of series (unique label sets)
+    /// kept per metric family registered via this [`Registry`].
+    ///
+    /// Defaults to [`DEFAULT_MAX_SERIES`].
+    pub fn with_max_series(mut self, max_series: usize) -> Self {
+        self.max_series = max_series;
+        self
+    }
+
+    /// Set the maximum amount of series (unique label sets)
+    /// kept per metric family registered via this [`Registry`].
+    ///
+    /// Defaults to [`DEFAULT_MAX_SERIES`].
+    pub fn set_max_series(&mut self, max_series: usize) -> &mut Self {
+        self.max_series = max_series;
+        self
+    }
+
+    /// Register a [`Counter`] family, or return the existing one with the same name.
+    ///
+    /// The name should not end with `_total`, as this suffix is added when encoding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or a label name is invalid, or in case a family with the same name
+    /// but a different type or label names was registered before.
+    pub fn counter(&self, name: &str, help: &str, label_names: &[&str]) -> Counter {
+        Counter {
+            family: self.register(name, help, label_names, FamilyKind::Counter),
+        }
+    }
+
+    /// Register a [`Gauge`] family, or return the existing one with the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or a label name is invalid, or in case a family with the same name
+    /// but a different type or label names was registered before.
+    pub fn gauge(&self, name: &str, help: &str, label_names: &[&str]) -> Gauge {
+        Gauge {
+            family: self.register(name, help, label_names, FamilyKind::Gauge),
+        }
+    }
+
+    /// Register a [`Histogram`] family with the given (upper bound) buckets,
+    /// or return the existing one with the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or a label name is invalid, in case the buckets are not sorted,
+    /// or in case a family with the same name but a different type,
+    /// buckets or label names was registered before.
+    pub fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&str],
+        buckets: &[f64],
+    ) -> Histogram {
+        assert!(
+            buckets.windows(2).all(|w| w[0] < w[1]),
+            "histogram buckets of {name} have to be sorted in increasing order"
+        );
+        Histogram {
+            family: self.register(
+                name,
+                help,
+                label_names,
+                FamilyKind::Histogram(buckets.into()),
+            ),
+        }
+    }
+
+    fn register(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&str],
+        kind: FamilyKind,
+    ) -> Arc<Family> {
+        assert!(is_valid_metric_name(name), "invalid metric name: {name}");
+        for label_name in label_names {
+            assert!(
+                is_valid_label_name(label_name) && *label_name != "le",
+                "invalid label name {label_name} for metric {name}"
+            );
+        }
+
+        let mut families = self.families.lock();
+        if let Some(family) = families.iter().find(|family| family.name == name) {
+            assert!(
+                family.kind == kind && family.label_names == label_names,
+                "metric {name} already registered with a different type or labels"
+            );
+            return family.clone();
+        }
+
+        let family = Arc::new(Family {
+            name: name.to_owned(),
+            help: help.to_owned(),
+            label_names: label_names.iter().map(|name| (*name).to_owned()).collect(),
+            kind,
+            max_series: self.max_series,
+            series: Default::default(),
+        });
+        families.push(family.clone());
+        family
+    }
+}
+
+fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FamilyKind {
+    Counter,
+    Gauge,
+    Histogram(Arc<[f64]>),
+}
+
+struct Family {
+    name: String,
+    help: String,
+    label_names: Vec<String>,
+    kind: FamilyKind,
+    max_series: usize,
+    series: Mutex<BTreeMap<Vec<String>, Arc<Value>>>,
+}
+
+impl fmt::Debug for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Family")
+            .field("name", &self.name)
+            .field("label_names", &self.label_names)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl Family {
+    /// Get or create the value for the given label values.
+    fn value(&self, label_values: &[&str]) -> Option<Arc<Value>> {
+        if label_values.len() != self.label_names.len() {
+            tracing::error!(
+                metric = %self.name,
+                "prometheus: unexpected amount of label values: {} (expected {})",
+                label_values.len(),
+                self.label_names.len(),
+            );
+            return None;
+        }
+
+        let mut key: Vec<String> = label_values
+            .iter()
+            .map(|value| (*value).to_owned())
+            .collect();
+        let mut series = self.series.lock();
+        if let Some(value) = series.get(&key) {
+            return Some(value.clone());
+        }
+
+        if series.len() >= self.max_series {
+            tracing::trace!(metric = %self.name, "prometheus: max series reached: use overflow series");
+            key = vec![OVERFLOW_LABEL_VALUE.to_owned(); label_values.len()];
+        }
+        Some(
+            series
+                .entry(key)
+                .or_insert_with(|| Arc::new(Value::new(&self.kind)))
+                .clone(),
+        )
+    }
+}
+
+enum Value {
+    Counter(AtomicU64),
+    Gauge(AtomicI64),
+    Histogram(HistogramValue),
+}
+
+impl Value {
+    fn new(kind: &FamilyKind) -> Self {
+        match kind {
+            FamilyKind::Counter => Self::Counter(AtomicU64::new(0)),
+            FamilyKind::Gauge => Self::Gauge(AtomicI64::new(0)),
+            FamilyKind::Histogram(buckets) => Self::Histogram(HistogramValue {
+                buckets: buckets.iter().map(|_| AtomicU64::new(0)).collect(),
+                sum: AtomicU64::new(0f64.to_bits()),
+                count: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+struct HistogramValue {
+    /// non-cumulative bucket counts, the +Inf bucket is derived from the count
+    buckets: Box<[AtomicU64]>,
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+/// A Prometheus counter family, a value which only goes up.
+///
+/// Created using [`Registry::counter`].
+#[derive(Debug, Clone)]
+pub struct Counter {
+    family: Arc<Family>,
+}
+
+impl Counter {
+    /// Increment the counter of the series with the given label values by one.
+    pub fn inc(&self, label_values: &[&str]) {
+        self.add(label_values, 1)
+    }
+
+    /// Increment the counter of the series with the given label values.
+    pub fn add(&self, label_values: &[&str], value: u64) {
+        if let Some(Value::Counter(counter)) = self.family.value(label_values).as_deref() {
+            counter.fetch_add(value, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A Prometheus gauge family, a value which can go up and down.
+///
+/// Created using [`Registry::gauge`].
+#[derive(Debug, Clone)]
+pub struct Gauge {
+    family: Arc<Family>,
+}
+
+impl Gauge {
+    /// Increment the gauge of the series with the given label values by one.
+    pub fn inc(&self, label_values: &[&str]) {
+        self.add(label_values, 1)
+    }
+
+    /// Decrement the gauge of the series with the given label values by one.
+    pub fn dec(&self, label_values: &[&str]) {
+        self.add(label_values, -1)
+    }
+
+    /// Add the given (possibly negative) value to the gauge
+    /// of the series with the given label values.
+    pub fn add(&self, label_values: &[&str], value: i64) {
+        if let Some(Value::Gauge(gauge)) = self.family.value(label_values).as_deref() {
+            gauge.fetch_add(value, Ordering::Relaxed);
+        }
+    }
+
+    /// Set the gauge of the series with the given label values.
+    pub fn set(&self, label_values: &[&str], value: i64) {
+        if let Some(Value::Gauge(gauge)) = self.family.value(label_values).as_deref() {
+            gauge.store(value, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A Prometheus histogram family, counting observations in configurable buckets.
+///
+/// Created using [`Registry::histogram`].
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    family: Arc<Family>,
+}
+
+impl Histogram {
+    /// Record an observation in the series with the given label values.
+    pub fn observe(&self, label_values: &[&str], value: f64) {
+        let Some(value_ref) = self.family.value(label_values) else {
+            return;
+        };
+        let (Value::Histogram(histogram), FamilyKind::Histogram(buckets)) =
+            (value_ref.as_ref(), &self.family.kind)
+        else {
+            return;
+        };
+
+        if let Some(index) = buckets.iter().position(|bound| value <= *bound) {
+            histogram.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = histogram
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some((f64::from_bits(sum) + value).to_bits())
+            });
+        histogram.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_existing_family() {
+        let registry = Registry::new();
+        let a = registry.counter("requests", "help", &["method"]);
+        let b = registry.counter("requests", "help", &["method"]);
+        a.inc(&["GET"]);
+        b.add(&["GET"], 2);
+
+        let output = registry.encode(ExpositionFormat::Prometheus);
+        assert!(
+            output.contains(r#"requests_total{method="GET"} 3"#),
+            "{output}"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_register_conflicting_family() {
+        let registry = Registry::new();
+        registry.counter("requests", "help", &["method"]);
+        registry.gauge("requests", "help", &["method"]);
+    }
+
+    #[test]
+    fn test_max_series() {
+        let registry = Registry::new().with_max_series(2);
+        let counter = registry.counter("requests", "help", &["path"]);
+        for path in ["/a", "/b", "/c", "/d", "/a"] {
+            counter.inc(&[path]);
+        }
+
+        let output = registry.encode(ExpositionFormat::Prometheus);
+        assert!(
+            output.contains(r#"requests_total{path="/a"} 2"#),
+            "{output}"
+        );
+        assert!(
+            output.contains(r#"requests_total{path="/b"} 1"#),
+            "{output}"
+        );
+        assert!(
+            output.contains(r#"requests_total{path="__overflow__"} 2"#),
+            "{output}"
+        );
+        assert!(!output.contains("/c"), "{output}");
+    }
+
+    #[test]
+    fn test_wrong_label_count_is_ignored() {
+        let registry = Registry::new();
+        let gauge = registry.gauge("active", "help", &["a", "b"]);
+        gauge.inc(&["only-one"]);
+        assert!(
+            !registry
+                .encode(ExpositionFormat::Prometheus)
+                .contains("only-one")
+        );
+    }
+}
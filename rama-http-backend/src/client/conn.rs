@@ -126,7 +126,7 @@ where
                 let (sender, conn) = rama_http_core::client::conn::http1::handshake(io).await?;
 
                 ctx.spawn(async move {
-                    if let Err(err) = conn.await {
+                    if let Err(err) = conn.with_upgrades().await {
                         tracing::debug!("connection failed: {:?}", err);
                     }
                 });
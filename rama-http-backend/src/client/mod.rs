@@ -1,14 +1,22 @@
 //! Rama HTTP client module,
 //! which provides the [`HttpClient`] type to serve HTTP requests.
 
-use proxy::layer::HttpProxyConnector;
+use proxy::{
+    ProxyEnv,
+    layer::{HttpProxyConnector, insert_proxy_address},
+};
 use rama_core::{
     Context, Service,
+    combinators::Either,
     error::{BoxError, ErrorExt, OpaqueError},
 };
 use rama_http_types::{Request, Response, dep::http_body};
-use rama_net::client::{ConnectorService, EstablishedClientConnection};
-use rama_tcp::client::service::TcpConnector;
+use rama_net::{
+    address::{ProxyAddress, ProxyChain},
+    client::{ConnectorService, EgressPolicy, EstablishedClientConnection},
+};
+use rama_tcp::client::{EgressGuardConnector, service::TcpConnector};
+use std::sync::Arc;
 
 #[cfg(any(feature = "rustls", feature = "boring"))]
 use rama_tls::std::client::{TlsConnector, TlsConnectorData};
@@ -19,6 +27,13 @@ use rama_net::tls::client::ClientConfig;
 #[cfg(any(feature = "rustls", feature = "boring"))]
 use rama_core::error::ErrorContext;
 
+#[cfg(feature = "proxy")]
+use rama_core::{Layer, service::BoxService};
+#[cfg(feature = "proxy")]
+use rama_net::transport::TransportContext;
+#[cfg(feature = "proxy")]
+use rama_proxy::{ProxyFailoverConnector, ProxyFailoverLayer, ProxyHealthLayer};
+
 mod svc;
 #[doc(inline)]
 pub use svc::HttpClientService;
@@ -40,6 +55,12 @@ pub mod proxy;
 /// http client. Rama is here to empower you, the building blocks are there, go crazy
 /// with your own service fork and use the full power of Rust at your fingertips ;)
 pub struct HttpClient {
+    egress_guard: Option<EgressGuardConnector>,
+    proxy_env: Option<Arc<ProxyEnv>>,
+    #[cfg(feature = "proxy")]
+    proxy_failover: Option<ProxyFailoverLayer>,
+    #[cfg(feature = "proxy")]
+    proxy_health: Option<ProxyHealthLayer>,
     #[cfg(any(feature = "rustls", feature = "boring"))]
     tls_config: Option<ClientConfig>,
     #[cfg(any(feature = "rustls", feature = "boring"))]
@@ -52,6 +73,104 @@ impl HttpClient {
         Self::default()
     }
 
+    /// Set the [`EgressPolicy`] of this [`HttpClient`],
+    /// restricting the addresses it can connect to.
+    ///
+    /// As the policy is checked for every connection, after dns resolution,
+    /// it also applies to redirects followed by this client.
+    /// Denied connections result in an error containing an [`EgressDenied`] error.
+    ///
+    /// [`EgressDenied`]: rama_net::client::EgressDenied
+    pub fn set_egress_policy(&mut self, policy: EgressPolicy) -> &mut Self {
+        self.egress_guard = Some(EgressGuardConnector::new(policy));
+        self
+    }
+
+    /// Replace this [`HttpClient`] with the [`EgressPolicy`] set,
+    /// restricting the addresses it can connect to.
+    ///
+    /// As the policy is checked for every connection, after dns resolution,
+    /// it also applies to redirects followed by this client.
+    /// Denied connections result in an error containing an [`EgressDenied`] error.
+    ///
+    /// [`EgressDenied`]: rama_net::client::EgressDenied
+    pub fn with_egress_policy(mut self, policy: EgressPolicy) -> Self {
+        self.egress_guard = Some(EgressGuardConnector::new(policy));
+        self
+    }
+
+    /// Set the [`ProxyEnv`] of this [`HttpClient`],
+    /// used to select the proxy for requests without a [`ProxyAddress`] in their [`Context`].
+    ///
+    /// Use [`ProxyEnv::try_from_env`] to behave like curl,
+    /// respecting the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables.
+    pub fn set_proxy_env(&mut self, env: ProxyEnv) -> &mut Self {
+        self.proxy_env = Some(Arc::new(env));
+        self
+    }
+
+    /// Replace this [`HttpClient`] with the [`ProxyEnv`] set,
+    /// used to select the proxy for requests without a [`ProxyAddress`] in their [`Context`].
+    ///
+    /// Use [`ProxyEnv::try_from_env`] to behave like curl,
+    /// respecting the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables.
+    pub fn with_proxy_env(mut self, env: ProxyEnv) -> Self {
+        self.proxy_env = Some(Arc::new(env));
+        self
+    }
+
+    #[cfg(feature = "proxy")]
+    /// Set the [`ProxyFailoverLayer`] of this [`HttpClient`], used to wrap its proxy connector,
+    /// such that it fails over to the next [`ProxyCandidates`] (e.g. as selected by
+    /// the [`ProxyDBLayer`]) in case it fails to establish a connection via the selected proxy.
+    ///
+    /// A default (sequential) [`ProxyFailoverLayer`] is used if none is set,
+    /// which only has effect in case [`ProxyCandidates`] are present in the [`Context`].
+    ///
+    /// [`ProxyCandidates`]: rama_proxy::ProxyCandidates
+    /// [`ProxyDBLayer`]: rama_proxy::ProxyDBLayer
+    pub fn set_proxy_failover(&mut self, layer: ProxyFailoverLayer) -> &mut Self {
+        self.proxy_failover = Some(layer);
+        self
+    }
+
+    #[cfg(feature = "proxy")]
+    /// Replace this [`HttpClient`] with the [`ProxyFailoverLayer`] set, used to wrap its proxy connector,
+    /// such that it fails over to the next [`ProxyCandidates`] (e.g. as selected by
+    /// the [`ProxyDBLayer`]) in case it fails to establish a connection via the selected proxy.
+    ///
+    /// A default (sequential) [`ProxyFailoverLayer`] is used if none is set,
+    /// which only has effect in case [`ProxyCandidates`] are present in the [`Context`].
+    ///
+    /// [`ProxyCandidates`]: rama_proxy::ProxyCandidates
+    /// [`ProxyDBLayer`]: rama_proxy::ProxyDBLayer
+    pub fn with_proxy_failover(mut self, layer: ProxyFailoverLayer) -> Self {
+        self.proxy_failover = Some(layer);
+        self
+    }
+
+    #[cfg(feature = "proxy")]
+    /// Set the [`ProxyHealthLayer`] of this [`HttpClient`], used to wrap its proxy connector,
+    /// such that failures to connect via the selected proxy (or failover candidate)
+    /// are recorded in the [`ProxyHealthTable`], as well as the established connections.
+    ///
+    /// [`ProxyHealthTable`]: rama_proxy::ProxyHealthTable
+    pub fn set_proxy_health(&mut self, layer: ProxyHealthLayer) -> &mut Self {
+        self.proxy_health = Some(layer);
+        self
+    }
+
+    #[cfg(feature = "proxy")]
+    /// Replace this [`HttpClient`] with the [`ProxyHealthLayer`] set, used to wrap its proxy connector,
+    /// such that failures to connect via the selected proxy (or failover candidate)
+    /// are recorded in the [`ProxyHealthTable`], as well as the established connections.
+    ///
+    /// [`ProxyHealthTable`]: rama_proxy::ProxyHealthTable
+    pub fn with_proxy_health(mut self, layer: ProxyHealthLayer) -> Self {
+        self.proxy_health = Some(layer);
+        self
+    }
+
     #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Set the [`ClientConfig`] of this [`HttpClient`].
     pub fn set_tls_config(&mut self, cfg: ClientConfig) -> &mut Self {
@@ -95,6 +214,46 @@ impl HttpClient {
     }
 }
 
+impl HttpClient {
+    #[cfg(feature = "proxy")]
+    fn wrap_proxy_connector<S, State, Connection>(
+        &self,
+        connector: S,
+    ) -> ProxyFailoverConnector<
+        BoxService<
+            State,
+            TransportContext,
+            EstablishedClientConnection<Connection, State, TransportContext>,
+            BoxError,
+        >,
+    >
+    where
+        S: Service<
+                State,
+                TransportContext,
+                Response = EstablishedClientConnection<Connection, State, TransportContext>,
+                Error = BoxError,
+            >,
+        State: Clone + Send + Sync + 'static,
+    {
+        // boxed to keep the (already deep) connector stack of the client
+        // from blowing up the compile time and memory usage
+        let connector = match &self.proxy_health {
+            Some(layer) => layer.layer(connector).boxed(),
+            None => connector.boxed(),
+        };
+        self.proxy_failover
+            .clone()
+            .unwrap_or_default()
+            .layer(connector)
+    }
+
+    #[cfg(not(feature = "proxy"))]
+    fn wrap_proxy_connector<S>(&self, connector: S) -> S {
+        connector
+    }
+}
+
 impl<State, Body> Service<State, Request<Body>> for HttpClient
 where
     State: Clone + Send + Sync + 'static,
@@ -105,16 +264,26 @@ where
 
     async fn serve(
         &self,
-        ctx: Context<State>,
+        mut ctx: Context<State>,
         req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
         let uri = req.uri().clone();
 
+        if let Some(proxy_env) = &self.proxy_env {
+            // a proxy chain takes the place of a single proxy
+            if !ctx.contains::<ProxyAddress>() && !ctx.contains::<ProxyChain>() {
+                insert_proxy_address(proxy_env, &mut ctx, &req);
+            }
+        }
+
         // record original req version,
         // so we can put the response back
         let original_req_version = req.version();
 
-        let tcp_connector = TcpConnector::new();
+        let tcp_connector = TcpConnector::new().with_connector(match &self.egress_guard {
+            Some(egress_guard) => Either::A(egress_guard.clone()),
+            None => Either::B(()),
+        });
 
         #[cfg(any(feature = "rustls", feature = "boring"))]
         let connector = {
@@ -134,9 +303,12 @@ where
                 }
             };
 
-            let transport_connector = HttpProxyConnector::optional(
-                TlsConnector::tunnel(tcp_connector, None)
-                    .with_connector_data(proxy_tls_connector_data),
+            let transport_connector = self.wrap_proxy_connector(
+                HttpProxyConnector::optional(
+                    TlsConnector::tunnel(tcp_connector, None)
+                        .with_connector_data(proxy_tls_connector_data.clone()),
+                )
+                .with_proxy_tls_connector_data(proxy_tls_connector_data),
             );
             let tls_connector_data = match &self.tls_config {
                 Some(tls_config) => {
@@ -157,7 +329,9 @@ where
             )
         };
         #[cfg(not(any(feature = "rustls", feature = "boring")))]
-        let connector = HttpConnector::new(HttpProxyConnector::optional(tcp_connector));
+        let connector = HttpConnector::new(
+            self.wrap_proxy_connector(HttpProxyConnector::optional(tcp_connector)),
+        );
 
         // NOTE: stack might change request version based on connector data,
         // such as ALPN (tls), as such it is important to reset it back below,
@@ -188,3 +362,145 @@ where
         Ok(resp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::Body;
+
+    #[tokio::test]
+    async fn test_http_client_socks5_proxy_env() {
+        let env = ProxyEnv::try_from_lookup(|key| {
+            (key == "ALL_PROXY").then(|| "socks5://127.0.0.1:1".to_owned())
+        })
+        .unwrap();
+        let client = HttpClient::new().with_proxy_env(env);
+
+        let req = Request::builder()
+            .uri("http://example.com")
+            .body(Body::empty())
+            .unwrap();
+        let err = client.serve(Context::default(), req).await.unwrap_err();
+        assert!(err.to_string().contains("socks5"), "{err}");
+    }
+
+    #[cfg(feature = "proxy")]
+    fn test_proxy(id: &'static str, address: std::net::SocketAddr) -> rama_proxy::Proxy {
+        rama_proxy::Proxy {
+            id: rama_utils::str::NonEmptyString::from_static(id),
+            address: ProxyAddress::try_from(format!("http://{address}").as_str()).unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: true,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+        }
+    }
+
+    #[cfg(feature = "proxy")]
+    async fn dead_proxy_address() -> std::net::SocketAddr {
+        tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    /// Spawn a fake http proxy which answers every request with "proxied".
+    #[cfg(feature = "proxy")]
+    async fn spawn_live_proxy() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    stream
+                        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 7\r\n\r\nproxied")
+                        .await
+                        .unwrap();
+                });
+            }
+        });
+        address
+    }
+
+    #[cfg(feature = "proxy")]
+    #[tokio::test]
+    async fn test_http_client_proxy_failover() {
+        use rama_core::Layer;
+        use rama_http_types::BodyExtractExt;
+        use rama_proxy::{MemoryProxyDB, ProxyDBLayer, ProxyFilterMode};
+
+        let db = MemoryProxyDB::try_from_iter([
+            test_proxy("dead", dead_proxy_address().await),
+            test_proxy("live", spawn_live_proxy().await),
+        ])
+        .unwrap();
+        let client = ProxyDBLayer::new(Arc::new(db))
+            .filter_mode(ProxyFilterMode::Default)
+            .failover(2)
+            .layer(HttpClient::new().with_proxy_failover(ProxyFailoverLayer::new()));
+
+        // the primary proxy is selected at random,
+        // so repeat to make it (very) likely the dead one is tried first at least once
+        for _ in 0..8 {
+            let req = Request::builder()
+                .uri("http://example.com")
+                .body(Body::empty())
+                .unwrap();
+            let resp = client.serve(Context::default(), req).await.unwrap();
+            assert_eq!(resp.into_body().try_into_string().await.unwrap(), "proxied");
+        }
+    }
+
+    #[cfg(feature = "proxy")]
+    #[tokio::test]
+    async fn test_http_client_proxy_health() {
+        use rama_proxy::{ProxyHealthTable, ProxyID};
+
+        let table = ProxyHealthTable::new();
+        let client = HttpClient::new().with_proxy_health(ProxyHealthLayer::new(table.clone()));
+
+        let dead = test_proxy("dead", dead_proxy_address().await);
+        let live = test_proxy("live", spawn_live_proxy().await);
+        for proxy in [&dead, &live, &live] {
+            let mut ctx = Context::default();
+            ctx.insert(proxy.address.clone());
+            ctx.insert(ProxyID::from(proxy.id.clone()));
+            ctx.insert(proxy.clone());
+            let req = Request::builder()
+                .uri("http://example.com")
+                .body(Body::empty())
+                .unwrap();
+            let _ = client.serve(ctx, req).await;
+        }
+
+        let dead = table.health(&ProxyID::from(dead.id)).unwrap();
+        assert_eq!((dead.successes, dead.failures), (0, 1));
+        let live = table.health(&ProxyID::from(live.id)).unwrap();
+        assert_eq!((live.successes, live.failures), (2, 0));
+    }
+}
@@ -0,0 +1,467 @@
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::{
+    Protocol,
+    address::{Authority, Domain, Host, ProxyAddress},
+    stream::dep::ipnet::IpNet,
+};
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Default)]
+/// Proxy configuration as defined by the (curl-compatible) proxy environment variables.
+///
+/// The proxy used for a request is selected based on the protocol of that request:
+///
+/// - `https_proxy` / `HTTPS_PROXY` for secure (`https` and `wss`) requests;
+/// - `http_proxy` / `HTTP_PROXY` for all other requests;
+/// - `all_proxy` / `ALL_PROXY` as the fallback in case no protocol specific proxy is defined.
+///
+/// No proxy is used for requests matching the `no_proxy` / `NO_PROXY` variable,
+/// see [`NoProxy`] for its syntax.
+///
+/// The lowercase variants take precedence over the uppercase ones.
+/// Same as most other implementations `HTTP_PROXY` is ignored when
+/// `REQUEST_METHOD` is defined, as the environment of a CGI program
+/// can be controlled using the `Proxy` request header ("httpoxy").
+///
+/// Proxy values without a scheme are treated as http proxies.
+/// `socks5://` and `socks5h://` proxies are selected like any other proxy,
+/// but the [`HttpProxyConnector`] (and thus the `HttpClient`) cannot connect via them
+/// until `rama-socks5` provides a socks5 client. Requests for which such a proxy
+/// is selected therefore fail, rather than being sent without the proxy.
+///
+/// [`HttpProxyConnector`]: crate::client::proxy::layer::HttpProxyConnector
+pub struct ProxyEnv {
+    http: Option<ProxyAddress>,
+    https: Option<ProxyAddress>,
+    all: Option<ProxyAddress>,
+    no_proxy: NoProxy,
+}
+
+impl ProxyEnv {
+    /// Create a new empty [`ProxyEnv`], which does not proxy any request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try to create a [`ProxyEnv`] from the environment variables of the current process.
+    pub fn try_from_env() -> Result<Self, OpaqueError> {
+        Self::try_from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Try to create a [`ProxyEnv`] using the given function to
+    /// look up the value of an environment variable.
+    pub fn try_from_lookup<F>(lookup: F) -> Result<Self, OpaqueError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let get = |key: &str| {
+            lookup(key).and_then(|value| {
+                let value = value.trim();
+                (!value.is_empty()).then(|| value.to_owned())
+            })
+        };
+        let parse = |key: &str| {
+            get(key)
+                .map(|value| {
+                    value
+                        .parse::<ProxyAddress>()
+                        .with_context(|| format!("parse proxy address from env var {key}"))
+                })
+                .transpose()
+        };
+
+        let http = match parse("http_proxy")? {
+            Some(address) => Some(address),
+            None if get("REQUEST_METHOD").is_some() => {
+                tracing::debug!("ignore HTTP_PROXY env var as REQUEST_METHOD is defined (CGI)");
+                None
+            }
+            None => parse("HTTP_PROXY")?,
+        };
+        let https = match parse("https_proxy")? {
+            Some(address) => Some(address),
+            None => parse("HTTPS_PROXY")?,
+        };
+        let all = match parse("all_proxy")? {
+            Some(address) => Some(address),
+            None => parse("ALL_PROXY")?,
+        };
+        let no_proxy = NoProxy::from_lookup(&lookup);
+
+        Ok(Self {
+            http,
+            https,
+            all,
+            no_proxy,
+        })
+    }
+
+    /// Set the proxy to be used for plain text (http and ws) requests.
+    pub fn with_http_proxy(mut self, address: ProxyAddress) -> Self {
+        self.http = Some(address);
+        self
+    }
+
+    /// Set the proxy to be used for plain text (http and ws) requests.
+    pub fn set_http_proxy(&mut self, address: ProxyAddress) -> &mut Self {
+        self.http = Some(address);
+        self
+    }
+
+    /// Set the proxy to be used for secure (https and wss) requests.
+    pub fn with_https_proxy(mut self, address: ProxyAddress) -> Self {
+        self.https = Some(address);
+        self
+    }
+
+    /// Set the proxy to be used for secure (https and wss) requests.
+    pub fn set_https_proxy(&mut self, address: ProxyAddress) -> &mut Self {
+        self.https = Some(address);
+        self
+    }
+
+    /// Set the proxy to be used for requests without a protocol specific proxy.
+    pub fn with_all_proxy(mut self, address: ProxyAddress) -> Self {
+        self.all = Some(address);
+        self
+    }
+
+    /// Set the proxy to be used for requests without a protocol specific proxy.
+    pub fn set_all_proxy(&mut self, address: ProxyAddress) -> &mut Self {
+        self.all = Some(address);
+        self
+    }
+
+    /// Set the [`NoProxy`] rules, defining the requests not to be proxied.
+    pub fn with_no_proxy(mut self, no_proxy: NoProxy) -> Self {
+        self.no_proxy = no_proxy;
+        self
+    }
+
+    /// Set the [`NoProxy`] rules, defining the requests not to be proxied.
+    pub fn set_no_proxy(&mut self, no_proxy: NoProxy) -> &mut Self {
+        self.no_proxy = no_proxy;
+        self
+    }
+
+    /// Get the [`NoProxy`] rules of this [`ProxyEnv`].
+    pub fn no_proxy(&self) -> &NoProxy {
+        &self.no_proxy
+    }
+
+    /// Returns `true` if no proxy is defined at all.
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none() && self.all.is_none()
+    }
+
+    /// Select the [`ProxyAddress`] to be used for a request
+    /// with the given [`Protocol`] to the given [`Authority`], if any.
+    pub fn proxy_for(&self, protocol: &Protocol, authority: &Authority) -> Option<&ProxyAddress> {
+        if self.is_empty() || self.no_proxy.matches(authority) {
+            return None;
+        }
+        let proxy = if protocol.is_secure() {
+            self.https.as_ref()
+        } else {
+            self.http.as_ref()
+        };
+        proxy.or(self.all.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Rules defining which requests are not to be proxied,
+/// as defined by the (curl-compatible) `NO_PROXY` environment variable.
+///
+/// It is a comma (or whitespace) separated list of entries, where each entry is one of:
+///
+/// - `*`: matches all hosts, disabling the proxy altogether;
+/// - a domain (e.g. `example.com`, `.example.com` or `*.example.com`),
+///   matching that domain and all its subdomains;
+/// - an IP address (e.g. `127.0.0.1` or `::1`), matching that address;
+/// - a CIDR (e.g. `10.0.0.0/8` or `fd00::/8`), matching all addresses in that network.
+///
+/// Domains and IP addresses can be suffixed with a port (e.g. `example.com:8080`
+/// or `[::1]:8080`) in which case only requests to that port are matched.
+///
+/// Hosts are matched as is, no DNS resolution is performed.
+/// Invalid entries are ignored.
+pub struct NoProxy {
+    all: bool,
+    rules: Vec<NoProxyRule>,
+}
+
+#[derive(Debug, Clone)]
+struct NoProxyRule {
+    target: NoProxyTarget,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+enum NoProxyTarget {
+    Domain(Domain),
+    Net(IpNet),
+}
+
+impl NoProxy {
+    /// Create a new [`NoProxy`] from the given `NO_PROXY` value.
+    pub fn new(value: &str) -> Self {
+        let mut no_proxy = Self::default();
+        for entry in value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+        {
+            if entry == "*" {
+                no_proxy.all = true;
+                continue;
+            }
+            match parse_rule(entry) {
+                Some(rule) => no_proxy.rules.push(rule),
+                None => tracing::debug!(%entry, "ignore invalid NO_PROXY entry"),
+            }
+        }
+        no_proxy
+    }
+
+    /// Create a new [`NoProxy`] from the `no_proxy` (or `NO_PROXY`)
+    /// environment variable of the current process.
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    fn from_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match lookup("no_proxy").filter(|value| !value.trim().is_empty()) {
+            Some(value) => Self::new(&value),
+            None => lookup("NO_PROXY")
+                .map(|value| Self::new(&value))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns `true` if no rules are defined, meaning all requests can be proxied.
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.rules.is_empty()
+    }
+
+    /// Returns `true` if a request to the given [`Authority`] is not to be proxied.
+    pub fn matches(&self, authority: &Authority) -> bool {
+        if self.all {
+            return true;
+        }
+        self.rules.iter().any(|rule| {
+            if rule.port.is_some_and(|port| port != authority.port()) {
+                return false;
+            }
+            match (&rule.target, authority.host()) {
+                (NoProxyTarget::Domain(domain), Host::Name(host)) => host.is_sub_of(domain),
+                (NoProxyTarget::Net(net), Host::Address(ip)) => net.contains(&ip.to_canonical()),
+                _ => false,
+            }
+        })
+    }
+}
+
+fn parse_rule(entry: &str) -> Option<NoProxyRule> {
+    if let Ok(net) = entry.parse::<IpNet>() {
+        return Some(NoProxyRule {
+            target: NoProxyTarget::Net(net),
+            port: None,
+        });
+    }
+
+    let (host, port) = match entry.strip_prefix('[') {
+        // bracketed ipv6 address, optionally with a port
+        Some(entry) => {
+            let (host, rest) = entry.split_once(']')?;
+            let port = match rest {
+                "" => None,
+                rest => Some(rest.strip_prefix(':')?.parse().ok()?),
+            };
+            (host, port)
+        }
+        None => match entry.rsplit_once(':') {
+            // a single colon can only be a port separator
+            Some((host, port)) if !host.contains(':') => (host, Some(port.parse().ok()?)),
+            _ => (entry, None),
+        },
+    };
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Some(NoProxyRule {
+            target: NoProxyTarget::Net(ip.to_canonical().into()),
+            port,
+        });
+    }
+
+    let host = host.trim_start_matches("*.").trim_start_matches('.');
+    let domain = host.parse::<Domain>().ok()?;
+    Some(NoProxyRule {
+        target: NoProxyTarget::Domain(domain),
+        port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> ProxyEnv {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ProxyEnv::try_from_lookup(|key| vars.get(key).cloned()).unwrap()
+    }
+
+    fn proxy_for(env: &ProxyEnv, protocol: Protocol, authority: &str) -> Option<String> {
+        env.proxy_for(&protocol, &authority.parse().unwrap())
+            .map(|address| address.authority.to_string())
+    }
+
+    #[test]
+    fn test_proxy_env_scheme_selection() {
+        let env = env(&[
+            ("HTTP_PROXY", "http-proxy:3128"),
+            ("HTTPS_PROXY", "https://https-proxy:8443"),
+            ("ALL_PROXY", "all-proxy:3128"),
+        ]);
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTP, "example.com:80").as_deref(),
+            Some("http-proxy:3128")
+        );
+        assert_eq!(
+            proxy_for(&env, Protocol::WS, "example.com:80").as_deref(),
+            Some("http-proxy:3128")
+        );
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTPS, "example.com:443").as_deref(),
+            Some("https-proxy:8443")
+        );
+        assert_eq!(
+            proxy_for(&env, Protocol::WSS, "example.com:443").as_deref(),
+            Some("https-proxy:8443")
+        );
+
+        let env = super::tests::env(&[("all_proxy", "https://all-proxy")]);
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTPS, "example.com:443").as_deref(),
+            Some("all-proxy:443")
+        );
+        let address = env
+            .proxy_for(&Protocol::HTTP, &"example.com:80".parse().unwrap())
+            .unwrap();
+        assert_eq!(address.protocol, Some(Protocol::HTTPS));
+
+        assert!(super::tests::env(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_proxy_env_lowercase_precedence() {
+        let env = env(&[
+            ("HTTP_PROXY", "upper:3128"),
+            ("http_proxy", "lower:3128"),
+            ("HTTPS_PROXY", "upper:3129"),
+            ("https_proxy", " "),
+        ]);
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTP, "example.com:80").as_deref(),
+            Some("lower:3128")
+        );
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTPS, "example.com:443").as_deref(),
+            Some("upper:3129")
+        );
+    }
+
+    #[test]
+    fn test_proxy_env_cgi() {
+        let env = env(&[("HTTP_PROXY", "attacker:3128"), ("REQUEST_METHOD", "GET")]);
+        assert_eq!(proxy_for(&env, Protocol::HTTP, "example.com:80"), None);
+
+        let env = super::tests::env(&[("http_proxy", "proxy:3128"), ("REQUEST_METHOD", "GET")]);
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTP, "example.com:80").as_deref(),
+            Some("proxy:3128")
+        );
+    }
+
+    #[test]
+    fn test_proxy_env_invalid() {
+        let err = ProxyEnv::try_from_lookup(|key| {
+            (key == "HTTPS_PROXY").then(|| "http://user@proxy:foo".to_owned())
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("HTTPS_PROXY"), "{err}");
+    }
+
+    #[test]
+    fn test_proxy_env_socks5() {
+        let env = env(&[
+            ("ALL_PROXY", "socks5://all-proxy"),
+            ("HTTPS_PROXY", "https-proxy:3128"),
+        ]);
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTPS, "example.com:443").as_deref(),
+            Some("https-proxy:3128")
+        );
+        assert_eq!(
+            proxy_for(&env, Protocol::HTTP, "example.com:80").as_deref(),
+            Some("all-proxy:1080")
+        );
+
+        let env = super::tests::env(&[("all_proxy", "socks5h://all-proxy")]);
+        let address = env
+            .proxy_for(&Protocol::HTTP, &"example.com:80".parse().unwrap())
+            .unwrap();
+        assert_eq!(address.protocol, Some(Protocol::SOCKS5H));
+    }
+
+    #[test]
+    fn test_proxy_env_no_proxy() {
+        let env = env(&[
+            ("ALL_PROXY", "proxy:3128"),
+            (
+                "NO_PROXY",
+                "localhost, .internal.example,*.corp.example  example.org:8080,\
+                 127.0.0.1,10.0.0.0/8,::1,[fd00::1]:8443,fe80::/10,not a valid[entry",
+            ),
+        ]);
+
+        for (authority, proxied) in [
+            ("example.com:443", true),
+            ("localhost:80", false),
+            ("api.localhost:80", false),
+            ("internal.example:443", false),
+            ("foo.internal.example:443", false),
+            ("fooInternal.example:443", true),
+            ("corp.example:443", false),
+            ("a.b.corp.example:443", false),
+            ("example.org:8080", false),
+            ("www.example.org:8080", false),
+            ("example.org:443", true),
+            ("127.0.0.1:80", false),
+            ("127.0.0.2:80", true),
+            ("10.20.30.40:443", false),
+            ("11.0.0.1:443", true),
+            ("[::1]:80", false),
+            ("[::ffff:10.0.0.1]:80", false),
+            ("[fd00::1]:8443", false),
+            ("[fd00::1]:443", true),
+            ("[fe80::1234]:443", false),
+            ("entry:443", true),
+        ] {
+            assert_eq!(
+                proxy_for(&env, Protocol::HTTPS, authority).is_some(),
+                proxied,
+                "{authority}"
+            );
+        }
+
+        let env = super::tests::env(&[("https_proxy", "proxy:3128"), ("no_proxy", "*")]);
+        assert_eq!(proxy_for(&env, Protocol::HTTPS, "example.com:443"), None);
+    }
+}
@@ -3,9 +3,15 @@
 mod proxy_address;
 pub use proxy_address::{HttpProxyAddressLayer, HttpProxyAddressService};
 
+mod proxy_env;
+pub(crate) use proxy_env::insert_proxy_address;
+pub use proxy_env::{HttpProxyEnvLayer, HttpProxyEnvService};
+
 mod proxy_auth_header;
 pub use proxy_auth_header::{SetProxyAuthHttpHeaderLayer, SetProxyAuthHttpHeaderService};
 
 mod proxy_connector;
 #[doc(inline)]
-pub use proxy_connector::{HttpProxyConnector, HttpProxyConnectorLayer, HttpProxyError};
+pub use proxy_connector::{
+    HttpProxyConnector, HttpProxyConnectorLayer, HttpProxyError, ProxyChainError,
+};
@@ -5,11 +5,15 @@
 use rama_core::error::{ErrorContext, OpaqueError};
 use rama_http_core::{client::conn::http1, upgrade};
 use rama_http_types::{
-    Body, HeaderName, HeaderValue, Method, Request, StatusCode, Version,
-    header::{HOST, USER_AGENT},
-    headers::{Header, HeaderMapExt},
+    Body, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Version,
+    header::{HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, USER_AGENT},
+    headers::{Header, HeaderMapExt, ProxyAuthorization},
+};
+use rama_net::{
+    address::Authority,
+    stream::Stream,
+    user::{Basic, Digest, DigestChallenge, ProxyCredential},
 };
-use rama_net::{address::Authority, stream::Stream};
 
 use super::HttpProxyError;
 
@@ -19,6 +23,7 @@ use super::HttpProxyError;
 /// Used to connect as a client to a HTTP proxy server.
 pub(super) struct InnerHttpProxyConnector {
     req: Request,
+    challenge_credential: Option<Basic>,
 }
 
 impl InnerHttpProxyConnector {
@@ -43,7 +48,10 @@ impl InnerHttpProxyConnector {
             .body(Body::empty())
             .context("build http request")?;
 
-        Ok(Self { req })
+        Ok(Self {
+            req,
+            challenge_credential: None,
+        })
     }
 
     #[expect(unused)]
@@ -59,12 +67,33 @@ impl InnerHttpProxyConnector {
         self
     }
 
+    /// Authenticate with the proxy server using the given credential.
+    ///
+    /// In case digest auth is enabled [`Basic`] credentials are not sent preemptively,
+    /// but only used to answer the challenge of the proxy server, retrying the handshake
+    /// once on the same connection. A digest challenge is preferred, such that the
+    /// password is only sent in cleartext in case the proxy server only accepts basic auth.
+    pub(super) fn with_proxy_credential(
+        &mut self,
+        credential: ProxyCredential,
+        digest_auth: bool,
+    ) -> &mut Self {
+        match credential {
+            ProxyCredential::Basic(basic) if digest_auth => {
+                self.challenge_credential = Some(basic);
+                self
+            }
+            ProxyCredential::Basic(basic) => self.with_typed_header(ProxyAuthorization(basic)),
+            ProxyCredential::Bearer(bearer) => self.with_typed_header(ProxyAuthorization(bearer)),
+        }
+    }
+
     /// Connect to the proxy server.
     pub(super) async fn handshake<S: Stream + Unpin>(
         self,
         stream: S,
     ) -> Result<upgrade::Upgraded, HttpProxyError> {
-        let (tx, conn) = http1::Builder::default()
+        let (mut tx, conn) = http1::Builder::default()
             .ignore_invalid_headers(true)
             .handshake(stream)
             .await
@@ -76,11 +105,35 @@ impl InnerHttpProxyConnector {
             }
         });
 
-        let response = tx
+        let retry_req = self.challenge_credential.as_ref().map(|_| {
+            let mut req = Request::new(Body::empty());
+            *req.method_mut() = self.req.method().clone();
+            *req.uri_mut() = self.req.uri().clone();
+            *req.version_mut() = self.req.version();
+            *req.headers_mut() = self.req.headers().clone();
+            req
+        });
+
+        let mut response = tx
             .send_request(self.req)
             .await
             .map_err(|err| HttpProxyError::Transport(OpaqueError::from_std(err).into_boxed()))?;
 
+        if response.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+            if let (Some(credential), Some(mut req)) = (self.challenge_credential, retry_req) {
+                if let Some(value) = challenge_proxy_authorization(&response, &req, &credential) {
+                    tracing::trace!("retry http proxy handshake answering its auth challenge");
+                    req.headers_mut().insert(PROXY_AUTHORIZATION, value);
+                    tx.ready().await.map_err(|err| {
+                        HttpProxyError::Transport(OpaqueError::from_std(err).into_boxed())
+                    })?;
+                    response = tx.send_request(req).await.map_err(|err| {
+                        HttpProxyError::Transport(OpaqueError::from_std(err).into_boxed())
+                    })?;
+                }
+            }
+        }
+
         match response.status() {
             StatusCode::OK => upgrade::on(response)
                 .await
@@ -93,3 +146,146 @@ impl InnerHttpProxyConnector {
         }
     }
 }
+
+/// Compute the credentials for the request, answering the challenge found in the proxy response:
+/// a digest challenge is preferred, basic credentials are only used when explicitly asked for.
+fn challenge_proxy_authorization<B>(
+    response: &Response<B>,
+    req: &Request,
+    credential: &Basic,
+) -> Option<HeaderValue> {
+    let challenges: Vec<_> = response
+        .headers()
+        .get_all(PROXY_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    let Some(challenge) = challenges
+        .iter()
+        .find_map(|value| DigestChallenge::try_from_header_str(value).ok())
+    else {
+        return challenges.into_iter().any(is_basic_challenge).then(|| {
+            let mut value = credential.as_header_value();
+            value.set_sensitive(true);
+            value
+        });
+    };
+    match Digest::respond(
+        &challenge,
+        credential.username(),
+        credential.password(),
+        req.method().as_str(),
+        &req.uri().to_string(),
+        1,
+    ) {
+        Ok(digest) => {
+            let mut value = digest.as_header_value();
+            value.set_sensitive(true);
+            Some(value)
+        }
+        Err(err) => {
+            tracing::debug!(?err, "failed to answer digest challenge of http proxy");
+            None
+        }
+    }
+}
+
+/// Returns `true` if the given `Proxy-Authenticate` value contains a basic challenge.
+fn is_basic_challenge(value: &str) -> bool {
+    value.split(',').any(|challenge| {
+        challenge
+            .split_ascii_whitespace()
+            .next()
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("basic"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    async fn read_request_head(stream: &mut DuplexStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    async fn handshake(
+        digest_auth: bool,
+        challenges: &'static str,
+    ) -> (Result<upgrade::Upgraded, HttpProxyError>, Vec<String>) {
+        let (client, mut server) = tokio::io::duplex(4096);
+
+        let server = tokio::spawn(async move {
+            let mut heads = vec![read_request_head(&mut server).await];
+            server
+                .write_all(
+                    format!(
+                        "HTTP/1.1 407 Proxy Authentication Required\r\n{challenges}content-length: 0\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            heads.push(read_request_head(&mut server).await);
+            server.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+            heads
+        });
+
+        let mut connector =
+            InnerHttpProxyConnector::new(Authority::try_from("example.com:443").unwrap()).unwrap();
+        connector.with_proxy_credential(Basic::new("john", "secret").into(), digest_auth);
+        let result = connector.handshake(client).await;
+        let heads = if result.is_ok() {
+            server.await.unwrap()
+        } else {
+            server.abort();
+            Vec::new()
+        };
+        (result, heads)
+    }
+
+    #[tokio::test]
+    async fn test_digest_auth_never_sends_basic_preemptively() {
+        let (result, heads) = handshake(
+            true,
+            "proxy-authenticate: Basic realm=\"proxy\"\r\n\
+            proxy-authenticate: Digest realm=\"proxy\", nonce=\"abc\", qop=\"auth\"\r\n",
+        )
+        .await;
+        result.unwrap();
+        assert!(!heads[0].contains("proxy-authorization"), "{}", heads[0]);
+        assert!(
+            heads[1].contains("proxy-authorization: Digest username=\"john\""),
+            "{}",
+            heads[1]
+        );
+        assert!(!heads[1].contains("Basic"), "{}", heads[1]);
+    }
+
+    #[tokio::test]
+    async fn test_digest_auth_answers_basic_challenge() {
+        let (result, heads) =
+            handshake(true, "proxy-authenticate: Basic realm=\"proxy\"\r\n").await;
+        result.unwrap();
+        assert!(!heads[0].contains("proxy-authorization"), "{}", heads[0]);
+        assert!(
+            heads[1].contains("proxy-authorization: Basic am9objpzZWNyZXQ=\r\n"),
+            "{}",
+            heads[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preemptive_basic_auth_is_not_retried() {
+        let (result, _) = handshake(
+            false,
+            "proxy-authenticate: Digest realm=\"proxy\", nonce=\"abc\", qop=\"auth\"\r\n",
+        )
+        .await;
+        assert!(matches!(result, Err(HttpProxyError::AuthRequired)));
+    }
+}
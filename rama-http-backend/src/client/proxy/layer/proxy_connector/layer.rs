@@ -1,15 +1,30 @@
 use super::HttpProxyConnector;
 use rama_core::Layer;
 
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::std::client::TlsConnectorData;
+
 #[derive(Debug, Clone, Default)]
 /// A [`Layer`] which wraps the given service with a [`HttpProxyConnector`].
 ///
 /// See [`HttpProxyConnector`] for more information.
 pub struct HttpProxyConnectorLayer {
     required: bool,
+    digest_auth: bool,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    proxy_tls_connector_data: Option<TlsConnectorData>,
 }
 
 impl HttpProxyConnectorLayer {
+    fn new(required: bool) -> Self {
+        Self {
+            required,
+            digest_auth: false,
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            proxy_tls_connector_data: None,
+        }
+    }
+
     /// Create a new [`HttpProxyConnectorLayer`] which creates a [`HttpProxyConnector`]
     /// which will only connect via an http proxy in case the [`ProxyAddress`] is available
     /// in the [`Context`].
@@ -17,7 +32,7 @@ impl HttpProxyConnectorLayer {
     /// [`Context`]: rama_core::Context
     /// [`ProxyAddress`]: rama_net::address::ProxyAddress
     pub fn optional() -> Self {
-        Self { required: false }
+        Self::new(false)
     }
 
     /// Create a new [`HttpProxyConnectorLayer`] which creates a [`HttpProxyConnector`]
@@ -27,7 +42,49 @@ impl HttpProxyConnectorLayer {
     /// [`Context`]: rama_core::Context
     /// [`ProxyAddress`]: rama_net::address::ProxyAddress
     pub fn required() -> Self {
-        Self { required: true }
+        Self::new(true)
+    }
+
+    /// Set whether the created [`HttpProxyConnector`] only uses [`Basic`] proxy credentials
+    /// to answer the (digest) challenge of the proxy, rather than sending them preemptively.
+    ///
+    /// See [`HttpProxyConnector::with_digest_auth`] for more information.
+    ///
+    /// [`Basic`]: rama_net::user::Basic
+    pub const fn with_digest_auth(mut self, digest_auth: bool) -> Self {
+        self.digest_auth = digest_auth;
+        self
+    }
+
+    /// Set whether the created [`HttpProxyConnector`] only uses [`Basic`] proxy credentials
+    /// to answer the (digest) challenge of the proxy, rather than sending them preemptively.
+    ///
+    /// See [`HttpProxyConnector::with_digest_auth`] for more information.
+    ///
+    /// [`Basic`]: rama_net::user::Basic
+    pub fn set_digest_auth(&mut self, digest_auth: bool) -> &mut Self {
+        self.digest_auth = digest_auth;
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to the created [`HttpProxyConnector`],
+    /// to be used to secure the connection to the `https` hops of a [`ProxyChain`].
+    ///
+    /// [`ProxyChain`]: rama_net::address::ProxyChain
+    pub fn with_proxy_tls_connector_data(mut self, connector_data: TlsConnectorData) -> Self {
+        self.proxy_tls_connector_data = Some(connector_data);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to the created [`HttpProxyConnector`],
+    /// to be used to secure the connection to the `https` hops of a [`ProxyChain`].
+    ///
+    /// [`ProxyChain`]: rama_net::address::ProxyChain
+    pub fn set_proxy_tls_connector_data(&mut self, connector_data: TlsConnectorData) -> &mut Self {
+        self.proxy_tls_connector_data = Some(connector_data);
+        self
     }
 }
 
@@ -35,6 +92,11 @@ impl<S> Layer<S> for HttpProxyConnectorLayer {
     type Service = HttpProxyConnector<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        HttpProxyConnector::new(inner, self.required)
+        let connector =
+            HttpProxyConnector::new(inner, self.required).with_digest_auth(self.digest_auth);
+        #[cfg(any(feature = "rustls", feature = "boring"))]
+        let connector =
+            connector.maybe_with_proxy_tls_connector_data(self.proxy_tls_connector_data.clone());
+        connector
     }
 }
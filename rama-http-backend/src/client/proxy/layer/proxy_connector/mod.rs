@@ -13,3 +13,7 @@ pub use layer::HttpProxyConnectorLayer;
 mod service;
 #[doc(inline)]
 pub use service::HttpProxyConnector;
+
+mod chain;
+#[doc(inline)]
+pub use chain::ProxyChainError;
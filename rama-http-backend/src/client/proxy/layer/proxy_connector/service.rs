@@ -1,19 +1,17 @@
 use crate::client::proxy::layer::HttpProxyError;
 
-use super::InnerHttpProxyConnector;
+use super::{InnerHttpProxyConnector, ProxyChainError, chain::ProxyChainTunneler};
 use rama_core::{
     Context, Service,
     combinators::Either,
     error::{BoxError, ErrorExt, OpaqueError},
 };
 use rama_http_core::upgrade;
-use rama_http_types::headers::ProxyAuthorization;
 use rama_net::{
-    address::ProxyAddress,
+    address::{ProxyAddress, ProxyChain},
     client::{ConnectorService, EstablishedClientConnection},
     stream::Stream,
     transport::TryRefIntoTransportContext,
-    user::ProxyCredential,
 };
 use rama_utils::macros::define_inner_service_accessors;
 use std::fmt;
@@ -21,13 +19,33 @@ use std::fmt;
 #[cfg(feature = "tls")]
 use rama_net::tls::TlsTunnel;
 
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::std::client::TlsConnectorData;
+
 /// A connector which can be used to establish a connection over an HTTP Proxy.
 ///
 /// This behaviour is optional and only triggered in case there
 /// is a [`ProxyAddress`] found in the [`Context`].
+///
+/// In case a [`ProxyChain`] is found in the [`Context`] it takes the place
+/// of the [`ProxyAddress`]: the inner connector is used to connect to the first hop,
+/// after which the connection is tunneled hop by hop (using http `CONNECT` requests)
+/// through all hops to the target. Each hop is authenticated using its own credential,
+/// and the connection to an `https` hop (other than the first one, which is up to the inner connector)
+/// is secured using the proxy `TlsConnectorData` of this connector, if defined.
+/// Errors contain a [`ProxyChainError`] reporting which hop failed.
+///
+/// [`Basic`] proxy credentials are sent preemptively by default. Enable digest auth
+/// using [`HttpProxyConnector::with_digest_auth`] in order to only use them to answer the
+/// challenge of the proxy instead, such that the password is never sent in cleartext
+/// unless the proxy only asks for basic auth.
+///
+/// [`Basic`]: rama_net::user::Basic
 pub struct HttpProxyConnector<S> {
     inner: S,
     required: bool,
+    digest_auth: bool,
+    chain: ProxyChainTunneler,
 }
 
 impl<S: fmt::Debug> fmt::Debug for HttpProxyConnector<S> {
@@ -35,6 +53,8 @@ impl<S: fmt::Debug> fmt::Debug for HttpProxyConnector<S> {
         f.debug_struct("HttpProxyConnector")
             .field("inner", &self.inner)
             .field("required", &self.required)
+            .field("digest_auth", &self.digest_auth)
+            .field("chain", &self.chain)
             .finish()
     }
 }
@@ -44,6 +64,8 @@ impl<S: Clone> Clone for HttpProxyConnector<S> {
         Self {
             inner: self.inner.clone(),
             required: self.required,
+            digest_auth: self.digest_auth,
+            chain: self.chain.clone(),
         }
     }
 }
@@ -51,7 +73,12 @@ impl<S: Clone> Clone for HttpProxyConnector<S> {
 impl<S> HttpProxyConnector<S> {
     /// Creates a new [`HttpProxyConnector`].
     pub(super) fn new(inner: S, required: bool) -> Self {
-        Self { inner, required }
+        Self {
+            inner,
+            required,
+            digest_auth: false,
+            chain: ProxyChainTunneler::default(),
+        }
     }
 
     /// Create a new [`HttpProxyConnector`]
@@ -68,6 +95,57 @@ impl<S> HttpProxyConnector<S> {
         Self::new(inner, true)
     }
 
+    /// Set whether [`Basic`] proxy credentials are only used to answer
+    /// the (digest) challenge of the proxy, rather than sent preemptively.
+    ///
+    /// A digest challenge is always preferred, basic credentials are only
+    /// sent in case the proxy does not offer digest auth.
+    ///
+    /// [`Basic`]: rama_net::user::Basic
+    pub const fn with_digest_auth(mut self, digest_auth: bool) -> Self {
+        self.digest_auth = digest_auth;
+        self
+    }
+
+    /// Set whether [`Basic`] proxy credentials are only used to answer
+    /// the (digest) challenge of the proxy, rather than sent preemptively.
+    ///
+    /// A digest challenge is always preferred, basic credentials are only
+    /// sent in case the proxy does not offer digest auth.
+    ///
+    /// [`Basic`]: rama_net::user::Basic
+    pub fn set_digest_auth(&mut self, digest_auth: bool) -> &mut Self {
+        self.digest_auth = digest_auth;
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// to be used to secure the connection to the `https` hops of a [`ProxyChain`].
+    pub fn with_proxy_tls_connector_data(mut self, connector_data: TlsConnectorData) -> Self {
+        self.chain.tls_connector_data = Some(connector_data);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Maybe attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// to be used to secure the connection to the `https` hops of a [`ProxyChain`].
+    pub fn maybe_with_proxy_tls_connector_data(
+        mut self,
+        connector_data: Option<TlsConnectorData>,
+    ) -> Self {
+        self.chain.tls_connector_data = connector_data;
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// to be used to secure the connection to the `https` hops of a [`ProxyChain`].
+    pub fn set_proxy_tls_connector_data(&mut self, connector_data: TlsConnectorData) -> &mut Self {
+        self.chain.tls_connector_data = Some(connector_data);
+        self
+    }
+
     define_inner_service_accessors!();
 }
 
@@ -88,7 +166,11 @@ where
         mut ctx: Context<State>,
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
-        let address = ctx.get::<ProxyAddress>().cloned();
+        let chain = ctx.get::<ProxyChain>().cloned();
+        let address = match &chain {
+            Some(chain) => Some(chain.first().clone()),
+            None => ctx.get::<ProxyAddress>().cloned(),
+        };
 
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
@@ -100,6 +182,19 @@ where
 
         // in case the provider gave us a proxy info, we insert it into the context
         if let Some(address) = &address {
+            if address
+                .protocol
+                .as_ref()
+                .is_some_and(|p| p.is_socks5() || p.is_socks5h())
+            {
+                // TODO: connect via socks5 proxies once rama-socks5 provides a client
+                return Err(OpaqueError::from_display(format!(
+                    "http proxy connector: unsupported proxy protocol for proxy {}: {:?} (socks5 proxies are not yet supported)",
+                    address.authority, address.protocol,
+                ))
+                .into_boxed());
+            }
+
             ctx.insert(address.clone());
 
             #[cfg(feature = "tls")]
@@ -123,8 +218,15 @@ where
             self.inner
                 .connect(ctx, req)
                 .await
-                .map_err(|err| match address.as_ref() {
-                    Some(address) => OpaqueError::from_std(HttpProxyError::Transport(
+                .map_err(|err| match (&chain, address.as_ref()) {
+                    (Some(chain), _) => OpaqueError::from_std(ProxyChainError::new(
+                        0,
+                        chain.first(),
+                        OpaqueError::from_boxed(err.into())
+                            .context("establish connection to proxy")
+                            .into_boxed(),
+                    )),
+                    (None, Some(address)) => OpaqueError::from_std(HttpProxyError::Transport(
                         OpaqueError::from_boxed(err.into())
                             .context(format!(
                                 "establish connection to proxy {} (protocol: {:?})",
@@ -132,7 +234,7 @@ where
                             ))
                             .into_boxed(),
                     )),
-                    None => {
+                    (None, None) => {
                         OpaqueError::from_boxed(err.into()).context("establish connection target")
                     }
                 })?;
@@ -158,7 +260,37 @@ where
         };
         // and do the handshake otherwise...
 
-        let EstablishedClientConnection { ctx, req, conn } = established_conn;
+        let EstablishedClientConnection { mut ctx, req, conn } = established_conn;
+
+        if let Some(chain) = chain {
+            // the first hop is not to be exposed beyond this connector,
+            // as the connection is tunneled through all hops to the target
+            ctx.remove::<ProxyAddress>();
+            #[cfg(feature = "tls")]
+            ctx.remove::<TlsTunnel>();
+
+            let conn = self
+                .chain
+                .tunnel(
+                    &ctx,
+                    &chain,
+                    &transport_ctx.authority,
+                    conn,
+                    self.digest_auth,
+                )
+                .await?;
+
+            tracing::trace!(
+                authority = %transport_ctx.authority,
+                hops = chain.hops().len(),
+                "http proxy connector: tunneled through proxy chain: ready secure request",
+            );
+            return Ok(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: Either::B(conn),
+            });
+        }
 
         tracing::trace!(
             authority = %transport_ctx.authority,
@@ -184,14 +316,7 @@ where
         let mut connector = InnerHttpProxyConnector::new(transport_ctx.authority.clone())?;
 
         if let Some(credential) = address.credential.clone() {
-            match credential {
-                ProxyCredential::Basic(basic) => {
-                    connector.with_typed_header(ProxyAuthorization(basic));
-                }
-                ProxyCredential::Bearer(bearer) => {
-                    connector.with_typed_header(ProxyAuthorization(bearer));
-                }
-            }
+            connector.with_proxy_credential(credential, self.digest_auth);
         }
 
         let conn = connector
@@ -0,0 +1,220 @@
+use crate::client::proxy::ProxyEnv;
+use rama_core::{
+    Context, Layer, Service,
+    error::{ErrorContext, OpaqueError},
+};
+use rama_http_types::Request;
+use rama_net::{address::ProxyAddress, http::RequestContext};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, future::Future, sync::Arc};
+
+#[derive(Debug, Clone, Default)]
+/// A [`Layer`] which selects the [`ProxyAddress`] to be used per request
+/// using a [`ProxyEnv`], in order to have your client connector
+/// make a connection via this proxy (e.g. by using [`HttpProxyConnectorLayer`]).
+///
+/// See [`HttpProxyEnvService`] for more information.
+///
+/// [`HttpProxyConnectorLayer`]: crate::client::proxy::layer::HttpProxyConnectorLayer
+pub struct HttpProxyEnvLayer {
+    env: Arc<ProxyEnv>,
+    preserve: bool,
+}
+
+impl HttpProxyEnvLayer {
+    /// Create a new [`HttpProxyEnvLayer`] that will create
+    /// a service to select the proxy using the given [`ProxyEnv`].
+    pub fn new(env: ProxyEnv) -> Self {
+        Self {
+            env: Arc::new(env),
+            preserve: false,
+        }
+    }
+
+    /// Try to create a new [`HttpProxyEnvLayer`] using the [`ProxyEnv`]
+    /// defined by the environment variables of the current process.
+    pub fn try_from_env() -> Result<Self, OpaqueError> {
+        let env = ProxyEnv::try_from_env().context("read proxy env")?;
+        Ok(Self::new(env))
+    }
+
+    /// Preserve the existing [`ProxyAddress`] in the context if it already exists.
+    pub fn preserve(mut self, preserve: bool) -> Self {
+        self.preserve = preserve;
+        self
+    }
+
+    /// Preserve the existing [`ProxyAddress`] in the context if it already exists.
+    pub fn set_preserve(&mut self, preserve: bool) -> &mut Self {
+        self.preserve = preserve;
+        self
+    }
+}
+
+impl<S> Layer<S> for HttpProxyEnvLayer {
+    type Service = HttpProxyEnvService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpProxyEnvService {
+            inner,
+            env: self.env.clone(),
+            preserve: self.preserve,
+        }
+    }
+}
+
+/// A [`Service`] which selects the [`ProxyAddress`] to be used per request
+/// using a [`ProxyEnv`], in order to have your client connector
+/// make a connection via this proxy (e.g. by using [`HttpProxyConnectorLayer`]).
+///
+/// The proxy is selected based on the [`RequestContext`] of each request,
+/// such that e.g. a redirect from `http` to `https` uses the `HTTPS_PROXY`
+/// and redirects to hosts matching `NO_PROXY` are not proxied.
+///
+/// [`HttpProxyConnectorLayer`]: crate::client::proxy::layer::HttpProxyConnectorLayer
+pub struct HttpProxyEnvService<S> {
+    inner: S,
+    env: Arc<ProxyEnv>,
+    preserve: bool,
+}
+
+impl<S: fmt::Debug> fmt::Debug for HttpProxyEnvService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpProxyEnvService")
+            .field("inner", &self.inner)
+            .field("env", &self.env)
+            .field("preserve", &self.preserve)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for HttpProxyEnvService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            env: self.env.clone(),
+            preserve: self.preserve,
+        }
+    }
+}
+
+impl<S> HttpProxyEnvService<S> {
+    /// Create a new [`HttpProxyEnvService`] that will
+    /// select the proxy using the given [`ProxyEnv`].
+    pub fn new(inner: S, env: ProxyEnv) -> Self {
+        Self {
+            inner,
+            env: Arc::new(env),
+            preserve: false,
+        }
+    }
+
+    /// Try to create a new [`HttpProxyEnvService`] using the [`ProxyEnv`]
+    /// defined by the environment variables of the current process.
+    pub fn try_from_env(inner: S) -> Result<Self, OpaqueError> {
+        let env = ProxyEnv::try_from_env().context("read proxy env")?;
+        Ok(Self::new(inner, env))
+    }
+
+    /// Preserve the existing [`ProxyAddress`] in the context if it already exists.
+    pub fn preserve(mut self, preserve: bool) -> Self {
+        self.preserve = preserve;
+        self
+    }
+
+    /// Preserve the existing [`ProxyAddress`] in the context if it already exists.
+    pub fn set_preserve(&mut self, preserve: bool) -> &mut Self {
+        self.preserve = preserve;
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State, Body> Service<State, Request<Body>> for HttpProxyEnvService<S>
+where
+    S: Service<State, Request<Body>>,
+    State: Clone + Send + Sync + 'static,
+    Body: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<Body>,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        if !self.preserve || !ctx.contains::<ProxyAddress>() {
+            insert_proxy_address(&self.env, &mut ctx, &req);
+        }
+        self.inner.serve(ctx, req)
+    }
+}
+
+/// Insert the [`ProxyAddress`] selected by the [`ProxyEnv`] for the given request
+/// into the [`Context`], removing any existing [`ProxyAddress`] if none is selected.
+pub(crate) fn insert_proxy_address<State, Body>(
+    env: &ProxyEnv,
+    ctx: &mut Context<State>,
+    req: &Request<Body>,
+) {
+    // computed for every request, as the context might be reused (e.g. for redirects)
+    let address = match RequestContext::try_from((&*ctx, req)) {
+        Ok(request_ctx) => env
+            .proxy_for(&request_ctx.protocol, &request_ctx.authority)
+            .cloned(),
+        Err(err) => {
+            tracing::debug!(%err, "proxy env: failed to get request context: no proxy selected");
+            None
+        }
+    };
+    match address {
+        Some(address) => {
+            tracing::trace!(authority = %address.authority, "proxy env: setting proxy address");
+            ctx.insert(address);
+        }
+        None => {
+            ctx.remove::<ProxyAddress>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::proxy::NoProxy;
+    use rama_core::service::service_fn;
+    use rama_net::Protocol;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_proxy_env_service() {
+        let env = ProxyEnv::new()
+            .with_http_proxy("http-proxy:3128".parse().unwrap())
+            .with_https_proxy("https-proxy:3128".parse().unwrap())
+            .with_no_proxy(NoProxy::new("internal.example"));
+
+        let svc = HttpProxyEnvLayer::new(env).layer(service_fn(
+            async |ctx: Context<()>, _req: Request<()>| {
+                Ok::<_, Infallible>(ctx.get::<ProxyAddress>().map(|a| a.authority.to_string()))
+            },
+        ));
+
+        for (uri, expected) in [
+            ("http://example.com", Some("http-proxy:3128")),
+            ("https://example.com", Some("https-proxy:3128")),
+            ("https://api.internal.example", None),
+        ] {
+            let req = Request::builder().uri(uri).body(()).unwrap();
+            let mut ctx = Context::default();
+            ctx.insert(ProxyAddress {
+                protocol: Some(Protocol::HTTP),
+                authority: "stale:3128".parse().unwrap(),
+                credential: None,
+            });
+            let address = svc.serve(ctx, req).await.unwrap();
+            assert_eq!(address.as_deref(), expected, "{uri}");
+        }
+    }
+}
@@ -1,3 +1,7 @@
 //! Client Http Proxy Connector Support.
 
 pub mod layer;
+
+mod env;
+#[doc(inline)]
+pub use env::{NoProxy, ProxyEnv};
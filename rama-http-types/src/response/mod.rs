@@ -37,6 +37,10 @@ mod redirect;
 #[doc(inline)]
 pub use redirect::Redirect;
 
+pub mod sse;
+#[doc(inline)]
+pub use sse::Sse;
+
 /// Type alias for [`http::Response`] whose body type defaults to [`Body`], the most common body
 /// type used with rama.
 pub type Response<T = Body> = http::Response<T>;
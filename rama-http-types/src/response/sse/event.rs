@@ -0,0 +1,224 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use rama_error::{ErrorContext, OpaqueError};
+use serde::{Serialize, de::DeserializeOwned};
+use std::time::Duration;
+
+/// A single Server-Sent Event.
+///
+/// Used both to produce events as part of an [`Sse`] response,
+/// and as the item type of an [`EventStream`] when consuming them.
+///
+/// [`Sse`]: super::Sse
+/// [`EventStream`]: super::EventStream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+    comment: Option<String>,
+}
+
+impl Event {
+    /// Create a new empty [`Event`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the id of this [`Event`], if any.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Set the id of this [`Event`].
+    ///
+    /// Fails if the id contains a newline, carriage return or null character.
+    pub fn try_with_id(mut self, id: impl Into<String>) -> Result<Self, OpaqueError> {
+        self.try_set_id(id)?;
+        Ok(self)
+    }
+
+    /// Set the id of this [`Event`].
+    ///
+    /// Fails if the id contains a newline, carriage return or null character.
+    pub fn try_set_id(&mut self, id: impl Into<String>) -> Result<&mut Self, OpaqueError> {
+        let id = id.into();
+        if id.contains(['\n', '\r', '\0']) {
+            return Err(OpaqueError::from_display(
+                "sse event id cannot contain newlines, carriage returns or null characters",
+            ));
+        }
+        self.id = Some(id);
+        Ok(self)
+    }
+
+    /// Return the event type of this [`Event`], if any.
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    /// Set the event type of this [`Event`].
+    ///
+    /// Fails if the event type contains a newline or carriage return.
+    pub fn try_with_event(mut self, event: impl Into<String>) -> Result<Self, OpaqueError> {
+        self.try_set_event(event)?;
+        Ok(self)
+    }
+
+    /// Set the event type of this [`Event`].
+    ///
+    /// Fails if the event type contains a newline or carriage return.
+    pub fn try_set_event(&mut self, event: impl Into<String>) -> Result<&mut Self, OpaqueError> {
+        let event = event.into();
+        if event.contains(['\n', '\r']) {
+            return Err(OpaqueError::from_display(
+                "sse event type cannot contain newlines or carriage returns",
+            ));
+        }
+        self.event = Some(event);
+        Ok(self)
+    }
+
+    /// Return the data of this [`Event`], if any.
+    pub fn data(&self) -> Option<&str> {
+        self.data.as_deref()
+    }
+
+    /// Set the data of this [`Event`].
+    ///
+    /// Multi-line data is allowed and will be sent
+    /// as multiple `data` fields, with any line break
+    /// (`\n`, `\r\n` or `\r`) normalized into a `\n`.
+    pub fn with_data(mut self, data: impl Into<String>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Set the data of this [`Event`].
+    ///
+    /// Multi-line data is allowed and will be sent
+    /// as multiple `data` fields, with any line break
+    /// (`\n`, `\r\n` or `\r`) normalized into a `\n`.
+    pub fn set_data(&mut self, data: impl Into<String>) -> &mut Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Set the data of this [`Event`] to the JSON serialization of the given value.
+    pub fn try_with_json_data<T: Serialize>(mut self, data: T) -> Result<Self, OpaqueError> {
+        let data = serde_json::to_string(&data).context("serialize sse event data as json")?;
+        self.data = Some(data);
+        Ok(self)
+    }
+
+    /// Try to deserialize the data of this [`Event`] as JSON.
+    pub fn try_into_json_data<T: DeserializeOwned>(&self) -> Result<T, OpaqueError> {
+        serde_json::from_str(self.data.as_deref().unwrap_or_default())
+            .context("deserialize sse event data as json")
+    }
+
+    /// Return the reconnection time of this [`Event`], if any.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Set the reconnection time the client should use
+    /// when the connection got lost.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Set the reconnection time the client should use
+    /// when the connection got lost.
+    pub fn set_retry(&mut self, retry: Duration) -> &mut Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Return the comment of this [`Event`], if any.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Set a comment for this [`Event`].
+    ///
+    /// Comments are ignored by clients, but can be useful
+    /// for debugging or to keep a connection alive.
+    /// Multi-line comments are sent as multiple comment lines.
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Set a comment for this [`Event`].
+    ///
+    /// Comments are ignored by clients, but can be useful
+    /// for debugging or to keep a connection alive.
+    /// Multi-line comments are sent as multiple comment lines.
+    pub fn set_comment(&mut self, comment: impl Into<String>) -> &mut Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub(super) fn serialize(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        if let Some(comment) = self.comment.as_deref() {
+            for line in split_lines(comment) {
+                write_field(&mut buf, "", line);
+            }
+        }
+        if let Some(event) = self.event.as_deref() {
+            write_field(&mut buf, "event", event);
+        }
+        if let Some(data) = self.data.as_deref() {
+            for line in split_lines(data) {
+                write_field(&mut buf, "data", line);
+            }
+        }
+        if let Some(id) = self.id.as_deref() {
+            write_field(&mut buf, "id", id);
+        }
+        if let Some(retry) = self.retry {
+            write_field(&mut buf, "retry", &retry.as_millis().to_string());
+        }
+
+        buf.put_u8(b'\n');
+        buf.freeze()
+    }
+}
+
+/// Split the value on every line break recognised by sse parsers (`\n`, `\r\n` or `\r`),
+/// such that no line can inject another field, keeping a trailing empty line.
+fn split_lines(value: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(value);
+    std::iter::from_fn(move || {
+        let value = rest?;
+        match value.find(['\r', '\n']) {
+            Some(index) => {
+                let next = if value[index..].starts_with("\r\n") {
+                    index + 2
+                } else {
+                    index + 1
+                };
+                rest = Some(&value[next..]);
+                Some(&value[..index])
+            }
+            None => {
+                rest = None;
+                Some(value)
+            }
+        }
+    })
+}
+
+fn write_field(buf: &mut BytesMut, name: &str, value: &str) {
+    buf.put_slice(name.as_bytes());
+    buf.put_u8(b':');
+    if !value.is_empty() {
+        buf.put_u8(b' ');
+        buf.put_slice(value.as_bytes());
+    }
+    buf.put_u8(b'\n');
+}
@@ -0,0 +1,261 @@
+//! Server-Sent Events (SSE) support.
+//!
+//! Use [`Sse`] to produce a `text/event-stream` response from a [`Stream`] of [`Event`]s,
+//! and [`EventStream`] to consume such a response body as a [`Stream`] of [`Event`]s.
+//!
+//! Learn more about SSE at
+//! <https://html.spec.whatwg.org/multipage/server-sent-events.html>.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_http_types::response::{IntoResponse, sse::{Event, KeepAlive, Sse}};
+//! use futures_lite::stream;
+//! use std::convert::Infallible;
+//!
+//! async fn handler() -> impl IntoResponse {
+//!     let events = stream::iter(vec![
+//!         Ok::<_, Infallible>(Event::new().with_data("hello")),
+//!         Ok(Event::new().with_data("world")),
+//!     ]);
+//!     Sse::new(events).with_keep_alive(KeepAlive::default())
+//! }
+//! ```
+//!
+//! [`Stream`]: futures_core::Stream
+
+use crate::response::{IntoResponse, Response};
+use crate::{
+    Body,
+    header::{CACHE_CONTROL, CONTENT_TYPE, HeaderValue},
+};
+use bytes::Bytes;
+use futures_core::{Stream, TryStream};
+use pin_project_lite::pin_project;
+use rama_error::BoxError;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Sleep;
+
+mod event;
+#[doc(inline)]
+pub use event::Event;
+
+mod stream;
+#[doc(inline)]
+pub use stream::EventStream;
+
+/// An SSE response, created from a [`Stream`] of [`Event`]s.
+///
+/// [`Stream`]: futures_core::Stream
+#[derive(Clone)]
+pub struct Sse<S> {
+    stream: S,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<S> Sse<S> {
+    /// Create a new [`Sse`] response that will respond with the given stream of [`Event`]s.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            keep_alive: None,
+        }
+    }
+
+    /// Configure the interval between keep-alive messages.
+    ///
+    /// Defaults to no keep-alive messages.
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    /// Configure the interval between keep-alive messages.
+    ///
+    /// Defaults to no keep-alive messages.
+    pub fn set_keep_alive(&mut self, keep_alive: KeepAlive) -> &mut Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+impl<S> fmt::Debug for Sse<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sse")
+            .field("stream", &format_args!("{}", std::any::type_name::<S>()))
+            .field("keep_alive", &self.keep_alive)
+            .finish()
+    }
+}
+
+impl<S> IntoResponse for Sse<S>
+where
+    S: TryStream<Ok = Event, Error: Into<BoxError>> + Send + 'static,
+{
+    fn into_response(self) -> Response {
+        (
+            [
+                (CONTENT_TYPE, HeaderValue::from_static("text/event-stream")),
+                (CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            Body::from_stream(SseStream {
+                event_stream: self.stream,
+                keep_alive: self.keep_alive.map(KeepAliveStream::new),
+            }),
+        )
+            .into_response()
+    }
+}
+
+pin_project! {
+    struct SseStream<S> {
+        #[pin]
+        event_stream: S,
+        #[pin]
+        keep_alive: Option<KeepAliveStream>,
+    }
+}
+
+impl<S> Stream for SseStream<S>
+where
+    S: TryStream<Ok = Event, Error: Into<BoxError>>,
+{
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        match this.event_stream.try_poll_next(cx) {
+            Poll::Pending => {
+                if let Some(keep_alive) = this.keep_alive.as_pin_mut() {
+                    keep_alive.poll_event(cx).map(|e| Some(Ok(e)))
+                } else {
+                    Poll::Pending
+                }
+            }
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some(keep_alive) = this.keep_alive.as_pin_mut() {
+                    keep_alive.reset();
+                }
+                Poll::Ready(Some(Ok(event.serialize())))
+            }
+            Poll::Ready(Some(Err(error))) => Poll::Ready(Some(Err(error.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+        }
+    }
+}
+
+/// Configure the interval between keep-alive messages, the content
+/// of each message, and the associated stream.
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    event: Bytes,
+    max_interval: Duration,
+}
+
+impl KeepAlive {
+    /// Create a new [`KeepAlive`], sending an empty comment every 15 seconds.
+    pub fn new() -> Self {
+        Self {
+            event: Bytes::from_static(b":\n\n"),
+            max_interval: Duration::from_secs(15),
+        }
+    }
+
+    /// Customize the interval between keep-alive messages.
+    ///
+    /// Default is 15 seconds.
+    pub fn with_interval(mut self, time: Duration) -> Self {
+        self.max_interval = time;
+        self
+    }
+
+    /// Customize the interval between keep-alive messages.
+    ///
+    /// Default is 15 seconds.
+    pub fn set_interval(&mut self, time: Duration) -> &mut Self {
+        self.max_interval = time;
+        self
+    }
+
+    /// Customize the text of the keep-alive message.
+    ///
+    /// The text is sent as an SSE comment and thus ignored by clients.
+    /// Default is an empty comment.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.event = Event::new().with_comment(text).serialize();
+        self
+    }
+
+    /// Customize the text of the keep-alive message.
+    ///
+    /// The text is sent as an SSE comment and thus ignored by clients.
+    /// Default is an empty comment.
+    pub fn set_text(&mut self, text: impl Into<String>) -> &mut Self {
+        self.event = Event::new().with_comment(text).serialize();
+        self
+    }
+
+    /// Customize the event of the keep-alive message.
+    ///
+    /// Default is an empty comment.
+    pub fn with_event(mut self, event: Event) -> Self {
+        self.event = event.serialize();
+        self
+    }
+
+    /// Customize the event of the keep-alive message.
+    ///
+    /// Default is an empty comment.
+    pub fn set_event(&mut self, event: Event) -> &mut Self {
+        self.event = event.serialize();
+        self
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pin_project! {
+    struct KeepAliveStream {
+        keep_alive: KeepAlive,
+        #[pin]
+        alive_timer: Sleep,
+    }
+}
+
+impl KeepAliveStream {
+    fn new(keep_alive: KeepAlive) -> Self {
+        Self {
+            alive_timer: tokio::time::sleep(keep_alive.max_interval),
+            keep_alive,
+        }
+    }
+
+    fn reset(self: Pin<&mut Self>) {
+        let this = self.project();
+        this.alive_timer
+            .reset(tokio::time::Instant::now() + this.keep_alive.max_interval);
+    }
+
+    fn poll_event(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Bytes> {
+        let this = self.as_mut().project();
+
+        futures_lite::ready!(this.alive_timer.poll(cx));
+
+        let event = this.keep_alive.event.clone();
+
+        self.reset();
+
+        Poll::Ready(event)
+    }
+}
@@ -0,0 +1,334 @@
+use super::Event;
+use crate::{Body, BodyDataStream, HeaderValue};
+use bytes::{Buf, Bytes, BytesMut};
+use futures_core::{Stream, TryStream};
+use pin_project_lite::pin_project;
+use rama_error::{BoxError, OpaqueError};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+pin_project! {
+    /// A [`Stream`] of [`Event`]s, parsed from a `text/event-stream` body.
+    ///
+    /// Parsing follows the event stream interpretation rules
+    /// of the WHATWG HTML specification. Comments are ignored.
+    ///
+    /// The last seen event id and reconnection time are tracked across events,
+    /// such that a client can reconnect using the `Last-Event-ID` header,
+    /// see [`EventStream::last_event_id_header`].
+    ///
+    /// [`Stream`]: futures_core::Stream
+    #[derive(Debug)]
+    pub struct EventStream<S> {
+        #[pin]
+        stream: S,
+        buffer: BytesMut,
+        parser: Parser,
+        done: bool,
+    }
+}
+
+impl EventStream<BodyDataStream> {
+    /// Create a new [`EventStream`] from a [`Body`].
+    pub fn from_body(body: Body) -> Self {
+        Self::new(body.into_data_stream())
+    }
+}
+
+impl<S> EventStream<S> {
+    /// Create a new [`EventStream`] from a [`Stream`] of bytes.
+    ///
+    /// [`Stream`]: futures_core::Stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: BytesMut::new(),
+            parser: Parser::default(),
+            done: false,
+        }
+    }
+
+    /// Start from a previously seen event id,
+    /// e.g. when resuming a stream after a reconnect.
+    pub fn with_last_event_id(mut self, id: impl Into<String>) -> Self {
+        self.parser.last_event_id = id.into();
+        self
+    }
+
+    /// Return the last event id seen on this stream, if any.
+    pub fn last_event_id(&self) -> Option<&str> {
+        (!self.parser.last_event_id.is_empty()).then_some(self.parser.last_event_id.as_str())
+    }
+
+    /// Return the last event id seen on this stream as a [`HeaderValue`],
+    /// to be used as the `Last-Event-ID` header when reconnecting.
+    pub fn last_event_id_header(&self) -> Option<HeaderValue> {
+        self.last_event_id()
+            .and_then(|id| HeaderValue::from_str(id).ok())
+    }
+
+    /// Return the reconnection time requested by the server, if any.
+    pub fn retry(&self) -> Option<Duration> {
+        self.parser.retry
+    }
+}
+
+impl<S> Stream for EventStream<S>
+where
+    S: TryStream<Ok: Into<Bytes>, Error: Into<BoxError>>,
+{
+    type Item = Result<Event, OpaqueError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            while let Some(line) = next_line(this.buffer, &mut this.parser.skip_lf, *this.done) {
+                if let Some(event) = this.parser.feed_line(&line) {
+                    return Poll::Ready(Some(Ok(event)));
+                }
+            }
+
+            if *this.done {
+                // incomplete events at the end of the stream are discarded
+                return Poll::Ready(None);
+            }
+
+            match futures_lite::ready!(this.stream.as_mut().try_poll_next(cx)) {
+                Some(Ok(chunk)) => {
+                    let chunk: Bytes = chunk.into();
+                    if !this.parser.bom_checked {
+                        this.buffer.extend_from_slice(&chunk);
+                        if this.buffer.len() < 3 && b"\xEF\xBB\xBF".starts_with(&this.buffer[..]) {
+                            continue;
+                        }
+                        if this.buffer.starts_with(b"\xEF\xBB\xBF") {
+                            this.buffer.advance(3);
+                        }
+                        this.parser.bom_checked = true;
+                    } else {
+                        this.buffer.extend_from_slice(&chunk);
+                    }
+                }
+                Some(Err(err)) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(OpaqueError::from_boxed(err.into()))));
+                }
+                None => *this.done = true,
+            }
+        }
+    }
+}
+
+/// Split the next line from the buffer,
+/// where a line ends in either CRLF, LF or CR.
+fn next_line(buffer: &mut BytesMut, skip_lf: &mut bool, eof: bool) -> Option<Bytes> {
+    if *skip_lf && !buffer.is_empty() {
+        if buffer[0] == b'\n' {
+            buffer.advance(1);
+        }
+        *skip_lf = false;
+    }
+
+    match buffer.iter().position(|b| *b == b'\n' || *b == b'\r') {
+        Some(pos) => {
+            let line = buffer.split_to(pos).freeze();
+            let terminator = buffer[0];
+            buffer.advance(1);
+            if terminator == b'\r' {
+                if buffer.is_empty() {
+                    *skip_lf = true;
+                } else if buffer[0] == b'\n' {
+                    buffer.advance(1);
+                }
+            }
+            Some(line)
+        }
+        None if eof && !buffer.is_empty() => {
+            // the final line is not terminated,
+            // which means that the pending event is incomplete
+            buffer.clear();
+            None
+        }
+        None => None,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Parser {
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    last_event_id: String,
+    retry: Option<Duration>,
+    skip_lf: bool,
+    bom_checked: bool,
+}
+
+impl Parser {
+    fn feed_line(&mut self, line: &[u8]) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        let line = String::from_utf8_lossy(line);
+        let (field, value) = match line.find(':') {
+            Some(0) => return None, // comment
+            Some(pos) => {
+                let value = &line[pos + 1..];
+                (&line[..pos], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line.as_ref(), ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_owned(),
+            "retry" if value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => (), // unknown fields are ignored
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event_type = self.event.take();
+        if !self.has_data {
+            return None;
+        }
+        self.has_data = false;
+
+        let mut data = std::mem::take(&mut self.data);
+        if data.ends_with('\n') {
+            data.pop();
+        }
+
+        let mut event = Event::new().with_data(data);
+        if let Some(event_type) = event_type.filter(|e| !e.is_empty()) {
+            event = event.try_with_event(event_type).ok()?;
+        }
+        if !self.last_event_id.is_empty() {
+            event = event.try_with_id(self.last_event_id.clone()).ok()?;
+        }
+        if let Some(retry) = self.retry {
+            event.set_retry(retry);
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::{StreamExt, stream};
+    use std::convert::Infallible;
+
+    async fn parse(chunks: &[&'static str]) -> Vec<Event> {
+        let stream = stream::iter(
+            chunks
+                .iter()
+                .map(|c| Ok::<_, Infallible>(Bytes::from_static(c.as_bytes())))
+                .collect::<Vec<_>>(),
+        );
+        EventStream::new(stream).map(|r| r.unwrap()).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_parse_simple_events() {
+        let events = parse(&["data: hello\n\ndata: world\n\n"]).await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data(), Some("hello"));
+        assert_eq!(events[1].data(), Some("world"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_multiline_data_and_fields() {
+        let events =
+            parse(&[": comment\nevent: update\nid: 42\nretry: 1500\ndata: a\ndata:b\n\n"]).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event(), Some("update"));
+        assert_eq!(events[0].id(), Some("42"));
+        assert_eq!(events[0].data(), Some("a\nb"));
+        assert_eq!(events[0].retry(), Some(Duration::from_millis(1500)));
+    }
+
+    #[tokio::test]
+    async fn test_parse_split_chunks_and_line_endings() {
+        let events = parse(&["\u{FEFF}da", "ta: one\r", "\n\r", "\ndata: two\r\r"]).await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data(), Some("one"));
+        assert_eq!(events[1].data(), Some("two"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_incomplete_event_discarded() {
+        let events = parse(&["data: one\n\ndata: two\n"]).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data(), Some("one"));
+    }
+
+    #[tokio::test]
+    async fn test_last_event_id_tracking() {
+        let stream = stream::iter(vec![Ok::<_, Infallible>(Bytes::from_static(
+            b"id: 1\ndata: a\n\ndata: b\n\nid: 2\n\n",
+        ))]);
+        let mut events = EventStream::new(stream).with_last_event_id("0");
+        assert_eq!(events.last_event_id(), Some("0"));
+
+        let first = events.next().await.unwrap().unwrap();
+        assert_eq!(first.id(), Some("1"));
+        let second = events.next().await.unwrap().unwrap();
+        assert_eq!(second.id(), Some("1"));
+        assert!(events.next().await.is_none());
+        assert_eq!(
+            events.last_event_id_header(),
+            Some(HeaderValue::from_static("2"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_serialized_event() {
+        let event = Event::new()
+            .try_with_event("message")
+            .unwrap()
+            .try_with_id("7")
+            .unwrap()
+            .with_data("line 1\nline 2");
+        let serialized = event.clone().with_comment("ignored").serialize();
+        let stream = stream::iter(vec![Ok::<_, Infallible>(serialized)]);
+        let parsed: Vec<_> = EventStream::new(stream).map(|r| r.unwrap()).collect().await;
+        assert_eq!(parsed, vec![event]);
+    }
+
+    #[tokio::test]
+    async fn test_serialize_line_breaks() {
+        let event = Event::new()
+            .with_comment("note\revent: injected")
+            .with_data("a\rid: injected\r\nb\n");
+        assert_eq!(
+            event.serialize(),
+            ": note\n: event: injected\ndata: a\ndata: id: injected\ndata: b\ndata:\n\n"
+        );
+
+        let stream = stream::iter(vec![Ok::<_, Infallible>(event.serialize())]);
+        let parsed: Vec<_> = EventStream::new(stream).map(|r| r.unwrap()).collect().await;
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].data(), Some("a\nid: injected\nb\n"));
+        assert_eq!(parsed[0].event(), None);
+        assert_eq!(parsed[0].id(), None);
+
+        let empty = Event::new().with_data("");
+        assert_eq!(empty.serialize(), "data:\n\n");
+    }
+}
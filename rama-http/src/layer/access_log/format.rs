@@ -0,0 +1,570 @@
+use crate::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version};
+use rama_core::error::OpaqueError;
+use std::{
+    fmt::{self, Write},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const COMMON_TEMPLATE: &str = r#"{client_ip} - {user} [{time}] "{request_line}" {status} {bytes}"#;
+const COMBINED_TEMPLATE: &str =
+    r#"{client_ip} - {user} [{time}] "{request_line}" {status} {bytes} "{referer}" "{user_agent}""#;
+
+/// The format used by the [`AccessLogLayer`] to render a single log line.
+///
+/// Built-in formats are available for the Apache
+/// [Common and Combined Log Format](https://httpd.apache.org/docs/current/logs.html#common)
+/// as well as a JSON format (one object per line).
+///
+/// Custom formats can be created from a template using [`AccessLogFormat::template`],
+/// where `{name}` placeholders are replaced with the value of the named field:
+///
+/// | placeholder | value |
+/// |-------------|-------|
+/// | `{client_ip}` | ip of the client, from the [`ClientAddr`] (resolved from trusted proxies) or else the peer [`SocketInfo`] |
+/// | `{user}` | the [`UserId`] username, or the basic auth username |
+/// | `{time}` | start time of the request, e.g. `10/Oct/2000:13:55:36 +0000` |
+/// | `{time_iso}` | start time of the request, e.g. `2000-10-10T13:55:36.000Z` |
+/// | `{method}` | request method |
+/// | `{uri}` | request uri |
+/// | `{path}` | request uri path |
+/// | `{query}` | request uri query |
+/// | `{version}` | request http version |
+/// | `{request_line}` | the request line, e.g. `GET /index.html HTTP/1.1` |
+/// | `{status}` | response status code |
+/// | `{bytes}` | amount of response body bytes sent |
+/// | `{duration_ms}` | time it took to serve the request, in milliseconds |
+/// | `{duration_us}` | time it took to serve the request, in microseconds |
+/// | `{referer}` | the `Referer` request header |
+/// | `{user_agent}` | the `User-Agent` request header |
+/// | `{tls_version}` | the negotiated tls version (requires the `tls` feature) |
+/// | `{ja4}` | the [`Ja4`] fingerprint of the client (requires the `tls` feature) |
+/// | `{header.<name>}` | the value of the `<name>` request header |
+/// | `{response_header.<name>}` | the value of the `<name>` response header |
+///
+/// Values which are not available are rendered as `-`.
+/// Use `{{` and `}}` to write a literal `{` or `}`.
+///
+/// [`AccessLogLayer`]: super::AccessLogLayer
+/// [`ClientAddr`]: rama_net::stream::ClientAddr
+/// [`SocketInfo`]: rama_net::stream::SocketInfo
+/// [`UserId`]: rama_net::user::UserId
+/// [`Ja4`]: https://blog.foxio.io/ja4%2B-network-fingerprinting
+#[derive(Debug, Clone)]
+pub struct AccessLogFormat {
+    kind: FormatKind,
+}
+
+#[derive(Debug, Clone)]
+enum FormatKind {
+    Template(Arc<[Segment]>),
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+    RequestHeader(HeaderName),
+    ResponseHeader(HeaderName),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    ClientIp,
+    User,
+    Time,
+    TimeIso,
+    Method,
+    Uri,
+    Path,
+    Query,
+    Version,
+    RequestLine,
+    Status,
+    Bytes,
+    DurationMs,
+    DurationUs,
+    Referer,
+    UserAgent,
+    TlsVersion,
+    Ja4,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "client_ip" => Self::ClientIp,
+            "user" => Self::User,
+            "time" => Self::Time,
+            "time_iso" => Self::TimeIso,
+            "method" => Self::Method,
+            "uri" => Self::Uri,
+            "path" => Self::Path,
+            "query" => Self::Query,
+            "version" => Self::Version,
+            "request_line" => Self::RequestLine,
+            "status" => Self::Status,
+            "bytes" => Self::Bytes,
+            "duration_ms" => Self::DurationMs,
+            "duration_us" => Self::DurationUs,
+            "referer" => Self::Referer,
+            "user_agent" => Self::UserAgent,
+            "tls_version" => Self::TlsVersion,
+            "ja4" => Self::Ja4,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for AccessLogFormat {
+    fn default() -> Self {
+        Self::combined()
+    }
+}
+
+impl AccessLogFormat {
+    /// The Apache Common Log Format:
+    ///
+    /// ```text
+    /// 127.0.0.1 - frank [10/Oct/2000:13:55:36 +0000] "GET /apache_pb.gif HTTP/1.0" 200 2326
+    /// ```
+    pub fn common() -> Self {
+        Self::template(COMMON_TEMPLATE).expect("valid common log template")
+    }
+
+    /// The Apache Combined Log Format,
+    /// which is the [`AccessLogFormat::common`] format
+    /// followed by the referer and user agent.
+    ///
+    /// This is the default format.
+    pub fn combined() -> Self {
+        Self::template(COMBINED_TEMPLATE).expect("valid combined log template")
+    }
+
+    /// A JSON format, writing a single object per line,
+    /// containing all fields which do not require a template argument.
+    ///
+    /// Values which are not available are `null`.
+    pub fn json() -> Self {
+        Self {
+            kind: FormatKind::Json,
+        }
+    }
+
+    /// Create a custom [`AccessLogFormat`] from a template.
+    ///
+    /// See the [`AccessLogFormat`] docs for the supported placeholders.
+    pub fn template(template: &str) -> Result<Self, OpaqueError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    if chars.as_str().starts_with('{') {
+                        chars.next();
+                        literal.push('{');
+                        continue;
+                    }
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        OpaqueError::from_display("unterminated placeholder in access log template")
+                    })?;
+                    let name = &rest[..end];
+                    chars = rest[end + 1..].chars();
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_placeholder(name)?);
+                }
+                '}' => {
+                    if !chars.as_str().starts_with('}') {
+                        return Err(OpaqueError::from_display(
+                            "unescaped '}' in access log template",
+                        ));
+                    }
+                    chars.next();
+                    literal.push('}');
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self {
+            kind: FormatKind::Template(segments.into()),
+        })
+    }
+
+    pub(super) fn needs_request_headers(&self) -> bool {
+        self.any_segment(|segment| matches!(segment, Segment::RequestHeader(_)))
+    }
+
+    pub(super) fn needs_response_headers(&self) -> bool {
+        self.any_segment(|segment| matches!(segment, Segment::ResponseHeader(_)))
+    }
+
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub(super) fn needs_ja4(&self) -> bool {
+        matches!(self.kind, FormatKind::Json)
+            || self.any_segment(|segment| matches!(segment, Segment::Field(Field::Ja4)))
+    }
+
+    fn any_segment(&self, f: impl Fn(&Segment) -> bool) -> bool {
+        match &self.kind {
+            FormatKind::Template(segments) => segments.iter().any(f),
+            FormatKind::Json => false,
+        }
+    }
+
+    pub(super) fn render(&self, entry: &AccessLogEntry) -> String {
+        match &self.kind {
+            FormatKind::Template(segments) => render_template(segments, entry),
+            FormatKind::Json => render_json(entry),
+        }
+    }
+}
+
+fn parse_placeholder(name: &str) -> Result<Segment, OpaqueError> {
+    if let Some(header) = name.strip_prefix("header.") {
+        return HeaderName::try_from(header)
+            .map(Segment::RequestHeader)
+            .map_err(|_| {
+                OpaqueError::from_display(format!(
+                    "invalid header name '{header}' in access log template"
+                ))
+            });
+    }
+    if let Some(header) = name.strip_prefix("response_header.") {
+        return HeaderName::try_from(header)
+            .map(Segment::ResponseHeader)
+            .map_err(|_| {
+                OpaqueError::from_display(format!(
+                    "invalid header name '{header}' in access log template"
+                ))
+            });
+    }
+    Field::parse(name).map(Segment::Field).ok_or_else(|| {
+        OpaqueError::from_display(format!(
+            "unknown placeholder '{{{name}}}' in access log template"
+        ))
+    })
+}
+
+/// All information gathered about a single request-response exchange.
+#[derive(Debug)]
+pub(super) struct AccessLogEntry {
+    pub(super) start: SystemTime,
+    pub(super) client_ip: Option<IpAddr>,
+    pub(super) user: Option<String>,
+    pub(super) method: Method,
+    pub(super) uri: Uri,
+    pub(super) version: Version,
+    pub(super) referer: Option<HeaderValue>,
+    pub(super) user_agent: Option<HeaderValue>,
+    pub(super) request_headers: HeaderMap,
+    pub(super) tls_version: Option<String>,
+    pub(super) ja4: Option<String>,
+    pub(super) status: Option<StatusCode>,
+    pub(super) response_headers: HeaderMap,
+    pub(super) bytes: u64,
+    pub(super) duration: Duration,
+}
+
+fn render_template(segments: &[Segment], entry: &AccessLogEntry) -> String {
+    let mut line = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => line.push_str(literal),
+            Segment::Field(field) => write_field(&mut line, *field, entry),
+            Segment::RequestHeader(name) => {
+                write_header_value(&mut line, entry.request_headers.get(name))
+            }
+            Segment::ResponseHeader(name) => {
+                write_header_value(&mut line, entry.response_headers.get(name))
+            }
+        }
+    }
+    line
+}
+
+fn write_field(line: &mut String, field: Field, entry: &AccessLogEntry) {
+    match field {
+        Field::ClientIp => write_optional(line, entry.client_ip.as_ref()),
+        Field::User => match &entry.user {
+            Some(user) => write_escaped(line, user.as_bytes()),
+            None => line.push('-'),
+        },
+        Field::Time => write_clf_time(line, entry.start),
+        Field::TimeIso => write_iso_time(line, entry.start),
+        Field::Method => write_escaped(line, entry.method.as_str().as_bytes()),
+        Field::Uri => write_escaped(line, entry.uri.to_string().as_bytes()),
+        Field::Path => write_escaped(line, entry.uri.path().as_bytes()),
+        Field::Query => match entry.uri.query() {
+            Some(query) => write_escaped(line, query.as_bytes()),
+            None => line.push('-'),
+        },
+        Field::Version => {
+            let _ = write!(line, "{:?}", entry.version);
+        }
+        Field::RequestLine => {
+            write_escaped(line, entry.method.as_str().as_bytes());
+            line.push(' ');
+            write_escaped(line, entry.uri.to_string().as_bytes());
+            let _ = write!(line, " {:?}", entry.version);
+        }
+        Field::Status => write_optional(line, entry.status.map(|status| status.as_u16()).as_ref()),
+        Field::Bytes => {
+            let _ = write!(line, "{}", entry.bytes);
+        }
+        Field::DurationMs => {
+            let _ = write!(line, "{}", entry.duration.as_millis());
+        }
+        Field::DurationUs => {
+            let _ = write!(line, "{}", entry.duration.as_micros());
+        }
+        Field::Referer => write_header_value(line, entry.referer.as_ref()),
+        Field::UserAgent => write_header_value(line, entry.user_agent.as_ref()),
+        Field::TlsVersion => match &entry.tls_version {
+            Some(version) => write_escaped(line, version.as_bytes()),
+            None => line.push('-'),
+        },
+        Field::Ja4 => match &entry.ja4 {
+            Some(ja4) => write_escaped(line, ja4.as_bytes()),
+            None => line.push('-'),
+        },
+    }
+}
+
+fn write_optional(line: &mut String, value: Option<&impl fmt::Display>) {
+    match value {
+        Some(value) => {
+            let _ = write!(line, "{value}");
+        }
+        None => line.push('-'),
+    }
+}
+
+fn write_header_value(line: &mut String, value: Option<&HeaderValue>) {
+    match value {
+        Some(value) => write_escaped(line, value.as_bytes()),
+        None => line.push('-'),
+    }
+}
+
+/// Write the value escaped in the same way as Apache does,
+/// such that a client cannot inject fake log lines or break quoted fields.
+fn write_escaped(line: &mut String, value: &[u8]) {
+    for &b in value {
+        match b {
+            b'"' => line.push_str("\\\""),
+            b'\\' => line.push_str("\\\\"),
+            0x20..=0x7e => line.push(b as char),
+            b => {
+                let _ = write!(line, "\\x{b:02x}");
+            }
+        }
+    }
+}
+
+fn render_json(entry: &AccessLogEntry) -> String {
+    let header_str = |value: &Option<HeaderValue>| {
+        value
+            .as_ref()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+    };
+
+    let mut time = String::new();
+    write_iso_time(&mut time, entry.start);
+
+    serde_json::json!({
+        "time": time,
+        "client_ip": entry.client_ip.map(|ip| ip.to_string()),
+        "user": entry.user,
+        "method": entry.method.as_str(),
+        "uri": entry.uri.to_string(),
+        "version": format!("{:?}", entry.version),
+        "status": entry.status.map(|status| status.as_u16()),
+        "bytes": entry.bytes,
+        "duration_ms": entry.duration.as_secs_f64() * 1000.0,
+        "referer": header_str(&entry.referer),
+        "user_agent": header_str(&entry.user_agent),
+        "tls_version": entry.tls_version,
+        "ja4": entry.ja4,
+    })
+    .to_string()
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u64,
+    minute: u64,
+    second: u64,
+    millis: u32,
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+        let secs_of_day = secs % 86_400;
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day % 3600 / 60,
+            second: secs_of_day % 60,
+            millis: since_epoch.subsec_millis(),
+        }
+    }
+}
+
+/// Convert days since the unix epoch into a (year, month, day) civil date,
+/// using the algorithm described in <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn write_clf_time(line: &mut String, time: SystemTime) {
+    let dt = DateTime::from(time);
+    let _ = write!(
+        line,
+        "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+        dt.day,
+        MONTHS[dt.month as usize - 1],
+        dt.year,
+        dt.hour,
+        dt.minute,
+        dt.second,
+    );
+}
+
+pub(crate) fn write_iso_time(line: &mut String, time: SystemTime) {
+    let dt = DateTime::from(time);
+    let _ = write!(
+        line,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.millis,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AccessLogEntry {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        AccessLogEntry {
+            start: UNIX_EPOCH + Duration::from_millis(971_186_136_042),
+            client_ip: Some(IpAddr::from([127, 0, 0, 1])),
+            user: Some("frank".to_owned()),
+            method: Method::GET,
+            uri: Uri::from_static("/apache_pb.gif?a=b"),
+            version: Version::HTTP_10,
+            referer: Some(HeaderValue::from_static(
+                "http://www.example.com/start.html",
+            )),
+            user_agent: Some(HeaderValue::from_static("Mozilla/4.08 \"evil\"")),
+            request_headers,
+            tls_version: None,
+            ja4: None,
+            status: Some(StatusCode::OK),
+            response_headers: HeaderMap::new(),
+            bytes: 2326,
+            duration: Duration::from_micros(1500),
+        }
+    }
+
+    #[test]
+    fn test_common_and_combined_format() {
+        let entry = entry();
+        assert_eq!(
+            AccessLogFormat::common().render(&entry),
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 +0000] "GET /apache_pb.gif?a=b HTTP/1.0" 200 2326"#
+        );
+        assert_eq!(
+            AccessLogFormat::combined().render(&entry),
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 +0000] "GET /apache_pb.gif?a=b HTTP/1.0" 200 2326 "http://www.example.com/start.html" "Mozilla/4.08 \"evil\"""#
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let line = AccessLogFormat::json().render(&entry());
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["time"], "2000-10-10T13:55:36.042Z");
+        assert_eq!(value["client_ip"], "127.0.0.1");
+        assert_eq!(value["user"], "frank");
+        assert_eq!(value["method"], "GET");
+        assert_eq!(value["uri"], "/apache_pb.gif?a=b");
+        assert_eq!(value["status"], 200);
+        assert_eq!(value["bytes"], 2326);
+        assert_eq!(value["duration_ms"], 1.5);
+        assert_eq!(value["user_agent"], "Mozilla/4.08 \"evil\"");
+        assert!(value["ja4"].is_null());
+    }
+
+    #[test]
+    fn test_template_format() {
+        let format = AccessLogFormat::template(
+            "{{{method}}} {path} {query} {status} {duration_us}us id={header.x-request-id} ct={response_header.content-type} {ja4}",
+        )
+        .unwrap();
+        assert!(format.needs_request_headers());
+        assert!(format.needs_response_headers());
+        assert!(format.needs_ja4());
+        assert_eq!(
+            format.render(&entry()),
+            "{GET} /apache_pb.gif a=b 200 1500us id=abc ct=- -"
+        );
+    }
+
+    #[test]
+    fn test_template_escapes_values() {
+        let mut entry = entry();
+        entry.user = Some("evil\nuser".to_owned());
+        let format = AccessLogFormat::template("{user}").unwrap();
+        assert_eq!(format.render(&entry), "evil\\x0auser");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for template in ["{unknown}", "{method", "method}", "{header.in valid}"] {
+            assert!(
+                AccessLogFormat::template(template).is_err(),
+                "template: {template}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+    }
+}
@@ -0,0 +1,578 @@
+//! Middleware that writes an access log, one line per served request.
+//!
+//! Where the [`trace`](super::trace) middleware emits tracing spans,
+//! this middleware writes a classic access log line for each request,
+//! once the response body has been sent (or dropped), using one of the
+//! built-in [`AccessLogFormat`]s (Apache Common, Combined or JSON)
+//! or a custom template.
+//!
+//! Lines are written through an [`AccessLogWriter`], which never blocks the request flow.
+//! The built-in writers send lines over a channel to a task spawned on the given
+//! [`Executor`], which writes them to stdout, stderr, any [`AsyncWrite`]r
+//! or a file rotated on size and/or time (see [`FileRotation`]).
+//!
+//! The user is taken from the [`UserId`] in the [`Context`], which requires the
+//! authorization middleware to be applied before this one. When no [`UserId`] is found
+//! the username of the (proxy) basic authorization header is used instead.
+//! [`UserId::Token`] users are never logged.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Layer, rt::Executor, service::service_fn};
+//! use rama_http::layer::access_log::{AccessLogFormat, AccessLogLayer, FileRotation};
+//! use rama_http::{Body, Request, Response};
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! async fn handle(_: Request) -> Result<Response, Infallible> {
+//!     Ok(Response::new(Body::from("hello")))
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let path = dir.path().join("access.log");
+//! let rotation = FileRotation::new(path)
+//!     .with_max_size(64 * 1024 * 1024)
+//!     .with_interval(Duration::from_secs(24 * 60 * 60));
+//!
+//! let service = AccessLogLayer::rotating_file(&Executor::new(), rotation, 1024)
+//!     .unwrap()
+//!     .with_format(AccessLogFormat::json())
+//!     .layer(service_fn(handle));
+//! # }
+//! ```
+//!
+//! [`Executor`]: rama_core::rt::Executor
+//! [`AsyncWrite`]: tokio::io::AsyncWrite
+//! [`UserId`]: rama_net::user::UserId
+//! [`UserId::Token`]: rama_net::user::UserId::Token
+
+use crate::dep::http_body::{self, Frame, SizeHint};
+use crate::header::{AUTHORIZATION, PROXY_AUTHORIZATION, REFERER, USER_AGENT};
+use crate::{Body, HeaderMap, Request, Response};
+use bytes::Bytes;
+use pin_project_lite::pin_project;
+use rama_core::rt::Executor;
+use rama_core::{Context, Layer, Service};
+use rama_net::stream::ClientAddr;
+use rama_net::user::{Basic, UserId};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll, ready},
+    time::{Instant, SystemTime},
+};
+use tokio::io::{AsyncWrite, stderr, stdout};
+use tokio::sync::mpsc::{Sender, UnboundedSender, channel, unbounded_channel};
+
+mod format;
+use format::AccessLogEntry;
+#[doc(inline)]
+pub use format::AccessLogFormat;
+pub(crate) use format::write_iso_time;
+
+mod writer;
+#[doc(inline)]
+pub use writer::{AccessLogWriter, FileRotation};
+use writer::{LineReceiver, LineSink, RotatingFile, spawn_line_writer};
+
+/// Layer that applies [`AccessLogService`] which writes an access log line per request.
+///
+/// See the [module docs](self) for more details.
+pub struct AccessLogLayer<W> {
+    writer: Arc<W>,
+    format: AccessLogFormat,
+}
+
+impl<W> fmt::Debug for AccessLogLayer<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogLayer")
+            .field("writer", &format_args!("{}", std::any::type_name::<W>()))
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl<W> Clone for AccessLogLayer<W> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            format: self.format.clone(),
+        }
+    }
+}
+
+impl<W> AccessLogLayer<W> {
+    /// Create a new [`AccessLogLayer`] with a custom [`AccessLogWriter`],
+    /// using the [`AccessLogFormat::combined`] format.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Arc::new(writer),
+            format: AccessLogFormat::default(),
+        }
+    }
+
+    /// Set the [`AccessLogFormat`] used to render the access log lines.
+    pub fn with_format(mut self, format: AccessLogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the [`AccessLogFormat`] used to render the access log lines.
+    pub fn set_format(&mut self, format: AccessLogFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+}
+
+impl AccessLogLayer<UnboundedSender<String>> {
+    /// Create a new [`AccessLogLayer`] that writes access log lines to an [`AsyncWrite`]r
+    /// over an unbounded channel.
+    pub fn writer_unbounded<W>(executor: &Executor, writer: W) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + Sync + 'static,
+    {
+        let (tx, rx) = unbounded_channel();
+        spawn_line_writer(
+            executor,
+            LineReceiver::Unbounded(rx),
+            LineSink::writer(writer),
+        );
+        Self::new(tx)
+    }
+
+    /// Create a new [`AccessLogLayer`] that writes access log lines to stdout
+    /// over an unbounded channel.
+    pub fn stdout_unbounded(executor: &Executor) -> Self {
+        Self::writer_unbounded(executor, stdout())
+    }
+
+    /// Create a new [`AccessLogLayer`] that writes access log lines to stderr
+    /// over an unbounded channel.
+    pub fn stderr_unbounded(executor: &Executor) -> Self {
+        Self::writer_unbounded(executor, stderr())
+    }
+}
+
+impl AccessLogLayer<Sender<String>> {
+    /// Create a new [`AccessLogLayer`] that writes access log lines to an [`AsyncWrite`]r
+    /// over a bounded channel with a fixed buffer size.
+    ///
+    /// Lines are dropped (with a warning) while the buffer is full.
+    pub fn writer<W>(executor: &Executor, writer: W, buffer_size: usize) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + Sync + 'static,
+    {
+        let (tx, rx) = channel(buffer_size);
+        spawn_line_writer(
+            executor,
+            LineReceiver::Bounded(rx),
+            LineSink::writer(writer),
+        );
+        Self::new(tx)
+    }
+
+    /// Create a new [`AccessLogLayer`] that writes access log lines to stdout
+    /// over a bounded channel with a fixed buffer size.
+    pub fn stdout(executor: &Executor, buffer_size: usize) -> Self {
+        Self::writer(executor, stdout(), buffer_size)
+    }
+
+    /// Create a new [`AccessLogLayer`] that writes access log lines to stderr
+    /// over a bounded channel with a fixed buffer size.
+    pub fn stderr(executor: &Executor, buffer_size: usize) -> Self {
+        Self::writer(executor, stderr(), buffer_size)
+    }
+
+    /// Create a new [`AccessLogLayer`] that writes access log lines
+    /// to a file rotated according to the given [`FileRotation`],
+    /// over a bounded channel with a fixed buffer size.
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn rotating_file(
+        executor: &Executor,
+        rotation: FileRotation,
+        buffer_size: usize,
+    ) -> io::Result<Self> {
+        let file = RotatingFile::open(rotation)?;
+        let (tx, rx) = channel(buffer_size);
+        spawn_line_writer(executor, LineReceiver::Bounded(rx), LineSink::File(file));
+        Ok(Self::new(tx))
+    }
+}
+
+impl<S, W> Layer<S> for AccessLogLayer<W> {
+    type Service = AccessLogService<S, W>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLogService {
+            inner,
+            writer: self.writer.clone(),
+            format: self.format.clone(),
+        }
+    }
+}
+
+/// Middleware that writes an access log line per request.
+///
+/// See the [module docs](self) for more details.
+pub struct AccessLogService<S, W> {
+    inner: S,
+    writer: Arc<W>,
+    format: AccessLogFormat,
+}
+
+impl<S, W> AccessLogService<S, W> {
+    /// Create a new [`AccessLogService`] with a custom [`AccessLogWriter`],
+    /// using the [`AccessLogFormat::combined`] format.
+    pub fn new(writer: W, inner: S) -> Self {
+        Self {
+            inner,
+            writer: Arc::new(writer),
+            format: AccessLogFormat::default(),
+        }
+    }
+
+    /// Set the [`AccessLogFormat`] used to render the access log lines.
+    pub fn with_format(mut self, format: AccessLogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the [`AccessLogFormat`] used to render the access log lines.
+    pub fn set_format(&mut self, format: AccessLogFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, W> fmt::Debug for AccessLogService<S, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogService")
+            .field("inner", &self.inner)
+            .field("writer", &format_args!("{}", std::any::type_name::<W>()))
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl<S: Clone, W> Clone for AccessLogService<S, W> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            writer: self.writer.clone(),
+            format: self.format.clone(),
+        }
+    }
+}
+
+impl<State, S, W, ReqBody, ResBody> Service<State, Request<ReqBody>> for AccessLogService<S, W>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    W: AccessLogWriter,
+    ReqBody: Send + 'static,
+    ResBody: http_body::Body<Data = Bytes, Error: Into<rama_core::error::BoxError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let entry = self.new_entry(&ctx, &req);
+        let mut log = PendingLog {
+            entry,
+            started: Instant::now(),
+            format: self.format.clone(),
+            writer: self.writer.clone(),
+        };
+
+        // on error the pending log is dropped, writing the line without status
+        let res = self.inner.serve(ctx, req).await?;
+
+        let (parts, body) = res.into_parts();
+        log.entry.status = Some(parts.status);
+        if self.format.needs_response_headers() {
+            log.entry.response_headers = parts.headers.clone();
+        }
+        let body = AccessLogBody {
+            inner: body,
+            log: Some(log),
+        };
+        Ok(Response::from_parts(parts, Body::new(body)))
+    }
+}
+
+impl<S, W> AccessLogService<S, W> {
+    fn new_entry<State, Body>(&self, ctx: &Context<State>, req: &Request<Body>) -> AccessLogEntry {
+        let headers = req.headers();
+
+        // NOTE: the forwarded info is not used, as it can be forged by the client,
+        // only the client address resolved from trusted proxies is
+        let client_ip = ClientAddr::from_ctx(ctx).map(|addr| addr.ip());
+
+        let user = match ctx.get::<UserId>() {
+            Some(UserId::Username(username)) => Some(username.clone()),
+            Some(UserId::Token(_) | UserId::Anonymous) => None,
+            None => [AUTHORIZATION, PROXY_AUTHORIZATION]
+                .into_iter()
+                .filter_map(|name| headers.get(name)?.to_str().ok())
+                .find_map(|value| Basic::try_from_header_str(value).ok())
+                .map(|basic| basic.username().to_owned()),
+        };
+
+        #[cfg(feature = "tls")]
+        let (tls_version, ja4) = {
+            use rama_net::{fingerprint::Ja4, tls::client::NegotiatedTlsParameters};
+
+            let tls_version = ctx
+                .get::<NegotiatedTlsParameters>()
+                .map(|params| params.protocol_version.to_string());
+            let ja4 = if self.format.needs_ja4() {
+                Ja4::compute(ctx.extensions())
+                    .ok()
+                    .map(|ja4| ja4.to_string())
+            } else {
+                None
+            };
+            (tls_version, ja4)
+        };
+        #[cfg(not(feature = "tls"))]
+        let (tls_version, ja4) = (None, None);
+
+        AccessLogEntry {
+            start: SystemTime::now(),
+            client_ip,
+            user,
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            version: req.version(),
+            referer: headers.get(REFERER).cloned(),
+            user_agent: headers.get(USER_AGENT).cloned(),
+            request_headers: if self.format.needs_request_headers() {
+                headers.clone()
+            } else {
+                HeaderMap::new()
+            },
+            tls_version,
+            ja4,
+            status: None,
+            response_headers: HeaderMap::new(),
+            bytes: 0,
+            duration: Default::default(),
+        }
+    }
+}
+
+/// An access log line waiting for the exchange to finish,
+/// written as soon as it is dropped.
+struct PendingLog<W: AccessLogWriter> {
+    entry: AccessLogEntry,
+    started: Instant,
+    format: AccessLogFormat,
+    writer: Arc<W>,
+}
+
+impl<W: AccessLogWriter> Drop for PendingLog<W> {
+    fn drop(&mut self) {
+        self.entry.duration = self.started.elapsed();
+        self.writer.write_log(self.format.render(&self.entry));
+    }
+}
+
+pin_project! {
+    /// Response body which counts the bytes sent,
+    /// writing the access log line once it is finished or dropped.
+    struct AccessLogBody<B, W: AccessLogWriter> {
+        #[pin]
+        inner: B,
+        log: Option<PendingLog<W>>,
+    }
+}
+
+impl<B, W> http_body::Body for AccessLogBody<B, W>
+where
+    B: http_body::Body<Data = Bytes>,
+    W: AccessLogWriter,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let result = ready!(this.inner.as_mut().poll_frame(cx));
+        match &result {
+            Some(Ok(frame)) => {
+                if let (Some(data), Some(log)) = (frame.data_ref(), this.log.as_mut()) {
+                    log.entry.bytes += data.len() as u64;
+                }
+                if this.inner.is_end_stream() {
+                    this.log.take();
+                }
+            }
+            Some(Err(_)) | None => {
+                this.log.take();
+            }
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::{HeaderValue, IntoResponse, StatusCode};
+    use rama_core::error::OpaqueError;
+    use rama_core::service::service_fn;
+    use rama_net::forwarded::{Forwarded, ForwardedElement};
+    use rama_net::stream::SocketInfo;
+    use std::{convert::Infallible, net::IpAddr};
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn layer() -> (
+        AccessLogLayer<UnboundedSender<String>>,
+        UnboundedReceiver<String>,
+    ) {
+        let (tx, rx) = unbounded_channel();
+        (AccessLogLayer::new(tx), rx)
+    }
+
+    #[tokio::test]
+    async fn test_access_log_written_after_body() {
+        let (layer, mut rx) = layer();
+        let service = layer
+            .with_format(
+                AccessLogFormat::template(
+                    "{client_ip} {user} \"{request_line}\" {status} {bytes} \"{user_agent}\"",
+                )
+                .unwrap(),
+            )
+            .layer(service_fn(|_: Request| async {
+                Ok::<_, Infallible>(Response::new(Body::from("hello world")))
+            }));
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, "10.0.0.1:4242".parse().unwrap()));
+        // forwarded info is not trusted, unless resolved as client address
+        ctx.insert(Forwarded::new(ForwardedElement::forwarded_for(
+            IpAddr::from([1, 2, 3, 4]),
+        )));
+        ctx.insert(UserId::Username("john".to_owned()));
+
+        let req = Request::builder()
+            .uri("/foo?bar=baz")
+            .header(USER_AGENT, "test")
+            .body(Body::empty())
+            .unwrap();
+        let res = service.serve(ctx, req).await.unwrap();
+        assert!(rx.try_recv().is_err());
+
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello world");
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            r#"10.0.0.1 john "GET /foo?bar=baz HTTP/1.1" 200 11 "test""#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_log_written_on_drop() {
+        let (layer, mut rx) = layer();
+        let service = layer
+            .with_format(
+                AccessLogFormat::template("{status} {bytes} {response_header.x-test}").unwrap(),
+            )
+            .layer(service_fn(|_: Request| async {
+                let mut res = StatusCode::NOT_FOUND.into_response();
+                res.headers_mut()
+                    .insert("x-test", HeaderValue::from_static("foo"));
+                Ok::<_, Infallible>(res)
+            }));
+
+        let res = service
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        drop(res);
+        assert_eq!(rx.try_recv().unwrap(), "404 0 foo");
+    }
+
+    #[tokio::test]
+    async fn test_access_log_on_error_and_basic_auth_user() {
+        let (layer, mut rx) = layer();
+        let service = layer
+            .with_format(AccessLogFormat::common())
+            .layer(service_fn(|_: Request| async {
+                Err::<Response, _>(OpaqueError::from_display("oops"))
+            }));
+
+        let req = Request::builder()
+            .uri("http://example.com/")
+            .header(
+                PROXY_AUTHORIZATION,
+                Basic::new("alice", "secret").as_header_string(),
+            )
+            .body(Body::empty())
+            .unwrap();
+        assert!(service.serve(Context::default(), req).await.is_err());
+
+        let line = rx.try_recv().unwrap();
+        assert!(line.starts_with("- - alice ["), "line: {line}");
+        assert!(
+            line.ends_with(r#"] "GET http://example.com/ HTTP/1.1" - 0"#),
+            "line: {line}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_log_rotating_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+
+        let service = AccessLogLayer::rotating_file(&Executor::new(), FileRotation::new(&path), 8)
+            .unwrap()
+            .with_format(AccessLogFormat::template("{method} {path}").unwrap())
+            .layer(service_fn(|_: Request| async {
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            }));
+
+        for path in ["/a", "/b"] {
+            let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+            let res = service.serve(Context::default(), req).await.unwrap();
+            res.into_body().collect().await.unwrap();
+        }
+
+        let mut content = String::new();
+        for _ in 0..100 {
+            content = tokio::fs::read_to_string(&path).await.unwrap();
+            if content.lines().count() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(content, "GET /a\nGET /b\n");
+    }
+}
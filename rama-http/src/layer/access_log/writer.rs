@@ -0,0 +1,306 @@
+use rama_core::rt::Executor;
+use std::{
+    ffi::OsString,
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender},
+};
+
+/// A trait for writing access log lines.
+///
+/// Lines are written from within the request flow,
+/// and as such implementations should never block.
+pub trait AccessLogWriter: Send + Sync + 'static {
+    /// Write a single access log line, without trailing newline.
+    fn write_log(&self, line: String);
+}
+
+impl AccessLogWriter for Sender<String> {
+    fn write_log(&self, line: String) {
+        match self.try_send(line) {
+            Ok(()) => (),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("access log buffer is full: dropping access log line")
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::error!("access log writer is closed: dropping access log line")
+            }
+        }
+    }
+}
+
+impl AccessLogWriter for UnboundedSender<String> {
+    fn write_log(&self, line: String) {
+        if self.send(line).is_err() {
+            tracing::error!("access log writer is closed: dropping access log line")
+        }
+    }
+}
+
+/// Configuration of a rotating access log file,
+/// used by [`AccessLogLayer::rotating_file`].
+///
+/// The file is rotated once it would grow beyond the maximum size
+/// and/or once the rotation interval passed since it was opened.
+/// On rotation `access.log` is renamed to `access.log.1`,
+/// `access.log.1` to `access.log.2` and so on,
+/// removing the oldest file once more than [`FileRotation::with_max_files`]
+/// rotated files exist.
+///
+/// [`AccessLogLayer::rotating_file`]: super::AccessLogLayer::rotating_file
+#[derive(Debug, Clone)]
+pub struct FileRotation {
+    path: PathBuf,
+    max_size: Option<u64>,
+    interval: Option<Duration>,
+    max_files: usize,
+}
+
+impl FileRotation {
+    /// Create a new [`FileRotation`] for the file at the given path.
+    ///
+    /// By default the file is never rotated and at most 7 rotated files are kept.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: None,
+            interval: None,
+            max_files: 7,
+        }
+    }
+
+    /// Rotate the file once it would grow beyond the given size in bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Rotate the file once it would grow beyond the given size in bytes.
+    pub fn set_max_size(&mut self, max_size: u64) -> &mut Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Rotate the file once the given interval passed since it was opened.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Rotate the file once the given interval passed since it was opened.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Set the amount of rotated files to keep.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Set the amount of rotated files to keep.
+    pub fn set_max_files(&mut self, max_files: usize) -> &mut Self {
+        self.max_files = max_files;
+        self
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+/// Where the background task writes its lines to.
+pub(super) enum LineSink {
+    Writer(BufWriter<Box<dyn AsyncWrite + Unpin + Send + Sync>>),
+    File(RotatingFile),
+}
+
+impl LineSink {
+    pub(super) fn writer<W>(writer: W) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + Sync + 'static,
+    {
+        Self::Writer(BufWriter::new(Box::new(writer)))
+    }
+
+    async fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Self::Writer(writer) => {
+                writer.write_all(line.as_bytes()).await?;
+                writer.write_all(b"\n").await
+            }
+            Self::File(file) => file.write_line(line).await,
+        }
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Writer(writer) => writer.flush().await,
+            Self::File(file) => file.writer.flush().await,
+        }
+    }
+}
+
+pub(super) struct RotatingFile {
+    rotation: FileRotation,
+    writer: BufWriter<File>,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub(super) fn open(rotation: FileRotation) -> io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&rotation.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            rotation,
+            writer: BufWriter::new(File::from_std(file)),
+            size,
+            opened_at: Instant::now(),
+        })
+    }
+
+    async fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.should_rotate(len) {
+            self.rotate().await?;
+        }
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn should_rotate(&self, len: u64) -> bool {
+        let too_large = self
+            .rotation
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + len > max_size);
+        let too_old = self
+            .rotation
+            .interval
+            .is_some_and(|interval| self.opened_at.elapsed() >= interval);
+        too_large || too_old
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush().await?;
+
+        let max_files = self.rotation.max_files;
+        if max_files == 0 {
+            tokio::fs::remove_file(&self.rotation.path).await?;
+        } else {
+            for index in (1..max_files).rev() {
+                let from = self.rotation.rotated_path(index);
+                if tokio::fs::try_exists(&from).await? {
+                    tokio::fs::rename(&from, self.rotation.rotated_path(index + 1)).await?;
+                }
+            }
+            tokio::fs::rename(&self.rotation.path, self.rotation.rotated_path(1)).await?;
+        }
+
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.rotation.path)
+            .await?;
+        self.size = file.metadata().await?.len();
+        self.writer = BufWriter::new(file);
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+pub(super) enum LineReceiver {
+    Bounded(Receiver<String>),
+    Unbounded(UnboundedReceiver<String>),
+}
+
+impl LineReceiver {
+    async fn recv(&mut self) -> Option<String> {
+        match self {
+            Self::Bounded(rx) => rx.recv().await,
+            Self::Unbounded(rx) => rx.recv().await,
+        }
+    }
+
+    fn try_recv(&mut self) -> Option<String> {
+        match self {
+            Self::Bounded(rx) => rx.try_recv().ok(),
+            Self::Unbounded(rx) => rx.try_recv().ok(),
+        }
+    }
+}
+
+/// Spawn the task writing all received lines to the given sink,
+/// flushing whenever no more lines are pending.
+pub(super) fn spawn_line_writer(executor: &Executor, mut rx: LineReceiver, mut sink: LineSink) {
+    executor.spawn_task(async move {
+        while let Some(line) = rx.recv().await {
+            let mut next = Some(line);
+            while let Some(line) = next {
+                if let Err(err) = sink.write_line(&line).await {
+                    tracing::error!(err = %err, "failed to write access log line")
+                }
+                next = rx.try_recv();
+            }
+            if let Err(err) = sink.flush().await {
+                tracing::error!(err = %err, "failed to flush access log writer")
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rotating_file_on_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let rotation = FileRotation::new(&path).with_max_size(10).with_max_files(2);
+        let mut file = RotatingFile::open(rotation.clone()).unwrap();
+
+        for line in ["line-1", "line-2", "line-3", "line-4"] {
+            file.write_line(line).await.unwrap();
+        }
+        file.writer.flush().await.unwrap();
+
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(path.clone()), "line-4\n");
+        assert_eq!(read(rotation.rotated_path(1)), "line-3\n");
+        assert_eq!(read(rotation.rotated_path(2)), "line-2\n");
+        assert!(!rotation.rotated_path(3).exists());
+    }
+
+    #[tokio::test]
+    async fn test_rotating_file_on_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let rotation = FileRotation::new(&path).with_interval(Duration::from_millis(10));
+        let mut file = RotatingFile::open(rotation.clone()).unwrap();
+
+        file.write_line("first").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        file.write_line("second").await.unwrap();
+        file.writer.flush().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(
+            std::fs::read_to_string(rotation.rotated_path(1)).unwrap(),
+            "first\n"
+        );
+    }
+}
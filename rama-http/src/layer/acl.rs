@@ -0,0 +1,329 @@
+//! Middleware that restricts the destinations a (proxy) user can reach
+//! using an [`Acl`], such as an [`AclPolicy`] or [`AclPolicyFile`].
+//!
+//! It is evaluated for both `CONNECT` requests and plain (forwarded) requests,
+//! using the target [`Authority`] and [`Protocol`] of the request, as well as the
+//! [`UserId`] and [`UsernameLabels`] found in the [`Context`], as inserted by
+//! for example the [`ProxyAuthLayer`]. As such this layer is to be added after
+//! the layer(s) authenticating the user.
+//!
+//! If the request is denied a `403 Forbidden` response will be sent.
+//!
+//! [`AclPolicy`]: rama_net::user::acl::AclPolicy
+//! [`AclPolicyFile`]: rama_net::user::acl::AclPolicyFile
+//! [`Authority`]: rama_net::address::Authority
+//! [`Protocol`]: rama_net::Protocol
+//! [`UsernameLabels`]: rama_core::username::UsernameLabels
+//! [`ProxyAuthLayer`]: crate::layer::proxy_auth::ProxyAuthLayer
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, service::service_fn};
+//! use rama_http::{Body, Method, Request, Response, StatusCode, layer::acl::AclLayer};
+//! use rama_net::user::UserId;
+//! use rama_net::user::acl::{AclAction, AclPolicy, AclRule, DomainPattern};
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let policy = AclPolicy::new(AclAction::Deny).with_rule(
+//!     AclRule::allow()
+//!         .with_user("alice")
+//!         .with_domain("*.example.com".parse::<DomainPattern>().unwrap()),
+//! );
+//!
+//! let service = AclLayer::new(policy).layer(service_fn(async |_: Request| {
+//!     Ok::<_, Infallible>(Response::new(Body::empty()))
+//! }));
+//!
+//! let mut ctx = Context::default();
+//! ctx.insert(UserId::Username("alice".to_owned()));
+//!
+//! let req = Request::builder()
+//!     .method(Method::CONNECT)
+//!     .uri("www.example.com:443")
+//!     .body(Body::empty())
+//!     .unwrap();
+//! let resp = service.serve(ctx.clone(), req).await.unwrap();
+//! assert_eq!(resp.status(), StatusCode::OK);
+//!
+//! let req = Request::builder()
+//!     .method(Method::CONNECT)
+//!     .uri("www.example.org:443")
+//!     .body(Body::empty())
+//!     .unwrap();
+//! let resp = service.serve(ctx, req).await.unwrap();
+//! assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+//! # }
+//! ```
+
+use crate::{Request, Response, StatusCode};
+use rama_core::username::UsernameLabels;
+use rama_core::{Context, Layer, Service};
+use rama_net::http::RequestContext;
+use rama_net::user::UserId;
+use rama_net::user::acl::{Acl, AclAction, AclRequest};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// Layer that applies the [`AclService`] middleware,
+/// denying requests which are not allowed by the [`Acl`].
+///
+/// See the [module docs](self) for an example.
+pub struct AclLayer<A> {
+    acl: A,
+}
+
+impl<A: fmt::Debug> fmt::Debug for AclLayer<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AclLayer").field("acl", &self.acl).finish()
+    }
+}
+
+impl<A: Clone> Clone for AclLayer<A> {
+    fn clone(&self) -> Self {
+        Self {
+            acl: self.acl.clone(),
+        }
+    }
+}
+
+impl<A> AclLayer<A> {
+    /// Creates a new [`AclLayer`].
+    pub const fn new(acl: A) -> Self {
+        Self { acl }
+    }
+}
+
+impl<A: Clone, S> Layer<S> for AclLayer<A> {
+    type Service = AclService<A, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AclService::new(self.acl.clone(), inner)
+    }
+}
+
+/// Middleware that denies requests which are not allowed by the [`Acl`].
+///
+/// If the request is denied a `403 Forbidden` response will be sent,
+/// and a `400 Bad Request` one in case no target authority can be determined.
+///
+/// See the [module docs](self) for an example.
+pub struct AclService<A, S> {
+    acl: A,
+    inner: S,
+}
+
+impl<A, S> AclService<A, S> {
+    /// Creates a new [`AclService`].
+    pub const fn new(acl: A, inner: S) -> Self {
+        Self { acl, inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<A: fmt::Debug, S: fmt::Debug> fmt::Debug for AclService<A, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AclService")
+            .field("acl", &self.acl)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<A: Clone, S: Clone> Clone for AclService<A, S> {
+    fn clone(&self) -> Self {
+        Self {
+            acl: self.acl.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A, S, State, ReqBody, ResBody> Service<State, Request<ReqBody>> for AclService<A, S>
+where
+    A: Acl,
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let request_ctx: &mut RequestContext =
+            match ctx.get_or_try_insert_with_ctx(|ctx| (ctx, &req).try_into()) {
+                Ok(request_ctx) => request_ctx,
+                Err(err) => {
+                    tracing::debug!(
+                        uri = %req.uri(),
+                        ?err,
+                        "AclService: failed to determine target of request",
+                    );
+                    return Ok(status_response(StatusCode::BAD_REQUEST));
+                }
+            };
+        let authority = request_ctx.authority.clone();
+        let protocol = request_ctx.protocol.clone();
+
+        let mut acl_request = AclRequest::new(&authority)
+            .with_protocol(&protocol)
+            .with_method(req.method());
+        if let Some(user) = ctx.get::<UserId>() {
+            acl_request = acl_request.with_user(user);
+        }
+        if let Some(UsernameLabels(labels)) = ctx.get::<UsernameLabels>() {
+            acl_request = acl_request.with_labels(labels);
+        }
+
+        match self.acl.evaluate(&acl_request).await {
+            AclAction::Allow => self.inner.serve(ctx, req).await,
+            AclAction::Deny => {
+                tracing::debug!(
+                    user = ?acl_request.user,
+                    %authority,
+                    "AclService: request denied by acl",
+                );
+                Ok(status_response(StatusCode::FORBIDDEN))
+            }
+        }
+    }
+}
+
+fn status_response<ResBody: Default>(status: StatusCode) -> Response<ResBody> {
+    Response::builder()
+        .status(status)
+        .body(Default::default())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, Method};
+    use rama_core::service::service_fn;
+    use rama_net::user::acl::{AclPolicy, AclRule, DomainPattern};
+    use std::convert::Infallible;
+
+    fn acl_service(
+        policy: AclPolicy,
+    ) -> impl Service<(), Request, Response = Response, Error = Infallible> {
+        AclLayer::new(policy).layer(service_fn(async |_: Request| {
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        }))
+    }
+
+    fn request(method: Method, uri: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn user_ctx(username: &str, labels: &[&str]) -> Context<()> {
+        let mut ctx = Context::default();
+        ctx.insert(UserId::Username(username.to_owned()));
+        ctx.insert(UsernameLabels(
+            labels.iter().map(|label| (*label).to_owned()).collect(),
+        ));
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_acl_connect() {
+        let service = acl_service(
+            AclPolicy::new(AclAction::Deny)
+                .with_rule(AclRule::allow().with_user("alice").with_port(443)),
+        );
+
+        for (username, uri, expected) in [
+            ("alice", "example.com:443", StatusCode::OK),
+            ("alice", "example.com:8443", StatusCode::FORBIDDEN),
+            ("bob", "example.com:443", StatusCode::FORBIDDEN),
+        ] {
+            let resp = service
+                .serve(user_ctx(username, &[]), request(Method::CONNECT, uri))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), expected, "{username} -> {uri}");
+        }
+
+        let resp = service
+            .serve(
+                Context::default(),
+                request(Method::CONNECT, "example.com:443"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_acl_forwarded_request() {
+        let service = acl_service(
+            AclPolicy::new(AclAction::Allow)
+                .with_rule(
+                    AclRule::deny()
+                        .with_domain("*.example.com".parse::<DomainPattern>().unwrap())
+                        .with_method(Method::POST),
+                )
+                .with_rule(
+                    AclRule::deny()
+                        .with_label("trial")
+                        .with_protocol(rama_net::Protocol::HTTP),
+                ),
+        );
+
+        for (labels, method, uri, expected) in [
+            (
+                &[][..],
+                Method::GET,
+                "http://www.example.com/",
+                StatusCode::OK,
+            ),
+            (
+                &[],
+                Method::POST,
+                "http://www.example.com/",
+                StatusCode::FORBIDDEN,
+            ),
+            (&[], Method::POST, "http://example.com/", StatusCode::OK),
+            (
+                &["trial"],
+                Method::GET,
+                "http://example.com/",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                &["trial"],
+                Method::GET,
+                "https://example.com/",
+                StatusCode::OK,
+            ),
+        ] {
+            let resp = service
+                .serve(user_ctx("alice", labels), request(method.clone(), uri))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), expected, "{labels:?}: {method} {uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acl_unknown_target() {
+        let service = acl_service(AclPolicy::new(AclAction::Allow));
+        let resp = service
+            .serve(Context::default(), request(Method::GET, "/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -40,12 +40,14 @@
 //! # }
 //! ```
 
-use crate::{HeaderValue, Request, Response};
+use crate::{HeaderValue, Request, Response, StatusCode, header};
 use base64::Engine as _;
 use rama_core::{Context, Layer, Service};
+use rama_net::user::{Digest, DigestChallenge};
 use rama_utils::macros::define_inner_service_accessors;
 use std::convert::TryFrom;
 use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
 
 const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
 
@@ -62,6 +64,7 @@ const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::
 #[derive(Debug, Clone)]
 pub struct AddAuthorizationLayer {
     value: Option<HeaderValue>,
+    digest: Option<Arc<DigestSession>>,
     if_not_present: bool,
 }
 
@@ -73,6 +76,7 @@ impl AddAuthorizationLayer {
     pub fn none() -> Self {
         Self {
             value: None,
+            digest: None,
             if_not_present: false,
         }
     }
@@ -89,6 +93,7 @@ impl AddAuthorizationLayer {
         let value = HeaderValue::try_from(format!("Basic {}", encoded)).unwrap();
         Self {
             value: Some(value),
+            digest: None,
             if_not_present: false,
         }
     }
@@ -105,6 +110,28 @@ impl AddAuthorizationLayer {
             HeaderValue::try_from(format!("Bearer {}", token)).expect("token is not valid header");
         Self {
             value: Some(value),
+            digest: None,
+            if_not_present: false,
+        }
+    }
+
+    /// Authorize requests using digest authentication, as defined in RFC 7616.
+    ///
+    /// Digest credentials answer a challenge of the server, and thus the first request
+    /// will be responded to with a `401 Unauthorized` response containing that challenge.
+    /// All requests made after that will have the `Authorization` header set to the
+    /// digest credentials for that challenge, until the server sends a new challenge.
+    ///
+    /// Combine it with a retry layer to transparently retry the unauthorized request.
+    /// The `Authorization` header is always marked as sensitive.
+    pub fn digest(username: &str, password: &str) -> Self {
+        Self {
+            value: None,
+            digest: Some(Arc::new(DigestSession {
+                username: username.to_owned(),
+                password: password.to_owned(),
+                challenge: Mutex::new(None),
+            })),
             if_not_present: false,
         }
     }
@@ -157,6 +184,7 @@ impl<S> Layer<S> for AddAuthorizationLayer {
         AddAuthorization {
             inner,
             value: self.value.clone(),
+            digest: self.digest.clone(),
             if_not_present: self.if_not_present,
         }
     }
@@ -174,6 +202,7 @@ impl<S> Layer<S> for AddAuthorizationLayer {
 pub struct AddAuthorization<S> {
     inner: S,
     value: Option<HeaderValue>,
+    digest: Option<Arc<DigestSession>>,
     if_not_present: bool,
 }
 
@@ -208,6 +237,13 @@ impl<S> AddAuthorization<S> {
         AddAuthorizationLayer::bearer(token).layer(inner)
     }
 
+    /// Authorize requests using digest authentication, as defined in RFC 7616.
+    ///
+    /// See [`AddAuthorizationLayer::digest`] for more details.
+    pub fn digest(inner: S, username: &str, password: &str) -> Self {
+        AddAuthorizationLayer::digest(username, password).layer(inner)
+    }
+
     define_inner_service_accessors!();
 
     /// Mark the header as [sensitive].
@@ -256,6 +292,7 @@ impl<S: fmt::Debug> fmt::Debug for AddAuthorization<S> {
         f.debug_struct("AddAuthorization")
             .field("inner", &self.inner)
             .field("value", &self.value)
+            .field("digest", &self.digest)
             .field("if_not_present", &self.if_not_present)
             .finish()
     }
@@ -266,6 +303,7 @@ impl<S: Clone> Clone for AddAuthorization<S> {
         AddAuthorization {
             inner: self.inner.clone(),
             value: self.value.clone(),
+            digest: self.digest.clone(),
             if_not_present: self.if_not_present,
         }
     }
@@ -286,13 +324,91 @@ where
         ctx: Context<State>,
         mut req: Request<ReqBody>,
     ) -> Result<Self::Response, Self::Error> {
-        if let Some(value) = &self.value {
-            if !self.if_not_present || !req.headers().contains_key(http::header::AUTHORIZATION) {
+        if !self.if_not_present || !req.headers().contains_key(http::header::AUTHORIZATION) {
+            if let Some(value) = &self.value {
                 req.headers_mut()
                     .insert(http::header::AUTHORIZATION, value.clone());
+            } else if let Some(value) = self.digest.as_ref().and_then(|digest| digest.value(&req)) {
+                req.headers_mut().insert(http::header::AUTHORIZATION, value);
+            }
+        }
+
+        let res = self.inner.serve(ctx, req).await?;
+
+        if let Some(digest) = &self.digest {
+            if res.status() == StatusCode::UNAUTHORIZED {
+                digest.update(&res);
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Digest credentials shared between all clones of an [`AddAuthorizationLayer`],
+/// along with the last challenge received and its nonce count.
+struct DigestSession {
+    username: String,
+    password: String,
+    challenge: Mutex<Option<(DigestChallenge, u32)>>,
+}
+
+impl DigestSession {
+    fn value<Body>(&self, req: &Request<Body>) -> Option<HeaderValue> {
+        let mut challenge = self
+            .challenge
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (challenge, nc) = challenge.as_mut()?;
+        *nc += 1;
+
+        let uri = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str().to_owned())
+            .unwrap_or_else(|| req.uri().to_string());
+        match Digest::respond(
+            challenge,
+            &self.username,
+            &self.password,
+            req.method().as_str(),
+            &uri,
+            *nc,
+        ) {
+            Ok(digest) => {
+                let mut value = digest.as_header_value();
+                value.set_sensitive(true);
+                Some(value)
+            }
+            Err(err) => {
+                tracing::debug!(?err, "failed to compute digest credentials");
+                None
             }
         }
-        self.inner.serve(ctx, req).await
+    }
+
+    fn update<Body>(&self, res: &Response<Body>) {
+        if let Some(challenge) = res
+            .headers()
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| DigestChallenge::try_from_header_str(value).ok())
+        {
+            *self
+                .challenge
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some((challenge, 0));
+        }
+    }
+}
+
+impl fmt::Debug for DigestSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestSession")
+            .field("username", &self.username)
+            .field("challenge", &self.challenge)
+            .finish()
     }
 }
 
@@ -361,6 +477,39 @@ mod tests {
         assert_eq!(res.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn digest() {
+        use rama_net::user::{Basic, auth::DigestAuthority};
+
+        // service that requires digest auth for all requests
+        let svc = ValidateRequestHeaderLayer::digest(DigestAuthority::new(
+            "test",
+            [Basic::new("foo", "bar")],
+        ))
+        .layer(service_fn(echo));
+
+        // make a client that adds auth
+        let client = AddAuthorization::digest(svc, "foo", "bar");
+
+        // first request receives the challenge
+        let res = client
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        for _ in 0..3 {
+            let res = client
+                .serve(
+                    Context::default(),
+                    Request::get("/foo?bar=baz").body(Body::empty()).unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+    }
+
     async fn echo<Body>(req: Request<Body>) -> Result<Response<Body>, BoxError> {
         Ok(Response::new(req.into_body()))
     }
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// The claims of a JSON Web Token, containing the registered claims
+/// defined in [RFC 7519] and all other (private) claims.
+///
+/// This is the default claims type inserted into the [`Context`] by the
+/// [`JwtAuthLayer`], use [`JwtAuthLayer::with_claims`] to decode the claims
+/// into your own type instead.
+///
+/// [RFC 7519]: https://datatracker.ietf.org/doc/html/rfc7519#section-4.1
+/// [`Context`]: rama_core::Context
+/// [`JwtAuthLayer`]: super::JwtAuthLayer
+/// [`JwtAuthLayer::with_claims`]: super::JwtAuthLayer::with_claims
+pub struct JwtClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Issuer
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Subject
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Audience
+    pub aud: Option<Audience>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Expiration time, in seconds since the unix epoch
+    pub exp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Not before, in seconds since the unix epoch
+    pub nbf: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Issued at, in seconds since the unix epoch
+    pub iat: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// JWT ID
+    pub jti: Option<String>,
+    #[serde(flatten)]
+    /// All other claims
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+/// The audience (`aud`) claim, which is either a single or multiple values.
+pub enum Audience {
+    /// A single audience.
+    Single(String),
+    /// Multiple audiences.
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    /// Returns true if the given audience is part of this claim.
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            Self::Single(aud) => aud == audience,
+            Self::Multiple(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
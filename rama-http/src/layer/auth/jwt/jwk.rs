@@ -0,0 +1,452 @@
+use aws_lc_rs::{hmac, signature};
+use base64::Engine as _;
+use rama_core::error::{ErrorContext, OpaqueError};
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
+
+const BASE64_URL: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Signature algorithm of a JSON Web Token, as defined in
+/// [RFC 7518](https://datatracker.ietf.org/doc/html/rfc7518#section-3.1)
+/// and [RFC 8037](https://datatracker.ietf.org/doc/html/rfc8037#section-3.1).
+pub enum JwtAlgorithm {
+    #[serde(rename = "HS256")]
+    /// HMAC using SHA-256
+    Hs256,
+    #[serde(rename = "HS384")]
+    /// HMAC using SHA-384
+    Hs384,
+    #[serde(rename = "HS512")]
+    /// HMAC using SHA-512
+    Hs512,
+    #[serde(rename = "RS256")]
+    /// RSASSA-PKCS1-v1_5 using SHA-256
+    Rs256,
+    #[serde(rename = "RS384")]
+    /// RSASSA-PKCS1-v1_5 using SHA-384
+    Rs384,
+    #[serde(rename = "RS512")]
+    /// RSASSA-PKCS1-v1_5 using SHA-512
+    Rs512,
+    #[serde(rename = "PS256")]
+    /// RSASSA-PSS using SHA-256 and MGF1 with SHA-256
+    Ps256,
+    #[serde(rename = "PS384")]
+    /// RSASSA-PSS using SHA-384 and MGF1 with SHA-384
+    Ps384,
+    #[serde(rename = "PS512")]
+    /// RSASSA-PSS using SHA-512 and MGF1 with SHA-512
+    Ps512,
+    #[serde(rename = "ES256")]
+    /// ECDSA using P-256 and SHA-256
+    Es256,
+    #[serde(rename = "ES384")]
+    /// ECDSA using P-384 and SHA-384
+    Es384,
+    #[serde(rename = "ES512")]
+    /// ECDSA using P-521 and SHA-512
+    Es512,
+    #[serde(rename = "EdDSA")]
+    /// EdDSA using Ed25519
+    EdDsa,
+}
+
+impl JwtAlgorithm {
+    /// All supported algorithms.
+    pub const ALL: [Self; 13] = [
+        Self::Hs256,
+        Self::Hs384,
+        Self::Hs512,
+        Self::Rs256,
+        Self::Rs384,
+        Self::Rs512,
+        Self::Ps256,
+        Self::Ps384,
+        Self::Ps512,
+        Self::Es256,
+        Self::Es384,
+        Self::Es512,
+        Self::EdDsa,
+    ];
+
+    /// Returns the name of this algorithm as used in the `alg` header parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hs256 => "HS256",
+            Self::Hs384 => "HS384",
+            Self::Hs512 => "HS512",
+            Self::Rs256 => "RS256",
+            Self::Rs384 => "RS384",
+            Self::Rs512 => "RS512",
+            Self::Ps256 => "PS256",
+            Self::Ps384 => "PS384",
+            Self::Ps512 => "PS512",
+            Self::Es256 => "ES256",
+            Self::Es384 => "ES384",
+            Self::Es512 => "ES512",
+            Self::EdDsa => "EdDSA",
+        }
+    }
+}
+
+impl fmt::Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// A JSON Web Key, as defined in [RFC 7517], used to verify the signature of a JSON Web Token.
+///
+/// Supported are symmetric (`oct`), `RSA`, `EC` (P-256, P-384 and P-521)
+/// and `OKP` (Ed25519) keys. Keys of other types can be parsed as part
+/// of a [`JwkSet`], but are never used.
+///
+/// [RFC 7517]: https://datatracker.ietf.org/doc/html/rfc7517
+pub struct Jwk {
+    kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alg: Option<String>,
+    #[serde(default, rename = "use", skip_serializing_if = "Option::is_none")]
+    use_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    k: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    e: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<String>,
+}
+
+impl Jwk {
+    /// Create a symmetric key, used for the `HS*` algorithms.
+    pub fn hmac(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            kty: "oct".to_owned(),
+            k: Some(BASE64_URL.encode(secret)),
+            ..Default::default()
+        }
+    }
+
+    /// Create a RSA public key from its (big-endian) modulus and exponent,
+    /// used for the `RS*` and `PS*` algorithms.
+    pub fn rsa(n: impl AsRef<[u8]>, e: impl AsRef<[u8]>) -> Self {
+        Self {
+            kty: "RSA".to_owned(),
+            n: Some(BASE64_URL.encode(n)),
+            e: Some(BASE64_URL.encode(e)),
+            ..Default::default()
+        }
+    }
+
+    /// Create an elliptic curve public key from its uncompressed point (`0x04 || x || y`),
+    /// used for the `ES256` (P-256), `ES384` (P-384) and `ES512` (P-521) algorithms.
+    pub fn ec(point: impl AsRef<[u8]>) -> Result<Self, OpaqueError> {
+        let point = point.as_ref();
+        let crv = match point.len() {
+            65 => "P-256",
+            97 => "P-384",
+            133 => "P-521",
+            _ => return Err(OpaqueError::from_display("unsupported EC point length")),
+        };
+        if point[0] != 0x04 {
+            return Err(OpaqueError::from_display("EC point is not uncompressed"));
+        }
+        let (x, y) = point[1..].split_at((point.len() - 1) / 2);
+        Ok(Self {
+            kty: "EC".to_owned(),
+            crv: Some(crv.to_owned()),
+            x: Some(BASE64_URL.encode(x)),
+            y: Some(BASE64_URL.encode(y)),
+            ..Default::default()
+        })
+    }
+
+    /// Create an Ed25519 public key, used for the `EdDSA` algorithm.
+    pub fn ed25519(public_key: impl AsRef<[u8]>) -> Self {
+        Self {
+            kty: "OKP".to_owned(),
+            crv: Some("Ed25519".to_owned()),
+            x: Some(BASE64_URL.encode(public_key)),
+            ..Default::default()
+        }
+    }
+
+    /// Set the key id (`kid`) of this key.
+    pub fn with_kid(mut self, kid: impl Into<String>) -> Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Set the key id (`kid`) of this key.
+    pub fn set_kid(&mut self, kid: impl Into<String>) -> &mut Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Restrict this key to be used only for the given algorithm.
+    pub fn with_alg(mut self, alg: JwtAlgorithm) -> Self {
+        self.alg = Some(alg.as_str().to_owned());
+        self
+    }
+
+    /// Restrict this key to be used only for the given algorithm.
+    pub fn set_alg(&mut self, alg: JwtAlgorithm) -> &mut Self {
+        self.alg = Some(alg.as_str().to_owned());
+        self
+    }
+
+    /// Returns the key type (`kty`) of this key.
+    pub fn kty(&self) -> &str {
+        &self.kty
+    }
+
+    /// Returns the key id (`kid`) of this key, if any.
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// Returns the algorithm (`alg`) this key is restricted to, if any.
+    pub fn alg(&self) -> Option<&str> {
+        self.alg.as_deref()
+    }
+
+    /// Returns true if this key can be used to verify signatures of the given algorithm.
+    pub fn supports(&self, alg: JwtAlgorithm) -> bool {
+        if self.alg.as_deref().is_some_and(|a| a != alg.as_str())
+            || self.use_.as_deref().is_some_and(|u| u != "sig")
+        {
+            return false;
+        }
+        match alg {
+            JwtAlgorithm::Hs256 | JwtAlgorithm::Hs384 | JwtAlgorithm::Hs512 => self.kty == "oct",
+            JwtAlgorithm::Rs256
+            | JwtAlgorithm::Rs384
+            | JwtAlgorithm::Rs512
+            | JwtAlgorithm::Ps256
+            | JwtAlgorithm::Ps384
+            | JwtAlgorithm::Ps512 => self.kty == "RSA",
+            JwtAlgorithm::Es256 => self.kty == "EC" && self.crv.as_deref() == Some("P-256"),
+            JwtAlgorithm::Es384 => self.kty == "EC" && self.crv.as_deref() == Some("P-384"),
+            JwtAlgorithm::Es512 => self.kty == "EC" && self.crv.as_deref() == Some("P-521"),
+            JwtAlgorithm::EdDsa => self.kty == "OKP" && self.crv.as_deref() == Some("Ed25519"),
+        }
+    }
+
+    /// Verify the signature of the given message using this key.
+    pub fn verify(
+        &self,
+        alg: JwtAlgorithm,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), OpaqueError> {
+        if !self.supports(alg) {
+            return Err(OpaqueError::from_display(
+                "jwk cannot be used for the jwt algorithm",
+            ));
+        }
+
+        let verified = match alg {
+            JwtAlgorithm::Hs256 | JwtAlgorithm::Hs384 | JwtAlgorithm::Hs512 => {
+                let algorithm = match alg {
+                    JwtAlgorithm::Hs256 => hmac::HMAC_SHA256,
+                    JwtAlgorithm::Hs384 => hmac::HMAC_SHA384,
+                    _ => hmac::HMAC_SHA512,
+                };
+                let key = hmac::Key::new(algorithm, &decode_param(&self.k, "k")?);
+                hmac::verify(&key, message, signature)
+            }
+            JwtAlgorithm::Rs256
+            | JwtAlgorithm::Rs384
+            | JwtAlgorithm::Rs512
+            | JwtAlgorithm::Ps256
+            | JwtAlgorithm::Ps384
+            | JwtAlgorithm::Ps512 => {
+                let params = match alg {
+                    JwtAlgorithm::Rs256 => &signature::RSA_PKCS1_2048_8192_SHA256,
+                    JwtAlgorithm::Rs384 => &signature::RSA_PKCS1_2048_8192_SHA384,
+                    JwtAlgorithm::Rs512 => &signature::RSA_PKCS1_2048_8192_SHA512,
+                    JwtAlgorithm::Ps256 => &signature::RSA_PSS_2048_8192_SHA256,
+                    JwtAlgorithm::Ps384 => &signature::RSA_PSS_2048_8192_SHA384,
+                    _ => &signature::RSA_PSS_2048_8192_SHA512,
+                };
+                let n = decode_param(&self.n, "n")?;
+                let e = decode_param(&self.e, "e")?;
+                signature::RsaPublicKeyComponents {
+                    n: trim_leading_zeros(&n),
+                    e: trim_leading_zeros(&e),
+                }
+                .verify(params, message, signature)
+            }
+            JwtAlgorithm::Es256 | JwtAlgorithm::Es384 | JwtAlgorithm::Es512 => {
+                let (algorithm, len) = match alg {
+                    JwtAlgorithm::Es256 => (&signature::ECDSA_P256_SHA256_FIXED, 32),
+                    JwtAlgorithm::Es384 => (&signature::ECDSA_P384_SHA384_FIXED, 48),
+                    _ => (&signature::ECDSA_P521_SHA512_FIXED, 66),
+                };
+                let x = decode_param(&self.x, "x")?;
+                let y = decode_param(&self.y, "y")?;
+                if x.len() != len || y.len() != len {
+                    return Err(OpaqueError::from_display(
+                        "invalid EC jwk coordinate length",
+                    ));
+                }
+                let mut point = Vec::with_capacity(1 + 2 * len);
+                point.push(0x04);
+                point.extend_from_slice(&x);
+                point.extend_from_slice(&y);
+                signature::UnparsedPublicKey::new(algorithm, point).verify(message, signature)
+            }
+            JwtAlgorithm::EdDsa => {
+                let x = decode_param(&self.x, "x")?;
+                signature::UnparsedPublicKey::new(&signature::ED25519, x).verify(message, signature)
+            }
+        };
+
+        verified.map_err(|_| OpaqueError::from_display("invalid jwt signature"))
+    }
+}
+
+fn decode_param(value: &Option<String>, name: &'static str) -> Result<Vec<u8>, OpaqueError> {
+    let value = value
+        .as_deref()
+        .ok_or_else(|| OpaqueError::from_display(format!("missing jwk parameter: {name}")))?;
+    BASE64_URL
+        .decode(value.trim_end_matches('='))
+        .with_context(|| format!("decode jwk parameter: {name}"))
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| *b != 0)
+        .unwrap_or(bytes.len().saturating_sub(1));
+    &bytes[start..]
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "JwkSetDef", into = "JwkSetDef")]
+/// A set of [`Jwk`]s, as defined in [RFC 7517].
+///
+/// Cloning a [`JwkSet`] is cheap, as the keys are shared.
+///
+/// [RFC 7517]: https://datatracker.ietf.org/doc/html/rfc7517#section-5
+pub struct JwkSet {
+    keys: Arc<Vec<Jwk>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JwkSetDef {
+    keys: Vec<Jwk>,
+}
+
+impl From<JwkSetDef> for JwkSet {
+    fn from(def: JwkSetDef) -> Self {
+        Self::new(def.keys)
+    }
+}
+
+impl From<JwkSet> for JwkSetDef {
+    fn from(set: JwkSet) -> Self {
+        Self {
+            keys: set.keys.as_ref().clone(),
+        }
+    }
+}
+
+impl JwkSet {
+    /// Create a new [`JwkSet`] from the given keys.
+    pub fn new(keys: impl IntoIterator<Item = Jwk>) -> Self {
+        Self {
+            keys: Arc::new(keys.into_iter().collect()),
+        }
+    }
+
+    /// Try to parse a [`JwkSet`] from its JSON representation (`{"keys": [...]}`).
+    pub fn try_from_json(json: &str) -> Result<Self, OpaqueError> {
+        serde_json::from_str(json).context("parse jwk set from json")
+    }
+
+    /// Returns the keys of this set.
+    pub fn keys(&self) -> &[Jwk] {
+        &self.keys
+    }
+
+    /// Returns the key with the given key id, if any.
+    pub fn find(&self, kid: &str) -> Option<&Jwk> {
+        self.keys.iter().find(|key| key.kid() == Some(kid))
+    }
+}
+
+impl FromIterator<Jwk> for JwkSet {
+    fn from_iter<T: IntoIterator<Item = Jwk>>(iter: T) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl From<Jwk> for JwkSet {
+    fn from(key: Jwk) -> Self {
+        Self::new([key])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jwk_set_from_json() {
+        let set = JwkSet::try_from_json(
+            r#"{"keys":[
+                {"kty":"oct","kid":"a","k":"c2VjcmV0","alg":"HS256"},
+                {"kty":"RSA","kid":"b","use":"enc","n":"AQAB","e":"AQAB","alg":"RSA-OAEP"},
+                {"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(set.keys().len(), 3);
+
+        let key = set.find("a").unwrap();
+        assert!(key.supports(JwtAlgorithm::Hs256));
+        assert!(!key.supports(JwtAlgorithm::Hs512));
+        assert!(!key.supports(JwtAlgorithm::Rs256));
+
+        let key = set.find("b").unwrap();
+        assert!(!key.supports(JwtAlgorithm::Rs256));
+
+        assert!(set.keys()[2].supports(JwtAlgorithm::EdDsa));
+        assert!(set.find("c").is_none());
+
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(JwkSet::try_from_json(&json).unwrap(), set);
+    }
+
+    #[test]
+    fn jwk_hmac_verify() {
+        let key = Jwk::hmac("secret");
+        let tag = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA384, b"secret"),
+            b"header.payload",
+        );
+        assert!(
+            key.verify(JwtAlgorithm::Hs384, b"header.payload", tag.as_ref())
+                .is_ok()
+        );
+        assert!(
+            key.verify(JwtAlgorithm::Hs256, b"header.payload", tag.as_ref())
+                .is_err()
+        );
+        assert!(
+            key.verify(JwtAlgorithm::Hs384, b"header.other", tag.as_ref())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,908 @@
+//! Middleware that authorizes requests using a JSON Web Token (JWT) bearer token.
+//!
+//! The [`JwtAuthLayer`] validates the token found in the `Authorization: Bearer <token>` header:
+//!
+//! - the signature, using the keys of a [`JwkSource`], supporting the
+//!   `HS*`, `RS*`, `PS*`, `ES*` and `EdDSA` algorithms;
+//! - the `exp` (required by default), `nbf` and `iat` claims, allowing some leeway;
+//! - the `iss` and `aud` claims, if configured;
+//! - the presence of all required claims.
+//!
+//! The keys can be a static [`JwkSet`], or be (re)loaded from a [`JwksFile`]
+//! or a [`JwksEndpoint`], which cache the keys and pick up rotated keys.
+//!
+//! Once validated the claims are inserted into the [`Context`], as [`JwtClaims`]
+//! or your own type (see [`JwtAuthLayer::with_claims`]), together with a [`UserId`]
+//! (the `sub` claim if present) so that downstream layers can use them.
+//!
+//! Requests without (valid) token are responded to with a `401 Unauthorized` response.
+//!
+//! [`Context`]: rama_core::Context
+//! [`UserId`]: rama_net::user::UserId
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, error::BoxError, service::service_fn};
+//! use rama_http::layer::auth::jwt::{Jwk, JwkSet, JwtAlgorithm, JwtAuthLayer, JwtClaims};
+//! use rama_http::{Body, Request, Response, StatusCode};
+//! use rama_net::user::UserId;
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), BoxError> {
+//! let keys = JwkSet::new([Jwk::hmac("secret")
+//!     .with_kid("2025-01")
+//!     .with_alg(JwtAlgorithm::Hs256)]);
+//!
+//! let service = JwtAuthLayer::new(keys)
+//!     .with_issuer("https://auth.example.com")
+//!     .with_audience("api")
+//!     .layer(service_fn(async |ctx: Context<()>, _req: Request| {
+//!         let claims: &JwtClaims = ctx.get().unwrap();
+//!         let user: &UserId = ctx.get().unwrap();
+//!         assert_eq!(Some(user), claims.sub.clone().map(UserId::Username).as_ref());
+//!         Ok::<_, Infallible>(Response::new(Body::empty()))
+//!     }));
+//!
+//! let response = service
+//!     .serve(Context::default(), Request::new(Body::empty()))
+//!     .await?;
+//! assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{HeaderValue, Request, Response, StatusCode, header};
+use base64::Engine as _;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_core::{Context, Layer, Service};
+use rama_net::user::UserId;
+use rama_utils::macros::define_inner_service_accessors;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod claims;
+mod jwk;
+mod source;
+
+#[doc(inline)]
+pub use claims::{Audience, JwtClaims};
+#[doc(inline)]
+pub use jwk::{Jwk, JwkSet, JwtAlgorithm};
+#[doc(inline)]
+pub use source::{JwkSource, JwksEndpoint, JwksFile};
+
+const BASE64_URL: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+/// Validation rules applied by the [`JwtAuthService`].
+#[derive(Debug, Clone)]
+struct Validation {
+    algorithms: Vec<JwtAlgorithm>,
+    leeway: Duration,
+    issuers: Vec<String>,
+    audiences: Vec<String>,
+    required_claims: Vec<String>,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            algorithms: JwtAlgorithm::ALL.to_vec(),
+            leeway: Duration::from_secs(60),
+            issuers: Vec::new(),
+            audiences: Vec::new(),
+            required_claims: vec!["exp".to_owned()],
+        }
+    }
+}
+
+/// A [`Layer`] that authorizes requests using a JSON Web Token bearer token.
+///
+/// See the [module docs](self) for more information.
+pub struct JwtAuthLayer<K, C = JwtClaims> {
+    source: Arc<K>,
+    validation: Validation,
+    allow_anonymous: bool,
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<K: fmt::Debug, C> fmt::Debug for JwtAuthLayer<K, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuthLayer")
+            .field("source", &self.source)
+            .field("validation", &self.validation)
+            .field("allow_anonymous", &self.allow_anonymous)
+            .field(
+                "_phantom",
+                &format_args!("{}", std::any::type_name::<fn() -> C>()),
+            )
+            .finish()
+    }
+}
+
+impl<K, C> Clone for JwtAuthLayer<K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            validation: self.validation.clone(),
+            allow_anonymous: self.allow_anonymous,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K> JwtAuthLayer<K> {
+    /// Create a new [`JwtAuthLayer`] verifying tokens using the keys of the given [`JwkSource`].
+    pub fn new(source: K) -> Self {
+        Self {
+            source: Arc::new(source),
+            validation: Validation::default(),
+            allow_anonymous: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K, C> JwtAuthLayer<K, C> {
+    /// Decode the validated claims into the given type,
+    /// instead of the default [`JwtClaims`].
+    pub fn with_claims<C2>(self) -> JwtAuthLayer<K, C2> {
+        JwtAuthLayer {
+            source: self.source,
+            validation: self.validation,
+            allow_anonymous: self.allow_anonymous,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Only accept tokens signed using one of the given algorithms.
+    ///
+    /// By default all supported algorithms are accepted, as long as the
+    /// algorithm is supported by the key used to verify the token.
+    pub fn with_algorithms(mut self, algorithms: impl IntoIterator<Item = JwtAlgorithm>) -> Self {
+        self.validation.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Only accept tokens signed using one of the given algorithms.
+    ///
+    /// By default all supported algorithms are accepted, as long as the
+    /// algorithm is supported by the key used to verify the token.
+    pub fn set_algorithms(
+        &mut self,
+        algorithms: impl IntoIterator<Item = JwtAlgorithm>,
+    ) -> &mut Self {
+        self.validation.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Set the leeway allowed for the time based claims (`exp`, `nbf` and `iat`),
+    /// to account for clock skew. Defaults to 60 seconds.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.validation.leeway = leeway;
+        self
+    }
+
+    /// Set the leeway allowed for the time based claims (`exp`, `nbf` and `iat`),
+    /// to account for clock skew. Defaults to 60 seconds.
+    pub fn set_leeway(&mut self, leeway: Duration) -> &mut Self {
+        self.validation.leeway = leeway;
+        self
+    }
+
+    /// Accept tokens issued (`iss`) by the given issuer.
+    ///
+    /// Can be called multiple times to accept multiple issuers.
+    /// Tokens of any (or no) issuer are accepted if no issuer is set.
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.validation.issuers.push(issuer.into());
+        self
+    }
+
+    /// Accept tokens issued (`iss`) by the given issuer.
+    ///
+    /// Can be called multiple times to accept multiple issuers.
+    /// Tokens of any (or no) issuer are accepted if no issuer is set.
+    pub fn set_issuer(&mut self, issuer: impl Into<String>) -> &mut Self {
+        self.validation.issuers.push(issuer.into());
+        self
+    }
+
+    /// Accept tokens intended for (`aud`) the given audience.
+    ///
+    /// Can be called multiple times to accept multiple audiences.
+    /// Tokens for any (or no) audience are accepted if no audience is set.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.validation.audiences.push(audience.into());
+        self
+    }
+
+    /// Accept tokens intended for (`aud`) the given audience.
+    ///
+    /// Can be called multiple times to accept multiple audiences.
+    /// Tokens for any (or no) audience are accepted if no audience is set.
+    pub fn set_audience(&mut self, audience: impl Into<String>) -> &mut Self {
+        self.validation.audiences.push(audience.into());
+        self
+    }
+
+    /// Require the given claim to be present in the token.
+    ///
+    /// By default only the `exp` claim is required.
+    pub fn with_required_claim(mut self, claim: impl Into<String>) -> Self {
+        self.validation.required_claims.push(claim.into());
+        self
+    }
+
+    /// Require the given claim to be present in the token.
+    ///
+    /// By default only the `exp` claim is required.
+    pub fn set_required_claim(&mut self, claim: impl Into<String>) -> &mut Self {
+        self.validation.required_claims.push(claim.into());
+        self
+    }
+
+    /// Set the claims required to be present in the token,
+    /// replacing the default (`exp`) and previously required claims.
+    pub fn with_required_claims(mut self, claims: impl IntoIterator<Item = String>) -> Self {
+        self.validation.required_claims = claims.into_iter().collect();
+        self
+    }
+
+    /// Set the claims required to be present in the token,
+    /// replacing the default (`exp`) and previously required claims.
+    pub fn set_required_claims(&mut self, claims: impl IntoIterator<Item = String>) -> &mut Self {
+        self.validation.required_claims = claims.into_iter().collect();
+        self
+    }
+
+    /// Allow requests without token, inserting [`UserId::Anonymous`] into the [`Context`].
+    ///
+    /// Requests with an invalid token are still rejected.
+    pub fn with_allow_anonymous(mut self, allow_anonymous: bool) -> Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+
+    /// Allow requests without token, inserting [`UserId::Anonymous`] into the [`Context`].
+    ///
+    /// Requests with an invalid token are still rejected.
+    pub fn set_allow_anonymous(&mut self, allow_anonymous: bool) -> &mut Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+}
+
+impl<K, C, S> Layer<S> for JwtAuthLayer<K, C> {
+    type Service = JwtAuthService<S, K, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtAuthService {
+            inner,
+            source: self.source.clone(),
+            validation: Arc::new(self.validation.clone()),
+            allow_anonymous: self.allow_anonymous,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// A [`Service`] that authorizes requests using a JSON Web Token bearer token.
+///
+/// See the [module docs](self) for more information.
+pub struct JwtAuthService<S, K, C = JwtClaims> {
+    inner: S,
+    source: Arc<K>,
+    validation: Arc<Validation>,
+    allow_anonymous: bool,
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<S, K, C> JwtAuthService<S, K, C> {
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, K: fmt::Debug, C> fmt::Debug for JwtAuthService<S, K, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuthService")
+            .field("inner", &self.inner)
+            .field("source", &self.source)
+            .field("validation", &self.validation)
+            .field("allow_anonymous", &self.allow_anonymous)
+            .field(
+                "_phantom",
+                &format_args!("{}", std::any::type_name::<fn() -> C>()),
+            )
+            .finish()
+    }
+}
+
+impl<S: Clone, K, C> Clone for JwtAuthService<S, K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            source: self.source.clone(),
+            validation: self.validation.clone(),
+            allow_anonymous: self.allow_anonymous,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Debug)]
+enum JwtError {
+    /// The token is malformed, expired or otherwise invalid.
+    Invalid(OpaqueError),
+    /// The keys could not be obtained from the [`JwkSource`].
+    Keys(OpaqueError),
+}
+
+impl<S, K, C> JwtAuthService<S, K, C>
+where
+    K: JwkSource,
+    C: DeserializeOwned,
+{
+    async fn validate(&self, token: &str) -> Result<(C, Option<String>), JwtError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(JwtError::Invalid(OpaqueError::from_display(
+                "jwt does not consist of 3 parts",
+            )));
+        };
+
+        let header: JwtHeader = decode_part(header)
+            .and_then(|header| {
+                serde_json::from_slice(&header).context("deserialize jwt header as json")
+            })
+            .map_err(JwtError::Invalid)?;
+        let alg = self
+            .validation
+            .algorithms
+            .iter()
+            .copied()
+            .find(|alg| alg.as_str() == header.alg)
+            .ok_or_else(|| {
+                JwtError::Invalid(OpaqueError::from_display(format!(
+                    "jwt algorithm is not accepted: {}",
+                    header.alg
+                )))
+            })?;
+
+        let signature = decode_part(signature).map_err(JwtError::Invalid)?;
+        let message = &token.as_bytes()[..header_payload_len(token)];
+        self.verify_signature(alg, header.kid.as_deref(), message, &signature)
+            .await?;
+
+        let claims: serde_json::Map<String, serde_json::Value> = decode_part(payload)
+            .and_then(|payload| {
+                serde_json::from_slice(&payload).context("deserialize jwt claims as json object")
+            })
+            .map_err(JwtError::Invalid)?;
+        self.validate_claims(&claims).map_err(JwtError::Invalid)?;
+
+        let sub = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .map(ToOwned::to_owned);
+        let claims = serde_json::from_value(serde_json::Value::Object(claims))
+            .context("deserialize jwt claims")
+            .map_err(JwtError::Invalid)?;
+        Ok((claims, sub))
+    }
+
+    async fn verify_signature(
+        &self,
+        alg: JwtAlgorithm,
+        kid: Option<&str>,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), JwtError> {
+        for refresh in [false, true] {
+            let set = self.source.jwk_set(refresh).await.map_err(JwtError::Keys)?;
+
+            let verified = match kid {
+                Some(kid) => set
+                    .find(kid)
+                    .map(|key| key.verify(alg, message, signature).is_ok()),
+                None => {
+                    let mut keys = set.keys().iter().filter(|key| key.supports(alg)).peekable();
+                    if keys.peek().is_none() {
+                        None
+                    } else {
+                        Some(keys.any(|key| key.verify(alg, message, signature).is_ok()))
+                    }
+                }
+            };
+
+            match verified {
+                Some(true) => return Ok(()),
+                Some(false) => {
+                    return Err(JwtError::Invalid(OpaqueError::from_display(
+                        "invalid jwt signature",
+                    )));
+                }
+                // no matching key found: refresh the keys once, as they might have been rotated
+                None => (),
+            }
+        }
+
+        Err(JwtError::Invalid(OpaqueError::from_display(
+            "no key found to verify jwt",
+        )))
+    }
+
+    fn validate_claims(
+        &self,
+        claims: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), OpaqueError> {
+        let validation = &self.validation;
+
+        if let Some(claim) = validation
+            .required_claims
+            .iter()
+            .find(|claim| !claims.contains_key(claim.as_str()))
+        {
+            return Err(OpaqueError::from_display(format!(
+                "jwt misses required claim: {claim}"
+            )));
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let leeway = validation.leeway.as_secs_f64();
+        let time_claim = |name: &str| -> Result<Option<f64>, OpaqueError> {
+            match claims.get(name) {
+                None => Ok(None),
+                Some(value) => value.as_f64().map(Some).ok_or_else(|| {
+                    OpaqueError::from_display(format!("jwt claim {name} is not a number"))
+                }),
+            }
+        };
+
+        if time_claim("exp")?.is_some_and(|exp| now > exp + leeway) {
+            return Err(OpaqueError::from_display("jwt is expired"));
+        }
+        if time_claim("nbf")?.is_some_and(|nbf| now + leeway < nbf) {
+            return Err(OpaqueError::from_display("jwt is not yet valid"));
+        }
+        if time_claim("iat")?.is_some_and(|iat| now + leeway < iat) {
+            return Err(OpaqueError::from_display("jwt is issued in the future"));
+        }
+
+        if !validation.issuers.is_empty() {
+            let iss = claims.get("iss").and_then(|iss| iss.as_str());
+            if !iss.is_some_and(|iss| validation.issuers.iter().any(|i| i == iss)) {
+                return Err(OpaqueError::from_display("jwt issuer is not accepted"));
+            }
+        }
+
+        if !validation.audiences.is_empty() {
+            let aud: Option<Audience> = claims
+                .get("aud")
+                .and_then(|aud| serde_json::from_value(aud.clone()).ok());
+            if !aud.is_some_and(|aud| validation.audiences.iter().any(|a| aud.contains(a))) {
+                return Err(OpaqueError::from_display("jwt audience is not accepted"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_part(part: &str) -> Result<Vec<u8>, OpaqueError> {
+    BASE64_URL.decode(part).context("decode base64url jwt part")
+}
+
+fn header_payload_len(token: &str) -> usize {
+    token.rfind('.').unwrap_or(token.len())
+}
+
+fn unauthorized<ResBody: Default>(error: Option<&str>) -> Response<ResBody> {
+    let challenge = match error {
+        Some(description) => HeaderValue::from_str(&format!(
+            "Bearer error=\"invalid_token\", error_description=\"{description}\""
+        ))
+        .unwrap_or_else(|_| HeaderValue::from_static("Bearer error=\"invalid_token\"")),
+        None => HeaderValue::from_static("Bearer"),
+    };
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, challenge)
+        .body(ResBody::default())
+        .unwrap()
+}
+
+impl<S, K, C, State, ReqBody, ResBody> Service<State, Request<ReqBody>> for JwtAuthService<S, K, C>
+where
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    K: JwkSource,
+    C: DeserializeOwned + Clone + Send + Sync + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                let (scheme, token) = value.split_once(' ')?;
+                scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+            });
+
+        let Some(token) = token else {
+            if self.allow_anonymous {
+                ctx.insert(UserId::Anonymous);
+                return self.inner.serve(ctx, req).await;
+            }
+            tracing::trace!("jwt auth: no bearer token found in request");
+            return Ok(unauthorized(None));
+        };
+
+        match self.validate(token).await {
+            Ok((claims, sub)) => {
+                ctx.insert(claims);
+                ctx.insert(match sub {
+                    Some(sub) => UserId::Username(sub),
+                    None => UserId::Token(token.as_bytes().to_vec()),
+                });
+                self.inner.serve(ctx, req).await
+            }
+            Err(JwtError::Invalid(err)) => {
+                tracing::debug!(?err, "jwt auth: invalid bearer token");
+                Ok(unauthorized(Some("invalid token")))
+            }
+            Err(JwtError::Keys(err)) => {
+                tracing::error!(?err, "jwt auth: failed to get keys to verify bearer token");
+                Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(ResBody::default())
+                    .unwrap())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use aws_lc_rs::{
+        hmac,
+        rand::SystemRandom,
+        rsa::KeySize,
+        signature::{self, KeyPair as _},
+    };
+    use rama_core::service::service_fn;
+    use serde_json::json;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn encode(header: serde_json::Value, claims: serde_json::Value) -> String {
+        format!(
+            "{}.{}",
+            BASE64_URL.encode(header.to_string()),
+            BASE64_URL.encode(claims.to_string())
+        )
+    }
+
+    fn sign_hs256(secret: &[u8], kid: &str, claims: serde_json::Value) -> String {
+        let message = encode(json!({"alg": "HS256", "typ": "JWT", "kid": kid}), claims);
+        let tag = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, secret),
+            message.as_bytes(),
+        );
+        format!("{message}.{}", BASE64_URL.encode(tag.as_ref()))
+    }
+
+    /// Parse the modulus and exponent from a DER encoded `RSAPublicKey`.
+    fn rsa_public_components(der: &[u8]) -> (&[u8], &[u8]) {
+        fn read_tlv(der: &[u8]) -> (&[u8], &[u8]) {
+            let (len, offset) = match der[1] {
+                len if len < 0x80 => (len as usize, 2),
+                len => {
+                    let n = (len & 0x7f) as usize;
+                    let len = der[2..2 + n]
+                        .iter()
+                        .fold(0, |acc, b| (acc << 8) | *b as usize);
+                    (len, 2 + n)
+                }
+            };
+            (&der[offset..offset + len], &der[offset + len..])
+        }
+        let (seq, _) = read_tlv(der);
+        let (n, rest) = read_tlv(seq);
+        let (e, _) = read_tlv(rest);
+        (n.strip_prefix(&[0]).unwrap_or(n), e)
+    }
+
+    fn valid_claims() -> serde_json::Value {
+        json!({
+            "iss": "https://auth.example.com",
+            "sub": "alice",
+            "aud": ["api", "web"],
+            "exp": now() + 60,
+            "iat": now(),
+            "scope": "read",
+        })
+    }
+
+    async fn serve<K: JwkSource>(
+        layer: JwtAuthLayer<K>,
+        token: Option<&str>,
+    ) -> (StatusCode, Option<JwtClaims>, Option<UserId>) {
+        let service = layer.layer(service_fn(async |ctx: Context<()>, _req: Request| {
+            let claims = ctx.get::<JwtClaims>().cloned();
+            let user = ctx.get::<UserId>().cloned();
+            let mut res = Response::new(Body::empty());
+            res.extensions_mut().insert((claims, user));
+            Ok::<_, Infallible>(res)
+        }));
+
+        let mut req = Request::builder();
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let res = service
+            .serve(Context::default(), req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (claims, user) = res
+            .extensions()
+            .get::<(Option<JwtClaims>, Option<UserId>)>()
+            .cloned()
+            .unwrap_or_default();
+        (res.status(), claims, user)
+    }
+
+    fn hmac_layer() -> JwtAuthLayer<JwkSet> {
+        JwtAuthLayer::new(JwkSet::new([Jwk::hmac("secret").with_kid("a")]))
+            .with_issuer("https://auth.example.com")
+            .with_audience("api")
+    }
+
+    #[tokio::test]
+    async fn test_jwt_hs256_valid() {
+        let token = sign_hs256(b"secret", "a", valid_claims());
+        let (status, claims, user) = serve(hmac_layer(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        let claims = claims.unwrap();
+        assert_eq!(claims.sub.as_deref(), Some("alice"));
+        assert!(claims.aud.unwrap().contains("web"));
+        assert_eq!(claims.extra.get("scope"), Some(&json!("read")));
+        assert_eq!(user, Some(UserId::Username("alice".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_missing_or_invalid() {
+        let (status, _, _) = serve(hmac_layer(), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _, user) = serve(hmac_layer().with_allow_anonymous(true), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(user, Some(UserId::Anonymous));
+
+        let mut claims = valid_claims();
+        let invalid_tokens = [
+            "not-a-jwt".to_owned(),
+            sign_hs256(b"other", "a", valid_claims()),
+            sign_hs256(b"secret", "unknown", valid_claims()),
+            {
+                claims["exp"] = json!(now() - 120);
+                sign_hs256(b"secret", "a", claims.clone())
+            },
+            {
+                claims["exp"] = json!(now() + 60);
+                claims["nbf"] = json!(now() + 120);
+                sign_hs256(b"secret", "a", claims.clone())
+            },
+            {
+                claims.as_object_mut().unwrap().remove("nbf");
+                claims["iss"] = json!("https://evil.example.com");
+                sign_hs256(b"secret", "a", claims.clone())
+            },
+            {
+                claims["iss"] = json!("https://auth.example.com");
+                claims["aud"] = json!("other");
+                sign_hs256(b"secret", "a", claims.clone())
+            },
+            {
+                claims["aud"] = json!("api");
+                claims.as_object_mut().unwrap().remove("exp");
+                sign_hs256(b"secret", "a", claims.clone())
+            },
+        ];
+        for token in invalid_tokens {
+            let (status, claims, _) = serve(hmac_layer(), Some(&token)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "token: {token}");
+            assert!(claims.is_none());
+        }
+
+        // expired within leeway is still accepted
+        claims["exp"] = json!(now() - 30);
+        let token = sign_hs256(b"secret", "a", claims.clone());
+        let (status, _, _) = serve(hmac_layer(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // only accept the configured algorithms
+        let (status, _, _) = serve(
+            hmac_layer().with_algorithms([JwtAlgorithm::Hs512]),
+            Some(&token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_jwt_asymmetric_algorithms() {
+        let rng = SystemRandom::new();
+
+        let ed25519 = signature::Ed25519KeyPair::generate().unwrap();
+        let ecdsa =
+            signature::EcdsaKeyPair::generate(&signature::ECDSA_P256_SHA256_FIXED_SIGNING).unwrap();
+        let ecdsa_p521 =
+            signature::EcdsaKeyPair::generate(&signature::ECDSA_P521_SHA512_FIXED_SIGNING).unwrap();
+        let rsa = signature::RsaKeyPair::generate(KeySize::Rsa2048).unwrap();
+        let (n, e) = rsa_public_components(rsa.public_key().as_ref());
+
+        let keys = JwkSet::new([
+            Jwk::ed25519(ed25519.public_key().as_ref()).with_kid("ed"),
+            Jwk::ec(ecdsa.public_key().as_ref()).unwrap().with_kid("ec"),
+            Jwk::ec(ecdsa_p521.public_key().as_ref())
+                .unwrap()
+                .with_kid("ec-p521"),
+            Jwk::rsa(n, e).with_kid("rsa"),
+        ]);
+
+        let claims = valid_claims();
+        let tokens = [
+            {
+                let message = encode(json!({"alg": "EdDSA", "kid": "ed"}), claims.clone());
+                let sig = ed25519.sign(message.as_bytes());
+                format!("{message}.{}", BASE64_URL.encode(sig.as_ref()))
+            },
+            {
+                let message = encode(json!({"alg": "ES256", "kid": "ec"}), claims.clone());
+                let sig = ecdsa.sign(&rng, message.as_bytes()).unwrap();
+                format!("{message}.{}", BASE64_URL.encode(sig.as_ref()))
+            },
+            {
+                let message = encode(json!({"alg": "ES512", "kid": "ec-p521"}), claims.clone());
+                let sig = ecdsa_p521.sign(&rng, message.as_bytes()).unwrap();
+                format!("{message}.{}", BASE64_URL.encode(sig.as_ref()))
+            },
+            {
+                // without kid all keys supporting the algorithm are tried
+                let message = encode(json!({"alg": "RS256"}), claims.clone());
+                let mut sig = vec![0; rsa.public_modulus_len()];
+                rsa.sign(
+                    &signature::RSA_PKCS1_SHA256,
+                    &rng,
+                    message.as_bytes(),
+                    &mut sig,
+                )
+                .unwrap();
+                format!("{message}.{}", BASE64_URL.encode(sig))
+            },
+        ];
+
+        for token in tokens {
+            let layer = JwtAuthLayer::new(keys.clone());
+            let (status, claims, _) = serve(layer, Some(&token)).await;
+            assert_eq!(status, StatusCode::OK, "token: {token}");
+            assert_eq!(claims.unwrap().sub.as_deref(), Some("alice"));
+
+            // tampered payload
+            let mut parts: Vec<_> = token.split('.').collect();
+            let payload =
+                BASE64_URL.encode(json!({"sub": "mallory", "exp": now() + 60}).to_string());
+            parts[1] = &payload;
+            let layer = JwtAuthLayer::new(keys.clone());
+            let (status, _, _) = serve(layer, Some(&parts.join("."))).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jwt_custom_claims_and_token_user() {
+        #[derive(Debug, Clone, Deserialize)]
+        struct Claims {
+            scope: String,
+        }
+
+        let service = hmac_layer().with_claims::<Claims>().layer(service_fn(
+            async |ctx: Context<()>, _req: Request| {
+                assert_eq!(ctx.get::<Claims>().unwrap().scope, "write");
+                assert!(matches!(ctx.get::<UserId>(), Some(UserId::Token(_))));
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            },
+        ));
+
+        let mut claims = valid_claims();
+        claims.as_object_mut().unwrap().remove("sub");
+        claims["scope"] = json!("write");
+        let token = sign_hs256(b"secret", "a", claims);
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, format!("bearer {token}"))
+            .body(Body::empty())
+            .unwrap();
+        let res = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_jwt_jwks_endpoint_key_rotation() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let client = service_fn({
+            let fetches = fetches.clone();
+            move |_ctx: Context<()>, req: Request| {
+                assert_eq!(req.uri().path(), "/.well-known/jwks.json");
+                let kid = match fetches.fetch_add(1, Ordering::SeqCst) {
+                    0 => "old",
+                    _ => "new",
+                };
+                let set = JwkSet::new([Jwk::hmac(kid).with_kid(kid)]);
+                std::future::ready(Ok::<_, Infallible>(Response::new(Body::from(
+                    serde_json::to_string(&set).unwrap(),
+                ))))
+            }
+        });
+        let endpoint = JwksEndpoint::new(
+            client,
+            "https://auth.example.com/.well-known/jwks.json"
+                .parse()
+                .unwrap(),
+        )
+        .with_min_refresh_interval(Duration::ZERO);
+        let layer = JwtAuthLayer::new(endpoint);
+
+        let token = sign_hs256(b"old", "old", valid_claims());
+        let (status, _, _) = serve(layer.clone(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = serve(layer.clone(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // unknown kid triggers a refresh, picking up the rotated key
+        let token = sign_hs256(b"new", "new", valid_claims());
+        let (status, _, _) = serve(layer.clone(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        let token = sign_hs256(b"old", "old", valid_claims());
+        let (status, _, _) = serve(layer, Some(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,274 @@
+use super::JwkSet;
+use crate::dep::http_body;
+use crate::{BodyExtractExt, Request, Response, Uri, header};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use rama_core::{Context, Service};
+use std::{
+    fmt,
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+/// A source of the [`JwkSet`] used to verify JSON Web Tokens.
+pub trait JwkSource: Send + Sync + 'static {
+    /// Returns the current [`JwkSet`].
+    ///
+    /// `refresh` is true when the token refers to a key not found in the set
+    /// returned earlier, in which case a source can reload its keys to pick up rotated keys.
+    fn jwk_set(
+        &self,
+        refresh: bool,
+    ) -> impl Future<Output = Result<JwkSet, OpaqueError>> + Send + '_;
+}
+
+impl JwkSource for JwkSet {
+    async fn jwk_set(&self, _refresh: bool) -> Result<JwkSet, OpaqueError> {
+        Ok(self.clone())
+    }
+}
+
+impl<T: JwkSource> JwkSource for Arc<T> {
+    fn jwk_set(
+        &self,
+        refresh: bool,
+    ) -> impl Future<Output = Result<JwkSet, OpaqueError>> + Send + '_ {
+        (**self).jwk_set(refresh)
+    }
+}
+
+/// Cache shared by the reloading [`JwkSource`]s.
+#[derive(Debug, Default)]
+struct JwkCache {
+    state: RwLock<Option<CacheState>>,
+}
+
+const DEFAULT_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+struct CacheState {
+    set: JwkSet,
+    loaded: Instant,
+    last_attempt: Instant,
+}
+
+impl JwkCache {
+    async fn get<F>(
+        &self,
+        ttl: Duration,
+        min_refresh_interval: Duration,
+        refresh: bool,
+        load: F,
+    ) -> Result<JwkSet, OpaqueError>
+    where
+        F: Future<Output = Result<JwkSet, OpaqueError>>,
+    {
+        let cached = self
+            .state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        if let Some(state) = &cached {
+            let expired = state.loaded.elapsed() >= ttl;
+            if !(expired || refresh) || state.last_attempt.elapsed() < min_refresh_interval {
+                return Ok(state.set.clone());
+            }
+        }
+
+        let now = Instant::now();
+        match load.await {
+            Ok(set) => {
+                *self.state.write().unwrap_or_else(PoisonError::into_inner) = Some(CacheState {
+                    set: set.clone(),
+                    loaded: now,
+                    last_attempt: now,
+                });
+                Ok(set)
+            }
+            Err(err) => match cached {
+                Some(mut state) => {
+                    tracing::warn!(?err, "failed to reload jwk set: keep using cached jwk set");
+                    state.last_attempt = now;
+                    let set = state.set.clone();
+                    *self.state.write().unwrap_or_else(PoisonError::into_inner) = Some(state);
+                    Ok(set)
+                }
+                None => Err(err),
+            },
+        }
+    }
+}
+
+/// A [`JwkSource`] which loads the [`JwkSet`] from a (JSON) file.
+///
+/// The file is reloaded once the cached keys are older than the ttl (1 minute by default),
+/// or when a token refers to an unknown key. The previously loaded keys are kept in use
+/// when reloading fails.
+///
+/// Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct JwksFile {
+    path: PathBuf,
+    ttl: Duration,
+    min_refresh_interval: Duration,
+    cache: Arc<JwkCache>,
+}
+
+impl JwksFile {
+    /// Create a new [`JwksFile`] loading the keys from the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ttl: Duration::from_secs(60),
+            min_refresh_interval: DEFAULT_MIN_REFRESH_INTERVAL,
+            cache: Default::default(),
+        }
+    }
+
+    /// Set the duration for which the loaded keys are used before reloading the file.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the duration for which the loaded keys are used before reloading the file.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the minimum duration between two (re)loads of the file, 10 seconds by default.
+    pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// Set the minimum duration between two (re)loads of the file, 10 seconds by default.
+    pub fn set_min_refresh_interval(&mut self, interval: Duration) -> &mut Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+}
+
+impl JwkSource for JwksFile {
+    async fn jwk_set(&self, refresh: bool) -> Result<JwkSet, OpaqueError> {
+        self.cache
+            .get(self.ttl, self.min_refresh_interval, refresh, async {
+                let json = tokio::fs::read_to_string(&self.path)
+                    .await
+                    .context("read jwks file")?;
+                JwkSet::try_from_json(&json)
+            })
+            .await
+    }
+}
+
+/// A [`JwkSource`] which fetches the [`JwkSet`] from a JWKS endpoint,
+/// using the given http client [`Service`].
+///
+/// The keys are fetched again once they are older than the ttl (5 minutes by default),
+/// or when a token refers to an unknown key. The previously fetched keys are kept in use
+/// when fetching fails.
+///
+/// Clones share the same cache.
+pub struct JwksEndpoint<S> {
+    client: Arc<S>,
+    uri: Uri,
+    ttl: Duration,
+    min_refresh_interval: Duration,
+    cache: Arc<JwkCache>,
+}
+
+impl<S> JwksEndpoint<S> {
+    /// Create a new [`JwksEndpoint`] fetching the keys from the given uri.
+    pub fn new(client: S, uri: Uri) -> Self {
+        Self {
+            client: Arc::new(client),
+            uri,
+            ttl: Duration::from_secs(300),
+            min_refresh_interval: DEFAULT_MIN_REFRESH_INTERVAL,
+            cache: Default::default(),
+        }
+    }
+
+    /// Set the duration for which the fetched keys are used before fetching them again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the duration for which the fetched keys are used before fetching them again.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the minimum duration between two fetches of the keys, 10 seconds by default.
+    pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// Set the minimum duration between two fetches of the keys, 10 seconds by default.
+    pub fn set_min_refresh_interval(&mut self, interval: Duration) -> &mut Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+}
+
+impl<S> Clone for JwksEndpoint<S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            uri: self.uri.clone(),
+            ttl: self.ttl,
+            min_refresh_interval: self.min_refresh_interval,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for JwksEndpoint<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwksEndpoint")
+            .field("client", &self.client)
+            .field("uri", &self.uri)
+            .field("ttl", &self.ttl)
+            .field("min_refresh_interval", &self.min_refresh_interval)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl<S, Body> JwkSource for JwksEndpoint<S>
+where
+    S: Service<(), Request, Response = Response<Body>, Error: Into<BoxError>>,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static,
+{
+    async fn jwk_set(&self, refresh: bool) -> Result<JwkSet, OpaqueError> {
+        self.cache
+            .get(self.ttl, self.min_refresh_interval, refresh, async {
+                let req = Request::get(self.uri.clone())
+                    .header(header::ACCEPT, "application/json")
+                    .body(crate::Body::empty())
+                    .context("build jwks request")?;
+                let res = self
+                    .client
+                    .serve(Context::default(), req)
+                    .await
+                    .map_err(|err| OpaqueError::from_boxed(err.into()))
+                    .context("fetch jwks")?;
+                if !res.status().is_success() {
+                    return Err(OpaqueError::from_display(format!(
+                        "fetch jwks: unexpected status code: {}",
+                        res.status()
+                    )));
+                }
+                let json = res.try_into_string().await.context("read jwks response")?;
+                JwkSet::try_from_json(&json)
+            })
+            .await
+    }
+}
@@ -4,6 +4,9 @@ pub mod add_authorization;
 pub mod async_require_authorization;
 pub mod require_authorization;
 
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
 #[doc(inline)]
 pub use self::{
     add_authorization::{AddAuthorization, AddAuthorizationLayer},
@@ -11,3 +14,7 @@ pub use self::{
         AsyncAuthorizeRequest, AsyncRequireAuthorization, AsyncRequireAuthorizationLayer,
     },
 };
+
+#[cfg(feature = "jwt")]
+#[doc(inline)]
+pub use self::jwt::{JwtAuthLayer, JwtAuthService};
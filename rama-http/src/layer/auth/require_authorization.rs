@@ -56,15 +56,17 @@ use crate::layer::validate_request::{
     ValidateRequest, ValidateRequestHeader, ValidateRequestHeaderLayer,
 };
 use crate::{
-    Request, Response, StatusCode,
+    Method, Request, Response, StatusCode, Uri,
     header::{self, HeaderValue},
 };
 use base64::Engine as _;
 use rama_core::Context;
 use std::{fmt, marker::PhantomData, sync::Arc};
 
-use rama_net::user::UserId;
-use sealed::AuthorizerSeal;
+use rama_net::user::{
+    self, UserId,
+    auth::{AuthoritySync, DigestAuthority},
+};
 
 const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
 
@@ -160,6 +162,36 @@ impl<ResBody> ValidateRequestHeaderLayer<AuthorizeContext<Bearer<ResBody>>> {
     }
 }
 
+impl<S, ResBody> ValidateRequestHeader<S, AuthorizeContext<Digest<ResBody>>> {
+    /// Authorize requests using [`Digest`] authentication, as defined in RFC 7616.
+    ///
+    /// The `Authorization` header is required to contain [`Digest`][user::Digest]
+    /// credentials answering a challenge of the given [`DigestAuthority`].
+    ///
+    /// Unauthorized requests are responded to with a fresh challenge.
+    pub fn digest(inner: S, authority: DigestAuthority) -> Self
+    where
+        ResBody: Default,
+    {
+        Self::custom(inner, AuthorizeContext::new(Digest::new(authority)))
+    }
+}
+
+impl<ResBody> ValidateRequestHeaderLayer<AuthorizeContext<Digest<ResBody>>> {
+    /// Authorize requests using [`Digest`] authentication, as defined in RFC 7616.
+    ///
+    /// The `Authorization` header is required to contain [`Digest`][user::Digest]
+    /// credentials answering a challenge of the given [`DigestAuthority`].
+    ///
+    /// Unauthorized requests are responded to with a fresh challenge.
+    pub fn digest(authority: DigestAuthority) -> Self
+    where
+        ResBody: Default,
+    {
+        Self::custom(AuthorizeContext::new(Digest::new(authority)))
+    }
+}
+
 /// Type that performs "bearer token" authorization.
 ///
 /// See [`ValidateRequestHeader::bearer`] for more details.
@@ -216,7 +248,13 @@ where
         request: Request<B>,
     ) -> Result<(Context<S>, Request<B>), Response<Self::ResponseBody>> {
         match request.headers().get(header::AUTHORIZATION) {
-            Some(header_value) if self.credential.is_valid(header_value) => Ok((ctx, request)),
+            Some(header_value)
+                if self
+                    .credential
+                    .is_valid(header_value, request.method(), request.uri()) =>
+            {
+                Ok((ctx, request))
+            }
             None if self.allow_anonymous => {
                 let mut ctx = ctx;
                 ctx.insert(UserId::Anonymous);
@@ -226,7 +264,8 @@ where
                 let mut res = Response::new(Self::ResponseBody::default());
                 *res.status_mut() = StatusCode::UNAUTHORIZED;
 
-                if let Some(www_auth) = C::www_authenticate_header() {
+                let header_value = request.headers().get(header::AUTHORIZATION);
+                if let Some(www_auth) = self.credential.challenge(header_value, request.method()) {
                     res.headers_mut().insert(header::WWW_AUTHENTICATE, www_auth);
                 } else {
                     res.headers_mut()
@@ -278,21 +317,71 @@ impl<ResBody> fmt::Debug for Basic<ResBody> {
     }
 }
 
+/// Type that performs digest authorization.
+///
+/// See [`ValidateRequestHeader::digest`] for more details.
+pub struct Digest<ResBody> {
+    authority: DigestAuthority,
+    _ty: PhantomData<fn() -> ResBody>,
+}
+
+impl<ResBody> Digest<ResBody> {
+    fn new(authority: DigestAuthority) -> Self
+    where
+        ResBody: Default,
+    {
+        Self {
+            authority,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<ResBody> Clone for Digest<ResBody> {
+    fn clone(&self) -> Self {
+        Self {
+            authority: self.authority.clone(),
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<ResBody> fmt::Debug for Digest<ResBody> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Digest")
+            .field("authority", &self.authority)
+            .finish()
+    }
+}
+
 // Private module with the actual implementation details
 mod sealed {
     use super::*;
 
     /// Private trait that contains the actual authorization logic
     pub(super) trait AuthorizerSeal: Send + Sync + 'static {
-        /// Check if the given header value is valid for this authorizer.
-        fn is_valid(&self, header_value: &HeaderValue) -> bool;
+        /// Check if the given header value is valid for this authorizer,
+        /// for a request with the given method and uri.
+        fn is_valid(&self, header_value: &HeaderValue, method: &Method, uri: &Uri) -> bool;
 
         /// Return the WWW-Authenticate header value if applicable.
         fn www_authenticate_header() -> Option<HeaderValue>;
+
+        /// Return the WWW-Authenticate header value for this authorizer if applicable,
+        /// given the rejected header value (if any) of the request with the given method,
+        /// defaulting to [`AuthorizerSeal::www_authenticate_header`].
+        fn challenge(
+            &self,
+            header_value: Option<&HeaderValue>,
+            method: &Method,
+        ) -> Option<HeaderValue> {
+            let _ = (header_value, method);
+            Self::www_authenticate_header()
+        }
     }
 
     impl<ResBody: Default + Send + 'static> AuthorizerSeal for Basic<ResBody> {
-        fn is_valid(&self, header_value: &HeaderValue) -> bool {
+        fn is_valid(&self, header_value: &HeaderValue, _method: &Method, _uri: &Uri) -> bool {
             header_value == &self.header_value
         }
 
@@ -302,7 +391,7 @@ mod sealed {
     }
 
     impl<ResBody: Default + Send + 'static> AuthorizerSeal for Bearer<ResBody> {
-        fn is_valid(&self, header_value: &HeaderValue) -> bool {
+        fn is_valid(&self, header_value: &HeaderValue, _method: &Method, _uri: &Uri) -> bool {
             header_value == &self.header_value
         }
 
@@ -311,43 +400,114 @@ mod sealed {
         }
     }
 
+    impl<ResBody: Default + Send + 'static> AuthorizerSeal for Digest<ResBody> {
+        fn is_valid(&self, header_value: &HeaderValue, method: &Method, uri: &Uri) -> bool {
+            let Some(credentials) = parse_digest(header_value) else {
+                return false;
+            };
+            AuthoritySync::<user::Digest>::authorized_request::<()>(
+                &self.authority,
+                &mut Default::default(),
+                &credentials,
+                method,
+                uri,
+            )
+        }
+
+        fn www_authenticate_header() -> Option<HeaderValue> {
+            None
+        }
+
+        fn challenge(
+            &self,
+            header_value: Option<&HeaderValue>,
+            method: &Method,
+        ) -> Option<HeaderValue> {
+            let credentials = header_value.and_then(parse_digest);
+            AuthoritySync::<user::Digest>::challenge::<()>(
+                &self.authority,
+                credentials.as_ref(),
+                method,
+            )
+        }
+    }
+
+    fn parse_digest(header_value: &HeaderValue) -> Option<user::Digest> {
+        header_value
+            .to_str()
+            .ok()
+            .and_then(|s| user::Digest::try_from_header_str(s).ok())
+    }
+
     impl<T, const N: usize> AuthorizerSeal for [T; N]
     where
         T: AuthorizerSeal,
     {
-        fn is_valid(&self, header_value: &HeaderValue) -> bool {
-            self.iter().any(|auth| auth.is_valid(header_value))
+        fn is_valid(&self, header_value: &HeaderValue, method: &Method, uri: &Uri) -> bool {
+            self.iter()
+                .any(|auth| auth.is_valid(header_value, method, uri))
         }
 
         fn www_authenticate_header() -> Option<HeaderValue> {
             T::www_authenticate_header()
         }
+
+        fn challenge(
+            &self,
+            header_value: Option<&HeaderValue>,
+            method: &Method,
+        ) -> Option<HeaderValue> {
+            self.first()
+                .map_or_else(T::www_authenticate_header, |auth| {
+                    auth.challenge(header_value, method)
+                })
+        }
     }
 
     impl<T> AuthorizerSeal for Vec<T>
     where
         T: AuthorizerSeal,
     {
-        fn is_valid(&self, header_value: &HeaderValue) -> bool {
-            self.iter().any(|auth| auth.is_valid(header_value))
+        fn is_valid(&self, header_value: &HeaderValue, method: &Method, uri: &Uri) -> bool {
+            self.iter()
+                .any(|auth| auth.is_valid(header_value, method, uri))
         }
 
         fn www_authenticate_header() -> Option<HeaderValue> {
             T::www_authenticate_header()
         }
+
+        fn challenge(
+            &self,
+            header_value: Option<&HeaderValue>,
+            method: &Method,
+        ) -> Option<HeaderValue> {
+            self.first()
+                .map_or_else(T::www_authenticate_header, |auth| {
+                    auth.challenge(header_value, method)
+                })
+        }
     }
 
     impl<T> AuthorizerSeal for Arc<T>
     where
         T: AuthorizerSeal,
     {
-        fn is_valid(&self, header_value: &HeaderValue) -> bool {
-            (**self).is_valid(header_value)
+        fn is_valid(&self, header_value: &HeaderValue, method: &Method, uri: &Uri) -> bool {
+            (**self).is_valid(header_value, method, uri)
         }
 
         fn www_authenticate_header() -> Option<HeaderValue> {
             T::www_authenticate_header()
         }
+
+        fn challenge(
+            &self,
+            header_value: Option<&HeaderValue>,
+            method: &Method,
+        ) -> Option<HeaderValue> {
+            (**self).challenge(header_value, method)
+        }
     }
 }
 
@@ -363,6 +523,9 @@ impl<ResBody: Default + Send + 'static> Authorizer for Basic<ResBody> {
 impl<ResBody: Default + Send + 'static> Authorizer for Bearer<ResBody> {
     type ResBody = ResBody;
 }
+impl<ResBody: Default + Send + 'static> Authorizer for Digest<ResBody> {
+    type ResBody = ResBody;
+}
 impl<T: Authorizer, const N: usize> Authorizer for [T; N] {
     type ResBody = T::ResBody;
 }
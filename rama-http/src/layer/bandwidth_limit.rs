@@ -0,0 +1,283 @@
+//! Throttle the bandwidth of request and response bodies.
+//!
+//! The request body is throttled by the read limit and the response body
+//! by the write limit of the [`BandwidthLimiter`] selected for the request.
+//! Limiters can be shared, e.g. per user, such that the bandwidth
+//! of all requests of that user is capped together.
+//!
+//! See [`rama_net::stream::layer::BandwidthLimitLayer`] in order
+//! to throttle (tunneled) streams instead.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_http::{Body, Request, Response};
+//! use rama_http::layer::bandwidth_limit::BandwidthLimitLayer;
+//! use rama_net::stream::layer::{BandwidthLimit, KeyedBandwidthLimiter};
+//! use rama_net::user::UserId;
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Layer, Service};
+//! use std::convert::Infallible;
+//!
+//! async fn handle(_: Request) -> Result<Response, Infallible> {
+//!     // ...
+//!     # Ok(Response::new(Body::default()))
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! // Limit the bodies of all requests of a user to 1MB/s in each direction
+//! let policy = KeyedBandwidthLimiter::<UserId>::new(
+//!     Some(BandwidthLimit::new(1024 * 1024)),
+//!     Some(BandwidthLimit::new(1024 * 1024)),
+//! );
+//! let svc = BandwidthLimitLayer::new(policy).layer(service_fn(handle));
+//!
+//! let mut ctx = Context::default();
+//! ctx.insert(UserId::Username("john".to_owned()));
+//! svc.serve(ctx, Request::new(Body::default())).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::dep::http_body::{self, Frame, SizeHint};
+use crate::{Request, Response};
+use bytes::Bytes;
+use pin_project_lite::pin_project;
+use rama_core::{Context, Layer, Service, error::BoxError};
+use rama_http_types::Body;
+use rama_net::stream::layer::{BandwidthLimiter, BandwidthLimiterPolicy, BandwidthThrottle};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Poll, ready},
+};
+
+/// Throttle the bandwidth of request and response bodies.
+///
+/// See the [module docs](crate::layer::bandwidth_limit) for an example.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimitLayer<P> {
+    policy: P,
+}
+
+impl<P> BandwidthLimitLayer<P> {
+    /// Create a new [`BandwidthLimitLayer`],
+    /// selecting the [`BandwidthLimiter`] using the given policy.
+    pub const fn new(policy: P) -> Self {
+        Self { policy }
+    }
+}
+
+impl<S, P: Clone> Layer<S> for BandwidthLimitLayer<P> {
+    type Service = BandwidthLimitService<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BandwidthLimitService::new(inner, self.policy.clone())
+    }
+}
+
+/// Throttle the bandwidth of request and response bodies.
+///
+/// The [`BandwidthLimiter`] is selected by the [`BandwidthLimiterPolicy`],
+/// falling back to a new unlimited limiter for this request only.
+/// It is inserted in the [`Context`], such that inner services can
+/// adjust its limits at runtime.
+///
+/// See the [module docs](crate::layer::bandwidth_limit) for an example.
+pub struct BandwidthLimitService<S, P> {
+    inner: S,
+    policy: P,
+}
+
+impl<S, P> BandwidthLimitService<S, P> {
+    /// Create a new [`BandwidthLimitService`].
+    pub const fn new(inner: S, policy: P) -> Self {
+        Self { inner, policy }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, P: fmt::Debug> fmt::Debug for BandwidthLimitService<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimitService")
+            .field("inner", &self.inner)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl<S: Clone, P: Clone> Clone for BandwidthLimitService<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl<S, P, State, ReqBody, ResBody> Service<State, Request<ReqBody>> for BandwidthLimitService<S, P>
+where
+    S: Service<State, Request<Body>, Response = Response<ResBody>>,
+    P: BandwidthLimiterPolicy<State>,
+    State: Clone + Send + Sync + 'static,
+    ReqBody: http_body::Body<Data = Bytes, Error: Into<BoxError>> + Send + Sync + 'static,
+    ResBody: http_body::Body<Data = Bytes, Error: Into<BoxError>> + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let limiter = self
+            .policy
+            .limiter(&ctx)
+            .unwrap_or_else(BandwidthLimiter::unlimited);
+        let req =
+            req.map(|body| Body::new(BandwidthLimitedBody::new(body, limiter.read_throttle())));
+        let write_throttle = limiter.write_throttle();
+        ctx.insert(limiter);
+
+        let res = self.inner.serve(ctx, req).await?;
+        Ok(res.map(|body| Body::new(BandwidthLimitedBody::new(body, write_throttle))))
+    }
+}
+
+pin_project! {
+    /// A body throttled by a [`BandwidthThrottle`].
+    ///
+    /// Data frames are split in case they exceed the available capacity.
+    pub struct BandwidthLimitedBody<B> {
+        #[pin]
+        inner: B,
+        throttle: BandwidthThrottle,
+        pending: Option<Bytes>,
+    }
+}
+
+impl<B: fmt::Debug> fmt::Debug for BandwidthLimitedBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimitedBody")
+            .field("inner", &self.inner)
+            .field("throttle", &self.throttle)
+            .field("pending", &self.pending.as_ref().map(Bytes::len))
+            .finish()
+    }
+}
+
+impl<B> BandwidthLimitedBody<B> {
+    /// Create a new [`BandwidthLimitedBody`].
+    pub fn new(inner: B, throttle: BandwidthThrottle) -> Self {
+        Self {
+            inner,
+            throttle,
+            pending: None,
+        }
+    }
+}
+
+impl<B> http_body::Body for BandwidthLimitedBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        loop {
+            if let Some(data) = this.pending.as_mut() {
+                let n = ready!(this.throttle.poll_capacity(cx, data.len()));
+                let chunk = if n < data.len() {
+                    data.split_to(n)
+                } else {
+                    this.pending.take().unwrap_or_default()
+                };
+                this.throttle.consume(chunk.len());
+                return Poll::Ready(Some(Ok(Frame::data(chunk))));
+            }
+
+            match ready!(this.inner.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) if !data.is_empty() => *this.pending = Some(data),
+                    Ok(data) => return Poll::Ready(Some(Ok(Frame::data(data)))),
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                other => return Poll::Ready(other),
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let pending = self.pending.as_ref().map(Bytes::len).unwrap_or_default() as u64;
+        let hint = self.inner.size_hint();
+        let mut size_hint = SizeHint::new();
+        size_hint.set_lower(hint.lower() + pending);
+        if let Some(upper) = hint.upper() {
+            size_hint.set_upper(upper + pending);
+        }
+        size_hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BodyExtractExt;
+    use rama_core::service::service_fn;
+    use rama_net::{
+        stream::layer::{BandwidthLimit, KeyedBandwidthLimiter},
+        user::UserId,
+    };
+    use std::convert::Infallible;
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth_limited_bodies() {
+        let policy = KeyedBandwidthLimiter::<UserId>::new(
+            Some(BandwidthLimit::new(4)),
+            Some(BandwidthLimit::new(2)),
+        );
+        let svc = BandwidthLimitLayer::new(policy).layer(service_fn(async |req: Request| {
+            let body = req.try_into_string().await.unwrap();
+            assert_eq!(body, "foobarbaz");
+            Ok::<_, Infallible>(Response::new(Body::from("hello")))
+        }));
+
+        let mut ctx = Context::default();
+        ctx.insert(UserId::Username("john".to_owned()));
+        let start = Instant::now();
+        let res = svc
+            .serve(ctx, Request::new(Body::from("foobarbaz")))
+            .await
+            .unwrap();
+        // 4 bytes of burst + 5 bytes at 4 bytes per second
+        assert_eq!(start.elapsed(), Duration::from_millis(1250));
+
+        let start = Instant::now();
+        assert_eq!(res.try_into_string().await.unwrap(), "hello");
+        // 2 bytes of burst + 3 bytes at 2 bytes per second
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+
+        // requests without a user are not limited
+        let start = Instant::now();
+        let res = svc
+            .serve(Context::default(), Request::new(Body::from("foobarbaz")))
+            .await
+            .unwrap();
+        assert_eq!(res.try_into_string().await.unwrap(), "hello");
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
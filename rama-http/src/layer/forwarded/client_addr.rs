@@ -0,0 +1,428 @@
+use crate::Request;
+use rama_core::{Context, Layer, Service, matcher::Matcher};
+use rama_net::forwarded::{Forwarded, ForwardedElement};
+use rama_net::stream::{ClientAddr, SocketInfo, dep::ipnet::IpNet};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// Decides which hops in the forwarding chain of a request are trusted proxies,
+/// as used by the [`ClientAddrLayer`].
+///
+/// Hops are numbered from right to left: hop `0` is the peer of the connection,
+/// hop `1` the last (right-most) element of the [`Forwarded`] chain, and so on.
+pub trait TrustedProxies<State>: Send + Sync + 'static {
+    /// Returns `true` if the given hop is a trusted proxy,
+    /// such that the element it appended to the [`Forwarded`] chain can be trusted.
+    fn is_trusted(&self, ctx: &Context<State>, hop: usize, addr: &ClientAddr) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// [`TrustedProxies`] trusting a fixed number of hops,
+/// e.g. `1` in case the service is only reachable via a single (load balancing) proxy.
+pub struct TrustedHops(pub usize);
+
+impl<State> TrustedProxies<State> for TrustedHops {
+    fn is_trusted(&self, _ctx: &Context<State>, hop: usize, _addr: &ClientAddr) -> bool {
+        hop < self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// [`TrustedProxies`] trusting the hops with an address in one of the given networks.
+pub struct TrustedNetworks(Vec<IpNet>);
+
+impl TrustedNetworks {
+    /// Create a new [`TrustedNetworks`] for the given networks.
+    pub fn new(networks: impl IntoIterator<Item = IpNet>) -> Self {
+        Self(networks.into_iter().collect())
+    }
+}
+
+impl<State> TrustedProxies<State> for TrustedNetworks {
+    fn is_trusted(&self, _ctx: &Context<State>, _hop: usize, addr: &ClientAddr) -> bool {
+        let ip = addr.ip();
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// [`TrustedProxies`] trusting the hops for which the [`SocketInfo`] is matched
+/// by the given [`Matcher`], e.g. a `SocketMatcher`.
+///
+/// The [`SocketInfo`] of hops which did not forward their port have port `0`.
+pub struct TrustedMatcher<M>(pub M);
+
+impl<State, M> TrustedProxies<State> for TrustedMatcher<M>
+where
+    M: Matcher<State, SocketInfo>,
+{
+    fn is_trusted(&self, ctx: &Context<State>, _hop: usize, addr: &ClientAddr) -> bool {
+        let peer_addr = addr.socket_addr().unwrap_or_else(|| (addr.ip(), 0).into());
+        self.0.matches(None, ctx, &SocketInfo::new(None, peer_addr))
+    }
+}
+
+/// Layer to resolve the [`ClientAddr`] of a request
+/// using the [`Forwarded`] chain and the [`TrustedProxies`].
+///
+/// The chain is walked from right to left, starting at the peer of the connection,
+/// for as long as the hop is a trusted proxy. The address forwarded by the
+/// last trusted proxy is the [`ClientAddr`], and the elements left of it
+/// are removed from the [`Forwarded`] chain, as these were appended by
+/// untrusted parties (e.g. a spoofed `X-Forwarded-For` header).
+/// In case the peer is not trusted, the [`Forwarded`] chain is removed altogether.
+///
+/// No [`ClientAddr`] is inserted in case the client address is not known,
+/// e.g. because it is obfuscated or no [`SocketInfo`] is available.
+///
+/// This layer is to be used after the [`GetForwardedHeadersLayer`], such that
+/// consumers using [`ClientAddr::from_ctx`] (e.g. the `SocketMatcher`s and the
+/// access log) can rely on the resolved address.
+///
+/// [`GetForwardedHeadersLayer`]: super::GetForwardedHeadersLayer
+///
+/// ## Example
+///
+/// ```rust
+/// use rama_core::{service::service_fn, Context, Layer, Service};
+/// use rama_http::layer::forwarded::{ClientAddrLayer, GetForwardedHeadersLayer};
+/// use rama_http::Request;
+/// use rama_net::stream::{ClientAddr, SocketInfo};
+/// use std::{convert::Infallible, net::IpAddr};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let service = (
+///     GetForwardedHeadersLayer::x_forwarded_for(),
+///     ClientAddrLayer::trusted_networks(["10.0.0.0/8".parse().unwrap()]),
+/// )
+///     .layer(service_fn(async |ctx: Context<()>, _| {
+///         let client_addr = ClientAddr::from_ctx(&ctx).unwrap();
+///         assert_eq!(client_addr.ip(), IpAddr::from([12, 23, 34, 45]));
+///         Ok::<_, Infallible>(())
+///     }));
+///
+/// let mut ctx = Context::default();
+/// ctx.insert(SocketInfo::new(None, ([10, 0, 0, 2], 40000).into()));
+///
+/// let req = Request::builder()
+///     .header("X-Forwarded-For", "1.1.1.1, 12.23.34.45, 10.0.0.1")
+///     .body(())
+///     .unwrap();
+///
+/// service.serve(ctx, req).await.unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientAddrLayer<T> {
+    trusted: T,
+}
+
+impl<T> ClientAddrLayer<T> {
+    /// Create a new [`ClientAddrLayer`] for the given [`TrustedProxies`].
+    pub const fn new(trusted: T) -> Self {
+        Self { trusted }
+    }
+}
+
+impl ClientAddrLayer<TrustedHops> {
+    /// Create a new [`ClientAddrLayer`] trusting the given number of hops.
+    pub const fn trusted_hops(hops: usize) -> Self {
+        Self::new(TrustedHops(hops))
+    }
+}
+
+impl ClientAddrLayer<TrustedNetworks> {
+    /// Create a new [`ClientAddrLayer`] trusting the hops with an address in one of the given networks.
+    pub fn trusted_networks(networks: impl IntoIterator<Item = IpNet>) -> Self {
+        Self::new(TrustedNetworks::new(networks))
+    }
+}
+
+impl<M> ClientAddrLayer<TrustedMatcher<M>> {
+    /// Create a new [`ClientAddrLayer`] trusting the hops matched by the given [`SocketInfo`] [`Matcher`].
+    pub const fn trusted_matcher(matcher: M) -> Self {
+        Self::new(TrustedMatcher(matcher))
+    }
+}
+
+impl<T: Clone, S> Layer<S> for ClientAddrLayer<T> {
+    type Service = ClientAddrService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientAddrService::new(inner, self.trusted.clone())
+    }
+}
+
+/// Middleware service to resolve the [`ClientAddr`] of a request.
+///
+/// See [`ClientAddrLayer`] for more information.
+pub struct ClientAddrService<S, T> {
+    inner: S,
+    trusted: T,
+}
+
+impl<S, T> ClientAddrService<S, T> {
+    /// Create a new [`ClientAddrService`] for the given [`TrustedProxies`].
+    pub const fn new(inner: S, trusted: T) -> Self {
+        Self { inner, trusted }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, T: fmt::Debug> fmt::Debug for ClientAddrService<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientAddrService")
+            .field("inner", &self.inner)
+            .field("trusted", &self.trusted)
+            .finish()
+    }
+}
+
+impl<S: Clone, T: Clone> Clone for ClientAddrService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            trusted: self.trusted.clone(),
+        }
+    }
+}
+
+impl<S, T> ClientAddrService<S, T> {
+    /// Walk the chain from right to left, returning the [`ClientAddr`] (if known)
+    /// and the index of its element in the chain (`None` for the peer).
+    fn resolve<State>(
+        &self,
+        ctx: &Context<State>,
+        peer: ClientAddr,
+        elements: &[ForwardedElement],
+    ) -> (Option<ClientAddr>, Option<usize>)
+    where
+        T: TrustedProxies<State>,
+    {
+        let mut client = (Some(peer), None);
+        for (hop, (index, element)) in elements.iter().enumerate().rev().enumerate() {
+            match client.0 {
+                Some(addr) if self.trusted.is_trusted(ctx, hop, &addr) => (),
+                _ => break,
+            }
+            let addr = element.ref_forwarded_for().and_then(|node| {
+                node.ip().map(|ip| {
+                    let addr = ClientAddr::new(ip);
+                    match node.port() {
+                        Some(port) => addr.with_port(port),
+                        None => addr,
+                    }
+                })
+            });
+            client = (addr, Some(index));
+        }
+        client
+    }
+}
+
+impl<S, T, State, Body> Service<State, Request<Body>> for ClientAddrService<S, T>
+where
+    S: Service<State, Request<Body>>,
+    T: TrustedProxies<State>,
+    Body: Send + 'static,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let forwarded = ctx.remove::<Forwarded>();
+
+        let Some(peer) = ctx
+            .get::<SocketInfo>()
+            .map(|info| (*info.peer_addr()).into())
+        else {
+            tracing::trace!("ClientAddrService: no peer address: discard forwarded chain");
+            return self.inner.serve(ctx, req).await;
+        };
+
+        let elements: Vec<ForwardedElement> = forwarded.into_iter().flatten().collect();
+        let (client_addr, index) = self.resolve(&ctx, peer, &elements);
+
+        if let Some(index) = index {
+            let mut it = elements.into_iter().skip(index);
+            if let Some(first) = it.next() {
+                let mut forwarded = Forwarded::new(first);
+                forwarded.extend(it);
+                ctx.insert(forwarded);
+            }
+        }
+        if let Some(client_addr) = client_addr {
+            ctx.insert(client_addr);
+        }
+
+        self.inner.serve(ctx, req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::forwarded::GetForwardedHeadersLayer;
+    use rama_core::service::service_fn;
+    use rama_net::stream::matcher::PrivateIpNetMatcher;
+    use std::{convert::Infallible, net::IpAddr};
+
+    async fn resolve<T>(
+        layer: ClientAddrLayer<T>,
+        peer: Option<&str>,
+        x_forwarded_for: Option<&str>,
+    ) -> (Option<ClientAddr>, Option<IpAddr>)
+    where
+        T: TrustedProxies<()> + Clone,
+    {
+        let service = (GetForwardedHeadersLayer::x_forwarded_for(), layer).layer(service_fn(
+            async |ctx: Context<()>, _| {
+                Ok::<_, Infallible>((
+                    ctx.get::<ClientAddr>().copied(),
+                    ctx.get::<Forwarded>()
+                        .and_then(|forwarded| forwarded.client_ip()),
+                ))
+            },
+        ));
+
+        let mut ctx = Context::default();
+        if let Some(peer) = peer {
+            ctx.insert(SocketInfo::new(None, peer.parse().unwrap()));
+        }
+        let mut req = Request::builder();
+        if let Some(x_forwarded_for) = x_forwarded_for {
+            req = req.header("X-Forwarded-For", x_forwarded_for);
+        }
+        service.serve(ctx, req.body(()).unwrap()).await.unwrap()
+    }
+
+    fn client(ip: &str) -> Option<ClientAddr> {
+        Some(ClientAddr::new(ip.parse().unwrap()))
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_client_addr_trusted_hops() {
+        for (hops, x_forwarded_for, expected) in [
+            (0, Some("1.1.1.1, 2.2.2.2"), (client("10.0.0.1"), None)),
+            (
+                1,
+                Some("1.1.1.1, 2.2.2.2"),
+                (client("2.2.2.2"), ip("2.2.2.2")),
+            ),
+            (
+                2,
+                Some("1.1.1.1, 2.2.2.2"),
+                (client("1.1.1.1"), ip("1.1.1.1")),
+            ),
+            (
+                5,
+                Some("1.1.1.1, 2.2.2.2"),
+                (client("1.1.1.1"), ip("1.1.1.1")),
+            ),
+            (1, None, (client("10.0.0.1"), None)),
+        ] {
+            let (client_addr, forwarded_ip) = resolve(
+                ClientAddrLayer::trusted_hops(hops),
+                Some("10.0.0.1:4000"),
+                x_forwarded_for,
+            )
+            .await;
+            let client_addr = client_addr.map(|addr| ClientAddr::new(addr.ip()));
+            assert_eq!((client_addr, forwarded_ip), expected, "hops: {hops}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_addr_trusted_networks() {
+        let layer = ClientAddrLayer::trusted_networks([
+            "10.0.0.0/8".parse().unwrap(),
+            "fd00::/8".parse().unwrap(),
+        ]);
+
+        for (peer, x_forwarded_for, expected) in [
+            // spoofed by untrusted peer
+            (
+                "1.2.3.4:4000",
+                "10.0.0.5, 10.0.0.6",
+                (client("1.2.3.4"), None),
+            ),
+            // client prepended a spoofed address
+            (
+                "10.0.0.1:4000",
+                "6.6.6.6, 5.5.5.5, 10.0.0.2",
+                (client("5.5.5.5"), ip("5.5.5.5")),
+            ),
+            // all trusted
+            (
+                "10.0.0.1:4000",
+                "10.0.0.3, 10.0.0.2",
+                (client("10.0.0.3"), ip("10.0.0.3")),
+            ),
+            // ipv6 and mapped ipv4 addresses
+            (
+                "[fd00::1]:4000",
+                "5.5.5.5, ::ffff:10.0.0.2",
+                (client("5.5.5.5"), ip("5.5.5.5")),
+            ),
+            (
+                "[::ffff:10.0.0.1]:4000",
+                "2001:db8::1",
+                (client("2001:db8::1"), ip("2001:db8::1")),
+            ),
+        ] {
+            let (client_addr, forwarded_ip) =
+                resolve(layer.clone(), Some(peer), Some(x_forwarded_for)).await;
+            let client_addr = client_addr.map(|addr| ClientAddr::new(addr.ip()));
+            assert_eq!(
+                (client_addr, forwarded_ip),
+                expected,
+                "{peer} -> {x_forwarded_for}"
+            );
+        }
+
+        let (client_addr, forwarded_ip) = resolve(layer.clone(), None, Some("5.5.5.5")).await;
+        assert!(client_addr.is_none());
+        assert!(forwarded_ip.is_none());
+
+        // obfuscated client
+        let service = (GetForwardedHeadersLayer::forwarded(), layer).layer(service_fn(
+            async |ctx: Context<()>, _| {
+                assert!(ctx.get::<ClientAddr>().is_none());
+                let forwarded = ctx.get::<Forwarded>().unwrap();
+                assert_eq!(forwarded.iter().count(), 2);
+                assert!(forwarded.client_ip().is_none());
+                Ok::<_, Infallible>(())
+            },
+        ));
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([10, 0, 0, 1], 4000).into()));
+        let req = Request::builder()
+            .header("Forwarded", "for=6.6.6.6, for=unknown, for=10.0.0.2")
+            .body(())
+            .unwrap();
+        service.serve(ctx, req).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_addr_trusted_matcher() {
+        let (client_addr, forwarded_ip) = resolve(
+            ClientAddrLayer::trusted_matcher(PrivateIpNetMatcher::new()),
+            Some("192.168.1.1:4000"),
+            Some("3.3.3.3, 192.168.1.2"),
+        )
+        .await;
+        assert_eq!(client_addr.map(|addr| addr.ip()), ip("3.3.3.3"));
+        assert_eq!(forwarded_ip, ip("3.3.3.3"));
+    }
+}
@@ -1,6 +1,8 @@
 //! Middleware to support the reading and writing of Forwarded headers.
 //!
 //! See the [`GetForwardedHeadersLayer`] and [`SetForwardedHeadersLayer`] documentation for more details.
+//! The [`ClientAddrLayer`] can be used to resolve the address of the client
+//! from the forwarded information, only trusting the hops appended by trusted proxies.
 
 mod get_forwarded;
 #[doc(inline)]
@@ -9,3 +11,12 @@ pub use get_forwarded::{GetForwardedHeadersLayer, GetForwardedHeadersService};
 mod set_forwarded;
 #[doc(inline)]
 pub use set_forwarded::{SetForwardedHeadersLayer, SetForwardedHeadersService};
+
+mod client_addr;
+#[doc(inline)]
+pub use client_addr::{
+    ClientAddrLayer, ClientAddrService, TrustedHops, TrustedMatcher, TrustedNetworks,
+    TrustedProxies,
+};
+
+pub(crate) use set_forwarded::append_forwarded_element;
@@ -312,38 +312,49 @@ where
         mut ctx: Context<State>,
         mut req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
-        let forwarded: Option<Forwarded> = ctx.get().cloned();
+        let forwarded = append_forwarded_element(&self.by_node, &mut ctx, &req)?;
+        if let Some(header) = H::try_from_forwarded(forwarded.iter()) {
+            req.headers_mut().typed_insert(header);
+        }
 
-        let mut forwarded_element = ForwardedElement::forwarded_by(self.by_node.clone());
+        self.inner.serve(ctx, req).await.map_err(Into::into)
+    }
+}
 
-        if let Some(peer_addr) = ctx.get::<SocketInfo>().map(|socket| *socket.peer_addr()) {
-            forwarded_element.set_forwarded_for(peer_addr);
-        }
-        let request_ctx: &mut RequestContext =
-            ctx.get_or_try_insert_with_ctx(|ctx| (ctx, &req).try_into())?;
+/// Create the [`ForwardedElement`] for this proxy hop and
+/// append it to the chain of forwarded information already known.
+pub(crate) fn append_forwarded_element<State, Body>(
+    by_node: &NodeId,
+    ctx: &mut Context<State>,
+    req: &Request<Body>,
+) -> Result<Forwarded, BoxError>
+where
+    State: Clone + Send + Sync + 'static,
+{
+    let forwarded: Option<Forwarded> = ctx.get().cloned();
 
-        forwarded_element.set_forwarded_host(request_ctx.authority.clone());
+    let mut forwarded_element = ForwardedElement::forwarded_by(by_node.clone());
 
-        if let Ok(forwarded_proto) = (&request_ctx.protocol).try_into() {
-            forwarded_element.set_forwarded_proto(forwarded_proto);
-        }
+    if let Some(peer_addr) = ctx.get::<SocketInfo>().map(|socket| *socket.peer_addr()) {
+        forwarded_element.set_forwarded_for(peer_addr);
+    }
 
-        let forwarded = match forwarded {
-            None => Some(Forwarded::new(forwarded_element)),
-            Some(mut forwarded) => {
-                forwarded.append(forwarded_element);
-                Some(forwarded)
-            }
-        };
+    let request_ctx: &mut RequestContext =
+        ctx.get_or_try_insert_with_ctx(|ctx| (ctx, req).try_into())?;
 
-        if let Some(forwarded) = forwarded {
-            if let Some(header) = H::try_from_forwarded(forwarded.iter()) {
-                req.headers_mut().typed_insert(header);
-            }
-        }
+    forwarded_element.set_forwarded_host(request_ctx.authority.clone());
 
-        self.inner.serve(ctx, req).await.map_err(Into::into)
+    if let Ok(forwarded_proto) = (&request_ctx.protocol).try_into() {
+        forwarded_element.set_forwarded_proto(forwarded_proto);
     }
+
+    Ok(match forwarded {
+        None => Forwarded::new(forwarded_element),
+        Some(mut forwarded) => {
+            forwarded.append(forwarded_element);
+            forwarded
+        }
+    })
 }
 
 macro_rules! set_forwarded_service_for_tuple {
@@ -364,38 +375,12 @@ macro_rules! set_forwarded_service_for_tuple {
                 mut ctx: Context<State>,
                 mut req: Request<Body>,
             ) -> Result<Self::Response, Self::Error> {
-                let forwarded: Option<Forwarded> = ctx.get().cloned();
-
-                let mut forwarded_element = ForwardedElement::forwarded_by(self.by_node.clone());
-
-                if let Some(peer_addr) = ctx.get::<SocketInfo>().map(|socket| *socket.peer_addr()) {
-                    forwarded_element.set_forwarded_for(peer_addr);
-                }
-
-                let request_ctx: &mut RequestContext =
-                    ctx.get_or_try_insert_with_ctx(|ctx| (ctx, &req).try_into())?;
-
-                forwarded_element.set_forwarded_host(request_ctx.authority.clone());
-
-                if let Ok(forwarded_proto) = (&request_ctx.protocol).try_into() {
-                    forwarded_element.set_forwarded_proto(forwarded_proto);
-                }
-
-                let forwarded = match forwarded {
-                    None => Some(Forwarded::new(forwarded_element)),
-                    Some(mut forwarded) => {
-                        forwarded.append(forwarded_element);
-                        Some(forwarded)
+                let forwarded = append_forwarded_element(&self.by_node, &mut ctx, &req)?;
+                $(
+                    if let Some(header) = $ty::try_from_forwarded(forwarded.iter()) {
+                        req.headers_mut().typed_insert(header);
                     }
-                };
-
-                if let Some(forwarded) = forwarded {
-                    $(
-                        if let Some(header) = $ty::try_from_forwarded(forwarded.iter()) {
-                            req.headers_mut().typed_insert(header);
-                        }
-                    )*
-                }
+                )*
 
                 self.inner.serve(ctx, req).await.map_err(Into::into)
             }
@@ -0,0 +1,469 @@
+//! Translate gRPC-Web requests into gRPC requests and back.
+//!
+//! Browsers cannot speak native gRPC, as they cannot access http trailers
+//! nor force HTTP/2. gRPC-Web works around this by sending the trailers
+//! as a special frame in the response body, optionally base64 encoded (text mode).
+//!
+//! The [`GrpcWebLayer`] allows browsers to reach your gRPC services,
+//! such as the ones created using [`crate::service::grpc`],
+//! by translating both the `application/grpc-web` (binary) and
+//! `application/grpc-web-text` (text) variants. Requests that are not gRPC-Web
+//! are passed through as-is.
+//!
+//! You'll likely want to combine it with a [`CorsLayer`] for browsers
+//! to be allowed to make gRPC-Web requests to another origin.
+//!
+//! Learn more about the protocol at
+//! <https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md>.
+//!
+//! # Example
+//!
+//! ```
+//! use bytes::Bytes;
+//! use rama_core::{Context, Layer};
+//! use rama_http::layer::grpc_web::GrpcWebLayer;
+//! use rama_http::service::grpc::{self, BytesCodec, GrpcRequest, Status};
+//! use rama_http::service::web::WebService;
+//!
+//! async fn echo(_ctx: Context<()>, req: GrpcRequest<Bytes>) -> Result<Bytes, Status> {
+//!     Ok(req.into_inner())
+//! }
+//!
+//! let service = GrpcWebLayer::new().layer(
+//!     WebService::default().post("/echo.Echo/Echo", grpc::unary(BytesCodec::new(), echo)),
+//! );
+//! ```
+//!
+//! [`CorsLayer`]: crate::layer::cors::CorsLayer
+
+use crate::{
+    Body, HeaderMap, HeaderValue, Request, Response,
+    dep::http_body::{self, Frame},
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use bytes::{BufMut, Bytes, BytesMut};
+use rama_core::{Context, Layer, Service, error::BoxError};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+/// Flag of the gRPC-Web frame that contains the trailers.
+const TRAILERS_FRAME_FLAG: u8 = 0x80;
+
+/// Layer that applies [`GrpcWeb`] which translates gRPC-Web requests into gRPC requests.
+///
+/// See the [module docs](crate::layer::grpc_web) for more details.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct GrpcWebLayer;
+
+impl GrpcWebLayer {
+    /// Create a new [`GrpcWebLayer`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for GrpcWebLayer {
+    type Service = GrpcWeb<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcWeb::new(inner)
+    }
+}
+
+/// Middleware that translates gRPC-Web requests into gRPC requests,
+/// and the gRPC responses back into gRPC-Web responses.
+///
+/// See the [module docs](crate::layer::grpc_web) for more details.
+#[derive(Debug, Clone)]
+pub struct GrpcWeb<S> {
+    inner: S,
+}
+
+impl<S> GrpcWeb<S> {
+    /// Create a new [`GrpcWeb`] middleware.
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Binary,
+    Text,
+}
+
+/// Split a gRPC-Web content type into its mode and (optional) suffix,
+/// e.g. `application/grpc-web-text+proto` into `(Mode::Text, "+proto")`.
+fn parse_grpc_web_content_type(value: &HeaderValue) -> Option<(Mode, &str)> {
+    let value = value.to_str().ok()?;
+    let prefix = value.get(..20)?;
+    if !prefix.eq_ignore_ascii_case("application/grpc-web") {
+        return None;
+    }
+    let rest = &value[20..];
+    if rest.len() >= 5 && rest[..5].eq_ignore_ascii_case("-text") {
+        return Some((Mode::Text, &rest[5..]));
+    }
+    if rest.is_empty() || rest.starts_with(['+', ';']) {
+        return Some((Mode::Binary, rest));
+    }
+    None
+}
+
+impl<State, S> Service<State, Request> for GrpcWeb<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some((mode, suffix)) = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(parse_grpc_web_content_type)
+        else {
+            return self.inner.serve(ctx, req).await;
+        };
+
+        let grpc_content_type = HeaderValue::try_from(format!("application/grpc{suffix}"))
+            .unwrap_or_else(|_| HeaderValue::from_static("application/grpc"));
+
+        let (mut parts, body) = req.into_parts();
+        parts.headers.insert(CONTENT_TYPE, grpc_content_type);
+        parts.headers.remove(CONTENT_LENGTH);
+        let body = match mode {
+            Mode::Binary => body,
+            Mode::Text => Body::new(TextDecodeBody {
+                inner: body,
+                buf: BytesMut::new(),
+            }),
+        };
+
+        let res = self
+            .inner
+            .serve(ctx, Request::from_parts(parts, body))
+            .await?;
+        Ok(translate_response(res, mode))
+    }
+}
+
+fn translate_response(res: Response, mode: Mode) -> Response {
+    let (mut parts, body) = res.into_parts();
+
+    let suffix = match parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(value)
+            if value.len() >= 16 && value[..16].eq_ignore_ascii_case("application/grpc") =>
+        {
+            value[16..].to_owned()
+        }
+        // not a gRPC response, e.g. a 415 or 404 error
+        _ => return Response::from_parts(parts, body),
+    };
+
+    let content_type = match mode {
+        Mode::Binary => format!("application/grpc-web{suffix}"),
+        Mode::Text => format!("application/grpc-web-text{suffix}"),
+    };
+    if let Ok(value) = HeaderValue::try_from(content_type) {
+        parts.headers.insert(CONTENT_TYPE, value);
+    }
+    parts.headers.remove(CONTENT_LENGTH);
+
+    Response::from_parts(
+        parts,
+        Body::new(WebResponseBody {
+            inner: body,
+            mode,
+            pending: BytesMut::new(),
+            done: false,
+        }),
+    )
+}
+
+/// Encode trailers as a gRPC-Web trailers frame.
+fn encode_trailers(trailers: &HeaderMap) -> Bytes {
+    let mut block = BytesMut::new();
+    for (name, value) in trailers {
+        block.put_slice(name.as_str().as_bytes());
+        block.put_slice(b": ");
+        block.put_slice(value.as_bytes());
+        block.put_slice(b"\r\n");
+    }
+
+    let mut frame = BytesMut::with_capacity(5 + block.len());
+    frame.put_u8(TRAILERS_FRAME_FLAG);
+    frame.put_u32(block.len() as u32);
+    frame.put(block);
+    frame.freeze()
+}
+
+/// Response body that moves the trailers into the body,
+/// and base64 encodes the data in text mode.
+struct WebResponseBody {
+    inner: Body,
+    mode: Mode,
+    /// Bytes not yet base64 encoded in text mode,
+    /// such that no padding is emitted in the middle of the body.
+    pending: BytesMut,
+    done: bool,
+}
+
+impl WebResponseBody {
+    fn encode(&mut self, data: Bytes, flush: bool) -> Option<Bytes> {
+        match self.mode {
+            Mode::Binary => (!data.is_empty()).then_some(data),
+            Mode::Text => {
+                self.pending.put(data);
+                let len = if flush {
+                    self.pending.len()
+                } else {
+                    self.pending.len() - self.pending.len() % 3
+                };
+                if len == 0 {
+                    return None;
+                }
+                let chunk = self.pending.split_to(len);
+                Some(Bytes::from(STANDARD.encode(&chunk)))
+            }
+        }
+    }
+}
+
+impl http_body::Body for WebResponseBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        loop {
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            match futures_lite::ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    let data = match frame.into_data() {
+                        Ok(data) => self.encode(data, false),
+                        Err(frame) => match frame.into_trailers() {
+                            Ok(trailers) => {
+                                self.done = true;
+                                self.encode(encode_trailers(&trailers), true)
+                            }
+                            Err(_) => None,
+                        },
+                    };
+                    if let Some(data) = data {
+                        return Poll::Ready(Some(Ok(Frame::data(data))));
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None => {
+                    self.done = true;
+                    return Poll::Ready(
+                        self.encode(Bytes::new(), true)
+                            .map(|data| Ok(Frame::data(data))),
+                    );
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+/// Request body that decodes the base64 encoded data in text mode.
+struct TextDecodeBody {
+    inner: Body,
+    buf: BytesMut,
+}
+
+impl TextDecodeBody {
+    /// Decode all complete base64 quads available in the buffer.
+    ///
+    /// Padded quads can appear in the middle of the stream,
+    /// as clients are allowed to encode each message separately.
+    fn decode(&mut self, flush: bool) -> Result<Option<Bytes>, BoxError> {
+        let len = self.buf.len() - self.buf.len() % 4;
+        if flush && len != self.buf.len() {
+            return Err("grpc-web-text: invalid base64 body length".into());
+        }
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let input = self.buf.split_to(len);
+        let mut output = Vec::with_capacity(len / 4 * 3);
+        let mut start = 0;
+        for (i, quad) in input.chunks(4).enumerate() {
+            if quad[3] == b'=' {
+                let end = (i + 1) * 4;
+                STANDARD.decode_vec(&input[start..end], &mut output)?;
+                start = end;
+            }
+        }
+        if start < input.len() {
+            STANDARD.decode_vec(&input[start..], &mut output)?;
+        }
+        Ok(Some(output.into()))
+    }
+}
+
+impl http_body::Body for TextDecodeBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        loop {
+            match futures_lite::ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        self.buf
+                            .extend(data.iter().copied().filter(|b| !b.is_ascii_whitespace()));
+                        if let Some(data) = self.decode(false)? {
+                            return Poll::Ready(Some(Ok(Frame::data(data))));
+                        }
+                    }
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None => {
+                    return Poll::Ready(self.decode(true)?.map(|data| Ok(Frame::data(data))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::service::grpc::{self, BytesCodec, GrpcRequest, Status, encode_frame};
+    use rama_core::service::service_fn;
+
+    async fn echo(_ctx: Context<()>, req: GrpcRequest<Bytes>) -> Result<Bytes, Status> {
+        Ok(req.into_inner())
+    }
+
+    fn echo_service()
+    -> impl Service<(), Request, Response = Response, Error = std::convert::Infallible> {
+        grpc::unary(BytesCodec::new(), echo)
+    }
+
+    fn grpc_message(data: &'static [u8]) -> Bytes {
+        encode_frame(
+            Bytes::from_static(data),
+            grpc::CompressionEncoding::Identity,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_grpc_web_content_type() {
+        for (value, expected) in [
+            ("application/grpc-web", Some((Mode::Binary, ""))),
+            ("application/grpc-web+proto", Some((Mode::Binary, "+proto"))),
+            ("application/grpc-web-text", Some((Mode::Text, ""))),
+            (
+                "application/grpc-web-text+proto",
+                Some((Mode::Text, "+proto")),
+            ),
+            ("application/grpc", None),
+            ("application/grpc-webfoo", None),
+            ("text/plain", None),
+        ] {
+            assert_eq!(
+                parse_grpc_web_content_type(&HeaderValue::from_static(value)),
+                expected,
+                "{value}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_binary() {
+        let svc = GrpcWebLayer::new().layer(echo_service());
+        let req = Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/grpc-web+proto")
+            .body(Body::from(grpc_message(b"hello")))
+            .unwrap();
+
+        let res = svc.serve(Context::default(), req).await.unwrap();
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/grpc-web"
+        );
+        let body = res.into_body().collect().await.unwrap();
+        assert!(body.trailers().is_none());
+
+        let mut expected = BytesMut::new();
+        expected.put(grpc_message(b"hello"));
+        expected.put_slice(b"\x80\x00\x00\x00\x10grpc-status: 0\r\n");
+        assert_eq!(body.to_bytes(), expected.freeze());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_text() {
+        let svc = GrpcWebLayer::new().layer(echo_service());
+        let req = Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/grpc-web-text")
+            .body(Body::from(STANDARD.encode(grpc_message(b"hello"))))
+            .unwrap();
+
+        let res = svc.serve(Context::default(), req).await.unwrap();
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/grpc-web-text"
+        );
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body = STANDARD.decode(body).unwrap();
+
+        let mut expected = BytesMut::new();
+        expected.put(grpc_message(b"hello"));
+        expected.put_slice(b"\x80\x00\x00\x00\x10grpc-status: 0\r\n");
+        assert_eq!(&body[..], &expected[..]);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_passthrough() {
+        async fn echo_body(req: Request) -> Result<Response, std::convert::Infallible> {
+            Ok(Response::new(req.into_body()))
+        }
+
+        let svc = GrpcWebLayer::new().layer(service_fn(echo_body));
+        let req = Request::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("hello"))
+            .unwrap();
+        let res = svc.serve(Context::default(), req).await.unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+    }
+}
@@ -0,0 +1,114 @@
+//! Middleware to record Http traffic as [HAR 1.2] and replay it.
+//!
+//! Where the [`traffic_writer`](super::traffic_writer) middleware dumps raw Http text,
+//! the [`HarRecordLayer`] records each request-response exchange as a HAR [`Entry`],
+//! including the timings, server IP and tls version of the connection
+//! as recorded by the connectors in a [`ConnectionTraceHandle`].
+//! It is therefore meant to wrap an Http client, e.g. the upstream client of a MITM proxy.
+//!
+//! Request and response bodies are recorded up to a maximum size,
+//! base64 encoded in case they are binary (or content encoded).
+//! Entries are recorded through a [`HarRecorder`] once the response body is finished (or dropped),
+//! e.g. a [`HarCollector`] which can save all entries as a single HAR file.
+//!
+//! The [`HarReplayService`] serves the recorded entries by matching method, URL and body,
+//! such that offline fixtures can be built from recorded sessions.
+//!
+//! [HAR 1.2]: http://www.softwareishard.com/blog/har-12-spec/
+//! [`ConnectionTraceHandle`]: rama_net::client::ConnectionTraceHandle
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, service::service_fn};
+//! use rama_http::layer::har::{HarCollector, HarRecordLayer, HarReplayService};
+//! use rama_http::{Body, Request, Response};
+//! use std::convert::Infallible;
+//!
+//! async fn upstream(_: Request) -> Result<Response, Infallible> {
+//!     Ok(Response::new(Body::from("hello")))
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let collector = HarCollector::new();
+//! let client = HarRecordLayer::new(collector.clone())
+//!     .with_max_body_size(64 * 1024)
+//!     .layer(service_fn(upstream));
+//!
+//! let req = Request::get("http://example.com/").body(Body::empty()).unwrap();
+//! let res = client.serve(Context::default(), req).await.unwrap();
+//! drop(res);
+//!
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let path = dir.path().join("session.har");
+//! collector.save(&path).await.unwrap();
+//!
+//! let replay = HarReplayService::from_file(&path).await.unwrap();
+//! let req = Request::get("http://example.com/").body(Body::empty()).unwrap();
+//! let res = replay.serve(Context::default(), req).await.unwrap();
+//! assert_eq!(res.status(), 200);
+//! # }
+//! ```
+
+use crate::Request;
+use rama_core::Context;
+use rama_net::http::RequestContext;
+use std::time::SystemTime;
+
+mod spec;
+#[doc(inline)]
+pub use spec::{
+    Cache, Content, Cookie, Creator, Entry, EntryRequest, EntryResponse, Har, Header, Log,
+    PostData, QueryString, Timings,
+};
+
+mod recorder;
+#[doc(inline)]
+pub use recorder::{HarCollector, HarRecorder};
+
+mod record;
+#[doc(inline)]
+pub use record::{HarRecordLayer, HarRecordService};
+
+mod replay;
+#[doc(inline)]
+pub use replay::HarReplayService;
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
+
+/// Compute the absolute URL of the request,
+/// omitting the port in case it is the default one of the protocol.
+fn request_url<State, Body>(ctx: &Context<State>, req: &Request<Body>) -> String {
+    let uri = req.uri();
+    if uri.scheme().is_some() && uri.authority().is_some() {
+        return uri.to_string();
+    }
+
+    let request_ctx = match ctx.get::<RequestContext>() {
+        Some(request_ctx) => request_ctx.clone(),
+        None => match RequestContext::try_from((ctx, req)) {
+            Ok(request_ctx) => request_ctx,
+            Err(_) => return uri.to_string(),
+        },
+    };
+    let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    if request_ctx.authority.port() == request_ctx.protocol.default_port() {
+        format!(
+            "{}://{}{path_and_query}",
+            request_ctx.protocol,
+            request_ctx.authority.host()
+        )
+    } else {
+        format!(
+            "{}://{}{path_and_query}",
+            request_ctx.protocol, request_ctx.authority
+        )
+    }
+}
+
+fn iso_time(time: SystemTime) -> String {
+    let mut s = String::new();
+    super::access_log::write_iso_time(&mut s, time);
+    s
+}
@@ -0,0 +1,688 @@
+use super::recorder::HarRecorder;
+use super::spec::{
+    Content, Cookie, Entry, EntryRequest, EntryResponse, Header, PostData, QueryString, Timings,
+};
+use super::{BASE64, request_url};
+use crate::dep::http_body::{self, Frame, SizeHint};
+use crate::header::{CONTENT_ENCODING, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use crate::{Body, HeaderMap, Request, Response, Version};
+use base64::Engine as _;
+use bytes::Bytes;
+use pin_project_lite::pin_project;
+use rama_core::error::BoxError;
+use rama_core::{Context, Layer, Service};
+use rama_net::client::{ConnectionTrace, ConnectionTraceHandle};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context as TaskContext, Poll, ready},
+    time::{Duration, Instant, SystemTime},
+};
+
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Layer that applies [`HarRecordService`] which records each exchange as a HAR [`Entry`].
+///
+/// See the [module docs](super) for more details.
+pub struct HarRecordLayer<R> {
+    recorder: Arc<R>,
+    max_body_size: usize,
+}
+
+impl<R> fmt::Debug for HarRecordLayer<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HarRecordLayer")
+            .field("recorder", &format_args!("{}", std::any::type_name::<R>()))
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+impl<R> Clone for HarRecordLayer<R> {
+    fn clone(&self) -> Self {
+        Self {
+            recorder: self.recorder.clone(),
+            max_body_size: self.max_body_size,
+        }
+    }
+}
+
+impl<R> HarRecordLayer<R> {
+    /// Create a new [`HarRecordLayer`] recording its entries using the given [`HarRecorder`].
+    ///
+    /// By default at most 1 MiB of each request and response body is recorded.
+    pub fn new(recorder: R) -> Self {
+        Self {
+            recorder: Arc::new(recorder),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Set the maximum amount of bytes recorded for each request and response body.
+    ///
+    /// Bodies are still proxied in full, only the recorded content is truncated.
+    pub fn with_max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes recorded for each request and response body.
+    ///
+    /// Bodies are still proxied in full, only the recorded content is truncated.
+    pub fn set_max_body_size(&mut self, size: usize) -> &mut Self {
+        self.max_body_size = size;
+        self
+    }
+}
+
+impl<S, R> Layer<S> for HarRecordLayer<R> {
+    type Service = HarRecordService<S, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HarRecordService {
+            inner,
+            recorder: self.recorder.clone(),
+            max_body_size: self.max_body_size,
+        }
+    }
+}
+
+/// Middleware that records each exchange as a HAR [`Entry`].
+///
+/// See the [module docs](super) for more details.
+pub struct HarRecordService<S, R> {
+    inner: S,
+    recorder: Arc<R>,
+    max_body_size: usize,
+}
+
+impl<S, R> HarRecordService<S, R> {
+    /// Create a new [`HarRecordService`] recording its entries using the given [`HarRecorder`].
+    ///
+    /// By default at most 1 MiB of each request and response body is recorded.
+    pub fn new(recorder: R, inner: S) -> Self {
+        HarRecordLayer::new(recorder).layer(inner)
+    }
+
+    /// Set the maximum amount of bytes recorded for each request and response body.
+    ///
+    /// Bodies are still proxied in full, only the recorded content is truncated.
+    pub fn with_max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes recorded for each request and response body.
+    ///
+    /// Bodies are still proxied in full, only the recorded content is truncated.
+    pub fn set_max_body_size(&mut self, size: usize) -> &mut Self {
+        self.max_body_size = size;
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, R> fmt::Debug for HarRecordService<S, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HarRecordService")
+            .field("inner", &self.inner)
+            .field("recorder", &format_args!("{}", std::any::type_name::<R>()))
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+impl<S: Clone, R> Clone for HarRecordService<S, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            recorder: self.recorder.clone(),
+            max_body_size: self.max_body_size,
+        }
+    }
+}
+
+impl<State, S, R, ReqBody, ResBody> Service<State, Request<ReqBody>> for HarRecordService<S, R>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request, Response = Response<ResBody>>,
+    R: HarRecorder,
+    ReqBody: http_body::Body<Data = Bytes, Error: Into<BoxError>> + Send + Sync + 'static,
+    ResBody: http_body::Body<Data = Bytes, Error: Into<BoxError>> + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let started_date_time = SystemTime::now();
+        let started = Instant::now();
+
+        let trace = ConnectionTraceHandle::new();
+        ctx.insert(trace.clone());
+
+        let mut request = EntryRequest {
+            method: req.method().to_string(),
+            url: request_url(&ctx, &req),
+            http_version: format!("{:?}", req.version()),
+            cookies: request_cookies(req.headers()),
+            headers: har_headers(req.headers()),
+            query_string: query_string(req.uri().query()),
+            post_data: None,
+            headers_size: -1,
+            body_size: 0,
+            comment: None,
+        };
+        let request_body = BodyCapture::new(self.max_body_size, req.headers());
+
+        let (parts, body) = req.into_parts();
+        let body = CaptureBody {
+            inner: body,
+            capture: request_body.clone(),
+        };
+        // on error no entry is recorded, as a HAR entry requires a response
+        let res = self
+            .inner
+            .serve(ctx, Request::from_parts(parts, Body::new(body)))
+            .await?;
+
+        let headers_received = Instant::now();
+        let trace = trace.trace();
+        let (parts, body) = res.into_parts();
+
+        // the negotiated version is the one used on the wire
+        let response_version = match negotiated_version(&trace) {
+            Some(version) => {
+                request.http_version = format!("{version:?}");
+                version
+            }
+            None => parts.version,
+        };
+
+        let entry = Entry {
+            started_date_time: super::iso_time(started_date_time),
+            time: 0.0,
+            request,
+            response: EntryResponse {
+                status: parts.status.as_u16(),
+                status_text: parts
+                    .status
+                    .canonical_reason()
+                    .unwrap_or_default()
+                    .to_owned(),
+                http_version: format!("{response_version:?}"),
+                cookies: response_cookies(&parts.headers),
+                headers: har_headers(&parts.headers),
+                content: Content::default(),
+                redirect_url: parts
+                    .headers
+                    .get(LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_owned(),
+                headers_size: -1,
+                body_size: 0,
+                comment: None,
+            },
+            cache: Default::default(),
+            timings: connection_timings(&trace, headers_received - started),
+            server_ip_address: trace.server_addr().map(|addr| addr.ip().to_string()),
+            connection: None,
+            tls_version: tls_version(&trace),
+            comment: None,
+        };
+        let response_body = BodyCapture::new(self.max_body_size, &parts.headers);
+
+        let pending = PendingEntry {
+            entry,
+            headers_received,
+            request_body,
+            response_body: response_body.clone(),
+            recorder: self.recorder.clone(),
+        };
+        let body = HarRecordBody {
+            inner: CaptureBody {
+                inner: body,
+                capture: response_body,
+            },
+            pending: Some(pending),
+        };
+        Ok(Response::from_parts(parts, Body::new(body)))
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Compute all [`Timings`] known once the response head is received.
+fn connection_timings(trace: &ConnectionTrace, elapsed: Duration) -> Timings {
+    let dns = trace.dns();
+    let connect = trace
+        .connect()
+        .map(|connect| connect + trace.tls().unwrap_or_default());
+    let wait = elapsed
+        .saturating_sub(dns.unwrap_or_default())
+        .saturating_sub(connect.unwrap_or_default());
+    Timings {
+        dns: dns.map(millis).unwrap_or(-1.0),
+        connect: connect.map(millis).unwrap_or(-1.0),
+        ssl: trace.tls().map(millis).unwrap_or(-1.0),
+        wait: millis(wait),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "tls")]
+fn negotiated_version(trace: &ConnectionTrace) -> Option<Version> {
+    use rama_net::tls::ApplicationProtocol;
+
+    match trace
+        .tls_parameters()?
+        .application_layer_protocol
+        .as_ref()?
+    {
+        ApplicationProtocol::HTTP_09 => Some(Version::HTTP_09),
+        ApplicationProtocol::HTTP_10 => Some(Version::HTTP_10),
+        ApplicationProtocol::HTTP_11 => Some(Version::HTTP_11),
+        ApplicationProtocol::HTTP_2 => Some(Version::HTTP_2),
+        ApplicationProtocol::HTTP_3 => Some(Version::HTTP_3),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn negotiated_version(_: &ConnectionTrace) -> Option<Version> {
+    None
+}
+
+#[cfg(feature = "tls")]
+fn tls_version(trace: &ConnectionTrace) -> Option<String> {
+    trace
+        .tls_parameters()
+        .map(|params| params.protocol_version.to_string())
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_version(_: &ConnectionTrace) -> Option<String> {
+    None
+}
+
+fn har_headers(headers: &HeaderMap) -> Vec<Header> {
+    headers
+        .iter()
+        .map(|(name, value)| Header {
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            comment: None,
+        })
+        .collect()
+}
+
+fn query_string(query: Option<&str>) -> Vec<QueryString> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            QueryString {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                comment: None,
+            }
+        })
+        .collect()
+}
+
+fn request_cookies(headers: &HeaderMap) -> Vec<Cookie> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            Some(Cookie {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn response_cookies(headers: &HeaderMap) -> Vec<Cookie> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| {
+            let mut attributes = value.split(';').map(str::trim);
+            let (name, value) = attributes.next()?.split_once('=')?;
+            let mut cookie = Cookie {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                ..Default::default()
+            };
+            for attribute in attributes {
+                let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+                if key.eq_ignore_ascii_case("path") {
+                    cookie.path = Some(value.to_owned());
+                } else if key.eq_ignore_ascii_case("domain") {
+                    cookie.domain = Some(value.to_owned());
+                } else if key.eq_ignore_ascii_case("expires") {
+                    cookie.expires = Some(value.to_owned());
+                } else if key.eq_ignore_ascii_case("httponly") {
+                    cookie.http_only = Some(true);
+                } else if key.eq_ignore_ascii_case("secure") {
+                    cookie.secure = Some(true);
+                }
+            }
+            Some(cookie)
+        })
+        .collect()
+}
+
+/// The (size capped) bytes of a body seen so far.
+#[derive(Debug, Clone)]
+struct BodyCapture(Arc<Mutex<BodyCaptureState>>);
+
+#[derive(Debug)]
+struct BodyCaptureState {
+    data: Vec<u8>,
+    size: usize,
+    max_size: usize,
+    mime_type: String,
+    content_encoded: bool,
+}
+
+impl BodyCapture {
+    fn new(max_size: usize, headers: &HeaderMap) -> Self {
+        let mime_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let content_encoded = headers
+            .get(CONTENT_ENCODING)
+            .is_some_and(|encoding| encoding != "identity");
+        Self(Arc::new(Mutex::new(BodyCaptureState {
+            data: Vec::new(),
+            size: 0,
+            max_size,
+            mime_type: mime_type.to_owned(),
+            content_encoded,
+        })))
+    }
+
+    fn push(&self, data: &[u8]) {
+        let mut state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        state.size += data.len();
+        let remaining = state.max_size.saturating_sub(state.data.len());
+        state
+            .data
+            .extend_from_slice(&data[..remaining.min(data.len())]);
+    }
+
+    /// Returns the size, mime type, (possibly base64 encoded) text, its encoding
+    /// and a comment in case the recorded body got truncated.
+    fn finish(&self) -> (usize, String, String, Option<String>, Option<String>) {
+        let state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let text = match std::str::from_utf8(&state.data) {
+            Ok(text) if !state.content_encoded => (text.to_owned(), None),
+            _ => (BASE64.encode(&state.data), Some("base64".to_owned())),
+        };
+        let comment = (state.size > state.data.len()).then(|| {
+            format!(
+                "body truncated: {} of {} bytes recorded",
+                state.data.len(),
+                state.size
+            )
+        });
+        (state.size, state.mime_type.clone(), text.0, text.1, comment)
+    }
+}
+
+/// A HAR entry waiting for the exchange to finish,
+/// recorded as soon as it is dropped.
+struct PendingEntry<R: HarRecorder> {
+    entry: Entry,
+    headers_received: Instant,
+    request_body: BodyCapture,
+    response_body: BodyCapture,
+    recorder: Arc<R>,
+}
+
+impl<R: HarRecorder> Drop for PendingEntry<R> {
+    fn drop(&mut self) {
+        let mut entry = std::mem::take(&mut self.entry);
+
+        let (size, mime_type, text, encoding, comment) = self.request_body.finish();
+        entry.request.body_size = size as i64;
+        if size > 0 {
+            entry.request.post_data = Some(PostData {
+                mime_type,
+                text,
+                encoding,
+                comment,
+            });
+        }
+
+        let (size, mime_type, text, encoding, comment) = self.response_body.finish();
+        entry.response.body_size = size as i64;
+        entry.response.content = Content {
+            size: size as i64,
+            compression: None,
+            mime_type,
+            text: (size > 0).then_some(text),
+            encoding: encoding.filter(|_| size > 0),
+            comment,
+        };
+
+        let timings = &mut entry.timings;
+        timings.receive = millis(self.headers_received.elapsed());
+        entry.time = [
+            timings.blocked,
+            timings.dns,
+            timings.connect,
+            timings.send,
+            timings.wait,
+            timings.receive,
+        ]
+        .into_iter()
+        .filter(|timing| *timing > 0.0)
+        .sum();
+
+        self.recorder.record(entry);
+    }
+}
+
+pin_project! {
+    /// Body which captures the (size capped) data passing through it.
+    struct CaptureBody<B> {
+        #[pin]
+        inner: B,
+        capture: BodyCapture,
+    }
+}
+
+impl<B> http_body::Body for CaptureBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let result = ready!(this.inner.poll_frame(cx));
+        if let Some(data) = result
+            .as_ref()
+            .and_then(|result| result.as_ref().ok())
+            .and_then(Frame::data_ref)
+        {
+            this.capture.push(data);
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pin_project! {
+    /// Response body which records the HAR entry once it is finished or dropped.
+    struct HarRecordBody<B, R: HarRecorder> {
+        #[pin]
+        inner: CaptureBody<B>,
+        pending: Option<PendingEntry<R>>,
+    }
+}
+
+impl<B, R> http_body::Body for HarRecordBody<B, R>
+where
+    B: http_body::Body<Data = Bytes>,
+    R: HarRecorder,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let result = ready!(this.inner.as_mut().poll_frame(cx));
+        if !matches!(result, Some(Ok(_))) || this.inner.is_end_stream() {
+            this.pending.take();
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::layer::har::HarCollector;
+    use crate::{HeaderValue, StatusCode};
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_har_record_entry() {
+        let collector = HarCollector::new();
+        let service = HarRecordLayer::new(collector.clone())
+            .with_max_body_size(4)
+            .layer(service_fn(|req: Request| async move {
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(body, "hello world");
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .status(StatusCode::CREATED)
+                        .header(CONTENT_TYPE, "application/octet-stream")
+                        .header(SET_COOKIE, "session=abc; Path=/; HttpOnly")
+                        .body(Body::from(vec![0xff, 0xfe]))
+                        .unwrap(),
+                )
+            }));
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("http://example.com/upload?a=1&b")
+            .header(CONTENT_TYPE, "text/plain")
+            .header(COOKIE, HeaderValue::from_static("foo=bar; baz=qux"))
+            .body(Body::from("hello world"))
+            .unwrap();
+        let res = service.serve(Context::default(), req).await.unwrap();
+        assert!(collector.entries().is_empty());
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.as_ref(), &[0xff, 0xfe]);
+
+        let entries = collector.entries();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+
+        assert_eq!(entry.request.method, "POST");
+        assert_eq!(entry.request.url, "http://example.com/upload?a=1&b");
+        assert_eq!(entry.request.http_version, "HTTP/1.1");
+        assert_eq!(entry.request.query_string.len(), 2);
+        assert_eq!(entry.request.query_string[1].name, "b");
+        assert_eq!(entry.request.cookies.len(), 2);
+        assert_eq!(entry.request.cookies[1].value, "qux");
+        assert_eq!(entry.request.body_size, 11);
+        let post_data = entry.request.post_data.as_ref().unwrap();
+        assert_eq!(post_data.mime_type, "text/plain");
+        assert_eq!(post_data.text, "hell");
+        assert!(post_data.encoding.is_none());
+        assert!(post_data.comment.is_some());
+
+        assert_eq!(entry.response.status, 201);
+        assert_eq!(entry.response.status_text, "Created");
+        assert_eq!(entry.response.cookies[0].name, "session");
+        assert_eq!(entry.response.cookies[0].path.as_deref(), Some("/"));
+        assert_eq!(entry.response.cookies[0].http_only, Some(true));
+        assert_eq!(entry.response.content.size, 2);
+        assert_eq!(entry.response.content.text.as_deref(), Some("//4="));
+        assert_eq!(entry.response.content.encoding.as_deref(), Some("base64"));
+
+        // no connection was established by the inner service
+        assert_eq!(entry.timings.dns, -1.0);
+        assert_eq!(entry.timings.connect, -1.0);
+        assert!(entry.server_ip_address.is_none());
+        assert!(entry.time >= entry.timings.wait);
+    }
+
+    #[tokio::test]
+    async fn test_har_record_connection_trace() {
+        let collector = HarCollector::new();
+        let service = HarRecordLayer::new(collector.clone()).layer(service_fn(
+            |ctx: Context<()>, _: Request| async move {
+                let trace = ctx.get::<ConnectionTraceHandle>().unwrap();
+                trace.record_dns(Duration::from_millis(2));
+                trace.record_connect(Duration::from_millis(3), ([10, 0, 0, 1], 443).into());
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            },
+        ));
+
+        let req = Request::builder()
+            .uri("/")
+            .header("host", "example.com")
+            .body(Body::empty())
+            .unwrap();
+        let res = service.serve(Context::default(), req).await.unwrap();
+        drop(res);
+
+        let entry = collector.take_entries().pop().unwrap();
+        assert_eq!(entry.request.url, "http://example.com/");
+        assert!(entry.request.post_data.is_none());
+        assert_eq!(entry.timings.dns, 2.0);
+        assert_eq!(entry.timings.connect, 3.0);
+        assert_eq!(entry.timings.ssl, -1.0);
+        assert_eq!(entry.server_ip_address.as_deref(), Some("10.0.0.1"));
+        assert!(entry.response.content.text.is_none());
+        assert!(collector.entries().is_empty());
+    }
+}
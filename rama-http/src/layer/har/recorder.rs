@@ -0,0 +1,90 @@
+use super::spec::{Entry, Har, Log};
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::sync::mpsc::{self, Sender, UnboundedSender};
+
+/// A trait for recording HAR [`Entry`]s.
+///
+/// Entries are recorded from within the request flow,
+/// and as such implementations should never block.
+pub trait HarRecorder: Send + Sync + 'static {
+    /// Record a single finished exchange.
+    fn record(&self, entry: Entry);
+}
+
+impl HarRecorder for Sender<Entry> {
+    fn record(&self, entry: Entry) {
+        match self.try_send(entry) {
+            Ok(()) => (),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("har recorder buffer is full: dropping har entry")
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::error!("har recorder is closed: dropping har entry")
+            }
+        }
+    }
+}
+
+impl HarRecorder for UnboundedSender<Entry> {
+    fn record(&self, entry: Entry) {
+        if self.send(entry).is_err() {
+            tracing::error!("har recorder is closed: dropping har entry")
+        }
+    }
+}
+
+/// A [`HarRecorder`] which keeps all recorded entries in memory,
+/// such that they can be exported as a single [`Har`] document.
+///
+/// Cloning a [`HarCollector`] gives access to the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct HarCollector {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl HarCollector {
+    /// Create a new empty [`HarCollector`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a copy of all entries recorded so far.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.lock().clone()
+    }
+
+    /// Take all entries recorded so far, leaving the collector empty.
+    pub fn take_entries(&self) -> Vec<Entry> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Export all entries recorded so far as a [`Har`] document.
+    pub fn har(&self) -> Har {
+        Har {
+            log: Log {
+                entries: self.entries(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Write all entries recorded so far as a [`Har`] document to the given file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(&self.har())?;
+        tokio::fs::write(path, data).await
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl HarRecorder for HarCollector {
+    fn record(&self, entry: Entry) {
+        self.lock().push(entry);
+    }
+}
@@ -0,0 +1,337 @@
+use super::spec::{Entry, Har, PostData};
+use super::{BASE64, request_url};
+use crate::dep::http_body;
+use crate::dep::http_body_util::{BodyExt, LengthLimitError, Limited};
+use crate::header::{CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING};
+use crate::{Body, HeaderName, HeaderValue, Request, Response, StatusCode};
+use base64::Engine as _;
+use bytes::Bytes;
+use rama_core::error::{BoxError, ErrorContext, ErrorExt, OpaqueError};
+use rama_core::{Context, Service};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// A [`Service`] which serves the responses of recorded HAR [`Entry`]s.
+///
+/// A request is matched against the recorded entries by method, URL and body.
+/// In case multiple entries match, they are served in recorded order,
+/// with the last matching entry being served for all subsequent requests.
+/// Requests which match no entry get a `404 Not Found` response,
+/// and requests with a body larger than the max request body size (8 MiB by default)
+/// a `413 Payload Too Large` response.
+///
+/// Recorded bodies can be truncated (see [`HarRecordLayer::with_max_body_size`]):
+/// a truncated request body only matches a request body of the original size
+/// starting with the recorded part, while serving an entry of which the
+/// response content is truncated fails, as its response cannot be replayed in full.
+///
+/// [`HarRecordLayer::with_max_body_size`]: super::HarRecordLayer::with_max_body_size
+#[derive(Debug, Clone)]
+pub struct HarReplayService {
+    entries: Arc<[Entry]>,
+    served: Arc<Mutex<Vec<bool>>>,
+    max_request_body_size: usize,
+}
+
+const DEFAULT_MAX_REQUEST_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+impl HarReplayService {
+    /// Create a new [`HarReplayService`] serving the entries of the given [`Har`] document.
+    pub fn new(har: Har) -> Self {
+        Self::from_entries(har.log.entries)
+    }
+
+    /// Create a new [`HarReplayService`] serving the given entries.
+    pub fn from_entries(entries: Vec<Entry>) -> Self {
+        Self {
+            served: Arc::new(Mutex::new(vec![false; entries.len()])),
+            entries: entries.into(),
+            max_request_body_size: DEFAULT_MAX_REQUEST_BODY_SIZE,
+        }
+    }
+
+    /// Set the maximum size of a request body collected to match it
+    /// against the recorded entries (8 MiB by default).
+    pub fn with_max_request_body_size(mut self, size: usize) -> Self {
+        self.max_request_body_size = size;
+        self
+    }
+
+    /// Set the maximum size of a request body collected to match it
+    /// against the recorded entries (8 MiB by default).
+    pub fn set_max_request_body_size(&mut self, size: usize) -> &mut Self {
+        self.max_request_body_size = size;
+        self
+    }
+
+    /// Create a new [`HarReplayService`] serving the entries of the HAR file at the given path.
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self, OpaqueError> {
+        let data = tokio::fs::read(path).await.context("read har file")?;
+        let har: Har = serde_json::from_slice(&data).context("parse har file")?;
+        Ok(Self::new(har))
+    }
+
+    fn find_entry(&self, method: &str, url: &str, body: &[u8]) -> Option<&Entry> {
+        let mut served = self.served.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut last_match = None;
+        for (index, entry) in self.entries.iter().enumerate() {
+            if !entry.request.method.eq_ignore_ascii_case(method)
+                || entry.request.url != url
+                || !request_body_matches(entry, body)
+            {
+                continue;
+            }
+            if !served[index] {
+                served[index] = true;
+                return Some(entry);
+            }
+            last_match = Some(entry);
+        }
+        last_match
+    }
+}
+
+/// Returns `true` in case the body matches the recorded request body,
+/// of which only the recorded part is compared in case it got truncated.
+fn request_body_matches(entry: &Entry, body: &[u8]) -> bool {
+    let Some(recorded) = post_data_bytes(entry.request.post_data.as_ref()) else {
+        return false;
+    };
+    if is_truncated(entry.request.body_size, recorded.len()) {
+        body.len() as i64 == entry.request.body_size && body.starts_with(&recorded)
+    } else {
+        recorded == body
+    }
+}
+
+/// Returns `true` in case less than the (known) size got recorded.
+fn is_truncated(size: i64, recorded: usize) -> bool {
+    usize::try_from(size).is_ok_and(|size| size > recorded)
+}
+
+fn post_data_bytes(post_data: Option<&PostData>) -> Option<Vec<u8>> {
+    match post_data {
+        None => Some(Vec::new()),
+        Some(post_data) => decode_text(&post_data.text, post_data.encoding.as_deref()),
+    }
+}
+
+fn decode_text(text: &str, encoding: Option<&str>) -> Option<Vec<u8>> {
+    match encoding {
+        Some(encoding) if encoding.eq_ignore_ascii_case("base64") => BASE64.decode(text).ok(),
+        _ => Some(text.as_bytes().to_vec()),
+    }
+}
+
+fn replay_response(entry: &Entry) -> Result<Response, OpaqueError> {
+    let content = &entry.response.content;
+    let body = match &content.text {
+        Some(text) => decode_text(text, content.encoding.as_deref())
+            .context("decode recorded response content")?,
+        None => Vec::new(),
+    };
+    if content.text.is_some() && is_truncated(content.size, body.len()) {
+        return Err(OpaqueError::from_display(format!(
+            "recorded response content is truncated: {} of {} bytes recorded",
+            body.len(),
+            content.size
+        )));
+    }
+
+    let mut res = Response::new(Body::from(body));
+    *res.status_mut() =
+        StatusCode::from_u16(entry.response.status).context("invalid recorded status code")?;
+    let headers = res.headers_mut();
+    for header in &entry.response.headers {
+        let name =
+            HeaderName::try_from(header.name.as_str()).context("invalid recorded header name")?;
+        // the body is replayed in full, making these recorded headers invalid
+        if name == CONTENT_LENGTH || name == TRANSFER_ENCODING || name == CONNECTION {
+            continue;
+        }
+        let value = HeaderValue::try_from(header.value.as_str())
+            .context("invalid recorded header value")?;
+        headers.append(name, value);
+    }
+    Ok(res)
+}
+
+impl<State, ReqBody> Service<State, Request<ReqBody>> for HarReplayService
+where
+    State: Clone + Send + Sync + 'static,
+    ReqBody: http_body::Body<Data = Bytes, Error: Into<BoxError>> + Send + 'static,
+{
+    type Response = Response;
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let url = request_url(&ctx, &req);
+        let method = req.method().clone();
+        let body = match Limited::new(req.into_body(), self.max_request_body_size)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(err) if err.is::<LengthLimitError>() => {
+                tracing::debug!(%method, %url, "har replay: request body too large");
+                let mut res = Response::new(Body::from("request body too large"));
+                *res.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+                return Ok(res);
+            }
+            Err(err) => {
+                return Err(OpaqueError::from_boxed(err).context("collect request body"));
+            }
+        };
+
+        match self.find_entry(method.as_str(), &url, &body) {
+            Some(entry) => replay_response(entry)
+                .with_context(|| format!("replay recorded har entry for {method} {url}")),
+            None => {
+                tracing::debug!(%method, %url, "no recorded har entry matches request");
+                let mut res = Response::new(Body::from("no recorded har entry matches request"));
+                *res.status_mut() = StatusCode::NOT_FOUND;
+                Ok(res)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::har::{Content, EntryRequest, EntryResponse, Header};
+
+    fn entry(method: &str, url: &str, post_data: Option<PostData>, text: &str) -> Entry {
+        Entry {
+            request: EntryRequest {
+                method: method.to_owned(),
+                url: url.to_owned(),
+                post_data,
+                ..Default::default()
+            },
+            response: EntryResponse {
+                status: 200,
+                headers: vec![
+                    Header {
+                        name: "content-type".to_owned(),
+                        value: "text/plain".to_owned(),
+                        comment: None,
+                    },
+                    Header {
+                        name: "transfer-encoding".to_owned(),
+                        value: "chunked".to_owned(),
+                        comment: None,
+                    },
+                ],
+                content: Content {
+                    size: text.len() as i64,
+                    text: Some(BASE64.encode(text)),
+                    encoding: Some("base64".to_owned()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn replay(service: &HarReplayService, method: &str, uri: &str, body: &str) -> Response {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body.to_owned()))
+            .unwrap();
+        service.serve(Context::default(), req).await.unwrap()
+    }
+
+    async fn body_string(res: Response) -> String {
+        String::from_utf8(res.into_body().collect().await.unwrap().to_bytes().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_har_replay_matching() {
+        let post_data = |text: &str| {
+            Some(PostData {
+                mime_type: "text/plain".to_owned(),
+                text: text.to_owned(),
+                ..Default::default()
+            })
+        };
+        let service = HarReplayService::from_entries(vec![
+            entry("GET", "http://example.com/", None, "first"),
+            entry("GET", "http://example.com/", None, "second"),
+            entry("POST", "http://example.com/", post_data("a"), "post a"),
+            entry("POST", "http://example.com/", post_data("b"), "post b"),
+        ]);
+
+        let res = replay(&service, "GET", "http://example.com/", "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+        assert!(!res.headers().contains_key(TRANSFER_ENCODING));
+        assert_eq!(body_string(res).await, "first");
+        for _ in 0..2 {
+            let res = replay(&service, "GET", "http://example.com/", "").await;
+            assert_eq!(body_string(res).await, "second");
+        }
+
+        let res = replay(&service, "POST", "http://example.com/", "b").await;
+        assert_eq!(body_string(res).await, "post b");
+        let res = replay(&service, "POST", "http://example.com/", "a").await;
+        assert_eq!(body_string(res).await, "post a");
+
+        let res = replay(&service, "POST", "http://example.com/", "c").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = replay(&service, "GET", "http://example.com/other", "").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_har_replay_truncated_bodies() {
+        let mut truncated_request = entry(
+            "POST",
+            "http://example.com/upload",
+            Some(PostData {
+                text: "hell".to_owned(),
+                comment: Some("body truncated: 4 of 11 bytes recorded".to_owned()),
+                ..Default::default()
+            }),
+            "uploaded",
+        );
+        truncated_request.request.body_size = 11;
+        let mut truncated_response = entry("GET", "http://example.com/large", None, "large");
+        truncated_response.response.content.size = 1024;
+        let service = HarReplayService::from_entries(vec![truncated_request, truncated_response])
+            .with_max_request_body_size(16);
+
+        // only the recorded part of a truncated request body is compared
+        let res = replay(&service, "POST", "http://example.com/upload", "hello world").await;
+        assert_eq!(body_string(res).await, "uploaded");
+        let res = replay(&service, "POST", "http://example.com/upload", "hello").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = replay(&service, "POST", "http://example.com/upload", "help world!").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        // truncated responses are not served as if complete
+        let req = Request::builder()
+            .uri("http://example.com/large")
+            .body(Body::empty())
+            .unwrap();
+        let err = service.serve(Context::default(), req).await.unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{err}");
+
+        let res = replay(
+            &service,
+            "POST",
+            "http://example.com/upload",
+            "way too large body",
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
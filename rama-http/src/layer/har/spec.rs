@@ -0,0 +1,325 @@
+//! HAR 1.2 data types, as specified in
+//! <http://www.softwareishard.com/blog/har-12-spec/>.
+//!
+//! Optional fields which are absent are not serialized,
+//! and fields missing in a deserialized HAR file get their default value,
+//! such that HAR files produced by other tools (e.g. browsers) can be read as well.
+
+use serde::{Deserialize, Serialize};
+
+/// The root of a HAR file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Har {
+    /// The exported HTTP archive.
+    pub log: Log,
+}
+
+/// The exported HTTP archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    /// Version of the HAR format, `1.2`.
+    pub version: String,
+    /// The application which created the log.
+    pub creator: Creator,
+    /// The browser which created the log, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<Creator>,
+    /// The recorded exchanges, in the order they were started.
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self {
+            version: "1.2".to_owned(),
+            creator: Creator::default(),
+            browser: None,
+            entries: Vec::new(),
+            comment: None,
+        }
+    }
+}
+
+/// The application (or browser) which created the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Creator {
+    /// Name of the application.
+    pub name: String,
+    /// Version of the application.
+    pub version: String,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for Creator {
+    fn default() -> Self {
+        Self {
+            name: rama_utils::info::NAME.to_owned(),
+            version: rama_utils::info::VERSION.to_owned(),
+            comment: None,
+        }
+    }
+}
+
+/// A single recorded request-response exchange.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// Date and time of the request start (ISO 8601).
+    pub started_date_time: String,
+    /// Total elapsed time of the exchange in milliseconds,
+    /// the sum of all non-negative [`Timings`] (excluding `ssl`).
+    pub time: f64,
+    /// The recorded request.
+    pub request: EntryRequest,
+    /// The recorded response.
+    pub response: EntryResponse,
+    /// Cache info, left empty by rama.
+    #[serde(default)]
+    pub cache: Cache,
+    /// Detailed timing info of the exchange.
+    #[serde(default)]
+    pub timings: Timings,
+    /// IP address of the server the connection was established with.
+    #[serde(
+        rename = "serverIPAddress",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub server_ip_address: Option<String>,
+    /// Unique id of the underlying connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<String>,
+    /// Negotiated TLS version of the connection, if secured (custom field).
+    #[serde(
+        rename = "_tlsVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tls_version: Option<String>,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A recorded request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryRequest {
+    /// Request method, e.g. `GET`.
+    pub method: String,
+    /// Absolute URL of the request, fragments excluded.
+    pub url: String,
+    /// Request http version, e.g. `HTTP/1.1`.
+    pub http_version: String,
+    /// The cookies sent with the request.
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// The request headers.
+    #[serde(default)]
+    pub headers: Vec<Header>,
+    /// The parameters parsed from the query string.
+    #[serde(default)]
+    pub query_string: Vec<QueryString>,
+    /// The posted data, if the request has a body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    /// Total amount of bytes of the request head, `-1` if unknown.
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    /// Size of the request body in bytes, `-1` if unknown.
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryResponse {
+    /// Response status code.
+    pub status: u16,
+    /// Response status reason, e.g. `OK`.
+    #[serde(default)]
+    pub status_text: String,
+    /// Response http version, e.g. `HTTP/1.1`.
+    pub http_version: String,
+    /// The cookies set by the response.
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// The response headers.
+    #[serde(default)]
+    pub headers: Vec<Header>,
+    /// The response content.
+    pub content: Content,
+    /// Target of the `Location` response header, empty if none.
+    #[serde(rename = "redirectURL", default)]
+    pub redirect_url: String,
+    /// Total amount of bytes of the response head, `-1` if unknown.
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    /// Size of the response body as transferred in bytes, `-1` if unknown.
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A request or response cookie.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    /// Name of the cookie.
+    pub name: String,
+    /// Value of the cookie.
+    pub value: String,
+    /// Path of the cookie, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Domain of the cookie, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Expiration time of the cookie, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// Whether the cookie is `HttpOnly`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_only: Option<bool>,
+    /// Whether the cookie is `Secure`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A request or response header.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    /// Name of the header.
+    pub name: String,
+    /// Value of the header.
+    pub value: String,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A parameter parsed from the query string.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryString {
+    /// Name of the parameter.
+    pub name: String,
+    /// Value of the parameter.
+    pub value: String,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// The data posted with a request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    /// Mime type of the posted data.
+    #[serde(default)]
+    pub mime_type: String,
+    /// The posted data, encoded as defined by [`PostData::encoding`].
+    #[serde(default)]
+    pub text: String,
+    /// Encoding of the text, `base64` for binary data (custom field).
+    #[serde(rename = "_encoding", default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// The content of a response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    /// Size of the content in bytes.
+    pub size: i64,
+    /// Amount of bytes saved by compression, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<i64>,
+    /// Mime type of the content.
+    #[serde(default)]
+    pub mime_type: String,
+    /// The (possibly truncated) content, encoded as defined by [`Content::encoding`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Encoding of the text, `base64` for binary content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Cache info of an [`Entry`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cache {
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Timings of an [`Entry`] in milliseconds, `-1` when not applicable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Time spent in a queue waiting for a connection.
+    #[serde(default = "not_applicable")]
+    pub blocked: f64,
+    /// Time spent resolving the server domain.
+    #[serde(default = "not_applicable")]
+    pub dns: f64,
+    /// Time spent establishing the connection, including `ssl`.
+    #[serde(default = "not_applicable")]
+    pub connect: f64,
+    /// Time spent sending the request.
+    pub send: f64,
+    /// Time spent waiting for the response head.
+    pub wait: f64,
+    /// Time spent receiving the response body.
+    pub receive: f64,
+    /// Time spent on the tls handshake.
+    #[serde(default = "not_applicable")]
+    pub ssl: f64,
+    /// A comment provided by the user or the application.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: 0.0,
+            receive: 0.0,
+            ssl: -1.0,
+            comment: None,
+        }
+    }
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn not_applicable() -> f64 {
+    -1.0
+}
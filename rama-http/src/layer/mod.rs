@@ -16,7 +16,10 @@
 //! [`Layer`]: rama_core::Layer
 //! [`Service`]: rama_core::Service
 
+pub mod access_log;
+pub mod acl;
 pub mod auth;
+pub mod bandwidth_limit;
 pub mod body_limit;
 pub mod catch_panic;
 pub mod classify;
@@ -26,11 +29,14 @@ pub mod dns;
 pub mod error_handling;
 pub mod follow_redirect;
 pub mod forwarded;
+pub mod grpc_web;
+pub mod har;
 pub mod header_config;
 pub mod header_option_value;
 pub mod map_request_body;
 pub mod map_response_body;
 pub mod normalize_path;
+pub mod prometheus;
 pub mod propagate_headers;
 pub mod proxy_auth;
 pub mod remove_header;
@@ -0,0 +1,237 @@
+//! Http Prometheus [`Layer`] Support for Rama.
+//!
+//! The recorded metrics can be exposed using the
+//! [`PrometheusService`](crate::service::prometheus::PrometheusService).
+//!
+//! [`Layer`]: rama_core::Layer
+
+use crate::{Method, Request, Response};
+use rama_core::telemetry::prometheus::{Counter, DEFAULT_BUCKETS, Gauge, Histogram, Registry};
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, sync::Arc, time::Instant};
+
+const HTTP_SERVER_REQUESTS: &str = "rama_http_server_requests";
+const HTTP_SERVER_ACTIVE_REQUESTS: &str = "rama_http_server_active_requests";
+const HTTP_SERVER_REQUEST_DURATION: &str = "rama_http_server_request_duration_seconds";
+
+/// Records http server metrics.
+#[derive(Debug)]
+struct Metrics {
+    requests: Counter,
+    active_requests: Gauge,
+    request_duration: Histogram,
+}
+
+impl Metrics {
+    fn new(registry: &Registry, buckets: &[f64]) -> Self {
+        Self {
+            requests: registry.counter(
+                HTTP_SERVER_REQUESTS,
+                "Amount of http requests served.",
+                &["method", "status"],
+            ),
+            active_requests: registry.gauge(
+                HTTP_SERVER_ACTIVE_REQUESTS,
+                "Amount of http requests currently being served.",
+                &["method"],
+            ),
+            request_duration: registry.histogram(
+                HTTP_SERVER_REQUEST_DURATION,
+                "Duration until the http response (head) is returned.",
+                &["method", "status"],
+                buckets,
+            ),
+        }
+    }
+}
+
+/// A layer that records http server metrics in a Prometheus [`Registry`].
+///
+/// Requests are labeled by method and status code, where non-standard methods
+/// are recorded as `_OTHER` and failed requests with the status `error`,
+/// such that the cardinality remains bounded.
+pub struct RequestMetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl fmt::Debug for RequestMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetricsLayer")
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl Clone for RequestMetricsLayer {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl RequestMetricsLayer {
+    /// Create a new [`RequestMetricsLayer`] recording its metrics in the given [`Registry`],
+    /// using the [`DEFAULT_BUCKETS`] for the request latency histogram.
+    pub fn new(registry: &Registry) -> Self {
+        Self::with_buckets(registry, DEFAULT_BUCKETS)
+    }
+
+    /// Create a new [`RequestMetricsLayer`] recording its metrics in the given [`Registry`],
+    /// using the given buckets (in seconds) for the request latency histogram.
+    pub fn with_buckets(registry: &Registry, buckets: &[f64]) -> Self {
+        Self {
+            metrics: Arc::new(Metrics::new(registry, buckets)),
+        }
+    }
+}
+
+impl Default for RequestMetricsLayer {
+    /// Create a new [`RequestMetricsLayer`] recording its metrics
+    /// in the [global `Registry`](Registry::global).
+    fn default() -> Self {
+        Self::new(Registry::global())
+    }
+}
+
+impl<S> Layer<S> for RequestMetricsLayer {
+    type Service = RequestMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// A [`Service`] that records http server metrics in a Prometheus [`Registry`].
+pub struct RequestMetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> RequestMetricsService<S> {
+    /// Create a new [`RequestMetricsService`] recording its metrics in the given [`Registry`].
+    pub fn new(registry: &Registry, inner: S) -> Self {
+        RequestMetricsLayer::new(registry).layer(inner)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for RequestMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetricsService")
+            .field("inner", &self.inner)
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for RequestMetricsService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<S, State, ReqBody, ResBody> Service<State, Request<ReqBody>> for RequestMetricsService<S>
+where
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    State: Clone + Send + Sync + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let method = method_label(req.method());
+
+        self.metrics.active_requests.inc(&[method]);
+        let start = Instant::now();
+
+        let result = self.inner.serve(ctx, req).await;
+
+        self.metrics.active_requests.dec(&[method]);
+        let status = match &result {
+            Ok(res) => res.status().as_str().to_owned(),
+            Err(_) => "error".to_owned(),
+        };
+        let labels = &[method, status.as_str()];
+        self.metrics.requests.inc(labels);
+        self.metrics
+            .request_duration
+            .observe(labels, start.elapsed().as_secs_f64());
+
+        result
+    }
+}
+
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::PATCH => "PATCH",
+        Method::TRACE => "TRACE",
+        _ => "_OTHER",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, IntoResponse, StatusCode};
+    use rama_core::service::service_fn;
+    use rama_core::telemetry::prometheus::ExpositionFormat;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_request_metrics() {
+        let registry = Registry::new();
+        let service =
+            RequestMetricsLayer::new(&registry).layer(service_fn(|req: Request| async move {
+                Ok::<_, Infallible>(if req.uri().path() == "/missing" {
+                    StatusCode::NOT_FOUND.into_response()
+                } else {
+                    Response::new(Body::empty())
+                })
+            }));
+
+        for (method, path) in [("GET", "/"), ("GET", "/missing"), ("FOO", "/")] {
+            let req = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            service.serve(Context::default(), req).await.unwrap();
+        }
+
+        let output = registry.encode(ExpositionFormat::Prometheus);
+        for expected in [
+            r#"rama_http_server_requests_total{method="GET",status="200"} 1"#,
+            r#"rama_http_server_requests_total{method="GET",status="404"} 1"#,
+            r#"rama_http_server_requests_total{method="_OTHER",status="200"} 1"#,
+            r#"rama_http_server_active_requests{method="GET"} 0"#,
+            r#"rama_http_server_request_duration_seconds_count{method="GET",status="404"} 1"#,
+        ] {
+            assert!(
+                output.contains(expected),
+                "{expected} not found in: {output}"
+            );
+        }
+    }
+}
@@ -4,7 +4,7 @@
 
 use crate::header::PROXY_AUTHENTICATE;
 use crate::headers::{HeaderMapExt, ProxyAuthorization, authorization::Credentials};
-use crate::{Request, Response, StatusCode};
+use crate::{HeaderValue, Method, Request, Response, StatusCode};
 use rama_core::{Context, Layer, Service};
 use rama_net::user::{UserId, auth::Authority};
 use rama_utils::macros::define_inner_service_accessors;
@@ -161,6 +161,28 @@ impl<A: Clone, C, S: Clone, L> Clone for ProxyAuthService<A, C, S, L> {
     }
 }
 
+impl<A, C, S, L> ProxyAuthService<A, C, S, L>
+where
+    A: Authority<C, L>,
+    C: Credentials,
+{
+    fn proxy_auth_required<ResBody: Default>(
+        &self,
+        credentials: Option<&C>,
+        method: &Method,
+    ) -> Response<ResBody> {
+        let challenge = self
+            .proxy_auth
+            .challenge(credentials, method)
+            .unwrap_or_else(|| HeaderValue::from_static(C::SCHEME));
+        Response::builder()
+            .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            .header(PROXY_AUTHENTICATE, challenge)
+            .body(Default::default())
+            .unwrap()
+    }
+}
+
 impl<A, C, L, S, State, ReqBody, ResBody> Service<State, Request<ReqBody>>
     for ProxyAuthService<A, C, S, L>
 where
@@ -186,25 +208,21 @@ where
             .map(|h| h.0)
             .or_else(|| ctx.get::<C>().cloned())
         {
-            if let Some(ext) = self.proxy_auth.authorized(credentials).await {
+            if let Some(ext) = self
+                .proxy_auth
+                .authorized_request(credentials.clone(), req.method(), req.uri())
+                .await
+            {
                 ctx.extend(ext);
                 self.inner.serve(ctx, req).await
             } else {
-                Ok(Response::builder()
-                    .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
-                    .header(PROXY_AUTHENTICATE, C::SCHEME)
-                    .body(Default::default())
-                    .unwrap())
+                Ok(self.proxy_auth_required(Some(&credentials), req.method()))
             }
         } else if self.allow_anonymous {
             ctx.insert(UserId::Anonymous);
             self.inner.serve(ctx, req).await
         } else {
-            Ok(Response::builder()
-                .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
-                .header(PROXY_AUTHENTICATE, C::SCHEME)
-                .body(Default::default())
-                .unwrap())
+            Ok(self.proxy_auth_required(None, req.method()))
         }
     }
 }
@@ -7,7 +7,7 @@
 //! [`service::matcher` module]: rama_core
 use crate::Request;
 use rama_core::{Context, context::Extensions, matcher::IteratorMatcherExt};
-use rama_net::{address::Domain, stream::matcher::SocketMatcher};
+use rama_net::{address::Domain, asn::Asn, stream::matcher::SocketMatcher};
 use std::fmt;
 use std::sync::Arc;
 
@@ -566,6 +566,52 @@ impl<State, Body> HttpMatcher<State, Body> {
         self.or(Self::socket(socket))
     }
 
+    /// Create a [`SocketMatcher`] matcher to match on the country of the client.
+    ///
+    /// See [`SocketMatcher::country`] for more information.
+    pub fn country(country: impl Into<String>) -> Self {
+        Self::socket(SocketMatcher::country(country))
+    }
+
+    /// Add a [`SocketMatcher`] matcher to match on the country of the client
+    /// on top of the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`SocketMatcher::country`] for more information.
+    pub fn and_country(self, country: impl Into<String>) -> Self {
+        self.and(Self::country(country))
+    }
+
+    /// Create a [`SocketMatcher`] matcher to match on the country of the client
+    /// as an alternative to the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`SocketMatcher::country`] for more information.
+    pub fn or_country(self, country: impl Into<String>) -> Self {
+        self.or(Self::country(country))
+    }
+
+    /// Create a [`SocketMatcher`] matcher to match on the autonomous system of the client.
+    ///
+    /// See [`SocketMatcher::asn`] for more information.
+    pub fn asn(asn: Asn) -> Self {
+        Self::socket(SocketMatcher::asn(asn))
+    }
+
+    /// Add a [`SocketMatcher`] matcher to match on the autonomous system of the client
+    /// on top of the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`SocketMatcher::asn`] for more information.
+    pub fn and_asn(self, asn: Asn) -> Self {
+        self.and(Self::asn(asn))
+    }
+
+    /// Create a [`SocketMatcher`] matcher to match on the autonomous system of the client
+    /// as an alternative to the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`SocketMatcher::asn`] for more information.
+    pub fn or_asn(self, asn: Asn) -> Self {
+        self.or(Self::asn(asn))
+    }
+
     /// Create a [`PathMatcher`] matcher to match for a GET request.
     pub fn get(path: impl AsRef<str>) -> Self {
         Self::method_get().and_path(path)
@@ -880,4 +926,35 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_matcher_country_and_asn() {
+        use rama_net::geo::GeoInfo;
+
+        let matcher = HttpMatcher::<(), ()>::country("BE")
+            .or_country("NL")
+            .and_asn(Asn::from_static(5432))
+            .negate();
+        let req = Request::builder().body(()).unwrap();
+
+        for (country, asn, expected) in [
+            ("BE", 5432, false),
+            ("nl", 5432, false),
+            ("BE", 6848, true),
+            ("US", 5432, true),
+        ] {
+            let mut ctx = Context::default();
+            ctx.insert(GeoInfo {
+                country: Some(country.to_owned()),
+                asn: Some(Asn::try_from(asn).unwrap()),
+                ..Default::default()
+            });
+            assert_eq!(
+                matcher.matches(None, &ctx, &req),
+                expected,
+                "({matcher:#?}).matches({country}, {asn})",
+            );
+        }
+        assert!(matcher.matches(None, &Context::default(), &req));
+    }
 }
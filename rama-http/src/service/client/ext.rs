@@ -603,6 +603,45 @@ where
         self
     }
 
+    /// Set the given [`multipart::Form`] as a streaming `multipart/form-data` [`Body`] in the [`Request`].
+    ///
+    /// The `Content-Type` header is always overwritten, as it has to contain the boundary of the form,
+    /// and the `Content-Length` header is set as well when the length of all parts is known.
+    ///
+    /// [`multipart::Form`]: super::multipart::Form
+    /// [`Body`]: crate::Body
+    pub fn multipart(mut self, form: super::multipart::Form) -> Self {
+        let content_type = form.content_type();
+        let content_length = form.content_length();
+        let body = form.into_body();
+
+        self.state = match self.state {
+            RequestBuilderState::PreBody(builder) => match builder.body(body) {
+                Ok(req) => RequestBuilderState::PostBody(req),
+                Err(err) => RequestBuilderState::Error(OpaqueError::from_std(err)),
+            },
+            RequestBuilderState::PostBody(mut req) => {
+                *req.body_mut() = body;
+                RequestBuilderState::PostBody(req)
+            }
+            RequestBuilderState::Error(err) => RequestBuilderState::Error(err),
+        };
+
+        if let RequestBuilderState::PostBody(req) = &mut self.state {
+            let headers = req.headers_mut();
+            headers.insert(crate::header::CONTENT_TYPE, content_type);
+            match content_length {
+                Some(length) => {
+                    headers.insert(crate::header::CONTENT_LENGTH, length.into());
+                }
+                None => {
+                    headers.remove(crate::header::CONTENT_LENGTH);
+                }
+            }
+        }
+        self
+    }
+
     /// Set the http [`Version`] of this [`Request`].
     ///
     /// [`Version`]: crate::Version
@@ -652,7 +691,7 @@ mod test {
 
     use super::*;
     use crate::{
-        IntoResponse,
+        BodyExtractExt, IntoResponse,
         layer::{
             required_header::AddRequiredRequestHeadersLayer,
             retry::{ManagedPolicy, RetryLayer},
@@ -734,4 +773,26 @@ mod test {
             .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_client_multipart_overwrites_content_type() {
+        let client = service_fn(async |req: Request| {
+            let content_type = req.headers()[crate::header::CONTENT_TYPE].clone();
+            Ok::<_, Infallible>(Response::new(crate::Body::from(
+                content_type.to_str().unwrap().to_owned(),
+            )))
+        });
+
+        let form = crate::service::client::multipart::Form::new().with_text("title", "hello");
+        let expected = format!("multipart/form-data; boundary={}", form.boundary());
+
+        let response = client
+            .post("http://127.0.0.1:8080")
+            .header(crate::header::CONTENT_TYPE, "multipart/form-data")
+            .multipart(form)
+            .send(Context::default())
+            .await
+            .unwrap();
+        assert_eq!(response.try_into_string().await.unwrap(), expected);
+    }
 }
@@ -3,3 +3,5 @@
 mod ext;
 #[doc(inline)]
 pub use ext::{HttpClientExt, IntoUrl, RequestBuilder};
+
+pub mod multipart;
@@ -0,0 +1,314 @@
+//! `multipart/form-data` request bodies,
+//! to be sent using [`RequestBuilder::multipart`].
+//!
+//! [`RequestBuilder::multipart`]: super::RequestBuilder::multipart
+
+use crate::{Body, HeaderValue, dep::mime};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_lite::{StreamExt, stream};
+use rama_core::error::BoxError;
+use std::{borrow::Cow, fmt, io, path::Path};
+
+/// A `multipart/form-data` request body, consisting of one or more named [`Part`]s.
+///
+/// The parts are streamed as the body is sent,
+/// which means files are never loaded entirely in memory.
+pub struct Form {
+    boundary: String,
+    parts: Vec<(Cow<'static, str>, Part)>,
+}
+
+impl fmt::Debug for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Form")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts)
+            .finish()
+    }
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Form {
+    /// Create a new empty [`Form`], using a random boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: format!("rama-boundary-{}", uuid::Uuid::new_v4().simple()),
+            parts: Vec::new(),
+        }
+    }
+
+    /// The boundary used to separate the parts of this [`Form`].
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Add a text field to this [`Form`].
+    pub fn with_text(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.parts.push((name.into(), Part::text(value)));
+        self
+    }
+
+    /// Add a text field to this [`Form`].
+    pub fn set_text(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.parts.push((name.into(), Part::text(value)));
+        self
+    }
+
+    /// Add a [`Part`] to this [`Form`].
+    pub fn with_part(mut self, name: impl Into<Cow<'static, str>>, part: Part) -> Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// Add a [`Part`] to this [`Form`].
+    pub fn set_part(&mut self, name: impl Into<Cow<'static, str>>, part: Part) -> &mut Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// The `Content-Type` header value to be used for this [`Form`].
+    pub fn content_type(&self) -> HeaderValue {
+        // the boundary is generated by us and thus always a valid header value
+        HeaderValue::try_from(format!("multipart/form-data; boundary={}", self.boundary))
+            .expect("valid multipart content type")
+    }
+
+    /// The length of the encoded [`Form`],
+    /// only known if the length of all its parts is known.
+    pub fn content_length(&self) -> Option<u64> {
+        let mut length = self.boundary.len() as u64 + 6;
+        for (name, part) in &self.parts {
+            length += part.encode_headers(&self.boundary, name).len() as u64;
+            length += part.length? + 2;
+        }
+        Some(length)
+    }
+
+    /// Turn this [`Form`] into a streaming [`Body`].
+    pub fn into_body(self) -> Body {
+        let Self { boundary, parts } = self;
+        let closing = Bytes::from(format!("--{boundary}--\r\n"));
+
+        let parts = stream::iter(parts).flat_map(move |(name, part)| {
+            let headers = part.encode_headers(&boundary, &name);
+            stream::once(Ok::<_, BoxError>(headers))
+                .chain(part.body.into_data_stream())
+                .chain(stream::once(Ok(Bytes::from_static(b"\r\n"))))
+        });
+        Body::from_stream(parts.chain(stream::once(Ok(closing))))
+    }
+}
+
+/// A single part of a multipart [`Form`].
+pub struct Part {
+    body: Body,
+    length: Option<u64>,
+    file_name: Option<Cow<'static, str>>,
+    content_type: Option<mime::Mime>,
+}
+
+impl fmt::Debug for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Part")
+            .field("length", &self.length)
+            .field("file_name", &self.file_name)
+            .field("content_type", &self.content_type)
+            .finish()
+    }
+}
+
+impl Part {
+    /// Create a [`Part`] containing the given text.
+    pub fn text(value: impl Into<Cow<'static, str>>) -> Self {
+        match value.into() {
+            Cow::Borrowed(value) => Self::bytes(value.as_bytes()),
+            Cow::Owned(value) => Self::bytes(value.into_bytes()),
+        }
+    }
+
+    /// Create a [`Part`] containing the given bytes.
+    pub fn bytes(value: impl Into<Bytes>) -> Self {
+        let value = value.into();
+        Self {
+            length: Some(value.len() as u64),
+            body: Body::from(value),
+            file_name: None,
+            content_type: None,
+        }
+    }
+
+    /// Create a [`Part`] streaming the given [`Body`], of unknown length.
+    pub fn stream(body: impl Into<Body>) -> Self {
+        Self {
+            body: body.into(),
+            length: None,
+            file_name: None,
+            content_type: None,
+        }
+    }
+
+    /// Create a [`Part`] streaming the given [`Body`] of the given length.
+    pub fn stream_with_length(body: impl Into<Body>, length: u64) -> Self {
+        Self {
+            body: body.into(),
+            length: Some(length),
+            file_name: None,
+            content_type: None,
+        }
+    }
+
+    /// Create a [`Part`] streaming the file at the given path.
+    ///
+    /// The file name and content type are derived from the path.
+    pub async fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+
+        let mut part = Self::stream_with_length(
+            Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+            length,
+        )
+        .with_content_type(mime_guess::from_path(path).first_or_octet_stream());
+        if let Some(file_name) = path.file_name() {
+            part.set_file_name(file_name.to_string_lossy().into_owned());
+        }
+        Ok(part)
+    }
+
+    /// Set the file name of this [`Part`].
+    pub fn with_file_name(mut self, file_name: impl Into<Cow<'static, str>>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the file name of this [`Part`].
+    pub fn set_file_name(&mut self, file_name: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the content type of this [`Part`].
+    pub fn with_content_type(mut self, content_type: mime::Mime) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// Set the content type of this [`Part`].
+    pub fn set_content_type(&mut self, content_type: mime::Mime) -> &mut Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    fn encode_headers(&self, boundary: &str, name: &str) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_slice(b"--");
+        buf.put_slice(boundary.as_bytes());
+        buf.put_slice(b"\r\nContent-Disposition: form-data; name=\"");
+        put_quoted(&mut buf, name);
+        buf.put_u8(b'"');
+        if let Some(file_name) = &self.file_name {
+            buf.put_slice(b"; filename=\"");
+            put_quoted(&mut buf, file_name);
+            buf.put_u8(b'"');
+        }
+        if let Some(content_type) = &self.content_type {
+            buf.put_slice(b"\r\nContent-Type: ");
+            buf.put_slice(content_type.as_ref().as_bytes());
+        }
+        buf.put_slice(b"\r\n\r\n");
+        buf.freeze()
+    }
+}
+
+/// Write a quoted parameter value, percent-encoding
+/// the characters that cannot appear in it, as browsers do.
+fn put_quoted(buf: &mut BytesMut, value: &str) {
+    for b in value.bytes() {
+        match b {
+            b'"' => buf.put_slice(b"%22"),
+            b'\r' => buf.put_slice(b"%0D"),
+            b'\n' => buf.put_slice(b"%0A"),
+            b => buf.put_u8(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::service::web::extract::Multipart;
+
+    #[tokio::test]
+    async fn test_form_encoding() {
+        let form = Form::new().with_text("title", "hello").with_part(
+            "file",
+            Part::bytes("some \"data\"")
+                .with_file_name("a\"b.txt")
+                .with_content_type(mime::TEXT_PLAIN),
+        );
+        let boundary = form.boundary().to_owned();
+        let content_length = form.content_length().unwrap();
+
+        let body = form.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.len() as u64, content_length);
+        assert_eq!(
+            body,
+            format!(
+                "--{boundary}\r\n\
+                Content-Disposition: form-data; name=\"title\"\r\n\
+                \r\n\
+                hello\r\n\
+                --{boundary}\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"a%22b.txt\"\r\n\
+                Content-Type: text/plain\r\n\
+                \r\n\
+                some \"data\"\r\n\
+                --{boundary}--\r\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_form_roundtrip_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload.json");
+        tokio::fs::write(&path, r#"{"hello":"world"}"#)
+            .await
+            .unwrap();
+
+        let form = Form::new()
+            .with_part("stream", Part::stream(Body::from("streamed")))
+            .with_part("file", Part::file(&path).await.unwrap());
+        assert!(form.content_length().is_none());
+        let boundary = form.boundary().to_owned();
+
+        let mut multipart = Multipart::new(form.into_body(), boundary);
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("stream"));
+        assert_eq!(field.text().await.unwrap(), "streamed");
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert_eq!(field.file_name(), Some("upload.json"));
+        assert_eq!(field.content_type(), Some("application/json"));
+        assert_eq!(field.text().await.unwrap(), r#"{"hello":"world"}"#);
+
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use super::{Codec, CompressionEncoding, Status, codec::encode_frame};
+use crate::{
+    HeaderMap,
+    dep::http_body::{self, Frame},
+};
+use bytes::{Bytes, BytesMut};
+use futures_lite::Stream;
+use pin_project_lite::pin_project;
+use rama_core::error::BoxError;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use sync_wrapper::SyncWrapper;
+use tokio::time::Sleep;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Role {
+    /// Encoding the response messages, as a server,
+    /// which means the body ends with the grpc status trailers.
+    Server,
+    /// Encoding the request messages, as a client.
+    Client,
+}
+
+pin_project! {
+    /// An http body encoding a [`Stream`] of gRPC messages.
+    ///
+    /// [`Stream`]: futures_lite::Stream
+    pub(super) struct EncodeBody<S, C> {
+        #[pin]
+        stream: SyncWrapper<S>,
+        codec: C,
+        encoding: CompressionEncoding,
+        role: Role,
+        #[pin]
+        deadline: Option<Sleep>,
+        done: bool,
+    }
+}
+
+impl<S, C> EncodeBody<S, C> {
+    pub(super) fn new(
+        stream: S,
+        codec: C,
+        encoding: CompressionEncoding,
+        role: Role,
+        deadline: Option<Sleep>,
+    ) -> Self {
+        Self {
+            stream: SyncWrapper::new(stream),
+            codec,
+            encoding,
+            role,
+            deadline,
+            done: false,
+        }
+    }
+}
+
+impl<S, C> http_body::Body for EncodeBody<S, C>
+where
+    S: Stream<Item = Result<C::Encode, Status>>,
+    C: Codec,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let result = match this.stream.get_pin_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(item))) => {
+                let mut buf = BytesMut::new();
+                match this
+                    .codec
+                    .encode(item, &mut buf)
+                    .and_then(|_| encode_frame(buf.freeze(), *this.encoding))
+                {
+                    Ok(frame) => return Poll::Ready(Some(Ok(Frame::data(frame)))),
+                    Err(status) => Err(status),
+                }
+            }
+            Poll::Ready(Some(Err(status))) => Err(status),
+            Poll::Ready(None) => Ok(()),
+            Poll::Pending => match this.deadline.as_mut().as_pin_mut() {
+                Some(deadline) => {
+                    futures_lite::ready!(deadline.poll(cx));
+                    Err(Status::deadline_exceeded("grpc deadline exceeded"))
+                }
+                None => return Poll::Pending,
+            },
+        };
+
+        *this.done = true;
+        match (this.role, result) {
+            (Role::Server, Ok(())) => {
+                Poll::Ready(Some(Ok(Frame::trailers(status_trailers(&Status::ok(""))))))
+            }
+            (Role::Server, Err(status)) => {
+                Poll::Ready(Some(Ok(Frame::trailers(status_trailers(&status)))))
+            }
+            (Role::Client, Ok(())) => Poll::Ready(None),
+            (Role::Client, Err(status)) => Poll::Ready(Some(Err(status.into()))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+fn status_trailers(status: &Status) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    status.add_header(&mut trailers);
+    trailers
+}
@@ -0,0 +1,246 @@
+use super::{
+    Codec, CompressionEncoding, DEFAULT_MAX_MESSAGE_SIZE, GRPC_ACCEPT_ENCODING, GRPC_ENCODING,
+    GRPC_TIMEOUT, GrpcRequest, GrpcResponse, Status, Streaming,
+    body::{EncodeBody, Role},
+    codec::Direction,
+    encode_grpc_timeout, is_grpc_content_type,
+    server::{deadline_from_timeout, with_deadline},
+};
+use crate::{
+    Body, HeaderValue, Method, Request, Response, StatusCode, Uri, Version,
+    dep::http::uri::PathAndQuery,
+    header::{CONTENT_TYPE, TE},
+};
+use futures_lite::{Stream, StreamExt, stream};
+use rama_core::{Context, Service, error::BoxError};
+use std::time::Duration;
+
+/// A gRPC client, built on top of any http client [`Service`],
+/// such as the `HttpClient` provided by `rama-http-backend`.
+///
+/// Requests are sent as HTTP/2 requests to the configured origin.
+#[derive(Debug, Clone)]
+pub struct GrpcClient<S, C> {
+    inner: S,
+    codec: C,
+    origin: Uri,
+    send_compression: CompressionEncoding,
+    accept_compression: Vec<CompressionEncoding>,
+    max_message_size: usize,
+    timeout: Option<Duration>,
+}
+
+impl<S, C> GrpcClient<S, C> {
+    /// Create a new [`GrpcClient`] sending requests using the given http client
+    /// to the given origin (scheme and authority), encoding messages with the given [`Codec`].
+    pub fn new(inner: S, codec: C, origin: Uri) -> Self {
+        Self {
+            inner,
+            codec,
+            origin,
+            send_compression: CompressionEncoding::Identity,
+            accept_compression: Vec::new(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            timeout: None,
+        }
+    }
+
+    /// Compress request messages with the given encoding.
+    pub fn with_send_compression(mut self, encoding: CompressionEncoding) -> Self {
+        self.send_compression = encoding;
+        self
+    }
+
+    /// Compress request messages with the given encoding.
+    pub fn set_send_compression(&mut self, encoding: CompressionEncoding) -> &mut Self {
+        self.send_compression = encoding;
+        self
+    }
+
+    /// Accept response messages compressed with the given encoding.
+    pub fn with_accept_compression(mut self, encoding: CompressionEncoding) -> Self {
+        self.accept_compression.push(encoding);
+        self
+    }
+
+    /// Accept response messages compressed with the given encoding.
+    pub fn set_accept_compression(&mut self, encoding: CompressionEncoding) -> &mut Self {
+        self.accept_compression.push(encoding);
+        self
+    }
+
+    /// Set the maximum size of a single (decompressed) response message.
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Set the maximum size of a single (decompressed) response message.
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn set_max_message_size(&mut self, size: usize) -> &mut Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Set the timeout of each call, which is also
+    /// communicated to the server using the `grpc-timeout` header.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout of each call, which is also
+    /// communicated to the server using the `grpc-timeout` header.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<S, C> GrpcClient<S, C>
+where
+    C: Codec,
+{
+    /// Call a unary gRPC method, e.g. `/helloworld.Greeter/SayHello`.
+    pub async fn unary<State>(
+        &self,
+        ctx: Context<State>,
+        path: &str,
+        request: GrpcRequest<C::Encode>,
+    ) -> Result<GrpcResponse<C::Decode>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error: Into<BoxError>>,
+    {
+        let (metadata, extensions, message) = request.into_parts();
+        let request = GrpcRequest::from_parts(metadata, extensions, stream::once(message));
+
+        let call = async {
+            let (metadata, mut messages) = self.streaming(ctx, path, request).await?.into_parts();
+            let message = messages
+                .message()
+                .await?
+                .ok_or_else(|| Status::internal("missing response message"))?;
+            if messages.message().await?.is_some() {
+                return Err(Status::internal("unexpected second response message"));
+            }
+            Ok(GrpcResponse::from_parts(metadata, message))
+        };
+
+        with_deadline(deadline_from_timeout(self.timeout), call).await
+    }
+
+    /// Call a streaming gRPC method, e.g. `/routeguide.RouteGuide/RouteChat`.
+    ///
+    /// The returned [`Streaming`] yields the response messages,
+    /// ending with an error in case the call did not succeed.
+    pub async fn streaming<State, M>(
+        &self,
+        ctx: Context<State>,
+        path: &str,
+        request: GrpcRequest<M>,
+    ) -> Result<GrpcResponse<Streaming<C>>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error: Into<BoxError>>,
+        M: Stream<Item = C::Encode> + Send + 'static,
+    {
+        let req = self.build_request(path, request)?;
+
+        let res = with_deadline(deadline_from_timeout(self.timeout), async {
+            self.inner
+                .serve(ctx, req)
+                .await
+                .map_err(|err| Status::unavailable(err.into().to_string()))
+        })
+        .await?;
+
+        self.handle_response(res)
+    }
+
+    fn build_request<M>(&self, path: &str, request: GrpcRequest<M>) -> Result<Request, Status>
+    where
+        M: Stream<Item = C::Encode> + Send + 'static,
+    {
+        let path_and_query = PathAndQuery::try_from(path)
+            .map_err(|err| Status::invalid_argument(format!("invalid grpc method path: {err}")))?;
+        let mut uri_parts = self.origin.clone().into_parts();
+        uri_parts.path_and_query = Some(path_and_query);
+        let uri = Uri::from_parts(uri_parts)
+            .map_err(|err| Status::invalid_argument(format!("invalid grpc uri: {err}")))?;
+
+        let (metadata, extensions, messages) = request.into_parts();
+        let body = EncodeBody::new(
+            messages.map(Ok::<_, Status>),
+            self.codec.clone(),
+            self.send_compression,
+            Role::Client,
+            None,
+        );
+
+        let mut req = Request::new(Body::new(body));
+        *req.method_mut() = Method::POST;
+        *req.uri_mut() = uri;
+        *req.version_mut() = Version::HTTP_2;
+        *req.extensions_mut() = extensions;
+
+        let headers = req.headers_mut();
+        *headers = metadata;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+        if self.send_compression != CompressionEncoding::Identity {
+            headers.insert(&GRPC_ENCODING, self.send_compression.into_header_value());
+        }
+        if let Some(value) =
+            CompressionEncoding::accept_encoding_header_value(&self.accept_compression)
+        {
+            headers.insert(&GRPC_ACCEPT_ENCODING, value);
+        }
+        if let Some(timeout) = self.timeout {
+            headers.insert(&GRPC_TIMEOUT, encode_grpc_timeout(timeout));
+        }
+
+        Ok(req)
+    }
+
+    fn handle_response(&self, res: Response) -> Result<GrpcResponse<Streaming<C>>, Status> {
+        let (parts, body) = res.into_parts();
+
+        if parts.status != StatusCode::OK {
+            return Err(Status::from_http_status(parts.status));
+        }
+
+        // trailers-only response
+        let header_status = Status::from_header_map(&parts.headers);
+        if let Some(status) = header_status.as_ref().filter(|status| !status.is_ok()) {
+            return Err(status.clone());
+        }
+
+        if !parts
+            .headers
+            .get(CONTENT_TYPE)
+            .map(is_grpc_content_type)
+            .unwrap_or_default()
+        {
+            return Err(Status::unknown("response is not a grpc response"));
+        }
+
+        let encoding =
+            CompressionEncoding::from_encoding_header(&parts.headers, &self.accept_compression)?;
+        let mut messages = Streaming::new(
+            body,
+            self.codec.clone(),
+            encoding,
+            self.max_message_size,
+            Direction::Response,
+        );
+        if header_status.is_some() {
+            messages.set_trailers(parts.headers.clone());
+        }
+
+        Ok(GrpcResponse::from_parts(parts.headers, messages))
+    }
+}
@@ -0,0 +1,356 @@
+use super::{CompressionEncoding, Status};
+use crate::{Body, HeaderMap, dep::http_body::Body as _};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_lite::{Stream, StreamExt};
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Size of the header that prefixes each gRPC message:
+/// a single compression flag byte followed by a big-endian `u32` length.
+pub(super) const HEADER_SIZE: usize = 5;
+
+/// Default maximum size of a single (decompressed) gRPC message: 4 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Encode and decode gRPC messages to and from their wire format.
+///
+/// Rama does not ship a protobuf implementation,
+/// instead you can implement this trait for the (de)serializer of your choice,
+/// e.g. using `prost::Message`.
+pub trait Codec: Clone + Send + Sync + 'static {
+    /// The type of messages encoded by this codec.
+    type Encode: Send + 'static;
+    /// The type of messages decoded by this codec.
+    type Decode: Send + 'static;
+
+    /// Encode a message into the given buffer.
+    fn encode(&self, item: Self::Encode, buf: &mut BytesMut) -> Result<(), Status>;
+
+    /// Decode a message from the given buffer.
+    fn decode(&self, buf: Bytes) -> Result<Self::Decode, Status>;
+}
+
+/// A [`Codec`] that passes the raw message bytes as-is.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct BytesCodec;
+
+impl BytesCodec {
+    /// Create a new [`BytesCodec`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Codec for BytesCodec {
+    type Encode = Bytes;
+    type Decode = Bytes;
+
+    fn encode(&self, item: Self::Encode, buf: &mut BytesMut) -> Result<(), Status> {
+        buf.put(item);
+        Ok(())
+    }
+
+    fn decode(&self, buf: Bytes) -> Result<Self::Decode, Status> {
+        Ok(buf)
+    }
+}
+
+/// Frame an (already encoded) message as a length-prefixed gRPC message,
+/// compressing it using the given encoding.
+pub fn encode_frame(message: Bytes, encoding: CompressionEncoding) -> Result<Bytes, Status> {
+    let compressed = encoding != CompressionEncoding::Identity;
+    let message = encoding.compress(message)?;
+    let len = u32::try_from(message.len())
+        .map_err(|_| Status::resource_exhausted("message too large to be framed"))?;
+
+    let mut buf = BytesMut::with_capacity(HEADER_SIZE + message.len());
+    buf.put_u8(compressed as u8);
+    buf.put_u32(len);
+    buf.put(message);
+    Ok(buf.freeze())
+}
+
+/// Try to split a single length-prefixed gRPC message from the given buffer.
+///
+/// Returns `Ok(None)` if the buffer does not yet contain a full message.
+/// The returned message is decompressed using the given encoding in case it was compressed.
+pub fn decode_frame(
+    buf: &mut BytesMut,
+    encoding: CompressionEncoding,
+    max_message_size: usize,
+) -> Result<Option<Bytes>, Status> {
+    if buf.len() < HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let compressed = match buf[0] {
+        0 => false,
+        1 => true,
+        flag => {
+            return Err(Status::internal(format!(
+                "invalid grpc message compression flag: {flag}"
+            )));
+        }
+    };
+    let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+    if len > max_message_size {
+        return Err(Status::resource_exhausted(format!(
+            "grpc message of {len} bytes larger than max ({max_message_size} bytes)"
+        )));
+    }
+    if buf.len() < HEADER_SIZE + len {
+        buf.reserve(HEADER_SIZE + len - buf.len());
+        return Ok(None);
+    }
+
+    buf.advance(HEADER_SIZE);
+    let message = buf.split_to(len).freeze();
+
+    if compressed {
+        if encoding == CompressionEncoding::Identity {
+            return Err(Status::internal(
+                "received compressed grpc message without grpc-encoding",
+            ));
+        }
+        encoding.decompress(message, max_message_size).map(Some)
+    } else {
+        Ok(Some(message))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Direction {
+    /// Decoding the request messages, as a server.
+    Request,
+    /// Decoding the response messages, as a client,
+    /// which means the trailers are expected to contain the grpc status.
+    Response,
+}
+
+/// A [`Stream`] of gRPC messages, decoded from an http [`Body`].
+///
+/// Used by servers to receive the request messages,
+/// and by clients to receive the response messages.
+///
+/// [`Stream`]: futures_lite::Stream
+pub struct Streaming<C> {
+    body: Body,
+    codec: C,
+    buf: BytesMut,
+    encoding: CompressionEncoding,
+    max_message_size: usize,
+    direction: Direction,
+    trailers: Option<HeaderMap>,
+    body_done: bool,
+    done: bool,
+}
+
+impl<C> fmt::Debug for Streaming<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Streaming")
+            .field("encoding", &self.encoding)
+            .field("max_message_size", &self.max_message_size)
+            .field("direction", &self.direction)
+            .field("trailers", &self.trailers)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<C> Unpin for Streaming<C> {}
+
+impl<C: Codec> Streaming<C> {
+    pub(super) fn new(
+        body: Body,
+        codec: C,
+        encoding: CompressionEncoding,
+        max_message_size: usize,
+        direction: Direction,
+    ) -> Self {
+        Self {
+            body,
+            codec,
+            buf: BytesMut::new(),
+            encoding,
+            max_message_size,
+            direction,
+            trailers: None,
+            body_done: false,
+            done: false,
+        }
+    }
+
+    pub(super) fn set_trailers(&mut self, trailers: HeaderMap) {
+        self.trailers = Some(trailers);
+    }
+
+    /// Receive the next message, if any.
+    pub async fn message(&mut self) -> Result<Option<C::Decode>, Status> {
+        self.next().await.transpose()
+    }
+
+    /// Return the trailers received at the end of the stream, if any.
+    ///
+    /// Only available once the stream is fully consumed.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.trailers.as_ref()
+    }
+
+    fn poll_end(&mut self) -> Option<Result<C::Decode, Status>> {
+        self.done = true;
+
+        if !self.buf.is_empty() {
+            return Some(Err(Status::internal(
+                "unexpected end of stream while decoding grpc message",
+            )));
+        }
+
+        if self.direction == Direction::Response {
+            return match self.trailers.as_ref().and_then(Status::from_header_map) {
+                Some(status) if status.is_ok() => None,
+                Some(status) => Some(Err(status)),
+                None => Some(Err(Status::internal("missing grpc-status trailer"))),
+            };
+        }
+
+        None
+    }
+}
+
+impl<C: Codec> Stream for Streaming<C> {
+    type Item = Result<C::Decode, Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            match decode_frame(&mut this.buf, this.encoding, this.max_message_size) {
+                Ok(Some(message)) => return Poll::Ready(Some(this.codec.decode(message))),
+                Ok(None) => (),
+                Err(status) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(status)));
+                }
+            }
+
+            if this.body_done {
+                return Poll::Ready(this.poll_end());
+            }
+
+            match futures_lite::ready!(Pin::new(&mut this.body).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.buf.put(data),
+                    Err(frame) => {
+                        if let Ok(trailers) = frame.into_trailers() {
+                            match this.trailers.as_mut() {
+                                Some(existing) => existing.extend(trailers),
+                                None => this.trailers = Some(trailers),
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(Status::unknown(format!(
+                        "grpc body error: {err}"
+                    )))));
+                }
+                None => this.body_done = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let frame =
+            encode_frame(Bytes::from_static(b"hello"), CompressionEncoding::Identity).unwrap();
+        assert_eq!(&frame[..], b"\x00\x00\x00\x00\x05hello");
+
+        let mut buf = BytesMut::from(&frame[..3]);
+        assert_eq!(
+            decode_frame(&mut buf, CompressionEncoding::Identity, 1024).unwrap(),
+            None
+        );
+        buf.extend_from_slice(&frame[3..]);
+        assert_eq!(
+            decode_frame(&mut buf, CompressionEncoding::Identity, 1024).unwrap(),
+            Some(Bytes::from_static(b"hello"))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let frame =
+            encode_frame(Bytes::from_static(b"hello"), CompressionEncoding::Identity).unwrap();
+        let mut buf = BytesMut::from(&frame[..]);
+        let status = decode_frame(&mut buf, CompressionEncoding::Identity, 4).unwrap_err();
+        assert_eq!(
+            status.code(),
+            crate::layer::classify::GrpcCode::ResourceExhausted
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_frame_roundtrip_gzip() {
+        let frame = encode_frame(Bytes::from_static(b"hello"), CompressionEncoding::Gzip).unwrap();
+        assert_eq!(frame[0], 1);
+        let mut buf = BytesMut::from(&frame[..]);
+        assert_eq!(
+            decode_frame(&mut buf, CompressionEncoding::Gzip, 1024).unwrap(),
+            Some(Bytes::from_static(b"hello"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_response_status() {
+        use crate::dep::http_body::Frame;
+
+        let mut data = BytesMut::new();
+        data.put(encode_frame(Bytes::from_static(b"a"), CompressionEncoding::Identity).unwrap());
+        data.put(encode_frame(Bytes::from_static(b"b"), CompressionEncoding::Identity).unwrap());
+        let mut trailers = HeaderMap::new();
+        Status::not_found("nope").add_header(&mut trailers);
+
+        let body = Body::new(crate::dep::http_body_util::StreamBody::new(
+            futures_lite::stream::iter(vec![
+                Ok::<_, std::convert::Infallible>(Frame::data(data.freeze())),
+                Ok(Frame::trailers(trailers)),
+            ]),
+        ));
+
+        let mut stream = Streaming::new(
+            body,
+            BytesCodec,
+            CompressionEncoding::Identity,
+            DEFAULT_MAX_MESSAGE_SIZE,
+            Direction::Response,
+        );
+        assert_eq!(
+            stream.message().await.unwrap(),
+            Some(Bytes::from_static(b"a"))
+        );
+        assert_eq!(
+            stream.message().await.unwrap(),
+            Some(Bytes::from_static(b"b"))
+        );
+        assert_eq!(
+            stream.message().await.unwrap_err(),
+            Status::not_found("nope")
+        );
+        assert_eq!(stream.message().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{GRPC_ACCEPT_ENCODING, GRPC_ENCODING, Status};
+use crate::{HeaderMap, HeaderValue};
+use bytes::Bytes;
+use std::fmt;
+
+/// The compression encodings supported for gRPC messages.
+///
+/// Compression other than [`CompressionEncoding::Identity`]
+/// requires the `compression` feature to be enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum CompressionEncoding {
+    /// No compression.
+    #[default]
+    Identity,
+    #[cfg(feature = "compression")]
+    /// Gzip compression.
+    Gzip,
+}
+
+impl CompressionEncoding {
+    /// All encodings supported by this build.
+    pub const ALL: &'static [Self] = &[
+        Self::Identity,
+        #[cfg(feature = "compression")]
+        Self::Gzip,
+    ];
+
+    /// Return the name of this encoding as used in the `grpc-encoding` header.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Identity => "identity",
+            #[cfg(feature = "compression")]
+            Self::Gzip => "gzip",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|e| e.as_str().eq_ignore_ascii_case(s.trim()))
+    }
+
+    /// Determine the encoding of received messages from the `grpc-encoding` header,
+    /// only accepting the given encodings.
+    ///
+    /// A [`Status`] with [`GrpcCode::Unimplemented`] is returned for unsupported encodings.
+    ///
+    /// [`GrpcCode::Unimplemented`]: crate::layer::classify::GrpcCode::Unimplemented
+    pub fn from_encoding_header(headers: &HeaderMap, accept: &[Self]) -> Result<Self, Status> {
+        let Some(value) = headers.get(&GRPC_ENCODING) else {
+            return Ok(Self::Identity);
+        };
+        let value = value.to_str().unwrap_or_default();
+        match Self::parse(value) {
+            Some(encoding) if encoding == Self::Identity || accept.contains(&encoding) => {
+                Ok(encoding)
+            }
+            _ => Err(Status::unimplemented(format!(
+                "message compressed with unsupported encoding: {value}"
+            ))),
+        }
+    }
+
+    /// Select the encoding to send messages with,
+    /// based on the `grpc-accept-encoding` header of the peer
+    /// and the encodings enabled locally (in order of preference).
+    pub fn from_accept_encoding_header(headers: &HeaderMap, enabled: &[Self]) -> Self {
+        let Some(accept) = headers
+            .get(&GRPC_ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Self::Identity;
+        };
+        enabled
+            .iter()
+            .copied()
+            .find(|encoding| {
+                accept
+                    .split(',')
+                    .any(|value| Self::parse(value) == Some(*encoding))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Create a `grpc-accept-encoding` header value for the given encodings.
+    pub fn accept_encoding_header_value(encodings: &[Self]) -> Option<HeaderValue> {
+        let value = encodings
+            .iter()
+            .filter(|e| **e != Self::Identity)
+            .map(|e| e.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        if value.is_empty() {
+            None
+        } else {
+            HeaderValue::try_from(value).ok()
+        }
+    }
+
+    /// Return this encoding as a [`HeaderValue`].
+    pub fn into_header_value(self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+
+    pub(super) fn compress(self, data: Bytes) -> Result<Bytes, Status> {
+        match self {
+            Self::Identity => Ok(data),
+            #[cfg(feature = "compression")]
+            Self::Gzip => {
+                use std::io::Write;
+
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::with_capacity(data.len()),
+                    flate2::Compression::default(),
+                );
+                encoder
+                    .write_all(&data)
+                    .map_err(|err| Status::internal(format!("gzip compress message: {err}")))?;
+                encoder
+                    .finish()
+                    .map(Bytes::from)
+                    .map_err(|err| Status::internal(format!("gzip compress message: {err}")))
+            }
+        }
+    }
+
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    pub(super) fn decompress(self, data: Bytes, max_size: usize) -> Result<Bytes, Status> {
+        match self {
+            Self::Identity => Ok(data),
+            #[cfg(feature = "compression")]
+            Self::Gzip => {
+                use std::io::Read;
+
+                let mut out = Vec::with_capacity(data.len() * 2);
+                flate2::read::GzDecoder::new(&data[..])
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut out)
+                    .map_err(|err| Status::internal(format!("gzip decompress message: {err}")))?;
+                if out.len() > max_size {
+                    return Err(Status::resource_exhausted(format!(
+                        "decompressed message larger than max ({max_size} bytes)"
+                    )));
+                }
+                Ok(out.into())
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompressionEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
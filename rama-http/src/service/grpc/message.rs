@@ -0,0 +1,113 @@
+use crate::{HeaderMap, dep::http::Extensions};
+
+/// A gRPC request, the message(s) together with its metadata.
+#[derive(Debug)]
+pub struct GrpcRequest<T> {
+    metadata: HeaderMap,
+    extensions: Extensions,
+    message: T,
+}
+
+impl<T> GrpcRequest<T> {
+    /// Create a new [`GrpcRequest`] without any metadata.
+    pub fn new(message: T) -> Self {
+        Self {
+            metadata: HeaderMap::new(),
+            extensions: Extensions::new(),
+            message,
+        }
+    }
+
+    pub(super) fn from_parts(metadata: HeaderMap, extensions: Extensions, message: T) -> Self {
+        Self {
+            metadata,
+            extensions,
+            message,
+        }
+    }
+
+    /// Return the metadata (custom headers) of this request.
+    pub fn metadata(&self) -> &HeaderMap {
+        &self.metadata
+    }
+
+    /// Return the metadata (custom headers) of this request mutably.
+    pub fn metadata_mut(&mut self) -> &mut HeaderMap {
+        &mut self.metadata
+    }
+
+    /// Return the extensions of the underlying http request.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Return the extensions of the underlying http request mutably.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Return a reference to the message of this request.
+    pub fn get_ref(&self) -> &T {
+        &self.message
+    }
+
+    /// Return a mutable reference to the message of this request.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.message
+    }
+
+    /// Consume this request, returning its message.
+    pub fn into_inner(self) -> T {
+        self.message
+    }
+
+    /// Consume this request, returning its metadata, extensions and message.
+    pub fn into_parts(self) -> (HeaderMap, Extensions, T) {
+        (self.metadata, self.extensions, self.message)
+    }
+}
+
+impl<T> From<T> for GrpcRequest<T> {
+    fn from(message: T) -> Self {
+        Self::new(message)
+    }
+}
+
+/// A gRPC response as received by a client,
+/// the message(s) together with its metadata.
+#[derive(Debug)]
+pub struct GrpcResponse<T> {
+    metadata: HeaderMap,
+    message: T,
+}
+
+impl<T> GrpcResponse<T> {
+    pub(super) fn from_parts(metadata: HeaderMap, message: T) -> Self {
+        Self { metadata, message }
+    }
+
+    /// Return the metadata (headers) of this response.
+    pub fn metadata(&self) -> &HeaderMap {
+        &self.metadata
+    }
+
+    /// Return a reference to the message of this response.
+    pub fn get_ref(&self) -> &T {
+        &self.message
+    }
+
+    /// Return a mutable reference to the message of this response.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.message
+    }
+
+    /// Consume this response, returning its message.
+    pub fn into_inner(self) -> T {
+        self.message
+    }
+
+    /// Consume this response, returning its metadata and message.
+    pub fn into_parts(self) -> (HeaderMap, T) {
+        (self.metadata, self.message)
+    }
+}
@@ -0,0 +1,92 @@
+//! gRPC support on top of rama http.
+//!
+//! This module provides the building blocks to serve and call gRPC methods:
+//!
+//! - length-prefixed message framing, see [`encode_frame`], [`decode_frame`] and [`Streaming`];
+//! - the `grpc-status` and `grpc-message` trailer model, see [`Status`];
+//! - deadlines via the `grpc-timeout` header, see [`try_parse_grpc_timeout`];
+//! - message compression negotiation, see [`CompressionEncoding`];
+//! - [`unary`] and [`streaming`] server services, usable as endpoints of a [`WebService`];
+//! - a [`GrpcClient`] built on top of any http client, such as `HttpClient`, over HTTP/2.
+//!
+//! Rama does not ship a protobuf implementation. Messages are (de)serialized
+//! using a [`Codec`], which you can implement for the protobuf library of your choice.
+//! The [`BytesCodec`] can be used to work with the raw message bytes.
+//!
+//! Browsers can reach gRPC services through the [`GrpcWebLayer`].
+//!
+//! # Example
+//!
+//! ```
+//! use bytes::Bytes;
+//! use rama_core::Context;
+//! use rama_http::service::grpc::{self, BytesCodec, GrpcRequest, Status};
+//! use rama_http::service::web::WebService;
+//!
+//! async fn echo(_ctx: Context<()>, req: GrpcRequest<Bytes>) -> Result<Bytes, Status> {
+//!     Ok(req.into_inner())
+//! }
+//!
+//! let service = WebService::default()
+//!     .post("/echo.Echo/Echo", grpc::unary(BytesCodec::new(), echo));
+//! ```
+//!
+//! [`WebService`]: crate::service::web::WebService
+//! [`GrpcWebLayer`]: crate::layer::grpc_web::GrpcWebLayer
+
+use crate::{HeaderName, HeaderValue};
+
+mod body;
+
+mod codec;
+#[doc(inline)]
+pub use codec::{
+    BytesCodec, Codec, DEFAULT_MAX_MESSAGE_SIZE, Streaming, decode_frame, encode_frame,
+};
+
+mod compression;
+#[doc(inline)]
+pub use compression::CompressionEncoding;
+
+mod message;
+#[doc(inline)]
+pub use message::{GrpcRequest, GrpcResponse};
+
+mod status;
+#[doc(inline)]
+pub use status::Status;
+
+mod timeout;
+#[doc(inline)]
+pub use timeout::{encode_grpc_timeout, try_parse_grpc_timeout};
+
+mod server;
+#[doc(inline)]
+pub use server::{StreamingService, UnaryService, streaming, unary};
+
+mod client;
+#[doc(inline)]
+pub use client::GrpcClient;
+
+#[doc(inline)]
+pub use crate::layer::classify::GrpcCode;
+
+/// Header name constant for `grpc-status`.
+pub static GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+/// Header name constant for `grpc-message`.
+pub static GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+/// Header name constant for `grpc-timeout`.
+pub static GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+/// Header name constant for `grpc-encoding`.
+pub static GRPC_ENCODING: HeaderName = HeaderName::from_static("grpc-encoding");
+/// Header name constant for `grpc-accept-encoding`.
+pub static GRPC_ACCEPT_ENCODING: HeaderName = HeaderName::from_static("grpc-accept-encoding");
+
+/// Returns `true` if the given `Content-Type` header value
+/// denotes a gRPC message, e.g. `application/grpc` or `application/grpc+proto`.
+pub fn is_grpc_content_type(value: &HeaderValue) -> bool {
+    let value = value.as_bytes();
+    value.len() >= 16
+        && value[..16].eq_ignore_ascii_case(b"application/grpc")
+        && matches!(value.get(16), None | Some(b'+' | b';'))
+}
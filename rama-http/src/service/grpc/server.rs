@@ -0,0 +1,346 @@
+use super::{
+    Codec, CompressionEncoding, DEFAULT_MAX_MESSAGE_SIZE, GRPC_ACCEPT_ENCODING, GRPC_ENCODING,
+    GrpcRequest, Status, Streaming,
+    body::{EncodeBody, Role},
+    codec::Direction,
+    is_grpc_content_type, try_parse_grpc_timeout,
+};
+use crate::{Body, HeaderValue, IntoResponse, Request, Response, StatusCode, header::CONTENT_TYPE};
+use futures_lite::{Stream, stream};
+use rama_core::{Context, Service};
+use std::{convert::Infallible, fmt, future::Future, time::Duration};
+use tokio::time::Instant;
+
+/// Configuration shared by the gRPC server services.
+#[derive(Debug, Clone)]
+struct ServerConfig {
+    accept_compression: Vec<CompressionEncoding>,
+    send_compression: Vec<CompressionEncoding>,
+    max_message_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            accept_compression: Vec::new(),
+            send_compression: Vec::new(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+/// The negotiated properties of an incoming gRPC call.
+struct CallProperties {
+    request_encoding: CompressionEncoding,
+    response_encoding: CompressionEncoding,
+    deadline: Option<Instant>,
+}
+
+impl ServerConfig {
+    fn negotiate(&self, req: &Request) -> Result<CallProperties, Status> {
+        let request_encoding =
+            CompressionEncoding::from_encoding_header(req.headers(), &self.accept_compression)?;
+        let response_encoding =
+            CompressionEncoding::from_accept_encoding_header(req.headers(), &self.send_compression);
+        let deadline = try_parse_grpc_timeout(req.headers())
+            .map_err(|err| Status::invalid_argument(err.to_string()))?
+            .map(|timeout| Instant::now() + timeout);
+
+        Ok(CallProperties {
+            request_encoding,
+            response_encoding,
+            deadline,
+        })
+    }
+
+    fn status_response(&self, status: Status) -> Response {
+        let mut res = status.into_response();
+        if let Some(value) =
+            CompressionEncoding::accept_encoding_header_value(&self.accept_compression)
+        {
+            res.headers_mut().insert(&GRPC_ACCEPT_ENCODING, value);
+        }
+        res
+    }
+
+    fn stream_response<S, C>(&self, stream: S, codec: C, properties: &CallProperties) -> Response
+    where
+        S: Stream<Item = Result<C::Encode, Status>> + Send + 'static,
+        C: Codec,
+    {
+        let body = EncodeBody::new(
+            stream,
+            codec,
+            properties.response_encoding,
+            Role::Server,
+            properties.deadline.map(tokio::time::sleep_until),
+        );
+        let mut res = Response::new(Body::new(body));
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        if properties.response_encoding != CompressionEncoding::Identity {
+            headers.insert(
+                &GRPC_ENCODING,
+                properties.response_encoding.into_header_value(),
+            );
+        }
+        if let Some(value) =
+            CompressionEncoding::accept_encoding_header_value(&self.accept_compression)
+        {
+            headers.insert(&GRPC_ACCEPT_ENCODING, value);
+        }
+        res
+    }
+}
+
+macro_rules! impl_server_config_setters {
+    ($name:ident) => {
+        impl<F, C> $name<F, C> {
+            /// Accept request messages compressed with the given encoding.
+            pub fn with_accept_compression(mut self, encoding: CompressionEncoding) -> Self {
+                self.config.accept_compression.push(encoding);
+                self
+            }
+
+            /// Accept request messages compressed with the given encoding.
+            pub fn set_accept_compression(&mut self, encoding: CompressionEncoding) -> &mut Self {
+                self.config.accept_compression.push(encoding);
+                self
+            }
+
+            /// Compress response messages with the given encoding,
+            /// if the client supports it.
+            ///
+            /// Encodings are preferred in the order they are added.
+            pub fn with_send_compression(mut self, encoding: CompressionEncoding) -> Self {
+                self.config.send_compression.push(encoding);
+                self
+            }
+
+            /// Compress response messages with the given encoding,
+            /// if the client supports it.
+            ///
+            /// Encodings are preferred in the order they are added.
+            pub fn set_send_compression(&mut self, encoding: CompressionEncoding) -> &mut Self {
+                self.config.send_compression.push(encoding);
+                self
+            }
+
+            /// Set the maximum size of a single (decompressed) request message.
+            ///
+            /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+            pub fn with_max_message_size(mut self, size: usize) -> Self {
+                self.config.max_message_size = size;
+                self
+            }
+
+            /// Set the maximum size of a single (decompressed) request message.
+            ///
+            /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+            pub fn set_max_message_size(&mut self, size: usize) -> &mut Self {
+                self.config.max_message_size = size;
+                self
+            }
+        }
+
+        impl<F, C: fmt::Debug> fmt::Debug for $name<F, C> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("handler", &format_args!("{}", std::any::type_name::<F>()))
+                    .field("codec", &self.codec)
+                    .field("config", &self.config)
+                    .finish()
+            }
+        }
+
+        impl<F: Clone, C: Clone> Clone for $name<F, C> {
+            fn clone(&self) -> Self {
+                Self {
+                    handler: self.handler.clone(),
+                    codec: self.codec.clone(),
+                    config: self.config.clone(),
+                }
+            }
+        }
+    };
+}
+
+/// A [`Service`] serving a unary gRPC method,
+/// where the client sends a single message and receives a single message.
+///
+/// Created using [`unary`], it can be used as an endpoint
+/// for [`WebService`] or any other http router.
+///
+/// [`WebService`]: crate::service::web::WebService
+pub struct UnaryService<F, C> {
+    handler: F,
+    codec: C,
+    config: ServerConfig,
+}
+
+impl_server_config_setters!(UnaryService);
+
+/// Create a [`UnaryService`] for the given [`Codec`] and handler.
+///
+/// The handler receives the decoded request message
+/// and returns the response message or a [`Status`] on failure.
+pub fn unary<F, C>(codec: C, handler: F) -> UnaryService<F, C> {
+    UnaryService {
+        handler,
+        codec,
+        config: ServerConfig::default(),
+    }
+}
+
+impl<State, F, Fut, C> Service<State, Request> for UnaryService<F, C>
+where
+    State: Clone + Send + Sync + 'static,
+    F: Fn(Context<State>, GrpcRequest<C::Decode>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<C::Encode, Status>> + Send + 'static,
+    C: Codec,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if !is_grpc_request(&req) {
+            return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+        }
+        let properties = match self.config.negotiate(&req) {
+            Ok(properties) => properties,
+            Err(status) => return Ok(self.config.status_response(status)),
+        };
+
+        let (parts, body) = req.into_parts();
+        let mut messages = Streaming::new(
+            body,
+            self.codec.clone(),
+            properties.request_encoding,
+            self.config.max_message_size,
+            Direction::Request,
+        );
+
+        let call = async {
+            let message = messages
+                .message()
+                .await?
+                .ok_or_else(|| Status::internal("missing request message"))?;
+            (self.handler)(
+                ctx,
+                GrpcRequest::from_parts(parts.headers, parts.extensions, message),
+            )
+            .await
+        };
+
+        match with_deadline(properties.deadline, call).await {
+            Ok(message) => Ok(self.config.stream_response(
+                stream::once(Ok(message)),
+                self.codec.clone(),
+                &properties,
+            )),
+            Err(status) => Ok(self.config.status_response(status)),
+        }
+    }
+}
+
+/// A [`Service`] serving a streaming gRPC method,
+/// covering client-, server- and bidirectional streaming.
+///
+/// Created using [`streaming`], it can be used as an endpoint
+/// for [`WebService`] or any other http router.
+///
+/// [`WebService`]: crate::service::web::WebService
+pub struct StreamingService<F, C> {
+    handler: F,
+    codec: C,
+    config: ServerConfig,
+}
+
+impl_server_config_setters!(StreamingService);
+
+/// Create a [`StreamingService`] for the given [`Codec`] and handler.
+///
+/// The handler receives the stream of decoded request messages
+/// and returns a stream of response messages or a [`Status`] on failure.
+/// An error returned as part of the response stream ends the call with that [`Status`].
+pub fn streaming<F, C>(codec: C, handler: F) -> StreamingService<F, C> {
+    StreamingService {
+        handler,
+        codec,
+        config: ServerConfig::default(),
+    }
+}
+
+impl<State, F, Fut, S, C> Service<State, Request> for StreamingService<F, C>
+where
+    State: Clone + Send + Sync + 'static,
+    F: Fn(Context<State>, GrpcRequest<Streaming<C>>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<S, Status>> + Send + 'static,
+    S: Stream<Item = Result<C::Encode, Status>> + Send + 'static,
+    C: Codec,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if !is_grpc_request(&req) {
+            return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+        }
+        let properties = match self.config.negotiate(&req) {
+            Ok(properties) => properties,
+            Err(status) => return Ok(self.config.status_response(status)),
+        };
+
+        let (parts, body) = req.into_parts();
+        let messages = Streaming::new(
+            body,
+            self.codec.clone(),
+            properties.request_encoding,
+            self.config.max_message_size,
+            Direction::Request,
+        );
+
+        let call = (self.handler)(
+            ctx,
+            GrpcRequest::from_parts(parts.headers, parts.extensions, messages),
+        );
+
+        match with_deadline(properties.deadline, call).await {
+            Ok(stream) => Ok(self
+                .config
+                .stream_response(stream, self.codec.clone(), &properties)),
+            Err(status) => Ok(self.config.status_response(status)),
+        }
+    }
+}
+
+fn is_grpc_request(req: &Request) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .map(is_grpc_content_type)
+        .unwrap_or_default()
+}
+
+pub(super) async fn with_deadline<T>(
+    deadline: Option<Instant>,
+    fut: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut)
+            .await
+            .unwrap_or_else(|_| Err(Status::deadline_exceeded("grpc deadline exceeded"))),
+        None => fut.await,
+    }
+}
+
+pub(super) fn deadline_from_timeout(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.map(|timeout| Instant::now() + timeout)
+}
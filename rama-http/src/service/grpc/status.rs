@@ -0,0 +1,205 @@
+use super::{GRPC_MESSAGE, GRPC_STATUS};
+use crate::layer::classify::GrpcCode;
+use crate::{HeaderMap, HeaderValue, IntoResponse, Response, StatusCode, header::CONTENT_TYPE};
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode, percent_encode};
+use rama_core::error::BoxError;
+use std::fmt;
+
+/// Characters that have to be percent-encoded in the `grpc-message` header.
+const GRPC_MESSAGE_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%');
+
+/// A gRPC status, describing the result of a gRPC call.
+///
+/// Sent by servers as the `grpc-status` and `grpc-message`
+/// trailers (or headers in case of a trailers-only response).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    code: GrpcCode,
+    message: String,
+}
+
+macro_rules! status_constructors {
+    ($($(#[$doc:meta])* $name:ident => $code:ident),+ $(,)?) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(message: impl Into<String>) -> Self {
+                Self::new(GrpcCode::$code, message)
+            }
+        )+
+    };
+}
+
+impl Status {
+    /// Create a new [`Status`] with the given code and message.
+    pub fn new(code: GrpcCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    status_constructors! {
+        /// Create a new [`Status`] with [`GrpcCode::Ok`].
+        ok => Ok,
+        /// Create a new [`Status`] with [`GrpcCode::Cancelled`].
+        cancelled => Cancelled,
+        /// Create a new [`Status`] with [`GrpcCode::Unknown`].
+        unknown => Unknown,
+        /// Create a new [`Status`] with [`GrpcCode::InvalidArgument`].
+        invalid_argument => InvalidArgument,
+        /// Create a new [`Status`] with [`GrpcCode::DeadlineExceeded`].
+        deadline_exceeded => DeadlineExceeded,
+        /// Create a new [`Status`] with [`GrpcCode::NotFound`].
+        not_found => NotFound,
+        /// Create a new [`Status`] with [`GrpcCode::AlreadyExists`].
+        already_exists => AlreadyExists,
+        /// Create a new [`Status`] with [`GrpcCode::PermissionDenied`].
+        permission_denied => PermissionDenied,
+        /// Create a new [`Status`] with [`GrpcCode::ResourceExhausted`].
+        resource_exhausted => ResourceExhausted,
+        /// Create a new [`Status`] with [`GrpcCode::FailedPrecondition`].
+        failed_precondition => FailedPrecondition,
+        /// Create a new [`Status`] with [`GrpcCode::Aborted`].
+        aborted => Aborted,
+        /// Create a new [`Status`] with [`GrpcCode::OutOfRange`].
+        out_of_range => OutOfRange,
+        /// Create a new [`Status`] with [`GrpcCode::Unimplemented`].
+        unimplemented => Unimplemented,
+        /// Create a new [`Status`] with [`GrpcCode::Internal`].
+        internal => Internal,
+        /// Create a new [`Status`] with [`GrpcCode::Unavailable`].
+        unavailable => Unavailable,
+        /// Create a new [`Status`] with [`GrpcCode::DataLoss`].
+        data_loss => DataLoss,
+        /// Create a new [`Status`] with [`GrpcCode::Unauthenticated`].
+        unauthenticated => Unauthenticated,
+    }
+
+    /// Create a new [`Status`] with [`GrpcCode::Internal`] from an error.
+    pub fn from_error(error: impl Into<BoxError>) -> Self {
+        Self::internal(error.into().to_string())
+    }
+
+    /// Return the [`GrpcCode`] of this [`Status`].
+    pub fn code(&self) -> GrpcCode {
+        self.code
+    }
+
+    /// Return the message of this [`Status`].
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return `true` if this [`Status`] has [`GrpcCode::Ok`] as its code.
+    pub fn is_ok(&self) -> bool {
+        self.code == GrpcCode::Ok
+    }
+
+    /// Try to extract a [`Status`] from the given headers or trailers.
+    ///
+    /// Returns `None` if no `grpc-status` header is present.
+    pub fn from_header_map(headers: &HeaderMap) -> Option<Self> {
+        let code = headers.get(&GRPC_STATUS)?;
+        let code = code
+            .to_str()
+            .ok()
+            .and_then(|code| code.parse::<i32>().ok())
+            .map(GrpcCode::from_i32)
+            .unwrap_or(GrpcCode::Unknown);
+        let message = headers
+            .get(&GRPC_MESSAGE)
+            .map(|value| {
+                percent_decode(value.as_bytes())
+                    .decode_utf8_lossy()
+                    .into_owned()
+            })
+            .unwrap_or_default();
+        Some(Self { code, message })
+    }
+
+    /// Add the `grpc-status` and `grpc-message` headers of this [`Status`]
+    /// to the given headers or trailers.
+    pub fn add_header(&self, headers: &mut HeaderMap) {
+        headers.insert(&GRPC_STATUS, HeaderValue::from(self.code.as_i32()));
+        if !self.message.is_empty() {
+            let message =
+                percent_encode(self.message.as_bytes(), GRPC_MESSAGE_ENCODE_SET).to_string();
+            if let Ok(value) = HeaderValue::try_from(message) {
+                headers.insert(&GRPC_MESSAGE, value);
+            }
+        }
+    }
+
+    /// Create a [`Status`] from an unexpected http [`StatusCode`],
+    /// as defined by the gRPC http status code mapping.
+    pub fn from_http_status(status: StatusCode) -> Self {
+        let code = match status {
+            StatusCode::BAD_REQUEST => GrpcCode::Internal,
+            StatusCode::UNAUTHORIZED => GrpcCode::Unauthenticated,
+            StatusCode::FORBIDDEN => GrpcCode::PermissionDenied,
+            StatusCode::NOT_FOUND => GrpcCode::Unimplemented,
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => GrpcCode::Unavailable,
+            _ => GrpcCode::Unknown,
+        };
+        Self::new(code, format!("unexpected http status code: {status}"))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "grpc status {:?}", self.code)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl IntoResponse for Status {
+    /// Create a trailers-only gRPC response for this [`Status`].
+    fn into_response(self) -> Response {
+        let mut res = Response::new(crate::Body::empty());
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        self.add_header(headers);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_header_roundtrip() {
+        let status = Status::not_found("100% not here\nsorry ✨");
+        let mut headers = HeaderMap::new();
+        status.add_header(&mut headers);
+        assert_eq!(headers.get(&GRPC_STATUS).unwrap(), "5");
+        assert_eq!(
+            headers.get(&GRPC_MESSAGE).unwrap(),
+            "100%25 not here%0Asorry %E2%9C%A8"
+        );
+        assert_eq!(Status::from_header_map(&headers), Some(status));
+    }
+
+    #[test]
+    fn test_status_from_header_map_missing() {
+        assert_eq!(Status::from_header_map(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_status_from_header_map_invalid_code() {
+        let mut headers = HeaderMap::new();
+        headers.insert(&GRPC_STATUS, HeaderValue::from_static("foo"));
+        assert_eq!(
+            Status::from_header_map(&headers).unwrap().code(),
+            GrpcCode::Unknown
+        );
+    }
+}
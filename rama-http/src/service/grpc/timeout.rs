@@ -0,0 +1,108 @@
+use super::GRPC_TIMEOUT;
+use crate::{HeaderMap, HeaderValue};
+use rama_core::error::OpaqueError;
+use std::time::Duration;
+
+/// The maximum number of digits allowed in a `grpc-timeout` value.
+const MAX_TIMEOUT_DIGITS: usize = 8;
+
+/// Try to parse the `grpc-timeout` header from the given headers.
+///
+/// Returns `Ok(None)` if the header is missing.
+pub fn try_parse_grpc_timeout(headers: &HeaderMap) -> Result<Option<Duration>, OpaqueError> {
+    let Some(value) = headers.get(&GRPC_TIMEOUT) else {
+        return Ok(None);
+    };
+
+    let value = value
+        .to_str()
+        .map_err(|_| OpaqueError::from_display("grpc-timeout: non-ascii header value"))?;
+    if value.len() < 2 {
+        return Err(OpaqueError::from_display("grpc-timeout: value too short"));
+    }
+
+    let (digits, unit) = value.split_at(value.len() - 1);
+    if digits.len() > MAX_TIMEOUT_DIGITS || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(OpaqueError::from_display("grpc-timeout: invalid amount"));
+    }
+    let amount: u64 = digits
+        .parse()
+        .map_err(|_| OpaqueError::from_display("grpc-timeout: invalid amount"))?;
+
+    let timeout = match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return Err(OpaqueError::from_display("grpc-timeout: invalid unit")),
+    };
+
+    Ok(Some(timeout))
+}
+
+/// Encode the given [`Duration`] as a `grpc-timeout` header value.
+///
+/// The most precise unit is used that still fits the
+/// maximum of 8 digits allowed by the gRPC specification.
+pub fn encode_grpc_timeout(timeout: Duration) -> HeaderValue {
+    const MAX: u128 = 99_999_999;
+
+    let nanos = timeout.as_nanos();
+    let (amount, unit) = if nanos <= MAX {
+        (nanos, 'n')
+    } else if timeout.as_micros() <= MAX {
+        (timeout.as_micros(), 'u')
+    } else if timeout.as_millis() <= MAX {
+        (timeout.as_millis(), 'm')
+    } else if u128::from(timeout.as_secs()) <= MAX {
+        (u128::from(timeout.as_secs()), 'S')
+    } else if u128::from(timeout.as_secs() / 60) <= MAX {
+        (u128::from(timeout.as_secs() / 60), 'M')
+    } else {
+        (u128::from(timeout.as_secs() / 3600).min(MAX), 'H')
+    };
+
+    HeaderValue::try_from(format!("{amount}{unit}"))
+        .expect("grpc-timeout value is always valid ascii")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &'static str) -> Result<Option<Duration>, OpaqueError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(&GRPC_TIMEOUT, HeaderValue::from_static(value));
+        try_parse_grpc_timeout(&headers)
+    }
+
+    #[test]
+    fn test_parse_grpc_timeout() {
+        assert_eq!(parse("3H").unwrap(), Some(Duration::from_secs(3 * 3600)));
+        assert_eq!(parse("2M").unwrap(), Some(Duration::from_secs(120)));
+        assert_eq!(parse("5S").unwrap(), Some(Duration::from_secs(5)));
+        assert_eq!(parse("100m").unwrap(), Some(Duration::from_millis(100)));
+        assert_eq!(parse("42u").unwrap(), Some(Duration::from_micros(42)));
+        assert_eq!(parse("7n").unwrap(), Some(Duration::from_nanos(7)));
+        assert_eq!(try_parse_grpc_timeout(&HeaderMap::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_grpc_timeout_invalid() {
+        assert!(parse("S").is_err());
+        assert!(parse("10").is_err());
+        assert!(parse("10s").is_err());
+        assert!(parse("-1S").is_err());
+        assert!(parse("123456789S").is_err());
+    }
+
+    #[test]
+    fn test_encode_grpc_timeout() {
+        assert_eq!(encode_grpc_timeout(Duration::from_nanos(500)), "500n");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(1)), "1000000u");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(200)), "200000m");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(200_000)), "200000S");
+    }
+}
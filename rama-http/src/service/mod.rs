@@ -2,5 +2,8 @@
 
 pub mod client;
 pub mod fs;
+pub mod grpc;
+pub mod prometheus;
 pub mod redirect;
+pub mod reverse_proxy;
 pub mod web;
@@ -0,0 +1,130 @@
+//! Prometheus metrics exposition [`Service`].
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::telemetry::prometheus::Registry;
+//! use rama_http::layer::prometheus::RequestMetricsLayer;
+//! use rama_http::service::prometheus::PrometheusService;
+//! use rama_http::service::web::WebService;
+//! use rama_core::Layer;
+//!
+//! let registry = Registry::new();
+//!
+//! let service = RequestMetricsLayer::new(&registry).layer(
+//!     WebService::<()>::default()
+//!         .get("/", "hello")
+//!         .get("/metrics", PrometheusService::new(registry)),
+//! );
+//! ```
+//!
+//! [`Service`]: rama_core::Service
+
+use crate::{
+    Body, HeaderValue, Request, Response,
+    header::{ACCEPT, CONTENT_TYPE},
+};
+use rama_core::telemetry::prometheus::{ExpositionFormat, Registry};
+use rama_core::{Context, Service};
+use std::convert::Infallible;
+
+/// A [`Service`] which serves the metrics of a Prometheus [`Registry`],
+/// to be scraped by Prometheus or any other compatible collector.
+///
+/// The OpenMetrics format is served in case the client accepts it,
+/// otherwise the Prometheus text format is used.
+#[derive(Debug, Clone)]
+pub struct PrometheusService {
+    registry: Registry,
+}
+
+impl PrometheusService {
+    /// Create a new [`PrometheusService`] serving the metrics of the given [`Registry`].
+    pub fn new(registry: Registry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Default for PrometheusService {
+    /// Create a new [`PrometheusService`] serving the metrics
+    /// of the [global `Registry`](Registry::global).
+    fn default() -> Self {
+        Self::new(Registry::global().clone())
+    }
+}
+
+impl<State, Body_> Service<State, Request<Body_>> for PrometheusService
+where
+    State: Clone + Send + Sync + 'static,
+    Body_: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: Request<Body_>,
+    ) -> Result<Self::Response, Self::Error> {
+        let format = if req
+            .headers()
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("application/openmetrics-text"))
+        {
+            ExpositionFormat::OpenMetrics
+        } else {
+            ExpositionFormat::Prometheus
+        };
+
+        let mut res = Response::new(Body::from(self.registry.encode(format)));
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_prometheus_service_formats() {
+        let registry = Registry::new();
+        registry.counter("test", "Test counter.", &[]).inc(&[]);
+        let service = PrometheusService::new(registry);
+
+        let res = service
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "text/plain; version=0.0.4; charset=utf-8"
+        );
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            body,
+            "# HELP test_total Test counter.\n# TYPE test_total counter\ntest_total 1\n"
+        );
+
+        let req = Request::builder()
+            .header(
+                ACCEPT,
+                "application/openmetrics-text;version=1.0.0,text/plain;q=0.5",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let res = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/openmetrics-text; version=1.0.0; charset=utf-8"
+        );
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.ends_with(b"# EOF\n"));
+    }
+}
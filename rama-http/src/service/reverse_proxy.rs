@@ -0,0 +1,730 @@
+//! Reverse proxy service, forwarding requests to an upstream server.
+//!
+//! See [`ReverseProxy`] for more information.
+
+use crate::headers::{ForwardHeader, HeaderMapExt};
+use crate::layer::forwarded::append_forwarded_element;
+use crate::{
+    HeaderMap, HeaderName, HeaderValue, IntoResponse, Request, Response, StatusCode, Uri, Version,
+    dep::http::uri::PathAndQuery, header,
+};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use rama_core::{Context, Service};
+use rama_net::Protocol;
+use rama_net::address::{Authority, Domain, Host};
+use rama_net::forwarded::{Forwarded, NodeId};
+use rama_net::http::RequestContext;
+use std::{convert::Infallible, fmt, time::Duration};
+
+type ForwardedHeaderFn = fn(&Forwarded, &mut HeaderMap);
+
+/// A [`Service`] which forwards incoming requests to a single upstream server,
+/// using the given http client [`Service`], e.g. the `HttpClient` of `rama-http-backend`.
+///
+/// For each request it:
+///
+/// - removes the hop-by-hop headers defined in [RFC 9110],
+///   as well as any header listed in the `Connection` header;
+/// - rewrites the path, stripping an optional prefix and
+///   prepending the path of the upstream [`Uri`], rejecting paths with
+///   dot segments (`.` or `..`, also when percent-encoded) with a `400 Bad Request`,
+///   such that they cannot escape the stripped prefix or the upstream path;
+/// - rewrites the `Host` header to the upstream authority,
+///   unless [`ReverseProxy::set_preserve_host`] is enabled;
+/// - appends the [`Forwarded`] header (or any other [`ForwardHeader`])
+///   for this hop, the same way [`SetForwardedHeadersLayer`] does;
+/// - splices the upgraded client and upstream connections
+///   in case the upstream server accepts a protocol upgrade (e.g. WebSocket);
+/// - maps upstream failures to a `502 Bad Gateway`, or `504 Gateway Timeout`
+///   in case the upstream did not respond in time.
+///
+/// Forwarded information received from the downstream client is only trusted
+/// if it was extracted into the [`Context`] using the [`GetForwardedHeadersLayer`].
+///
+/// [RFC 9110]: https://www.rfc-editor.org/rfc/rfc9110#section-7.6.1
+/// [`SetForwardedHeadersLayer`]: crate::layer::forwarded::SetForwardedHeadersLayer
+/// [`GetForwardedHeadersLayer`]: crate::layer::forwarded::GetForwardedHeadersLayer
+///
+/// # Example
+///
+/// ```
+/// use rama_core::{Context, Service, service::service_fn};
+/// use rama_http::{Body, Request, Response, headers::XForwardedFor, service::reverse_proxy::ReverseProxy};
+/// use std::convert::Infallible;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// // typically this would be the `HttpClient` of `rama-http-backend`
+/// let client = service_fn(|req: Request| async move {
+///     assert_eq!(req.uri(), "http://backend:8080/v1/users?page=2");
+///     Ok::<_, Infallible>(Response::new(Body::empty()))
+/// });
+///
+/// let proxy = ReverseProxy::new(client, "http://backend:8080/v1".parse().unwrap())
+///     .with_strip_prefix("/api")
+///     .with_forwarded_header::<XForwardedFor>();
+///
+/// let req = Request::builder()
+///     .uri("http://example.com/api/users?page=2")
+///     .body(Body::empty())
+///     .unwrap();
+/// let resp = proxy.serve(Context::default(), req).await.unwrap();
+/// assert!(resp.status().is_success());
+/// # }
+/// ```
+pub struct ReverseProxy<S> {
+    client: S,
+    upstream: Uri,
+    upstream_path: String,
+    upstream_ctx: RequestContext,
+    strip_prefix: Option<String>,
+    preserve_host: bool,
+    by_node: NodeId,
+    forwarded_headers: Vec<ForwardedHeaderFn>,
+    timeout: Option<Duration>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for ReverseProxy<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReverseProxy")
+            .field("client", &self.client)
+            .field("upstream", &self.upstream)
+            .field("strip_prefix", &self.strip_prefix)
+            .field("preserve_host", &self.preserve_host)
+            .field("by_node", &self.by_node)
+            .field("forwarded_headers", &self.forwarded_headers.len())
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ReverseProxy<S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            upstream: self.upstream.clone(),
+            upstream_path: self.upstream_path.clone(),
+            upstream_ctx: self.upstream_ctx.clone(),
+            strip_prefix: self.strip_prefix.clone(),
+            preserve_host: self.preserve_host,
+            by_node: self.by_node.clone(),
+            forwarded_headers: self.forwarded_headers.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<S> ReverseProxy<S> {
+    /// Create a new [`ReverseProxy`] forwarding requests using the given client
+    /// to the given upstream [`Uri`].
+    ///
+    /// The upstream [`Uri`] has to define a scheme and authority,
+    /// and can optionally define a path which is prepended to the path of each request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the upstream [`Uri`] is not a valid upstream,
+    /// use [`ReverseProxy::try_new`] for a fallible alternative.
+    pub fn new(client: S, upstream: Uri) -> Self {
+        Self::try_new(client, upstream).expect("valid reverse proxy upstream uri")
+    }
+
+    /// Try to create a new [`ReverseProxy`] forwarding requests using the given client
+    /// to the given upstream [`Uri`].
+    ///
+    /// Fails if the upstream [`Uri`] does not define a scheme and a (valid) authority.
+    pub fn try_new(client: S, upstream: Uri) -> Result<Self, OpaqueError> {
+        let protocol: Protocol = upstream
+            .scheme()
+            .context("reverse proxy upstream uri: missing scheme")?
+            .into();
+        let host = upstream
+            .host()
+            .context("reverse proxy upstream uri: missing host")?;
+        let host = Host::try_from(host.trim_start_matches('[').trim_end_matches(']'))
+            .context("reverse proxy upstream uri: invalid host")?;
+        let port = upstream
+            .port_u16()
+            .unwrap_or_else(|| protocol.default_port());
+
+        let upstream_path = upstream.path().trim_end_matches('/').to_owned();
+        let upstream_ctx = RequestContext {
+            http_version: Version::HTTP_11,
+            protocol,
+            authority: Authority::new(host, port),
+        };
+
+        Ok(Self {
+            client,
+            upstream,
+            upstream_path,
+            upstream_ctx,
+            strip_prefix: None,
+            preserve_host: false,
+            by_node: Domain::from_static("rama").into(),
+            forwarded_headers: vec![set_forwarded_header::<Forwarded>],
+            timeout: None,
+        })
+    }
+
+    /// Strip the given path prefix from the request path,
+    /// prior to prepending the path of the upstream [`Uri`].
+    ///
+    /// Requests of which the path does not start with the prefix are forwarded as-is.
+    pub fn with_strip_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.strip_prefix = Some(prefix.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Strip the given path prefix from the request path,
+    /// prior to prepending the path of the upstream [`Uri`].
+    ///
+    /// Requests of which the path does not start with the prefix are forwarded as-is.
+    pub fn set_strip_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.strip_prefix = Some(prefix.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Preserve the `Host` of the incoming request,
+    /// instead of rewriting it to the upstream authority (the default).
+    pub fn with_preserve_host(mut self, preserve: bool) -> Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    /// Preserve the `Host` of the incoming request,
+    /// instead of rewriting it to the upstream authority (the default).
+    pub fn set_preserve_host(&mut self, preserve: bool) -> &mut Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    /// Set the given [`NodeId`] as the "by" property, identifying this proxy.
+    ///
+    /// Default of `None` will be set to `rama` otherwise.
+    pub fn with_forward_by(mut self, node_id: impl Into<NodeId>) -> Self {
+        self.by_node = node_id.into();
+        self
+    }
+
+    /// Set the given [`NodeId`] as the "by" property, identifying this proxy.
+    ///
+    /// Default of `None` will be set to `rama` otherwise.
+    pub fn set_forward_by(&mut self, node_id: impl Into<NodeId>) -> &mut Self {
+        self.by_node = node_id.into();
+        self
+    }
+
+    /// Also write the forwarded information using the given [`ForwardHeader`],
+    /// e.g. [`XForwardedFor`], in addition to the [`Forwarded`] header set by default.
+    ///
+    /// [`XForwardedFor`]: crate::headers::XForwardedFor
+    pub fn with_forwarded_header<H: ForwardHeader>(mut self) -> Self {
+        self.forwarded_headers.push(set_forwarded_header::<H>);
+        self
+    }
+
+    /// Also write the forwarded information using the given [`ForwardHeader`],
+    /// e.g. [`XForwardedFor`], in addition to the [`Forwarded`] header set by default.
+    ///
+    /// [`XForwardedFor`]: crate::headers::XForwardedFor
+    pub fn set_forwarded_header<H: ForwardHeader>(&mut self) -> &mut Self {
+        self.forwarded_headers.push(set_forwarded_header::<H>);
+        self
+    }
+
+    /// Do not write any forwarded information,
+    /// not even the [`Forwarded`] header set by default.
+    pub fn without_forwarded_headers(mut self) -> Self {
+        self.forwarded_headers.clear();
+        self
+    }
+
+    /// Do not write any forwarded information,
+    /// not even the [`Forwarded`] header set by default.
+    pub fn unset_forwarded_headers(&mut self) -> &mut Self {
+        self.forwarded_headers.clear();
+        self
+    }
+
+    /// Set the time to wait for the upstream response (headers),
+    /// after which a `504 Gateway Timeout` is returned.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the time to wait for the upstream response (headers),
+    /// after which a `504 Gateway Timeout` is returned.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn upstream_uri(&self, uri: &Uri) -> Result<Uri, OpaqueError> {
+        let mut path = uri.path();
+        if has_dot_segment(path) {
+            return Err(OpaqueError::from_display(
+                "request path contains a dot segment",
+            ));
+        }
+        if let Some(prefix) = self.strip_prefix.as_deref() {
+            if let Some(rest) = path.strip_prefix(prefix) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    path = rest;
+                }
+            }
+        }
+
+        let mut path_and_query = format!("{}/{}", self.upstream_path, path.trim_start_matches('/'));
+        if let Some(query) = uri.query() {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+
+        let mut parts = self.upstream.clone().into_parts();
+        parts.path_and_query =
+            Some(PathAndQuery::try_from(path_and_query).context("create upstream path and query")?);
+        Uri::from_parts(parts).context("create upstream uri")
+    }
+}
+
+/// Returns `true` in case the path contains a `.` or `..` segment,
+/// also when (partially) percent-encoded, or when separated by an encoded (back)slash,
+/// as the upstream server might decode and normalize those.
+fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        let segment = segment
+            .to_ascii_lowercase()
+            .replace("%2e", ".")
+            .replace("%2f", "/")
+            .replace("%5c", "\\");
+        segment
+            .split(['/', '\\'])
+            .any(|part| part == "." || part == "..")
+    })
+}
+
+fn set_forwarded_header<H: ForwardHeader>(forwarded: &Forwarded, headers: &mut HeaderMap) {
+    if let Some(header) = H::try_from_forwarded(forwarded.iter()) {
+        headers.typed_insert(header);
+    }
+}
+
+impl<S, State> Service<State, Request> for ReverseProxy<S>
+where
+    S: Service<State, Request, Response = Response, Error: Into<BoxError>>,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let original_version = req.version();
+
+        let upgrade = requested_upgrade(req.headers());
+        let client_upgrade = upgrade
+            .is_some()
+            .then(|| rama_http_core::upgrade::on(&mut req));
+        remove_hop_by_hop_headers(req.headers_mut());
+        if let Some(upgrade) = upgrade {
+            req.headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+            req.headers_mut().insert(header::UPGRADE, upgrade);
+        }
+
+        if !self.forwarded_headers.is_empty() {
+            match append_forwarded_element(&self.by_node, &mut ctx, &req) {
+                Ok(forwarded) => {
+                    for set_header in &self.forwarded_headers {
+                        set_header(&forwarded, req.headers_mut());
+                    }
+                }
+                Err(err) => {
+                    tracing::debug!(error = %err, "reverse proxy: failed to create forwarded info");
+                    return Ok(StatusCode::BAD_REQUEST.into_response());
+                }
+            }
+        }
+
+        let uri = match self.upstream_uri(req.uri()) {
+            Ok(uri) => uri,
+            Err(err) => {
+                tracing::debug!(error = %err, "reverse proxy: failed to create upstream uri");
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            }
+        };
+
+        if self.preserve_host {
+            if !req.headers().contains_key(header::HOST) {
+                if let Some(value) = req
+                    .uri()
+                    .authority()
+                    .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+                {
+                    req.headers_mut().insert(header::HOST, value);
+                }
+            }
+        } else if let Some(value) = uri
+            .authority()
+            .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        {
+            req.headers_mut().insert(header::HOST, value);
+        }
+
+        *req.uri_mut() = uri;
+        *req.version_mut() = Version::HTTP_11;
+
+        // the context of the incoming request (e.g. sni or forwarded info)
+        // should not be used by the client to connect to the upstream
+        ctx.insert(self.upstream_ctx.clone());
+        let executor = ctx.executor().clone();
+
+        let result: Result<Response, BoxError> = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, self.client.serve(ctx, req)).await
+            {
+                Ok(result) => result.map_err(Into::into),
+                Err(elapsed) => Err(elapsed.into()),
+            },
+            None => self.client.serve(ctx, req).await.map_err(Into::into),
+        };
+        let mut res = match result {
+            Ok(res) => res,
+            Err(err) => return Ok(upstream_error_response(err)),
+        };
+
+        if res.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let Some(client_upgrade) = client_upgrade else {
+                tracing::debug!(
+                    "reverse proxy: upstream switched protocols without upgrade request"
+                );
+                return Ok(StatusCode::BAD_GATEWAY.into_response());
+            };
+            let upgrade = res.headers().get(header::UPGRADE).cloned();
+            let upstream_upgrade = rama_http_core::upgrade::on(&mut res);
+            executor.spawn_task(async move {
+                let (mut client_io, mut upstream_io) =
+                    match tokio::try_join!(client_upgrade, upstream_upgrade) {
+                        Ok(upgraded) => upgraded,
+                        Err(err) => {
+                            tracing::debug!(error = %err, "reverse proxy: upgrade failed");
+                            return;
+                        }
+                    };
+                if let Err(err) =
+                    tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io).await
+                {
+                    tracing::debug!(error = %err, "reverse proxy: upgraded connection closed");
+                }
+            });
+
+            remove_hop_by_hop_headers(res.headers_mut());
+            if let Some(upgrade) = upgrade {
+                res.headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+                res.headers_mut().insert(header::UPGRADE, upgrade);
+            }
+        } else {
+            remove_hop_by_hop_headers(res.headers_mut());
+        }
+
+        *res.version_mut() = original_version;
+        Ok(res)
+    }
+}
+
+/// Returns the requested protocol in case the headers request a protocol upgrade.
+fn requested_upgrade(headers: &HeaderMap) -> Option<HeaderValue> {
+    let upgrade = headers.get(header::UPGRADE)?;
+    connection_tokens(headers)
+        .any(|token| token.eq_ignore_ascii_case("upgrade"))
+        .then(|| upgrade.clone())
+}
+
+fn connection_tokens(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(header::CONNECTION)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Remove the hop-by-hop headers as defined in RFC 9110, section 7.6.1,
+/// including the headers listed in the `Connection` header.
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = connection_tokens(headers)
+        .filter_map(|token| HeaderName::from_bytes(token.as_bytes()).ok())
+        .collect();
+    for name in listed {
+        // `TE: trailers` is required for protocols such as gRPC
+        if name != header::TE {
+            headers.remove(name);
+        }
+    }
+
+    let te_trailers = headers.get_all(header::TE).iter().any(|value| {
+        value
+            .to_str()
+            .map(|value| {
+                value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("trailers"))
+            })
+            .unwrap_or_default()
+    });
+
+    for name in [
+        &header::CONNECTION,
+        &header::PROXY_CONNECTION,
+        &header::KEEP_ALIVE,
+        &header::PROXY_AUTHENTICATE,
+        &header::PROXY_AUTHORIZATION,
+        &header::TE,
+        &header::TRAILER,
+        &header::TRANSFER_ENCODING,
+        &header::UPGRADE,
+    ] {
+        headers.remove(name);
+    }
+
+    if te_trailers {
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+}
+
+fn upstream_error_response(err: BoxError) -> Response {
+    if is_timeout_error(err.as_ref()) {
+        tracing::debug!(error = %err, "reverse proxy: upstream timeout");
+        StatusCode::GATEWAY_TIMEOUT.into_response()
+    } else {
+        tracing::debug!(error = %err, "reverse proxy: upstream failure");
+        StatusCode::BAD_GATEWAY.into_response()
+    }
+}
+
+fn is_timeout_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<rama_core::layer::timeout::Elapsed>()
+            || err.is::<tokio::time::error::Elapsed>()
+            || err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use crate::headers::{XForwardedFor, XForwardedHost};
+    use rama_core::service::service_fn;
+    use rama_net::stream::SocketInfo;
+
+    fn request(uri: &str) -> Request {
+        Request::builder()
+            .uri(uri)
+            .header(header::HOST, "example.com")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_rewrite() {
+        let client = service_fn(|ctx: Context<()>, req: Request| async move {
+            assert_eq!(req.uri(), "http://backend:8080/v1/users?page=2");
+            assert_eq!(req.headers().get(header::HOST).unwrap(), "backend:8080");
+            assert_eq!(req.version(), Version::HTTP_11);
+            assert_eq!(
+                ctx.get::<RequestContext>().unwrap().authority,
+                Authority::new(Host::try_from("backend").unwrap(), 8080)
+            );
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        });
+
+        let proxy = ReverseProxy::new(client, "http://backend:8080/v1/".parse().unwrap())
+            .with_strip_prefix("/api/");
+        let resp = proxy
+            .serve(Context::default(), request("/api/users?page=2"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_strip_prefix_mismatch_and_preserve_host() {
+        let client = service_fn(|req: Request| async move {
+            assert_eq!(req.uri(), "https://backend/apis/users");
+            assert_eq!(req.headers().get(header::HOST).unwrap(), "example.com");
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        });
+
+        let proxy = ReverseProxy::new(client, "https://backend".parse().unwrap())
+            .with_strip_prefix("/api")
+            .with_preserve_host(true);
+        let resp = proxy
+            .serve(Context::default(), request("/apis/users"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_hop_by_hop_headers() {
+        let client = service_fn(|req: Request| async move {
+            let headers = req.headers();
+            assert!(!headers.contains_key(header::CONNECTION));
+            assert!(!headers.contains_key(&header::KEEP_ALIVE));
+            assert!(!headers.contains_key(header::PROXY_AUTHORIZATION));
+            assert!(!headers.contains_key("x-hop"));
+            assert!(!headers.contains_key(header::UPGRADE));
+            assert_eq!(headers.get(header::TE).unwrap(), "trailers");
+            assert_eq!(headers.get("x-end-to-end").unwrap(), "1");
+
+            let mut resp = Response::new(Body::empty());
+            resp.headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("x-secret"));
+            resp.headers_mut()
+                .insert("x-secret", HeaderValue::from_static("1"));
+            resp.headers_mut()
+                .insert(&header::KEEP_ALIVE, HeaderValue::from_static("timeout=5"));
+            resp.headers_mut()
+                .insert("x-end-to-end", HeaderValue::from_static("1"));
+            Ok::<_, Infallible>(resp)
+        });
+
+        let proxy = ReverseProxy::new(client, "http://backend".parse().unwrap());
+        let mut req = request("/");
+        let headers = req.headers_mut();
+        headers.insert(
+            header::CONNECTION,
+            HeaderValue::from_static("keep-alive, x-hop"),
+        );
+        headers.insert(&header::KEEP_ALIVE, HeaderValue::from_static("timeout=5"));
+        headers.insert(
+            header::PROXY_AUTHORIZATION,
+            HeaderValue::from_static("Basic Zm9vOmJhcg=="),
+        );
+        headers.insert("x-hop", HeaderValue::from_static("1"));
+        headers.insert(header::TE, HeaderValue::from_static("trailers, deflate"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert("x-end-to-end", HeaderValue::from_static("1"));
+
+        let resp = proxy.serve(Context::default(), req).await.unwrap();
+        assert!(!resp.headers().contains_key(header::CONNECTION));
+        assert!(!resp.headers().contains_key(&header::KEEP_ALIVE));
+        assert!(!resp.headers().contains_key("x-secret"));
+        assert_eq!(resp.headers().get("x-end-to-end").unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_forwarded_headers() {
+        let client = service_fn(|req: Request| async move {
+            let headers = req.headers();
+            assert_eq!(
+                headers.get(header::FORWARDED).unwrap(),
+                r#"by=proxy;for="42.37.100.50:62345";host="example.com:80";proto=http"#
+            );
+            assert_eq!(headers.get("x-forwarded-for").unwrap(), "42.37.100.50");
+            assert_eq!(headers.get("x-forwarded-host").unwrap(), "example.com:80");
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        });
+
+        let proxy = ReverseProxy::new(client, "http://backend".parse().unwrap())
+            .with_forward_by(Domain::from_static("proxy"))
+            .with_forwarded_header::<XForwardedFor>()
+            .with_forwarded_header::<XForwardedHost>();
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, "42.37.100.50:62345".parse().unwrap()));
+        let resp = proxy
+            .serve(ctx, request("http://example.com/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_upstream_errors() {
+        let proxy = ReverseProxy::new(
+            service_fn(|_req: Request| async move {
+                Err::<Response, _>(OpaqueError::from_display("connection refused"))
+            }),
+            "http://backend".parse().unwrap(),
+        );
+        let resp = proxy.serve(Context::default(), request("/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+
+        let proxy = ReverseProxy::new(
+            service_fn(|_req: Request| async move {
+                Err::<Response, _>(std::io::Error::from(std::io::ErrorKind::TimedOut))
+            }),
+            "http://backend".parse().unwrap(),
+        );
+        let resp = proxy.serve(Context::default(), request("/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        let proxy = ReverseProxy::new(
+            service_fn(|_req: Request| async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            }),
+            "http://backend".parse().unwrap(),
+        )
+        .with_timeout(Duration::from_millis(10));
+        let resp = proxy.serve(Context::default(), request("/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_dot_segments() {
+        let proxy = ReverseProxy::new(
+            service_fn(|req: Request| async move {
+                assert_eq!(req.uri(), "http://backend/v1/..users/a.b");
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            }),
+            "http://backend/v1".parse().unwrap(),
+        )
+        .with_strip_prefix("/api");
+
+        for path in [
+            "/api/../admin",
+            "/api/./users",
+            "/api/users/..",
+            "/api/%2e%2E/admin",
+            "/api/.%2e/admin",
+            "/api/..%2fadmin",
+            "/api/..%5Cadmin",
+            "/api/%2e",
+        ] {
+            let resp = proxy
+                .serve(Context::default(), request(path))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{path}");
+        }
+
+        let resp = proxy
+            .serve(Context::default(), request("/api/..users/a.b"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_reverse_proxy_invalid_upstream() {
+        assert!(ReverseProxy::try_new((), "/no/authority".parse().unwrap()).is_err());
+        assert!(ReverseProxy::try_new((), "backend:8080".parse().unwrap()).is_err());
+    }
+}
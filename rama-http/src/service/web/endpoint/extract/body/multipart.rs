@@ -0,0 +1,717 @@
+use crate::dep::http_body_util::BodyExt;
+use crate::dep::mime;
+use crate::service::web::extract::FromRequest;
+use crate::utils::macros::{composite_http_rejection, define_http_rejection};
+use crate::{HeaderMap, HeaderName, HeaderValue, IntoResponse, Request, Response, StatusCode};
+use bytes::{Buf, Bytes, BytesMut};
+use rama_core::error::BoxError;
+use std::fmt;
+
+/// Maximum size of the headers of a single multipart field.
+const MAX_FIELD_HEADERS_SIZE: usize = 16 * 1024;
+
+/// Extractor that parses `multipart/form-data` requests,
+/// commonly used for file uploads.
+///
+/// The body is parsed in a streaming fashion: fields are yielded one by one
+/// using [`Multipart::next_field`], and the data of each [`Field`] can in turn
+/// be consumed chunk by chunk using [`Field::chunk`], without buffering
+/// the whole (file) content in memory.
+///
+/// No size limits are applied by default. Use [`Multipart::set_field_limit`]
+/// and [`Multipart::set_total_limit`] to protect your endpoint against
+/// oversized uploads.
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::web::{WebService, extract::Multipart};
+/// use rama_http::StatusCode;
+///
+/// let service = WebService::<()>::default().post("/upload", |mut multipart: Multipart| async move {
+///     multipart.set_field_limit(10 * 1024 * 1024);
+///     while let Some(mut field) = multipart.next_field().await? {
+///         let name = field.name().unwrap_or_default().to_owned();
+///         let mut size = 0;
+///         while let Some(chunk) = field.chunk().await? {
+///             size += chunk.len();
+///         }
+///         println!("received field {name} of {size} bytes");
+///     }
+///     Ok::<_, rama_http::service::web::extract::MultipartError>(StatusCode::OK)
+/// });
+/// ```
+pub struct Multipart {
+    stream: crate::Body,
+    buffer: BytesMut,
+    delimiter: Bytes,
+    state: ParserState,
+    field_limit: Option<usize>,
+    total_limit: Option<usize>,
+    total_read: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParserState {
+    /// Skipping the preamble, looking for the first boundary.
+    Preamble,
+    /// Right after a boundary, which is either followed by
+    /// a CRLF (next field) or `--` (end of the body).
+    Boundary,
+    /// Reading the headers of a field.
+    Headers,
+    /// Reading the data of a field.
+    Data { read: usize },
+    /// The closing boundary was found.
+    End,
+}
+
+impl fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("delimiter", &self.delimiter)
+            .field("state", &self.state)
+            .field("field_limit", &self.field_limit)
+            .field("total_limit", &self.total_limit)
+            .field("total_read", &self.total_read)
+            .finish()
+    }
+}
+
+impl Multipart {
+    /// Create a new [`Multipart`] parser for the given body,
+    /// using the boundary as found in the `Content-Type` header.
+    pub fn new(body: crate::Body, boundary: impl AsRef<str>) -> Self {
+        let boundary = boundary.as_ref();
+        let mut delimiter = BytesMut::with_capacity(boundary.len() + 4);
+        delimiter.extend_from_slice(b"\r\n--");
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            stream: body,
+            buffer: BytesMut::new(),
+            delimiter: delimiter.freeze(),
+            state: ParserState::Preamble,
+            field_limit: None,
+            total_limit: None,
+            total_read: 0,
+        }
+    }
+
+    /// Limit the size of the data of a single field.
+    pub fn with_field_limit(mut self, limit: usize) -> Self {
+        self.field_limit = Some(limit);
+        self
+    }
+
+    /// Limit the size of the data of a single field.
+    pub fn set_field_limit(&mut self, limit: usize) -> &mut Self {
+        self.field_limit = Some(limit);
+        self
+    }
+
+    /// Limit the size of the entire multipart body,
+    /// including the field headers and boundaries.
+    pub fn with_total_limit(mut self, limit: usize) -> Self {
+        self.total_limit = Some(limit);
+        self
+    }
+
+    /// Limit the size of the entire multipart body,
+    /// including the field headers and boundaries.
+    pub fn set_total_limit(&mut self, limit: usize) -> &mut Self {
+        self.total_limit = Some(limit);
+        self
+    }
+
+    /// Yields the next [`Field`] if available.
+    ///
+    /// Any data left unread in the previous field is skipped.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>, MultipartError> {
+        loop {
+            match self.state {
+                ParserState::Preamble => {
+                    // the first boundary is not preceded by a CRLF when there is no preamble,
+                    // which can only be the case while nothing was discarded from the buffer
+                    let dash_boundary = &self.delimiter[2..];
+                    if self.total_read == self.buffer.len()
+                        && self.buffer.starts_with(dash_boundary)
+                    {
+                        self.buffer.advance(dash_boundary.len());
+                        self.state = ParserState::Boundary;
+                    } else if let Some(index) = find(&self.buffer, &self.delimiter) {
+                        self.buffer.advance(index + self.delimiter.len());
+                        self.state = ParserState::Boundary;
+                    } else {
+                        if self.buffer.len() >= self.delimiter.len() {
+                            let keep = self.delimiter.len() - 1;
+                            self.buffer.advance(self.buffer.len() - keep);
+                        }
+                        self.fill_or_incomplete().await?;
+                    }
+                }
+                ParserState::Boundary => {
+                    let padding = self
+                        .buffer
+                        .iter()
+                        .take_while(|b| **b == b' ' || **b == b'\t')
+                        .count();
+                    if self.buffer.starts_with(b"--") {
+                        self.state = ParserState::End;
+                    } else if self.buffer[padding..].starts_with(b"\r\n") {
+                        self.buffer.advance(padding + 2);
+                        self.state = ParserState::Headers;
+                    } else if self.buffer.len() >= padding + 2 {
+                        return Err(MultipartError::new(ErrorKind::InvalidBoundary));
+                    } else {
+                        self.fill_or_incomplete().await?;
+                    }
+                }
+                ParserState::Headers => {
+                    let end = if self.buffer.starts_with(b"\r\n") {
+                        Some(0)
+                    } else {
+                        find(&self.buffer, b"\r\n\r\n").map(|index| index + 2)
+                    };
+                    match end {
+                        Some(end) => {
+                            let raw = self.buffer.split_to(end);
+                            self.buffer.advance(2);
+                            let headers = parse_headers(&raw)?;
+                            self.state = ParserState::Data { read: 0 };
+                            return Ok(Some(Field::new(self, headers)));
+                        }
+                        None if self.buffer.len() > MAX_FIELD_HEADERS_SIZE => {
+                            return Err(MultipartError::new(ErrorKind::InvalidHeaders));
+                        }
+                        None => self.fill_or_incomplete().await?,
+                    }
+                }
+                ParserState::Data { .. } => while self.next_chunk().await?.is_some() {},
+                ParserState::End => return Ok(None),
+            }
+        }
+    }
+
+    /// Read the next chunk of data of the current field,
+    /// returning `None` once the field is consumed.
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        loop {
+            let ParserState::Data { read } = self.state else {
+                return Ok(None);
+            };
+
+            let chunk = match find(&self.buffer, &self.delimiter) {
+                Some(0) => {
+                    self.buffer.advance(self.delimiter.len());
+                    self.state = ParserState::Boundary;
+                    return Ok(None);
+                }
+                Some(index) => self.buffer.split_to(index).freeze(),
+                None => {
+                    // keep enough bytes around to detect a delimiter split over chunks
+                    let safe = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+                    if safe == 0 {
+                        self.fill_or_incomplete().await?;
+                        continue;
+                    }
+                    self.buffer.split_to(safe).freeze()
+                }
+            };
+
+            let read = read + chunk.len();
+            if let Some(limit) = self.field_limit {
+                if read > limit {
+                    return Err(MultipartError::new(ErrorKind::FieldTooLarge(limit)));
+                }
+            }
+            self.state = ParserState::Data { read };
+            return Ok(Some(chunk));
+        }
+    }
+
+    /// Read more data from the body into the buffer,
+    /// failing if the body ends prematurely.
+    async fn fill_or_incomplete(&mut self) -> Result<(), MultipartError> {
+        loop {
+            let frame = match self.stream.frame().await {
+                Some(Ok(frame)) => frame,
+                Some(Err(err)) => return Err(MultipartError::new(ErrorKind::Body(err.into()))),
+                None => return Err(MultipartError::new(ErrorKind::IncompleteStream)),
+            };
+            let Ok(data) = frame.into_data() else {
+                continue;
+            };
+
+            self.total_read += data.len();
+            if let Some(limit) = self.total_limit {
+                if self.total_read > limit {
+                    return Err(MultipartError::new(ErrorKind::StreamTooLarge(limit)));
+                }
+            }
+
+            if !data.is_empty() {
+                self.buffer.extend_from_slice(&data);
+                return Ok(());
+            }
+        }
+    }
+}
+
+define_http_rejection! {
+    #[status = UNSUPPORTED_MEDIA_TYPE]
+    #[body = "Multipart requests must have `Content-Type: multipart/form-data`"]
+    /// Rejection type for [`Multipart`]
+    /// used if the `Content-Type` header is missing
+    /// or its value is not `multipart/form-data`.
+    pub struct InvalidMultipartContentType;
+}
+
+define_http_rejection! {
+    #[status = BAD_REQUEST]
+    #[body = "Multipart requests must define a valid boundary"]
+    /// Rejection type for [`Multipart`]
+    /// used if the `Content-Type` header has no (valid) boundary parameter.
+    pub struct InvalidMultipartBoundary;
+}
+
+composite_http_rejection! {
+    /// Rejection used for [`Multipart`]
+    ///
+    /// Contains one variant for each way the [`Multipart`] extractor
+    /// can fail.
+    pub enum MultipartRejection {
+        InvalidMultipartContentType,
+        InvalidMultipartBoundary,
+    }
+}
+
+impl FromRequest for Multipart {
+    type Rejection = MultipartRejection;
+
+    async fn from_request(req: Request) -> Result<Self, Self::Rejection> {
+        let content_type: mime::Mime = req
+            .headers()
+            .get(crate::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .ok_or(InvalidMultipartContentType)?;
+        if content_type.type_() != mime::MULTIPART || content_type.subtype() != mime::FORM_DATA {
+            return Err(InvalidMultipartContentType.into());
+        }
+
+        let boundary = content_type
+            .get_param(mime::BOUNDARY)
+            .map(|boundary| boundary.as_str())
+            .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+            .ok_or(InvalidMultipartBoundary)?;
+
+        Ok(Self::new(req.into_body(), boundary))
+    }
+}
+
+/// A single field of a [`Multipart`] body.
+///
+/// Its headers are available immediately,
+/// while the data has to be read using [`Field::chunk`],
+/// or collected at once using [`Field::bytes`] or [`Field::text`].
+#[derive(Debug)]
+pub struct Field<'a> {
+    multipart: &'a mut Multipart,
+    headers: HeaderMap,
+    name: Option<String>,
+    file_name: Option<String>,
+}
+
+impl<'a> Field<'a> {
+    fn new(multipart: &'a mut Multipart, headers: HeaderMap) -> Self {
+        let (name, file_name) = headers
+            .get(crate::header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_content_disposition)
+            .unwrap_or_default();
+        Self {
+            multipart,
+            headers,
+            name,
+            file_name,
+        }
+    }
+
+    /// The name of the field, as found in its `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The file name of the field, as found in its `Content-Disposition` header.
+    ///
+    /// This value is controlled by the client and should not be trusted
+    /// to be used as a path on the local file system.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// The value of the `Content-Type` header of the field.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(crate::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// All headers of the field.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Read the next chunk of data of this field,
+    /// returning `None` once all data was read.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        self.multipart.next_chunk().await
+    }
+
+    /// Collect all (remaining) data of this field.
+    pub async fn bytes(mut self) -> Result<Bytes, MultipartError> {
+        let mut data = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data.freeze())
+    }
+
+    /// Collect all (remaining) data of this field as UTF-8 text.
+    pub async fn text(self) -> Result<String, MultipartError> {
+        let data = self.bytes().await?;
+        String::from_utf8(data.into())
+            .map_err(|err| MultipartError::new(ErrorKind::Body(err.into())))
+    }
+}
+
+/// Error returned while reading a [`Multipart`] body.
+///
+/// It can be returned as-is from an endpoint as it implements [`IntoResponse`].
+#[derive(Debug)]
+pub struct MultipartError {
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    InvalidBoundary,
+    InvalidHeaders,
+    IncompleteStream,
+    FieldTooLarge(usize),
+    StreamTooLarge(usize),
+    Body(BoxError),
+}
+
+impl MultipartError {
+    fn new(kind: ErrorKind) -> Self {
+        Self { kind }
+    }
+
+    /// Get the response body text used for this error.
+    pub fn body_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Get the status code used for this error.
+    pub fn status(&self) -> StatusCode {
+        match self.kind {
+            ErrorKind::FieldTooLarge(_) | ErrorKind::StreamTooLarge(_) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::InvalidBoundary => write!(f, "invalid multipart boundary"),
+            ErrorKind::InvalidHeaders => write!(f, "invalid multipart field headers"),
+            ErrorKind::IncompleteStream => write!(f, "incomplete multipart body"),
+            ErrorKind::FieldTooLarge(limit) => {
+                write!(f, "multipart field exceeds the limit of {limit} bytes")
+            }
+            ErrorKind::StreamTooLarge(limit) => {
+                write!(f, "multipart body exceeds the limit of {limit} bytes")
+            }
+            ErrorKind::Body(err) => write!(f, "failed to read multipart body: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Body(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for MultipartError {
+    fn into_response(self) -> Response {
+        let body_text = self.body_text();
+        crate::utils::macros::log_http_rejection!(
+            rejection_type = MultipartError,
+            body_text = body_text,
+            status = self.status(),
+        );
+        (self.status(), body_text).into_response()
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if haystack.len() < needle.len() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn parse_headers(raw: &[u8]) -> Result<HeaderMap, MultipartError> {
+    let mut headers = HeaderMap::new();
+    for line in raw.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let colon = line
+            .iter()
+            .position(|b| *b == b':')
+            .ok_or_else(|| MultipartError::new(ErrorKind::InvalidHeaders))?;
+        let name = HeaderName::from_bytes(line[..colon].trim_ascii())
+            .map_err(|_| MultipartError::new(ErrorKind::InvalidHeaders))?;
+        let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii())
+            .map_err(|_| MultipartError::new(ErrorKind::InvalidHeaders))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Parse the `name` and `filename` parameters from a `Content-Disposition` header value.
+fn parse_content_disposition(value: &str) -> (Option<String>, Option<String>) {
+    let mut name = None;
+    let mut file_name = None;
+    let mut file_name_ext = None;
+
+    let mut rest = value.split_once(';').map(|(_, rest)| rest).unwrap_or("");
+    while let Some((key, after_key)) = rest.split_once('=') {
+        let key = key.trim();
+        let after_key = after_key.trim_start();
+        let (param, remainder) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let mut param = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                param.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = index + 1;
+                            break;
+                        }
+                        c => param.push(c),
+                    }
+                }
+                let remainder = &quoted[end..];
+                let remainder = remainder
+                    .split_once(';')
+                    .map(|(_, rest)| rest)
+                    .unwrap_or("");
+                (param, remainder)
+            }
+            None => {
+                let (param, remainder) = after_key.split_once(';').unwrap_or((after_key, ""));
+                (param.trim().to_owned(), remainder)
+            }
+        };
+
+        if key.eq_ignore_ascii_case("name") {
+            name = Some(param);
+        } else if key.eq_ignore_ascii_case("filename") {
+            file_name = Some(param);
+        } else if key.eq_ignore_ascii_case("filename*") {
+            // RFC 5987 extended notation, e.g. `UTF-8''na%C3%AFve.txt`
+            file_name_ext = param
+                .split_once("''")
+                .filter(|(charset, _)| charset.eq_ignore_ascii_case("utf-8"))
+                .and_then(|(_, encoded)| {
+                    percent_encoding::percent_decode_str(encoded)
+                        .decode_utf8()
+                        .ok()
+                        .map(|decoded| decoded.into_owned())
+                });
+        }
+        rest = remainder;
+    }
+
+    (name, file_name_ext.or(file_name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::service::web::WebService;
+    use crate::{Body, Method, Request, StatusCode};
+    use rama_core::{Context, Service};
+
+    const BODY: &str = "preamble\r\n\
+        --X-BOUNDARY\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        hello\r\n\
+        --X-BOUNDARY\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a \\\"b\\\".txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line one\r\n--X-BOUNDAR\r\nline two\r\n\
+        --X-BOUNDARY--\r\n\
+        epilogue";
+
+    fn chunked_body(data: &'static str, chunk_size: usize) -> Body {
+        let chunks: Vec<Result<Bytes, BoxError>> = data
+            .as_bytes()
+            .chunks(chunk_size)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        Body::from_stream(futures_lite::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn test_multipart_fields() {
+        for chunk_size in [1, 3, 7, 64, BODY.len()] {
+            let mut multipart = Multipart::new(chunked_body(BODY, chunk_size), "X-BOUNDARY");
+
+            let field = multipart.next_field().await.unwrap().unwrap();
+            assert_eq!(field.name(), Some("title"));
+            assert_eq!(field.file_name(), None);
+            assert_eq!(field.content_type(), None);
+            assert_eq!(field.text().await.unwrap(), "hello");
+
+            let field = multipart.next_field().await.unwrap().unwrap();
+            assert_eq!(field.name(), Some("file"));
+            assert_eq!(field.file_name(), Some("a \"b\".txt"));
+            assert_eq!(field.content_type(), Some("text/plain"));
+            assert_eq!(
+                field.bytes().await.unwrap(),
+                "line one\r\n--X-BOUNDAR\r\nline two"
+            );
+
+            assert!(multipart.next_field().await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multipart_skip_unread_field() {
+        let mut multipart = Multipart::new(chunked_body(BODY, 5), "X-BOUNDARY");
+        let _ = multipart.next_field().await.unwrap().unwrap();
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_limits() {
+        let mut multipart =
+            Multipart::new(chunked_body(BODY, 4), "X-BOUNDARY").with_field_limit(16);
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.text().await.unwrap(), "hello");
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let err = field.bytes().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut multipart =
+            Multipart::new(chunked_body(BODY, 4), "X-BOUNDARY").with_total_limit(64);
+        let err = loop {
+            match multipart.next_field().await {
+                Ok(Some(field)) => {
+                    if let Err(err) = field.bytes().await {
+                        break err;
+                    }
+                }
+                Ok(None) => panic!("expected total limit to be exceeded"),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_incomplete() {
+        let body = "--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nabc";
+        let mut multipart = Multipart::new(chunked_body(body, 8), "X-BOUNDARY");
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let err = field.bytes().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_parse_content_disposition() {
+        for (input, expected_name, expected_file_name) in [
+            ("form-data; name=\"a\"", Some("a"), None),
+            (
+                "form-data; name=a; filename=b.txt",
+                Some("a"),
+                Some("b.txt"),
+            ),
+            (
+                "form-data; name=\"a;b\"; filename=\"c.txt\"",
+                Some("a;b"),
+                Some("c.txt"),
+            ),
+            (
+                "form-data; name=\"f\"; filename=\"fallback.txt\"; filename*=UTF-8''na%C3%AFve.txt",
+                Some("f"),
+                Some("naïve.txt"),
+            ),
+            ("form-data", None, None),
+        ] {
+            let (name, file_name) = parse_content_disposition(input);
+            assert_eq!(name.as_deref(), expected_name, "input: {input}");
+            assert_eq!(file_name.as_deref(), expected_file_name, "input: {input}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multipart_extractor() {
+        let service = WebService::default().post("/", |mut multipart: Multipart| async move {
+            let field = multipart.next_field().await.unwrap().unwrap();
+            assert_eq!(field.name(), Some("title"));
+            assert_eq!(field.text().await.unwrap(), "hello");
+        });
+
+        let req = Request::builder()
+            .uri("/")
+            .method(Method::POST)
+            .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+            .body(BODY.into())
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = Request::builder()
+            .uri("/")
+            .method(Method::POST)
+            .header("content-type", "multipart/form-data")
+            .body(BODY.into())
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::builder()
+            .uri("/")
+            .method(Method::POST)
+            .header("content-type", "application/json")
+            .body(BODY.into())
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
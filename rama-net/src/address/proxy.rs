@@ -265,6 +265,7 @@ mod tests {
             };
             let out = addr.to_string();
             let mut s = s.to_owned();
+            let port = if s.starts_with("socks5://") { 1080 } else { 80 };
             if !s.ends_with(":8080") {
                 if s.contains("::1") {
                     let mut it = s.split("://");
@@ -275,17 +276,17 @@ mod tests {
                         let credential = it.next().unwrap();
                         let host = it.next().unwrap();
                         s = match scheme {
-                            Some(scheme) => format!("{scheme}://{credential}@[{host}]:80"),
-                            None => format!("{credential}@[{host}]:80"),
+                            Some(scheme) => format!("{scheme}://{credential}@[{host}]:{port}"),
+                            None => format!("{credential}@[{host}]:{port}"),
                         };
                     } else {
                         s = match scheme {
-                            Some(scheme) => format!("{scheme}://[{host}]:80"),
-                            None => format!("[{host}]:80"),
+                            Some(scheme) => format!("{scheme}://[{host}]:{port}"),
+                            None => format!("[{host}]:{port}"),
                         };
                     }
                 } else {
-                    s = format!("{s}:80");
+                    s = format!("{s}:{port}");
                 }
             }
             assert_eq!(s, out);
@@ -0,0 +1,334 @@
+use crate::stream::dep::ipnet::IpNet;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Policy restricting the IP addresses which can be connected to,
+/// used to guard against server-side request forgery (SSRF).
+///
+/// By default it denies all private and reserved addresses, as listed in the
+/// [IANA Special-Purpose Address Registries] (e.g. loopback, link-local and
+/// [RFC 1918] addresses). Additional networks can be denied and networks can be
+/// allowed, where an allowed network takes precedence over a denied one.
+///
+/// IPv6 addresses which embed an IPv4 address — IPv4-mapped (`::ffff:0:0/96`),
+/// IPv4-compatible (`::/96`), NAT64 (`64:ff9b::/96`) and 6to4 (`2002::/16`)
+/// addresses — are denied when the embedded IPv4 address is denied.
+/// Local-use NAT64 (`64:ff9b:1::/48`) and Teredo (`2001::/32`) addresses, which can
+/// embed an IPv4 address in other ways, are denied as a whole.
+///
+/// The policy is to be checked against the resolved addresses right before connecting,
+/// such that the check cannot be bypassed using a domain resolving to
+/// a denied address (DNS rebinding). The `EgressGuardConnector` of `rama-tcp`
+/// does exactly that for tcp connections.
+///
+/// [IANA Special-Purpose Address Registries]: https://www.iana.org/assignments/iana-ipv4-special-registry/iana-ipv4-special-registry.xhtml
+/// [RFC 1918]: https://datatracker.ietf.org/doc/html/rfc1918
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    deny: Vec<IpNet>,
+    allow: Vec<IpNet>,
+}
+
+const RESERVED_NETWORKS: [&str; 25] = [
+    // This host on this network (RFC 1122)
+    "0.0.0.0/8",
+    // Private-Use (RFC 1918)
+    "10.0.0.0/8",
+    // Shared Address Space (RFC 6598)
+    "100.64.0.0/10",
+    // Loopback (RFC 1122)
+    "127.0.0.0/8",
+    // Link Local (RFC 3927)
+    "169.254.0.0/16",
+    // Private-Use (RFC 1918)
+    "172.16.0.0/12",
+    // IETF Protocol Assignments (RFC 6890)
+    "192.0.0.0/24",
+    // Documentation (TEST-NET-1, RFC 5737)
+    "192.0.2.0/24",
+    // 6to4 Relay Anycast (RFC 7526)
+    "192.88.99.0/24",
+    // Private-Use (RFC 1918)
+    "192.168.0.0/16",
+    // Benchmarking (RFC 2544)
+    "198.18.0.0/15",
+    // Documentation (TEST-NET-2, RFC 5737)
+    "198.51.100.0/24",
+    // Documentation (TEST-NET-3, RFC 5737)
+    "203.0.113.0/24",
+    // Multicast (RFC 5771)
+    "224.0.0.0/4",
+    // Reserved and Limited Broadcast (RFC 1112, RFC 919)
+    "240.0.0.0/4",
+    // Unspecified Address (RFC 4291)
+    "::/128",
+    // Loopback Address (RFC 4291)
+    "::1/128",
+    // Local-Use IPv4/IPv6 Translation (RFC 8215)
+    "64:ff9b:1::/48",
+    // Discard-Only Address Block (RFC 6666)
+    "100::/64",
+    // TEREDO (RFC 4380)
+    "2001::/32",
+    // Documentation (RFC 3849)
+    "2001:db8::/32",
+    // Unique-Local (RFC 4193)
+    "fc00::/7",
+    // Site-Local, deprecated (RFC 3879)
+    "fec0::/10",
+    // Link-Scoped Unicast (RFC 4291)
+    "fe80::/10",
+    // Multicast (RFC 4291)
+    "ff00::/8",
+];
+
+impl EgressPolicy {
+    /// Create a new [`EgressPolicy`] denying all private and reserved addresses.
+    pub fn new() -> Self {
+        Self {
+            deny: RESERVED_NETWORKS
+                .iter()
+                .map(|net| net.parse().expect("parse reserved network as IpNet"))
+                .collect(),
+            allow: Vec::new(),
+        }
+    }
+
+    /// Create a new [`EgressPolicy`] which does not deny any address,
+    /// to be used in case you want to define the denied networks yourself.
+    pub fn empty() -> Self {
+        Self {
+            deny: Vec::new(),
+            allow: Vec::new(),
+        }
+    }
+
+    /// Deny the given network (or address).
+    pub fn with_deny(mut self, net: impl Into<IpNet>) -> Self {
+        self.deny.push(net.into());
+        self
+    }
+
+    /// Deny the given network (or address).
+    pub fn set_deny(&mut self, net: impl Into<IpNet>) -> &mut Self {
+        self.deny.push(net.into());
+        self
+    }
+
+    /// Allow the given network (or address), even if it is denied.
+    pub fn with_allow(mut self, net: impl Into<IpNet>) -> Self {
+        self.allow.push(net.into());
+        self
+    }
+
+    /// Allow the given network (or address), even if it is denied.
+    pub fn set_allow(&mut self, net: impl Into<IpNet>) -> &mut Self {
+        self.allow.push(net.into());
+        self
+    }
+
+    /// Returns `true` if the given address is allowed to be connected to.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.check(ip).is_ok()
+    }
+
+    /// Check if the given address is allowed to be connected to,
+    /// returning an [`EgressDenied`] error if it isn't.
+    pub fn check(&self, ip: IpAddr) -> Result<(), EgressDenied> {
+        let ip = ip.to_canonical();
+        let embedded = match ip {
+            IpAddr::V4(_) => None,
+            IpAddr::V6(ip) => embedded_ipv4(ip).map(IpAddr::V4),
+        };
+        if std::iter::once(ip)
+            .chain(embedded)
+            .any(|ip| self.is_denied(ip))
+        {
+            return Err(EgressDenied { ip });
+        }
+        Ok(())
+    }
+
+    fn is_denied(&self, ip: IpAddr) -> bool {
+        let contains = |net: &IpNet| net.contains(&ip);
+        self.deny.iter().any(contains) && !self.allow.iter().any(contains)
+    }
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the IPv4 address embedded in an IPv4-compatible,
+/// NAT64 or 6to4 address, if any.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    match segments {
+        // IPv4-compatible (excluding the unspecified and loopback addresses)
+        [0, 0, 0, 0, 0, 0, _, _] if !matches!(segments[6..], [0, 0 | 1]) => Some(Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        )),
+        // NAT64 well-known prefix
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        )),
+        // 6to4
+        [0x2002, _, _, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Error returned when a connection to an address is denied by an [`EgressPolicy`].
+///
+/// Use [`EgressDenied::find`] to find it within the chain of a (boxed) error,
+/// e.g. to respond with a `403 Forbidden` rather than a `502 Bad Gateway`,
+/// which is the response it turns into (with the `http` feature enabled).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressDenied {
+    ip: IpAddr,
+}
+
+impl EgressDenied {
+    /// The (canonical) address to which the connection was denied.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Find an [`EgressDenied`] error in the given error or its sources.
+    pub fn find<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a Self> {
+        let mut err = Some(err);
+        while let Some(current) = err {
+            if let Some(denied) = current.downcast_ref::<Self>() {
+                return Some(denied);
+            }
+            err = current.source();
+        }
+        None
+    }
+}
+
+impl fmt::Display for EgressDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "egress to {} denied by policy", self.ip)
+    }
+}
+
+impl std::error::Error for EgressDenied {}
+
+#[cfg(feature = "http")]
+impl rama_http_types::IntoResponse for EgressDenied {
+    fn into_response(self) -> rama_http_types::Response {
+        rama_http_types::StatusCode::FORBIDDEN.into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::error::{ErrorContext, ErrorExt, OpaqueError};
+
+    #[test]
+    fn test_default_policy() {
+        let policy = EgressPolicy::default();
+        for (ip, allowed) in [
+            ("1.1.1.1", true),
+            ("93.184.216.34", true),
+            ("2606:4700:4700::1111", true),
+            ("0.0.0.0", false),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.20.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.100.100.200", false),
+            ("255.255.255.255", false),
+            ("224.0.0.1", false),
+            ("::", false),
+            ("::1", false),
+            ("fe80::1", false),
+            ("fd00::1", false),
+            ("ff02::1", false),
+            // IPv4-mapped
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:169.254.169.254", false),
+            ("::ffff:1.1.1.1", true),
+            // IPv4-compatible
+            ("::127.0.0.1", false),
+            ("::1.1.1.1", true),
+            // NAT64
+            ("64:ff9b::a00:1", false),
+            ("64:ff9b::101:101", true),
+            // local-use NAT64
+            ("64:ff9b:1::a00:1", false),
+            ("64:ff9b:1:a00:1::", false),
+            ("64:ff9b:1::101:101", false),
+            // Teredo
+            ("2001:0:4136:e378:8000:63bf:3fff:fdd2", false),
+            ("2001:0:101:101::", false),
+            ("2001:4860:4860::8888", true),
+            // 6to4
+            ("2002:7f00:1::1", false),
+            ("2002:101:101::1", true),
+        ] {
+            let ip: IpAddr = ip.parse().unwrap();
+            assert_eq!(policy.is_allowed(ip), allowed, "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        let policy = EgressPolicy::new()
+            .with_allow("10.0.0.0/24".parse::<IpNet>().unwrap())
+            .with_deny(IpAddr::from([1, 1, 1, 1]));
+
+        for (ip, allowed) in [
+            ("10.0.0.5", true),
+            ("::ffff:10.0.0.5", true),
+            ("10.0.1.5", false),
+            ("1.1.1.1", false),
+            ("1.0.0.1", true),
+        ] {
+            let ip: IpAddr = ip.parse().unwrap();
+            assert_eq!(policy.is_allowed(ip), allowed, "{ip}");
+        }
+
+        let policy = EgressPolicy::empty();
+        assert!(policy.is_allowed(IpAddr::from([127, 0, 0, 1])));
+    }
+
+    #[test]
+    fn test_egress_denied_find() {
+        let ip: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        let err = EgressPolicy::new().check(ip).unwrap_err();
+        assert_eq!(err.ip(), IpAddr::from([127, 0, 0, 1]));
+
+        let err: Result<(), _> = Err(err);
+        let err = err
+            .context("connect")
+            .map_err(|err| OpaqueError::from_boxed(err.into()).context("serve"))
+            .unwrap_err();
+        assert_eq!(
+            EgressDenied::find(&err).map(EgressDenied::ip),
+            Some(IpAddr::from([127, 0, 0, 1])),
+        );
+
+        let err = OpaqueError::from_display("connection refused");
+        assert!(EgressDenied::find(&err).is_none());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_egress_denied_into_response() {
+        use rama_http_types::{IntoResponse, StatusCode};
+
+        let err = EgressPolicy::new()
+            .check(IpAddr::from([127, 0, 0, 1]))
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -3,3 +3,11 @@
 mod conn;
 #[doc(inline)]
 pub use conn::{ConnectorService, EstablishedClientConnection};
+
+mod trace;
+#[doc(inline)]
+pub use trace::{ConnectionTrace, ConnectionTraceHandle};
+
+mod egress;
+#[doc(inline)]
+pub use egress::{EgressDenied, EgressPolicy};
@@ -0,0 +1,136 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+#[cfg(feature = "tls")]
+use crate::tls::client::NegotiatedTlsParameters;
+
+/// Information on how a client connection was established,
+/// as recorded by the connectors via a [`ConnectionTraceHandle`].
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTrace {
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    tls: Option<Duration>,
+    server_addr: Option<SocketAddr>,
+    #[cfg(feature = "tls")]
+    tls_parameters: Option<NegotiatedTlsParameters>,
+}
+
+impl ConnectionTrace {
+    /// Time it took to resolve the server domain,
+    /// `None` in case no resolution was required.
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Time it took to establish the transport connection,
+    /// excluding the [`ConnectionTrace::dns`] time.
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// Time it took to complete the tls handshake,
+    /// `None` in case the connection is not secured.
+    pub fn tls(&self) -> Option<Duration> {
+        self.tls
+    }
+
+    /// Address of the server the transport connection was established with.
+    pub fn server_addr(&self) -> Option<SocketAddr> {
+        self.server_addr
+    }
+
+    #[cfg(feature = "tls")]
+    /// Parameters negotiated during the tls handshake,
+    /// `None` in case the connection is not secured.
+    pub fn tls_parameters(&self) -> Option<&NegotiatedTlsParameters> {
+        self.tls_parameters.as_ref()
+    }
+}
+
+/// A handle which can be inserted in the [`Context`] of a client request,
+/// such that the connectors can record how the connection is established.
+///
+/// Connectors only record what they find out about the connection,
+/// e.g. no [`ConnectionTrace::dns`] time is recorded when connecting to an IP address.
+/// In case the connector stack retries, the first recorded value is kept.
+///
+/// [`Context`]: rama_core::Context
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTraceHandle(Arc<Mutex<ConnectionTrace>>);
+
+impl ConnectionTraceHandle {
+    /// Create a new empty [`ConnectionTraceHandle`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a copy of the [`ConnectionTrace`] recorded so far.
+    pub fn trace(&self) -> ConnectionTrace {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Record the time it took to resolve the server domain.
+    pub fn record_dns(&self, duration: Duration) {
+        self.update(|trace| {
+            trace.dns.get_or_insert(duration);
+        })
+    }
+
+    /// Record the time it took to establish the transport connection,
+    /// as well as the address of the server it was established with.
+    pub fn record_connect(&self, duration: Duration, server_addr: SocketAddr) {
+        self.update(|trace| {
+            if trace.connect.is_none() {
+                trace.connect = Some(duration);
+                trace.server_addr = Some(server_addr);
+            }
+        })
+    }
+
+    #[cfg(feature = "tls")]
+    /// Record the time it took to complete the tls handshake,
+    /// as well as the parameters negotiated during it.
+    pub fn record_tls(&self, duration: Duration, parameters: NegotiatedTlsParameters) {
+        self.update(|trace| {
+            if trace.tls.is_none() {
+                trace.tls = Some(duration);
+                trace.tls_parameters = Some(parameters);
+            }
+        })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ConnectionTrace)) {
+        f(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_trace_keeps_first_record() {
+        let handle = ConnectionTraceHandle::new();
+        assert!(handle.trace().dns().is_none());
+
+        handle.record_dns(Duration::from_millis(5));
+        handle.record_dns(Duration::from_millis(10));
+        handle.record_connect(Duration::from_millis(20), ([127, 0, 0, 1], 80).into());
+        handle
+            .clone()
+            .record_connect(Duration::from_millis(30), ([127, 0, 0, 2], 80).into());
+
+        let trace = handle.trace();
+        assert_eq!(trace.dns(), Some(Duration::from_millis(5)));
+        assert_eq!(trace.connect(), Some(Duration::from_millis(20)));
+        assert_eq!(trace.server_addr(), Some(([127, 0, 0, 1], 80).into()));
+        assert!(trace.tls().is_none());
+    }
+}
@@ -0,0 +1,449 @@
+use super::GeoInfo;
+use crate::asn::Asn;
+use maxminddb::{Mmap, Reader};
+use rama_core::error::{ErrorContext, OpaqueError};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// A GeoIP database, looking up [`GeoInfo`] in one or more local MMDB files.
+///
+/// Supported are MMDB files following the GeoIP2 schema, such as the
+/// MaxMind GeoIP2/GeoLite2 City, Country, ASN and ISP databases,
+/// as well as the IP2Location databases in MMDB format. Multiple databases
+/// can be combined, e.g. a City and an ASN database, in which case the information
+/// found for an address is merged, with the first database taking precedence.
+///
+/// The files are memory-mapped and can be reloaded using [`GeoIpDatabase::reload`],
+/// while lookups in progress keep using the previously loaded files.
+/// Updated files should be moved into place (e.g. renamed) rather than
+/// written in-place, as modifying a memory-mapped file is not safe.
+///
+/// The database is cheap to clone, clones share the loaded files.
+#[derive(Clone)]
+pub struct GeoIpDatabase {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    paths: Vec<PathBuf>,
+    readers: RwLock<Arc<Vec<Reader<Mmap>>>>,
+}
+
+impl fmt::Debug for GeoIpDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoIpDatabase")
+            .field("paths", &self.inner.paths)
+            .finish()
+    }
+}
+
+impl GeoIpDatabase {
+    /// Open the MMDB file at the given path as a [`GeoIpDatabase`].
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, OpaqueError> {
+        Self::open_all([path])
+    }
+
+    /// Open all the MMDB files at the given paths as a single [`GeoIpDatabase`].
+    ///
+    /// See [`GeoIpDatabase`] for more information on how the results are combined.
+    pub fn open_all<P: Into<PathBuf>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<Self, OpaqueError> {
+        let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+        if paths.is_empty() {
+            return Err(OpaqueError::from_display(
+                "geoip database: no database files specified",
+            ));
+        }
+        let readers = open_readers(&paths)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                paths,
+                readers: RwLock::new(Arc::new(readers)),
+            }),
+        })
+    }
+
+    /// Paths of the MMDB files this database is loaded from.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.inner.paths.iter().map(PathBuf::as_path)
+    }
+
+    /// (Re)load all the MMDB files of this database from disk.
+    ///
+    /// In case any of the files fails to load an error is returned and
+    /// the previously loaded files remain in use.
+    pub fn reload(&self) -> Result<(), OpaqueError> {
+        let readers = open_readers(&self.inner.paths)?;
+        *self
+            .inner
+            .readers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(readers);
+        tracing::debug!(paths = ?self.inner.paths, "reloaded geoip database");
+        Ok(())
+    }
+
+    /// Look up the [`GeoInfo`] of the given IP address.
+    ///
+    /// Returns `None` in case none of the databases has any information about the address.
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let ip = ip.to_canonical();
+        let readers = self
+            .inner
+            .readers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let mut info = GeoInfo::default();
+        for (reader, path) in readers.iter().zip(self.inner.paths.iter()) {
+            match reader.lookup::<GeoRecord>(ip) {
+                Ok(Some(record)) => record.merge_into(&mut info),
+                Ok(None) => (),
+                Err(err) => {
+                    tracing::trace!(%ip, ?path, ?err, "geoip database: lookup failed");
+                }
+            }
+        }
+        (!info.is_empty()).then_some(info)
+    }
+}
+
+fn open_readers(paths: &[PathBuf]) -> Result<Vec<Reader<Mmap>>, OpaqueError> {
+    paths
+        .iter()
+        .map(|path| {
+            Reader::open_mmap(path)
+                .with_context(|| format!("geoip database: open mmdb file {}", path.display()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+/// The subset of a GeoIP2 record that makes up the [`GeoInfo`].
+struct GeoRecord<'a> {
+    #[serde(borrow)]
+    continent: Option<ContinentRecord<'a>>,
+    #[serde(borrow)]
+    country: Option<CountryRecord<'a>>,
+    #[serde(borrow)]
+    city: Option<CityRecord<'a>>,
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<&'a str>,
+    organization: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContinentRecord<'a> {
+    code: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountryRecord<'a> {
+    iso_code: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CityRecord<'a> {
+    #[serde(borrow)]
+    names: Option<BTreeMap<&'a str, &'a str>>,
+}
+
+impl GeoRecord<'_> {
+    /// Fill in the fields of the [`GeoInfo`] that are not yet known.
+    fn merge_into(self, info: &mut GeoInfo) {
+        if info.continent.is_none() {
+            info.continent = self.continent.and_then(|c| c.code).map(Into::into);
+        }
+        if info.country.is_none() {
+            info.country = self.country.and_then(|c| c.iso_code).map(Into::into);
+        }
+        if info.city.is_none() {
+            info.city = self
+                .city
+                .and_then(|c| c.names)
+                .and_then(|names| names.get("en").copied())
+                .map(Into::into);
+        }
+        if info.asn.is_none() {
+            info.asn = self
+                .autonomous_system_number
+                .and_then(|asn| Asn::try_from(asn).ok());
+        }
+        if info.org.is_none() {
+            info.org = self
+                .autonomous_system_organization
+                .or(self.organization)
+                .map(Into::into);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// Minimal MMDB (IPv4, 24-bit records) writer used to create test databases.
+    pub(in crate::geo) fn build_mmdb(entries: &[(Ipv4Addr, u8, Vec<u8>)]) -> Vec<u8> {
+        #[derive(Clone, Copy)]
+        enum Record {
+            Empty,
+            Node(usize),
+            Data(usize),
+        }
+
+        let mut nodes = vec![[Record::Empty; 2]];
+        let mut data = Vec::new();
+        let mut data_offsets = Vec::new();
+
+        for (index, (ip, prefix_len, record)) in entries.iter().enumerate() {
+            data_offsets.push(data.len());
+            data.extend_from_slice(record);
+
+            let bits = u32::from(*ip);
+            let mut node = 0;
+            for i in 0..*prefix_len {
+                let bit = ((bits >> (31 - i)) & 1) as usize;
+                if i + 1 == *prefix_len {
+                    nodes[node][bit] = Record::Data(index);
+                } else {
+                    node = match nodes[node][bit] {
+                        Record::Node(next) => next,
+                        _ => {
+                            nodes.push([Record::Empty; 2]);
+                            let next = nodes.len() - 1;
+                            nodes[node][bit] = Record::Node(next);
+                            next
+                        }
+                    };
+                }
+            }
+        }
+
+        let node_count = nodes.len();
+        let mut buf = Vec::new();
+        for node in nodes {
+            for record in node {
+                let value = match record {
+                    Record::Empty => node_count,
+                    Record::Node(next) => next,
+                    Record::Data(index) => node_count + 16 + data_offsets[index],
+                };
+                buf.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+            }
+        }
+        buf.extend_from_slice(&[0; 16]);
+        buf.extend_from_slice(&data);
+
+        buf.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        buf.extend(map(9));
+        buf.extend(string("binary_format_major_version"));
+        buf.extend(uint16(2));
+        buf.extend(string("binary_format_minor_version"));
+        buf.extend(uint16(0));
+        buf.extend(string("build_epoch"));
+        buf.extend([8, 2]);
+        buf.extend_from_slice(&0u64.to_be_bytes());
+        buf.extend(string("database_type"));
+        buf.extend(string("rama-test"));
+        buf.extend(string("description"));
+        buf.extend(map(0));
+        buf.extend(string("ip_version"));
+        buf.extend(uint16(4));
+        buf.extend(string("languages"));
+        buf.extend([1, 4]);
+        buf.extend(string("en"));
+        buf.extend(string("node_count"));
+        buf.extend(uint32(node_count as u32));
+        buf.extend(string("record_size"));
+        buf.extend(uint16(24));
+        buf
+    }
+
+    pub(in crate::geo) fn map(len: u8) -> Vec<u8> {
+        vec![(7 << 5) | len]
+    }
+
+    pub(in crate::geo) fn string(s: &str) -> Vec<u8> {
+        let mut buf = if s.len() < 29 {
+            vec![(2 << 5) | s.len() as u8]
+        } else {
+            vec![(2 << 5) | 29, (s.len() - 29) as u8]
+        };
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    pub(in crate::geo) fn uint16(v: u16) -> Vec<u8> {
+        let mut buf = vec![(5 << 5) | 2];
+        buf.extend_from_slice(&v.to_be_bytes());
+        buf
+    }
+
+    pub(in crate::geo) fn uint32(v: u32) -> Vec<u8> {
+        let mut buf = vec![(6 << 5) | 4];
+        buf.extend_from_slice(&v.to_be_bytes());
+        buf
+    }
+
+    pub(in crate::geo) fn city_record(continent: &str, country: &str, city: &str) -> Vec<u8> {
+        let mut buf = map(3);
+        buf.extend(string("continent"));
+        buf.extend(map(1));
+        buf.extend(string("code"));
+        buf.extend(string(continent));
+        buf.extend(string("country"));
+        buf.extend(map(1));
+        buf.extend(string("iso_code"));
+        buf.extend(string(country));
+        buf.extend(string("city"));
+        buf.extend(map(1));
+        buf.extend(string("names"));
+        buf.extend(map(1));
+        buf.extend(string("en"));
+        buf.extend(string(city));
+        buf
+    }
+
+    pub(in crate::geo) fn asn_record(asn: u32, org: &str) -> Vec<u8> {
+        let mut buf = map(2);
+        buf.extend(string("autonomous_system_number"));
+        buf.extend(uint32(asn));
+        buf.extend(string("autonomous_system_organization"));
+        buf.extend(string(org));
+        buf
+    }
+
+    pub(in crate::geo) fn write_mmdb(
+        dir: &Path,
+        name: &str,
+        entries: &[(Ipv4Addr, u8, Vec<u8>)],
+    ) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, build_mmdb(entries)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_geoip_database_lookup_merges_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let city = write_mmdb(
+            dir.path(),
+            "city.mmdb",
+            &[
+                (
+                    Ipv4Addr::new(81, 0, 0, 0),
+                    8,
+                    city_record("EU", "BE", "Brussels"),
+                ),
+                (
+                    Ipv4Addr::new(24, 0, 0, 0),
+                    8,
+                    city_record("NA", "US", "New York"),
+                ),
+            ],
+        );
+        let asn = write_mmdb(
+            dir.path(),
+            "asn.mmdb",
+            &[(
+                Ipv4Addr::new(81, 82, 0, 0),
+                16,
+                asn_record(5432, "Proximus NV"),
+            )],
+        );
+
+        let db = GeoIpDatabase::open_all([city, asn]).unwrap();
+
+        assert_eq!(
+            db.lookup(Ipv4Addr::new(81, 82, 1, 2).into()),
+            Some(GeoInfo {
+                continent: Some("EU".to_owned()),
+                country: Some("BE".to_owned()),
+                city: Some("Brussels".to_owned()),
+                asn: Some(Asn::from_static(5432)),
+                org: Some("Proximus NV".to_owned()),
+            })
+        );
+        assert_eq!(
+            db.lookup(Ipv4Addr::new(24, 1, 2, 3).into()),
+            Some(GeoInfo {
+                continent: Some("NA".to_owned()),
+                country: Some("US".to_owned()),
+                city: Some("New York".to_owned()),
+                asn: None,
+                org: None,
+            })
+        );
+        // ipv4-mapped ipv6 addresses are looked up as their ipv4 address
+        assert_eq!(
+            db.lookup(Ipv4Addr::new(24, 1, 2, 3).to_ipv6_mapped().into())
+                .and_then(|info| info.country),
+            Some("US".to_owned())
+        );
+        assert_eq!(db.lookup(Ipv4Addr::new(10, 0, 0, 1).into()), None);
+        assert_eq!(db.lookup("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_geoip_database_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_mmdb(
+            dir.path(),
+            "city.mmdb",
+            &[(
+                Ipv4Addr::new(81, 0, 0, 0),
+                8,
+                city_record("EU", "BE", "Brussels"),
+            )],
+        );
+        let db = GeoIpDatabase::open(path.clone()).unwrap();
+        let ip = Ipv4Addr::new(81, 1, 1, 1).into();
+        assert_eq!(
+            db.lookup(ip).and_then(|info| info.city),
+            Some("Brussels".to_owned())
+        );
+
+        // replace the file by moving an updated one in place
+        let updated = write_mmdb(
+            dir.path(),
+            "city.mmdb.tmp",
+            &[(
+                Ipv4Addr::new(81, 0, 0, 0),
+                8,
+                city_record("EU", "BE", "Antwerp"),
+            )],
+        );
+        std::fs::rename(updated, &path).unwrap();
+        db.reload().unwrap();
+        assert_eq!(
+            db.lookup(ip).and_then(|info| info.city),
+            Some("Antwerp".to_owned())
+        );
+
+        // a failed reload keeps the loaded database
+        let invalid = dir.path().join("invalid.mmdb.tmp");
+        std::fs::write(&invalid, b"not a mmdb file").unwrap();
+        std::fs::rename(invalid, &path).unwrap();
+        assert!(db.reload().is_err());
+        assert_eq!(
+            db.lookup(ip).and_then(|info| info.city),
+            Some("Antwerp".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_geoip_database_open_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(GeoIpDatabase::open(dir.path().join("missing.mmdb")).is_err());
+        assert!(GeoIpDatabase::open_all(Vec::<PathBuf>::new()).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use super::{GeoInfo, GeoIpDatabase};
+use crate::stream::ClientAddr;
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// A [`Service`] which looks up the [`GeoInfo`] of the client
+/// and inserts it into the [`Context`] when found.
+///
+/// Any [`GeoInfo`] already present in the [`Context`] is removed when the client
+/// cannot be found, so that it never describes another address than the client's.
+///
+/// The client address is the resolved [`ClientAddr`] if there is one,
+/// or otherwise the peer address of the [`SocketInfo`], see [`ClientAddr::from_ctx`].
+/// The service should therefore be placed after any layer resolving the client address
+/// of forwarded requests, when those are to be trusted.
+///
+/// [`SocketInfo`]: crate::stream::SocketInfo
+pub struct GeoIpService<S> {
+    inner: S,
+    db: GeoIpDatabase,
+}
+
+impl<S: fmt::Debug> fmt::Debug for GeoIpService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoIpService")
+            .field("inner", &self.inner)
+            .field("db", &self.db)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for GeoIpService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl<S> GeoIpService<S> {
+    /// Create a new [`GeoIpService`], looking up the client in the given [`GeoIpDatabase`].
+    pub const fn new(inner: S, db: GeoIpDatabase) -> Self {
+        Self { inner, db }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<State, Request, S> Service<State, Request> for GeoIpService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+    S: Service<State, Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        if let Some(addr) = ClientAddr::from_ctx(&ctx) {
+            match self.db.lookup(addr.ip()) {
+                Some(info) => {
+                    tracing::trace!(ip = %addr.ip(), ?info, "geoip: client found");
+                    ctx.insert(info);
+                }
+                None => {
+                    tracing::trace!(ip = %addr.ip(), "geoip: client not found");
+                    ctx.remove::<GeoInfo>();
+                }
+            }
+        } else {
+            ctx.remove::<GeoInfo>();
+        }
+        self.inner.serve(ctx, req)
+    }
+}
+
+/// A [`Layer`] that produces a [`GeoIpService`].
+///
+/// See [`GeoIpService`] for more information.
+#[derive(Debug, Clone)]
+pub struct GeoIpLayer {
+    db: GeoIpDatabase,
+}
+
+impl GeoIpLayer {
+    /// Create a new [`GeoIpLayer`], looking up clients in the given [`GeoIpDatabase`].
+    pub const fn new(db: GeoIpDatabase) -> Self {
+        Self { db }
+    }
+}
+
+impl<S> Layer<S> for GeoIpLayer {
+    type Service = GeoIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GeoIpService::new(inner, self.db.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{asn::Asn, geo::db::test::*, stream::SocketInfo};
+    use rama_core::service::service_fn;
+    use std::{convert::Infallible, net::Ipv4Addr};
+
+    #[tokio::test]
+    async fn test_geoip_service_inserts_client_geo_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_mmdb(
+            dir.path(),
+            "asn.mmdb",
+            &[(
+                Ipv4Addr::new(81, 82, 0, 0),
+                16,
+                asn_record(5432, "Proximus NV"),
+            )],
+        );
+        let svc = GeoIpLayer::new(GeoIpDatabase::open(path).unwrap()).layer(service_fn(
+            async |ctx: Context<()>, ()| Ok::<_, Infallible>(ctx.get::<GeoInfo>().cloned()),
+        ));
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([81, 82, 1, 1], 40000).into()));
+        let info = svc.serve(ctx, ()).await.unwrap().unwrap();
+        assert_eq!(info.asn, Some(Asn::from_static(5432)));
+        assert_eq!(info.org.as_deref(), Some("Proximus NV"));
+
+        // the resolved client address takes precedence over the peer address
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([81, 82, 1, 1], 40000).into()));
+        ctx.insert(ClientAddr::new(Ipv4Addr::new(127, 0, 0, 1).into()));
+        assert!(svc.serve(ctx, ()).await.unwrap().is_none());
+
+        assert!(svc.serve(Context::default(), ()).await.unwrap().is_none());
+
+        // a stale geo info is removed when the client is not found
+        let stale = svc
+            .serve(
+                {
+                    let mut ctx = Context::default();
+                    ctx.insert(SocketInfo::new(None, ([81, 82, 1, 1], 40000).into()));
+                    ctx
+                },
+                (),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 40000).into()));
+        ctx.insert(stale.clone());
+        assert!(svc.serve(ctx, ()).await.unwrap().is_none());
+
+        let mut ctx = Context::default();
+        ctx.insert(stale);
+        assert!(svc.serve(ctx, ()).await.unwrap().is_none());
+    }
+}
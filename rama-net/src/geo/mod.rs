@@ -0,0 +1,57 @@
+//! Geo location and network ownership information of IP addresses.
+//!
+//! [`GeoInfo`] is the [`Context`] extension describing where an IP address is located
+//! and which autonomous system (AS) announces it. It is used by the country and ASN matchers
+//! of [`SocketMatcher`] and can be used by any other service interested in it.
+//!
+//! With the `geoip` feature enabled this module also provides a [`GeoIpDatabase`],
+//! which looks up [`GeoInfo`] in local MMDB files, and a [`GeoIpLayer`] which
+//! attaches the [`GeoInfo`] of the client (see [`ClientAddr::from_ctx`]) to the [`Context`].
+//!
+//! [`Context`]: rama_core::Context
+//! [`SocketMatcher`]: crate::stream::matcher::SocketMatcher
+//! [`ClientAddr::from_ctx`]: crate::stream::ClientAddr::from_ctx
+
+use crate::asn::Asn;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "geoip")]
+mod db;
+#[cfg(feature = "geoip")]
+#[doc(inline)]
+pub use db::GeoIpDatabase;
+
+#[cfg(feature = "geoip")]
+mod layer;
+#[cfg(feature = "geoip")]
+#[doc(inline)]
+pub use layer::{GeoIpLayer, GeoIpService};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Geo location and network ownership information of an IP address.
+///
+/// All fields are optional, as databases (or the combination of databases)
+/// do not necessarily contain all information for all addresses.
+pub struct GeoInfo {
+    /// Two-letter continent code (e.g. `EU`).
+    pub continent: Option<String>,
+    /// ISO 3166-1 alpha-2 country code (e.g. `BE`).
+    pub country: Option<String>,
+    /// English name of the city (e.g. `Brussels`).
+    pub city: Option<String>,
+    /// Autonomous system number of the network that announces the address.
+    pub asn: Option<Asn>,
+    /// Name of the organization that owns the network of the address.
+    pub org: Option<String>,
+}
+
+impl GeoInfo {
+    /// Returns `true` if none of the information is known.
+    pub fn is_empty(&self) -> bool {
+        self.continent.is_none()
+            && self.country.is_none()
+            && self.city.is_none()
+            && self.asn.is_none()
+            && self.org.is_none()
+    }
+}
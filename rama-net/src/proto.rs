@@ -178,7 +178,8 @@ impl Protocol {
         match &self.0 {
             ProtocolKind::Https | ProtocolKind::Wss => 443,
             ProtocolKind::Http | ProtocolKind::Ws => 80,
-            ProtocolKind::Socks5 | ProtocolKind::Socks5h | ProtocolKind::Custom(_) => 80, // \_(ツ)_/¯
+            ProtocolKind::Socks5 | ProtocolKind::Socks5h => 1080,
+            ProtocolKind::Custom(_) => 80, // \_(ツ)_/¯
         }
     }
 
@@ -0,0 +1,88 @@
+use super::SocketInfo;
+use rama_core::Context;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Address of the client which originated the request.
+///
+/// This is the (canonical) address of the client as resolved from the chain of
+/// (trusted) proxies the request was forwarded by, e.g. by the `ClientAddrLayer` of `rama-http`.
+/// The port is only known in case the proxies forwarded it.
+///
+/// Consumers interested in the address of the client should use [`ClientAddr::from_ctx`],
+/// which falls back to the peer address of the [`SocketInfo`] in case no
+/// [`ClientAddr`] was resolved.
+pub struct ClientAddr {
+    ip: IpAddr,
+    port: Option<u16>,
+}
+
+impl ClientAddr {
+    /// Create a new [`ClientAddr`] for the given ip, with an unknown port.
+    pub fn new(ip: IpAddr) -> Self {
+        Self {
+            ip: ip.to_canonical(),
+            port: None,
+        }
+    }
+
+    /// Set the port of this [`ClientAddr`].
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set the port of this [`ClientAddr`].
+    pub fn set_port(&mut self, port: u16) -> &mut Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Get the [`ClientAddr`] from the [`Context`],
+    /// falling back to the peer address of the [`SocketInfo`] if there is none.
+    pub fn from_ctx<State>(ctx: &Context<State>) -> Option<Self> {
+        ctx.get::<Self>().copied().or_else(|| {
+            ctx.get::<SocketInfo>()
+                .map(|info| (*info.peer_addr()).into())
+        })
+    }
+
+    /// Get the ip of the client.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Get the port of the client, if known.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Get the [`SocketAddr`] of the client, if the port is known.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.port.map(|port| (self.ip, port).into())
+    }
+}
+
+impl From<IpAddr> for ClientAddr {
+    fn from(ip: IpAddr) -> Self {
+        Self::new(ip)
+    }
+}
+
+impl From<SocketAddr> for ClientAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self::new(addr.ip()).with_port(addr.port())
+    }
+}
+
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.socket_addr() {
+            Some(addr) => addr.fmt(f),
+            None => self.ip.fmt(f),
+        }
+    }
+}
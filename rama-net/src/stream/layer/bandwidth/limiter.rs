@@ -0,0 +1,617 @@
+use rama_core::Context;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{self, Poll, ready},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+
+/// Maximum time a throttled stream waits before checking its bucket again,
+/// such that limits changed at runtime are picked up in time.
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A bandwidth limit, defined as a rate in bytes per second
+/// and a burst, the amount of bytes that can be transferred at once.
+pub struct BandwidthLimit {
+    rate: u64,
+    burst: u64,
+}
+
+impl BandwidthLimit {
+    /// Create a new [`BandwidthLimit`] of the given amount of bytes per second,
+    /// with a burst of one second worth of bytes.
+    ///
+    /// A rate of 0 is treated as a rate of 1 byte per second.
+    pub fn new(bytes_per_second: u64) -> Self {
+        let rate = bytes_per_second.max(1);
+        Self { rate, burst: rate }
+    }
+
+    /// Set the burst of this [`BandwidthLimit`],
+    /// the maximum amount of bytes that can be transferred at once.
+    ///
+    /// A burst of 0 is treated as a burst of 1 byte.
+    pub fn with_burst(mut self, burst: u64) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Set the burst of this [`BandwidthLimit`],
+    /// the maximum amount of bytes that can be transferred at once.
+    ///
+    /// A burst of 0 is treated as a burst of 1 byte.
+    pub fn set_burst(&mut self, burst: u64) -> &mut Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// The rate of this [`BandwidthLimit`] in bytes per second.
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// The burst of this [`BandwidthLimit`] in bytes.
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: Option<BandwidthLimit>,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: Option<BandwidthLimit>) -> Self {
+        Self {
+            limit,
+            tokens: limit.map(|limit| limit.burst as f64).unwrap_or_default(),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, limit: BandwidthLimit) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = elapsed
+            .mul_add(limit.rate as f64, self.tokens)
+            .min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    fn set_limit(&mut self, limit: Option<BandwidthLimit>) {
+        if let Some(current) = self.limit {
+            self.refill(current);
+        }
+        match (self.limit, limit) {
+            (None, Some(limit)) => {
+                self.tokens = limit.burst as f64;
+                self.updated = Instant::now();
+            }
+            (Some(_), Some(limit)) => self.tokens = self.tokens.min(limit.burst as f64),
+            (_, None) => (),
+        }
+        self.limit = limit;
+    }
+
+    /// Returns the amount of bytes (at most `want`) that can be transferred,
+    /// or the time to wait in case the bucket is depleted.
+    fn capacity(&mut self, want: usize) -> Result<usize, Duration> {
+        let Some(limit) = self.limit else {
+            return Ok(want);
+        };
+        self.refill(limit);
+        if self.tokens >= 1.0 {
+            return Ok(want.min(self.tokens as usize));
+        }
+        let target = (want as f64).min(limit.burst as f64);
+        let wait = Duration::from_secs_f64((target - self.tokens) / limit.rate as f64);
+        Err(wait.min(MAX_WAIT))
+    }
+
+    fn consume(&mut self, n: usize) {
+        // tokens can go negative when multiple streams share the bucket,
+        // this debt is paid back before any of them can continue
+        if self.limit.is_some() {
+            self.tokens -= n as f64;
+        }
+    }
+}
+
+#[derive(Clone)]
+/// A shareable bandwidth limiter, with separate token buckets
+/// for the bytes read and the bytes written.
+///
+/// All streams and bodies throttled by the same limiter (or its clones)
+/// share its buckets, capping their aggregated bandwidth.
+/// The limits can be changed at any time, also while in use,
+/// e.g. by a service that finds the limiter in the [`Context`].
+///
+/// [`Context`]: rama_core::Context
+pub struct BandwidthLimiter {
+    read: Arc<Mutex<TokenBucket>>,
+    write: Arc<Mutex<TokenBucket>>,
+}
+
+impl fmt::Debug for BandwidthLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimiter")
+            .field("read", &self.read_limit())
+            .field("write", &self.write_limit())
+            .finish()
+    }
+}
+
+impl Default for BandwidthLimiter {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl BandwidthLimiter {
+    /// Create a new [`BandwidthLimiter`] with the given read and write limits,
+    /// `None` meaning unlimited.
+    pub fn new(read: Option<BandwidthLimit>, write: Option<BandwidthLimit>) -> Self {
+        Self {
+            read: Arc::new(Mutex::new(TokenBucket::new(read))),
+            write: Arc::new(Mutex::new(TokenBucket::new(write))),
+        }
+    }
+
+    /// Create a new [`BandwidthLimiter`] without any limits.
+    pub fn unlimited() -> Self {
+        Self::new(None, None)
+    }
+
+    /// Create a new [`BandwidthLimiter`] with the same limit for reading and writing.
+    ///
+    /// The limit is applied to both directions independently.
+    pub fn symmetric(limit: BandwidthLimit) -> Self {
+        Self::new(Some(limit), Some(limit))
+    }
+
+    /// Create a new [`BandwidthLimiter`] which only limits reading.
+    pub fn read_only(limit: BandwidthLimit) -> Self {
+        Self::new(Some(limit), None)
+    }
+
+    /// Create a new [`BandwidthLimiter`] which only limits writing.
+    pub fn write_only(limit: BandwidthLimit) -> Self {
+        Self::new(None, Some(limit))
+    }
+
+    /// The current read limit, `None` meaning unlimited.
+    pub fn read_limit(&self) -> Option<BandwidthLimit> {
+        lock(&self.read).limit
+    }
+
+    /// The current write limit, `None` meaning unlimited.
+    pub fn write_limit(&self) -> Option<BandwidthLimit> {
+        lock(&self.write).limit
+    }
+
+    /// Change the read limit, `None` meaning unlimited.
+    ///
+    /// The new limit applies to all streams and bodies using this limiter,
+    /// including the ones already in progress.
+    pub fn set_read_limit(&self, limit: Option<BandwidthLimit>) -> &Self {
+        lock(&self.read).set_limit(limit);
+        self
+    }
+
+    /// Change the write limit, `None` meaning unlimited.
+    ///
+    /// The new limit applies to all streams and bodies using this limiter,
+    /// including the ones already in progress.
+    pub fn set_write_limit(&self, limit: Option<BandwidthLimit>) -> &Self {
+        lock(&self.write).set_limit(limit);
+        self
+    }
+
+    /// Returns `true` in case another clone of this limiter,
+    /// or a throttle created by it, exists.
+    fn is_in_use(&self) -> bool {
+        Arc::strong_count(&self.read) > 1 || Arc::strong_count(&self.write) > 1
+    }
+
+    /// Create a [`BandwidthThrottle`] to throttle reads using this limiter.
+    pub fn read_throttle(&self) -> BandwidthThrottle {
+        BandwidthThrottle::new(self.read.clone())
+    }
+
+    /// Create a [`BandwidthThrottle`] to throttle writes using this limiter.
+    pub fn write_throttle(&self) -> BandwidthThrottle {
+        BandwidthThrottle::new(self.write.clone())
+    }
+}
+
+fn lock(bucket: &Mutex<TokenBucket>) -> std::sync::MutexGuard<'_, TokenBucket> {
+    bucket.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Throttles a single direction of a single stream or body,
+/// using one of the buckets of a [`BandwidthLimiter`].
+///
+/// Use [`BandwidthThrottle::poll_capacity`] to wait for capacity
+/// prior to transferring data and [`BandwidthThrottle::consume`]
+/// to report the amount of bytes actually transferred.
+pub struct BandwidthThrottle {
+    bucket: Arc<Mutex<TokenBucket>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    waiting: bool,
+}
+
+impl fmt::Debug for BandwidthThrottle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthThrottle")
+            .field("limit", &lock(&self.bucket).limit)
+            .field("waiting", &self.waiting)
+            .finish()
+    }
+}
+
+impl BandwidthThrottle {
+    fn new(bucket: Arc<Mutex<TokenBucket>>) -> Self {
+        Self {
+            bucket,
+            sleep: None,
+            waiting: false,
+        }
+    }
+
+    /// Poll for the amount of bytes that can be transferred, at most `want` bytes.
+    ///
+    /// Returns [`Poll::Pending`] while the bucket is depleted,
+    /// in which case the task is woken up once capacity is available (again).
+    /// The returned amount is only non-zero if `want` is non-zero.
+    pub fn poll_capacity(&mut self, cx: &mut task::Context<'_>, want: usize) -> Poll<usize> {
+        if want == 0 {
+            return Poll::Ready(0);
+        }
+        loop {
+            if self.waiting {
+                if let Some(sleep) = self.sleep.as_mut() {
+                    ready!(sleep.as_mut().poll(cx));
+                }
+                self.waiting = false;
+            }
+            match lock(&self.bucket).capacity(want) {
+                Ok(n) => return Poll::Ready(n),
+                Err(wait) => {
+                    let deadline = Instant::now() + wait;
+                    match self.sleep.as_mut() {
+                        Some(sleep) => sleep.as_mut().reset(deadline),
+                        None => self.sleep = Some(Box::pin(tokio::time::sleep_until(deadline))),
+                    }
+                    self.waiting = true;
+                }
+            }
+        }
+    }
+
+    /// Consume the given amount of bytes from the bucket,
+    /// to be called with the amount of bytes actually transferred.
+    pub fn consume(&self, n: usize) {
+        if n > 0 {
+            lock(&self.bucket).consume(n);
+        }
+    }
+}
+
+/// A policy to select the [`BandwidthLimiter`] to be used
+/// for a stream or body, given the [`Context`].
+///
+/// Returning `None` means that no shared limiter applies,
+/// in which case a per-connection (initially unlimited) limiter is used.
+pub trait BandwidthLimiterPolicy<State>: Send + Sync + 'static {
+    /// Select the [`BandwidthLimiter`] for the given [`Context`].
+    fn limiter(&self, ctx: &Context<State>) -> Option<BandwidthLimiter>;
+}
+
+impl<State> BandwidthLimiterPolicy<State> for BandwidthLimiter {
+    fn limiter(&self, _ctx: &Context<State>) -> Option<BandwidthLimiter> {
+        Some(self.clone())
+    }
+}
+
+impl<State, P> BandwidthLimiterPolicy<State> for Option<P>
+where
+    P: BandwidthLimiterPolicy<State>,
+{
+    fn limiter(&self, ctx: &Context<State>) -> Option<BandwidthLimiter> {
+        self.as_ref().and_then(|policy| policy.limiter(ctx))
+    }
+}
+
+impl<State, P> BandwidthLimiterPolicy<State> for Arc<P>
+where
+    P: BandwidthLimiterPolicy<State>,
+{
+    fn limiter(&self, ctx: &Context<State>) -> Option<BandwidthLimiter> {
+        self.as_ref().limiter(ctx)
+    }
+}
+
+/// A [`BandwidthLimiterPolicy`] which shares a [`BandwidthLimiter`]
+/// for all streams and bodies of the same key, found as an extension
+/// in the [`Context`] (e.g. the [`UserId`]).
+///
+/// Limiters are created on first use with the default limits of this policy,
+/// and can be replaced or adjusted at any time, e.g. to apply the plan of a user.
+/// No limiter is selected in case the key is not found in the [`Context`].
+///
+/// Limiters created on first use are dropped once they are no longer in use
+/// by any stream, body or other handle, and as such start over with the default limits
+/// when used again. Limiters inserted using [`KeyedBandwidthLimiter::insert`]
+/// are kept until removed.
+///
+/// [`UserId`]: crate::user::UserId
+pub struct KeyedBandwidthLimiter<K> {
+    read: Option<BandwidthLimit>,
+    write: Option<BandwidthLimit>,
+    limiters: Arc<Mutex<KeyedLimiters<K>>>,
+}
+
+#[derive(Debug)]
+struct KeyedLimiters<K> {
+    entries: HashMap<K, KeyedLimiter>,
+    /// Number of entries at which the idle limiters are dropped,
+    /// doubled relative to the remaining entries after every sweep
+    /// to keep the cost of sweeping amortized.
+    sweep_at: usize,
+}
+
+#[derive(Debug)]
+struct KeyedLimiter {
+    limiter: BandwidthLimiter,
+    inserted: bool,
+}
+
+impl<K> KeyedLimiters<K> {
+    const MIN_SWEEP_AT: usize = 64;
+
+    fn drop_idle(&mut self) {
+        if self.entries.len() < self.sweep_at {
+            return;
+        }
+        self.entries
+            .retain(|_, entry| entry.inserted || entry.limiter.is_in_use());
+        self.sweep_at = (self.entries.len() * 2).max(Self::MIN_SWEEP_AT);
+    }
+}
+
+impl<K> Default for KeyedLimiters<K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            sweep_at: Self::MIN_SWEEP_AT,
+        }
+    }
+}
+
+impl<K> Clone for KeyedBandwidthLimiter<K> {
+    fn clone(&self) -> Self {
+        Self {
+            read: self.read,
+            write: self.write,
+            limiters: self.limiters.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for KeyedBandwidthLimiter<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedBandwidthLimiter")
+            .field("read", &self.read)
+            .field("write", &self.write)
+            .field(
+                "limiters",
+                &self
+                    .limiters
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .entries,
+            )
+            .finish()
+    }
+}
+
+impl<K> KeyedBandwidthLimiter<K>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Create a new [`KeyedBandwidthLimiter`], creating limiters
+    /// with the given read and write limits, `None` meaning unlimited.
+    pub fn new(read: Option<BandwidthLimit>, write: Option<BandwidthLimit>) -> Self {
+        Self {
+            read,
+            write,
+            limiters: Default::default(),
+        }
+    }
+
+    /// Get the [`BandwidthLimiter`] for the given key,
+    /// creating it with the default limits if it does not exist yet.
+    pub fn limiter_for(&self, key: &K) -> BandwidthLimiter {
+        let mut limiters = self.limiters.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = limiters.entries.get(key) {
+            return entry.limiter.clone();
+        }
+        limiters.drop_idle();
+        let limiter = BandwidthLimiter::new(self.read, self.write);
+        limiters.entries.insert(
+            key.clone(),
+            KeyedLimiter {
+                limiter: limiter.clone(),
+                inserted: false,
+            },
+        );
+        limiter
+    }
+
+    /// Insert the [`BandwidthLimiter`] to be used for the given key,
+    /// returning the previous one if any.
+    ///
+    /// Streams and bodies already using the previous limiter keep using it,
+    /// use [`BandwidthLimiter::set_read_limit`] and [`BandwidthLimiter::set_write_limit`]
+    /// to adjust the limits of all of them instead.
+    pub fn insert(&self, key: K, limiter: BandwidthLimiter) -> Option<BandwidthLimiter> {
+        self.limiters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .insert(
+                key,
+                KeyedLimiter {
+                    limiter,
+                    inserted: true,
+                },
+            )
+            .map(|entry| entry.limiter)
+    }
+
+    /// Remove the [`BandwidthLimiter`] of the given key, returning it if it existed.
+    pub fn remove(&self, key: &K) -> Option<BandwidthLimiter> {
+        self.limiters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .remove(key)
+            .map(|entry| entry.limiter)
+    }
+}
+
+impl<State, K> BandwidthLimiterPolicy<State> for KeyedBandwidthLimiter<K>
+where
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    fn limiter(&self, ctx: &Context<State>) -> Option<BandwidthLimiter> {
+        ctx.get::<K>().map(|key| self.limiter_for(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserId;
+    use std::future::poll_fn;
+
+    async fn acquire(throttle: &mut BandwidthThrottle, want: usize) -> usize {
+        let n = poll_fn(|cx| throttle.poll_capacity(cx, want)).await;
+        throttle.consume(n);
+        n
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_burst_and_rate() {
+        let limiter = BandwidthLimiter::read_only(BandwidthLimit::new(100).with_burst(50));
+        let mut throttle = limiter.read_throttle();
+
+        let start = Instant::now();
+        assert_eq!(acquire(&mut throttle, 80).await, 50);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(acquire(&mut throttle, 80).await, 50);
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        // writes are not limited
+        let mut throttle = limiter.write_throttle();
+        assert_eq!(acquire(&mut throttle, 1_000_000).await, 1_000_000);
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_shared_bucket() {
+        let limiter = BandwidthLimiter::symmetric(BandwidthLimit::new(100));
+        let mut a = limiter.write_throttle();
+        let mut b = limiter.clone().write_throttle();
+
+        let start = Instant::now();
+        let mut total = 0;
+        while total < 400 {
+            total += acquire(&mut a, 50).await;
+            total += acquire(&mut b, 50).await;
+        }
+        // 100 bytes of burst + 300 bytes at 100 bytes per second
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_limit_changed_at_runtime() {
+        let limiter = BandwidthLimiter::read_only(BandwidthLimit::new(10).with_burst(1000));
+        let mut throttle = limiter.read_throttle();
+        assert_eq!(acquire(&mut throttle, 1000).await, 1000);
+
+        let start = Instant::now();
+        let task = tokio::spawn(async move { acquire(&mut throttle, 1000).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        limiter.set_read_limit(None);
+        assert_eq!(task.await.unwrap(), 1000);
+        assert!(start.elapsed() <= MAX_WAIT);
+        assert_eq!(limiter.read_limit(), None);
+    }
+
+    #[test]
+    fn test_keyed_bandwidth_limiter() {
+        let policy = KeyedBandwidthLimiter::<UserId>::new(Some(BandwidthLimit::new(100)), None);
+
+        assert!(policy.limiter(&Context::<()>::default()).is_none());
+
+        let mut ctx = Context::<()>::default();
+        ctx.insert(UserId::Username("john".to_owned()));
+        let limiter = policy.limiter(&ctx).unwrap();
+        assert_eq!(limiter.read_limit(), Some(BandwidthLimit::new(100)));
+        assert_eq!(limiter.write_limit(), None);
+
+        // limiters are shared per key
+        policy
+            .limiter_for(&UserId::Username("john".to_owned()))
+            .set_write_limit(Some(BandwidthLimit::new(10)));
+        assert_eq!(limiter.write_limit(), Some(BandwidthLimit::new(10)));
+        assert_eq!(
+            policy
+                .limiter_for(&UserId::Username("jane".to_owned()))
+                .write_limit(),
+            None
+        );
+
+        policy.insert(
+            UserId::Username("john".to_owned()),
+            BandwidthLimiter::unlimited(),
+        );
+        assert_eq!(policy.limiter(&ctx).unwrap().read_limit(), None);
+        assert!(
+            policy
+                .remove(&UserId::Username("john".to_owned()))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_keyed_bandwidth_limiter_drops_idle_limiters() {
+        let policy = KeyedBandwidthLimiter::<u64>::new(Some(BandwidthLimit::new(100)), None);
+        let len = || policy.limiters.lock().unwrap().entries.len();
+
+        let in_use = policy.limiter_for(&0);
+        let throttle = policy.limiter_for(&1).read_throttle();
+        policy.insert(2, BandwidthLimiter::unlimited());
+        for key in 3..1000 {
+            policy.limiter_for(&key);
+        }
+        assert!(len() <= KeyedLimiters::<u64>::MIN_SWEEP_AT, "{}", len());
+
+        // limiters in use, as well as inserted ones, are retained
+        in_use.set_read_limit(None);
+        assert_eq!(policy.limiter_for(&0).read_limit(), None);
+        assert!(policy.limiters.lock().unwrap().entries.contains_key(&1));
+        assert_eq!(policy.limiter_for(&2).read_limit(), None);
+        drop(throttle);
+    }
+}
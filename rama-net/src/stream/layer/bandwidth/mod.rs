@@ -0,0 +1,23 @@
+//! Bandwidth throttling of streams using shareable token buckets.
+//!
+//! A [`BandwidthLimiter`] has a token bucket for reading and one for writing,
+//! each with its own [`BandwidthLimit`] (rate and burst). Limiters are shareable,
+//! such that for example all connections of the same user are capped together,
+//! see [`KeyedBandwidthLimiter`], and their limits can be changed at runtime.
+//!
+//! Use the [`BandwidthLimitLayer`] to throttle streams, or [`BandwidthLimitedStream`] directly.
+
+mod limiter;
+#[doc(inline)]
+pub use limiter::{
+    BandwidthLimit, BandwidthLimiter, BandwidthLimiterPolicy, BandwidthThrottle,
+    KeyedBandwidthLimiter,
+};
+
+mod stream;
+#[doc(inline)]
+pub use stream::BandwidthLimitedStream;
+
+mod service;
+#[doc(inline)]
+pub use service::{BandwidthLimitLayer, BandwidthLimitService};
@@ -0,0 +1,156 @@
+use super::{BandwidthLimitedStream, BandwidthLimiter, BandwidthLimiterPolicy};
+use crate::stream::Stream;
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// A [`Service`] that wraps a [`Service`]'s input IO [`Stream`] with a bandwidth throttle.
+///
+/// The [`BandwidthLimiter`] is selected by the [`BandwidthLimiterPolicy`],
+/// falling back to a new unlimited limiter for this stream only.
+/// It is inserted in the [`Context`], such that inner services can
+/// adjust its limits at runtime, e.g. once the user is known.
+///
+/// Wrap the service handling (tunneled) streams with this service,
+/// such as a `Forwarder`, in order to throttle the traffic of that tunnel.
+///
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+pub struct BandwidthLimitService<S, P> {
+    inner: S,
+    policy: P,
+}
+
+impl<S: fmt::Debug, P: fmt::Debug> fmt::Debug for BandwidthLimitService<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimitService")
+            .field("inner", &self.inner)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl<S: Clone, P: Clone> Clone for BandwidthLimitService<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl<S, P> BandwidthLimitService<S, P> {
+    /// Create a new [`BandwidthLimitService`].
+    ///
+    /// See [`BandwidthLimitService`] for more information.
+    pub const fn new(inner: S, policy: P) -> Self {
+        Self { inner, policy }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<State, S, P, IO> Service<State, IO> for BandwidthLimitService<S, P>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, BandwidthLimitedStream<IO>>,
+    P: BandwidthLimiterPolicy<State>,
+    IO: Stream,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        mut ctx: Context<State>,
+        stream: IO,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let limiter = self
+            .policy
+            .limiter(&ctx)
+            .unwrap_or_else(BandwidthLimiter::unlimited);
+        let stream = BandwidthLimitedStream::new(stream, &limiter);
+        ctx.insert(limiter);
+        self.inner.serve(ctx, stream)
+    }
+}
+
+/// A [`Layer`] that wraps a [`Service`]'s input IO [`Stream`] with a bandwidth throttle.
+///
+/// See [`BandwidthLimitService`] for more information.
+///
+/// [`Layer`]: rama_core::Layer
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+#[derive(Debug, Clone)]
+pub struct BandwidthLimitLayer<P> {
+    policy: P,
+}
+
+impl<P> BandwidthLimitLayer<P> {
+    /// Create a new [`BandwidthLimitLayer`],
+    /// selecting the [`BandwidthLimiter`] using the given policy.
+    pub const fn new(policy: P) -> Self {
+        Self { policy }
+    }
+}
+
+impl<S, P: Clone> Layer<S> for BandwidthLimitLayer<P> {
+    type Service = BandwidthLimitService<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BandwidthLimitService::new(inner, self.policy.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        stream::layer::{BandwidthLimit, KeyedBandwidthLimiter},
+        user::UserId,
+    };
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio::{
+        io::AsyncReadExt,
+        time::{Duration, Instant},
+    };
+    use tokio_test::io::Builder;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth_limit_service_shared_per_key() {
+        let policy = KeyedBandwidthLimiter::<UserId>::new(Some(BandwidthLimit::new(4)), None);
+        let svc = BandwidthLimitLayer::new(policy).layer(service_fn(
+            async |ctx: Context<()>, mut stream: BandwidthLimitedStream<tokio_test::io::Mock>| {
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                Ok::<_, Infallible>(ctx.get::<BandwidthLimiter>().unwrap().read_limit())
+            },
+        ));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            let mut ctx = Context::default();
+            ctx.insert(UserId::Username("john".to_owned()));
+            let limit = svc
+                .serve(ctx, Builder::new().read(b"test").build())
+                .await
+                .unwrap();
+            assert_eq!(limit, Some(BandwidthLimit::new(4)));
+        }
+        // the burst is shared by all streams of the same user
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        // streams without a user are not limited
+        let start = Instant::now();
+        for _ in 0..3 {
+            let limit = svc
+                .serve(Context::default(), Builder::new().read(b"test").build())
+                .await
+                .unwrap();
+            assert_eq!(limit, None);
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,148 @@
+use super::{BandwidthLimiter, BandwidthThrottle};
+use pin_project_lite::pin_project;
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pin_project! {
+    /// A wrapper around a [`AsyncRead`] and/or [`AsyncWrite`] that throttles
+    /// the bytes read and written according to a [`BandwidthLimiter`].
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub struct BandwidthLimitedStream<S> {
+        read: BandwidthThrottle,
+        write: BandwidthThrottle,
+        #[pin]
+        stream: S,
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for BandwidthLimitedStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimitedStream")
+            .field("read", &self.read)
+            .field("write", &self.write)
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<S> BandwidthLimitedStream<S> {
+    /// Create a new [`BandwidthLimitedStream`] that wraps the
+    /// given [`AsyncRead`] and/or [`AsyncWrite`], throttled by the given [`BandwidthLimiter`].
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn new(stream: S, limiter: &BandwidthLimiter) -> Self {
+        Self {
+            read: limiter.read_throttle(),
+            write: limiter.write_throttle(),
+            stream,
+        }
+    }
+
+    /// Get the inner [`AsyncRead`] and/or [`AsyncWrite`] stream,
+    /// no longer throttled.
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> AsyncRead for BandwidthLimitedStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let capacity = ready!(this.read.poll_capacity(cx, buf.remaining()));
+        if capacity == buf.remaining() {
+            let size = buf.filled().len();
+            ready!(this.stream.poll_read(cx, buf))?;
+            this.read.consume(buf.filled().len().saturating_sub(size));
+            return Poll::Ready(Ok(()));
+        }
+
+        let mut limited = ReadBuf::new(buf.initialize_unfilled_to(capacity));
+        ready!(this.stream.poll_read(cx, &mut limited))?;
+        let n = limited.filled().len();
+        buf.advance(n);
+        this.read.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for BandwidthLimitedStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.project();
+        let capacity = ready!(this.write.poll_capacity(cx, buf.len()));
+        let n = ready!(this.stream.poll_write(cx, &buf[..capacity]))?;
+        this.write.consume(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::layer::BandwidthLimit;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        time::{Duration, Instant},
+    };
+    use tokio_test::io::Builder;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth_limited_read() {
+        let stream = Builder::new().read(b"foobarbaz").read(b"qux").build();
+        let limiter = BandwidthLimiter::read_only(BandwidthLimit::new(4));
+        let mut stream = BandwidthLimitedStream::new(stream, &limiter);
+
+        let start = Instant::now();
+        let mut buf = [0u8; 12];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"foobarbazqux");
+        // 4 bytes of burst + 8 bytes at 4 bytes per second
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth_limited_write() {
+        let stream = Builder::new()
+            .write(b"foo")
+            .write(b"bar")
+            .write(b"baz")
+            .build();
+        let limiter = BandwidthLimiter::write_only(BandwidthLimit::new(3));
+        let mut stream = BandwidthLimitedStream::new(stream, &limiter);
+
+        let start = Instant::now();
+        stream.write_all(b"foobarbaz").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+}
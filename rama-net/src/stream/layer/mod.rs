@@ -9,8 +9,19 @@ pub use tracker::{
     OutgoingBytesTrackerLayer, OutgoingBytesTrackerService,
 };
 
+mod bandwidth;
+#[doc(inline)]
+pub use bandwidth::{
+    BandwidthLimit, BandwidthLimitLayer, BandwidthLimitService, BandwidthLimitedStream,
+    BandwidthLimiter, BandwidthLimiterPolicy, BandwidthThrottle, KeyedBandwidthLimiter,
+};
+
 #[cfg(feature = "http")]
 pub mod http;
 
 #[cfg(feature = "telemetry")]
 pub mod opentelemetry;
+
+pub mod pcap;
+
+pub mod prometheus;
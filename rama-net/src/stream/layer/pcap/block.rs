@@ -0,0 +1,130 @@
+//! Encoding of the pcapng blocks, as specified in
+//! <https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-02.html>.
+//!
+//! All blocks are written in little endian byte order.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const DECRYPTION_SECRETS_BLOCK: u32 = 0x0000_000A;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+/// Raw IP packets, with the IP version found in the first nibble.
+const LINKTYPE_RAW: u16 = 101;
+/// NSS Key Log formatted TLS secrets.
+const SECRETS_TYPE_TLS_KEY_LOG: u32 = 0x544C_534B;
+
+const OPTION_END: u16 = 0;
+const OPTION_SHB_USER_APPL: u16 = 4;
+
+/// The section header block, starting each pcapng file.
+pub(super) fn section_header() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+    body.extend_from_slice(&1u16.to_le_bytes()); // major version
+    body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+    body.extend_from_slice(&(-1i64).to_le_bytes()); // section length: unknown
+    let user_appl = format!("{} {}", rama_utils::info::NAME, rama_utils::info::VERSION);
+    push_option(&mut body, OPTION_SHB_USER_APPL, user_appl.as_bytes());
+    push_option(&mut body, OPTION_END, &[]);
+    block(SECTION_HEADER_BLOCK, &body)
+}
+
+/// The description of the single (raw IP) interface all packets are captured on.
+pub(super) fn interface_description() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    body.extend_from_slice(&0u32.to_le_bytes()); // snap length: unlimited
+    block(INTERFACE_DESCRIPTION_BLOCK, &body)
+}
+
+/// A block with a single (raw IP) packet captured at the given time.
+pub(super) fn enhanced_packet(timestamp: SystemTime, packet: &[u8]) -> Vec<u8> {
+    // the default timestamp resolution is microseconds
+    let micros = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+
+    let mut body = Vec::with_capacity(20 + packet.len() + 3);
+    body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+    body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+    body.extend_from_slice(&(micros as u32).to_le_bytes());
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // captured length
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // original length
+    body.extend_from_slice(packet);
+    pad(&mut body);
+    block(ENHANCED_PACKET_BLOCK, &body)
+}
+
+/// A block with TLS secrets in the NSS key log format.
+pub(super) fn decryption_secrets(key_log: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(8 + key_log.len() + 3);
+    body.extend_from_slice(&SECRETS_TYPE_TLS_KEY_LOG.to_le_bytes());
+    body.extend_from_slice(&(key_log.len() as u32).to_le_bytes());
+    body.extend_from_slice(key_log);
+    pad(&mut body);
+    block(DECRYPTION_SECRETS_BLOCK, &body)
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad(body);
+}
+
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let total_length = (12 + body.len()) as u32;
+    let mut block = Vec::with_capacity(total_length as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total_length.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&total_length.to_le_bytes());
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_blocks_are_aligned_and_framed() {
+        for block in [
+            section_header(),
+            interface_description(),
+            enhanced_packet(SystemTime::now(), &[1, 2, 3, 4, 5]),
+            decryption_secrets(b"CLIENT_RANDOM 00 11\n"),
+        ] {
+            assert_eq!(block.len() % 4, 0);
+            let total_length = u32_at(&block, 4);
+            assert_eq!(total_length as usize, block.len());
+            assert_eq!(u32_at(&block, block.len() - 4), total_length);
+        }
+    }
+
+    #[test]
+    fn test_enhanced_packet() {
+        let timestamp = UNIX_EPOCH + Duration::from_micros(0x1_0000_0002);
+        let block = enhanced_packet(timestamp, &[0xab; 5]);
+        assert_eq!(u32_at(&block, 0), ENHANCED_PACKET_BLOCK);
+        assert_eq!(u32_at(&block, 12), 1); // timestamp high
+        assert_eq!(u32_at(&block, 16), 2); // timestamp low
+        assert_eq!(u32_at(&block, 20), 5);
+        assert_eq!(u32_at(&block, 24), 5);
+        assert_eq!(&block[28..33], &[0xab; 5]);
+        assert_eq!(&block[33..36], &[0; 3]);
+    }
+}
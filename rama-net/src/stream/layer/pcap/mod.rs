@@ -0,0 +1,387 @@
+//! Record the bytes exchanged over streams into a pcapng file.
+//!
+//! The [`PcapLayer`] wraps each incoming stream such that all bytes read from it
+//! (client to server) and written to it (server to client) are captured as
+//! synthesized TCP/IP packets, using the addresses of the [`SocketInfo`] found
+//! in the [`Context`]. The resulting file can be opened as-is in Wireshark.
+//!
+//! Used in front of a TLS acceptor the encrypted traffic is captured,
+//! which Wireshark can decrypt when the TLS secrets are embedded in the file.
+//! Enable this by logging the keys of the acceptor using a [`KeyLogIntent`]
+//! and pointing [`PcapFile::with_key_log_file`] to that same file.
+//! Used after the TLS acceptor the decrypted traffic is captured instead.
+//!
+//! Connections can be filtered using [`PcapLayer::with_filter`],
+//! e.g. using a [`SocketMatcher`], which is matched against the [`SocketInfo`].
+//!
+//! [`Context`]: rama_core::Context
+//! [`KeyLogIntent`]: crate::tls::KeyLogIntent
+//! [`SocketMatcher`]: crate::stream::matcher::SocketMatcher
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rama_core::{Layer, rt::Executor};
+//! use rama_net::stream::{
+//!     SocketInfo,
+//!     layer::pcap::{PcapFile, PcapLayer, PcapWriter},
+//!     matcher::SocketMatcher,
+//! };
+//! use std::time::Duration;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let writer = PcapWriter::new(
+//!     &Executor::default(),
+//!     PcapFile::new("capture.pcapng")
+//!         .with_key_log_file("keylog.txt")
+//!         .with_interval(Duration::from_secs(3600)),
+//!     1024,
+//! )?;
+//!
+//! let layer = PcapLayer::new(writer).with_filter(SocketMatcher::<(), SocketInfo>::private_ip_net());
+//! # let _ = layer;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::stream::{SocketInfo, Stream};
+use pin_project_lite::pin_project;
+use rama_core::{Context, Layer, Service, matcher::Matcher};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    task::{self, Poll},
+    time::SystemTime,
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+mod block;
+mod packet;
+mod writer;
+
+use packet::{Direction, TcpFlow};
+use writer::Packet;
+
+#[doc(inline)]
+pub use writer::{PcapFile, PcapWriter};
+
+/// A [`Layer`] that produces a [`PcapService`].
+///
+/// See the [module docs](self) for more information.
+#[derive(Debug, Clone)]
+pub struct PcapLayer<M = bool> {
+    writer: PcapWriter,
+    filter: M,
+}
+
+impl PcapLayer {
+    /// Create a new [`PcapLayer`] recording all streams using the given [`PcapWriter`].
+    pub const fn new(writer: PcapWriter) -> Self {
+        Self {
+            writer,
+            filter: true,
+        }
+    }
+}
+
+impl<M> PcapLayer<M> {
+    /// Only record the streams for which the [`SocketInfo`] matches the given [`Matcher`].
+    pub fn with_filter<T>(self, filter: T) -> PcapLayer<T> {
+        PcapLayer {
+            writer: self.writer,
+            filter,
+        }
+    }
+}
+
+impl<S, M: Clone> Layer<S> for PcapLayer<M> {
+    type Service = PcapService<S, M>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PcapService {
+            inner,
+            writer: self.writer.clone(),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// A [`Service`] that records the bytes exchanged over its input stream into a pcapng file.
+///
+/// See the [module docs](self) for more information.
+#[derive(Debug, Clone)]
+pub struct PcapService<S, M = bool> {
+    inner: S,
+    writer: PcapWriter,
+    filter: M,
+}
+
+impl<S> PcapService<S> {
+    /// Create a new [`PcapService`] recording all streams using the given [`PcapWriter`].
+    pub const fn new(inner: S, writer: PcapWriter) -> Self {
+        Self {
+            inner,
+            writer,
+            filter: true,
+        }
+    }
+}
+
+impl<S, M> PcapService<S, M> {
+    /// Only record the streams for which the [`SocketInfo`] matches the given [`Matcher`].
+    pub fn with_filter<T>(self, filter: T) -> PcapService<S, T> {
+        PcapService {
+            inner: self.inner,
+            writer: self.writer,
+            filter,
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<State, S, M, IO> Service<State, IO> for PcapService<S, M>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, PcapStream<IO>>,
+    M: Matcher<State, SocketInfo>,
+    IO: Stream,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        ctx: Context<State>,
+        stream: IO,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let flow = match ctx.get::<SocketInfo>() {
+            Some(info) if self.filter.matches(None, &ctx, info) => {
+                let client = *info.peer_addr();
+                let server = info
+                    .local_addr()
+                    .copied()
+                    .unwrap_or_else(|| unspecified_addr(client.ip()));
+                Some(TcpFlow::new(client, server))
+            }
+            Some(_) => None,
+            None => {
+                tracing::debug!("no socket info found in context: skip pcap recording of stream");
+                None
+            }
+        };
+        let stream = PcapStream::new(stream, flow, self.writer.clone());
+        self.inner.serve(ctx, stream)
+    }
+}
+
+fn unspecified_addr(like: IpAddr) -> SocketAddr {
+    match like {
+        IpAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        IpAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    }
+}
+
+pin_project! {
+    /// A wrapper around a [`Stream`] which records the bytes read from it
+    /// and written to it, as produced by the [`PcapService`].
+    ///
+    /// The connection is recorded as closed once this stream is dropped.
+    pub struct PcapStream<S> {
+        recorder: Option<Recorder>,
+        #[pin]
+        stream: S,
+    }
+
+    impl<S> PinnedDrop for PcapStream<S> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(recorder) = this.project().recorder.as_mut() {
+                let packets = recorder.flow.close();
+                recorder.write(packets);
+            }
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for PcapStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapStream")
+            .field("recorder", &self.recorder)
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct Recorder {
+    flow: TcpFlow,
+    writer: PcapWriter,
+}
+
+impl Recorder {
+    fn write(&self, packets: Vec<Vec<u8>>) {
+        let timestamp = SystemTime::now();
+        self.writer.write_packets(
+            packets
+                .into_iter()
+                .map(|data| Packet { timestamp, data })
+                .collect(),
+        );
+    }
+
+    fn record(&mut self, direction: Direction, payload: &[u8]) {
+        if !payload.is_empty() {
+            let packets = self.flow.data(direction, payload);
+            self.write(packets);
+        }
+    }
+}
+
+impl<S> PcapStream<S> {
+    fn new(stream: S, flow: Option<TcpFlow>, writer: PcapWriter) -> Self {
+        let recorder = flow.map(|mut flow| {
+            let packets = flow.open();
+            let recorder = Recorder { flow, writer };
+            recorder.write(packets);
+            recorder
+        });
+        Self { recorder, stream }
+    }
+
+    /// Returns true if the bytes exchanged over this stream are recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+}
+
+impl<S> AsyncRead for PcapStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let offset = buf.filled().len();
+        let res = this.stream.poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(recorder)) = (&res, this.recorder.as_mut()) {
+            if let Some(read) = buf.filled().get(offset..) {
+                recorder.record(Direction::ClientToServer, read);
+            }
+        }
+        res
+    }
+}
+
+impl<S> AsyncWrite for PcapStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.project();
+        let res = this.stream.poll_write(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(recorder)) = (&res, this.recorder.as_mut()) {
+            recorder.record(Direction::ServerToClient, &buf[..*n]);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::matcher::SocketMatcher;
+    use rama_core::{rt::Executor, service::service_fn};
+    use std::{convert::Infallible, time::Duration};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn block_types(data: &[u8]) -> Vec<u32> {
+        let mut types = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            types.push(u32_at(offset));
+            offset += u32_at(offset + 4) as usize;
+        }
+        types
+    }
+
+    async fn echo(mut stream: PcapStream<tokio::io::DuplexStream>) -> Result<bool, Infallible> {
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&buf).await.unwrap();
+        Ok(stream.is_recording())
+    }
+
+    #[tokio::test]
+    async fn test_pcap_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.pcapng");
+        let key_log_path = dir.path().join("keylog.txt");
+        std::fs::write(&key_log_path, "CLIENT_RANDOM 00 11\n").unwrap();
+
+        let writer = PcapWriter::new(
+            &Executor::default(),
+            PcapFile::new(&path).with_key_log_file(&key_log_path),
+            16,
+        )
+        .unwrap();
+        let service = PcapLayer::new(writer.clone())
+            .with_filter(SocketMatcher::loopback())
+            .layer(service_fn(echo));
+
+        for peer_ip in [[127, 0, 0, 1], [10, 0, 0, 1]] {
+            let mut ctx = Context::default();
+            ctx.insert(SocketInfo::new(
+                Some(([127, 0, 0, 1], 443).into()),
+                (peer_ip, 50000).into(),
+            ));
+
+            let (mut client, server) = tokio::io::duplex(64);
+            client.write_all(b"hello").await.unwrap();
+            let is_recording = service.serve(ctx, server).await.unwrap();
+            assert_eq!(is_recording, peer_ip == [127, 0, 0, 1]);
+
+            let mut buf = [0; 5];
+            client.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        }
+        drop(service);
+        drop(writer);
+
+        let mut data = Vec::new();
+        for _ in 0..50 {
+            data = tokio::fs::read(&path).await.unwrap();
+            if block_types(&data).len() == 11 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // header, secrets, handshake, request, response and close
+        assert_eq!(
+            block_types(&data),
+            [0x0A0D_0D0A, 1, 0xA, 6, 6, 6, 6, 6, 6, 6, 6]
+        );
+    }
+}
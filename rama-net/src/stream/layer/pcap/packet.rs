@@ -0,0 +1,270 @@
+//! Synthesized TCP/IP framing of the bytes exchanged over a stream.
+
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+/// Maximum amount of payload bytes per synthesized segment,
+/// such that each packet fits in the IPv4 and IPv6 length fields.
+const MAX_SEGMENT_SIZE: usize = 65_000;
+
+const INITIAL_SEQUENCE_NUMBER: u32 = 1;
+
+pub(super) const FLAG_FIN: u8 = 0x01;
+pub(super) const FLAG_SYN: u8 = 0x02;
+pub(super) const FLAG_PSH: u8 = 0x08;
+pub(super) const FLAG_ACK: u8 = 0x10;
+
+/// The direction of a packet within a [`TcpFlow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// The state of a single synthesized TCP connection,
+/// used to build the raw IP packets of each direction.
+#[derive(Debug)]
+pub(super) struct TcpFlow {
+    client: SocketAddr,
+    server: SocketAddr,
+    client_seq: u32,
+    server_seq: u32,
+}
+
+impl TcpFlow {
+    /// Create a new [`TcpFlow`] between the given client and server.
+    ///
+    /// In case the address families differ, IPv4 addresses are mapped to IPv6.
+    pub(super) fn new(client: SocketAddr, server: SocketAddr) -> Self {
+        let (client, server) = match (client.ip(), server.ip()) {
+            (IpAddr::V4(ip), IpAddr::V6(_)) => (
+                SocketAddr::new(ip.to_ipv6_mapped().into(), client.port()),
+                server,
+            ),
+            (IpAddr::V6(_), IpAddr::V4(ip)) => (
+                client,
+                SocketAddr::new(ip.to_ipv6_mapped().into(), server.port()),
+            ),
+            _ => (client, server),
+        };
+        Self {
+            client,
+            server,
+            client_seq: INITIAL_SEQUENCE_NUMBER,
+            server_seq: INITIAL_SEQUENCE_NUMBER,
+        }
+    }
+
+    /// The packets of the three-way handshake opening the connection.
+    pub(super) fn open(&mut self) -> Vec<Vec<u8>> {
+        vec![
+            self.segment(Direction::ClientToServer, FLAG_SYN, &[]),
+            self.segment(Direction::ServerToClient, FLAG_SYN | FLAG_ACK, &[]),
+            self.segment(Direction::ClientToServer, FLAG_ACK, &[]),
+        ]
+    }
+
+    /// The packets carrying the given payload in the given direction.
+    pub(super) fn data(&mut self, direction: Direction, payload: &[u8]) -> Vec<Vec<u8>> {
+        payload
+            .chunks(MAX_SEGMENT_SIZE)
+            .map(|chunk| self.segment(direction, FLAG_PSH | FLAG_ACK, chunk))
+            .collect()
+    }
+
+    /// The packets closing the connection.
+    pub(super) fn close(&mut self) -> Vec<Vec<u8>> {
+        vec![
+            self.segment(Direction::ServerToClient, FLAG_FIN | FLAG_ACK, &[]),
+            self.segment(Direction::ClientToServer, FLAG_FIN | FLAG_ACK, &[]),
+            self.segment(Direction::ServerToClient, FLAG_ACK, &[]),
+        ]
+    }
+
+    fn segment(&mut self, direction: Direction, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let (src, dst, seq, ack) = match direction {
+            Direction::ClientToServer => {
+                (self.client, self.server, self.client_seq, self.server_seq)
+            }
+            Direction::ServerToClient => {
+                (self.server, self.client, self.server_seq, self.client_seq)
+            }
+        };
+        // only acknowledge once the peer its sequence number is known
+        let ack = if flags & FLAG_ACK != 0 { ack } else { 0 };
+
+        let mut advance = payload.len() as u32;
+        if flags & (FLAG_SYN | FLAG_FIN) != 0 {
+            advance += 1;
+        }
+        match direction {
+            Direction::ClientToServer => self.client_seq = self.client_seq.wrapping_add(advance),
+            Direction::ServerToClient => self.server_seq = self.server_seq.wrapping_add(advance),
+        }
+
+        ip_packet(src, dst, &tcp_segment(src, dst, seq, ack, flags, payload))
+    }
+}
+
+fn tcp_segment(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.push(5 << 4); // data offset: 5 words, no options
+    segment.push(flags);
+    segment.extend_from_slice(&u16::MAX.to_be_bytes()); // window
+    segment.extend_from_slice(&[0, 0]); // checksum
+    segment.extend_from_slice(&[0, 0]); // urgent pointer
+    segment.extend_from_slice(payload);
+
+    let mut pseudo_header = Vec::with_capacity(40);
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            pseudo_header.extend_from_slice(&src.octets());
+            pseudo_header.extend_from_slice(&dst.octets());
+            pseudo_header.extend_from_slice(&[0, 6]);
+            pseudo_header.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            pseudo_header.extend_from_slice(&ipv6(src).octets());
+            pseudo_header.extend_from_slice(&ipv6(dst).octets());
+            pseudo_header.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, 6]);
+        }
+    }
+    let checksum = internet_checksum(&[&pseudo_header, &segment]);
+    segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+    segment
+}
+
+fn ip_packet(src: SocketAddr, dst: SocketAddr, segment: &[u8]) -> Vec<u8> {
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut packet = Vec::with_capacity(20 + segment.len());
+            packet.push(0x45); // version 4, header length: 5 words
+            packet.push(0); // dscp & ecn
+            packet.extend_from_slice(&((20 + segment.len()) as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0]); // identification
+            packet.extend_from_slice(&[0x40, 0]); // don't fragment
+            packet.push(64); // ttl
+            packet.push(6); // protocol: tcp
+            packet.extend_from_slice(&[0, 0]); // checksum
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            let checksum = internet_checksum(&[&packet]);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+            packet.extend_from_slice(segment);
+            packet
+        }
+        (src, dst) => {
+            let mut packet = Vec::with_capacity(40 + segment.len());
+            packet.extend_from_slice(&[0x60, 0, 0, 0]); // version 6
+            packet.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+            packet.push(6); // next header: tcp
+            packet.push(64); // hop limit
+            packet.extend_from_slice(&ipv6(src).octets());
+            packet.extend_from_slice(&ipv6(dst).octets());
+            packet.extend_from_slice(segment);
+            packet
+        }
+    }
+}
+
+fn ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+/// The one's complement checksum as defined in RFC 1071.
+fn internet_checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    let mut odd = None;
+    for byte in parts.iter().flat_map(|part| part.iter().copied()) {
+        match odd.take() {
+            Some(high) => sum += u32::from(u16::from_be_bytes([high, byte])),
+            None => odd = Some(byte),
+        }
+    }
+    if let Some(high) = odd {
+        sum += u32::from(u16::from_be_bytes([high, 0]));
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcp_flow_ipv4() {
+        let mut flow = TcpFlow::new(
+            "10.0.0.1:50000".parse().unwrap(),
+            "10.0.0.2:443".parse().unwrap(),
+        );
+        let packets = flow.open();
+        assert_eq!(packets.len(), 3);
+
+        let packets = flow.data(Direction::ClientToServer, b"hello");
+        assert_eq!(packets.len(), 1);
+        let packet = &packets[0];
+        assert_eq!(packet.len(), 20 + 20 + 5);
+        // valid ip header checksum
+        assert_eq!(internet_checksum(&[&packet[..20]]), 0);
+        assert_eq!(&packet[12..16], &[10, 0, 0, 1]);
+        assert_eq!(u16::from_be_bytes([packet[20], packet[21]]), 50000);
+        // seq after SYN, ack after SYN-ACK
+        assert_eq!(&packet[24..28], &2u32.to_be_bytes());
+        assert_eq!(&packet[28..32], &2u32.to_be_bytes());
+        assert_eq!(packet[33], FLAG_PSH | FLAG_ACK);
+        assert_eq!(&packet[40..], b"hello");
+
+        let packets = flow.data(Direction::ServerToClient, b"world!");
+        let packet = &packets[0];
+        assert_eq!(&packet[12..16], &[10, 0, 0, 2]);
+        assert_eq!(&packet[24..28], &2u32.to_be_bytes());
+        assert_eq!(&packet[28..32], &7u32.to_be_bytes());
+
+        // valid tcp checksum
+        let segment = &packet[20..];
+        let pseudo_header = [
+            &packet[12..20],
+            &[0, 6][..],
+            &(segment.len() as u16).to_be_bytes()[..],
+        ]
+        .concat();
+        assert_eq!(internet_checksum(&[&pseudo_header, segment]), 0);
+    }
+
+    #[test]
+    fn test_tcp_flow_mixed_families_and_large_payload() {
+        let mut flow = TcpFlow::new(
+            "127.0.0.1:50000".parse().unwrap(),
+            "[::1]:443".parse().unwrap(),
+        );
+        let packets = flow.data(Direction::ServerToClient, &[0; MAX_SEGMENT_SIZE + 1]);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][0] >> 4, 6);
+        assert_eq!(packets[1].len(), 40 + 20 + 1);
+        assert_eq!(
+            &packets[1][24..40],
+            &"127.0.0.1"
+                .parse::<std::net::Ipv4Addr>()
+                .unwrap()
+                .to_ipv6_mapped()
+                .octets()
+        );
+    }
+}
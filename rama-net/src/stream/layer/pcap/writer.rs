@@ -0,0 +1,287 @@
+use super::block;
+use rama_core::rt::Executor;
+use std::{
+    ffi::OsString,
+    io::{self, SeekFrom},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+/// Configuration of the pcapng file written by a [`PcapWriter`].
+///
+/// The file is rotated once it would grow beyond the maximum size
+/// and/or once the rotation interval passed since it was opened.
+/// On rotation `capture.pcapng` is renamed to `capture.pcapng.1`,
+/// `capture.pcapng.1` to `capture.pcapng.2` and so on,
+/// removing the oldest file once more than [`PcapFile::with_max_files`]
+/// rotated files exist. Each file starts with all TLS secrets known so far,
+/// such that each file can be opened on its own.
+#[derive(Debug, Clone)]
+pub struct PcapFile {
+    path: PathBuf,
+    key_log_path: Option<PathBuf>,
+    max_size: Option<u64>,
+    interval: Option<Duration>,
+    max_files: usize,
+}
+
+impl PcapFile {
+    /// Create a new [`PcapFile`] for the file at the given path.
+    ///
+    /// By default the file is never rotated and at most 7 rotated files are kept.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            key_log_path: None,
+            max_size: None,
+            interval: None,
+            max_files: 7,
+        }
+    }
+
+    /// Embed the TLS secrets appended to the NSS key log file at the given path
+    /// as decryption secrets blocks, such that the captured TLS traffic can be decrypted.
+    ///
+    /// Use the same path as the one of the [`KeyLogIntent`] of the TLS acceptor.
+    ///
+    /// [`KeyLogIntent`]: crate::tls::KeyLogIntent
+    pub fn with_key_log_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.key_log_path = Some(path.into());
+        self
+    }
+
+    /// Embed the TLS secrets appended to the NSS key log file at the given path
+    /// as decryption secrets blocks, such that the captured TLS traffic can be decrypted.
+    ///
+    /// Use the same path as the one of the [`KeyLogIntent`] of the TLS acceptor.
+    ///
+    /// [`KeyLogIntent`]: crate::tls::KeyLogIntent
+    pub fn set_key_log_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.key_log_path = Some(path.into());
+        self
+    }
+
+    /// Rotate the file once it would grow beyond the given size in bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Rotate the file once it would grow beyond the given size in bytes.
+    pub fn set_max_size(&mut self, max_size: u64) -> &mut Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Rotate the file once the given interval passed since it was opened.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Rotate the file once the given interval passed since it was opened.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Set the amount of rotated files to keep.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Set the amount of rotated files to keep.
+    pub fn set_max_files(&mut self, max_files: usize) -> &mut Self {
+        self.max_files = max_files;
+        self
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+/// A captured raw IP packet.
+#[derive(Debug)]
+pub(super) struct Packet {
+    pub(super) timestamp: SystemTime,
+    pub(super) data: Vec<u8>,
+}
+
+/// Handle used to write captured packets to a pcapng file,
+/// by a task spawned on the given [`Executor`].
+///
+/// Packets are written from within the stream flow over a bounded channel,
+/// and are dropped when the channel is full, such that capturing never blocks the traffic.
+#[derive(Debug, Clone)]
+pub struct PcapWriter {
+    sender: Sender<Vec<Packet>>,
+}
+
+impl PcapWriter {
+    /// Create a new [`PcapWriter`] writing to the given [`PcapFile`],
+    /// buffering at most `buffer_size` batches of packets.
+    pub fn new(executor: &Executor, file: PcapFile, buffer_size: usize) -> io::Result<Self> {
+        let (tx, rx) = mpsc::channel(buffer_size);
+        let sink = PcapSink::open(file)?;
+        executor.spawn_task(write_packets(rx, sink));
+        Ok(Self { sender: tx })
+    }
+
+    pub(super) fn write_packets(&self, packets: Vec<Packet>) {
+        match self.sender.try_send(packets) {
+            Ok(()) => (),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("pcap writer buffer is full: dropping captured packets")
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::error!("pcap writer is closed: dropping captured packets")
+            }
+        }
+    }
+}
+
+async fn write_packets(mut rx: Receiver<Vec<Packet>>, mut sink: PcapSink) {
+    while let Some(packets) = rx.recv().await {
+        let mut next = Some(packets);
+        while let Some(packets) = next {
+            if let Err(err) = sink.write_packets(packets).await {
+                tracing::error!(err = %err, "failed to write captured packets")
+            }
+            next = rx.try_recv().ok();
+        }
+        if let Err(err) = sink.writer.flush().await {
+            tracing::error!(err = %err, "failed to flush pcap writer")
+        }
+    }
+}
+
+/// The pcapng file (and its rotation) written to by the background task.
+struct PcapSink {
+    file: PcapFile,
+    writer: BufWriter<File>,
+    size: u64,
+    has_packets: bool,
+    opened_at: Instant,
+    /// All complete key log lines read so far.
+    secrets: Vec<u8>,
+    key_log_offset: u64,
+}
+
+impl PcapSink {
+    fn open(file: PcapFile) -> io::Result<Self> {
+        let mut writer = std::fs::File::create(&file.path)?;
+        let header = header(&[]);
+        std::io::Write::write_all(&mut writer, &header)?;
+        Ok(Self {
+            file,
+            writer: BufWriter::new(File::from_std(writer)),
+            size: header.len() as u64,
+            has_packets: false,
+            opened_at: Instant::now(),
+            secrets: Vec::new(),
+            key_log_offset: 0,
+        })
+    }
+
+    async fn write_packets(&mut self, packets: Vec<Packet>) -> io::Result<()> {
+        if let Some(secrets) = self.read_key_log().await? {
+            let secrets = block::decryption_secrets(&secrets);
+            self.writer.write_all(&secrets).await?;
+            self.size += secrets.len() as u64;
+        }
+
+        for packet in packets {
+            let block = block::enhanced_packet(packet.timestamp, &packet.data);
+            if self.should_rotate(block.len() as u64) {
+                self.rotate().await?;
+            }
+            self.writer.write_all(&block).await?;
+            self.size += block.len() as u64;
+            self.has_packets = true;
+        }
+        Ok(())
+    }
+
+    /// Read the complete key log lines appended since the last read, if any.
+    async fn read_key_log(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(path) = &self.file.key_log_path else {
+            return Ok(None);
+        };
+        let mut file = match File::open(path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if file.metadata().await?.len() <= self.key_log_offset {
+            return Ok(None);
+        }
+
+        file.seek(SeekFrom::Start(self.key_log_offset)).await?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await?;
+        let Some(end) = data.iter().rposition(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+        data.truncate(end + 1);
+
+        self.key_log_offset += data.len() as u64;
+        self.secrets.extend_from_slice(&data);
+        Ok(Some(data))
+    }
+
+    fn should_rotate(&self, len: u64) -> bool {
+        let too_large = self
+            .file
+            .max_size
+            .is_some_and(|max_size| self.has_packets && self.size + len > max_size);
+        let too_old = self
+            .file
+            .interval
+            .is_some_and(|interval| self.opened_at.elapsed() >= interval);
+        too_large || too_old
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush().await?;
+
+        let max_files = self.file.max_files;
+        if max_files == 0 {
+            tokio::fs::remove_file(&self.file.path).await?;
+        } else {
+            for index in (1..max_files).rev() {
+                let from = self.file.rotated_path(index);
+                if tokio::fs::try_exists(&from).await? {
+                    tokio::fs::rename(&from, self.file.rotated_path(index + 1)).await?;
+                }
+            }
+            tokio::fs::rename(&self.file.path, self.file.rotated_path(1)).await?;
+        }
+
+        self.writer = BufWriter::new(File::create(&self.file.path).await?);
+        let header = header(&self.secrets);
+        self.writer.write_all(&header).await?;
+        self.size = header.len() as u64;
+        self.has_packets = false;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+/// The blocks starting each pcapng file, including all TLS secrets known so far.
+fn header(secrets: &[u8]) -> Vec<u8> {
+    let mut header = block::section_header();
+    header.extend(block::interface_description());
+    if !secrets.is_empty() {
+        header.extend(block::decryption_secrets(secrets));
+    }
+    header
+}
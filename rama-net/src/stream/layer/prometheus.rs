@@ -0,0 +1,231 @@
+//! Network Prometheus [`Layer`] Support for Rama.
+//!
+//! See [`rama_core::telemetry::prometheus`] for more information
+//! on how to expose the recorded metrics.
+//!
+//! [`Layer`]: rama_core::Layer
+
+use super::tracker::BytesRWTracker;
+use crate::stream::{SocketInfo, Stream};
+use rama_core::telemetry::prometheus::{Counter, DEFAULT_BUCKETS, Gauge, Histogram, Registry};
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, net::IpAddr, sync::Arc, time::Instant};
+
+const CONNECTIONS: &str = "rama_network_connections";
+const ACTIVE_CONNECTIONS: &str = "rama_network_active_connections";
+const CONNECTION_DURATION: &str = "rama_network_connection_duration_seconds";
+const RECEIVED_BYTES: &str = "rama_network_received_bytes";
+const SENT_BYTES: &str = "rama_network_sent_bytes";
+
+/// Records network server metrics.
+#[derive(Debug)]
+struct Metrics {
+    connections: Counter,
+    active_connections: Gauge,
+    connection_duration: Histogram,
+    received_bytes: Counter,
+    sent_bytes: Counter,
+}
+
+impl Metrics {
+    fn new(registry: &Registry) -> Self {
+        let labels = &["network_type"];
+        Self {
+            connections: registry.counter(
+                CONNECTIONS,
+                "Amount of inbound network connections accepted.",
+                labels,
+            ),
+            active_connections: registry.gauge(
+                ACTIVE_CONNECTIONS,
+                "Amount of inbound network connections currently being served.",
+                labels,
+            ),
+            connection_duration: registry.histogram(
+                CONNECTION_DURATION,
+                "Duration of inbound network connections.",
+                labels,
+                DEFAULT_BUCKETS,
+            ),
+            received_bytes: registry.counter(
+                RECEIVED_BYTES,
+                "Amount of bytes received over inbound network connections.",
+                labels,
+            ),
+            sent_bytes: registry.counter(
+                SENT_BYTES,
+                "Amount of bytes sent over inbound network connections.",
+                labels,
+            ),
+        }
+    }
+}
+
+/// A layer that records network server metrics in a Prometheus [`Registry`].
+///
+/// The received and sent bytes are tracked using a [`BytesRWTrackerHandle`],
+/// and recorded once the connection is finished.
+///
+/// [`BytesRWTrackerHandle`]: super::BytesRWTrackerHandle
+pub struct ConnectionMetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl fmt::Debug for ConnectionMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionMetricsLayer")
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl Clone for ConnectionMetricsLayer {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl ConnectionMetricsLayer {
+    /// Create a new [`ConnectionMetricsLayer`] recording its metrics in the given [`Registry`].
+    pub fn new(registry: &Registry) -> Self {
+        Self {
+            metrics: Arc::new(Metrics::new(registry)),
+        }
+    }
+}
+
+impl Default for ConnectionMetricsLayer {
+    /// Create a new [`ConnectionMetricsLayer`] recording its metrics
+    /// in the [global `Registry`](Registry::global).
+    fn default() -> Self {
+        Self::new(Registry::global())
+    }
+}
+
+impl<S> Layer<S> for ConnectionMetricsLayer {
+    type Service = ConnectionMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectionMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// A [`Service`] that records network server metrics in a Prometheus [`Registry`].
+pub struct ConnectionMetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> ConnectionMetricsService<S> {
+    /// Create a new [`ConnectionMetricsService`] recording its metrics in the given [`Registry`].
+    pub fn new(registry: &Registry, inner: S) -> Self {
+        ConnectionMetricsLayer::new(registry).layer(inner)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for ConnectionMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionMetricsService")
+            .field("inner", &self.inner)
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ConnectionMetricsService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<S, State, IO> Service<State, IO> for ConnectionMetricsService<S>
+where
+    S: Service<State, BytesRWTracker<IO>>,
+    State: Clone + Send + Sync + 'static,
+    IO: Stream,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(&self, ctx: Context<State>, stream: IO) -> Result<Self::Response, Self::Error> {
+        let network_type = match ctx.get::<SocketInfo>().map(|info| info.peer_addr().ip()) {
+            Some(IpAddr::V4(_)) => "ipv4",
+            Some(IpAddr::V6(_)) => "ipv6",
+            None => "unknown",
+        };
+        let labels = &[network_type];
+
+        self.metrics.connections.inc(labels);
+        self.metrics.active_connections.inc(labels);
+        let start = Instant::now();
+
+        let stream = BytesRWTracker::new(stream);
+        let handle = stream.handle();
+        let result = self.inner.serve(ctx, stream).await;
+
+        self.metrics.active_connections.dec(labels);
+        self.metrics
+            .connection_duration
+            .observe(labels, start.elapsed().as_secs_f64());
+        self.metrics
+            .received_bytes
+            .add(labels, handle.read() as u64);
+        self.metrics.sent_bytes.add(labels, handle.written() as u64);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use rama_core::telemetry::prometheus::ExpositionFormat;
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_connection_metrics() {
+        let registry = Registry::new();
+        let service = ConnectionMetricsLayer::new(&registry).layer(service_fn(
+            |mut stream: BytesRWTracker<tokio::io::DuplexStream>| async move {
+                let mut buf = [0u8; 5];
+                stream.read_exact(&mut buf).await.unwrap();
+                stream.write_all(b"hello world").await.unwrap();
+                Ok::<_, Infallible>(())
+            },
+        ));
+
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"hello").await.unwrap();
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, "127.0.0.1:8080".parse().unwrap()));
+        service.serve(ctx, server).await.unwrap();
+
+        let output = registry.encode(ExpositionFormat::Prometheus);
+        for expected in [
+            r#"rama_network_connections_total{network_type="ipv4"} 1"#,
+            r#"rama_network_active_connections{network_type="ipv4"} 0"#,
+            r#"rama_network_connection_duration_seconds_count{network_type="ipv4"} 1"#,
+            r#"rama_network_received_bytes_total{network_type="ipv4"} 5"#,
+            r#"rama_network_sent_bytes_total{network_type="ipv4"} 11"#,
+        ] {
+            assert!(
+                output.contains(expected),
+                "{expected} not found in: {output}"
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod bytes;
+pub(crate) use bytes::BytesRWTracker;
 #[doc(inline)]
 pub use bytes::BytesRWTrackerHandle;
 
@@ -0,0 +1,137 @@
+use crate::{asn::Asn, geo::GeoInfo};
+use rama_core::{Context, context::Extensions};
+
+#[derive(Debug, Clone)]
+/// Matcher based on the country of the client, as found in the [`GeoInfo`] of the [`Context`].
+///
+/// The [`GeoInfo`] is typically inserted by the `GeoIpLayer`,
+/// which has to run prior to this matcher.
+pub struct CountryMatcher {
+    country: String,
+    optional: bool,
+}
+
+impl CountryMatcher {
+    /// create a new country matcher to match on an ISO 3166-1 alpha-2 country code (e.g. `BE`),
+    /// matched case-insensitive.
+    ///
+    /// This matcher will not match in case the country could not be found,
+    /// if you want to match in case the country could not be found,
+    /// use the [`CountryMatcher::optional`] constructor.
+    pub fn new(country: impl Into<String>) -> Self {
+        Self {
+            country: country.into(),
+            optional: false,
+        }
+    }
+
+    /// create a new country matcher to match on an ISO 3166-1 alpha-2 country code (e.g. `BE`),
+    /// matched case-insensitive.
+    ///
+    /// This matcher will match in case the country could not be found.
+    /// Use the [`CountryMatcher::new`] constructor if you want do not want
+    /// to match in case the country could not be found.
+    pub fn optional(country: impl Into<String>) -> Self {
+        Self {
+            country: country.into(),
+            optional: true,
+        }
+    }
+}
+
+impl<State, Request> rama_core::matcher::Matcher<State, Request> for CountryMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        ctx.get::<GeoInfo>()
+            .and_then(|info| info.country.as_deref())
+            .map(|country| country.eq_ignore_ascii_case(&self.country))
+            .unwrap_or(self.optional)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the [`Asn`] of the client, as found in the [`GeoInfo`] of the [`Context`].
+///
+/// The [`GeoInfo`] is typically inserted by the `GeoIpLayer`,
+/// which has to run prior to this matcher.
+pub struct AsnMatcher {
+    asn: Asn,
+    optional: bool,
+}
+
+impl AsnMatcher {
+    /// create a new ASN matcher to match on the autonomous system of the client.
+    ///
+    /// This matcher will not match in case the ASN could not be found,
+    /// if you want to match in case the ASN could not be found,
+    /// use the [`AsnMatcher::optional`] constructor.
+    pub fn new(asn: Asn) -> Self {
+        Self {
+            asn,
+            optional: false,
+        }
+    }
+
+    /// create a new ASN matcher to match on the autonomous system of the client.
+    ///
+    /// This matcher will match in case the ASN could not be found.
+    /// Use the [`AsnMatcher::new`] constructor if you want do not want
+    /// to match in case the ASN could not be found.
+    pub fn optional(asn: Asn) -> Self {
+        Self {
+            asn,
+            optional: true,
+        }
+    }
+}
+
+impl<State, Request> rama_core::matcher::Matcher<State, Request> for AsnMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        ctx.get::<GeoInfo>()
+            .and_then(|info| info.asn.as_ref())
+            .map(|asn| asn == &self.asn)
+            .unwrap_or(self.optional)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stream::SocketInfo;
+    use rama_core::matcher::Matcher;
+
+    fn ctx_with_geo_info(country: &str, asn: u32) -> Context<()> {
+        let mut ctx = Context::default();
+        ctx.insert(GeoInfo {
+            country: Some(country.to_owned()),
+            asn: Some(Asn::try_from(asn).unwrap()),
+            ..Default::default()
+        });
+        ctx
+    }
+
+    #[test]
+    fn test_country_matcher() {
+        let stream = SocketInfo::new(None, ([81, 82, 1, 1], 40000).into());
+        let matcher = CountryMatcher::new("be");
+        assert!(matcher.matches(None, &ctx_with_geo_info("BE", 5432), &stream));
+        assert!(!matcher.matches(None, &ctx_with_geo_info("NL", 5432), &stream));
+        assert!(!matcher.matches(None, &Context::default(), &stream));
+
+        let matcher = CountryMatcher::optional("BE");
+        assert!(matcher.matches(None, &Context::default(), &stream));
+        assert!(!matcher.matches(None, &ctx_with_geo_info("NL", 5432), &stream));
+    }
+
+    #[test]
+    fn test_asn_matcher() {
+        let stream = SocketInfo::new(None, ([81, 82, 1, 1], 40000).into());
+        let matcher = AsnMatcher::new(Asn::from_static(5432));
+        assert!(matcher.matches(None, &ctx_with_geo_info("BE", 5432), &stream));
+        assert!(!matcher.matches(None, &ctx_with_geo_info("BE", 6848), &stream));
+        assert!(!matcher.matches(None, &Context::default(), &stream));
+
+        let matcher = AsnMatcher::optional(Asn::from_static(5432));
+        assert!(matcher.matches(None, &Context::default(), &stream));
+        assert!(!matcher.matches(None, &ctx_with_geo_info("BE", 6848), &stream));
+    }
+}
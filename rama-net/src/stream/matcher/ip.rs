@@ -5,7 +5,7 @@ pub use crate::stream::dep::ipnet::{IpNet, Ipv4Net, Ipv6Net};
 use rama_core::{Context, context::Extensions};
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::stream::ClientAddr;
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
@@ -52,8 +52,8 @@ impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for IpNetMat
         ctx: &Context<State>,
         _req: &Request<Body>,
     ) -> bool {
-        ctx.get::<SocketInfo>()
-            .map(|info| self.net.contains(&IpNet::from(info.peer_addr().ip())))
+        ClientAddr::from_ctx(ctx)
+            .map(|addr| self.net.contains(&IpNet::from(addr.ip())))
             .unwrap_or(self.optional)
     }
 }
@@ -150,6 +150,8 @@ mod private {
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "http")]
+    use crate::stream::SocketInfo;
     use rama_core::matcher::Matcher;
     use std::net::SocketAddr;
 
@@ -194,12 +196,19 @@ mod test {
         ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8080).into()));
         assert!(matcher.matches(None, &ctx, &req));
 
-        // test #4: match: test with missing socket info, but it's seen as optional
+        // test #4: the resolved client address takes precedence over the socket info
+        ctx.insert(ClientAddr::new([127, 0, 0, 2].into()));
+        assert!(!matcher.matches(None, &ctx, &req));
+        ctx.insert(ClientAddr::new([127, 0, 0, 1].into()));
+        ctx.insert(SocketInfo::new(None, ([127, 0, 0, 2], 8080).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        // test #5: match: test with missing socket info, but it's seen as optional
         let matcher = IpNetMatcher::optional([127, 0, 0, 1]);
         let mut ctx = Context::default();
         assert!(matcher.matches(None, &ctx, &req));
 
-        // test #5: match: valid ipv4 subnets
+        // test #6: match: valid ipv4 subnets
         let matcher = IpNetMatcher::new(SUBNET_IPV4.parse::<IpNet>().unwrap());
         for subnet in SUBNET_IPV4_VALID_CASES.iter() {
             let addr = socket_addr_from_case(subnet);
@@ -213,7 +222,7 @@ mod test {
             );
         }
 
-        // test #6: match: valid ipv6 subnets
+        // test #7: match: valid ipv6 subnets
         let matcher = IpNetMatcher::new(SUBNET_IPV6.parse::<IpNet>().unwrap());
         for subnet in SUBNET_IPV6_VALID_CASES.iter() {
             let addr = socket_addr_from_case(subnet);
@@ -227,7 +236,7 @@ mod test {
             );
         }
 
-        // test #7: match: invalid ipv4 subnets
+        // test #8: match: invalid ipv4 subnets
         let matcher = IpNetMatcher::new(SUBNET_IPV4.parse::<IpNet>().unwrap());
         for subnet in SUBNET_IPV4_INVALID_CASES.iter() {
             let addr = socket_addr_from_case(subnet);
@@ -241,7 +250,7 @@ mod test {
             );
         }
 
-        // test #8: match: invalid ipv6 subnets
+        // test #9: match: invalid ipv6 subnets
         let matcher = IpNetMatcher::new(SUBNET_IPV6.parse::<IpNet>().unwrap());
         for subnet in SUBNET_IPV6_INVALID_CASES.iter() {
             let addr = socket_addr_from_case(subnet);
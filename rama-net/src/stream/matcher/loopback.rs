@@ -1,7 +1,7 @@
 use rama_core::{Context, context::Extensions};
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::stream::ClientAddr;
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
@@ -54,8 +54,8 @@ impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for Loopback
         ctx: &Context<State>,
         _req: &Request<Body>,
     ) -> bool {
-        ctx.get::<SocketInfo>()
-            .map(|info| info.peer_addr().ip().is_loopback())
+        ClientAddr::from_ctx(ctx)
+            .map(|addr| addr.ip().is_loopback())
             .unwrap_or(self.optional)
     }
 }
@@ -80,6 +80,8 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "http")]
+    use crate::stream::SocketInfo;
     use rama_core::matcher::Matcher;
     use std::net::SocketAddr;
 
@@ -1,9 +1,15 @@
 //! [`service::Matcher`]s implementations to match on [`Socket`]s.
 //!
+//! When matching on (http) requests the address of the client is used,
+//! which is the resolved [`ClientAddr`] if there is one, or otherwise
+//! the peer address of the [`SocketInfo`].
+//!
 //! See [`service::matcher` module] for more information.
 //!
 //! [`service::Matcher`]: rama_core::matcher::Matcher
 //! [`Socket`]: crate::stream::Socket
+//! [`ClientAddr`]: crate::stream::ClientAddr
+//! [`SocketInfo`]: crate::stream::SocketInfo
 //! [`service::matcher` module]: rama_core::matcher
 
 mod socket;
@@ -26,6 +32,10 @@ pub mod ip;
 #[doc(inline)]
 pub use ip::IpNetMatcher;
 
+mod geo;
+#[doc(inline)]
+pub use geo::{AsnMatcher, CountryMatcher};
+
 use rama_core::{Context, context::Extensions, matcher::IteratorMatcherExt};
 use std::{fmt, sync::Arc};
 
@@ -78,6 +88,10 @@ enum SocketMatcherKind<State, Socket> {
     /// [`IpNet`]: ipnet::IpNet
     /// [`SocketAddr`]: std::net::SocketAddr
     IpNet(IpNetMatcher),
+    /// [`CountryMatcher`], a matcher based on the country of the client.
+    Country(CountryMatcher),
+    /// [`AsnMatcher`], a matcher based on the autonomous system of the client.
+    Asn(AsnMatcher),
     /// zero or more matchers that all need to match in order for the matcher to return `true`.
     All(Vec<SocketMatcher<State, Socket>>),
     /// `true` if no matchers are defined, or any of the defined matcher match.
@@ -94,6 +108,8 @@ impl<State, Socket> Clone for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => Self::PrivateIpNet(matcher.clone()),
             Self::Port(matcher) => Self::Port(matcher.clone()),
             Self::IpNet(matcher) => Self::IpNet(matcher.clone()),
+            Self::Country(matcher) => Self::Country(matcher.clone()),
+            Self::Asn(matcher) => Self::Asn(matcher.clone()),
             Self::All(matcher) => Self::All(matcher.clone()),
             Self::Any(matcher) => Self::Any(matcher.clone()),
             Self::Custom(matcher) => Self::Custom(matcher.clone()),
@@ -109,6 +125,8 @@ impl<State, Socket> fmt::Debug for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => f.debug_tuple("PrivateIpNet").field(matcher).finish(),
             Self::Port(matcher) => f.debug_tuple("Port").field(matcher).finish(),
             Self::IpNet(matcher) => f.debug_tuple("IpNet").field(matcher).finish(),
+            Self::Country(matcher) => f.debug_tuple("Country").field(matcher).finish(),
+            Self::Asn(matcher) => f.debug_tuple("Asn").field(matcher).finish(),
             Self::All(matcher) => f.debug_tuple("All").field(matcher).finish(),
             Self::Any(matcher) => f.debug_tuple("Any").field(matcher).finish(),
             Self::Custom(_) => f.debug_tuple("Custom").finish(),
@@ -364,6 +382,104 @@ impl<State, Socket> SocketMatcher<State, Socket> {
         self.or(Self::optional_private_ip_net())
     }
 
+    /// create a new country matcher to match on the country of the client.
+    ///
+    /// See [`CountryMatcher::new`] for more information.
+    pub fn country(country: impl Into<String>) -> Self {
+        Self {
+            kind: SocketMatcherKind::Country(CountryMatcher::new(country)),
+            negate: false,
+        }
+    }
+
+    /// Create a new optional country matcher to match on the country of the client,
+    /// this matcher will match in case the country could not be found.
+    ///
+    /// See [`CountryMatcher::optional`] for more information.
+    pub fn optional_country(country: impl Into<String>) -> Self {
+        Self {
+            kind: SocketMatcherKind::Country(CountryMatcher::optional(country)),
+            negate: false,
+        }
+    }
+
+    /// Add a new country matcher to the existing [`SocketMatcher`] to also match on the country of the client.
+    ///
+    /// See [`CountryMatcher::new`] for more information.
+    pub fn and_country(self, country: impl Into<String>) -> Self {
+        self.and(Self::country(country))
+    }
+
+    /// Add a new optional country matcher to the existing [`SocketMatcher`] to also match on the country of the client.
+    ///
+    /// See [`CountryMatcher::optional`] for more information.
+    pub fn and_optional_country(self, country: impl Into<String>) -> Self {
+        self.and(Self::optional_country(country))
+    }
+
+    /// Add a new country matcher to the existing [`SocketMatcher`] as an alternative matcher to match on the country of the client.
+    ///
+    /// See [`CountryMatcher::new`] for more information.
+    pub fn or_country(self, country: impl Into<String>) -> Self {
+        self.or(Self::country(country))
+    }
+
+    /// Add a new optional country matcher to the existing [`SocketMatcher`] as an alternative matcher to match on the country of the client.
+    ///
+    /// See [`CountryMatcher::optional`] for more information.
+    pub fn or_optional_country(self, country: impl Into<String>) -> Self {
+        self.or(Self::optional_country(country))
+    }
+
+    /// create a new ASN matcher to match on the autonomous system of the client.
+    ///
+    /// See [`AsnMatcher::new`] for more information.
+    pub fn asn(asn: crate::asn::Asn) -> Self {
+        Self {
+            kind: SocketMatcherKind::Asn(AsnMatcher::new(asn)),
+            negate: false,
+        }
+    }
+
+    /// Create a new optional ASN matcher to match on the autonomous system of the client,
+    /// this matcher will match in case the ASN could not be found.
+    ///
+    /// See [`AsnMatcher::optional`] for more information.
+    pub fn optional_asn(asn: crate::asn::Asn) -> Self {
+        Self {
+            kind: SocketMatcherKind::Asn(AsnMatcher::optional(asn)),
+            negate: false,
+        }
+    }
+
+    /// Add a new ASN matcher to the existing [`SocketMatcher`] to also match on the autonomous system of the client.
+    ///
+    /// See [`AsnMatcher::new`] for more information.
+    pub fn and_asn(self, asn: crate::asn::Asn) -> Self {
+        self.and(Self::asn(asn))
+    }
+
+    /// Add a new optional ASN matcher to the existing [`SocketMatcher`] to also match on the autonomous system of the client.
+    ///
+    /// See [`AsnMatcher::optional`] for more information.
+    pub fn and_optional_asn(self, asn: crate::asn::Asn) -> Self {
+        self.and(Self::optional_asn(asn))
+    }
+
+    /// Add a new ASN matcher to the existing [`SocketMatcher`] as an alternative matcher to match on the autonomous system of the client.
+    ///
+    /// See [`AsnMatcher::new`] for more information.
+    pub fn or_asn(self, asn: crate::asn::Asn) -> Self {
+        self.or(Self::asn(asn))
+    }
+
+    /// Add a new optional ASN matcher to the existing [`SocketMatcher`] as an alternative matcher to match on the autonomous system of the client.
+    ///
+    /// See [`AsnMatcher::optional`] for more information.
+    pub fn or_optional_asn(self, asn: crate::asn::Asn) -> Self {
+        self.or(Self::optional_asn(asn))
+    }
+
     /// Create a matcher that matches according to a custom predicate.
     ///
     /// See [`rama_core::matcher::Matcher`] for more information.
@@ -455,6 +571,8 @@ where
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, req),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, req),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, req),
+            SocketMatcherKind::Country(matcher) => matcher.matches(ext, ctx, req),
+            SocketMatcherKind::Asn(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, req),
         }
     }
@@ -490,6 +608,8 @@ where
             SocketMatcherKind::Loopback(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::PrivateIpNet(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, stream),
+            SocketMatcherKind::Country(matcher) => matcher.matches(ext, ctx, stream),
+            SocketMatcherKind::Asn(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, stream),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, stream),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, stream),
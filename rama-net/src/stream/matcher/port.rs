@@ -1,14 +1,20 @@
 use rama_core::{Context, context::Extensions};
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::stream::{ClientAddr, SocketInfo};
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
 #[derive(Debug, Clone)]
 /// Matcher based on the port part of the [`SocketAddr`] of the peer.
 ///
+/// For http requests the port of the [`ClientAddr`] is matched instead,
+/// falling back to the port of the peer address of the [`SocketInfo`]
+/// in case the proxies did not forward the port of the client.
+///
 /// [`SocketAddr`]: std::net::SocketAddr
+/// [`ClientAddr`]: crate::stream::ClientAddr
+/// [`SocketInfo`]: crate::stream::SocketInfo
 pub struct PortMatcher {
     port: u16,
     optional: bool,
@@ -52,8 +58,10 @@ impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for PortMatc
         ctx: &Context<State>,
         _req: &Request<Body>,
     ) -> bool {
-        ctx.get::<SocketInfo>()
-            .map(|info| info.peer_addr().port() == self.port)
+        ClientAddr::from_ctx(ctx)
+            .and_then(|addr| addr.port())
+            .or_else(|| ctx.get::<SocketInfo>().map(|info| info.peer_addr().port()))
+            .map(|port| port == self.port)
             .unwrap_or(self.optional)
     }
 }
@@ -108,7 +116,17 @@ mod test {
         ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8080).into()));
         assert!(matcher.matches(None, &ctx, &req));
 
-        // test #5: match: test with missing socket info, but it's seen as optional
+        // test #5: match: test with client addr with matching port
+        ctx.insert(ClientAddr::new([10, 0, 0, 1].into()).with_port(8080));
+        ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8081).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        // test #6: match: test with client addr without port, falling back to the peer port
+        ctx.insert(ClientAddr::new([10, 0, 0, 1].into()));
+        ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8080).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        // test #7: match: test with missing socket info, but it's seen as optional
         let matcher = PortMatcher::optional(8080);
         let ctx = Context::default();
         assert!(matcher.matches(None, &ctx, &req));
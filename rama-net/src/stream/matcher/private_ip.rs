@@ -2,7 +2,7 @@ use crate::stream::dep::ipnet::IpNet;
 use rama_core::{Context, context::Extensions};
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::stream::ClientAddr;
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
@@ -126,9 +126,9 @@ impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for PrivateI
         ctx: &Context<State>,
         _req: &Request<Body>,
     ) -> bool {
-        ctx.get::<SocketInfo>()
-            .map(|info| {
-                let peer_ip = IpNet::from(info.peer_addr().ip());
+        ClientAddr::from_ctx(ctx)
+            .map(|addr| {
+                let peer_ip = IpNet::from(addr.ip());
                 self.matchers.iter().any(|ip_net| ip_net.contains(&peer_ip))
             })
             .unwrap_or(self.optional)
@@ -158,6 +158,8 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "http")]
+    use crate::stream::SocketInfo;
     use rama_core::matcher::Matcher;
     use std::net::SocketAddr;
 
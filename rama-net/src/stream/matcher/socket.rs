@@ -2,12 +2,19 @@ use rama_core::{Context, context::Extensions};
 use std::net::SocketAddr;
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::stream::{ClientAddr, SocketInfo};
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
 #[derive(Debug, Clone)]
 /// Matcher based on the [`SocketAddr`] of the peer.
+///
+/// For http requests the [`ClientAddr`] is matched instead, using the port
+/// of the peer address of the [`SocketInfo`] in case the proxies
+/// did not forward the port of the client.
+///
+/// [`ClientAddr`]: crate::stream::ClientAddr
+/// [`SocketInfo`]: crate::stream::SocketInfo
 pub struct SocketAddressMatcher {
     addr: SocketAddr,
     optional: bool,
@@ -47,8 +54,14 @@ impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for SocketAd
         ctx: &Context<State>,
         _req: &Request<Body>,
     ) -> bool {
-        ctx.get::<SocketInfo>()
-            .map(|info| info.peer_addr() == &self.addr)
+        ClientAddr::from_ctx(ctx)
+            .and_then(|addr| {
+                let port = addr
+                    .port()
+                    .or_else(|| ctx.get::<SocketInfo>().map(|info| info.peer_addr().port()))?;
+                Some(SocketAddr::new(addr.ip(), port))
+            })
+            .map(|addr| addr == self.addr)
             .unwrap_or(self.optional)
     }
 }
@@ -104,7 +117,21 @@ mod test {
         ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8080).into()));
         assert!(matcher.matches(None, &ctx, &req));
 
-        // test #5: match: test with missing socket info, but it's seen as optional
+        // test #5: match: test with client addr matching the address
+        ctx.insert(ClientAddr::from(SocketAddr::from(([127, 0, 0, 1], 8080))));
+        ctx.insert(SocketInfo::new(None, ([10, 0, 0, 1], 8081).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        // test #6: match: test with client addr without port, falling back to the peer port
+        ctx.insert(ClientAddr::new([127, 0, 0, 1].into()));
+        ctx.insert(SocketInfo::new(None, ([10, 0, 0, 1], 8080).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        // test #7: no match: test with client addr without port, different peer port
+        ctx.insert(SocketInfo::new(None, ([10, 0, 0, 1], 8081).into()));
+        assert!(!matcher.matches(None, &ctx, &req));
+
+        // test #8: match: test with missing socket info, but it's seen as optional
         let matcher = SocketAddressMatcher::optional(([127, 0, 0, 1], 8080));
         let ctx = Context::default();
         assert!(matcher.matches(None, &ctx, &req));
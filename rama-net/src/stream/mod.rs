@@ -9,7 +9,7 @@ pub mod service;
 
 mod read;
 #[doc(inline)]
-pub use read::{ChainReader, HeapReader};
+pub use read::{ChainReader, HeapReader, PeekStream};
 
 /// A stream is a type that implements `AsyncRead`, `AsyncWrite` and `Send`.
 /// This is specific to Rama and is directly linked to the supertraits of `Tokio`.
@@ -21,6 +21,10 @@ mod socket;
 #[doc(inline)]
 pub use socket::{Socket, SocketInfo};
 
+mod client_addr;
+#[doc(inline)]
+pub use client_addr::ClientAddr;
+
 pub mod dep {
     //! Dependencies for rama stream modules.
     //!
@@ -66,6 +66,13 @@ impl AsyncRead for HeapReader {
     }
 }
 
+/// A stream of which the first bytes were already read (peeked),
+/// replaying these bytes prior to reading from the original stream.
+///
+/// Created by splitting the original stream and chaining a [`HeapReader`]
+/// containing the peeked bytes in front of its read half.
+pub type PeekStream<S> = io::Join<ChainReader<HeapReader, io::ReadHalf<S>>, io::WriteHalf<S>>;
+
 pin_project! {
     /// Reader that can be used to chain two readers together.
     #[must_use = "streams do nothing unless polled"]
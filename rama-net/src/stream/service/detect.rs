@@ -0,0 +1,520 @@
+//! A service which detects the protocol spoken over a stream,
+//! in order to serve multiple protocols on a single listener.
+
+use crate::stream::{ChainReader, HeapReader, PeekStream, Stream};
+use rama_core::{
+    Context, Service,
+    error::{BoxError, ErrorContext, OpaqueError},
+    layer::MapErr,
+    service::BoxService,
+};
+use std::{fmt, io, ops::RangeInclusive, sync::Arc, time::Duration};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const DEFAULT_MAX_PEEK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+/// The protocol detected by the [`ProtocolDetector`],
+/// added to the [`Context`] of the service serving the stream.
+pub enum DetectedProtocol {
+    /// A TLS handshake record.
+    Tls,
+    /// An HTTP/1.x request, starting with a known method token.
+    Http1,
+    /// HTTP/2 with prior knowledge (h2c), starting with the connection preface.
+    Http2,
+    /// A SOCKS4(a) request.
+    Socks4,
+    /// A SOCKS5 client greeting.
+    Socks5,
+    /// A (human-readable) HAProxy PROXY protocol v1 header.
+    HaProxyV1,
+    /// A (binary) HAProxy PROXY protocol v2 header.
+    HaProxyV2,
+}
+
+enum Signature {
+    Literal(&'static [u8]),
+    Ranges(&'static [RangeInclusive<u8>]),
+}
+
+/// The signatures of the supported protocols,
+/// no signature is a prefix of another one.
+const SIGNATURES: &[(DetectedProtocol, Signature)] = &[
+    // content type handshake, followed by the legacy record version (SSL 3.0 up to TLS 1.3)
+    (
+        DetectedProtocol::Tls,
+        Signature::Ranges(&[0x16..=0x16, 0x03..=0x03, 0x00..=0x04]),
+    ),
+    (
+        DetectedProtocol::Http2,
+        Signature::Literal(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"),
+    ),
+    (DetectedProtocol::Http1, Signature::Literal(b"GET ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"HEAD ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"POST ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"PUT ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"DELETE ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"CONNECT ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"OPTIONS ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"TRACE ")),
+    (DetectedProtocol::Http1, Signature::Literal(b"PATCH ")),
+    // version, followed by the connect or bind command
+    (
+        DetectedProtocol::Socks4,
+        Signature::Ranges(&[0x04..=0x04, 0x01..=0x02]),
+    ),
+    // version, followed by the (non-zero) number of authentication methods
+    (
+        DetectedProtocol::Socks5,
+        Signature::Ranges(&[0x05..=0x05, 0x01..=0xff]),
+    ),
+    (DetectedProtocol::HaProxyV1, Signature::Literal(b"PROXY ")),
+    (
+        DetectedProtocol::HaProxyV2,
+        Signature::Literal(b"\r\n\r\n\0\r\nQUIT\n"),
+    ),
+];
+
+impl Signature {
+    fn len(&self) -> usize {
+        match self {
+            Self::Literal(literal) => literal.len(),
+            Self::Ranges(ranges) => ranges.len(),
+        }
+    }
+
+    /// Returns true if the data matches the signature, as far as the data goes.
+    fn matches_prefix(&self, data: &[u8]) -> bool {
+        match self {
+            Self::Literal(literal) => data.iter().zip(literal.iter()).all(|(a, b)| a == b),
+            Self::Ranges(ranges) => data.iter().zip(ranges.iter()).all(|(b, r)| r.contains(b)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Detection {
+    Detected(DetectedProtocol),
+    Incomplete,
+    Unknown,
+}
+
+fn detect(data: &[u8]) -> Detection {
+    let mut incomplete = false;
+    for (protocol, signature) in SIGNATURES {
+        if signature.matches_prefix(data) {
+            if data.len() >= signature.len() {
+                return Detection::Detected(*protocol);
+            }
+            incomplete = true;
+        }
+    }
+    if incomplete {
+        Detection::Incomplete
+    } else {
+        Detection::Unknown
+    }
+}
+
+/// A [`Service`] which detects the protocol spoken over a stream,
+/// and passes the stream to the service defined for that protocol.
+///
+/// The first bytes of the stream are peeked in order to detect the [`DetectedProtocol`],
+/// which is added to the [`Context`]. The peeked bytes are replayed
+/// to the service serving the stream, such that it receives the stream untouched.
+///
+/// Streams of which the protocol could not be detected, or for which
+/// no service is defined, are passed to the fallback service if defined,
+/// and are otherwise refused with an error. This is also the case
+/// for streams which did not send enough bytes within the peek timeout,
+/// which is for example the case for protocols where the server speaks first.
+///
+/// The service of the [`DetectedProtocol::HaProxyV1`] and [`DetectedProtocol::HaProxyV2`]
+/// protocols will usually consume the PROXY header, and pass the remaining stream
+/// to another [`ProtocolDetector`] in order to detect the proxied protocol.
+///
+/// # Example
+///
+/// ```
+/// use rama_core::{Context, service::service_fn};
+/// use rama_net::stream::{
+///     PeekStream,
+///     service::{DetectedProtocol, ProtocolDetector},
+/// };
+/// use std::{convert::Infallible, time::Duration};
+/// use tokio::io::DuplexStream;
+///
+/// async fn serve(_ctx: Context<()>, _stream: PeekStream<DuplexStream>) -> Result<(), Infallible> {
+///     Ok(())
+/// }
+///
+/// let detector = ProtocolDetector::new()
+///     .with_peek_timeout(Duration::from_secs(5))
+///     .on(DetectedProtocol::Tls, service_fn(serve))
+///     .on(DetectedProtocol::Http1, service_fn(serve))
+///     .on(DetectedProtocol::Http2, service_fn(serve))
+///     .on(DetectedProtocol::Socks5, service_fn(serve));
+/// # let _ = detector;
+/// ```
+pub struct ProtocolDetector<State, IO> {
+    services: Vec<(
+        DetectedProtocol,
+        Arc<BoxService<State, PeekStream<IO>, (), BoxError>>,
+    )>,
+    fallback: Option<Arc<BoxService<State, PeekStream<IO>, (), BoxError>>>,
+    peek_timeout: Option<Duration>,
+    max_peek_size: usize,
+}
+
+impl<State, IO> fmt::Debug for ProtocolDetector<State, IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolDetector")
+            .field(
+                "protocols",
+                &self
+                    .services
+                    .iter()
+                    .map(|(protocol, _)| protocol)
+                    .collect::<Vec<_>>(),
+            )
+            .field("fallback", &self.fallback.is_some())
+            .field("peek_timeout", &self.peek_timeout)
+            .field("max_peek_size", &self.max_peek_size)
+            .finish()
+    }
+}
+
+impl<State, IO> Clone for ProtocolDetector<State, IO> {
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+            fallback: self.fallback.clone(),
+            peek_timeout: self.peek_timeout,
+            max_peek_size: self.max_peek_size,
+        }
+    }
+}
+
+impl<State, IO> Default for ProtocolDetector<State, IO> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State, IO> ProtocolDetector<State, IO> {
+    /// Create a new [`ProtocolDetector`] without any services or fallback.
+    pub const fn new() -> Self {
+        Self {
+            services: Vec::new(),
+            fallback: None,
+            peek_timeout: None,
+            max_peek_size: DEFAULT_MAX_PEEK_SIZE,
+        }
+    }
+
+    /// Set the maximum duration to wait for enough bytes to detect the protocol,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default there is no timeout.
+    pub fn with_peek_timeout(mut self, timeout: Duration) -> Self {
+        self.peek_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum duration to wait for enough bytes to detect the protocol,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default there is no timeout.
+    pub fn set_peek_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.peek_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum amount of bytes to read in order to detect the protocol,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default this is 64 bytes, enough to detect all supported protocols.
+    pub fn with_max_peek_size(mut self, size: usize) -> Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes to read in order to detect the protocol,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default this is 64 bytes, enough to detect all supported protocols.
+    pub fn set_max_peek_size(&mut self, size: usize) -> &mut Self {
+        self.max_peek_size = size;
+        self
+    }
+}
+
+impl<State, IO> ProtocolDetector<State, IO>
+where
+    State: Clone + Send + Sync + 'static,
+    IO: Stream,
+{
+    /// Pass the streams of the given protocol to the given [`Service`],
+    /// overwriting the service previously defined for that protocol, if any.
+    pub fn on<S>(mut self, protocol: DetectedProtocol, service: S) -> Self
+    where
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        let service = Arc::new(MapErr::new(service, Into::into).boxed());
+        match self.services.iter_mut().find(|(p, _)| *p == protocol) {
+            Some((_, existing)) => *existing = service,
+            None => self.services.push((protocol, service)),
+        }
+        self
+    }
+
+    /// Set the [`Service`] to which the streams are passed
+    /// of which the protocol is not detected or has no service defined.
+    pub fn with_fallback<S>(mut self, service: S) -> Self
+    where
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        self.fallback = Some(Arc::new(MapErr::new(service, Into::into).boxed()));
+        self
+    }
+
+    /// Set the [`Service`] to which the streams are passed
+    /// of which the protocol is not detected or has no service defined.
+    pub fn set_fallback<S>(&mut self, service: S) -> &mut Self
+    where
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        self.fallback = Some(Arc::new(MapErr::new(service, Into::into).boxed()));
+        self
+    }
+}
+
+impl<State, IO> Service<State, IO> for ProtocolDetector<State, IO>
+where
+    State: Clone + Send + Sync + 'static,
+    IO: Stream + Unpin,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(&self, mut ctx: Context<State>, mut stream: IO) -> Result<(), BoxError> {
+        let mut peeked = Vec::new();
+        let read = read_protocol(&mut stream, &mut peeked, self.max_peek_size);
+        let protocol = match self.peek_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, read).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::debug!("ProtocolDetector: timeout while detecting protocol");
+                    Ok(None)
+                }
+            },
+            None => read.await,
+        }
+        .context("ProtocolDetector: detect protocol")?;
+
+        let service = protocol.and_then(|protocol| {
+            self.services
+                .iter()
+                .find_map(|(p, service)| (*p == protocol).then_some(service))
+        });
+        if let Some(protocol) = protocol {
+            tracing::trace!(?protocol, "ProtocolDetector: detected protocol");
+            ctx.insert(protocol);
+        }
+
+        let (r, w) = tokio::io::split(stream);
+        let stream = tokio::io::join(ChainReader::new(HeapReader::new(peeked), r), w);
+
+        match service.or(self.fallback.as_ref()) {
+            Some(service) => service.serve(ctx, stream).await,
+            None => Err(OpaqueError::from_display(format!(
+                "ProtocolDetector: no service found for incoming stream (protocol: {protocol:?})"
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Read from the given stream until its protocol is detected,
+/// appending all bytes read to the given buffer.
+///
+/// Returns `None` in case the protocol is unknown or could not
+/// be detected within the byte budget.
+async fn read_protocol<R>(
+    stream: &mut R,
+    buffer: &mut Vec<u8>,
+    max_size: usize,
+) -> io::Result<Option<DetectedProtocol>>
+where
+    R: AsyncRead + Unpin,
+{
+    buffer.reserve(max_size);
+    loop {
+        match detect(buffer) {
+            Detection::Detected(protocol) => return Ok(Some(protocol)),
+            Detection::Unknown => return Ok(None),
+            Detection::Incomplete => (),
+        }
+        if buffer.len() >= max_size {
+            tracing::debug!("ProtocolDetector: no protocol detected within {max_size} bytes");
+            return Ok(None);
+        }
+        if stream.read_buf(buffer).await? == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use tokio::io::{AsyncWriteExt, DuplexStream};
+
+    #[test]
+    fn test_detect() {
+        for (data, expected) in [
+            (&b""[..], Detection::Incomplete),
+            (
+                &[0x16, 0x03, 0x01, 0x02, 0x00][..],
+                Detection::Detected(DetectedProtocol::Tls),
+            ),
+            (&[0x16, 0x03][..], Detection::Incomplete),
+            (&[0x16, 0x02, 0x01][..], Detection::Unknown),
+            (
+                &b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"[..],
+                Detection::Detected(DetectedProtocol::Http2),
+            ),
+            (&b"PRI * HTTP/2.0"[..], Detection::Incomplete),
+            (
+                &b"GET / HTTP/1.1\r\n"[..],
+                Detection::Detected(DetectedProtocol::Http1),
+            ),
+            (
+                &b"OPTIONS * HTTP/1.1\r\n"[..],
+                Detection::Detected(DetectedProtocol::Http1),
+            ),
+            (&b"P"[..], Detection::Incomplete),
+            (&b"PUT"[..], Detection::Incomplete),
+            (&b"GOT "[..], Detection::Unknown),
+            (
+                &[0x04, 0x01, 0x00, 0x50][..],
+                Detection::Detected(DetectedProtocol::Socks4),
+            ),
+            (&[0x04, 0x03][..], Detection::Unknown),
+            (
+                &[0x05, 0x01, 0x00][..],
+                Detection::Detected(DetectedProtocol::Socks5),
+            ),
+            (&[0x05, 0x00][..], Detection::Unknown),
+            (
+                &b"PROXY TCP4 127.0.0.1 127.0.0.1 1 2\r\n"[..],
+                Detection::Detected(DetectedProtocol::HaProxyV1),
+            ),
+            (
+                &b"\r\n\r\n\0\r\nQUIT\n\x21\x11"[..],
+                Detection::Detected(DetectedProtocol::HaProxyV2),
+            ),
+            (&b"\r\n\r\n"[..], Detection::Incomplete),
+            (&b"SSH-2.0-OpenSSH\r\n"[..], Detection::Unknown),
+        ] {
+            assert_eq!(detect(data), expected, "{data:?}");
+        }
+    }
+
+    fn named_service(
+        name: &'static str,
+    ) -> impl Service<(), PeekStream<DuplexStream>, Response = (), Error = BoxError> {
+        service_fn(
+            move |ctx: Context<()>, mut stream: PeekStream<DuplexStream>| async move {
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data).await.unwrap();
+                let protocol = ctx.get::<DetectedProtocol>();
+                Err::<(), BoxError>(
+                    OpaqueError::from_display(format!("{name}:{protocol:?}:{}", data.len())).into(),
+                )
+            },
+        )
+    }
+
+    async fn serve(detector: &ProtocolDetector<(), DuplexStream>, data: &[u8]) -> String {
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(data).await.unwrap();
+        client.shutdown().await.unwrap();
+        detector
+            .serve(Context::default(), server)
+            .await
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_protocol_detector() {
+        let detector = ProtocolDetector::new()
+            .on(DetectedProtocol::Tls, named_service("tls"))
+            .on(DetectedProtocol::Http1, named_service("http"))
+            .on(DetectedProtocol::Http2, named_service("h2"))
+            .on(DetectedProtocol::Http1, named_service("http1"))
+            .with_fallback(named_service("fallback"));
+
+        let data = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        assert_eq!(
+            serve(&detector, data).await,
+            format!("http1:Some(Http1):{}", data.len())
+        );
+        let data = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+        assert_eq!(
+            serve(&detector, data).await,
+            format!("h2:Some(Http2):{}", data.len())
+        );
+        let data = [0x16, 0x03, 0x01, 0x00, 0x01, 0x01];
+        assert_eq!(
+            serve(&detector, &data).await,
+            format!("tls:Some(Tls):{}", data.len())
+        );
+        let data = [0x05, 0x01, 0x00];
+        assert_eq!(
+            serve(&detector, &data).await,
+            "fallback:Some(Socks5):3".to_owned()
+        );
+        let data = b"SSH-2.0-OpenSSH\r\n";
+        assert_eq!(
+            serve(&detector, data).await,
+            format!("fallback:None:{}", data.len())
+        );
+        // stream ended before protocol could be detected
+        assert_eq!(serve(&detector, b"PU").await, "fallback:None:2".to_owned());
+    }
+
+    #[tokio::test]
+    async fn test_protocol_detector_peek_timeout() {
+        let detector = ProtocolDetector::new()
+            .with_peek_timeout(Duration::from_millis(10))
+            .on(DetectedProtocol::Http1, named_service("http"))
+            .with_fallback(named_service("fallback"));
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(b"GE").await.unwrap();
+        let handle = tokio::spawn(async move { detector.serve(Context::default(), server).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.write_all(b"T /").await.unwrap();
+        client.shutdown().await.unwrap();
+        assert_eq!(
+            handle.await.unwrap().unwrap_err().to_string(),
+            "fallback:None:5"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_protocol_detector_without_fallback() {
+        let detector: ProtocolDetector<(), DuplexStream> =
+            ProtocolDetector::new().on(DetectedProtocol::Http1, named_service("http"));
+        assert!(
+            serve(&detector, b"SSH-2.0-OpenSSH\r\n")
+                .await
+                .starts_with("ProtocolDetector: no service found")
+        );
+    }
+}
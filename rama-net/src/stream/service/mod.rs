@@ -5,3 +5,7 @@
 mod echo;
 #[doc(inline)]
 pub use echo::EchoService;
+
+mod detect;
+#[doc(inline)]
+pub use detect::{DetectedProtocol, ProtocolDetector};
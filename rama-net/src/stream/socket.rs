@@ -87,3 +87,19 @@ impl SocketInfo {
         &self.peer_addr
     }
 }
+
+impl Socket for SocketInfo {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.local_addr.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "local address of socket is unknown",
+            )
+        })
+    }
+
+    #[inline]
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.peer_addr)
+    }
+}
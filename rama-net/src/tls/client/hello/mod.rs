@@ -20,10 +20,10 @@ mod boring;
 /// For Rama however we only focus on the parts which
 /// a user might want to inspect and/or set.
 pub struct ClientHello {
-    pub(super) protocol_version: ProtocolVersion,
-    pub(super) cipher_suites: Vec<CipherSuite>,
-    pub(super) compression_algorithms: Vec<CompressionAlgorithm>,
-    pub(super) extensions: Vec<ClientHelloExtension>,
+    pub(crate) protocol_version: ProtocolVersion,
+    pub(crate) cipher_suites: Vec<CipherSuite>,
+    pub(crate) compression_algorithms: Vec<CompressionAlgorithm>,
+    pub(crate) extensions: Vec<ClientHelloExtension>,
 }
 
 impl ClientHello {
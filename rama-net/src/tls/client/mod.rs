@@ -12,10 +12,7 @@ mod hello;
 #[doc(inline)]
 pub use hello::{ClientHello, ClientHelloExtension};
 
-#[cfg(any(test, feature = "boring"))]
 mod parser;
-
-#[cfg(test)]
 pub(crate) use parser::parse_client_hello;
 
 mod config;
@@ -0,0 +1,180 @@
+use crate::address::{Domain, Host};
+use crate::tls::{ApplicationProtocol, client::ClientHello};
+use rama_core::{Context, context::Extensions, error::OpaqueError, matcher::Matcher};
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+/// Matcher based on the server name (SNI) of a [`ClientHello`].
+///
+/// A [`ClientHello`] without server name, or with an IP as server name,
+/// is never matched by an exact or subdomain matcher.
+pub struct ServerNameMatcher {
+    kind: ServerNameMatcherKind,
+}
+
+#[derive(Debug, Clone)]
+enum ServerNameMatcherKind {
+    Exact(Domain),
+    Sub(Domain),
+    Regex(Regex),
+}
+
+impl ServerNameMatcher {
+    /// create a new server name matcher to match on an exact server name match.
+    pub fn exact(domain: Domain) -> Self {
+        Self {
+            kind: ServerNameMatcherKind::Exact(domain),
+        }
+    }
+
+    /// create a new server name matcher to match on a subdomain of the given domain,
+    /// the equivalent of a `*.example.com` wildcard.
+    ///
+    /// Note that a domain is also a subdomain of itself, so this will also
+    /// include all matches that [`Self::exact`] would capture.
+    pub fn sub(domain: Domain) -> Self {
+        Self {
+            kind: ServerNameMatcherKind::Sub(domain),
+        }
+    }
+
+    /// create a new server name matcher to match the (lowercase) server name
+    /// against the given regular expression.
+    ///
+    /// A server name which is an IP is matched using its string representation.
+    pub fn regex(re: &str) -> Result<Self, OpaqueError> {
+        let re = Regex::new(re).map_err(OpaqueError::from_std)?;
+        Ok(Self {
+            kind: ServerNameMatcherKind::Regex(re),
+        })
+    }
+}
+
+impl<State> Matcher<State, ClientHello> for ServerNameMatcher {
+    fn matches(
+        &self,
+        _ext: Option<&mut Extensions>,
+        _ctx: &Context<State>,
+        hello: &ClientHello,
+    ) -> bool {
+        let Some(host) = hello.ext_server_name() else {
+            tracing::trace!("ServerNameMatcher: no server name found in client hello");
+            return false;
+        };
+        match (&self.kind, host) {
+            (ServerNameMatcherKind::Exact(domain), Host::Name(name)) => domain == name,
+            (ServerNameMatcherKind::Sub(domain), Host::Name(name)) => domain.is_parent_of(name),
+            (ServerNameMatcherKind::Regex(re), host) => {
+                re.is_match(&host.to_string().to_ascii_lowercase())
+            }
+            (_, Host::Address(_)) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the application protocols offered (ALPN) in a [`ClientHello`].
+pub struct AlpnMatcher {
+    protocol: ApplicationProtocol,
+}
+
+impl AlpnMatcher {
+    /// create a new ALPN matcher, matching if the given protocol
+    /// is one of the application protocols offered by the client.
+    pub const fn new(protocol: ApplicationProtocol) -> Self {
+        Self { protocol }
+    }
+}
+
+impl<State> Matcher<State, ClientHello> for AlpnMatcher {
+    fn matches(
+        &self,
+        _ext: Option<&mut Extensions>,
+        _ctx: &Context<State>,
+        hello: &ClientHello,
+    ) -> bool {
+        hello
+            .ext_alpn()
+            .is_some_and(|protocols| protocols.contains(&self.protocol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::client::ClientHelloExtension;
+
+    fn client_hello(server_name: Option<Host>, alpn: &[ApplicationProtocol]) -> ClientHello {
+        ClientHello {
+            protocol_version: crate::tls::ProtocolVersion::TLSv1_2,
+            cipher_suites: vec![],
+            compression_algorithms: vec![],
+            extensions: vec![
+                ClientHelloExtension::ServerName(server_name),
+                ClientHelloExtension::ApplicationLayerProtocolNegotiation(alpn.to_vec()),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_server_name_matcher() {
+        let ctx = Context::default();
+        let hello = client_hello(
+            Some(Host::Name(Domain::from_static("api.Example.com"))),
+            &[],
+        );
+
+        for (matcher, expected) in [
+            (
+                ServerNameMatcher::exact(Domain::from_static("api.example.com")),
+                true,
+            ),
+            (
+                ServerNameMatcher::exact(Domain::from_static("example.com")),
+                false,
+            ),
+            (
+                ServerNameMatcher::sub(Domain::from_static("example.com")),
+                true,
+            ),
+            (
+                ServerNameMatcher::sub(Domain::from_static("example.org")),
+                false,
+            ),
+            (
+                ServerNameMatcher::regex(r"^api\.example\.com$").unwrap(),
+                true,
+            ),
+            (ServerNameMatcher::regex(r"^www\.").unwrap(), false),
+        ] {
+            assert_eq!(matcher.matches(None, &ctx, &hello), expected, "{matcher:?}");
+        }
+
+        let hello = client_hello(None, &[]);
+        assert!(
+            !ServerNameMatcher::regex(".*")
+                .unwrap()
+                .matches(None, &ctx, &hello)
+        );
+        let hello = client_hello(Some(Host::Address([127, 0, 0, 1].into())), &[]);
+        assert!(
+            !ServerNameMatcher::sub(Domain::from_static("example.com")).matches(None, &ctx, &hello)
+        );
+        assert!(
+            ServerNameMatcher::regex(r"^127\.")
+                .unwrap()
+                .matches(None, &ctx, &hello)
+        );
+    }
+
+    #[test]
+    fn test_alpn_matcher() {
+        let ctx = Context::default();
+        let hello = client_hello(
+            None,
+            &[ApplicationProtocol::HTTP_2, ApplicationProtocol::HTTP_11],
+        );
+        assert!(AlpnMatcher::new(ApplicationProtocol::HTTP_2).matches(None, &ctx, &hello));
+        assert!(!AlpnMatcher::new(ApplicationProtocol::HTTP_3).matches(None, &ctx, &hello));
+    }
+}
@@ -6,3 +6,11 @@ pub use config::{
     CacheKind, ClientVerifyMode, DynamicCertIssuer, DynamicIssuer, SelfSignedData, ServerAuth,
     ServerAuthData, ServerCertIssuerData, ServerCertIssuerKind, ServerConfig,
 };
+
+mod matcher;
+#[doc(inline)]
+pub use matcher::{AlpnMatcher, ServerNameMatcher};
+
+mod router;
+#[doc(inline)]
+pub use router::SniRouter;
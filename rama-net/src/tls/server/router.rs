@@ -0,0 +1,482 @@
+use crate::stream::{ChainReader, HeapReader, PeekStream, Stream};
+use crate::tls::client::{ClientHello, parse_client_hello};
+use rama_core::{
+    Context, Service,
+    error::{BoxError, ErrorContext, OpaqueError},
+    layer::MapErr,
+    matcher::Matcher,
+    service::BoxService,
+};
+use std::{fmt, io, sync::Arc, time::Duration};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const RECORD_HEADER_LEN: usize = 5;
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_HEADER_LEN: usize = 4;
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+
+const DEFAULT_MAX_PEEK_SIZE: usize = 32 * 1024;
+
+/// A [`Service`] which routes TLS streams based on their [`ClientHello`],
+/// without terminating the TLS connection.
+///
+/// The [`ClientHello`] is peeked from the incoming stream, after which the stream
+/// is passed untouched to the first route of which the [`Matcher`] matches,
+/// such that the routed service can either terminate the TLS connection itself
+/// (e.g. using a TLS acceptor) or forward it as-is to an upstream server
+/// (e.g. using the `Forwarder` of `rama-tcp`). The peeked [`ClientHello`]
+/// is added to the [`Context`] of the routed service.
+///
+/// Streams which do not start with a [`ClientHello`], or which
+/// do not match any route, are passed to the fallback service if defined,
+/// and are otherwise refused with an error.
+///
+/// Use the [`ServerNameMatcher`] and [`AlpnMatcher`] to route on the server name (SNI)
+/// and offered application protocols (ALPN) respectively.
+///
+/// [`ServerNameMatcher`]: super::ServerNameMatcher
+/// [`AlpnMatcher`]: super::AlpnMatcher
+///
+/// # Example
+///
+/// ```
+/// use rama_core::{Context, service::service_fn};
+/// use rama_net::{
+///     address::Domain,
+///     stream::PeekStream,
+///     tls::server::{ServerNameMatcher, SniRouter},
+/// };
+/// use std::convert::Infallible;
+/// use tokio::io::DuplexStream;
+///
+/// async fn backend(_ctx: Context<()>, _stream: PeekStream<DuplexStream>) -> Result<(), Infallible> {
+///     Ok(())
+/// }
+///
+/// let router = SniRouter::new()
+///     .on(ServerNameMatcher::exact(Domain::from_static("example.com")), service_fn(backend))
+///     .on(
+///         ServerNameMatcher::sub(Domain::from_static("internal.example.com")),
+///         service_fn(backend),
+///     )
+///     .with_fallback(service_fn(backend));
+/// # let _ = router;
+/// ```
+pub struct SniRouter<State, IO> {
+    routes: Vec<Arc<Route<State, IO>>>,
+    fallback: Option<Arc<BoxService<State, PeekStream<IO>, (), BoxError>>>,
+    peek_timeout: Option<Duration>,
+    max_peek_size: usize,
+}
+
+struct Route<State, IO> {
+    matcher: Box<dyn Matcher<State, ClientHello>>,
+    service: BoxService<State, PeekStream<IO>, (), BoxError>,
+}
+
+impl<State, IO> fmt::Debug for SniRouter<State, IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SniRouter")
+            .field("routes", &self.routes.len())
+            .field("fallback", &self.fallback.is_some())
+            .field("peek_timeout", &self.peek_timeout)
+            .field("max_peek_size", &self.max_peek_size)
+            .finish()
+    }
+}
+
+impl<State, IO> Clone for SniRouter<State, IO> {
+    fn clone(&self) -> Self {
+        Self {
+            routes: self.routes.clone(),
+            fallback: self.fallback.clone(),
+            peek_timeout: self.peek_timeout,
+            max_peek_size: self.max_peek_size,
+        }
+    }
+}
+
+impl<State, IO> Default for SniRouter<State, IO> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State, IO> SniRouter<State, IO> {
+    /// Create a new [`SniRouter`] without any routes or fallback.
+    pub const fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            fallback: None,
+            peek_timeout: None,
+            max_peek_size: DEFAULT_MAX_PEEK_SIZE,
+        }
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default there is no timeout.
+    pub fn with_peek_timeout(mut self, timeout: Duration) -> Self {
+        self.peek_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default there is no timeout.
+    pub fn set_peek_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.peek_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum amount of bytes to read in order to find the [`ClientHello`],
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default this is 32 KiB.
+    pub fn with_max_peek_size(mut self, size: usize) -> Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes to read in order to find the [`ClientHello`],
+    /// after which the stream is passed to the fallback service.
+    ///
+    /// By default this is 32 KiB.
+    pub fn set_max_peek_size(&mut self, size: usize) -> &mut Self {
+        self.max_peek_size = size;
+        self
+    }
+}
+
+impl<State, IO> SniRouter<State, IO>
+where
+    State: Clone + Send + Sync + 'static,
+    IO: Stream,
+{
+    /// Add a route, passing the streams of which the [`ClientHello`]
+    /// matches the given [`Matcher`] to the given [`Service`].
+    ///
+    /// Routes are matched in the order they were added.
+    pub fn on<M, S>(mut self, matcher: M, service: S) -> Self
+    where
+        M: Matcher<State, ClientHello>,
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        self.routes.push(Arc::new(Route {
+            matcher: Box::new(matcher),
+            service: MapErr::new(service, Into::into).boxed(),
+        }));
+        self
+    }
+
+    /// Set the [`Service`] to which the streams are passed
+    /// which do not match any route or which could not be peeked.
+    pub fn with_fallback<S>(mut self, service: S) -> Self
+    where
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        self.fallback = Some(Arc::new(MapErr::new(service, Into::into).boxed()));
+        self
+    }
+
+    /// Set the [`Service`] to which the streams are passed
+    /// which do not match any route or which could not be peeked.
+    pub fn set_fallback<S>(&mut self, service: S) -> &mut Self
+    where
+        S: Service<State, PeekStream<IO>, Response = (), Error: Into<BoxError>>,
+    {
+        self.fallback = Some(Arc::new(MapErr::new(service, Into::into).boxed()));
+        self
+    }
+}
+
+impl<State, IO> Service<State, IO> for SniRouter<State, IO>
+where
+    State: Clone + Send + Sync + 'static,
+    IO: Stream + Unpin,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(&self, mut ctx: Context<State>, mut stream: IO) -> Result<(), BoxError> {
+        let mut peeked = Vec::new();
+        let read = read_client_hello(&mut stream, &mut peeked, self.max_peek_size);
+        let hello = match self.peek_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, read).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::debug!("SniRouter: timeout while reading client hello");
+                    Ok(None)
+                }
+            },
+            None => read.await,
+        }
+        .context("SniRouter: read client hello")?;
+
+        let route = hello.as_ref().and_then(|hello| {
+            self.routes
+                .iter()
+                .find(|route| route.matcher.matches(None, &ctx, hello))
+        });
+        if let Some(hello) = hello {
+            ctx.insert(hello);
+        }
+
+        let (r, w) = tokio::io::split(stream);
+        let stream = tokio::io::join(ChainReader::new(HeapReader::new(peeked), r), w);
+
+        match (route, &self.fallback) {
+            (Some(route), _) => route.service.serve(ctx, stream).await,
+            (None, Some(fallback)) => fallback.serve(ctx, stream).await,
+            (None, None) => Err(OpaqueError::from_display(
+                "SniRouter: no route found for incoming stream",
+            )
+            .into()),
+        }
+    }
+}
+
+/// Read the TLS records containing the [`ClientHello`] from the given stream,
+/// appending all bytes read to the given buffer.
+///
+/// Returns `None` in case the stream does not start with a [`ClientHello`],
+/// or it could not be read within the byte budget.
+async fn read_client_hello<R>(
+    stream: &mut R,
+    buffer: &mut Vec<u8>,
+    max_size: usize,
+) -> io::Result<Option<ClientHello>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut handshake = Vec::new();
+    let mut offset = 0;
+    loop {
+        if !fill(stream, buffer, offset + RECORD_HEADER_LEN, max_size).await? {
+            return Ok(None);
+        }
+        let header = &buffer[offset..offset + RECORD_HEADER_LEN];
+        if header[0] != CONTENT_TYPE_HANDSHAKE || header[1] != 0x03 {
+            tracing::trace!("SniRouter: stream does not start with a tls handshake record");
+            return Ok(None);
+        }
+        let end = offset + RECORD_HEADER_LEN + u16::from_be_bytes([header[3], header[4]]) as usize;
+        if !fill(stream, buffer, end, max_size).await? {
+            return Ok(None);
+        }
+        handshake.extend_from_slice(&buffer[offset + RECORD_HEADER_LEN..end]);
+        offset = end;
+
+        if handshake.len() < HANDSHAKE_HEADER_LEN {
+            continue;
+        }
+        if handshake[0] != HANDSHAKE_TYPE_CLIENT_HELLO {
+            tracing::trace!("SniRouter: first tls handshake message is not a client hello");
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+        if let Some(message) = handshake.get(HANDSHAKE_HEADER_LEN..HANDSHAKE_HEADER_LEN + len) {
+            return Ok(parse_client_hello(message)
+                .inspect_err(|err| tracing::debug!(%err, "SniRouter: invalid client hello"))
+                .ok());
+        }
+    }
+}
+
+/// Read from the stream until the buffer contains at least `size` bytes.
+///
+/// Returns `false` if the stream ended before or `size` exceeds `max_size`.
+async fn fill<R>(
+    stream: &mut R,
+    buffer: &mut Vec<u8>,
+    size: usize,
+    max_size: usize,
+) -> io::Result<bool>
+where
+    R: AsyncRead + Unpin,
+{
+    if size > max_size {
+        tracing::debug!("SniRouter: client hello exceeds max peek size of {max_size} bytes");
+        return Ok(false);
+    }
+    while buffer.len() < size {
+        if stream.read_buf(buffer).await? == 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Domain;
+    use crate::tls::ApplicationProtocol;
+    use crate::tls::server::{AlpnMatcher, ServerNameMatcher};
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// A minimal TLS 1.2 client hello, split over two records.
+    fn client_hello_records(server_name: &str, alpn: &[u8]) -> Vec<u8> {
+        let mut extensions = Vec::new();
+        // server name
+        let name = server_name.as_bytes();
+        extensions.extend_from_slice(&[0, 0]);
+        extensions.extend_from_slice(&((name.len() + 5) as u16).to_be_bytes());
+        extensions.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        extensions.push(0);
+        extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(name);
+        // alpn
+        extensions.extend_from_slice(&[0, 16]);
+        extensions.extend_from_slice(&((alpn.len() + 3) as u16).to_be_bytes());
+        extensions.extend_from_slice(&((alpn.len() + 1) as u16).to_be_bytes());
+        extensions.push(alpn.len() as u8);
+        extensions.extend_from_slice(alpn);
+
+        let mut hello = vec![3, 3];
+        hello.extend_from_slice(&[0; 32]); // random
+        hello.push(0); // session id
+        hello.extend_from_slice(&[0, 2, 0x13, 0x01]); // cipher suites
+        hello.extend_from_slice(&[1, 0]); // compression methods
+        hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        hello.extend_from_slice(&extensions);
+
+        let mut handshake = vec![HANDSHAKE_TYPE_CLIENT_HELLO];
+        handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&hello);
+
+        let (first, second) = handshake.split_at(10);
+        let mut records = Vec::new();
+        for fragment in [first, second] {
+            records.extend_from_slice(&[CONTENT_TYPE_HANDSHAKE, 3, 1]);
+            records.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            records.extend_from_slice(fragment);
+        }
+        records
+    }
+
+    async fn route(router: &SniRouter<(), DuplexStream>, data: &[u8]) -> Result<(), BoxError> {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client.write_all(data).await.unwrap();
+        client.shutdown().await.unwrap();
+        router.serve(Context::default(), server).await
+    }
+
+    fn named_service(
+        name: &'static str,
+    ) -> impl Service<(), PeekStream<DuplexStream>, Response = (), Error = BoxError> {
+        service_fn(
+            move |ctx: Context<()>, mut stream: PeekStream<DuplexStream>| async move {
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data).await.unwrap();
+                let sni = ctx
+                    .get::<ClientHello>()
+                    .and_then(|hello| hello.ext_server_name())
+                    .map(|host| host.to_string());
+                Err::<(), BoxError>(
+                    OpaqueError::from_display(format!("{name}:{sni:?}:{}", data.len())).into(),
+                )
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sni_router() {
+        let router = SniRouter::new()
+            .on(
+                ServerNameMatcher::exact(Domain::from_static("example.com")),
+                named_service("exact"),
+            )
+            .on(
+                ServerNameMatcher::sub(Domain::from_static("example.org"))
+                    .and(AlpnMatcher::new(ApplicationProtocol::HTTP_2)),
+                named_service("sub"),
+            )
+            .on(
+                ServerNameMatcher::regex(r"^api-\d+\.").unwrap(),
+                named_service("regex"),
+            )
+            .with_fallback(named_service("fallback"));
+
+        for (data, expected) in [
+            (
+                client_hello_records("example.com", b"h2"),
+                "exact:Some(\"example.com\")",
+            ),
+            (
+                client_hello_records("www.example.org", b"h2"),
+                "sub:Some(\"www.example.org\")",
+            ),
+            (
+                client_hello_records("www.example.org", b"http/1.1"),
+                "fallback:Some(\"www.example.org\")",
+            ),
+            (
+                client_hello_records("api-1.example.net", b"h2"),
+                "regex:Some(\"api-1.example.net\")",
+            ),
+            (b"GET / HTTP/1.1\r\n\r\n".to_vec(), "fallback:None"),
+        ] {
+            let err = route(&router, &data).await.unwrap_err();
+            // the full stream is replayed to the routed service
+            assert_eq!(err.to_string(), format!("{expected}:{}", data.len()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sni_router_without_fallback() {
+        let router: SniRouter<(), DuplexStream> = SniRouter::new().on(
+            ServerNameMatcher::exact(Domain::from_static("example.com")),
+            service_fn(async |_: PeekStream<DuplexStream>| Ok::<_, Infallible>(())),
+        );
+        assert!(
+            route(&router, &client_hello_records("example.com", b"h2"))
+                .await
+                .is_ok()
+        );
+        assert!(
+            route(&router, &client_hello_records("example.org", b"h2"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sni_router_max_peek_size() {
+        let router = SniRouter::new()
+            .with_max_peek_size(16)
+            .on(
+                ServerNameMatcher::exact(Domain::from_static("example.com")),
+                named_service("exact"),
+            )
+            .with_fallback(named_service("fallback"));
+        let data = client_hello_records("example.com", b"h2");
+        let err = route(&router, &data).await.unwrap_err();
+        assert_eq!(err.to_string(), format!("fallback:None:{}", data.len()));
+    }
+
+    #[test]
+    fn test_client_hello_records_parse() {
+        let records = client_hello_records("example.com", b"h2");
+        let mut buffer = Vec::new();
+        let hello = futures_lite::future::block_on(read_client_hello(
+            &mut &records[..],
+            &mut buffer,
+            DEFAULT_MAX_PEEK_SIZE,
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(buffer, records);
+        assert!(
+            ServerNameMatcher::exact(Domain::from_static("example.com")).matches(
+                None,
+                &Context::<()>::default(),
+                &hello
+            )
+        );
+    }
+}
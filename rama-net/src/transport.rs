@@ -6,6 +6,7 @@ use crate::http::RequestContext;
 use crate::{Protocol, address::Authority};
 use rama_core::{Context, error::OpaqueError};
 use rama_http_types::{Request, Version, dep::http::request::Parts as HttpParts};
+use std::convert::Infallible;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// The context as relevant to the transport layer,
@@ -51,6 +52,17 @@ pub trait TryRefIntoTransportContext<State> {
     ) -> Result<TransportContext, Self::Error>;
 }
 
+impl<State> TryRefIntoTransportContext<State> for TransportContext {
+    type Error = Infallible;
+
+    fn try_ref_into_transport_ctx(
+        &self,
+        _ctx: &Context<State>,
+    ) -> Result<TransportContext, Self::Error> {
+        Ok(self.clone())
+    }
+}
+
 impl<State, Body> TryFrom<(&Context<State>, &Request<Body>)> for TransportContext {
     type Error = OpaqueError;
 
@@ -0,0 +1,512 @@
+//! Access control lists (ACL) to restrict the destinations users can reach.
+//!
+//! An [`AclPolicy`] consists of [`AclRule`]s, keyed on the [`UserId`] and username labels
+//! of the user, matching destinations by domain, IP network or port, as well as the
+//! [`Protocol`], http [`Method`] and [`TimeWindow`] of a request. The action applied to
+//! a request is determined by the [`AclPrecedence`] of the policy, falling back to its
+//! default [`AclAction`] when no rule matches.
+//!
+//! Policies can be created in code or deserialized (e.g. from json),
+//! and loaded from a file which is reloaded when it changes using [`AclPolicyFile`].
+//!
+//! It is protocol agnostic: layers (such as the http `AclLayer` of `rama-http`)
+//! evaluate an [`AclRequest`] using an [`Acl`].
+//!
+//! [`Method`]: rama_http_types::Method
+//!
+//! # Example
+//!
+//! ```
+//! use rama_net::address::Authority;
+//! use rama_net::user::UserId;
+//! use rama_net::user::acl::{AclAction, AclPolicy, AclRequest};
+//!
+//! let policy = AclPolicy::try_from_json(r#"{
+//!     "default": "deny",
+//!     "rules": [
+//!         { "action": "allow", "users": ["alice"], "domains": ["*.example.com"], "ports": [443] },
+//!         { "action": "deny", "labels": ["trial"], "time_windows": ["sat,sun 00:00-24:00"] },
+//!         { "action": "allow", "cidrs": ["203.0.113.0/24"], "protocols": ["http", "https"] }
+//!     ]
+//! }"#).unwrap();
+//!
+//! let alice = UserId::Username("alice".to_owned());
+//! let authority: Authority = "api.example.com:443".parse().unwrap();
+//! let request = AclRequest::new(&authority).with_user(&alice);
+//! assert_eq!(policy.evaluate(&request), AclAction::Allow);
+//!
+//! let authority: Authority = "example.com:443".parse().unwrap();
+//! let request = AclRequest::new(&authority).with_user(&alice);
+//! assert_eq!(policy.evaluate(&request), AclAction::Deny);
+//! ```
+
+use crate::Protocol;
+use crate::address::Authority;
+use crate::user::{UserId, reload::ReloadingFile};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::Method;
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+mod rule;
+#[doc(inline)]
+pub use rule::{AclRule, DomainPattern, PortRange, TimeWindow, Weekday};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The action to apply to a request.
+pub enum AclAction {
+    /// Allow the request.
+    Allow,
+    /// Deny the request.
+    #[default]
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// How the action is decided when multiple [`AclRule`]s match a request.
+pub enum AclPrecedence {
+    /// Deny the request if any of the matching rules denies it.
+    #[default]
+    DenyOverrides,
+    /// Allow the request if any of the matching rules allows it.
+    AllowOverrides,
+    /// Apply the action of the first matching rule.
+    FirstMatch,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// A policy of [`AclRule`]s, see the [module docs](self) for more information.
+pub struct AclPolicy {
+    #[serde(default, rename = "default")]
+    default_action: AclAction,
+    #[serde(default)]
+    precedence: AclPrecedence,
+    #[serde(default)]
+    rules: Vec<AclRule>,
+}
+
+impl AclPolicy {
+    /// Create a new [`AclPolicy`] without rules,
+    /// applying the given action to all requests.
+    pub fn new(default_action: AclAction) -> Self {
+        Self {
+            default_action,
+            precedence: AclPrecedence::default(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Try to deserialize an [`AclPolicy`] from json.
+    pub fn try_from_json(json: &str) -> Result<Self, OpaqueError> {
+        serde_json::from_str(json).context("deserialize acl policy from json")
+    }
+
+    /// Set the [`AclPrecedence`] of this policy, [`AclPrecedence::DenyOverrides`] by default.
+    pub fn with_precedence(mut self, precedence: AclPrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Set the [`AclPrecedence`] of this policy, [`AclPrecedence::DenyOverrides`] by default.
+    pub fn set_precedence(&mut self, precedence: AclPrecedence) -> &mut Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Add an [`AclRule`] to this policy.
+    pub fn with_rule(mut self, rule: AclRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Add an [`AclRule`] to this policy.
+    pub fn set_rule(&mut self, rule: AclRule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The [`AclAction`] applied to requests not matched by any rule.
+    pub fn default_action(&self) -> AclAction {
+        self.default_action
+    }
+
+    /// The [`AclRule`]s of this policy.
+    pub fn rules(&self) -> &[AclRule] {
+        &self.rules
+    }
+
+    /// Evaluate the [`AclAction`] to apply to the given request.
+    pub fn evaluate(&self, request: &AclRequest<'_>) -> AclAction {
+        let mut matched = None;
+        for rule in self.rules.iter().filter(|rule| rule.matches(request)) {
+            match (self.precedence, rule.action()) {
+                (AclPrecedence::FirstMatch, action)
+                | (AclPrecedence::DenyOverrides, action @ AclAction::Deny)
+                | (AclPrecedence::AllowOverrides, action @ AclAction::Allow) => return action,
+                (_, action) => matched = Some(action),
+            }
+        }
+        matched.unwrap_or(self.default_action)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A request evaluated by an [`Acl`].
+pub struct AclRequest<'a> {
+    /// The user making the request, if known.
+    pub user: Option<&'a UserId>,
+    /// The username labels of the user.
+    pub labels: &'a [String],
+    /// The destination of the request.
+    pub authority: &'a Authority,
+    /// The protocol of the request, if known.
+    pub protocol: Option<&'a Protocol>,
+    /// The http method of the request, if any.
+    pub method: Option<&'a Method>,
+    /// The time of the request.
+    pub time: SystemTime,
+}
+
+impl<'a> AclRequest<'a> {
+    /// Create a new [`AclRequest`] for the given destination, made now.
+    pub fn new(authority: &'a Authority) -> Self {
+        Self {
+            user: None,
+            labels: &[],
+            authority,
+            protocol: None,
+            method: None,
+            time: SystemTime::now(),
+        }
+    }
+
+    /// Set the user making the request.
+    pub fn with_user(mut self, user: &'a UserId) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Set the username labels of the user.
+    pub fn with_labels(mut self, labels: &'a [String]) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Set the protocol of the request.
+    pub fn with_protocol(mut self, protocol: &'a Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Set the http method of the request.
+    pub fn with_method(mut self, method: &'a Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Set the time of the request.
+    pub fn with_time(mut self, time: SystemTime) -> Self {
+        self.time = time;
+        self
+    }
+}
+
+/// An access control list, evaluating the [`AclAction`] to apply to an [`AclRequest`].
+pub trait Acl: Send + Sync + 'static {
+    /// Evaluate the [`AclAction`] to apply to the given request.
+    fn evaluate(&self, request: &AclRequest<'_>) -> impl Future<Output = AclAction> + Send;
+}
+
+impl Acl for AclPolicy {
+    fn evaluate(&self, request: &AclRequest<'_>) -> impl Future<Output = AclAction> + Send {
+        std::future::ready(AclPolicy::evaluate(self, request))
+    }
+}
+
+impl<T: Acl> Acl for Arc<T> {
+    fn evaluate(&self, request: &AclRequest<'_>) -> impl Future<Output = AclAction> + Send {
+        (**self).evaluate(request)
+    }
+}
+
+/// An [`Acl`] loading its [`AclPolicy`] from a json file,
+/// which is reloaded when it changes.
+///
+/// The file is checked for changes at most once per check interval (5 seconds by default).
+/// The previously loaded policy remains in use when the file cannot be reloaded.
+/// Clones share the loaded policy.
+#[derive(Debug, Clone)]
+pub struct AclPolicyFile {
+    file: Arc<ReloadingFile<AclPolicy>>,
+    check_interval: Duration,
+}
+
+impl AclPolicyFile {
+    /// Open the json file at the given path, loading its [`AclPolicy`].
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self, OpaqueError> {
+        let file =
+            ReloadingFile::open("acl policy file", path.into(), AclPolicy::try_from_json).await?;
+        Ok(Self {
+            file: Arc::new(file),
+            check_interval: Duration::from_secs(5),
+        })
+    }
+
+    /// Set the minimum duration between two checks for changes of the file,
+    /// 5 seconds by default.
+    pub fn with_check_interval(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Set the minimum duration between two checks for changes of the file,
+    /// 5 seconds by default.
+    pub fn set_check_interval(&mut self, interval: Duration) -> &mut Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// View the path of the policy file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Returns the current [`AclPolicy`], reloading it first if the file changed.
+    pub async fn policy(&self) -> Arc<AclPolicy> {
+        self.file.get(self.check_interval).await
+    }
+}
+
+impl Acl for AclPolicyFile {
+    async fn evaluate(&self, request: &AclRequest<'_>) -> AclAction {
+        AclPolicy::evaluate(&*self.policy().await, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Domain;
+
+    fn username(name: &str) -> UserId {
+        UserId::Username(name.to_owned())
+    }
+
+    #[test]
+    fn acl_policy_precedence() {
+        let authority: Authority = "example.com:443".parse().unwrap();
+        let alice = username("alice");
+        let request = AclRequest::new(&authority).with_user(&alice);
+
+        let rules = [
+            AclRule::allow().with_user("alice"),
+            AclRule::deny().with_port(443),
+        ];
+        for (precedence, expected) in [
+            (AclPrecedence::DenyOverrides, AclAction::Deny),
+            (AclPrecedence::AllowOverrides, AclAction::Allow),
+            (AclPrecedence::FirstMatch, AclAction::Allow),
+        ] {
+            let policy = rules
+                .iter()
+                .cloned()
+                .fold(AclPolicy::new(AclAction::Deny), AclPolicy::with_rule)
+                .with_precedence(precedence);
+            assert_eq!(policy.evaluate(&request), expected, "{precedence:?}");
+        }
+
+        let policy = AclPolicy::new(AclAction::Allow).with_rule(AclRule::deny().with_user("bob"));
+        assert_eq!(policy.evaluate(&request), AclAction::Allow);
+        assert_eq!(
+            AclPolicy::default().evaluate(&AclRequest::new(&authority)),
+            AclAction::Deny
+        );
+    }
+
+    #[test]
+    fn acl_rule_criteria() {
+        let alice = username("alice");
+        let labels = vec!["premium".to_owned(), "eu".to_owned()];
+        let domain: Authority = "api.example.com:8080".parse().unwrap();
+        let ip: Authority = "[::ffff:10.0.0.1]:443".parse().unwrap();
+
+        let domain_request = AclRequest::new(&domain)
+            .with_user(&alice)
+            .with_labels(&labels)
+            .with_protocol(&Protocol::HTTP)
+            .with_method(&Method::GET);
+        let ip_request = AclRequest::new(&ip)
+            .with_protocol(&Protocol::HTTPS)
+            .with_method(&Method::CONNECT);
+
+        let test_cases = [
+            (AclRule::allow(), true, true),
+            (AclRule::allow().with_user("alice"), true, false),
+            (AclRule::allow().with_label("PREMIUM"), true, false),
+            (
+                AclRule::allow().with_label("premium").with_label("us"),
+                false,
+                false,
+            ),
+            (
+                AclRule::allow().with_domain(DomainPattern::Wildcard(Domain::example())),
+                true,
+                false,
+            ),
+            (
+                AclRule::allow().with_domain(DomainPattern::Exact(Domain::example())),
+                false,
+                false,
+            ),
+            (
+                AclRule::allow()
+                    .with_domain("*.example.com".parse::<DomainPattern>().unwrap())
+                    .with_cidr("10.0.0.0/8".parse::<ipnet::IpNet>().unwrap()),
+                true,
+                true,
+            ),
+            (AclRule::allow().with_port(8000..=8999), true, false),
+            (AclRule::allow().with_protocol(Protocol::HTTPS), false, true),
+            (
+                AclRule::allow()
+                    .with_method(Method::GET)
+                    .with_method(Method::CONNECT),
+                true,
+                true,
+            ),
+        ];
+        for (rule, domain_match, ip_match) in test_cases {
+            assert_eq!(rule.matches(&domain_request), domain_match, "{rule:?}");
+            assert_eq!(rule.matches(&ip_request), ip_match, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn acl_time_window() {
+        // thursday 1 january 1970
+        let at = |day: u64, hours: u64, minutes: u64| {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(day * 86400 + hours * 3600 + minutes * 60)
+        };
+
+        let window: TimeWindow = "mon-fri 09:00-17:00".parse().unwrap();
+        assert_eq!(window.to_string(), "mon,tue,wed,thu,fri 09:00-17:00");
+        assert!(window.contains(at(0, 9, 0)));
+        assert!(window.contains(at(1, 16, 59)));
+        assert!(!window.contains(at(1, 17, 0)));
+        assert!(!window.contains(at(2, 12, 0))); // saturday
+
+        let window: TimeWindow = "fri-sat 22:00-06:00".parse().unwrap();
+        assert!(window.contains(at(1, 23, 0))); // friday night
+        assert!(window.contains(at(2, 5, 0))); // early saturday, window of friday
+        assert!(window.contains(at(3, 5, 0))); // early sunday, window of saturday
+        assert!(!window.contains(at(4, 5, 0))); // early monday, window of sunday
+        assert!(!window.contains(at(1, 12, 0)));
+
+        let window = TimeWindow::new((0, 0), (24, 0)).with_days([Weekday::Sunday]);
+        assert!(window.contains(at(3, 23, 59)));
+        assert!(!window.contains(at(4, 0, 0)));
+
+        for invalid in [
+            "",
+            "09:00",
+            "mon 9-17",
+            "funday 09:00-17:00",
+            "25:00-26:00",
+            "00:00-00:00",
+            "24:00-00:00",
+            "mon 12:00-12:00",
+        ] {
+            assert!(invalid.parse::<TimeWindow>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn acl_time_window_cannot_be_empty() {
+        TimeWindow::new((24, 0), (0, 0));
+    }
+
+    #[test]
+    fn acl_policy_from_json() {
+        let policy = AclPolicy::try_from_json(
+            r#"{
+                "default": "allow",
+                "precedence": "first-match",
+                "rules": [{
+                    "action": "deny",
+                    "users": ["bob"],
+                    "labels": ["trial"],
+                    "domains": ["internal.example.com", "*.corp"],
+                    "cidrs": ["10.0.0.0/8", "::1"],
+                    "ports": [22, "8000-8999"],
+                    "protocols": ["https", "socks5"],
+                    "methods": ["connect"],
+                    "time_windows": ["mon-fri 09:00-17:00"]
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(policy.default_action(), AclAction::Allow);
+
+        let expected = AclRule::deny()
+            .with_user("bob")
+            .with_label("trial")
+            .with_domain("internal.example.com".parse::<DomainPattern>().unwrap())
+            .with_domain("*.corp".parse::<DomainPattern>().unwrap())
+            .with_cidr("10.0.0.0/8".parse::<ipnet::IpNet>().unwrap())
+            .with_cidr("::1/128".parse::<ipnet::IpNet>().unwrap())
+            .with_port(22)
+            .with_port(8000..=8999)
+            .with_protocol(Protocol::HTTPS)
+            .with_protocol(Protocol::SOCKS5)
+            .with_method(Method::CONNECT)
+            .with_time_window("mon-fri 09:00-17:00".parse::<TimeWindow>().unwrap());
+        assert_eq!(policy.rules(), &[expected]);
+
+        for invalid in [
+            r#"{"rules": [{"action": "maybe"}]}"#,
+            r#"{"rules": [{"action": "allow", "ports": ["443-80"]}]}"#,
+            r#"{"rules": [{"action": "allow", "cidrs": ["10.0.0.0/33"]}]}"#,
+            r#"{"rules": [{"action": "allow", "unknown": true}]}"#,
+        ] {
+            assert!(AclPolicy::try_from_json(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn acl_policy_file_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acl.json");
+        tokio::fs::write(&path, r#"{"default": "allow"}"#)
+            .await
+            .unwrap();
+
+        let acl = AclPolicyFile::open(&path)
+            .await
+            .unwrap()
+            .with_check_interval(Duration::ZERO);
+        let authority: Authority = "example.com:443".parse().unwrap();
+        let request = AclRequest::new(&authority);
+        assert_eq!(Acl::evaluate(&acl, &request).await, AclAction::Allow);
+
+        tokio::fs::write(
+            &path,
+            r#"{"default": "allow", "rules": [{"action": "deny", "ports": [443]}]}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(Acl::evaluate(&acl, &request).await, AclAction::Deny);
+
+        // an invalid policy is ignored
+        tokio::fs::write(&path, "{").await.unwrap();
+        assert_eq!(Acl::evaluate(&acl, &request).await, AclAction::Deny);
+    }
+}
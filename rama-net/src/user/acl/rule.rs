@@ -0,0 +1,654 @@
+use super::{AclAction, AclRequest};
+use crate::Protocol;
+use crate::address::{Domain, Host};
+use crate::user::UserId;
+use ipnet::IpNet;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::Method;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    fmt,
+    net::IpAddr,
+    ops::RangeInclusive,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "AclRuleConfig")]
+/// A rule of an [`AclPolicy`], which applies its [`AclAction`]
+/// to the requests matching all its (non-empty) criteria.
+///
+/// Within a criterion a single value has to match, e.g. a rule for the
+/// ports `80` and `443` matches requests for either of these ports.
+/// A rule without criteria matches all requests.
+///
+/// [`AclPolicy`]: super::AclPolicy
+pub struct AclRule {
+    action: AclAction,
+    users: Vec<String>,
+    labels: Vec<String>,
+    domains: Vec<DomainPattern>,
+    cidrs: Vec<IpNet>,
+    ports: Vec<PortRange>,
+    protocols: Vec<Protocol>,
+    methods: Vec<Method>,
+    time_windows: Vec<TimeWindow>,
+}
+
+macro_rules! rule_criterion {
+    ($(#[$doc:meta])* $with:ident, $set:ident, $field:ident: $ty:ty) => {
+        $(#[$doc])*
+        pub fn $with(mut self, value: impl Into<$ty>) -> Self {
+            self.$field.push(value.into());
+            self
+        }
+
+        $(#[$doc])*
+        pub fn $set(&mut self, value: impl Into<$ty>) -> &mut Self {
+            self.$field.push(value.into());
+            self
+        }
+    };
+}
+
+impl AclRule {
+    /// Create a new [`AclRule`] with the given [`AclAction`], matching all requests.
+    pub fn new(action: AclAction) -> Self {
+        Self {
+            action,
+            users: Vec::new(),
+            labels: Vec::new(),
+            domains: Vec::new(),
+            cidrs: Vec::new(),
+            ports: Vec::new(),
+            protocols: Vec::new(),
+            methods: Vec::new(),
+            time_windows: Vec::new(),
+        }
+    }
+
+    /// Create a new [`AclRule`] which allows the requests it matches.
+    pub fn allow() -> Self {
+        Self::new(AclAction::Allow)
+    }
+
+    /// Create a new [`AclRule`] which denies the requests it matches.
+    pub fn deny() -> Self {
+        Self::new(AclAction::Deny)
+    }
+
+    /// The [`AclAction`] applied to the requests matched by this rule.
+    pub fn action(&self) -> AclAction {
+        self.action
+    }
+
+    rule_criterion!(
+        /// Match requests of the user with the given username ([`UserId::Username`]).
+        with_user,
+        set_user,
+        users: String
+    );
+
+    rule_criterion!(
+        /// Match requests of users with the given username label.
+        ///
+        /// Unlike the other criteria, all labels of a rule have to be present
+        /// in the [`UsernameLabels`] of the request.
+        ///
+        /// [`UsernameLabels`]: rama_core::username::UsernameLabels
+        with_label,
+        set_label,
+        labels: String
+    );
+
+    rule_criterion!(
+        /// Match requests for a domain matching the given [`DomainPattern`].
+        ///
+        /// The domains and cidrs of a rule are combined:
+        /// a destination matching one of either suffices.
+        with_domain,
+        set_domain,
+        domains: DomainPattern
+    );
+
+    rule_criterion!(
+        /// Match requests for an IP address within the given network.
+        ///
+        /// Only IP destinations are matched, domains are not resolved.
+        /// The domains and cidrs of a rule are combined:
+        /// a destination matching one of either suffices.
+        with_cidr,
+        set_cidr,
+        cidrs: IpNet
+    );
+
+    rule_criterion!(
+        /// Match requests for a port within the given [`PortRange`].
+        with_port,
+        set_port,
+        ports: PortRange
+    );
+
+    rule_criterion!(
+        /// Match requests using the given [`Protocol`].
+        with_protocol,
+        set_protocol,
+        protocols: Protocol
+    );
+
+    rule_criterion!(
+        /// Match (http) requests with the given [`Method`].
+        with_method,
+        set_method,
+        methods: Method
+    );
+
+    rule_criterion!(
+        /// Match requests made within the given [`TimeWindow`].
+        with_time_window,
+        set_time_window,
+        time_windows: TimeWindow
+    );
+
+    /// Returns true if the request matches all criteria of this rule.
+    pub fn matches(&self, request: &AclRequest<'_>) -> bool {
+        self.matches_user(request)
+            && self.matches_destination(request)
+            && matches_any(&self.ports, |range| {
+                range.contains(request.authority.port())
+            })
+            && matches_any(&self.protocols, |protocol| {
+                request.protocol == Some(protocol)
+            })
+            && matches_any(&self.methods, |method| request.method == Some(method))
+            && matches_any(&self.time_windows, |window| window.contains(request.time))
+    }
+
+    fn matches_user(&self, request: &AclRequest<'_>) -> bool {
+        matches_any(
+            &self.users,
+            |user| matches!(request.user, Some(UserId::Username(username)) if username == user),
+        ) && self
+            .labels
+            .iter()
+            .all(|label| request.labels.iter().any(|l| l.eq_ignore_ascii_case(label)))
+    }
+
+    fn matches_destination(&self, request: &AclRequest<'_>) -> bool {
+        if self.domains.is_empty() && self.cidrs.is_empty() {
+            return true;
+        }
+        match request.authority.host() {
+            Host::Name(domain) => self.domains.iter().any(|pattern| pattern.matches(domain)),
+            Host::Address(ip) => {
+                let ip = canonical_ip(*ip);
+                self.cidrs.iter().any(|net| net.contains(&ip))
+            }
+        }
+    }
+}
+
+fn matches_any<T>(values: &[T], f: impl Fn(&T) -> bool) -> bool {
+    values.is_empty() || values.iter().any(f)
+}
+
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => ip
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(ip)),
+        ip @ IpAddr::V4(_) => ip,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AclRuleConfig {
+    action: AclAction,
+    #[serde(default)]
+    users: Vec<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    domains: Vec<DomainPattern>,
+    #[serde(default)]
+    cidrs: Vec<String>,
+    #[serde(default)]
+    ports: Vec<PortRange>,
+    #[serde(default)]
+    protocols: Vec<String>,
+    #[serde(default)]
+    methods: Vec<String>,
+    #[serde(default)]
+    time_windows: Vec<TimeWindow>,
+}
+
+impl TryFrom<AclRuleConfig> for AclRule {
+    type Error = OpaqueError;
+
+    fn try_from(config: AclRuleConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            action: config.action,
+            users: config.users,
+            labels: config.labels,
+            domains: config.domains,
+            cidrs: config
+                .cidrs
+                .iter()
+                .map(|cidr| {
+                    cidr.parse()
+                        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                        .with_context(|| format!("parse cidr: {cidr}"))
+                })
+                .collect::<Result<_, _>>()?,
+            ports: config.ports,
+            protocols: config
+                .protocols
+                .iter()
+                .map(|protocol| {
+                    Protocol::try_from(protocol.as_str())
+                        .with_context(|| format!("parse protocol: {protocol}"))
+                })
+                .collect::<Result<_, _>>()?,
+            methods: config
+                .methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .with_context(|| format!("parse method: {method}"))
+                })
+                .collect::<Result<_, _>>()?,
+            time_windows: config.time_windows,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A pattern matching domains, either exactly (e.g. `example.com`)
+/// or all its subdomains (e.g. `*.example.com`), excluding the domain itself.
+pub enum DomainPattern {
+    /// Match the domain exactly.
+    Exact(Domain),
+    /// Match all subdomains of the domain.
+    Wildcard(Domain),
+}
+
+impl DomainPattern {
+    /// Returns true if the domain matches this pattern.
+    pub fn matches(&self, domain: &Domain) -> bool {
+        match self {
+            Self::Exact(pattern) => pattern == domain,
+            Self::Wildcard(parent) => domain.is_sub_of(parent) && domain != parent,
+        }
+    }
+}
+
+impl From<Domain> for DomainPattern {
+    fn from(domain: Domain) -> Self {
+        Self::Exact(domain)
+    }
+}
+
+impl FromStr for DomainPattern {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("*.") {
+            Some(parent) => parent.parse().map(Self::Wildcard),
+            None => s.parse().map(Self::Exact),
+        }
+        .with_context(|| format!("parse domain pattern: {s}"))
+    }
+}
+
+impl fmt::Display for DomainPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(domain) => domain.fmt(f),
+            Self::Wildcard(domain) => write!(f, "*.{domain}"),
+        }
+    }
+}
+
+impl Serialize for DomainPattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DomainPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An inclusive range of ports, e.g. `443` or `8000-8999`.
+pub struct PortRange(RangeInclusive<u16>);
+
+impl PortRange {
+    /// Create a new [`PortRange`] from the first to the last port (inclusive).
+    pub fn new(first: u16, last: u16) -> Self {
+        Self(first..=last)
+    }
+
+    /// Returns true if the port is within this range.
+    pub fn contains(&self, port: u16) -> bool {
+        self.0.contains(&port)
+    }
+}
+
+impl From<u16> for PortRange {
+    fn from(port: u16) -> Self {
+        Self(port..=port)
+    }
+}
+
+impl From<RangeInclusive<u16>> for PortRange {
+    fn from(range: RangeInclusive<u16>) -> Self {
+        Self(range)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        let first = first.trim().parse().context("parse first port")?;
+        let last = last.trim().parse().context("parse last port")?;
+        if first > last {
+            return Err(OpaqueError::from_display(format!(
+                "invalid port range: {s}"
+            )));
+        }
+        Ok(Self(first..=last))
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.start() == self.0.end() {
+            write!(f, "{}", self.0.start())
+        } else {
+            write!(f, "{}-{}", self.0.start(), self.0.end())
+        }
+    }
+}
+
+impl Serialize for PortRange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum PortOrRange<'a> {
+            Port(u16),
+            Range(Cow<'a, str>),
+        }
+
+        match PortOrRange::deserialize(deserializer)? {
+            PortOrRange::Port(port) => Ok(port.into()),
+            PortOrRange::Range(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A day of the week.
+pub enum Weekday {
+    /// Monday
+    Monday,
+    /// Tuesday
+    Tuesday,
+    /// Wednesday
+    Wednesday,
+    /// Thursday
+    Thursday,
+    /// Friday
+    Friday,
+    /// Saturday
+    Saturday,
+    /// Sunday
+    Sunday,
+}
+
+impl Weekday {
+    const ALL: [Self; 7] = [
+        Self::Monday,
+        Self::Tuesday,
+        Self::Wednesday,
+        Self::Thursday,
+        Self::Friday,
+        Self::Saturday,
+        Self::Sunday,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Monday => "mon",
+            Self::Tuesday => "tue",
+            Self::Wednesday => "wed",
+            Self::Thursday => "thu",
+            Self::Friday => "fri",
+            Self::Saturday => "sat",
+            Self::Sunday => "sun",
+        }
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|day| s.len() >= 3 && day.as_str().eq_ignore_ascii_case(&s[..3]))
+            .ok_or_else(|| OpaqueError::from_display(format!("invalid weekday: {s}")))
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A window of time on (some) days of the week, in UTC.
+///
+/// Its string format is `[<days> ]<start>-<end>`, where the optional days are
+/// a comma separated list of days or ranges of days, e.g. `mon-fri 09:00-17:00`
+/// or `sat,sun 10:00-14:00`. A window ending before it starts wraps around midnight,
+/// e.g. `22:00-06:00`, in which case the days refer to the start of the window.
+/// A window starting and ending at the same time is empty and therefore rejected,
+/// use `00:00-24:00` for a window covering the full day instead.
+pub struct TimeWindow {
+    days: Vec<Weekday>,
+    start: u32,
+    end: u32,
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+impl TimeWindow {
+    /// Create a new [`TimeWindow`] between the given times (hours and minutes) on every day.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hours or minutes are out of range,
+    /// or if the window is empty as it starts and ends at the same time.
+    pub fn new(start: (u8, u8), end: (u8, u8)) -> Self {
+        let start = secs_of_day(start).expect("valid start time");
+        let end = secs_of_day(end).expect("valid end time");
+        assert!(
+            !is_empty_window(start, end),
+            "time window cannot start and end at the same time"
+        );
+        Self {
+            days: Vec::new(),
+            start,
+            end,
+        }
+    }
+
+    /// Only match this window on the given days.
+    pub fn with_days(mut self, days: impl IntoIterator<Item = Weekday>) -> Self {
+        self.days = days.into_iter().collect();
+        self
+    }
+
+    /// Only match this window on the given days.
+    pub fn set_days(&mut self, days: impl IntoIterator<Item = Weekday>) -> &mut Self {
+        self.days = days.into_iter().collect();
+        self
+    }
+
+    /// Returns true if the given time falls within this window.
+    pub fn contains(&self, time: SystemTime) -> bool {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let days = secs / SECS_PER_DAY;
+        let secs_of_day = (secs % SECS_PER_DAY) as u32;
+        // the unix epoch was on a thursday
+        let weekday = |days: u64| Weekday::ALL[((days + 3) % 7) as usize];
+
+        let (in_window, day) = if self.start <= self.end {
+            ((self.start..self.end).contains(&secs_of_day), weekday(days))
+        } else if secs_of_day >= self.start {
+            (true, weekday(days))
+        } else {
+            (secs_of_day < self.end, weekday(days + 6))
+        };
+        in_window && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+/// A window which starts at the time it ends never contains any time,
+/// which includes `24:00-00:00` as it wraps around midnight.
+fn is_empty_window(start: u32, end: u32) -> bool {
+    start == end || (start == SECS_PER_DAY as u32 && end == 0)
+}
+
+fn secs_of_day((hours, minutes): (u8, u8)) -> Option<u32> {
+    (hours <= 24 && minutes < 60 && (hours < 24 || minutes == 0))
+        .then(|| hours as u32 * 3600 + minutes as u32 * 60)
+}
+
+fn parse_time(s: &str) -> Result<u32, OpaqueError> {
+    let (hours, minutes) = s
+        .split_once(':')
+        .ok_or_else(|| OpaqueError::from_display(format!("invalid time: {s}")))?;
+    let hours = hours.parse().context("parse hours")?;
+    let minutes = minutes.parse().context("parse minutes")?;
+    secs_of_day((hours, minutes))
+        .ok_or_else(|| OpaqueError::from_display(format!("invalid time: {s}")))
+}
+
+impl FromStr for TimeWindow {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (days, times) = match s.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (Some(days.trim()), times),
+            None => (None, s),
+        };
+
+        let mut window_days = Vec::new();
+        for day in days.into_iter().flat_map(|days| days.split(',')) {
+            match day.split_once('-') {
+                Some((first, last)) => {
+                    let first = first.trim().parse::<Weekday>()? as usize;
+                    let last = last.trim().parse::<Weekday>()? as usize;
+                    let mut index = first;
+                    loop {
+                        window_days.push(Weekday::ALL[index]);
+                        if index == last {
+                            break;
+                        }
+                        index = (index + 1) % 7;
+                    }
+                }
+                None => window_days.push(day.trim().parse()?),
+            }
+        }
+
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| OpaqueError::from_display(format!("invalid time window: {s}")))?;
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if is_empty_window(start, end) {
+            return Err(OpaqueError::from_display(format!(
+                "empty time window: {s} (use 00:00-24:00 for the full day)"
+            )));
+        }
+        Ok(Self {
+            days: window_days,
+            start,
+            end,
+        })
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, day) in self.days.iter().enumerate() {
+            let sep = if index + 1 == self.days.len() {
+                " "
+            } else {
+                ","
+            };
+            write!(f, "{day}{sep}")?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 3600,
+            self.start % 3600 / 60,
+            self.end / 3600,
+            self.end % 3600 / 60
+        )
+    }
+}
+
+impl Serialize for TimeWindow {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeWindow {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,369 @@
+use super::Authority;
+use crate::user::{Basic, UserId, credentials::constant_time_eq, reload::ReloadingFile};
+use base64::Engine as _;
+use rama_core::context::Extensions;
+use rama_core::error::OpaqueError;
+use rama_core::username::{UsernameLabelParser, parse_username};
+use sha1::Digest as _;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// An [`Authority`] for [`Basic`] credentials, backed by an Apache htpasswd file.
+///
+/// The following password formats are supported:
+///
+/// - bcrypt (`$2y$`, `$2b$`, `$2a$`), the default of `htpasswd -B`;
+/// - SHA-crypt (`$5$` and `$6$`), as generated by `mkpasswd`;
+/// - apr1-MD5 (`$apr1$`), the default of `htpasswd`;
+/// - SHA-1 (`{SHA}`), as generated by `htpasswd -s`.
+///
+/// Entries using any other format (e.g. `crypt(3)` or plain text) are ignored.
+///
+/// The file is checked for changes at most once per check interval (5 seconds by default),
+/// and reloaded when it was modified. The previously loaded users remain in use when
+/// the file cannot be (re)loaded. Clones share the loaded users.
+///
+/// Passwords are verified in constant time on the blocking thread pool,
+/// as the computation of (most) password hashes is expensive by design.
+/// The password of an unknown user is verified against the password hash of the first user
+/// in the file (always failing), such that it takes as long as for a known user
+/// and the response time does not reveal which users exist.
+///
+/// The username labels are parsed by the [`UsernameLabelParser`] of the layer using it,
+/// the same as for the [`Authority`] implementations of [`Basic`] credentials.
+#[derive(Clone)]
+pub struct HtpasswdAuthority {
+    file: Arc<ReloadingFile<HtpasswdUsers>>,
+    check_interval: Duration,
+}
+
+#[derive(Debug, Default)]
+struct HtpasswdUsers {
+    users: HashMap<String, PasswordHash>,
+    /// Verified for unknown users, in order to not reveal which users exist.
+    dummy: Option<PasswordHash>,
+}
+
+impl HtpasswdAuthority {
+    /// Open the htpasswd file at the given path, loading its users.
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self, OpaqueError> {
+        let file = ReloadingFile::open("htpasswd file", path.into(), |content| {
+            Ok(parse_htpasswd(content))
+        })
+        .await?;
+        Ok(Self {
+            file: Arc::new(file),
+            check_interval: Duration::from_secs(5),
+        })
+    }
+
+    /// Set the minimum duration between two checks for changes of the file,
+    /// 5 seconds by default.
+    pub fn with_check_interval(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Set the minimum duration between two checks for changes of the file,
+    /// 5 seconds by default.
+    pub fn set_check_interval(&mut self, interval: Duration) -> &mut Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// View the path of the htpasswd file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+impl fmt::Debug for HtpasswdAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HtpasswdAuthority")
+            .field("path", &self.path())
+            .field("check_interval", &self.check_interval)
+            .finish()
+    }
+}
+
+impl<L: UsernameLabelParser> Authority<Basic, L> for HtpasswdAuthority {
+    async fn authorized(&self, credentials: Basic) -> Option<Extensions> {
+        let mut ext = Extensions::new();
+        let username = match parse_username(&mut ext, L::default(), credentials.username()) {
+            Ok(username) => username,
+            Err(err) => {
+                tracing::trace!("failed to parse username: {:?}", err);
+                ext = Extensions::new();
+                credentials.username().to_owned()
+            }
+        };
+
+        let users = self.file.get(self.check_interval).await;
+        let (hash, known) = match users.users.get(&username) {
+            Some(hash) => (hash.clone(), true),
+            None => {
+                tracing::trace!("htpasswd: unknown user");
+                (users.dummy.clone()?, false)
+            }
+        };
+
+        let password = credentials.password().to_owned();
+        let verified = tokio::task::spawn_blocking(move || hash.verify(&password))
+            .await
+            .unwrap_or_else(|err| {
+                tracing::error!(?err, "htpasswd: failed to verify password");
+                false
+            });
+        if !(verified && known) {
+            return None;
+        }
+
+        ext.insert(UserId::Username(username));
+        Some(ext)
+    }
+}
+
+fn parse_htpasswd(content: &str) -> HtpasswdUsers {
+    let mut users = HtpasswdUsers::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((username, hash)) = line.split_once(':') else {
+            tracing::warn!(line = index + 1, "htpasswd: ignore line without password");
+            continue;
+        };
+        match PasswordHash::parse(hash) {
+            Some(hash) => {
+                if users.dummy.is_none() {
+                    users.dummy = Some(hash.clone());
+                }
+                users.users.insert(username.to_owned(), hash);
+            }
+            None => tracing::warn!(
+                line = index + 1,
+                "htpasswd: ignore user with unsupported password format"
+            ),
+        }
+    }
+    users
+}
+
+#[derive(Clone)]
+enum PasswordHash {
+    Bcrypt(Arc<str>),
+    Sha256Crypt(Arc<str>),
+    Sha512Crypt(Arc<str>),
+    Apr1 { salt: Arc<str>, hash: Arc<str> },
+    Sha1([u8; 20]),
+}
+
+impl fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the hashes themselves
+        f.write_str(match self {
+            Self::Bcrypt(_) => "Bcrypt",
+            Self::Sha256Crypt(_) => "Sha256Crypt",
+            Self::Sha512Crypt(_) => "Sha512Crypt",
+            Self::Apr1 { .. } => "Apr1",
+            Self::Sha1(_) => "Sha1",
+        })
+    }
+}
+
+impl PasswordHash {
+    fn parse(s: &str) -> Option<Self> {
+        if ["$2y$", "$2b$", "$2a$"].iter().any(|p| s.starts_with(p)) {
+            Some(Self::Bcrypt(s.into()))
+        } else if s.starts_with("$5$") {
+            Some(Self::Sha256Crypt(s.into()))
+        } else if s.starts_with("$6$") {
+            Some(Self::Sha512Crypt(s.into()))
+        } else if let Some(rest) = s.strip_prefix(APR1_MAGIC) {
+            let (salt, hash) = rest.split_once('$')?;
+            (salt.len() <= 8 && hash.len() == 22).then(|| Self::Apr1 {
+                salt: salt.into(),
+                hash: hash.into(),
+            })
+        } else if let Some(hash) = s.strip_prefix("{SHA}") {
+            let hash = base64::engine::general_purpose::STANDARD
+                .decode(hash)
+                .ok()?;
+            hash.try_into().ok().map(Self::Sha1)
+        } else {
+            None
+        }
+    }
+
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or_default(),
+            Self::Sha256Crypt(hash) => sha_crypt::sha256_check(password, hash).is_ok(),
+            Self::Sha512Crypt(hash) => sha_crypt::sha512_check(password, hash).is_ok(),
+            Self::Apr1 { salt, hash } => {
+                constant_time_eq(apr1_hash(password, salt).as_bytes(), hash.as_bytes())
+            }
+            Self::Sha1(hash) => constant_time_eq(&sha1::Sha1::digest(password)[..], hash),
+        }
+    }
+}
+
+const APR1_MAGIC: &str = "$apr1$";
+
+/// Computes the apr1 variant of the md5-crypt password hash,
+/// returning only the (encoded) hash part.
+fn apr1_hash(password: &str, salt: &str) -> String {
+    let password = password.as_bytes();
+    let salt = salt.as_bytes();
+
+    let mut ctx = md5::Context::new();
+    ctx.consume(password);
+    ctx.consume(APR1_MAGIC);
+    ctx.consume(salt);
+
+    let mut alt = md5::Context::new();
+    alt.consume(password);
+    alt.consume(salt);
+    alt.consume(password);
+    let alt = alt.compute();
+
+    for chunk in password.chunks(16) {
+        ctx.consume(&alt[..chunk.len()]);
+    }
+    let mut i = password.len();
+    while i > 0 {
+        if i & 1 == 1 {
+            ctx.consume([0]);
+        } else {
+            ctx.consume(&password[..1]);
+        }
+        i >>= 1;
+    }
+    let mut hash = ctx.compute().0;
+
+    for round in 0..1000 {
+        let mut ctx = md5::Context::new();
+        if round & 1 == 1 {
+            ctx.consume(password);
+        } else {
+            ctx.consume(hash);
+        }
+        if round % 3 != 0 {
+            ctx.consume(salt);
+        }
+        if round % 7 != 0 {
+            ctx.consume(password);
+        }
+        if round & 1 == 1 {
+            ctx.consume(hash);
+        } else {
+            ctx.consume(password);
+        }
+        hash = ctx.compute().0;
+    }
+
+    const ITOA64: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut encoded = String::with_capacity(22);
+    let mut encode = |mut value: u32, n: usize| {
+        for _ in 0..n {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        encode(
+            (hash[a] as u32) << 16 | (hash[b] as u32) << 8 | hash[c] as u32,
+            4,
+        );
+    }
+    encode(hash[11] as u32, 2);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::username::{UsernameLabels, UsernameOpaqueLabelParser};
+
+    // examples of https://httpd.apache.org/docs/2.4/misc/password_encryptions.html,
+    // all for the password "myPassword"
+    const HTPASSWD: &str = "\
+# proxy users
+bcrypt:$2y$05$c4WoMPo3SXsafkva.HHa6uXQZWr7oboPiC2bT/r7q1BB8I2s0BRqC
+apr1:$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/
+sha1:{SHA}VBPuJHI7uixaa6LQGWx4s+5GKNE=
+sha512:$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1
+crypt:rqXexS6ZhobKA
+";
+
+    #[test]
+    fn parse_htpasswd_entries() {
+        let HtpasswdUsers { users, dummy } = parse_htpasswd(HTPASSWD);
+        assert_eq!(users.len(), 4);
+        assert!(matches!(users["bcrypt"], PasswordHash::Bcrypt(_)));
+        assert!(matches!(users["apr1"], PasswordHash::Apr1 { .. }));
+        assert!(matches!(users["sha1"], PasswordHash::Sha1(_)));
+        assert!(matches!(users["sha512"], PasswordHash::Sha512Crypt(_)));
+        assert!(!users.contains_key("crypt"));
+        assert!(matches!(dummy, Some(PasswordHash::Bcrypt(_))));
+    }
+
+    #[test]
+    fn verify_password_hashes() {
+        let users = parse_htpasswd(HTPASSWD).users;
+        for user in ["apr1", "sha1", "bcrypt"] {
+            assert!(users[user].verify("myPassword"), "user: {user}");
+            assert!(!users[user].verify("myPassword2"), "user: {user}");
+        }
+        // test vector of the SHA-crypt specification
+        assert!(users["sha512"].verify("Hello world!"));
+        assert!(!users["sha512"].verify("myPassword"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn htpasswd_authority_with_labels_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".htpasswd");
+        tokio::fs::write(&path, HTPASSWD).await.unwrap();
+
+        let authority = HtpasswdAuthority::open(&path)
+            .await
+            .unwrap()
+            .with_check_interval(Duration::ZERO);
+        let authorized = |credentials| {
+            Authority::<_, UsernameOpaqueLabelParser>::authorized(&authority, credentials)
+        };
+
+        assert!(authorized(Basic::new("apr1", "wrong")).await.is_none());
+        assert!(
+            authorized(Basic::new("unknown", "myPassword"))
+                .await
+                .is_none()
+        );
+
+        let ext = authorized(Basic::new("apr1-green-red", "myPassword"))
+            .await
+            .unwrap();
+        let user: &UserId = ext.get().unwrap();
+        assert_eq!(user, "apr1");
+        let labels: &UsernameLabels = ext.get().unwrap();
+        assert_eq!(&labels.0, &vec!["green".to_owned(), "red".to_owned()]);
+
+        // users are reloaded when the file changes
+        tokio::fs::write(&path, "john:{SHA}VBPuJHI7uixaa6LQGWx4s+5GKNE=\n")
+            .await
+            .unwrap();
+        assert!(authorized(Basic::new("john", "myPassword")).await.is_some());
+        assert!(authorized(Basic::new("apr1", "myPassword")).await.is_none());
+
+        // loaded users remain in use if the file can no longer be read
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(authorized(Basic::new("john", "myPassword")).await.is_some());
+    }
+}
@@ -0,0 +1,755 @@
+//! types and utilities for authorising users.
+
+use crate::user::credentials::constant_time_eq;
+use crate::user::{Basic, Digest, DigestAlgorithm, DigestChallenge, UserId};
+use rama_core::context::Extensions;
+use rama_core::username::{UsernameLabelParser, parse_username};
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// TODO: decouple this from http
+use rama_http_types::{HeaderValue, Method, Uri, headers::authorization::Credentials};
+
+#[cfg(feature = "htpasswd")]
+mod htpasswd;
+#[cfg(feature = "htpasswd")]
+#[doc(inline)]
+pub use htpasswd::HtpasswdAuthority;
+
+/// The `Authority` trait is used to determine if a set of [`Credential`]s are authorized.
+///
+/// [`Credential`]: headers::authorization::Credentials
+pub trait Authority<C, L>: Send + Sync + 'static {
+    /// Returns `true` if the credentials are authorized, otherwise `false`.
+    fn authorized(&self, credentials: C) -> impl Future<Output = Option<Extensions>> + Send + '_;
+
+    /// Same as [`Authority::authorized`], but for credentials received
+    /// as part of a request with the given method and uri.
+    ///
+    /// This is required for credentials bound to the request, such as [`Digest`],
+    /// and defaults to [`Authority::authorized`].
+    fn authorized_request(
+        &self,
+        credentials: C,
+        method: &Method,
+        uri: &Uri,
+    ) -> impl Future<Output = Option<Extensions>> + Send {
+        let _ = (method, uri);
+        self.authorized(credentials)
+    }
+
+    /// Returns the challenge to send to the client when it is not authorized,
+    /// given the rejected credentials (if any) of the request with the given method,
+    /// defaulting to the scheme of the credentials when `None` is returned.
+    fn challenge(&self, credentials: Option<&C>, method: &Method) -> Option<HeaderValue> {
+        let _ = (credentials, method);
+        None
+    }
+}
+
+/// A synchronous version of [`Authority`], to be used for primitive implementations.
+///
+/// The username labels are parsed using the [`UsernameLabelParser`] `L`
+/// of the [`Authority`] implemented for it.
+pub trait AuthoritySync<C>: Send + Sync + 'static {
+    /// Returns `true` if the credentials are authorized, otherwise `false`.
+    fn authorized<L: UsernameLabelParser>(&self, ext: &mut Extensions, credentials: &C) -> bool;
+
+    /// Same as [`AuthoritySync::authorized`], but for credentials received
+    /// as part of a request with the given method and uri.
+    ///
+    /// This is required for credentials bound to the request, such as [`Digest`],
+    /// and defaults to [`AuthoritySync::authorized`].
+    fn authorized_request<L: UsernameLabelParser>(
+        &self,
+        ext: &mut Extensions,
+        credentials: &C,
+        method: &Method,
+        uri: &Uri,
+    ) -> bool {
+        let _ = (method, uri);
+        self.authorized::<L>(ext, credentials)
+    }
+
+    /// Returns the challenge to send to the client when it is not authorized,
+    /// given the rejected credentials (if any) of the request with the given method,
+    /// defaulting to the scheme of the credentials when `None` is returned.
+    fn challenge<L: UsernameLabelParser>(
+        &self,
+        credentials: Option<&C>,
+        method: &Method,
+    ) -> Option<HeaderValue> {
+        let _ = (credentials, method);
+        None
+    }
+}
+
+impl<A, C, L> Authority<C, L> for A
+where
+    A: AuthoritySync<C>,
+    C: Credentials + Send + 'static,
+    L: UsernameLabelParser,
+{
+    async fn authorized(&self, credentials: C) -> Option<Extensions> {
+        let mut ext = Extensions::new();
+        if AuthoritySync::authorized::<L>(self, &mut ext, &credentials) {
+            Some(ext)
+        } else {
+            None
+        }
+    }
+
+    async fn authorized_request(
+        &self,
+        credentials: C,
+        method: &Method,
+        uri: &Uri,
+    ) -> Option<Extensions> {
+        let mut ext = Extensions::new();
+        if AuthoritySync::authorized_request::<L>(self, &mut ext, &credentials, method, uri) {
+            Some(ext)
+        } else {
+            None
+        }
+    }
+
+    fn challenge(&self, credentials: Option<&C>, method: &Method) -> Option<HeaderValue> {
+        AuthoritySync::challenge::<L>(self, credentials, method)
+    }
+}
+
+impl AuthoritySync<Basic> for Basic {
+    fn authorized<L: UsernameLabelParser>(
+        &self,
+        ext: &mut Extensions,
+        credentials: &Basic,
+    ) -> bool {
+        let username = credentials.username();
+        let password = credentials.password();
+
+        if password != self.password() {
+            return false;
+        }
+
+        let mut parser_ext = Extensions::new();
+        let username = match parse_username(&mut parser_ext, L::default(), username) {
+            Ok(t) => t,
+            Err(err) => {
+                tracing::trace!("failed to parse username: {:?}", err);
+                return if self == credentials {
+                    ext.insert(UserId::Username(username.to_owned()));
+                    true
+                } else {
+                    false
+                };
+            }
+        };
+
+        if username != self.username() {
+            return false;
+        }
+
+        ext.extend(parser_ext);
+        ext.insert(UserId::Username(username));
+        true
+    }
+}
+
+impl<C, T, const N: usize> AuthoritySync<C> for [T; N]
+where
+    C: Credentials + Send + 'static,
+    T: AuthoritySync<C>,
+{
+    fn authorized<L: UsernameLabelParser>(&self, ext: &mut Extensions, credentials: &C) -> bool {
+        self.iter().any(|t| t.authorized::<L>(ext, credentials))
+    }
+
+    fn authorized_request<L: UsernameLabelParser>(
+        &self,
+        ext: &mut Extensions,
+        credentials: &C,
+        method: &Method,
+        uri: &Uri,
+    ) -> bool {
+        self.iter()
+            .any(|t| t.authorized_request::<L>(ext, credentials, method, uri))
+    }
+
+    fn challenge<L: UsernameLabelParser>(
+        &self,
+        credentials: Option<&C>,
+        method: &Method,
+    ) -> Option<HeaderValue> {
+        self.first()
+            .and_then(|t| t.challenge::<L>(credentials, method))
+    }
+}
+
+impl<C, T> AuthoritySync<C> for Vec<T>
+where
+    C: Credentials + Send + 'static,
+    T: AuthoritySync<C>,
+{
+    fn authorized<L: UsernameLabelParser>(&self, ext: &mut Extensions, credentials: &C) -> bool {
+        self.iter().any(|t| t.authorized::<L>(ext, credentials))
+    }
+
+    fn authorized_request<L: UsernameLabelParser>(
+        &self,
+        ext: &mut Extensions,
+        credentials: &C,
+        method: &Method,
+        uri: &Uri,
+    ) -> bool {
+        self.iter()
+            .any(|t| t.authorized_request::<L>(ext, credentials, method, uri))
+    }
+
+    fn challenge<L: UsernameLabelParser>(
+        &self,
+        credentials: Option<&C>,
+        method: &Method,
+    ) -> Option<HeaderValue> {
+        self.first()
+            .and_then(|t| t.challenge::<L>(credentials, method))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An [`Authority`] for [`Digest`] credentials, as defined in [RFC 7616].
+///
+/// It generates the challenges (with `qop=auth`) for its realm. Nonces are stateless:
+/// each nonce contains the time it was issued, authenticated using an HMAC with a secret
+/// key of this authority, such that unauthorized requests do not require any state.
+/// Credentials are only authorized for a nonce issued by this authority which is not yet expired.
+/// Requests answering an expired nonce with valid credentials are challenged with `stale=true`,
+/// such that clients can retry using the new nonce without asking the user for credentials again.
+///
+/// Once a nonce is used by an authorized user its nonce count is tracked, only authorizing
+/// credentials with a nonce count higher than the one previously used for that nonce,
+/// protecting against replay attacks.
+///
+/// Clones share the same secret key and nonce counts.
+///
+/// [RFC 7616]: https://datatracker.ietf.org/doc/html/rfc7616
+pub struct DigestAuthority {
+    realm: Arc<str>,
+    users: Arc<[Basic]>,
+    algorithm: DigestAlgorithm,
+    nonce_lifetime: Duration,
+    max_nonces: usize,
+    nonces: Arc<NonceStore>,
+}
+
+struct NonceStore {
+    key: [u8; 32],
+    epoch: Instant,
+    counts: Mutex<NonceCounts>,
+}
+
+impl fmt::Debug for NonceStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NonceStore")
+            .field("epoch", &self.epoch)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default)]
+struct NonceCounts {
+    /// Last nonce count used per nonce.
+    nc: HashMap<String, u32>,
+    /// Tracked nonces (and their issue time), in the order they were first used.
+    order: VecDeque<(u64, String)>,
+    /// Nonces issued up to this time (in ms since the epoch) might have been
+    /// evicted, and are thus no longer accepted when not tracked.
+    evicted_until: Option<u64>,
+}
+
+/// Result of the validation of a nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NonceValidity {
+    Valid(u64),
+    Expired,
+    Invalid,
+}
+
+impl DigestAuthority {
+    /// Creates a new [`DigestAuthority`] for the given realm, authorizing the given users.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the realm contains non visible ASCII characters.
+    pub fn new(realm: impl Into<String>, users: impl IntoIterator<Item = Basic>) -> Self {
+        let realm = realm.into();
+        assert!(
+            DigestChallenge::new(realm.as_str(), "", DigestAlgorithm::default()).is_ok(),
+            "digest realm is not a valid header value"
+        );
+        Self {
+            realm: realm.into(),
+            users: users.into_iter().collect(),
+            algorithm: DigestAlgorithm::default(),
+            nonce_lifetime: Duration::from_secs(300),
+            max_nonces: 4096,
+            nonces: Arc::new(NonceStore {
+                key: rand::random(),
+                epoch: Instant::now(),
+                counts: Default::default(),
+            }),
+        }
+    }
+
+    /// Set the [`DigestAlgorithm`] used in the challenges and required for the credentials.
+    ///
+    /// Defaults to [`DigestAlgorithm::Md5`] as it is the only algorithm supported by most clients.
+    pub fn with_algorithm(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the [`DigestAlgorithm`] used in the challenges and required for the credentials.
+    ///
+    /// Defaults to [`DigestAlgorithm::Md5`] as it is the only algorithm supported by most clients.
+    pub fn set_algorithm(&mut self, algorithm: DigestAlgorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the duration for which a nonce can be used, 5 minutes by default.
+    pub fn with_nonce_lifetime(mut self, lifetime: Duration) -> Self {
+        self.nonce_lifetime = lifetime;
+        self
+    }
+
+    /// Set the duration for which a nonce can be used, 5 minutes by default.
+    pub fn set_nonce_lifetime(&mut self, lifetime: Duration) -> &mut Self {
+        self.nonce_lifetime = lifetime;
+        self
+    }
+
+    /// Set the maximum amount of nonces for which the nonce count is tracked at once, 4096 by default.
+    ///
+    /// The nonce first used the longest ago is forgotten when a new nonce is used while at capacity,
+    /// after which nonces issued before it are answered with a stale challenge.
+    pub fn with_max_nonces(mut self, max: usize) -> Self {
+        self.max_nonces = max.max(1);
+        self
+    }
+
+    /// Set the maximum amount of nonces for which the nonce count is tracked at once, 4096 by default.
+    ///
+    /// The nonce first used the longest ago is forgotten when a new nonce is used while at capacity,
+    /// after which nonces issued before it are answered with a stale challenge.
+    pub fn set_max_nonces(&mut self, max: usize) -> &mut Self {
+        self.max_nonces = max.max(1);
+        self
+    }
+
+    /// View the realm of this authority.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Create a new [`DigestChallenge`], using a fresh nonce.
+    pub fn new_challenge(&self) -> DigestChallenge {
+        let issued = self.nonces.epoch.elapsed().as_millis() as u64;
+        let salt: u64 = rand::random();
+        let nonce = format!("{issued:016x}{salt:016x}{}", self.nonce_mac(issued, salt));
+        DigestChallenge::new(self.realm.as_ref(), nonce, self.algorithm)
+            .expect("realm and nonce are valid")
+    }
+
+    /// The (hex encoded) HMAC of a nonce, binding it to this authority and its realm.
+    fn nonce_mac(&self, issued: u64, salt: u64) -> String {
+        let mut message = Vec::with_capacity(16 + self.realm.len());
+        message.extend_from_slice(&issued.to_be_bytes());
+        message.extend_from_slice(&salt.to_be_bytes());
+        message.extend_from_slice(self.realm.as_bytes());
+        hex::encode(&hmac_sha256(&self.nonces.key, &message)[..16])
+    }
+
+    fn validate_nonce(&self, nonce: &str) -> NonceValidity {
+        let (Some(issued), Some(salt), Some(mac)) = (
+            nonce
+                .get(..16)
+                .and_then(|s| u64::from_str_radix(s, 16).ok()),
+            nonce
+                .get(16..32)
+                .and_then(|s| u64::from_str_radix(s, 16).ok()),
+            nonce.get(32..),
+        ) else {
+            return NonceValidity::Invalid;
+        };
+        if !constant_time_eq(mac.as_bytes(), self.nonce_mac(issued, salt).as_bytes()) {
+            return NonceValidity::Invalid;
+        }
+        let now = self.nonces.epoch.elapsed().as_millis() as u64;
+        if now.saturating_sub(issued) >= self.nonce_lifetime.as_millis() as u64 {
+            NonceValidity::Expired
+        } else {
+            NonceValidity::Valid(issued)
+        }
+    }
+
+    /// Returns `true` if the nonce was issued by this authority,
+    /// but expired or is no longer tracked.
+    fn is_stale_nonce(&self, nonce: &str) -> bool {
+        match self.validate_nonce(nonce) {
+            NonceValidity::Expired => true,
+            NonceValidity::Invalid => false,
+            NonceValidity::Valid(issued) => {
+                let counts = self
+                    .nonces
+                    .counts
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                !counts.nc.contains_key(nonce)
+                    && counts.evicted_until.is_some_and(|until| issued <= until)
+            }
+        }
+    }
+
+    /// Verify the credentials against the users of this authority,
+    /// returning the (parsed) username and the extensions of its parsed labels.
+    fn verify<L: UsernameLabelParser>(
+        &self,
+        credentials: &Digest,
+        method: &Method,
+    ) -> Option<(String, Extensions)> {
+        let mut parser_ext = Extensions::new();
+        let username = match parse_username(&mut parser_ext, L::default(), credentials.username()) {
+            Ok(username) => username,
+            Err(err) => {
+                tracing::trace!("failed to parse username: {:?}", err);
+                parser_ext = Extensions::new();
+                credentials.username().to_owned()
+            }
+        };
+
+        let user = self.users.iter().find(|user| user.username() == username)?;
+        credentials
+            .verify(user.password(), method.as_str())
+            .then_some((username, parser_ext))
+    }
+
+    /// Track the nonce count of a valid nonce, returning `false` in case
+    /// it is not higher than the nonce count previously used for that nonce.
+    fn track_nonce_count(&self, nonce: &str, issued: u64, nc: u32) -> bool {
+        let mut counts = self
+            .nonces
+            .counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(last) = counts.nc.get_mut(nonce) {
+            if nc <= *last {
+                tracing::trace!("digest credentials replay a nonce count");
+                return false;
+            }
+            *last = nc;
+            return true;
+        }
+        if counts.evicted_until.is_some_and(|until| issued <= until) {
+            tracing::trace!("digest credentials use a nonce which is no longer tracked");
+            return false;
+        }
+
+        // forget the nonces which expired, or the one used first when at capacity
+        let now = self.nonces.epoch.elapsed().as_millis() as u64;
+        let lifetime = self.nonce_lifetime.as_millis() as u64;
+        while let Some((front_issued, _)) = counts.order.front() {
+            let expired = now.saturating_sub(*front_issued) >= lifetime;
+            if !expired && counts.order.len() < self.max_nonces {
+                break;
+            }
+            let Some((front_issued, front)) = counts.order.pop_front() else {
+                break;
+            };
+            counts.nc.remove(&front);
+            if !expired {
+                counts.evicted_until = counts.evicted_until.max(Some(front_issued));
+            }
+        }
+
+        counts.nc.insert(nonce.to_owned(), nc);
+        counts.order.push_back((issued, nonce.to_owned()));
+        true
+    }
+}
+
+impl AuthoritySync<Digest> for DigestAuthority {
+    fn authorized<L: UsernameLabelParser>(
+        &self,
+        _ext: &mut Extensions,
+        _credentials: &Digest,
+    ) -> bool {
+        tracing::trace!("digest credentials can only be authorized for a request");
+        false
+    }
+
+    fn authorized_request<L: UsernameLabelParser>(
+        &self,
+        ext: &mut Extensions,
+        credentials: &Digest,
+        method: &Method,
+        uri: &Uri,
+    ) -> bool {
+        if credentials.realm() != self.realm.as_ref()
+            || credentials.algorithm() != self.algorithm
+            || credentials.qop() != Some("auth")
+        {
+            tracing::trace!("digest credentials do not match the challenge");
+            return false;
+        }
+        let Some(nc) = credentials.nc() else {
+            return false;
+        };
+
+        let request_uri = uri.to_string();
+        if credentials.uri() != request_uri
+            && Some(credentials.uri()) != uri.path_and_query().map(|pq| pq.as_str())
+        {
+            tracing::trace!("digest credentials uri does not match the request uri");
+            return false;
+        }
+
+        let issued = match self.validate_nonce(credentials.nonce()) {
+            NonceValidity::Valid(issued) => issued,
+            NonceValidity::Expired => {
+                tracing::trace!("digest credentials use an expired nonce");
+                return false;
+            }
+            NonceValidity::Invalid => {
+                tracing::trace!("digest credentials use an unknown nonce");
+                return false;
+            }
+        };
+
+        let Some((username, parser_ext)) = self.verify::<L>(credentials, method) else {
+            return false;
+        };
+        if !self.track_nonce_count(credentials.nonce(), issued, nc) {
+            return false;
+        }
+
+        ext.extend(parser_ext);
+        ext.insert(UserId::Username(username));
+        true
+    }
+
+    fn challenge<L: UsernameLabelParser>(
+        &self,
+        credentials: Option<&Digest>,
+        method: &Method,
+    ) -> Option<HeaderValue> {
+        let stale = credentials.is_some_and(|credentials| {
+            self.is_stale_nonce(credentials.nonce())
+                && self.verify::<L>(credentials, method).is_some()
+        });
+        Some(self.new_challenge().with_stale(stale).as_header_value())
+    }
+}
+
+/// HMAC-SHA256 as defined in [RFC 2104](https://datatracker.ietf.org/doc/html/rfc2104).
+fn hmac_sha256(key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut inner_pad = [0x36; BLOCK_SIZE];
+    let mut outer_pad = [0x5c; BLOCK_SIZE];
+    for (i, k) in key.iter().enumerate() {
+        inner_pad[i] ^= k;
+        outer_pad[i] ^= k;
+    }
+    let inner = Sha256::new()
+        .chain_update(inner_pad)
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(outer_pad)
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::user::Basic;
+    use rama_core::username::{UsernameLabels, UsernameOpaqueLabelParser};
+
+    #[tokio::test]
+    async fn basic_authorization() {
+        let auth = Basic::new("Aladdin", "open sesame");
+        let auths = vec![Basic::new("foo", "bar"), auth.clone()];
+        let ext = Authority::<_, ()>::authorized(&auths, auth).await.unwrap();
+        let user: &UserId = ext.get().unwrap();
+        assert_eq!(user, "Aladdin");
+    }
+
+    #[tokio::test]
+    async fn basic_authorization_with_labels_found() {
+        let auths = vec![Basic::new("foo", "bar"), Basic::new("john", "secret")];
+
+        let ext = Authority::<_, UsernameOpaqueLabelParser>::authorized(
+            &auths,
+            Basic::new("john-green-red", "secret"),
+        )
+        .await
+        .unwrap();
+
+        let c: &UserId = ext.get().unwrap();
+        assert_eq!(c, "john");
+
+        let labels: &UsernameLabels = ext.get().unwrap();
+        assert_eq!(&labels.0, &vec!["green".to_owned(), "red".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn basic_authorization_with_labels_not_found() {
+        let auth = Basic::new("john", "secret");
+        let auths = vec![Basic::new("foo", "bar"), auth.clone()];
+
+        let ext = Authority::<_, UsernameOpaqueLabelParser>::authorized(&auths, auth)
+            .await
+            .unwrap();
+
+        let c: &UserId = ext.get().unwrap();
+        assert_eq!(c, "john");
+
+        assert!(ext.get::<UsernameLabels>().is_none());
+    }
+
+    #[tokio::test]
+    async fn digest_authorization() {
+        let authority = DigestAuthority::new("test", [Basic::new("john", "secret")]);
+        let challenge = authority.new_challenge();
+        let uri: Uri = "/foo?bar".parse().unwrap();
+
+        let authorize = |username, password, method: Method, nc| {
+            let credentials = Digest::respond(
+                &challenge,
+                username,
+                password,
+                method.as_str(),
+                "/foo?bar",
+                nc,
+            )
+            .unwrap();
+            let authority = authority.clone();
+            let uri = uri.clone();
+            async move {
+                Authority::<_, UsernameOpaqueLabelParser>::authorized_request(
+                    &authority,
+                    credentials,
+                    &method,
+                    &uri,
+                )
+                .await
+            }
+        };
+
+        assert!(authorize("john", "wrong", Method::GET, 1).await.is_none());
+        assert!(authorize("jane", "secret", Method::GET, 1).await.is_none());
+        // credentials are bound to the method
+        let credentials =
+            Digest::respond(&challenge, "john", "secret", "POST", "/foo?bar", 1).unwrap();
+        assert!(
+            Authority::<_, ()>::authorized_request(&authority, credentials, &Method::GET, &uri)
+                .await
+                .is_none()
+        );
+
+        let ext = authorize("john-green", "secret", Method::GET, 1)
+            .await
+            .unwrap();
+        let user: &UserId = ext.get().unwrap();
+        assert_eq!(user, "john");
+        let labels: &UsernameLabels = ext.get().unwrap();
+        assert_eq!(&labels.0, &vec!["green".to_owned()]);
+
+        // replayed nonce count
+        assert!(authorize("john", "secret", Method::GET, 1).await.is_none());
+        assert!(authorize("john", "secret", Method::GET, 2).await.is_some());
+
+        // unknown nonce
+        let unknown = DigestChallenge::new("test", "unknown", DigestAlgorithm::Md5).unwrap();
+        let credentials =
+            Digest::respond(&unknown, "john", "secret", "GET", "/foo?bar", 1).unwrap();
+        assert!(
+            Authority::<_, ()>::authorized_request(&authority, credentials, &Method::GET, &uri)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn digest_authorization_expired_nonce() {
+        let authority = DigestAuthority::new("test", [Basic::new("john", "secret")])
+            .with_algorithm(DigestAlgorithm::Sha256)
+            .with_nonce_lifetime(Duration::from_millis(10));
+        let challenge = authority.new_challenge();
+        assert_eq!(challenge.algorithm(), DigestAlgorithm::Sha256);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let credentials = Digest::respond(&challenge, "john", "secret", "GET", "/", 1).unwrap();
+        assert!(
+            Authority::<_, ()>::authorized_request(
+                &authority,
+                credentials.clone(),
+                &Method::GET,
+                &Uri::from_static("/")
+            )
+            .await
+            .is_none()
+        );
+
+        // valid credentials for an expired nonce are challenged as stale
+        let stale = |credentials: &Digest| {
+            let challenge =
+                Authority::<_, ()>::challenge(&authority, Some(credentials), &Method::GET).unwrap();
+            DigestChallenge::try_from_header_str(challenge.to_str().unwrap())
+                .unwrap()
+                .stale()
+        };
+        assert!(stale(&credentials));
+        let wrong = Digest::respond(&challenge, "john", "wrong", "GET", "/", 1).unwrap();
+        assert!(!stale(&wrong));
+        let challenge = authority.new_challenge();
+        let fresh = Digest::respond(&challenge, "john", "wrong", "GET", "/", 1).unwrap();
+        assert!(!stale(&fresh));
+    }
+
+    #[tokio::test]
+    async fn digest_authorization_tracks_nonce_counts_of_authorized_nonces() {
+        let authority =
+            DigestAuthority::new("test", [Basic::new("john", "secret")]).with_max_nonces(2);
+        let uri = Uri::from_static("/");
+        let authorize = |challenge: &DigestChallenge, password, nc| {
+            let credentials = Digest::respond(challenge, "john", password, "GET", "/", nc).unwrap();
+            Authority::<_, ()>::authorized_request(&authority, credentials, &Method::GET, &uri)
+        };
+
+        let challenges: Vec<_> = (0..3).map(|_| authority.new_challenge()).collect();
+        // challenges and unauthorized requests do not track nonces
+        assert!(authorize(&challenges[0], "wrong", 1).await.is_none());
+        assert!(authority.nonces.counts.lock().unwrap().nc.is_empty());
+
+        assert!(authorize(&challenges[0], "secret", 1).await.is_some());
+        assert!(authorize(&challenges[1], "secret", 1).await.is_some());
+        assert!(authorize(&challenges[1], "secret", 1).await.is_none());
+        // the nonce first used is evicted once at capacity
+        assert!(authorize(&challenges[2], "secret", 1).await.is_some());
+        assert_eq!(authority.nonces.counts.lock().unwrap().nc.len(), 2);
+        // such that it can no longer be used, nor can older ones, instead challenged as stale
+        assert!(authorize(&challenges[0], "secret", 2).await.is_none());
+        let credentials = Digest::respond(&challenges[0], "john", "secret", "GET", "/", 2).unwrap();
+        let challenge =
+            Authority::<_, ()>::challenge(&authority, Some(&credentials), &Method::GET).unwrap();
+        assert!(challenge.to_str().unwrap().contains("stale=true"));
+        assert!(authorize(&challenges[1], "secret", 2).await.is_some());
+        assert!(authorize(&challenges[2], "secret", 2).await.is_some());
+    }
+}
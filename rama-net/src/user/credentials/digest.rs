@@ -0,0 +1,702 @@
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::{HeaderValue, headers::authorization};
+use sha2::{Digest as _, Sha256, Sha512_256};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// Hash algorithm used by [`Digest`] authentication, as defined in
+/// [RFC 7616](https://datatracker.ietf.org/doc/html/rfc7616#section-3.3).
+pub enum DigestAlgorithm {
+    #[default]
+    /// MD5, the (legacy) default algorithm if none is specified.
+    Md5,
+    /// SHA-256
+    Sha256,
+    /// SHA-512/256
+    Sha512_256,
+}
+
+impl DigestAlgorithm {
+    /// Returns the name of this algorithm as used in the digest header parameters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+            Self::Sha512_256 => "SHA-512-256",
+        }
+    }
+
+    /// Try to parse a [`DigestAlgorithm`] from its (case-insensitive) name.
+    pub fn try_from_str(s: &str) -> Result<Self, OpaqueError> {
+        [Self::Md5, Self::Sha256, Self::Sha512_256]
+            .into_iter()
+            .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| OpaqueError::from_display("unsupported digest algorithm"))
+    }
+
+    /// Hash the given data, returning the lowercase hex encoded digest.
+    fn hash(&self, data: &str) -> String {
+        match self {
+            Self::Md5 => format!("{:x}", md5::compute(data)),
+            Self::Sha256 => hex::encode(Sha256::digest(data)),
+            Self::Sha512_256 => hex::encode(Sha512_256::digest(data)),
+        }
+    }
+}
+
+impl fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A digest challenge, as sent by a server in the `WWW-Authenticate`
+/// or `Proxy-Authenticate` header, to request [`Digest`] credentials.
+pub struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: DigestAlgorithm,
+    qop: Option<String>,
+    stale: bool,
+}
+
+impl DigestChallenge {
+    /// Creates a new [`DigestChallenge`], requesting `qop=auth` protection.
+    pub fn new(
+        realm: impl Into<String>,
+        nonce: impl Into<String>,
+        algorithm: DigestAlgorithm,
+    ) -> Result<Self, OpaqueError> {
+        let realm = realm.into();
+        let nonce = nonce.into();
+        check_param_value(&realm).context("digest challenge realm")?;
+        check_param_value(&nonce).context("digest challenge nonce")?;
+        Ok(Self {
+            realm,
+            nonce,
+            opaque: None,
+            algorithm,
+            qop: Some(QOP_AUTH.to_owned()),
+            stale: false,
+        })
+    }
+
+    /// Set the opaque value, which the client has to echo back unchanged.
+    pub fn with_opaque(mut self, opaque: impl Into<String>) -> Result<Self, OpaqueError> {
+        let opaque = opaque.into();
+        check_param_value(&opaque).context("digest challenge opaque")?;
+        self.opaque = Some(opaque);
+        Ok(self)
+    }
+
+    /// Mark the challenge as stale, signalling the client that
+    /// it can retry with the same credentials using the new nonce.
+    pub fn with_stale(mut self, stale: bool) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Mark the challenge as stale, signalling the client that
+    /// it can retry with the same credentials using the new nonce.
+    pub fn set_stale(&mut self, stale: bool) -> &mut Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Try to create a [`DigestChallenge`] from a header string,
+    /// encoded as `Digest realm="...", nonce="...", ...`.
+    pub fn try_from_header_str(s: impl AsRef<str>) -> Result<Self, OpaqueError> {
+        let params = parse_params(strip_scheme(s.as_ref())?)?;
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut algorithm = DigestAlgorithm::default();
+        let mut qop = None;
+        let mut stale = false;
+
+        for (key, value) in params {
+            match key.as_str() {
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => algorithm = DigestAlgorithm::try_from_str(&value)?,
+                "qop" => qop = Some(value),
+                "stale" => stale = value.eq_ignore_ascii_case("true"),
+                "userhash" if value.eq_ignore_ascii_case("true") => {
+                    return Err(OpaqueError::from_display(
+                        "digest challenge: userhash is not supported",
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            realm: realm.ok_or_else(|| OpaqueError::from_display("digest challenge: no realm"))?,
+            nonce: nonce.ok_or_else(|| OpaqueError::from_display("digest challenge: no nonce"))?,
+            opaque,
+            algorithm,
+            qop,
+            stale,
+        })
+    }
+
+    /// Serialize this [`DigestChallenge`] as a header string.
+    pub fn as_header_string(&self) -> String {
+        let mut s = format!(
+            "{DIGEST_SCHEME} realm={}, nonce={}",
+            quote(&self.realm),
+            quote(&self.nonce)
+        );
+        if let Some(qop) = &self.qop {
+            s.push_str(", qop=");
+            s.push_str(&quote(qop));
+        }
+        s.push_str(", algorithm=");
+        s.push_str(self.algorithm.as_str());
+        if let Some(opaque) = &self.opaque {
+            s.push_str(", opaque=");
+            s.push_str(&quote(opaque));
+        }
+        if self.stale {
+            s.push_str(", stale=true");
+        }
+        s
+    }
+
+    /// View this [`DigestChallenge`] as a [`HeaderValue`].
+    pub fn as_header_value(&self) -> HeaderValue {
+        let encoded = self.as_header_string();
+        // we validate the inner values upon creation
+        HeaderValue::from_str(&encoded).expect("inner value should always be valid")
+    }
+
+    /// View the realm of this challenge.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// View the nonce of this challenge.
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// View the opaque value of this challenge, if any.
+    pub fn opaque(&self) -> Option<&str> {
+        self.opaque.as_deref()
+    }
+
+    /// Returns the [`DigestAlgorithm`] requested by this challenge.
+    pub fn algorithm(&self) -> DigestAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns true if the previous nonce was rejected only because it expired.
+    pub fn stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Returns true if the challenge offers `qop=auth` protection.
+    pub fn supports_qop_auth(&self) -> bool {
+        self.qop
+            .as_deref()
+            .is_some_and(|qop| qop.split(',').any(|v| v.trim() == QOP_AUTH))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Digest credentials, as defined in [RFC 7616].
+///
+/// Contrary to [`Basic`] credentials these do not contain the password,
+/// but a response computed for a single [`DigestChallenge`] and request.
+/// Use [`Digest::respond`] to compute them as a client,
+/// and [`Digest::verify`] to verify them as a server.
+///
+/// [RFC 7616]: https://datatracker.ietf.org/doc/html/rfc7616
+/// [`Basic`]: super::Basic
+pub struct Digest {
+    username: String,
+    realm: String,
+    nonce: String,
+    uri: String,
+    response: String,
+    algorithm: DigestAlgorithm,
+    cnonce: Option<String>,
+    opaque: Option<String>,
+    qop: Option<String>,
+    nc: Option<u32>,
+}
+
+impl Digest {
+    /// Compute the [`Digest`] credentials answering the given challenge
+    /// for a request with the given method and uri (as used in the request line).
+    ///
+    /// The nonce count (`nc`) has to be incremented by the caller
+    /// for each request made using the same challenge nonce, starting at 1.
+    pub fn respond(
+        challenge: &DigestChallenge,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        nc: u32,
+    ) -> Result<Self, OpaqueError> {
+        let cnonce = hex::encode(rand::random::<[u8; 16]>());
+        Self::respond_with_cnonce(challenge, username, password, method, uri, nc, cnonce)
+    }
+
+    fn respond_with_cnonce(
+        challenge: &DigestChallenge,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        nc: u32,
+        cnonce: String,
+    ) -> Result<Self, OpaqueError> {
+        check_param_value(username).context("digest username")?;
+        check_param_value(uri).context("digest uri")?;
+
+        let (qop, cnonce, nc) = if challenge.supports_qop_auth() {
+            (Some(QOP_AUTH.to_owned()), Some(cnonce), Some(nc))
+        } else if challenge.qop.is_some() {
+            return Err(OpaqueError::from_display(
+                "digest challenge: only qop=auth is supported",
+            ));
+        } else {
+            // legacy RFC 2069 challenge
+            (None, None, None)
+        };
+
+        let mut digest = Self {
+            username: username.to_owned(),
+            realm: challenge.realm.clone(),
+            nonce: challenge.nonce.clone(),
+            uri: uri.to_owned(),
+            response: String::new(),
+            algorithm: challenge.algorithm,
+            cnonce,
+            opaque: challenge.opaque.clone(),
+            qop,
+            nc,
+        };
+        digest.response = digest.compute_response(password, method);
+        Ok(digest)
+    }
+
+    /// Verify the response of these credentials, using the password of the user
+    /// and the method of the request they were received for.
+    ///
+    /// This only verifies the response itself, it is up to the caller to validate
+    /// the realm, nonce, nonce count and uri, e.g. using a [`DigestAuthority`].
+    ///
+    /// [`DigestAuthority`]: crate::user::auth::DigestAuthority
+    pub fn verify(&self, password: &str, method: &str) -> bool {
+        let expected = self.compute_response(password, method);
+        constant_time_eq(expected.as_bytes(), self.response.as_bytes())
+    }
+
+    fn compute_response(&self, password: &str, method: &str) -> String {
+        let ha1 = self
+            .algorithm
+            .hash(&format!("{}:{}:{}", self.username, self.realm, password));
+        let ha2 = self.algorithm.hash(&format!("{}:{}", method, self.uri));
+        match (&self.qop, &self.cnonce, self.nc) {
+            (Some(qop), Some(cnonce), Some(nc)) => self.algorithm.hash(&format!(
+                "{ha1}:{}:{nc:08x}:{cnonce}:{qop}:{ha2}",
+                self.nonce
+            )),
+            _ => self.algorithm.hash(&format!("{ha1}:{}:{ha2}", self.nonce)),
+        }
+    }
+
+    /// Try to create a [`Digest`] from a header string,
+    /// encoded as `Digest username="...", realm="...", ...`.
+    pub fn try_from_header_str(s: impl AsRef<str>) -> Result<Self, OpaqueError> {
+        let params = parse_params(strip_scheme(s.as_ref())?)?;
+
+        let mut username = None;
+        let mut realm = None;
+        let mut nonce = None;
+        let mut uri = None;
+        let mut response = None;
+        let mut algorithm = DigestAlgorithm::default();
+        let mut cnonce = None;
+        let mut opaque = None;
+        let mut qop = None;
+        let mut nc = None;
+
+        for (key, value) in params {
+            match key.as_str() {
+                "username" => username = Some(value),
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "uri" => uri = Some(value),
+                "response" => response = Some(value),
+                "algorithm" => algorithm = DigestAlgorithm::try_from_str(&value)?,
+                "cnonce" => cnonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "qop" => qop = Some(value),
+                "nc" => {
+                    nc = Some(u32::from_str_radix(&value, 16).context("digest: parse nonce count")?)
+                }
+                "userhash" if value.eq_ignore_ascii_case("true") => {
+                    return Err(OpaqueError::from_display(
+                        "digest: userhash is not supported",
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        if qop.is_some() && (cnonce.is_none() || nc.is_none()) {
+            return Err(OpaqueError::from_display(
+                "digest: cnonce and nc are required when qop is used",
+            ));
+        }
+
+        Ok(Self {
+            username: username.ok_or_else(|| OpaqueError::from_display("digest: no username"))?,
+            realm: realm.ok_or_else(|| OpaqueError::from_display("digest: no realm"))?,
+            nonce: nonce.ok_or_else(|| OpaqueError::from_display("digest: no nonce"))?,
+            uri: uri.ok_or_else(|| OpaqueError::from_display("digest: no uri"))?,
+            response: response.ok_or_else(|| OpaqueError::from_display("digest: no response"))?,
+            algorithm,
+            cnonce,
+            opaque,
+            qop,
+            nc,
+        })
+    }
+
+    /// Serialize this [`Digest`] credential as a header string.
+    pub fn as_header_string(&self) -> String {
+        let mut s = format!(
+            "{DIGEST_SCHEME} username={}, realm={}, nonce={}, uri={}, algorithm={}, response={}",
+            quote(&self.username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(&self.uri),
+            self.algorithm,
+            quote(&self.response),
+        );
+        if let Some(opaque) = &self.opaque {
+            s.push_str(", opaque=");
+            s.push_str(&quote(opaque));
+        }
+        if let (Some(qop), Some(nc), Some(cnonce)) = (&self.qop, self.nc, &self.cnonce) {
+            s.push_str(&format!(
+                ", qop={qop}, nc={nc:08x}, cnonce={}",
+                quote(cnonce)
+            ));
+        }
+        s
+    }
+
+    /// View this [`Digest`] as a [`HeaderValue`].
+    pub fn as_header_value(&self) -> HeaderValue {
+        let encoded = self.as_header_string();
+        // we validate the inner values upon creation
+        HeaderValue::from_str(&encoded).expect("inner value should always be valid")
+    }
+
+    /// View the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// View the realm.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// View the (server) nonce.
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// View the uri of the request these credentials were computed for.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// View the (hex encoded) response.
+    pub fn response(&self) -> &str {
+        &self.response
+    }
+
+    /// Returns the [`DigestAlgorithm`] used to compute the response.
+    pub fn algorithm(&self) -> DigestAlgorithm {
+        self.algorithm
+    }
+
+    /// View the client nonce, if any.
+    pub fn cnonce(&self) -> Option<&str> {
+        self.cnonce.as_deref()
+    }
+
+    /// View the opaque value echoed back from the challenge, if any.
+    pub fn opaque(&self) -> Option<&str> {
+        self.opaque.as_deref()
+    }
+
+    /// View the quality of protection, if any.
+    pub fn qop(&self) -> Option<&str> {
+        self.qop.as_deref()
+    }
+
+    /// Returns the nonce count, if any.
+    pub fn nc(&self) -> Option<u32> {
+        self.nc
+    }
+}
+
+const DIGEST_SCHEME: &str = "Digest";
+const QOP_AUTH: &str = "auth";
+
+impl authorization::Credentials for Digest {
+    const SCHEME: &'static str = DIGEST_SCHEME;
+
+    fn decode(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        Self::try_from_header_str(value).ok()
+    }
+
+    fn encode(&self) -> HeaderValue {
+        self.as_header_value()
+    }
+}
+
+fn strip_scheme(value: &str) -> Result<&str, OpaqueError> {
+    if value.len() <= DIGEST_SCHEME.len() + 1 {
+        return Err(OpaqueError::from_display("invalid digest scheme length"));
+    }
+    if !value.as_bytes()[..DIGEST_SCHEME.len()].eq_ignore_ascii_case(DIGEST_SCHEME.as_bytes()) {
+        return Err(OpaqueError::from_display("invalid digest scheme"));
+    }
+    let params = &value[DIGEST_SCHEME.len()..];
+    if !params.starts_with(' ') {
+        return Err(OpaqueError::from_display(
+            "missing space separator in digest str",
+        ));
+    }
+    Ok(params)
+}
+
+/// Parse a comma separated list of auth params (`key=token` or `key="quoted"`),
+/// returning the (lowercase) keys with their unquoted values.
+fn parse_params(s: &str) -> Result<Vec<(String, String)>, OpaqueError> {
+    let mut params = Vec::new();
+    let mut rest = s;
+
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return Ok(params);
+        }
+
+        let eq = rest
+            .find('=')
+            .ok_or_else(|| OpaqueError::from_display("missing '=' in digest param"))?;
+        let key = rest[..eq].trim().to_ascii_lowercase();
+        if key.is_empty() || key.contains([' ', '\t', ',', '"']) {
+            return Err(OpaqueError::from_display("invalid digest param name"));
+        }
+        rest = rest[eq + 1..].trim_start_matches([' ', '\t']);
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => {
+                            return Err(OpaqueError::from_display(
+                                "unterminated quoted digest param value",
+                            ));
+                        }
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => {
+                        return Err(OpaqueError::from_display(
+                            "unterminated quoted digest param value",
+                        ));
+                    }
+                }
+            };
+            rest = &quoted[end + 1..];
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = rest[..end].trim().to_owned();
+            rest = &rest[end..];
+            value
+        };
+
+        let rest_trimmed = rest.trim_start_matches([' ', '\t']);
+        if !rest_trimmed.is_empty() && !rest_trimmed.starts_with(',') {
+            return Err(OpaqueError::from_display(
+                "missing ',' separator between digest params",
+            ));
+        }
+        rest = rest_trimmed;
+
+        params.push((key, value));
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+    s
+}
+
+fn check_param_value(value: &str) -> Result<(), OpaqueError> {
+    if value
+        .bytes()
+        .any(|b| !(b == b' ' || b == b'\t' || (33..127).contains(&b)))
+    {
+        return Err(OpaqueError::from_display(
+            "value contains non visible ASCII characters",
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test vectors from RFC 7616, section 3.9.1
+    const NONCE: &str = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+    const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+    const OPAQUE: &str = "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS";
+
+    fn rfc_challenge(algorithm: DigestAlgorithm) -> DigestChallenge {
+        DigestChallenge::new("http-auth@example.org", NONCE, algorithm)
+            .unwrap()
+            .with_opaque(OPAQUE)
+            .unwrap()
+    }
+
+    #[test]
+    fn digest_respond_rfc7616() {
+        for (algorithm, expected) in [
+            (DigestAlgorithm::Md5, "8ca523f5e9506fed4657c9700eebdbec"),
+            (
+                DigestAlgorithm::Sha256,
+                "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+            ),
+        ] {
+            let digest = Digest::respond_with_cnonce(
+                &rfc_challenge(algorithm),
+                "Mufasa",
+                "Circle of Life",
+                "GET",
+                "/dir/index.html",
+                1,
+                CNONCE.to_owned(),
+            )
+            .unwrap();
+            assert_eq!(digest.response(), expected, "{algorithm}");
+            assert!(digest.verify("Circle of Life", "GET"));
+            assert!(!digest.verify("Circle of Life", "POST"));
+            assert!(!digest.verify("circle of life", "GET"));
+        }
+    }
+
+    #[test]
+    fn digest_header_roundtrip() {
+        let digest = Digest::respond(
+            &rfc_challenge(DigestAlgorithm::Sha512_256),
+            "Mufasa",
+            "Circle of Life",
+            "GET",
+            "/dir/index.html",
+            0x2a,
+        )
+        .unwrap();
+        let s = digest.as_header_string();
+        assert!(s.starts_with("Digest username=\"Mufasa\""), "{s}");
+        assert!(s.contains("algorithm=SHA-512-256"), "{s}");
+        assert!(s.contains("nc=0000002a"), "{s}");
+
+        let parsed = Digest::try_from_header_str(&s).unwrap();
+        assert_eq!(parsed, digest);
+        assert!(parsed.verify("Circle of Life", "GET"));
+    }
+
+    #[test]
+    fn digest_parse_header() {
+        let digest = Digest::try_from_header_str(
+            r#"digest username="Mufasa",realm="http-auth@example.org", uri="/dir/index.html",
+            algorithm=MD5, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", nc=00000001,
+            cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", qop=auth,
+            response="8ca523f5e9506fed4657c9700eebdbec",
+            opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+        )
+        .unwrap();
+        assert_eq!(digest.username(), "Mufasa");
+        assert_eq!(digest.nc(), Some(1));
+        assert_eq!(digest.opaque(), Some(OPAQUE));
+        assert!(digest.verify("Circle of Life", "GET"));
+
+        for s in [
+            "",
+            "Digest",
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==",
+            r#"Digest username="Mufasa""#,
+            r#"Digest username="Mufasa, realm="x""#,
+            r#"Digest username="a", realm="b", nonce="c", uri="/", response="d", qop=auth"#,
+            r#"Digest username="a", realm="b", nonce="c", uri="/", response="d", algorithm=SHA-1"#,
+        ] {
+            assert!(Digest::try_from_header_str(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn digest_challenge_header_roundtrip() {
+        let challenge = rfc_challenge(DigestAlgorithm::Sha256).with_stale(true);
+        let s = challenge.as_header_string();
+        assert_eq!(
+            s,
+            format!(
+                r#"Digest realm="http-auth@example.org", nonce="{NONCE}", qop="auth", algorithm=SHA-256, opaque="{OPAQUE}", stale=true"#
+            )
+        );
+        let parsed = DigestChallenge::try_from_header_str(&s).unwrap();
+        assert_eq!(parsed, challenge);
+        assert!(parsed.supports_qop_auth());
+
+        let legacy =
+            DigestChallenge::try_from_header_str(r#"Digest realm="a\"b", nonce=xyz"#).unwrap();
+        assert_eq!(legacy.realm(), "a\"b");
+        assert_eq!(legacy.nonce(), "xyz");
+        assert_eq!(legacy.algorithm(), DigestAlgorithm::Md5);
+        assert!(!legacy.supports_qop_auth());
+        let digest = Digest::respond(&legacy, "user", "pass", "GET", "/", 1).unwrap();
+        assert!(digest.qop().is_none());
+        assert!(digest.verify("pass", "GET"));
+
+        let auth_int =
+            DigestChallenge::try_from_header_str(r#"Digest realm="a", nonce="b", qop="auth-int""#)
+                .unwrap();
+        assert!(Digest::respond(&auth_int, "user", "pass", "GET", "/", 1).is_err());
+    }
+}
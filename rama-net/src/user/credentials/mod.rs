@@ -6,6 +6,14 @@ mod bearer;
 #[doc(inline)]
 pub use bearer::Bearer;
 
+#[cfg(feature = "http")]
+mod digest;
+#[cfg(feature = "http")]
+pub(crate) use digest::constant_time_eq;
+#[cfg(feature = "http")]
+#[doc(inline)]
+pub use digest::{Digest, DigestAlgorithm, DigestChallenge};
+
 mod proxy;
 #[doc(inline)]
 pub use proxy::ProxyCredential;
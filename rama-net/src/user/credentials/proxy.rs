@@ -8,7 +8,11 @@ use rama_http_types::HeaderValue;
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// Proxy credentials.
 pub enum ProxyCredential {
-    /// [`Basic`]` credentials.
+    /// [`Basic`] credentials.
+    ///
+    /// These can also be used to answer [`Digest`] challenges of http proxies.
+    ///
+    /// [`Digest`]: https://datatracker.ietf.org/doc/html/rfc7616
     Basic(Basic),
     /// [`Bearer`] credentials.
     Bearer(Bearer),
@@ -10,6 +10,16 @@ mod credentials;
 #[doc(inline)]
 pub use credentials::{Basic, Bearer, ProxyCredential};
 
+#[cfg(feature = "http")]
+#[doc(inline)]
+pub use credentials::{Digest, DigestAlgorithm, DigestChallenge};
+
 // todo: decouple from http
 #[cfg(feature = "http")]
 pub mod auth;
+
+#[cfg(feature = "http")]
+pub mod acl;
+
+#[cfg(feature = "http")]
+mod reload;
@@ -0,0 +1,164 @@
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::io::AsyncReadExt;
+
+/// A value parsed from a file, reloaded when the file changes.
+///
+/// The file is checked for changes (modification time and size) at most once per
+/// check interval, and the previously loaded value remains in use when the file
+/// cannot be (re)loaded.
+pub(crate) struct ReloadingFile<T> {
+    name: &'static str,
+    path: PathBuf,
+    parse: fn(&str) -> Result<T, OpaqueError>,
+    state: RwLock<State<T>>,
+    reload: tokio::sync::Mutex<()>,
+}
+
+struct State<T> {
+    value: Arc<T>,
+    version: FileVersion,
+    last_check: Instant,
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            version: self.version.clone(),
+            last_check: self.last_check,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileVersion {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl From<std::fs::Metadata> for FileVersion {
+    fn from(meta: std::fs::Metadata) -> Self {
+        Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        }
+    }
+}
+
+impl<T: Send + Sync> ReloadingFile<T> {
+    /// Open the file at the given path, parsing its content using the given function.
+    ///
+    /// The name describes the file in logs and errors.
+    pub(crate) async fn open(
+        name: &'static str,
+        path: PathBuf,
+        parse: fn(&str) -> Result<T, OpaqueError>,
+    ) -> Result<Self, OpaqueError> {
+        let (value, version) = load(&path, parse).await.context(name)?;
+        Ok(Self {
+            name,
+            path,
+            parse,
+            state: RwLock::new(State {
+                value: Arc::new(value),
+                version,
+                last_check: Instant::now(),
+            }),
+            reload: Default::default(),
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the current value, first reloading the file if it changed
+    /// and was not checked for longer than the given interval.
+    pub(crate) async fn get(&self, check_interval: Duration) -> Arc<T> {
+        let state = self.read_state();
+        if state.last_check.elapsed() < check_interval {
+            return state.value;
+        }
+
+        // only a single check at a time, others keep using the current value
+        let Ok(_guard) = self.reload.try_lock() else {
+            return state.value;
+        };
+        let state = self.read_state();
+        if state.last_check.elapsed() < check_interval {
+            return state.value;
+        }
+
+        let modified = match tokio::fs::metadata(&self.path).await {
+            Ok(meta) => FileVersion::from(meta) != state.version,
+            Err(err) => {
+                tracing::warn!(
+                    path = ?self.path,
+                    ?err,
+                    "failed to check {} for changes: keep using loaded version",
+                    self.name,
+                );
+                false
+            }
+        };
+
+        let mut new_state = State {
+            last_check: Instant::now(),
+            ..state
+        };
+        if modified {
+            match load(&self.path, self.parse).await {
+                Ok((value, version)) => {
+                    tracing::debug!(path = ?self.path, "reloaded {}", self.name);
+                    new_state.value = Arc::new(value);
+                    new_state.version = version;
+                }
+                Err(err) => tracing::warn!(
+                    path = ?self.path,
+                    ?err,
+                    "failed to reload {}: keep using loaded version",
+                    self.name,
+                ),
+            }
+        }
+
+        let value = new_state.value.clone();
+        *self.state.write().unwrap_or_else(PoisonError::into_inner) = new_state;
+        value
+    }
+
+    fn read_state(&self) -> State<T> {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl<T> fmt::Debug for ReloadingFile<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadingFile")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+async fn load<T>(
+    path: &Path,
+    parse: fn(&str) -> Result<T, OpaqueError>,
+) -> Result<(T, FileVersion), OpaqueError> {
+    let mut file = tokio::fs::File::open(path).await.context("open file")?;
+    let version = file.metadata().await.context("read file metadata")?.into();
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .await
+        .context("read file")?;
+    Ok((parse(&content)?, version))
+}
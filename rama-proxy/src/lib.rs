@@ -26,6 +26,10 @@
 //! As such the normal way to update data such as your proxy list
 //! is by performing a rolling update of your actual rama-driven proxy workloads.
 //!
+//! For proxy databases loaded from a (csv, json, jsonl or yaml) file,
+//! the `ProxyFileWatcher` can be used to reload the [`MemoryProxyDB`] each time the file changes,
+//! through a [`LiveUpdateProxyDBSetter`] (available with the `live-update` feature).
+//!
 //! That said. By using crates such as [left-right](https://crates.io/crates/left-right)
 //! you can relatively affordable perform live reloads by having the writer on its own tokio
 //! task and wrap the reader in a [`ProxyDB`] implementation. This way you can live reload based upon
@@ -46,6 +50,18 @@
 //!
 //! You can also give a single [`Proxy`] as "proxy db".
 //!
+//! Sticky sessions can be enabled using a [`ProxySessionTable`], in which case the same proxy
+//! is selected for all requests of a session, identified by a [`ProxySessionID`] found in the [`Context`],
+//! e.g. parsed from the username labels (e.g. `john-session-abc123`) using the [`ProxySessionUsernameParser`].
+//!
+//! Failover can be enabled by selecting multiple distinct candidates for the same [`ProxyFilter`],
+//! in which case the [`ProxyFailoverConnector`] tries the next candidate in case the connection
+//! via a proxy could not be established, before any bytes of the request are sent.
+//!
+//! Proxies can be ejected from selection based on their health, tracked in a `ProxyHealthTable`
+//! by active probes and passive signals, using the `ProxyHealthChecker` and `ProxyHealthLayer`
+//! (available with the `health-check` feature).
+//!
 //! The end result is that a [`ProxyAddress`] will be set in case a proxy was selected,
 //! an error is returned in case no proxy could be selected while one was expected
 //! or of course because the inner [`Service`][`rama_core::Service`] failed.
@@ -248,18 +264,28 @@
 
 mod username;
 #[doc(inline)]
-pub use username::ProxyFilterUsernameParser;
+pub use username::{ProxyFilterUsernameParser, ProxySessionUsernameParser};
 
 mod proxydb;
 
 #[doc(inline)]
 pub use proxydb::{
-    Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyID, ProxyQueryPredicate, StringFilter,
+    LeastRecentlyUsedSelector, Proxy, ProxyCandidates, ProxyContext, ProxyDB,
+    ProxyFailoverConnector, ProxyFailoverLayer, ProxyFailoverMode, ProxyFilter, ProxyID,
+    ProxyQueryPredicate, ProxyScorer, ProxySelector, ProxySessionID, ProxySessionTable, ProxyStats,
+    ProxyStatsTable, RoundRobinSelector, StringFilter, WeightedSelector,
 };
 
 #[doc(inline)]
 pub use proxydb::layer::{ProxyDBLayer, ProxyDBService, ProxyFilterMode, UsernameFormatter};
 
+#[cfg(feature = "health-check")]
+#[doc(inline)]
+pub use proxydb::{
+    ProxyHealth, ProxyHealthCheck, ProxyHealthChecker, ProxyHealthLayer, ProxyHealthService,
+    ProxyHealthTable, ProxyList, ServiceHealthCheck, TcpHealthCheck,
+};
+
 #[cfg(feature = "live-update")]
 #[doc(inline)]
 pub use proxydb::{LiveUpdateProxyDB, LiveUpdateProxyDBSetter, proxy_db_updater};
@@ -271,6 +297,18 @@ pub use proxydb::{
     MemoryProxyDBQueryErrorKind,
 };
 
+#[cfg(any(feature = "csv", feature = "json", feature = "yaml"))]
+#[doc(inline)]
+pub use proxydb::{ProxyFileError, ProxyFileErrorKind, ProxyFileFormat};
+
+#[cfg(all(
+    any(feature = "csv", feature = "json", feature = "yaml"),
+    feature = "live-update",
+    feature = "memory-db"
+))]
+#[doc(inline)]
+pub use proxydb::{ProxyFileDiff, ProxyFileWatcher};
+
 #[cfg(feature = "csv")]
 #[doc(inline)]
 pub use proxydb::{ProxyCsvRowReader, ProxyCsvRowReaderError, ProxyCsvRowReaderErrorKind};
@@ -0,0 +1,555 @@
+use super::{Proxy, ProxyID};
+use rama_core::{
+    Context, Layer, Service,
+    error::{BoxError, ErrorExt, OpaqueError},
+};
+use rama_net::{
+    address::ProxyAddress,
+    client::{ConnectorService, EstablishedClientConnection},
+    transport::{TransportContext, TryRefIntoTransportContext},
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, future::poll_fn, pin::Pin, task::Poll, time::Duration};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Default)]
+/// The failover candidates selected by the [`ProxyDBLayer`] in addition to the primary [`Proxy`],
+/// in order of preference, each with the [`ProxyAddress`] to connect to it.
+///
+/// Inserted into the [`Context`] in case failover is enabled,
+/// to be used by the [`ProxyFailoverConnector`].
+///
+/// [`ProxyDBLayer`]: crate::ProxyDBLayer
+pub struct ProxyCandidates {
+    candidates: Vec<(Proxy, ProxyAddress)>,
+}
+
+impl ProxyCandidates {
+    /// Create a new empty [`ProxyCandidates`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a candidate [`Proxy`], to be connected to via the given [`ProxyAddress`].
+    pub fn push(&mut self, proxy: Proxy, address: ProxyAddress) {
+        self.candidates.push((proxy, address));
+    }
+
+    /// Iterate over the candidates, in order of preference.
+    pub fn iter(&self) -> impl Iterator<Item = (&Proxy, &ProxyAddress)> + '_ {
+        self.candidates
+            .iter()
+            .map(|(proxy, address)| (proxy, address))
+    }
+
+    /// Returns the number of candidates.
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    /// Returns `true` in case there are no candidates.
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+impl IntoIterator for ProxyCandidates {
+    type Item = (Proxy, ProxyAddress);
+    type IntoIter = std::vec::IntoIter<(Proxy, ProxyAddress)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.candidates.into_iter()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The modus operandi of the [`ProxyFailoverConnector`] to try the proxy candidates.
+pub enum ProxyFailoverMode {
+    #[default]
+    /// Try the candidates one by one, only trying the next one after the previous one failed.
+    Sequential,
+    /// Try the next candidate as soon as the previous one failed, or in parallel
+    /// in case the previous attempts did not finish within the given delay.
+    /// The first established connection is used, and the other attempts are dropped.
+    Staggered(Duration),
+}
+
+/// A connector [`Service`] which fails over to the next [`ProxyCandidates`]
+/// in case the inner connector fails to establish a connection via the selected proxy,
+/// e.g. because it is refused, requires other credentials or timed out.
+///
+/// The inner connector is given the [`TransportContext`] as request,
+/// such that the actual request is only passed on (as part of the [`EstablishedClientConnection`])
+/// once a connection is established. This makes it safe to use for requests
+/// which cannot be replayed, e.g. those with a streaming body.
+///
+/// The [`Context`] of the established connection contains the [`ProxyAddress`],
+/// [`ProxyID`] and [`Proxy`] of the candidate which served the request.
+///
+/// Without [`ProxyCandidates`] in the [`Context`] it only tries the primary proxy (if any).
+pub struct ProxyFailoverConnector<S> {
+    inner: S,
+    mode: ProxyFailoverMode,
+    attempt_timeout: Option<Duration>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for ProxyFailoverConnector<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyFailoverConnector")
+            .field("inner", &self.inner)
+            .field("mode", &self.mode)
+            .field("attempt_timeout", &self.attempt_timeout)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ProxyFailoverConnector<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            mode: self.mode.clone(),
+            attempt_timeout: self.attempt_timeout,
+        }
+    }
+}
+
+impl<S> ProxyFailoverConnector<S> {
+    /// Create a new [`ProxyFailoverConnector`] wrapping the given connector.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            mode: ProxyFailoverMode::Sequential,
+            attempt_timeout: None,
+        }
+    }
+
+    /// Set the [`ProxyFailoverMode`] ([`ProxyFailoverMode::Sequential`] by default).
+    pub fn with_mode(mut self, mode: ProxyFailoverMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the [`ProxyFailoverMode`] ([`ProxyFailoverMode::Sequential`] by default).
+    pub fn set_mode(&mut self, mode: ProxyFailoverMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the timeout for a single connection attempt, none by default.
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for a single connection attempt, none by default.
+    pub fn set_attempt_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State, Request> Service<State, Request> for ProxyFailoverConnector<S>
+where
+    S: ConnectorService<State, TransportContext, Connection: Send>,
+    State: Clone + Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
+        + Send
+        + 'static,
+{
+    type Response = EstablishedClientConnection<S::Connection, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let transport_ctx = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into()).context("proxy failover: get transport context")
+            })?
+            .clone();
+
+        let candidates = ctx.remove::<ProxyCandidates>().unwrap_or_default();
+        if candidates.is_empty() {
+            let attempt: ConnectAttempt<'_, S::Connection, State> = Box::pin(async move {
+                self.inner
+                    .connect(ctx, transport_ctx)
+                    .await
+                    .map_err(Into::into)
+            });
+            let EstablishedClientConnection { ctx, conn, .. } = attempt.await?;
+            return Ok(EstablishedClientConnection { ctx, req, conn });
+        }
+
+        let mut pending = Vec::with_capacity(candidates.len() + 1);
+        pending.push(ctx.clone());
+        for (proxy, address) in candidates {
+            let mut ctx = ctx.clone();
+            ctx.insert(address);
+            ctx.insert(ProxyID::from(proxy.id.clone()));
+            ctx.insert(proxy);
+            pending.push(ctx);
+        }
+        let mut pending = pending.into_iter();
+
+        let connect = |ctx: Context<State>| -> ConnectAttempt<'_, S::Connection, State> {
+            let transport_ctx = transport_ctx.clone();
+            Box::pin(async move {
+                let attempt = self.inner.connect(ctx, transport_ctx);
+                match self.attempt_timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, attempt).await {
+                        Ok(result) => result.map_err(Into::into),
+                        Err(_) => {
+                            Err(OpaqueError::from_display("connect attempt timed out").into())
+                        }
+                    },
+                    None => attempt.await.map_err(Into::into),
+                }
+            })
+        };
+
+        let mut stagger = match self.mode {
+            ProxyFailoverMode::Sequential => None,
+            ProxyFailoverMode::Staggered(delay) => {
+                Some((delay, Box::pin(tokio::time::sleep(delay))))
+            }
+        };
+        let mut running: Vec<(Option<ProxyID>, ConnectAttempt<'_, S::Connection, State>)> =
+            Vec::new();
+        let mut errors: Vec<(Option<ProxyID>, BoxError)> = Vec::new();
+        let mut failed = false;
+
+        let established = poll_fn(|cx| {
+            loop {
+                let start_next = std::mem::take(&mut failed)
+                    || running.is_empty()
+                    || stagger
+                        .as_mut()
+                        .is_some_and(|(_, sleep)| sleep.as_mut().poll(cx).is_ready());
+                if start_next {
+                    if let Some(ctx) = pending.next() {
+                        if let Some((delay, sleep)) = stagger.as_mut() {
+                            sleep.as_mut().reset(Instant::now() + *delay);
+                        }
+                        running.push((ctx.get::<ProxyID>().cloned(), connect(ctx)));
+                        continue;
+                    }
+                    if running.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                }
+
+                let mut progress = false;
+                let mut index = 0;
+                while index < running.len() {
+                    match running[index].1.as_mut().poll(cx) {
+                        Poll::Ready(Ok(established)) => return Poll::Ready(Some(established)),
+                        Poll::Ready(Err(err)) => {
+                            let (proxy_id, _) = running.swap_remove(index);
+                            tracing::debug!(
+                                proxy.id = ?proxy_id,
+                                %err,
+                                "proxy failover: connect attempt failed",
+                            );
+                            errors.push((proxy_id, err));
+                            failed = true;
+                            progress = true;
+                        }
+                        Poll::Pending => index += 1,
+                    }
+                }
+                if !progress {
+                    return Poll::Pending;
+                }
+            }
+        })
+        .await;
+
+        match established {
+            Some(EstablishedClientConnection { ctx, conn, .. }) => {
+                if !errors.is_empty() {
+                    tracing::debug!(
+                        proxy.id = ?ctx.get::<ProxyID>(),
+                        failed_attempts = errors.len(),
+                        "proxy failover: connection established via failover candidate",
+                    );
+                }
+                Ok(EstablishedClientConnection { ctx, req, conn })
+            }
+            None => Err(OpaqueError::from_std(ProxyFailoverError { errors }).into()),
+        }
+    }
+}
+
+type ConnectAttempt<'a, Connection, State> = Pin<
+    Box<
+        dyn Future<
+                Output = Result<
+                    EstablishedClientConnection<Connection, State, TransportContext>,
+                    BoxError,
+                >,
+            > + Send
+            + 'a,
+    >,
+>;
+
+#[derive(Debug)]
+struct ProxyFailoverError {
+    errors: Vec<(Option<ProxyID>, BoxError)>,
+}
+
+impl fmt::Display for ProxyFailoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "proxy failover: all {} connect attempts failed:",
+            self.errors.len()
+        )?;
+        for (proxy_id, err) in &self.errors {
+            match proxy_id {
+                Some(proxy_id) => write!(f, " [proxy {proxy_id}: {err}]")?,
+                None => write!(f, " [{err}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProxyFailoverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.errors
+            .last()
+            .map(|(_, err)| err.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`Layer`] which wraps the given connector with a [`ProxyFailoverConnector`].
+///
+/// See [`ProxyFailoverConnector`] for more information.
+pub struct ProxyFailoverLayer {
+    mode: ProxyFailoverMode,
+    attempt_timeout: Option<Duration>,
+}
+
+impl ProxyFailoverLayer {
+    /// Create a new [`ProxyFailoverLayer`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the [`ProxyFailoverMode`] ([`ProxyFailoverMode::Sequential`] by default).
+    pub fn with_mode(mut self, mode: ProxyFailoverMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the [`ProxyFailoverMode`] ([`ProxyFailoverMode::Sequential`] by default).
+    pub fn set_mode(&mut self, mode: ProxyFailoverMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the timeout for a single connection attempt, none by default.
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for a single connection attempt, none by default.
+    pub fn set_attempt_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+}
+
+impl<S> Layer<S> for ProxyFailoverLayer {
+    type Service = ProxyFailoverConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProxyFailoverConnector {
+            inner,
+            mode: self.mode.clone(),
+            attempt_timeout: self.attempt_timeout,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use rama_net::transport::TransportProtocol;
+    use rama_utils::str::NonEmptyString;
+    use std::convert::Infallible;
+
+    fn test_proxy(id: &'static str, address: &str) -> Proxy {
+        Proxy {
+            id: NonEmptyString::from_static(id),
+            address: ProxyAddress::try_from(address).unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: true,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+        }
+    }
+
+    /// A request which cannot be cloned (and thus not replayed).
+    #[derive(Debug)]
+    struct NonReplayableRequest(TransportContext);
+
+    impl TryRefIntoTransportContext<()> for NonReplayableRequest {
+        type Error = Infallible;
+
+        fn try_ref_into_transport_ctx(
+            &self,
+            _ctx: &Context<()>,
+        ) -> Result<TransportContext, Self::Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn test_request() -> NonReplayableRequest {
+        NonReplayableRequest(TransportContext {
+            protocol: TransportProtocol::Tcp,
+            app_protocol: None,
+            http_version: None,
+            authority: "example.com:80".parse().unwrap(),
+        })
+    }
+
+    fn test_ctx(
+        primary: (&'static str, &'static str),
+        candidates: &[(&'static str, &'static str)],
+    ) -> Context<()> {
+        let mut ctx = Context::default();
+        let proxy = test_proxy(primary.0, primary.1);
+        ctx.insert(proxy.address.clone());
+        ctx.insert(ProxyID::from(proxy.id.clone()));
+        ctx.insert(proxy);
+        let mut proxy_candidates = ProxyCandidates::new();
+        for (id, address) in candidates {
+            let proxy = test_proxy(id, address);
+            proxy_candidates.push(proxy.clone(), proxy.address);
+        }
+        ctx.insert(proxy_candidates);
+        ctx
+    }
+
+    /// Connects (after the port in ms) via proxies with an even port, fails for the others.
+    fn test_connector() -> impl ConnectorService<(), TransportContext, Connection = u16> {
+        service_fn(async |ctx: Context<()>, req: TransportContext| {
+            let port = ctx.get::<ProxyAddress>().unwrap().authority.port();
+            tokio::time::sleep(Duration::from_millis((port % 1000).into())).await;
+            if port % 2 == 0 {
+                Ok(EstablishedClientConnection {
+                    ctx,
+                    req,
+                    conn: port,
+                })
+            } else {
+                Err(OpaqueError::from_display("proxy connect refused"))
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failover_sequential() {
+        let connector = ProxyFailoverConnector::new(test_connector());
+
+        let EstablishedClientConnection { ctx, req, conn } = connector
+            .serve(
+                test_ctx(
+                    ("1", "127.0.0.1:1003"),
+                    &[("2", "127.0.0.1:1001"), ("3", "127.0.0.1:1002")],
+                ),
+                test_request(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conn, 1002);
+        assert_eq!(req.0.authority.to_string(), "example.com:80");
+        assert_eq!(ctx.get::<ProxyID>().unwrap().as_str(), "3");
+        assert_eq!(ctx.get::<Proxy>().unwrap().id.as_str(), "3");
+        assert!(!ctx.contains::<ProxyCandidates>());
+
+        // the primary proxy is used in case it works
+        let ctx = test_ctx(("1", "127.0.0.1:1000"), &[("2", "127.0.0.1:1002")]);
+        let established = connector.serve(ctx, test_request()).await.unwrap();
+        assert_eq!(established.conn, 1000);
+        assert_eq!(established.ctx.get::<ProxyID>().unwrap().as_str(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failover_all_failed() {
+        let connector = ProxyFailoverConnector::new(test_connector());
+
+        let ctx = test_ctx(
+            ("1", "127.0.0.1:1005"),
+            &[("2", "127.0.0.1:1001"), ("3", "127.0.0.1:1003")],
+        );
+        let err = connector.serve(ctx, test_request()).await.unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("all 3 connect attempts failed"), "{err}");
+        assert!(err.contains("proxy 3"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failover_staggered() {
+        // primary proxy is slow, the first candidate fails, the second candidate is fast
+        let connector = ProxyFailoverConnector::new(test_connector())
+            .with_mode(ProxyFailoverMode::Staggered(Duration::from_millis(20)));
+        let ctx = test_ctx(
+            ("1", "127.0.0.1:1500"),
+            &[("2", "127.0.0.1:1001"), ("3", "127.0.0.1:1002")],
+        );
+
+        let established = connector.serve(ctx, test_request()).await.unwrap();
+        assert_eq!(established.conn, 1002);
+        assert_eq!(established.ctx.get::<ProxyID>().unwrap().as_str(), "3");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failover_staggered_next_on_failure() {
+        // the next candidate is started as soon as one failed, without awaiting the delay
+        let connector = ProxyFailoverConnector::new(test_connector())
+            .with_mode(ProxyFailoverMode::Staggered(Duration::from_millis(200)));
+        let ctx = test_ctx(
+            ("1", "127.0.0.1:1900"),
+            &[("2", "127.0.0.1:1001"), ("3", "127.0.0.1:1002")],
+        );
+
+        let start = Instant::now();
+        let established = connector.serve(ctx, test_request()).await.unwrap();
+        assert_eq!(established.conn, 1002);
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_millis(350), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failover_attempt_timeout() {
+        let connector = ProxyFailoverLayer::new()
+            .with_attempt_timeout(Duration::from_millis(20))
+            .layer(test_connector());
+        let ctx = test_ctx(("1", "127.0.0.1:1500"), &[("2", "127.0.0.1:1004")]);
+
+        let established = connector.serve(ctx, test_request()).await.unwrap();
+        assert_eq!(established.conn, 1004);
+    }
+}
@@ -0,0 +1,745 @@
+use super::{Proxy, internal::proxydb_insert_validator};
+use rama_utils::str::NonEmptyString;
+use std::{collections::HashMap, fmt, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The format of a file containing [`Proxy`] records,
+/// used to load a proxy database from it.
+pub enum ProxyFileFormat {
+    #[cfg(feature = "csv")]
+    /// One proxy per row, as read by the [`ProxyCsvRowReader`].
+    ///
+    /// [`ProxyCsvRowReader`]: crate::ProxyCsvRowReader
+    Csv,
+    #[cfg(feature = "json")]
+    /// A JSON array of [`Proxy`] objects.
+    Json,
+    #[cfg(feature = "json")]
+    /// One JSON [`Proxy`] object per line, also known as `ndjson`.
+    JsonLines,
+    #[cfg(feature = "yaml")]
+    /// A YAML sequence of [`Proxy`] mappings.
+    Yaml,
+}
+
+impl ProxyFileFormat {
+    /// Detect the [`ProxyFileFormat`] from the extension of the given path, if supported.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            #[cfg(feature = "csv")]
+            "csv" => Some(Self::Csv),
+            #[cfg(feature = "json")]
+            "json" => Some(Self::Json),
+            #[cfg(feature = "json")]
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Read all [`Proxy`] records from the file at the given path.
+    ///
+    /// See [`ProxyFileFormat::parse`] for more information.
+    pub async fn read(self, path: impl AsRef<Path>) -> Result<Vec<Proxy>, ProxyFileError> {
+        let data = tokio::fs::read_to_string(path).await?;
+        self.parse(&data)
+    }
+
+    /// Parse all [`Proxy`] records from the given data.
+    ///
+    /// Each record is validated, and an error pointing to the (first) invalid record
+    /// is returned in case a record could not be parsed, is invalid or has a duplicate id.
+    pub fn parse(self, data: &str) -> Result<Vec<Proxy>, ProxyFileError> {
+        let records = match self {
+            #[cfg(feature = "csv")]
+            Self::Csv => parse_csv(data)?,
+            #[cfg(feature = "json")]
+            Self::Json => parse_json(data)?,
+            #[cfg(feature = "json")]
+            Self::JsonLines => parse_json_lines(data)?,
+            #[cfg(feature = "yaml")]
+            Self::Yaml => parse_yaml(data)?,
+        };
+
+        let mut ids: HashMap<NonEmptyString, usize> = HashMap::with_capacity(records.len());
+        let mut proxies = Vec::with_capacity(records.len());
+        for (index, (proxy, line)) in records.into_iter().enumerate() {
+            let record = index + 1;
+            if let Some(first) = ids.insert(proxy.id.clone(), record) {
+                return Err(ProxyFileError::invalid_record(
+                    Some(record),
+                    line,
+                    None,
+                    format!(
+                        "duplicate proxy id '{}' (first defined by record #{first})",
+                        proxy.id
+                    ),
+                ));
+            }
+            if !proxydb_insert_validator(&proxy) {
+                return Err(ProxyFileError::invalid_record(
+                    Some(record),
+                    line,
+                    None,
+                    format!(
+                        "invalid proxy '{}': it is required to be a datacenter, residential or mobile proxy with a protocol supported for its transport(s)",
+                        proxy.id
+                    ),
+                ));
+            }
+            proxies.push(proxy);
+        }
+        Ok(proxies)
+    }
+}
+
+/// A parsed [`Proxy`] record, with the line it was defined on, if known.
+type ProxyRecord = (Proxy, Option<usize>);
+
+#[cfg(feature = "csv")]
+fn parse_csv(data: &str) -> Result<Vec<ProxyRecord>, ProxyFileError> {
+    let mut records = Vec::new();
+    for (index, row) in data.lines().enumerate() {
+        if row.trim().is_empty() {
+            continue;
+        }
+        let line = index + 1;
+        match super::csv::parse_csv_row(row) {
+            Some(proxy) => records.push((proxy, Some(line))),
+            None => {
+                return Err(ProxyFileError::invalid_record(
+                    Some(records.len() + 1),
+                    Some(line),
+                    None,
+                    format!("invalid csv row: {row}"),
+                ));
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "json")]
+fn parse_json(data: &str) -> Result<Vec<ProxyRecord>, ProxyFileError> {
+    use serde::de::DeserializeSeed;
+
+    let current = std::cell::Cell::new(0);
+    let mut deserializer = serde_json::Deserializer::from_str(data);
+    let proxies = ProxyRecordsSeed { current: &current }
+        .deserialize(&mut deserializer)
+        .and_then(|proxies| deserializer.end().map(|_| proxies))
+        .map_err(|err| {
+            ProxyFileError::from_json_error(&err, (current.get() > 0).then(|| current.get()), 0)
+        })?;
+    Ok(proxies.into_iter().map(|proxy| (proxy, None)).collect())
+}
+
+#[cfg(feature = "json")]
+fn parse_json_lines(data: &str) -> Result<Vec<ProxyRecord>, ProxyFileError> {
+    let mut records = Vec::new();
+    for (index, row) in data.lines().enumerate() {
+        if row.trim().is_empty() {
+            continue;
+        }
+        let line = index + 1;
+        match serde_json::from_str(row) {
+            Ok(proxy) => records.push((proxy, Some(line))),
+            Err(err) => {
+                return Err(ProxyFileError::from_json_error(
+                    &err,
+                    Some(records.len() + 1),
+                    index,
+                ));
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml(data: &str) -> Result<Vec<ProxyRecord>, ProxyFileError> {
+    use serde::de::DeserializeSeed;
+
+    let current = std::cell::Cell::new(0);
+    let proxies = ProxyRecordsSeed { current: &current }
+        .deserialize(serde_yaml::Deserializer::from_str(data))
+        .map_err(|err| {
+            let location = err.location();
+            let reason = err.to_string();
+            let reason = match &location {
+                Some(location) => strip_position(
+                    &reason,
+                    &format!(" at line {} column {}", location.line(), location.column()),
+                ),
+                None => reason,
+            };
+            ProxyFileError::invalid_record(
+                (current.get() > 0).then(|| current.get()),
+                location.as_ref().map(|location| location.line()),
+                location.as_ref().map(|location| location.column()),
+                reason,
+            )
+        })?;
+    Ok(proxies.into_iter().map(|proxy| (proxy, None)).collect())
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+/// Deserializes a sequence of [`Proxy`] records,
+/// keeping track of the (1-based) record being deserialized, `0` if none.
+struct ProxyRecordsSeed<'a> {
+    current: &'a std::cell::Cell<usize>,
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+impl<'de> serde::de::DeserializeSeed<'de> for ProxyRecordsSeed<'_> {
+    type Value = Vec<Proxy>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+impl<'de> serde::de::Visitor<'de> for ProxyRecordsSeed<'_> {
+    type Value = Vec<Proxy>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of proxy records")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut proxies = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        loop {
+            self.current.set(proxies.len() + 1);
+            match seq.next_element()? {
+                Some(proxy) => proxies.push(proxy),
+                None => break,
+            }
+        }
+        self.current.set(0);
+        Ok(proxies)
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn strip_position(reason: &str, position: &str) -> String {
+    reason.strip_suffix(position).unwrap_or(reason).to_owned()
+}
+
+#[derive(Debug)]
+/// The error returned by [`ProxyFileFormat`] in case proxies could not be loaded.
+pub struct ProxyFileError {
+    kind: ProxyFileErrorKind,
+}
+
+#[derive(Debug)]
+/// The kind of [`ProxyFileError`].
+pub enum ProxyFileErrorKind {
+    /// The file could not be read.
+    IoError(std::io::Error),
+    /// A record could not be parsed or is invalid.
+    InvalidRecord {
+        /// The (1-based) number of the record, if known.
+        record: Option<usize>,
+        /// The (1-based) line of the error, if known.
+        line: Option<usize>,
+        /// The (1-based) column of the error, if known.
+        column: Option<usize>,
+        /// The reason why the record is invalid.
+        reason: String,
+    },
+}
+
+impl ProxyFileError {
+    fn invalid_record(
+        record: Option<usize>,
+        line: Option<usize>,
+        column: Option<usize>,
+        reason: String,
+    ) -> Self {
+        Self {
+            kind: ProxyFileErrorKind::InvalidRecord {
+                record,
+                line,
+                column,
+                reason,
+            },
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn from_json_error(err: &serde_json::Error, record: Option<usize>, line_offset: usize) -> Self {
+        let reason = strip_position(
+            &err.to_string(),
+            &format!(" at line {} column {}", err.line(), err.column()),
+        );
+        let position = (err.line() > 0).then(|| (err.line() + line_offset, err.column()));
+        Self::invalid_record(
+            record,
+            position.map(|(line, _)| line),
+            position.map(|(_, column)| column),
+            reason,
+        )
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> &ProxyFileErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for ProxyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ProxyFileErrorKind::IoError(err) => write!(f, "I/O error: {err}"),
+            ProxyFileErrorKind::InvalidRecord {
+                record,
+                line,
+                column,
+                reason,
+            } => {
+                f.write_str("invalid proxy record")?;
+                if let Some(record) = record {
+                    write!(f, " #{record}")?;
+                }
+                match (line, column) {
+                    (Some(line), Some(column)) => write!(f, " (line {line}, column {column})")?,
+                    (Some(line), None) => write!(f, " (line {line})")?,
+                    _ => (),
+                }
+                write!(f, ": {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProxyFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ProxyFileErrorKind::IoError(err) => Some(err),
+            ProxyFileErrorKind::InvalidRecord { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProxyFileError {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            kind: ProxyFileErrorKind::IoError(err),
+        }
+    }
+}
+
+#[cfg(all(feature = "live-update", feature = "memory-db"))]
+mod watcher {
+    use super::*;
+    use crate::{LiveUpdateProxyDBSetter, MemoryProxyDB};
+    use rama_core::error::{BoxError, ErrorContext};
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    /// Summary of the changes between two versions of a proxy file.
+    pub struct ProxyFileDiff {
+        /// Number of proxies added.
+        pub added: usize,
+        /// Number of proxies removed.
+        pub removed: usize,
+        /// Number of proxies of which the definition changed.
+        pub changed: usize,
+        /// Number of proxies which remained the same.
+        pub unchanged: usize,
+    }
+
+    impl ProxyFileDiff {
+        fn new(old: &HashMap<NonEmptyString, Proxy>, new: &HashMap<NonEmptyString, Proxy>) -> Self {
+            let mut diff = Self::default();
+            for (id, proxy) in new {
+                match old.get(id) {
+                    None => diff.added += 1,
+                    Some(old_proxy) if old_proxy != proxy => diff.changed += 1,
+                    Some(_) => diff.unchanged += 1,
+                }
+            }
+            diff.removed = old.keys().filter(|id| !new.contains_key(*id)).count();
+            diff
+        }
+    }
+
+    impl fmt::Display for ProxyFileDiff {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} added, {} removed, {} changed, {} unchanged",
+                self.added, self.removed, self.changed, self.unchanged
+            )
+        }
+    }
+
+    #[derive(Debug)]
+    /// Watches a proxy file, and (re)loads it into a [`MemoryProxyDB`]
+    /// set through the given [`LiveUpdateProxyDBSetter`] every time it changes.
+    ///
+    /// The file is checked for changes (using its modification time and size)
+    /// every interval (`1s` by default). In case the new version of the file
+    /// cannot be loaded, e.g. because it contains an invalid record,
+    /// the previous proxy database remains in use, until the next change.
+    pub struct ProxyFileWatcher {
+        path: PathBuf,
+        format: ProxyFileFormat,
+        setter: LiveUpdateProxyDBSetter<MemoryProxyDB>,
+        interval: Duration,
+        proxies: HashMap<NonEmptyString, Proxy>,
+        version: Option<(SystemTime, u64)>,
+    }
+
+    impl ProxyFileWatcher {
+        /// Create a new [`ProxyFileWatcher`] for the file at the given path,
+        /// in the given [`ProxyFileFormat`].
+        pub fn new(
+            path: impl Into<PathBuf>,
+            format: ProxyFileFormat,
+            setter: LiveUpdateProxyDBSetter<MemoryProxyDB>,
+        ) -> Self {
+            Self {
+                path: path.into(),
+                format,
+                setter,
+                interval: Duration::from_secs(1),
+                proxies: HashMap::new(),
+                version: None,
+            }
+        }
+
+        /// Set the interval at which the file is checked for changes (`1s` by default).
+        pub fn with_interval(mut self, interval: Duration) -> Self {
+            self.interval = interval;
+            self
+        }
+
+        /// Set the interval at which the file is checked for changes (`1s` by default).
+        pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+            self.interval = interval;
+            self
+        }
+
+        /// (Re)load the file, setting the new proxy database in case it was loaded successfully.
+        ///
+        /// Returns the summary of the changes compared to the previously loaded version.
+        pub async fn reload(&mut self) -> Result<ProxyFileDiff, BoxError> {
+            let proxies = self.format.read(&self.path).await?;
+            let map: HashMap<_, _> = proxies
+                .iter()
+                .map(|proxy| (proxy.id.clone(), proxy.clone()))
+                .collect();
+            let db = MemoryProxyDB::try_from_rows(proxies)
+                .context("proxy file watcher: create memory proxy db")?;
+
+            let diff = ProxyFileDiff::new(&self.proxies, &map);
+            self.setter.set(db);
+            self.proxies = map;
+
+            tracing::info!(
+                path = %self.path.display(),
+                added = diff.added,
+                removed = diff.removed,
+                changed = diff.changed,
+                unchanged = diff.unchanged,
+                "proxy file watcher: proxy db reloaded",
+            );
+            Ok(diff)
+        }
+
+        /// Load the file, and reload it every time it changes, forever.
+        ///
+        /// Typically spawned as a background task, e.g. using a graceful shutdown guard.
+        pub async fn run(mut self) {
+            loop {
+                match self.changed_version().await {
+                    Ok(Some(version)) => {
+                        // the version is also updated on failure, to only retry on the next change
+                        self.version = Some(version);
+                        if let Err(err) = self.reload().await {
+                            tracing::error!(
+                                path = %self.path.display(),
+                                %err,
+                                "proxy file watcher: failed to reload proxy file: keep previous proxy db",
+                            );
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => {
+                        tracing::debug!(
+                            path = %self.path.display(),
+                            %err,
+                            "proxy file watcher: failed to read proxy file metadata",
+                        );
+                    }
+                }
+                tokio::time::sleep(self.interval).await;
+            }
+        }
+
+        async fn changed_version(&self) -> std::io::Result<Option<(SystemTime, u64)>> {
+            let metadata = tokio::fs::metadata(&self.path).await?;
+            let version = (metadata.modified()?, metadata.len());
+            Ok((self.version != Some(version)).then_some(version))
+        }
+    }
+}
+
+#[cfg(all(feature = "live-update", feature = "memory-db"))]
+pub use watcher::{ProxyFileDiff, ProxyFileWatcher};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "json")]
+    const JSON_PROXY: &str = r#"{"id":"1","address":"http://127.0.0.1:8080","tcp":true,"udp":false,"http":true,"https":false,"socks5":false,"socks5h":false,"datacenter":true,"residential":false,"mobile":false,"country":"BE"}"#;
+
+    #[cfg(feature = "json")]
+    fn json_proxy(id: &str) -> String {
+        JSON_PROXY.replace(r#""id":"1""#, &format!(r#""id":"{id}""#))
+    }
+
+    fn assert_invalid_record(
+        err: ProxyFileError,
+        expected_record: Option<usize>,
+        expected_line: Option<usize>,
+        expected_reason: &str,
+    ) {
+        match err.kind() {
+            ProxyFileErrorKind::InvalidRecord {
+                record,
+                line,
+                reason,
+                ..
+            } => {
+                assert_eq!(*record, expected_record, "{err}");
+                assert_eq!(*line, expected_line, "{err}");
+                assert!(reason.contains(expected_reason), "{err}");
+            }
+            ProxyFileErrorKind::IoError(_) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn test_proxy_file_format_from_path() {
+        for (path, expected) in [
+            #[cfg(feature = "csv")]
+            ("proxies.csv", Some(ProxyFileFormat::Csv)),
+            #[cfg(feature = "json")]
+            ("proxies.json", Some(ProxyFileFormat::Json)),
+            #[cfg(feature = "json")]
+            ("/data/proxies.JSONL", Some(ProxyFileFormat::JsonLines)),
+            #[cfg(feature = "json")]
+            ("proxies.ndjson", Some(ProxyFileFormat::JsonLines)),
+            #[cfg(feature = "yaml")]
+            ("proxies.yml", Some(ProxyFileFormat::Yaml)),
+            ("proxies.txt", None),
+            ("proxies", None),
+        ] {
+            assert_eq!(ProxyFileFormat::from_path(path), expected, "{path}");
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_parse_json() {
+        let data = format!("[\n  {},\n  {}\n]", json_proxy("1"), json_proxy("2"));
+        let proxies = ProxyFileFormat::Json.parse(&data).unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0].id, "1");
+        assert_eq!(proxies[1].id, "2");
+        assert_eq!(proxies[1].country, Some("be".into()));
+        assert_eq!(proxies[1].address.authority.to_string(), "127.0.0.1:8080");
+
+        let data = format!(
+            "[\n  {},\n  {}\n]",
+            json_proxy("1"),
+            json_proxy("2").replace(r#""tcp":true,"#, "")
+        );
+        let err = ProxyFileFormat::Json.parse(&data).unwrap_err();
+        assert_invalid_record(err, Some(2), Some(3), "missing field `tcp`");
+
+        let err = ProxyFileFormat::Json.parse(r#"{"id":"1"}"#).unwrap_err();
+        assert_invalid_record(err, None, Some(1), "expected a sequence");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_parse_json_lines() {
+        let data = format!("{}\n\n{}\n", json_proxy("1"), json_proxy("2"));
+        let proxies = ProxyFileFormat::JsonLines.parse(&data).unwrap();
+        assert_eq!(proxies.len(), 2);
+
+        let data = format!(
+            "{}\n\n{}\n",
+            json_proxy("1"),
+            json_proxy("2").replace("127.0.0.1:8080", "")
+        );
+        let err = ProxyFileFormat::JsonLines.parse(&data).unwrap_err();
+        assert_invalid_record(err, Some(2), Some(3), "");
+
+        // duplicate ids
+        let data = format!(
+            "{}\n{}\n{}",
+            json_proxy("1"),
+            json_proxy("2"),
+            json_proxy("1")
+        );
+        let err = ProxyFileFormat::JsonLines.parse(&data).unwrap_err();
+        assert_invalid_record(err, Some(3), Some(3), "duplicate proxy id '1'");
+
+        // invalid proxy
+        let data = json_proxy("1").replace(r#""datacenter":true"#, r#""datacenter":false"#);
+        let err = ProxyFileFormat::JsonLines.parse(&data).unwrap_err();
+        assert_invalid_record(err, Some(1), Some(1), "invalid proxy '1'");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_parse_yaml() {
+        let data = r#"
+- id: "1"
+  address: "http://127.0.0.1:8080"
+  tcp: true
+  udp: false
+  http: true
+  https: false
+  socks5: false
+  socks5h: false
+  datacenter: true
+  residential: false
+  mobile: false
+  asn: 42
+- id: "2"
+  address: "socks5://127.0.0.1:1080"
+  tcp: true
+  udp: true
+  http: false
+  https: false
+  socks5: true
+  socks5h: false
+  datacenter: false
+  residential: true
+  mobile: false
+  city: "ghent"
+"#;
+        let proxies = ProxyFileFormat::Yaml.parse(data).unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[1].id, "2");
+        assert_eq!(proxies[1].city, Some("ghent".into()));
+
+        let err = ProxyFileFormat::Yaml
+            .parse(&data.replace("udp: true", "udp: maybe"))
+            .unwrap_err();
+        assert_invalid_record(err, Some(2), Some(17), "invalid type");
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_parse_csv() {
+        let data = "1,1,,1,,,,1,,,authority,,,US,,,,,\n\n2,1,,1,,,,1,,,authority,,,BE,,,,,";
+        let proxies = ProxyFileFormat::Csv.parse(data).unwrap();
+        assert_eq!(proxies.len(), 2);
+
+        let err = ProxyFileFormat::Csv
+            .parse("1,1,,1,,,,1,,,authority,,,US,,,,,\n2,1,,1")
+            .unwrap_err();
+        assert_invalid_record(err, Some(2), Some(2), "invalid csv row");
+    }
+
+    #[cfg(all(feature = "json", feature = "live-update", feature = "memory-db"))]
+    #[tokio::test]
+    async fn test_proxy_file_watcher() {
+        use crate::{ProxyDB, ProxyFilter, proxy_db_updater, proxydb::ProxyContext};
+        use rama_net::transport::TransportProtocol;
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxies.jsonl");
+        std::fs::write(&path, format!("{}\n{}", json_proxy("1"), json_proxy("2"))).unwrap();
+
+        let (db, setter) = proxy_db_updater();
+        let mut watcher = ProxyFileWatcher::new(&path, ProxyFileFormat::JsonLines, setter);
+
+        let diff = watcher.reload().await.unwrap();
+        assert_eq!(
+            diff,
+            ProxyFileDiff {
+                added: 2,
+                ..Default::default()
+            }
+        );
+
+        let get_proxy = async |id: &'static str| {
+            db.get_proxy(
+                ProxyContext {
+                    protocol: TransportProtocol::Tcp,
+                },
+                ProxyFilter {
+                    id: Some(NonEmptyString::from_static(id)),
+                    ..Default::default()
+                },
+            )
+            .await
+        };
+        assert!(get_proxy("1").await.is_ok());
+
+        std::fs::write(
+            &path,
+            format!(
+                "{}\n{}",
+                json_proxy("2").replace(r#""country":"BE""#, r#""country":"NL""#),
+                json_proxy("3")
+            ),
+        )
+        .unwrap();
+        let diff = watcher.reload().await.unwrap();
+        assert_eq!(
+            diff,
+            ProxyFileDiff {
+                added: 1,
+                removed: 1,
+                changed: 1,
+                unchanged: 0,
+            }
+        );
+        assert!(get_proxy("1").await.is_err());
+        assert_eq!(get_proxy("2").await.unwrap().country, Some("nl".into()));
+
+        // the previous db is kept in case the file is invalid
+        std::fs::write(&path, format!("{}\n{{", json_proxy("4"))).unwrap();
+        assert!(watcher.reload().await.is_err());
+        assert!(get_proxy("3").await.is_ok());
+        assert!(get_proxy("4").await.is_err());
+
+        // the watcher picks up changes by itself
+        let watcher = watcher.with_interval(Duration::from_millis(10));
+        let handle = tokio::spawn(watcher.run());
+        std::fs::write(&path, json_proxy("5")).unwrap();
+        let mut found = false;
+        for _ in 0..100 {
+            if get_proxy("5").await.is_ok() {
+                found = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+        assert!(found);
+        assert!(get_proxy("3").await.is_err());
+    }
+}
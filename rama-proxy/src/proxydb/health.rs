@@ -0,0 +1,824 @@
+use super::{Proxy, ProxyID, ProxyQueryPredicate};
+use rama_core::{
+    Context, Layer, Service,
+    error::{BoxError, ErrorContext, OpaqueError},
+};
+use rama_dns::{DnsResolver, HickoryDns};
+use rama_net::address::Authority;
+use rama_tcp::client::{TcpStreamConnector, tcp_connect};
+use rama_utils::{macros::define_inner_service_accessors, str::NonEmptyString};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Health state of a [`Proxy`] as tracked by a [`ProxyHealthTable`].
+pub struct ProxyHealth {
+    /// Number of consecutive failures since the last success (or ejection).
+    pub consecutive_failures: u32,
+    /// Number of consecutive ejections since the last success,
+    /// used to back off the re-admission exponentially.
+    pub ejections: u32,
+    /// Moment until which the proxy is ejected from selection, if ejected.
+    pub ejected_until: Option<Instant>,
+    /// Exponentially weighted moving average of the latency, if known.
+    pub latency: Option<Duration>,
+    /// Total number of successes recorded.
+    pub successes: u64,
+    /// Total number of failures recorded.
+    pub failures: u64,
+}
+
+impl ProxyHealth {
+    /// Returns `true` in case the proxy is ejected from selection at the given moment.
+    pub fn is_ejected_at(&self, now: Instant) -> bool {
+        self.ejected_until.is_some_and(|until| until > now)
+    }
+
+    /// Returns `true` in case the proxy is currently ejected from selection.
+    pub fn is_ejected(&self) -> bool {
+        self.is_ejected_at(Instant::now())
+    }
+}
+
+#[derive(Debug)]
+struct HealthEntry {
+    authority: Authority,
+    health: ProxyHealth,
+}
+
+#[derive(Debug, Clone)]
+/// Shared table of [`ProxyHealth`], keyed by [`ProxyID`].
+///
+/// Successes and failures are recorded by active probes (see [`ProxyHealthChecker`])
+/// as well as by passive signals (see [`ProxyHealthLayer`]). A proxy is ejected from selection
+/// once it failed `failure_threshold` consecutive times (`3` by default), for a duration
+/// starting at the base ejection time (`30s` by default) and doubling for every consecutive ejection
+/// up to the max ejection time (`10m` by default). Once re-admitted a single failure
+/// is sufficient to eject it again, while a single success resets its state.
+///
+/// It is a [`ProxyQueryPredicate`] which rules out ejected proxies,
+/// to be used as (part of) the predicate of a `ProxyDBLayer`.
+///
+/// The state of a proxy is kept for as long as its [`ProxyID`] refers to the same proxy
+/// [`Authority`], and as such survives reloads of the proxy database.
+pub struct ProxyHealthTable {
+    entries: Arc<RwLock<HashMap<NonEmptyString, HealthEntry>>>,
+    failure_threshold: u32,
+    base_ejection: Duration,
+    max_ejection: Duration,
+}
+
+impl ProxyHealthTable {
+    const LATENCY_WEIGHT: f64 = 0.2;
+
+    /// Create a new empty [`ProxyHealthTable`].
+    pub fn new() -> Self {
+        Self {
+            entries: Default::default(),
+            failure_threshold: 3,
+            base_ejection: Duration::from_secs(30),
+            max_ejection: Duration::from_secs(600),
+        }
+    }
+
+    /// Set the number of consecutive failures after which a proxy is ejected (`3` by default).
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the number of consecutive failures after which a proxy is ejected (`3` by default).
+    pub fn set_failure_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the base (`30s` by default) and max (`10m` by default) duration
+    /// for which a proxy is ejected.
+    pub fn with_ejection_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_ejection = base;
+        self.max_ejection = max.max(base);
+        self
+    }
+
+    /// Set the base (`30s` by default) and max (`10m` by default) duration
+    /// for which a proxy is ejected.
+    pub fn set_ejection_backoff(&mut self, base: Duration, max: Duration) -> &mut Self {
+        self.base_ejection = base;
+        self.max_ejection = max.max(base);
+        self
+    }
+
+    /// Record a successful use (or probe) of the proxy, with the observed latency.
+    pub fn record_success(&self, proxy: &Proxy, latency: Duration) {
+        self.update(proxy, |health| {
+            health.successes += 1;
+            health.consecutive_failures = 0;
+            health.ejections = 0;
+            health.ejected_until = None;
+            health.latency = Some(match health.latency {
+                Some(avg) => {
+                    avg.mul_f64(1. - Self::LATENCY_WEIGHT) + latency.mul_f64(Self::LATENCY_WEIGHT)
+                }
+                None => latency,
+            });
+        });
+    }
+
+    /// Record a failed use (or probe) of the proxy.
+    pub fn record_failure(&self, proxy: &Proxy) {
+        self.record_failure_at(proxy, Instant::now())
+    }
+
+    /// Get the [`ProxyHealth`] of the proxy, if anything was recorded for it.
+    pub fn health(&self, id: &ProxyID) -> Option<ProxyHealth> {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id.0)
+            .map(|entry| entry.health.clone())
+    }
+
+    /// Get a snapshot of the [`ProxyHealth`] of all tracked proxies, e.g. to expose as metrics.
+    pub fn snapshot(&self) -> Vec<(ProxyID, ProxyHealth)> {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(id, entry)| (ProxyID::from(id.clone()), entry.health.clone()))
+            .collect()
+    }
+
+    /// Returns `true` in case the proxy is not ejected from selection.
+    pub fn is_available(&self, proxy: &Proxy) -> bool {
+        self.is_available_at(proxy, Instant::now())
+    }
+
+    /// Only retain the state of the given proxies, dropping the state of removed proxies
+    /// as well as of those of which the [`Authority`] changed.
+    pub fn retain_proxies<'a>(&self, proxies: impl IntoIterator<Item = &'a Proxy>) {
+        let proxies: HashMap<_, _> = proxies
+            .into_iter()
+            .map(|proxy| (&proxy.id, &proxy.address.authority))
+            .collect();
+        self.entries
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|id, entry| proxies.get(id) == Some(&&entry.authority));
+    }
+
+    fn is_available_at(&self, proxy: &Proxy, now: Instant) -> bool {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&proxy.id)
+            .filter(|entry| entry.authority == proxy.address.authority)
+            .is_none_or(|entry| !entry.health.is_ejected_at(now))
+    }
+
+    fn record_failure_at(&self, proxy: &Proxy, now: Instant) {
+        self.update(proxy, |health| {
+            health.failures += 1;
+            health.consecutive_failures += 1;
+            if health.is_ejected_at(now) {
+                return;
+            }
+            // a re-admitted proxy is ejected again on its first failure
+            let threshold = if health.ejections > 0 {
+                1
+            } else {
+                self.failure_threshold
+            };
+            if health.consecutive_failures >= threshold {
+                let backoff = self
+                    .base_ejection
+                    .saturating_mul(1 << health.ejections.min(16))
+                    .min(self.max_ejection);
+                health.ejections += 1;
+                health.consecutive_failures = 0;
+                health.ejected_until = Some(now + backoff);
+                tracing::debug!(
+                    proxy.id = %proxy.id,
+                    ejections = health.ejections,
+                    ?backoff,
+                    "proxy health: eject proxy from selection",
+                );
+            }
+        });
+    }
+
+    fn update(&self, proxy: &Proxy, f: impl FnOnce(&mut ProxyHealth)) {
+        let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+        let entry = entries
+            .entry(proxy.id.clone())
+            .or_insert_with(|| HealthEntry {
+                authority: proxy.address.authority.clone(),
+                health: ProxyHealth::default(),
+            });
+        if entry.authority != proxy.address.authority {
+            // the id refers to another proxy now, start over
+            entry.authority = proxy.address.authority.clone();
+            entry.health = ProxyHealth::default();
+        }
+        f(&mut entry.health);
+    }
+}
+
+impl Default for ProxyHealthTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProxyQueryPredicate for ProxyHealthTable {
+    fn execute(&self, proxy: &Proxy) -> bool {
+        self.is_available(proxy)
+    }
+}
+
+/// A health check used by the [`ProxyHealthChecker`] to actively probe a [`Proxy`].
+pub trait ProxyHealthCheck: Clone + Send + Sync + 'static {
+    /// Probe the given proxy, returning an error in case it is not healthy.
+    fn check(&self, proxy: &Proxy) -> impl Future<Output = Result<(), BoxError>> + Send;
+}
+
+#[derive(Debug, Clone)]
+/// A [`ProxyHealthCheck`] which checks if a tcp connection
+/// can be established with the proxy.
+///
+/// The connection is established using [`tcp_connect`], resolving the proxy
+/// using the [`HickoryDns`] resolver by default, and using the given [`TcpStreamConnector`]
+/// (if any), e.g. the same `EgressGuardConnector` as used by the (http) client.
+pub struct TcpHealthCheck<Dns = HickoryDns, Connector = ()> {
+    dns: Dns,
+    connector: Connector,
+}
+
+impl TcpHealthCheck {
+    /// Create a new [`TcpHealthCheck`].
+    pub fn new() -> Self {
+        Self {
+            dns: HickoryDns::default(),
+            connector: (),
+        }
+    }
+}
+
+impl Default for TcpHealthCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Dns, Connector> TcpHealthCheck<Dns, Connector> {
+    /// Use the given [`DnsResolver`] to resolve the proxies.
+    pub fn with_dns<OtherDns>(self, dns: OtherDns) -> TcpHealthCheck<OtherDns, Connector>
+    where
+        OtherDns: DnsResolver<Error: Into<BoxError>> + Clone,
+    {
+        TcpHealthCheck {
+            dns,
+            connector: self.connector,
+        }
+    }
+
+    /// Use the given [`TcpStreamConnector`] to connect to the proxies.
+    pub fn with_connector<OtherConnector>(
+        self,
+        connector: OtherConnector,
+    ) -> TcpHealthCheck<Dns, OtherConnector>
+    where
+        OtherConnector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
+    {
+        TcpHealthCheck {
+            dns: self.dns,
+            connector,
+        }
+    }
+}
+
+impl<Dns, Connector> ProxyHealthCheck for TcpHealthCheck<Dns, Connector>
+where
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+    Connector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
+{
+    async fn check(&self, proxy: &Proxy) -> Result<(), BoxError> {
+        let authority = &proxy.address.authority;
+        tcp_connect(
+            &Context::<()>::default(),
+            authority.clone(),
+            false,
+            self.dns.clone(),
+            self.connector.clone(),
+        )
+        .await
+        .with_context(|| format!("tcp health check: connect to {authority}"))?;
+        Ok(())
+    }
+}
+
+/// A [`ProxyHealthCheck`] which serves a request through the proxy,
+/// using the given [`Service`], e.g. an http client making an http(s) request.
+///
+/// The [`ProxyAddress`] of the probed proxy is inserted in the [`Context`]
+/// given to the service, and a new request is created for every probe.
+/// The proxy is considered healthy in case the service returns a response.
+pub struct ServiceHealthCheck<S, F> {
+    service: Arc<S>,
+    request: F,
+}
+
+impl<S: fmt::Debug, F> fmt::Debug for ServiceHealthCheck<S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceHealthCheck")
+            .field("service", &self.service)
+            .field("request", &std::any::type_name::<F>())
+            .finish()
+    }
+}
+
+impl<S, F: Clone> Clone for ServiceHealthCheck<S, F> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            request: self.request.clone(),
+        }
+    }
+}
+
+impl<S, F> ServiceHealthCheck<S, F> {
+    /// Create a new [`ServiceHealthCheck`] using the given service,
+    /// and the function to create the request with.
+    pub fn new(service: S, request: F) -> Self {
+        Self {
+            service: Arc::new(service),
+            request,
+        }
+    }
+}
+
+impl<S, F, Request> ProxyHealthCheck for ServiceHealthCheck<S, F>
+where
+    S: Service<(), Request, Error: Into<BoxError>>,
+    F: Fn() -> Request + Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    async fn check(&self, proxy: &Proxy) -> Result<(), BoxError> {
+        let mut ctx = Context::default();
+        ctx.insert(proxy.address.clone());
+        ctx.insert(ProxyID::from(proxy.id.clone()));
+        self.service
+            .serve(ctx, (self.request)())
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .with_context(|| format!("service health check: proxy {}", proxy.id))?;
+        Ok(())
+    }
+}
+
+/// A list of all [`Proxy`]s to be probed by the [`ProxyHealthChecker`].
+pub trait ProxyList: Send + Sync + 'static {
+    /// Get all proxies of this list.
+    fn proxies(&self) -> Vec<Proxy>;
+}
+
+impl ProxyList for Vec<Proxy> {
+    fn proxies(&self) -> Vec<Proxy> {
+        self.clone()
+    }
+}
+
+impl ProxyList for Proxy {
+    fn proxies(&self) -> Vec<Proxy> {
+        vec![self.clone()]
+    }
+}
+
+impl<T: ProxyList> ProxyList for Arc<T> {
+    fn proxies(&self) -> Vec<Proxy> {
+        (**self).proxies()
+    }
+}
+
+#[cfg(feature = "memory-db")]
+impl ProxyList for super::MemoryProxyDB {
+    fn proxies(&self) -> Vec<Proxy> {
+        self.iter().cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Actively probes all proxies of a [`ProxyList`] at a fixed interval,
+/// recording the results in a [`ProxyHealthTable`].
+///
+/// Proxies which are ejected are not probed until they are re-admitted,
+/// and the state of proxies no longer found in the list is dropped.
+pub struct ProxyHealthChecker<C> {
+    check: C,
+    table: ProxyHealthTable,
+    interval: Duration,
+    timeout: Duration,
+    concurrency: usize,
+}
+
+impl<C> ProxyHealthChecker<C> {
+    /// Create a new [`ProxyHealthChecker`] using the given [`ProxyHealthCheck`],
+    /// recording the results in the given [`ProxyHealthTable`].
+    pub fn new(check: C, table: ProxyHealthTable) -> Self {
+        Self {
+            check,
+            table,
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            concurrency: 16,
+        }
+    }
+
+    /// Set the interval between two probe rounds (`30s` by default).
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the interval between two probe rounds (`30s` by default).
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the timeout of a single probe (`10s` by default).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the timeout of a single probe (`10s` by default).
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the max number of probes running concurrently (`16` by default).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the max number of probes running concurrently (`16` by default).
+    pub fn set_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+impl<C: ProxyHealthCheck> ProxyHealthChecker<C> {
+    /// Probe all (non-ejected) proxies of the given list once.
+    pub async fn check_once(&self, list: &impl ProxyList) {
+        let proxies = list.proxies();
+        self.table.retain_proxies(&proxies);
+
+        let mut probes = JoinSet::new();
+        let mut seen = HashSet::new();
+        for proxy in proxies {
+            if !seen.insert(proxy.id.clone()) || !self.table.is_available(&proxy) {
+                continue;
+            }
+            while probes.len() >= self.concurrency {
+                probes.join_next().await;
+            }
+            let (check, table, timeout) = (self.check.clone(), self.table.clone(), self.timeout);
+            probes.spawn(async move {
+                let start = Instant::now();
+                match tokio::time::timeout(timeout, check.check(&proxy)).await {
+                    Ok(Ok(())) => table.record_success(&proxy, start.elapsed()),
+                    Ok(Err(err)) => {
+                        tracing::debug!(proxy.id = %proxy.id, %err, "proxy health: probe failed");
+                        table.record_failure(&proxy);
+                    }
+                    Err(_) => {
+                        tracing::debug!(proxy.id = %proxy.id, "proxy health: probe timed out");
+                        table.record_failure(&proxy);
+                    }
+                }
+            });
+        }
+        while probes.join_next().await.is_some() {}
+    }
+
+    /// Probe all (non-ejected) proxies of the given list every interval, forever.
+    ///
+    /// Typically spawned as a background task, e.g. using a graceful shutdown guard.
+    pub async fn run(self, list: impl ProxyList) {
+        loop {
+            self.check_once(&list).await;
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+/// A [`Service`] which records passive health signals in a [`ProxyHealthTable`],
+/// for the [`Proxy`] found in the [`Context`] (e.g. as selected by the `ProxyDBLayer`).
+///
+/// It is to wrap the (proxy) connector, such that connect errors
+/// (e.g. refused connections or failed proxy handshakes) are recorded as failures,
+/// and established connections as successes (with the time it took as latency).
+pub struct ProxyHealthService<S> {
+    inner: S,
+    table: ProxyHealthTable,
+}
+
+impl<S: fmt::Debug> fmt::Debug for ProxyHealthService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyHealthService")
+            .field("inner", &self.inner)
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ProxyHealthService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<S> ProxyHealthService<S> {
+    /// Create a new [`ProxyHealthService`].
+    pub fn new(inner: S, table: ProxyHealthTable) -> Self {
+        Self { inner, table }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State, Request> Service<State, Request> for ProxyHealthService<S>
+where
+    S: Service<State, Request>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let proxy = ctx.get::<Proxy>().cloned();
+        let start = Instant::now();
+        let result = self.inner.serve(ctx, req).await;
+        if let Some(proxy) = proxy {
+            match &result {
+                Ok(_) => self.table.record_success(&proxy, start.elapsed()),
+                Err(_) => self.table.record_failure(&proxy),
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`Layer`] which wraps the given service with a [`ProxyHealthService`].
+///
+/// See [`ProxyHealthService`] for more information.
+pub struct ProxyHealthLayer {
+    table: ProxyHealthTable,
+}
+
+impl ProxyHealthLayer {
+    /// Create a new [`ProxyHealthLayer`] recording in the given [`ProxyHealthTable`].
+    pub fn new(table: ProxyHealthTable) -> Self {
+        Self { table }
+    }
+}
+
+impl<S> Layer<S> for ProxyHealthLayer {
+    type Service = ProxyHealthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProxyHealthService::new(inner, self.table.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use rama_net::{address::ProxyAddress, client::EgressPolicy};
+    use rama_tcp::client::EgressGuardConnector;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn test_proxy(id: &'static str, address: &str) -> Proxy {
+        Proxy {
+            id: NonEmptyString::from_static(id),
+            address: ProxyAddress::try_from(address).unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: true,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+        }
+    }
+
+    fn proxy_id(id: &'static str) -> ProxyID {
+        NonEmptyString::from_static(id).into()
+    }
+
+    #[test]
+    fn test_proxy_health_table_ejection_backoff() {
+        let table = ProxyHealthTable::new()
+            .with_failure_threshold(2)
+            .with_ejection_backoff(Duration::from_secs(10), Duration::from_secs(25));
+        let proxy = test_proxy("a", "127.0.0.1:8080");
+        let now = Instant::now();
+
+        assert!(table.is_available_at(&proxy, now));
+        table.record_failure_at(&proxy, now);
+        assert!(table.is_available_at(&proxy, now));
+        table.record_failure_at(&proxy, now);
+        assert!(!table.is_available_at(&proxy, now));
+        assert!(!table.execute(&proxy));
+
+        // re-admitted after the backoff, ejected again on first failure with doubled backoff
+        let now = now + Duration::from_secs(10);
+        assert!(table.is_available_at(&proxy, now));
+        table.record_failure_at(&proxy, now);
+        assert!(!table.is_available_at(&proxy, now + Duration::from_secs(19)));
+        assert!(table.is_available_at(&proxy, now + Duration::from_secs(20)));
+
+        // backoff is capped
+        let now = now + Duration::from_secs(20);
+        table.record_failure_at(&proxy, now);
+        assert!(table.is_available_at(&proxy, now + Duration::from_secs(25)));
+        assert_eq!(table.health(&proxy_id("a")).unwrap().ejections, 3);
+
+        // a success resets the state
+        table.record_success(&proxy, Duration::from_millis(100));
+        let health = table.health(&proxy_id("a")).unwrap();
+        assert_eq!(health.ejections, 0);
+        assert!(!health.is_ejected());
+        assert_eq!(health.latency, Some(Duration::from_millis(100)));
+        assert_eq!((health.successes, health.failures), (1, 4));
+    }
+
+    #[test]
+    fn test_proxy_health_table_reload() {
+        let table = ProxyHealthTable::new().with_failure_threshold(1);
+        let (a, b) = (
+            test_proxy("a", "127.0.0.1:8080"),
+            test_proxy("b", "127.0.0.1:8081"),
+        );
+        table.record_failure(&a);
+        table.record_failure(&b);
+
+        // unchanged ids survive, changed or removed ones are dropped
+        let a_moved = test_proxy("a", "127.0.0.1:9090");
+        table.retain_proxies([&b]);
+        assert!(table.health(&proxy_id("a")).is_none());
+        assert!(!table.is_available(&b));
+        assert!(table.is_available(&a_moved));
+
+        // the same id for another authority starts over
+        table.record_failure(&b);
+        table.record_success(&test_proxy("b", "127.0.0.1:9091"), Duration::ZERO);
+        assert_eq!(table.snapshot().len(), 1);
+        assert_eq!(table.health(&proxy_id("b")).unwrap().failures, 0);
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct MockHealthCheck(Arc<AtomicUsize>);
+
+    impl ProxyHealthCheck for MockHealthCheck {
+        async fn check(&self, proxy: &Proxy) -> Result<(), BoxError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            match proxy.id.as_str() {
+                "ok" => Ok(()),
+                "slow" => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(())
+                }
+                _ => Err(OpaqueError::from_display("probe failed").into()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_health_checker() {
+        let table = ProxyHealthTable::new().with_failure_threshold(2);
+        let check = MockHealthCheck::default();
+        let checker = ProxyHealthChecker::new(check.clone(), table.clone())
+            .with_timeout(Duration::from_millis(50))
+            .with_concurrency(2);
+        let proxies = vec![
+            test_proxy("ok", "127.0.0.1:8080"),
+            test_proxy("fail", "127.0.0.1:8081"),
+            test_proxy("slow", "127.0.0.1:8082"),
+        ];
+
+        checker.check_once(&proxies).await;
+        checker.check_once(&proxies).await;
+        assert_eq!(check.0.load(Ordering::SeqCst), 6);
+        assert!(table.is_available(&proxies[0]));
+        assert!(!table.is_available(&proxies[1]));
+        assert!(!table.is_available(&proxies[2]));
+        assert_eq!(table.health(&proxy_id("ok")).unwrap().successes, 2);
+
+        // ejected proxies are not probed
+        checker.check_once(&proxies).await;
+        assert_eq!(check.0.load(Ordering::SeqCst), 7);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_health_check() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = test_proxy("a", &addr.to_string());
+        TcpHealthCheck::new().check(&proxy).await.unwrap();
+
+        // the connector is used to connect, e.g. to guard the egress
+        let guarded =
+            TcpHealthCheck::new().with_connector(EgressGuardConnector::new(EgressPolicy::new()));
+        let err = guarded.check(&proxy).await.unwrap_err();
+        assert!(err.to_string().contains("tcp health check"), "{err}");
+
+        drop(listener);
+        assert!(TcpHealthCheck::new().check(&proxy).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_service_health_check() {
+        let check = ServiceHealthCheck::new(
+            service_fn(async |ctx: Context<()>, path: &'static str| {
+                let address = ctx.get::<ProxyAddress>().unwrap();
+                if address.authority.port() == 8080 && path == "/health" {
+                    Ok(())
+                } else {
+                    Err(OpaqueError::from_display("unhealthy"))
+                }
+            }),
+            || "/health",
+        );
+        check
+            .check(&test_proxy("a", "127.0.0.1:8080"))
+            .await
+            .unwrap();
+        assert!(
+            check
+                .check(&test_proxy("b", "127.0.0.1:8081"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxy_health_layer() {
+        let table = ProxyHealthTable::new().with_failure_threshold(1);
+        let service = ProxyHealthLayer::new(table.clone()).layer(service_fn(
+            async |_ctx: Context<()>, fail: bool| {
+                if fail {
+                    Err(OpaqueError::from_display("connect error"))
+                } else {
+                    Ok::<_, OpaqueError>(())
+                }
+            },
+        ));
+
+        // no proxy in the context, nothing to record
+        service.serve(Context::default(), true).await.unwrap_err();
+        assert!(table.snapshot().is_empty());
+
+        let (a, b) = (
+            test_proxy("a", "127.0.0.1:8080"),
+            test_proxy("b", "127.0.0.1:8081"),
+        );
+        let mut ctx = Context::default();
+        ctx.insert(a.clone());
+        service.serve(ctx, false).await.unwrap();
+        let mut ctx = Context::default();
+        ctx.insert(b.clone());
+        service.serve(ctx, true).await.unwrap_err();
+
+        assert!(table.is_available(&a));
+        assert!(!table.is_available(&b));
+    }
+}
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "memory-db")]
 use venndb::VennDB;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "memory-db", derive(VennDB))]
 #[cfg_attr(feature = "memory-db", venndb(validator = proxydb_insert_validator))]
 /// The selected proxy to use to connect to the proxy.
@@ -74,9 +74,14 @@ pub struct Proxy {
     pub asn: Option<Asn>,
 }
 
-#[cfg(feature = "memory-db")]
+#[cfg(any(
+    feature = "memory-db",
+    feature = "csv",
+    feature = "json",
+    feature = "yaml"
+))]
 /// Validate the proxy is valid according to rules that are not enforced by the type system.
-fn proxydb_insert_validator(proxy: &Proxy) -> bool {
+pub(super) fn proxydb_insert_validator(proxy: &Proxy) -> bool {
     (proxy.datacenter || proxy.residential || proxy.mobile)
         && (((proxy.http || proxy.https) && proxy.tcp)
             || ((proxy.socks5 || proxy.socks5h) && (proxy.tcp || proxy.udp)))
@@ -1,4 +1,7 @@
-use super::{Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyQueryPredicate};
+use super::{
+    Proxy, ProxyCandidates, ProxyContext, ProxyDB, ProxyFilter, ProxyID, ProxyQueryPredicate,
+    ProxySelector, ProxySessionID, ProxySessionTable, session::SessionSelector,
+};
 use rama_core::{
     Context, Layer, Service,
     error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
@@ -7,7 +10,7 @@ use rama_net::{
     Protocol,
     address::ProxyAddress,
     transport::{TransportProtocol, TryRefIntoTransportContext},
-    user::{Basic, ProxyCredential},
+    user::{Basic, ProxyCredential, UserId},
 };
 use rama_utils::macros::define_inner_service_accessors;
 use std::fmt;
@@ -20,14 +23,20 @@ use std::fmt;
 /// A predicate can be used to provide additional filtering on the found proxies,
 /// that otherwise did match the used [`ProxyFilter`].
 ///
+/// A [`ProxySelector`] can be used to define how a proxy is selected
+/// from all proxies that match, by default a random one is selected.
+///
 /// See [the crate docs](crate) for examples and more info on the usage of this service.
 ///
 /// [`Proxy`]: crate::Proxy
-pub struct ProxyDBService<S, D, P, F> {
+pub struct ProxyDBService<S, D, P, F, R = ()> {
     inner: S,
     db: D,
     mode: ProxyFilterMode,
     predicate: P,
+    selector: R,
+    sessions: Option<ProxySessionTable>,
+    failover: usize,
     username_formatter: F,
     preserve: bool,
 }
@@ -50,12 +59,13 @@ pub enum ProxyFilterMode {
     Fallback(ProxyFilter),
 }
 
-impl<S, D, P, F> fmt::Debug for ProxyDBService<S, D, P, F>
+impl<S, D, P, F, R> fmt::Debug for ProxyDBService<S, D, P, F, R>
 where
     S: fmt::Debug,
     D: fmt::Debug,
     P: fmt::Debug,
     F: fmt::Debug,
+    R: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ProxyDBService")
@@ -63,18 +73,22 @@ where
             .field("db", &self.db)
             .field("mode", &self.mode)
             .field("predicate", &self.predicate)
+            .field("selector", &self.selector)
+            .field("sessions", &self.sessions)
+            .field("failover", &self.failover)
             .field("username_formatter", &self.username_formatter)
             .field("preserve", &self.preserve)
             .finish()
     }
 }
 
-impl<S, D, P, F> Clone for ProxyDBService<S, D, P, F>
+impl<S, D, P, F, R> Clone for ProxyDBService<S, D, P, F, R>
 where
     S: Clone,
     D: Clone,
     P: Clone,
     F: Clone,
+    R: Clone,
 {
     fn clone(&self) -> Self {
         Self {
@@ -82,6 +96,9 @@ where
             db: self.db.clone(),
             mode: self.mode.clone(),
             predicate: self.predicate.clone(),
+            selector: self.selector.clone(),
+            sessions: self.sessions.clone(),
+            failover: self.failover,
             username_formatter: self.username_formatter.clone(),
             preserve: self.preserve,
         }
@@ -96,13 +113,16 @@ impl<S, D> ProxyDBService<S, D, bool, ()> {
             db,
             mode: ProxyFilterMode::Optional,
             predicate: true,
+            selector: (),
+            sessions: None,
+            failover: 1,
             username_formatter: (),
             preserve: false,
         }
     }
 }
 
-impl<S, D, P, F> ProxyDBService<S, D, P, F> {
+impl<S, D, P, F, R> ProxyDBService<S, D, P, F, R> {
     /// Set a [`ProxyFilterMode`] to define the behaviour surrounding
     /// [`ProxyFilter`] usage, e.g. if a proxy filter is required to be available or not,
     /// or what to do if it is optional and not available.
@@ -144,27 +164,117 @@ impl<S, D, P, F> ProxyDBService<S, D, P, F> {
     /// Set a [`ProxyQueryPredicate`] that will be used
     /// to possibly filter out proxies that according to the filters are correct,
     /// but not according to the predicate.
-    pub fn select_predicate<Predicate>(self, p: Predicate) -> ProxyDBService<S, D, Predicate, F> {
+    pub fn select_predicate<Predicate>(
+        self,
+        p: Predicate,
+    ) -> ProxyDBService<S, D, Predicate, F, R> {
         ProxyDBService {
             inner: self.inner,
             db: self.db,
             mode: self.mode,
             predicate: p,
+            selector: self.selector,
+            sessions: self.sessions,
+            failover: self.failover,
+            username_formatter: self.username_formatter,
+            preserve: self.preserve,
+        }
+    }
+
+    /// Set a [`ProxySelector`] that will be used to select the proxy
+    /// from all proxies matching the filter and predicate,
+    /// e.g. a [`RoundRobinSelector`] or a [`WeightedSelector`].
+    ///
+    /// By default a random proxy is selected.
+    ///
+    /// [`RoundRobinSelector`]: crate::RoundRobinSelector
+    /// [`WeightedSelector`]: crate::WeightedSelector
+    pub fn select_with<Selector>(self, selector: Selector) -> ProxyDBService<S, D, P, F, Selector> {
+        ProxyDBService {
+            inner: self.inner,
+            db: self.db,
+            mode: self.mode,
+            predicate: self.predicate,
+            selector,
+            sessions: self.sessions,
+            failover: self.failover,
             username_formatter: self.username_formatter,
             preserve: self.preserve,
         }
     }
 
+    /// Enable sticky sessions using the given [`ProxySessionTable`].
+    ///
+    /// In case a [`ProxySessionID`] is found in the [`Context`]
+    /// (e.g. parsed by the [`ProxySessionUsernameParser`]) the proxy selected for that
+    /// session is selected again, for as long as the session is not expired,
+    /// the [`ProxyFilter`] remains the same and the proxy is still available.
+    /// Otherwise a proxy is selected deterministically for the session,
+    /// in which case the [`ProxySelector`] is not used.
+    ///
+    /// [`ProxySessionUsernameParser`]: crate::ProxySessionUsernameParser
+    pub fn sticky_sessions(mut self, sessions: ProxySessionTable) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Enable sticky sessions using the given [`ProxySessionTable`].
+    ///
+    /// In case a [`ProxySessionID`] is found in the [`Context`]
+    /// (e.g. parsed by the [`ProxySessionUsernameParser`]) the proxy selected for that
+    /// session is selected again, for as long as the session is not expired,
+    /// the [`ProxyFilter`] remains the same and the proxy is still available.
+    /// Otherwise a proxy is selected deterministically for the session,
+    /// in which case the [`ProxySelector`] is not used.
+    ///
+    /// [`ProxySessionUsernameParser`]: crate::ProxySessionUsernameParser
+    pub fn set_sticky_sessions(&mut self, sessions: ProxySessionTable) -> &mut Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Select up to `candidates` distinct proxies (including the primary one)
+    /// matching the same [`ProxyFilter`] and predicate, such that a connector
+    /// can fail over to the next candidate in case it fails to connect via a proxy.
+    ///
+    /// The additional candidates are inserted as [`ProxyCandidates`] in the [`Context`],
+    /// to be used by the [`ProxyFailoverConnector`]. By default only a single proxy is selected.
+    ///
+    /// [`ProxyFailoverConnector`]: crate::ProxyFailoverConnector
+    pub fn failover(mut self, candidates: usize) -> Self {
+        self.failover = candidates.max(1);
+        self
+    }
+
+    /// Select up to `candidates` distinct proxies (including the primary one)
+    /// matching the same [`ProxyFilter`] and predicate, such that a connector
+    /// can fail over to the next candidate in case it fails to connect via a proxy.
+    ///
+    /// The additional candidates are inserted as [`ProxyCandidates`] in the [`Context`],
+    /// to be used by the [`ProxyFailoverConnector`]. By default only a single proxy is selected.
+    ///
+    /// [`ProxyFailoverConnector`]: crate::ProxyFailoverConnector
+    pub fn set_failover(&mut self, candidates: usize) -> &mut Self {
+        self.failover = candidates.max(1);
+        self
+    }
+
     /// Set a [`UsernameFormatter`][crate::UsernameFormatter] that will be used to format
     /// the username based on the selected [`Proxy`]. This is required
     /// in case the proxy is a router that accepts or maybe even requires
     /// username labels to configure proxies further down/up stream.
-    pub fn username_formatter<Formatter>(self, f: Formatter) -> ProxyDBService<S, D, P, Formatter> {
+    pub fn username_formatter<Formatter>(
+        self,
+        f: Formatter,
+    ) -> ProxyDBService<S, D, P, Formatter, R> {
         ProxyDBService {
             inner: self.inner,
             db: self.db,
             mode: self.mode,
             predicate: self.predicate,
+            selector: self.selector,
+            sessions: self.sessions,
+            failover: self.failover,
             username_formatter: f,
             preserve: self.preserve,
         }
@@ -173,11 +283,12 @@ impl<S, D, P, F> ProxyDBService<S, D, P, F> {
     define_inner_service_accessors!();
 }
 
-impl<S, D, P, F, State, Request> Service<State, Request> for ProxyDBService<S, D, P, F>
+impl<S, D, P, F, R, State, Request> Service<State, Request> for ProxyDBService<S, D, P, F, R>
 where
     S: Service<State, Request, Error: Into<BoxError> + Send + Sync + 'static>,
     D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
     P: ProxyQueryPredicate,
+    R: ProxySelector,
     F: UsernameFormatter<State>,
     State: Clone + Send + Sync + 'static,
     Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
@@ -221,92 +332,260 @@ where
                 .into();
             let transport_protocol = proxy_ctx.protocol;
 
-            let proxy = self
-                .db
-                .get_proxy_if(proxy_ctx, filter.clone(), self.predicate.clone())
-                .await
-                .map_err(|err| {
-                    OpaqueError::from_std(ProxySelectError {
-                        inner: err.into(),
-                        filter: filter.clone(),
-                    })
-                })?;
-
-            let mut proxy_address = proxy.address.clone();
-
-            // prepare the credential with labels in username if desired
-            proxy_address.credential = proxy_address.credential.take().map(|credential| {
-                match credential {
-                    ProxyCredential::Basic(ref basic) => {
-                        match self.username_formatter.fmt_username(
-                            &ctx,
-                            &proxy,
-                            &filter,
-                            basic.username(),
-                        ) {
-                            Some(username) => ProxyCredential::Basic(Basic::new(
-                                username,
-                                basic.password().to_owned(),
-                            )),
-                            None => credential, // nothing to do
-                        }
+            let session = self
+                .sessions
+                .as_ref()
+                .zip(ctx.get::<ProxySessionID>().cloned());
+            let proxy = match session {
+                Some((sessions, session)) => {
+                    self.select_session_proxy(
+                        sessions,
+                        ctx.get::<UserId>().cloned(),
+                        session,
+                        proxy_ctx.clone(),
+                        &filter,
+                    )
+                    .await
+                }
+                None => {
+                    self.db
+                        .select_proxy_if(
+                            proxy_ctx.clone(),
+                            filter.clone(),
+                            self.predicate.clone(),
+                            self.selector.clone(),
+                        )
+                        .await
+                }
+            }
+            .map_err(|err| {
+                OpaqueError::from_std(ProxySelectError {
+                    inner: err.into(),
+                    filter: filter.clone(),
+                })
+            })?;
+
+            let proxy_address =
+                self.prepare_proxy_address(&ctx, &proxy, &filter, transport_protocol)?;
+
+            if self.failover > 1 {
+                let candidates = self
+                    .select_failover_candidates(&ctx, &proxy, proxy_ctx, &filter)
+                    .await?;
+                if candidates.is_empty() {
+                    ctx.remove::<ProxyCandidates>();
+                } else {
+                    ctx.insert(candidates);
+                }
+            }
+
+            // insert proxy address in context so it will be used
+            ctx.insert(proxy_address);
+
+            // insert the id of the selected proxy
+            ctx.insert(super::ProxyID::from(proxy.id.clone()));
+
+            // insert the entire proxy also in there, for full "Context"
+            ctx.insert(proxy);
+        }
+
+        self.inner.serve(ctx, req).await.map_err(Into::into)
+    }
+}
+
+impl<S, D, P, F, R> ProxyDBService<S, D, P, F, R>
+where
+    D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
+    P: ProxyQueryPredicate,
+    R: ProxySelector,
+{
+    /// Prepare the [`ProxyAddress`] to connect to the given [`Proxy`] with,
+    /// formatting the username and defining the protocol if not defined yet.
+    fn prepare_proxy_address<State>(
+        &self,
+        ctx: &Context<State>,
+        proxy: &Proxy,
+        filter: &ProxyFilter,
+        transport_protocol: TransportProtocol,
+    ) -> Result<ProxyAddress, BoxError>
+    where
+        F: UsernameFormatter<State>,
+    {
+        let mut proxy_address = proxy.address.clone();
+
+        // prepare the credential with labels in username if desired
+        proxy_address.credential = proxy_address.credential.take().map(|credential| {
+            match credential {
+                ProxyCredential::Basic(ref basic) => {
+                    match self
+                        .username_formatter
+                        .fmt_username(ctx, proxy, filter, basic.username())
+                    {
+                        Some(username) => ProxyCredential::Basic(Basic::new(
+                            username,
+                            basic.password().to_owned(),
+                        )),
+                        None => credential, // nothing to do
                     }
-                    ProxyCredential::Bearer(_) => credential, // Remark: we can support this in future too if needed
                 }
-            });
+                ProxyCredential::Bearer(_) => credential, // Remark: we can support this in future too if needed
+            }
+        });
 
-            // overwrite the proxy protocol if not set yet
-            if proxy_address.protocol.is_none() {
-                proxy_address.protocol = match transport_protocol {
-                    TransportProtocol::Udp => {
+        // overwrite the proxy protocol if not set yet
+        if proxy_address.protocol.is_none() {
+            proxy_address.protocol = match transport_protocol {
+                TransportProtocol::Udp => {
+                    if proxy.socks5 {
+                        Some(Protocol::SOCKS5)
+                    } else if proxy.socks5h {
+                        Some(Protocol::SOCKS5H)
+                    } else {
+                        return Err(OpaqueError::from_display(
+                            "selected udp proxy does not have a valid protocol available (db bug?!)",
+                        )
+                        .into());
+                    }
+                }
+                TransportProtocol::Tcp => match proxy_address.authority.port() {
+                    80 | 8080 if proxy.http => Some(Protocol::HTTP),
+                    443 | 8443 if proxy.https => Some(Protocol::HTTPS),
+                    1080 if proxy.socks5 => Some(Protocol::SOCKS5),
+                    1080 if proxy.socks5h => Some(Protocol::SOCKS5H),
+                    _ => {
+                        // speed: Socks5 > Http > Https
                         if proxy.socks5 {
                             Some(Protocol::SOCKS5)
                         } else if proxy.socks5h {
                             Some(Protocol::SOCKS5H)
+                        } else if proxy.http {
+                            Some(Protocol::HTTP)
+                        } else if proxy.https {
+                            Some(Protocol::HTTPS)
                         } else {
                             return Err(OpaqueError::from_display(
-                                "selected udp proxy does not have a valid protocol available (db bug?!)",
-                            )
-                            .into());
-                        }
-                    }
-                    TransportProtocol::Tcp => match proxy_address.authority.port() {
-                        80 | 8080 if proxy.http => Some(Protocol::HTTP),
-                        443 | 8443 if proxy.https => Some(Protocol::HTTPS),
-                        1080 if proxy.socks5 => Some(Protocol::SOCKS5),
-                        1080 if proxy.socks5h => Some(Protocol::SOCKS5H),
-                        _ => {
-                            // speed: Socks5 > Http > Https
-                            if proxy.socks5 {
-                                Some(Protocol::SOCKS5)
-                            } else if proxy.socks5h {
-                                Some(Protocol::SOCKS5H)
-                            } else if proxy.http {
-                                Some(Protocol::HTTP)
-                            } else if proxy.https {
-                                Some(Protocol::HTTPS)
-                            } else {
-                                return Err(OpaqueError::from_display(
                                 "selected tcp proxy does not have a valid protocol available (db bug?!)",
                             )
                             .into());
-                            }
                         }
-                    },
-                };
-            }
+                    }
+                },
+            };
+        }
 
-            // insert proxy address in context so it will be used
-            ctx.insert(proxy_address);
+        Ok(proxy_address)
+    }
 
-            // insert the id of the selected proxy
-            ctx.insert(super::ProxyID::from(proxy.id.clone()));
+    /// Select the failover candidates for the given (primary) proxy,
+    /// distinct from it and from one another.
+    async fn select_failover_candidates<State>(
+        &self,
+        ctx: &Context<State>,
+        proxy: &Proxy,
+        proxy_ctx: ProxyContext,
+        filter: &ProxyFilter,
+    ) -> Result<ProxyCandidates, BoxError>
+    where
+        F: UsernameFormatter<State>,
+    {
+        let transport_protocol = proxy_ctx.protocol;
+        let mut selected = vec![proxy.id.clone()];
+        let mut candidates = ProxyCandidates::new();
+
+        while selected.len() < self.failover {
+            let predicate = {
+                let predicate = self.predicate.clone();
+                let selected = selected.clone();
+                move |proxy: &Proxy| !selected.contains(&proxy.id) && predicate.execute(proxy)
+            };
+            let candidate = match self
+                .db
+                .select_proxy_if(
+                    proxy_ctx.clone(),
+                    filter.clone(),
+                    predicate,
+                    self.selector.clone(),
+                )
+                .await
+            {
+                Ok(candidate) => candidate,
+                Err(err) => {
+                    let err: BoxError = err.into();
+                    tracing::debug!(
+                        candidates = selected.len(),
+                        %err,
+                        "proxydb: no more failover candidates available",
+                    );
+                    break;
+                }
+            };
+            let address =
+                self.prepare_proxy_address(ctx, &candidate, filter, transport_protocol)?;
+            selected.push(candidate.id.clone());
+            candidates.push(candidate, address);
+        }
 
-            // insert the entire proxy also in there, for full "Context"
-            ctx.insert(proxy);
+        Ok(candidates)
+    }
+}
+
+impl<S, D, P, F, R> ProxyDBService<S, D, P, F, R>
+where
+    D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
+    P: ProxyQueryPredicate,
+{
+    /// Select the proxy for the given session,
+    /// reusing the proxy previously selected for it if still possible.
+    async fn select_session_proxy(
+        &self,
+        sessions: &ProxySessionTable,
+        user: Option<UserId>,
+        session: ProxySessionID,
+        proxy_ctx: ProxyContext,
+        filter: &ProxyFilter,
+    ) -> Result<Proxy, D::Error> {
+        if let Some(proxy_id) = sessions.get(user.as_ref(), &session, filter) {
+            let sticky_filter = ProxyFilter {
+                id: Some(proxy_id.0.clone()),
+                ..filter.clone()
+            };
+            match self
+                .db
+                .select_proxy_if(proxy_ctx.clone(), sticky_filter, self.predicate.clone(), ())
+                .await
+            {
+                Ok(proxy) => {
+                    sessions.insert(user, session, filter.clone(), proxy_id);
+                    return Ok(proxy);
+                }
+                Err(err) => {
+                    let err: BoxError = err.into();
+                    tracing::debug!(
+                        %session,
+                        %proxy_id,
+                        %err,
+                        "proxydb: sticky session proxy no longer available: re-select",
+                    );
+                }
+            }
         }
 
-        self.inner.serve(ctx, req).await.map_err(Into::into)
+        let proxy = self
+            .db
+            .select_proxy_if(
+                proxy_ctx,
+                filter.clone(),
+                self.predicate.clone(),
+                SessionSelector::new(user.as_ref(), &session),
+            )
+            .await?;
+        sessions.insert(
+            user,
+            session,
+            filter.clone(),
+            ProxyID::from(proxy.id.clone()),
+        );
+        Ok(proxy)
     }
 }
 
@@ -336,42 +615,53 @@ impl std::error::Error for ProxySelectError {
 /// and insert, if a [`Proxy`] is selected, it in the [`Context`] for further processing.
 ///
 /// See [the crate docs](crate) for examples and more info on the usage of this service.
-pub struct ProxyDBLayer<D, P, F> {
+pub struct ProxyDBLayer<D, P, F, R = ()> {
     db: D,
     mode: ProxyFilterMode,
     predicate: P,
+    selector: R,
+    sessions: Option<ProxySessionTable>,
+    failover: usize,
     username_formatter: F,
     preserve: bool,
 }
 
-impl<D, P, F> fmt::Debug for ProxyDBLayer<D, P, F>
+impl<D, P, F, R> fmt::Debug for ProxyDBLayer<D, P, F, R>
 where
     D: fmt::Debug,
     P: fmt::Debug,
     F: fmt::Debug,
+    R: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ProxyDBLayer")
             .field("db", &self.db)
             .field("mode", &self.mode)
             .field("predicate", &self.predicate)
+            .field("selector", &self.selector)
+            .field("sessions", &self.sessions)
+            .field("failover", &self.failover)
             .field("username_formatter", &self.username_formatter)
             .field("preserve", &self.preserve)
             .finish()
     }
 }
 
-impl<D, P, F> Clone for ProxyDBLayer<D, P, F>
+impl<D, P, F, R> Clone for ProxyDBLayer<D, P, F, R>
 where
     D: Clone,
     P: Clone,
     F: Clone,
+    R: Clone,
 {
     fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
             mode: self.mode.clone(),
             predicate: self.predicate.clone(),
+            selector: self.selector.clone(),
+            sessions: self.sessions.clone(),
+            failover: self.failover,
             username_formatter: self.username_formatter.clone(),
             preserve: self.preserve,
         }
@@ -385,13 +675,16 @@ impl<D> ProxyDBLayer<D, bool, ()> {
             db,
             mode: ProxyFilterMode::Optional,
             predicate: true,
+            selector: (),
+            sessions: None,
+            failover: 1,
             username_formatter: (),
             preserve: false,
         }
     }
 }
 
-impl<D, P, F> ProxyDBLayer<D, P, F> {
+impl<D, P, F, R> ProxyDBLayer<D, P, F, R> {
     /// Set a [`ProxyFilterMode`] to define the behaviour surrounding
     /// [`ProxyFilter`] usage, e.g. if a proxy filter is required to be available or not,
     /// or what to do if it is optional and not available.
@@ -414,38 +707,122 @@ impl<D, P, F> ProxyDBLayer<D, P, F> {
     /// Set a [`ProxyQueryPredicate`] that will be used
     /// to possibly filter out proxies that according to the filters are correct,
     /// but not according to the predicate.
-    pub fn select_predicate<Predicate>(self, p: Predicate) -> ProxyDBLayer<D, Predicate, F> {
+    pub fn select_predicate<Predicate>(self, p: Predicate) -> ProxyDBLayer<D, Predicate, F, R> {
         ProxyDBLayer {
             db: self.db,
             mode: self.mode,
             predicate: p,
+            selector: self.selector,
+            sessions: self.sessions,
+            failover: self.failover,
+            username_formatter: self.username_formatter,
+            preserve: self.preserve,
+        }
+    }
+
+    /// Set a [`ProxySelector`] that will be used to select the proxy
+    /// from all proxies matching the filter and predicate,
+    /// e.g. a [`RoundRobinSelector`] or a [`WeightedSelector`].
+    ///
+    /// By default a random proxy is selected.
+    ///
+    /// [`RoundRobinSelector`]: crate::RoundRobinSelector
+    /// [`WeightedSelector`]: crate::WeightedSelector
+    pub fn select_with<Selector>(self, selector: Selector) -> ProxyDBLayer<D, P, F, Selector> {
+        ProxyDBLayer {
+            db: self.db,
+            mode: self.mode,
+            predicate: self.predicate,
+            selector,
+            sessions: self.sessions,
+            failover: self.failover,
             username_formatter: self.username_formatter,
             preserve: self.preserve,
         }
     }
 
+    /// Enable sticky sessions using the given [`ProxySessionTable`].
+    ///
+    /// In case a [`ProxySessionID`] is found in the [`Context`]
+    /// (e.g. parsed by the [`ProxySessionUsernameParser`]) the proxy selected for that
+    /// session is selected again, for as long as the session is not expired,
+    /// the [`ProxyFilter`] remains the same and the proxy is still available.
+    /// Otherwise a proxy is selected deterministically for the session,
+    /// in which case the [`ProxySelector`] is not used.
+    ///
+    /// [`ProxySessionUsernameParser`]: crate::ProxySessionUsernameParser
+    pub fn sticky_sessions(mut self, sessions: ProxySessionTable) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Enable sticky sessions using the given [`ProxySessionTable`].
+    ///
+    /// In case a [`ProxySessionID`] is found in the [`Context`]
+    /// (e.g. parsed by the [`ProxySessionUsernameParser`]) the proxy selected for that
+    /// session is selected again, for as long as the session is not expired,
+    /// the [`ProxyFilter`] remains the same and the proxy is still available.
+    /// Otherwise a proxy is selected deterministically for the session,
+    /// in which case the [`ProxySelector`] is not used.
+    ///
+    /// [`ProxySessionUsernameParser`]: crate::ProxySessionUsernameParser
+    pub fn set_sticky_sessions(&mut self, sessions: ProxySessionTable) -> &mut Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Select up to `candidates` distinct proxies (including the primary one)
+    /// matching the same [`ProxyFilter`] and predicate, such that a connector
+    /// can fail over to the next candidate in case it fails to connect via a proxy.
+    ///
+    /// The additional candidates are inserted as [`ProxyCandidates`] in the [`Context`],
+    /// to be used by the [`ProxyFailoverConnector`]. By default only a single proxy is selected.
+    ///
+    /// [`ProxyFailoverConnector`]: crate::ProxyFailoverConnector
+    pub fn failover(mut self, candidates: usize) -> Self {
+        self.failover = candidates.max(1);
+        self
+    }
+
+    /// Select up to `candidates` distinct proxies (including the primary one)
+    /// matching the same [`ProxyFilter`] and predicate, such that a connector
+    /// can fail over to the next candidate in case it fails to connect via a proxy.
+    ///
+    /// The additional candidates are inserted as [`ProxyCandidates`] in the [`Context`],
+    /// to be used by the [`ProxyFailoverConnector`]. By default only a single proxy is selected.
+    ///
+    /// [`ProxyFailoverConnector`]: crate::ProxyFailoverConnector
+    pub fn set_failover(&mut self, candidates: usize) -> &mut Self {
+        self.failover = candidates.max(1);
+        self
+    }
+
     /// Set a [`UsernameFormatter`][crate::UsernameFormatter] that will be used to format
     /// the username based on the selected [`Proxy`]. This is required
     /// in case the proxy is a router that accepts or maybe even requires
     /// username labels to configure proxies further down/up stream.
-    pub fn username_formatter<Formatter>(self, f: Formatter) -> ProxyDBLayer<D, P, Formatter> {
+    pub fn username_formatter<Formatter>(self, f: Formatter) -> ProxyDBLayer<D, P, Formatter, R> {
         ProxyDBLayer {
             db: self.db,
             mode: self.mode,
             predicate: self.predicate,
+            selector: self.selector,
+            sessions: self.sessions,
+            failover: self.failover,
             username_formatter: f,
             preserve: self.preserve,
         }
     }
 }
 
-impl<S, D, P, F> Layer<S> for ProxyDBLayer<D, P, F>
+impl<S, D, P, F, R> Layer<S> for ProxyDBLayer<D, P, F, R>
 where
     D: Clone,
     P: Clone,
     F: Clone,
+    R: Clone,
 {
-    type Service = ProxyDBService<S, D, P, F>;
+    type Service = ProxyDBService<S, D, P, F, R>;
 
     fn layer(&self, inner: S) -> Self::Service {
         ProxyDBService {
@@ -453,6 +830,9 @@ where
             db: self.db.clone(),
             mode: self.mode.clone(),
             predicate: self.predicate.clone(),
+            selector: self.selector.clone(),
+            sessions: self.sessions.clone(),
+            failover: self.failover,
             username_formatter: self.username_formatter.clone(),
             preserve: self.preserve,
         }
@@ -1171,4 +1551,168 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_proxy_db_service_select_with_round_robin() {
+        let db = memproxydb().await;
+
+        let service = ProxyDBLayer::new(Arc::new(db))
+            .filter_mode(ProxyFilterMode::Default)
+            .select_predicate(|proxy: &Proxy| proxy.mobile)
+            .select_with(crate::RoundRobinSelector::new())
+            .layer(service_fn(|ctx: Context<()>, _: Request| async move {
+                Ok::<_, Infallible>(ctx.get::<crate::ProxyID>().unwrap().clone())
+            }));
+
+        let mut ids = Vec::new();
+        for _ in 0..64 {
+            let req = Request::builder()
+                .version(Version::HTTP_11)
+                .method("GET")
+                .uri("http://example.com")
+                .body(Body::empty())
+                .unwrap();
+            ids.push(service.serve(Context::default(), req).await.unwrap());
+        }
+
+        let unique: Vec<_> = ids.iter().unique().collect();
+        assert!(unique.len() > 1);
+        // the same cycle of proxies repeats itself
+        let cycle = unique.len();
+        assert_eq!(ids[..ids.len() - cycle], ids[cycle..]);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_db_service_failover_candidates() {
+        let db = memproxydb().await;
+
+        let service = ProxyDBLayer::new(Arc::new(db))
+            .filter_mode(ProxyFilterMode::Default)
+            .select_predicate(|proxy: &Proxy| proxy.mobile)
+            .failover(4)
+            .layer(service_fn(|ctx: Context<()>, _: Request| async move {
+                Ok::<_, Infallible>((
+                    ctx.get::<Proxy>().unwrap().clone(),
+                    ctx.get::<ProxyCandidates>().unwrap().clone(),
+                ))
+            }));
+
+        for _ in 0..16 {
+            let req = Request::builder()
+                .version(Version::HTTP_11)
+                .method("GET")
+                .uri("http://example.com")
+                .body(Body::empty())
+                .unwrap();
+            let (proxy, candidates) = service.serve(Context::default(), req).await.unwrap();
+            assert_eq!(candidates.len(), 3);
+
+            let ids: Vec<_> = std::iter::once(&proxy)
+                .chain(candidates.iter().map(|(proxy, _)| proxy))
+                .map(|proxy| proxy.id.clone())
+                .collect();
+            assert_eq!(ids.iter().unique().count(), 4);
+            for (proxy, address) in candidates.iter() {
+                assert!(proxy.mobile);
+                assert_eq!(address.authority, proxy.address.authority);
+                assert!(address.protocol.is_some());
+            }
+        }
+    }
+
+    #[cfg(feature = "live-update")]
+    #[tokio::test]
+    async fn test_proxy_db_service_sticky_sessions() {
+        let (db, db_setter) = crate::proxy_db_updater();
+        db_setter.set(memproxydb().await);
+
+        let service = ProxyDBLayer::new(db)
+            .filter_mode(ProxyFilterMode::Default)
+            .sticky_sessions(ProxySessionTable::new())
+            .layer(service_fn(|ctx: Context<()>, _: Request| async move {
+                Ok::<_, Infallible>(ctx.get::<Proxy>().unwrap().clone())
+            }));
+
+        let session_ctx = |session: &'static str, filter: Option<ProxyFilter>| {
+            let mut ctx = Context::default();
+            ctx.insert(ProxySessionID::from(NonEmptyString::from_static(session)));
+            if let Some(filter) = filter {
+                ctx.insert(filter);
+            }
+            ctx
+        };
+        let req = || {
+            Request::builder()
+                .version(Version::HTTP_11)
+                .method("GET")
+                .uri("http://example.com")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // same proxy for all requests of the session
+        let proxy = service
+            .serve(session_ctx("abc123", None), req())
+            .await
+            .unwrap();
+        for _ in 0..16 {
+            let sticky_proxy = service
+                .serve(session_ctx("abc123", None), req())
+                .await
+                .unwrap();
+            assert_eq!(sticky_proxy.id, proxy.id);
+        }
+
+        // a different filter results in a re-selection matching that filter
+        let filter = ProxyFilter {
+            country: Some(vec![StringFilter::new("BE")]),
+            ..Default::default()
+        };
+        let filtered_proxy = service
+            .serve(session_ctx("abc123", Some(filter.clone())), req())
+            .await
+            .unwrap();
+        assert_eq!(filtered_proxy.country, Some("BE".into()));
+        for _ in 0..16 {
+            let sticky_proxy = service
+                .serve(session_ctx("abc123", Some(filter.clone())), req())
+                .await
+                .unwrap();
+            assert_eq!(sticky_proxy.id, filtered_proxy.id);
+        }
+
+        // a proxy disappearing results in a deterministic re-selection
+        let mut reader = ProxyCsvRowReader::raw(RAW_CSV_DATA);
+        let mut rows = Vec::new();
+        while let Some(row) = reader.next().await.unwrap() {
+            if row.id != proxy.id {
+                rows.push(row);
+            }
+        }
+        db_setter.set(MemoryProxyDB::try_from_rows(rows).unwrap());
+
+        let reselected_proxy = service
+            .serve(session_ctx("abc123", None), req())
+            .await
+            .unwrap();
+        assert_ne!(reselected_proxy.id, proxy.id);
+        for _ in 0..16 {
+            let sticky_proxy = service
+                .serve(session_ctx("abc123", None), req())
+                .await
+                .unwrap();
+            assert_eq!(sticky_proxy.id, reselected_proxy.id);
+        }
+
+        // other sessions get their own proxy, independent of the first session
+        let mut ids = Vec::new();
+        for session in ["a", "b", "c", "d", "e", "f", "g", "h"] {
+            let proxy = service
+                .serve(session_ctx(session, None), req())
+                .await
+                .unwrap();
+            ids.push(proxy.id);
+        }
+        assert!(ids.iter().unique().count() > 1);
+    }
 }
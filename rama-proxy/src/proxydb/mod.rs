@@ -24,12 +24,53 @@ mod csv;
 #[doc(inline)]
 pub use csv::{ProxyCsvRowReader, ProxyCsvRowReaderError, ProxyCsvRowReaderErrorKind};
 
+#[cfg(any(feature = "csv", feature = "json", feature = "yaml"))]
+mod file;
+
+#[cfg(any(feature = "csv", feature = "json", feature = "yaml"))]
+#[doc(inline)]
+pub use file::{ProxyFileError, ProxyFileErrorKind, ProxyFileFormat};
+
+#[cfg(all(
+    any(feature = "csv", feature = "json", feature = "yaml"),
+    feature = "live-update",
+    feature = "memory-db"
+))]
+#[doc(inline)]
+pub use file::{ProxyFileDiff, ProxyFileWatcher};
+
 pub(super) mod layer;
 
 mod str;
 #[doc(inline)]
 pub use str::StringFilter;
 
+mod select;
+#[doc(inline)]
+pub use select::{
+    LeastRecentlyUsedSelector, ProxyScorer, ProxySelector, ProxyStats, ProxyStatsTable,
+    RoundRobinSelector, WeightedSelector,
+};
+
+mod session;
+#[doc(inline)]
+pub use session::{ProxySessionID, ProxySessionTable};
+
+mod failover;
+#[doc(inline)]
+pub use failover::{
+    ProxyCandidates, ProxyFailoverConnector, ProxyFailoverLayer, ProxyFailoverMode,
+};
+
+#[cfg(feature = "health-check")]
+mod health;
+#[cfg(feature = "health-check")]
+#[doc(inline)]
+pub use health::{
+    ProxyHealth, ProxyHealthCheck, ProxyHealthChecker, ProxyHealthLayer, ProxyHealthService,
+    ProxyHealthTable, ProxyList, ServiceHealthCheck, TcpHealthCheck,
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// `ID` of the selected proxy. To be inserted into the `Context`,
 /// only if that proxy is selected.
@@ -147,6 +188,23 @@ pub trait ProxyDB: Send + Sync + 'static {
     ) -> impl Future<Output = Result<Proxy, Self::Error>> + Send + '_ {
         self.get_proxy_if(ctx, filter, true)
     }
+
+    /// Same as [`Self::get_proxy_if`] but with a [`ProxySelector`]
+    /// to select the [`Proxy`] from all proxies that match
+    /// the given [`ProxyFilter`] and predicate.
+    ///
+    /// By default the selector is ignored, which is fine for databases
+    /// that can only return a single [`Proxy`] for a query.
+    fn select_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+        selector: impl ProxySelector,
+    ) -> impl Future<Output = Result<Proxy, Self::Error>> + Send + '_ {
+        let _ = selector;
+        self.get_proxy_if(ctx, filter, predicate)
+    }
 }
 
 impl ProxyDB for () {
@@ -218,6 +276,26 @@ where
             )),
         }
     }
+
+    #[inline]
+    async fn select_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+        selector: impl ProxySelector,
+    ) -> Result<Proxy, Self::Error> {
+        match self {
+            Some(db) => db
+                .select_proxy_if(ctx, filter, predicate, selector)
+                .await
+                .map_err(|err| OpaqueError::from_boxed(err.into()))
+                .context("Some::select_proxy_if"),
+            None => Err(OpaqueError::from_display(
+                "None::select_proxy_if: no ProxyDB defined",
+            )),
+        }
+    }
 }
 
 impl<T> ProxyDB for std::sync::Arc<T>
@@ -244,6 +322,17 @@ where
     ) -> impl Future<Output = Result<Proxy, Self::Error>> + Send + '_ {
         (**self).get_proxy(ctx, filter)
     }
+
+    #[inline]
+    fn select_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+        selector: impl ProxySelector,
+    ) -> impl Future<Output = Result<Proxy, Self::Error>> + Send + '_ {
+        (**self).select_proxy_if(ctx, filter, predicate, selector)
+    }
 }
 
 macro_rules! impl_proxydb_either {
@@ -282,6 +371,21 @@ macro_rules! impl_proxydb_either {
                 )+
             }
         }
+
+        #[inline]
+        async fn select_proxy_if(
+            &self,
+            ctx: ProxyContext,
+            filter: ProxyFilter,
+            predicate: impl ProxyQueryPredicate,
+            selector: impl ProxySelector,
+        ) -> Result<Proxy, Self::Error> {
+            match self {
+                $(
+                    rama_core::combinators::$id::$param(s) => s.select_proxy_if(ctx, filter, predicate, selector).await.map_err(Into::into),
+                )+
+            }
+        }
         }
     };
 }
@@ -379,6 +483,11 @@ mod memdb {
             self.data.is_empty()
         }
 
+        /// Iterate over all proxies in the database.
+        pub fn iter(&self) -> impl Iterator<Item = &Proxy> + '_ {
+            self.data.iter()
+        }
+
         fn query_from_filter(
             &self,
             ctx: ProxyContext,
@@ -431,12 +540,6 @@ mod memdb {
         }
     }
 
-    // TODO: custom query filters using ProxyQueryPredicate
-    // might be a lot faster for cases where we want to filter a big batch of proxies,
-    // in which case a bitmap could be supported by a future VennDB version...
-    //
-    // Would just need to figure out how to allow this to happen.
-
     impl ProxyDB for MemoryProxyDB {
         type Error = MemoryProxyDBQueryError;
 
@@ -445,6 +548,16 @@ mod memdb {
             ctx: ProxyContext,
             filter: ProxyFilter,
             predicate: impl ProxyQueryPredicate,
+        ) -> Result<Proxy, Self::Error> {
+            self.select_proxy_if(ctx, filter, predicate, ()).await
+        }
+
+        async fn select_proxy_if(
+            &self,
+            ctx: ProxyContext,
+            filter: ProxyFilter,
+            predicate: impl ProxyQueryPredicate,
+            selector: impl ProxySelector,
         ) -> Result<Proxy, Self::Error> {
             match &filter.id {
                 Some(id) => match self.data.get_by_id(id) {
@@ -459,14 +572,20 @@ mod memdb {
                 },
                 None => {
                     let query = self.query_from_filter(ctx, filter.clone());
-                    match query
-                        .execute()
-                        .and_then(|result| result.filter(|proxy| predicate.execute(proxy)))
-                        .map(|result| result.any())
-                    {
-                        None => Err(MemoryProxyDBQueryError::not_found()),
-                        Some(proxy) => Ok(proxy.clone()),
+                    let Some(result) = query.execute() else {
+                        return Err(MemoryProxyDBQueryError::not_found());
+                    };
+                    let candidates: Vec<_> = result
+                        .iter()
+                        .filter(|proxy| predicate.execute(proxy))
+                        .collect();
+                    if candidates.is_empty() {
+                        return Err(MemoryProxyDBQueryError::not_found());
                     }
+                    selector
+                        .select(&candidates)
+                        .cloned()
+                        .ok_or_else(MemoryProxyDBQueryError::not_found)
                 }
             }
         }
@@ -0,0 +1,433 @@
+use super::{Proxy, ProxyID};
+use rama_utils::str::NonEmptyString;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, PoisonError, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+/// Trait that is used by the [`ProxyDB`] to select a [`Proxy`]
+/// from all proxies matching the [`ProxyFilter`] and [`ProxyQueryPredicate`].
+///
+/// The `()` selector selects a (uniform) random proxy,
+/// which is also what a [`ProxyDB`] does by default.
+///
+/// [`ProxyDB`]: super::ProxyDB
+/// [`ProxyFilter`]: super::ProxyFilter
+/// [`ProxyQueryPredicate`]: super::ProxyQueryPredicate
+pub trait ProxySelector: Clone + Send + Sync + 'static {
+    /// Select a proxy from the given candidates, which is never empty.
+    ///
+    /// Returning `None` is treated the same as if no proxy was found.
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy>;
+}
+
+impl ProxySelector for () {
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy> {
+        random_index(candidates.len()).map(|index| candidates[index])
+    }
+}
+
+fn random_index(len: usize) -> Option<usize> {
+    (len > 0).then(|| rand::random_range(0..len))
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`ProxySelector`] which selects the proxies in turn.
+///
+/// The order is the one of the candidates, being the order of the proxies
+/// in the database. As it uses a single counter for all queries,
+/// this is only strictly round robin in case the same set of candidates is queried.
+pub struct RoundRobinSelector {
+    next: Arc<AtomicUsize>,
+}
+
+impl RoundRobinSelector {
+    /// Create a new [`RoundRobinSelector`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ProxySelector for RoundRobinSelector {
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[index])
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`ProxySelector`] which selects the least recently used proxy,
+/// preferring the first candidate in case of a tie (e.g. never used proxies).
+///
+/// Only the last `capacity` selections are remembered,
+/// such that its memory remains bounded as proxies come and go.
+pub struct LeastRecentlyUsedSelector {
+    state: Arc<Mutex<LruState>>,
+    capacity: u64,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    tick: u64,
+    last_used: HashMap<NonEmptyString, u64>,
+}
+
+impl LeastRecentlyUsedSelector {
+    const DEFAULT_CAPACITY: u64 = 65_536;
+
+    /// Create a new [`LeastRecentlyUsedSelector`].
+    pub fn new() -> Self {
+        Self {
+            state: Default::default(),
+            capacity: Self::DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Set the number of selections to remember (`65_536` by default),
+    /// which is to be at least the number of proxies in the database.
+    pub fn with_capacity(mut self, capacity: u64) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Set the number of selections to remember (`65_536` by default),
+    /// which is to be at least the number of proxies in the database.
+    pub fn set_capacity(&mut self, capacity: u64) -> &mut Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+}
+
+impl Default for LeastRecentlyUsedSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProxySelector for LeastRecentlyUsedSelector {
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let proxy = candidates
+            .iter()
+            .copied()
+            .min_by_key(|proxy| state.last_used.get(&proxy.id).copied().unwrap_or_default())?;
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.last_used.insert(proxy.id.clone(), tick);
+        if state.last_used.len() as u64 > self.capacity {
+            let oldest = tick.saturating_sub(self.capacity);
+            state.last_used.retain(|_, last_used| *last_used > oldest);
+        }
+
+        Some(proxy)
+    }
+}
+
+/// Trait that is used by the [`WeightedSelector`] to score proxies.
+pub trait ProxyScorer: Clone + Send + Sync + 'static {
+    /// Score the given proxy, where a higher score means the proxy is more likely selected.
+    ///
+    /// Scores which are not positive (or not finite) result in the proxy not being selected,
+    /// unless all candidates have such a score.
+    fn score(&self, proxy: &Proxy) -> f64;
+}
+
+impl<F> ProxyScorer for F
+where
+    F: Fn(&Proxy) -> f64 + Clone + Send + Sync + 'static,
+{
+    fn score(&self, proxy: &Proxy) -> f64 {
+        (self)(proxy)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`ProxySelector`] which selects a random proxy,
+/// weighted by the score given to it by the [`ProxyScorer`].
+///
+/// Use a [`ProxyStatsTable`] as the scorer to score proxies
+/// based on their latency, success rate and cost.
+pub struct WeightedSelector<S> {
+    scorer: S,
+}
+
+impl<S> WeightedSelector<S> {
+    /// Create a new [`WeightedSelector`] using the given [`ProxyScorer`].
+    pub fn new(scorer: S) -> Self {
+        Self { scorer }
+    }
+}
+
+impl<S: ProxyScorer> ProxySelector for WeightedSelector<S> {
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy> {
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|proxy| {
+                let score = self.scorer.score(proxy);
+                if score.is_finite() && score > 0. {
+                    score
+                } else {
+                    0.
+                }
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0. {
+            return ().select(candidates);
+        }
+
+        let mut point = rand::random_range(0. ..total);
+        for (proxy, weight) in candidates.iter().zip(weights) {
+            if point < weight {
+                return Some(proxy);
+            }
+            point -= weight;
+        }
+        // floating point rounding errors
+        candidates.last().copied()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Statistics of a [`Proxy`] as recorded in a [`ProxyStatsTable`].
+pub struct ProxyStats {
+    /// Exponentially weighted moving average of the latency, if known.
+    pub latency: Option<Duration>,
+    /// Number of successful uses of the proxy.
+    pub successes: u64,
+    /// Number of failed uses of the proxy.
+    pub failures: u64,
+    /// Cost of using the proxy (in an arbitrary unit), if known.
+    pub cost: Option<f64>,
+}
+
+impl ProxyStats {
+    /// Score the proxy based on these statistics, as used by the [`ProxyStatsTable`].
+    ///
+    /// The score is the (smoothed) success rate, divided by `1 + latency_in_seconds`
+    /// and `1 + cost`. A proxy without any recorded statistics has a score of `0.5`.
+    pub fn score(&self) -> f64 {
+        let success_rate =
+            (self.successes as f64 + 1.) / ((self.successes + self.failures) as f64 + 2.);
+        let latency = self.latency.map(|d| d.as_secs_f64()).unwrap_or_default();
+        let cost = self.cost.unwrap_or_default().max(0.);
+        success_rate / (1. + latency) / (1. + cost)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Shared table of [`ProxyStats`], keyed by [`ProxyID`].
+///
+/// It is a [`ProxyScorer`] to be used with a [`WeightedSelector`],
+/// scoring proxies using [`ProxyStats::score`].
+/// The statistics are to be recorded by you, e.g. based on the
+/// result of requests made using the selected proxy.
+pub struct ProxyStatsTable {
+    stats: Arc<RwLock<HashMap<NonEmptyString, ProxyStats>>>,
+}
+
+impl ProxyStatsTable {
+    const LATENCY_WEIGHT: f64 = 0.2;
+
+    /// Create a new empty [`ProxyStatsTable`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a successful use of the proxy, with the observed latency.
+    pub fn record_success(&self, id: &ProxyID, latency: Duration) {
+        self.update(id, |stats| {
+            stats.successes += 1;
+            stats.latency = Some(match stats.latency {
+                Some(avg) => {
+                    avg.mul_f64(1. - Self::LATENCY_WEIGHT) + latency.mul_f64(Self::LATENCY_WEIGHT)
+                }
+                None => latency,
+            });
+        });
+    }
+
+    /// Record a failed use of the proxy.
+    pub fn record_failure(&self, id: &ProxyID) {
+        self.update(id, |stats| stats.failures += 1);
+    }
+
+    /// Set the cost of using the proxy.
+    pub fn set_cost(&self, id: &ProxyID, cost: f64) {
+        self.update(id, |stats| stats.cost = Some(cost));
+    }
+
+    /// Get the [`ProxyStats`] of the proxy, if any were recorded.
+    pub fn stats(&self, id: &ProxyID) -> Option<ProxyStats> {
+        self.stats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id.0)
+            .cloned()
+    }
+
+    /// Remove the [`ProxyStats`] of the proxy, e.g. because it was removed from the database.
+    pub fn remove(&self, id: &ProxyID) -> Option<ProxyStats> {
+        self.stats
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id.0)
+    }
+
+    /// Only retain the [`ProxyStats`] of the proxies for which the predicate returns `true`.
+    pub fn retain(&self, mut predicate: impl FnMut(&str, &ProxyStats) -> bool) {
+        self.stats
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|id, stats| predicate(id.as_str(), stats));
+    }
+
+    fn update(&self, id: &ProxyID, f: impl FnOnce(&mut ProxyStats)) {
+        let mut stats = self.stats.write().unwrap_or_else(PoisonError::into_inner);
+        f(stats.entry(id.0.clone()).or_default());
+    }
+}
+
+impl ProxyScorer for ProxyStatsTable {
+    fn score(&self, proxy: &Proxy) -> f64 {
+        self.stats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&proxy.id)
+            .map(ProxyStats::score)
+            .unwrap_or_else(|| ProxyStats::default().score())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::address::ProxyAddress;
+
+    fn test_proxies(ids: &[&'static str]) -> Vec<Proxy> {
+        ids.iter()
+            .map(|id| Proxy {
+                id: NonEmptyString::from_static(id),
+                address: ProxyAddress::try_from("127.0.0.1:8080").unwrap(),
+                tcp: true,
+                udp: false,
+                http: true,
+                https: false,
+                socks5: false,
+                socks5h: false,
+                datacenter: true,
+                residential: false,
+                mobile: false,
+                pool_id: None,
+                continent: None,
+                country: None,
+                state: None,
+                city: None,
+                carrier: None,
+                asn: None,
+            })
+            .collect()
+    }
+
+    fn select_ids(selector: &impl ProxySelector, proxies: &[Proxy], n: usize) -> Vec<String> {
+        let candidates: Vec<_> = proxies.iter().collect();
+        (0..n)
+            .map(|_| selector.select(&candidates).unwrap().id.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin_selector() {
+        let proxies = test_proxies(&["a", "b", "c"]);
+        let selector = RoundRobinSelector::new();
+        assert_eq!(
+            select_ids(&selector.clone(), &proxies, 4),
+            ["a", "b", "c", "a"]
+        );
+        assert_eq!(select_ids(&selector, &proxies, 2), ["b", "c"]);
+    }
+
+    #[test]
+    fn test_least_recently_used_selector() {
+        let proxies = test_proxies(&["a", "b", "c"]);
+        let selector = LeastRecentlyUsedSelector::new();
+        assert_eq!(select_ids(&selector, &proxies, 3), ["a", "b", "c"]);
+
+        // a new proxy is preferred, after which the least recently used one
+        let mut more = proxies.clone();
+        more.extend(test_proxies(&["d"]));
+        assert_eq!(select_ids(&selector, &more, 3), ["d", "a", "b"]);
+
+        // filtered set
+        assert_eq!(select_ids(&selector, &more[2..], 2), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_least_recently_used_selector_bounded() {
+        let proxies = test_proxies(&["a", "b", "c", "d"]);
+        let selector = LeastRecentlyUsedSelector::new().with_capacity(2);
+        select_ids(&selector, &proxies, 8);
+        let state = selector.state.lock().unwrap();
+        assert!(state.last_used.len() <= 3, "{:?}", state.last_used);
+    }
+
+    #[test]
+    fn test_weighted_selector() {
+        let proxies = test_proxies(&["a", "b", "c"]);
+        let selector = WeightedSelector::new(|proxy: &Proxy| match proxy.id.as_str() {
+            "b" => 1.,
+            "c" => f64::NAN,
+            _ => 0.,
+        });
+        assert!(
+            select_ids(&selector, &proxies, 16)
+                .iter()
+                .all(|id| id == "b")
+        );
+
+        // all unusable scores fallback to uniform selection
+        let selector = WeightedSelector::new(|_: &Proxy| -1.);
+        assert_eq!(select_ids(&selector, &proxies, 16).len(), 16);
+    }
+
+    #[test]
+    fn test_proxy_stats_table() {
+        let table = ProxyStatsTable::new();
+        let (a, b) = (
+            ProxyID::from(NonEmptyString::from_static("a")),
+            ProxyID::from(NonEmptyString::from_static("b")),
+        );
+        let proxies = test_proxies(&["a", "b", "c"]);
+
+        assert_eq!(table.score(&proxies[0]), 0.5);
+
+        table.record_success(&a, Duration::from_millis(100));
+        table.record_success(&a, Duration::from_millis(200));
+        table.record_failure(&b);
+        table.set_cost(&b, 1.);
+
+        let stats = table.stats(&a).unwrap();
+        assert_eq!(stats.successes, 2);
+        assert_eq!(stats.latency, Some(Duration::from_millis(120)));
+
+        let (score_a, score_b, score_c) = (
+            table.score(&proxies[0]),
+            table.score(&proxies[1]),
+            table.score(&proxies[2]),
+        );
+        assert!(score_a > score_c, "{score_a} > {score_c}");
+        assert!(score_c > score_b, "{score_c} > {score_b}");
+
+        table.retain(|id, _| id != "a");
+        assert!(table.stats(&a).is_none());
+        assert!(table.remove(&b).is_some());
+    }
+}
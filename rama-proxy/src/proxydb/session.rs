@@ -0,0 +1,445 @@
+use super::{Proxy, ProxyFilter, ProxyID, ProxySelector};
+use rama_net::user::UserId;
+use rama_utils::str::NonEmptyString;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// `ID` of a (logical) proxy session, e.g. as parsed from a `session` username label
+/// by the [`ProxySessionUsernameParser`].
+///
+/// Used by the [`ProxyDBLayer`] to select the same proxy
+/// for all requests of that session, in case sticky sessions are enabled.
+///
+/// [`ProxySessionUsernameParser`]: crate::ProxySessionUsernameParser
+/// [`ProxyDBLayer`]: crate::ProxyDBLayer
+pub struct ProxySessionID(NonEmptyString);
+
+impl ProxySessionID {
+    /// View this [`ProxySessionID`] as a `str`.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl AsRef<str> for ProxySessionID {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Display for ProxySessionID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<NonEmptyString> for ProxySessionID {
+    fn from(value: NonEmptyString) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Shared table mapping proxy sessions to the [`ProxyID`] selected for them,
+/// used by the [`ProxyDBLayer`] to enable sticky sessions.
+///
+/// A session is identified by its [`ProxySessionID`] together with the [`UserId`]
+/// (if any) of the user owning it, such that users cannot hijack each other's sessions.
+///
+/// A session is mapped to the [`ProxyFilter`] used to select its proxy,
+/// and expires in case it is not used for the configured ttl (10 minutes by default).
+/// At most `capacity` sessions are tracked (`65_536` by default), after which
+/// expired sessions, and if needed the sessions expiring first, are dropped.
+///
+/// [`ProxyDBLayer`]: crate::ProxyDBLayer
+pub struct ProxySessionTable {
+    sessions: Arc<Mutex<Sessions>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+type SessionKey = (Option<UserId>, ProxySessionID);
+
+/// Moment a session expires at, made unique using an insertion sequence number.
+type ExpiryKey = (Instant, u64);
+
+#[derive(Debug, Default)]
+struct Sessions {
+    entries: HashMap<SessionKey, SessionEntry>,
+    /// Index of the sessions by expiry, such that the expired sessions
+    /// (or those expiring first) can be dropped without scanning all sessions.
+    expiry: BTreeMap<ExpiryKey, SessionKey>,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct SessionEntry {
+    filter: ProxyFilter,
+    proxy_id: ProxyID,
+    expires_at: ExpiryKey,
+}
+
+impl Sessions {
+    fn remove(&mut self, key: &SessionKey) -> Option<SessionEntry> {
+        let entry = self.entries.remove(key)?;
+        self.expiry.remove(&entry.expires_at);
+        Some(entry)
+    }
+
+    fn first_expires_at(&self) -> Option<Instant> {
+        self.expiry
+            .first_key_value()
+            .map(|((expires_at, _), _)| *expires_at)
+    }
+
+    fn pop_first_expiring(&mut self) {
+        if let Some((_, key)) = self.expiry.pop_first() {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+impl ProxySessionTable {
+    const DEFAULT_TTL: Duration = Duration::from_secs(600);
+    const DEFAULT_CAPACITY: usize = 65_536;
+
+    /// Create a new empty [`ProxySessionTable`].
+    pub fn new() -> Self {
+        Self {
+            sessions: Default::default(),
+            ttl: Self::DEFAULT_TTL,
+            capacity: Self::DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Set the duration for which an unused session is remembered (10 minutes by default).
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the duration for which an unused session is remembered (10 minutes by default).
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the maximum number of sessions to track (`65_536` by default).
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Set the maximum number of sessions to track (`65_536` by default).
+    pub fn set_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Get the [`ProxyID`] mapped to the given session,
+    /// in case it is not expired and was selected using the same [`ProxyFilter`].
+    pub fn get(
+        &self,
+        user: Option<&UserId>,
+        session: &ProxySessionID,
+        filter: &ProxyFilter,
+    ) -> Option<ProxyID> {
+        self.get_at(user, session, filter, Instant::now())
+    }
+
+    /// Map the given session to the [`ProxyID`] selected using the given [`ProxyFilter`],
+    /// (re)starting its ttl.
+    pub fn insert(
+        &self,
+        user: Option<UserId>,
+        session: ProxySessionID,
+        filter: ProxyFilter,
+        proxy_id: ProxyID,
+    ) {
+        self.insert_at(user, session, filter, proxy_id, Instant::now())
+    }
+
+    /// Remove the given session, returning the [`ProxyID`] it was mapped to, if any.
+    pub fn remove(&self, user: Option<UserId>, session: ProxySessionID) -> Option<ProxyID> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(user, session))
+            .map(|entry| entry.proxy_id)
+    }
+
+    /// Returns the number of tracked sessions, which might include expired ones.
+    pub fn len(&self) -> usize {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .len()
+    }
+
+    /// Returns `true` in case no sessions are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_at(
+        &self,
+        user: Option<&UserId>,
+        session: &ProxySessionID,
+        filter: &ProxyFilter,
+        now: Instant,
+    ) -> Option<ProxyID> {
+        let sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        // NOTE: the key is cloned as a tuple of references cannot be used as a borrowed key
+        let entry = sessions.entries.get(&(user.cloned(), session.clone()))?;
+        (entry.expires_at.0 > now && &entry.filter == filter).then(|| entry.proxy_id.clone())
+    }
+
+    fn insert_at(
+        &self,
+        user: Option<UserId>,
+        session: ProxySessionID,
+        filter: ProxyFilter,
+        proxy_id: ProxyID,
+        now: Instant,
+    ) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (user, session);
+
+        if sessions.remove(&key).is_none() && sessions.entries.len() >= self.capacity {
+            // drop the session expiring first, as well as all other expired sessions
+            sessions.pop_first_expiring();
+            while sessions
+                .first_expires_at()
+                .is_some_and(|expires_at| expires_at <= now)
+            {
+                sessions.pop_first_expiring();
+            }
+        }
+
+        let expires_at = (now + self.ttl, sessions.next_seq);
+        sessions.next_seq += 1;
+        sessions.expiry.insert(expires_at, key.clone());
+        sessions.entries.insert(
+            key,
+            SessionEntry {
+                filter,
+                proxy_id,
+                expires_at,
+            },
+        );
+    }
+}
+
+impl Default for ProxySessionTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`ProxySelector`] which deterministically selects a proxy for a session,
+/// using rendezvous hashing, such that the same proxy is selected as long as it
+/// remains available, and only the sessions of a removed proxy move to another one.
+pub(super) struct SessionSelector {
+    session_hash: u64,
+}
+
+impl SessionSelector {
+    pub(super) fn new(user: Option<&UserId>, session: &ProxySessionID) -> Self {
+        let mut hasher = DefaultHasher::new();
+        user.hash(&mut hasher);
+        session.hash(&mut hasher);
+        Self {
+            session_hash: hasher.finish(),
+        }
+    }
+
+    fn weight(&self, proxy: &Proxy) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.session_hash.hash(&mut hasher);
+        proxy.id.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl ProxySelector for SessionSelector {
+    fn select<'a>(&self, candidates: &[&'a Proxy]) -> Option<&'a Proxy> {
+        candidates
+            .iter()
+            .copied()
+            .max_by_key(|proxy| (self.weight(proxy), &proxy.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::address::ProxyAddress;
+
+    fn session(id: &'static str) -> ProxySessionID {
+        NonEmptyString::from_static(id).into()
+    }
+
+    fn proxy_id(id: &'static str) -> ProxyID {
+        NonEmptyString::from_static(id).into()
+    }
+
+    fn test_proxy(id: &'static str) -> Proxy {
+        Proxy {
+            id: NonEmptyString::from_static(id),
+            address: ProxyAddress::try_from("127.0.0.1:8080").unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: true,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+        }
+    }
+
+    #[test]
+    fn test_proxy_session_table() {
+        let table = ProxySessionTable::new().with_ttl(Duration::from_secs(10));
+        let user = UserId::Username("john".to_owned());
+        let filter = ProxyFilter {
+            country: Some(vec!["us".into()]),
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert!(table.get_at(None, &session("a"), &filter, now).is_none());
+
+        table.insert_at(
+            Some(user.clone()),
+            session("a"),
+            filter.clone(),
+            proxy_id("1"),
+            now,
+        );
+        assert_eq!(
+            table.get_at(Some(&user), &session("a"), &filter, now),
+            Some(proxy_id("1"))
+        );
+
+        // sessions are scoped per user
+        assert!(table.get_at(None, &session("a"), &filter, now).is_none());
+        // a different filter does not match the session
+        assert!(
+            table
+                .get_at(Some(&user), &session("a"), &ProxyFilter::default(), now)
+                .is_none()
+        );
+        // expired sessions are no longer matched
+        assert!(
+            table
+                .get_at(
+                    Some(&user),
+                    &session("a"),
+                    &filter,
+                    now + Duration::from_secs(10)
+                )
+                .is_none()
+        );
+
+        assert_eq!(table.remove(Some(user), session("a")), Some(proxy_id("1")));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_proxy_session_table_bounded() {
+        let table = ProxySessionTable::new()
+            .with_ttl(Duration::from_secs(10))
+            .with_capacity(2);
+        let filter = ProxyFilter::default();
+        let now = Instant::now();
+
+        table.insert_at(None, session("a"), filter.clone(), proxy_id("1"), now);
+        table.insert_at(
+            None,
+            session("b"),
+            filter.clone(),
+            proxy_id("2"),
+            now + Duration::from_secs(1),
+        );
+        table.insert_at(
+            None,
+            session("c"),
+            filter.clone(),
+            proxy_id("3"),
+            now + Duration::from_secs(2),
+        );
+        assert_eq!(table.len(), 2);
+        // the session expiring first is dropped
+        assert!(table.get_at(None, &session("a"), &filter, now).is_none());
+        assert!(table.get_at(None, &session("b"), &filter, now).is_some());
+        assert!(table.get_at(None, &session("c"), &filter, now).is_some());
+
+        // expired sessions are dropped first
+        let later = now + Duration::from_secs(11);
+        table.insert_at(None, session("d"), filter.clone(), proxy_id("4"), later);
+        assert_eq!(table.len(), 2);
+        assert!(table.get_at(None, &session("c"), &filter, later).is_some());
+        assert!(table.get_at(None, &session("d"), &filter, later).is_some());
+
+        // re-inserting a session restarts its ttl
+        let later = later + Duration::from_secs(1);
+        table.insert_at(None, session("c"), filter.clone(), proxy_id("3"), later);
+        table.insert_at(None, session("e"), filter.clone(), proxy_id("5"), later);
+        assert_eq!(table.len(), 2);
+        assert!(table.get_at(None, &session("c"), &filter, later).is_some());
+        assert!(table.get_at(None, &session("d"), &filter, later).is_none());
+        assert!(table.get_at(None, &session("e"), &filter, later).is_some());
+
+        table.remove(None, session("c"));
+        table.remove(None, session("e"));
+        let sessions = table.sessions.lock().unwrap();
+        assert!(sessions.entries.is_empty());
+        assert!(sessions.expiry.is_empty());
+    }
+
+    #[test]
+    fn test_session_selector() {
+        let proxies: Vec<_> = ["1", "2", "3", "4", "5"]
+            .into_iter()
+            .map(test_proxy)
+            .collect();
+        let candidates: Vec<_> = proxies.iter().collect();
+
+        let selector = SessionSelector::new(None, &session("abc123"));
+        let selected = selector.select(&candidates).unwrap();
+        for _ in 0..8 {
+            assert_eq!(selector.select(&candidates).unwrap().id, selected.id);
+        }
+
+        // independent of the order of the candidates
+        let reversed: Vec<_> = candidates.iter().rev().copied().collect();
+        assert_eq!(selector.select(&reversed).unwrap().id, selected.id);
+
+        // only moves in case the selected proxy disappears
+        let remaining: Vec<_> = candidates
+            .iter()
+            .copied()
+            .filter(|proxy| proxy.id != selected.id)
+            .collect();
+        let reselected = selector.select(&remaining).unwrap();
+        assert_ne!(reselected.id, selected.id);
+        assert_eq!(selector.select(&remaining).unwrap().id, reselected.id);
+    }
+}
@@ -90,6 +90,37 @@ where
             .into()),
         }
     }
+
+    async fn select_proxy_if(
+        &self,
+        ctx: super::ProxyContext,
+        filter: super::ProxyFilter,
+        predicate: impl super::ProxyQueryPredicate,
+        selector: impl super::ProxySelector,
+    ) -> Result<super::Proxy, Self::Error> {
+        match self.0.load().deref().deref() {
+            Some(db) => db
+                .select_proxy_if(ctx, filter, predicate, selector)
+                .await
+                .map_err(Into::into),
+            None => Err(OpaqueError::from_display(
+                "live proxy db: proxy db is None: select_proxy_if unable to proceed",
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(feature = "health-check")]
+impl<T: super::ProxyList> super::ProxyList for LiveUpdateProxyDB<T> {
+    fn proxies(&self) -> Vec<super::Proxy> {
+        self.0
+            .load()
+            .as_ref()
+            .as_ref()
+            .map(|db| db.proxies())
+            .unwrap_or_default()
+    }
 }
 
 /// Writer to set a new [`ProxyDB`] in the linked [`LiveUpdateProxyDB`].
@@ -1,10 +1,13 @@
-use super::ProxyFilter;
+use super::{ProxyFilter, ProxySessionID};
 use rama_core::{
     context::Extensions,
     error::{OpaqueError, error},
     username::{UsernameLabelParser, UsernameLabelState, UsernameLabelWriter},
 };
-use rama_utils::macros::match_ignore_ascii_case_str;
+use rama_utils::{
+    macros::{match_ignore_ascii_case_str, str::eq_ignore_ascii_case},
+    str::NonEmptyString,
+};
 
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
@@ -256,6 +259,71 @@ impl<const SEPARATOR: char> UsernameLabelWriter<SEPARATOR> for ProxyFilter {
     }
 }
 
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A parser which parses a [`ProxySessionID`] from username labels
+/// (e.g. `john-session-abc123`) and adds it to the [`Context`]'s [`Extensions`].
+///
+/// Combine it with the [`ProxyFilterUsernameParser`] (as a tuple)
+/// to parse both proxy filters and sessions from the same username.
+///
+/// [`Context`]: rama_core::Context
+/// [`Extensions`]: rama_core::context::Extensions
+pub struct ProxySessionUsernameParser {
+    key_found: bool,
+    session: Option<ProxySessionID>,
+}
+
+impl ProxySessionUsernameParser {
+    /// Create a new [`ProxySessionUsernameParser`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UsernameLabelParser for ProxySessionUsernameParser {
+    type Error = OpaqueError;
+
+    fn parse_label(&mut self, label: &str) -> UsernameLabelState {
+        if self.key_found {
+            self.session = match NonEmptyString::try_from(label) {
+                Ok(session) => Some(session.into()),
+                Err(err) => {
+                    tracing::trace!(err = %err, "abort username label parsing: invalid session label");
+                    return UsernameLabelState::Abort;
+                }
+            };
+            self.key_found = false;
+            UsernameLabelState::Used
+        } else if eq_ignore_ascii_case!("session", label) {
+            self.key_found = true;
+            UsernameLabelState::Used
+        } else {
+            UsernameLabelState::Ignored
+        }
+    }
+
+    fn build(self, ext: &mut Extensions) -> Result<(), Self::Error> {
+        if self.key_found {
+            return Err(error!("unused proxy session username key: session"));
+        }
+        if let Some(session) = self.session {
+            ext.insert(session);
+        }
+        Ok(())
+    }
+}
+
+impl<const SEPARATOR: char> UsernameLabelWriter<SEPARATOR> for ProxySessionID {
+    fn write_labels(
+        &self,
+        composer: &mut rama_core::username::Composer<SEPARATOR>,
+    ) -> Result<(), rama_core::username::ComposeError> {
+        composer.write_label("session")?;
+        composer.write_label(self.as_str())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -651,4 +719,55 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_username_proxy_session() {
+        let mut ext = Extensions::new();
+        let username = parse_username(
+            &mut ext,
+            (
+                ProxyFilterUsernameParser::default(),
+                ProxySessionUsernameParser::default(),
+            ),
+            "john-country-us-session-abc123",
+        )
+        .unwrap();
+        assert_eq!("john", username);
+        assert_eq!(ext.get::<ProxySessionID>().unwrap().as_str(), "abc123");
+        assert_eq!(
+            ext.get::<ProxyFilter>().unwrap().country,
+            Some(vec![StringFilter::from("us")])
+        );
+
+        let mut ext = Extensions::new();
+        parse_username(&mut ext, ProxySessionUsernameParser::default(), "john").unwrap();
+        assert!(!ext.contains::<ProxySessionID>());
+
+        let mut ext = Extensions::new();
+        assert!(
+            parse_username(
+                &mut ext,
+                ProxySessionUsernameParser::default(),
+                "john-session"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_username_compose_parser_proxy_session() {
+        let session = ProxySessionID::from(NonEmptyString::from_static("abc123"));
+        let fmt_username = compose_username("john".to_owned(), &session).unwrap();
+        assert_eq!("john-session-abc123", fmt_username);
+
+        let mut ext = Extensions::new();
+        let username = parse_username(
+            &mut ext,
+            ProxySessionUsernameParser::default(),
+            &fmt_username,
+        )
+        .unwrap();
+        assert_eq!("john", username);
+        assert_eq!(*ext.get::<ProxySessionID>().unwrap(), session);
+    }
 }
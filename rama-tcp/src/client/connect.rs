@@ -1,20 +1,21 @@
 use rama_core::{
     Context,
     combinators::Either,
-    error::{BoxError, ErrorContext, OpaqueError},
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
 };
 use rama_dns::{DnsOverwrite, DnsResolver, HickoryDns};
 use rama_net::address::{Authority, Domain, Host};
+use rama_net::client::ConnectionTraceHandle;
 use rama_net::mode::{ConnectIpMode, DnsResolveIpMode};
 use std::{
     future::Future,
     net::{IpAddr, SocketAddr},
     ops::Deref,
     sync::{
-        Arc,
+        Arc, Mutex, PoisonError,
         atomic::{AtomicBool, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     net::TcpStream,
@@ -118,6 +119,9 @@ where
 }
 
 /// Establish a [`TcpStream`] connection for the given [`Authority`].
+///
+/// The dns and connect timings are recorded in the
+/// [`ConnectionTraceHandle`] found in the [`Context`], if any.
 pub async fn tcp_connect<State, Dns, Connector>(
     ctx: &Context<State>,
     authority: Authority,
@@ -125,6 +129,40 @@ pub async fn tcp_connect<State, Dns, Connector>(
     dns: Dns,
     connector: Connector,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError>
+where
+    State: Clone + Send + Sync + 'static,
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+    Connector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
+{
+    let trace = ctx.get::<ConnectionTraceHandle>().cloned();
+    let start = Instant::now();
+
+    let (stream, addr) = tcp_connect_traced(
+        ctx,
+        authority,
+        allow_overwrites,
+        dns,
+        connector,
+        trace.clone(),
+    )
+    .await?;
+
+    if let Some(trace) = trace {
+        let dns = trace.trace().dns().unwrap_or_default();
+        trace.record_connect(start.elapsed().saturating_sub(dns), addr);
+    }
+
+    Ok((stream, addr))
+}
+
+async fn tcp_connect_traced<State, Dns, Connector>(
+    ctx: &Context<State>,
+    authority: Authority,
+    allow_overwrites: bool,
+    dns: Dns,
+    connector: Connector,
+    trace: Option<ConnectionTraceHandle>,
+) -> Result<(TcpStream, SocketAddr), OpaqueError>
 where
     State: Clone + Send + Sync + 'static,
     Dns: DnsResolver<Error: Into<BoxError>> + Clone,
@@ -169,6 +207,7 @@ where
                 dns_overwrite.deref().clone(), // Convert DnsOverwrite to a DnsResolver
                 connector.clone(),
                 ip_mode,
+                trace.clone(),
             )
             .await
             {
@@ -180,9 +219,10 @@ where
     //... otherwise we'll try to establish a connection,
     // with dual-stack parallel connections...
 
-    tcp_connect_inner(ctx, domain, port, dns_mode, dns, connector, ip_mode).await
+    tcp_connect_inner(ctx, domain, port, dns_mode, dns, connector, ip_mode, trace).await
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn tcp_connect_inner<State, Dns, Connector>(
     ctx: &Context<State>,
     domain: Domain,
@@ -191,6 +231,7 @@ async fn tcp_connect_inner<State, Dns, Connector>(
     dns: Dns,
     connector: Connector,
     connect_mode: ConnectIpMode,
+    trace: Option<ConnectionTraceHandle>,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError>
 where
     State: Clone + Send + Sync + 'static,
@@ -200,6 +241,7 @@ where
     let (tx, mut rx) = channel(1);
     let connected = Arc::new(AtomicBool::new(false));
     let sem = Arc::new(Semaphore::new(3));
+    let connect_err = Arc::new(Mutex::new(None));
 
     if dns_mode.ipv4_supported() {
         ctx.spawn(tcp_connect_inner_branch(
@@ -213,6 +255,8 @@ where
             tx.clone(),
             connected.clone(),
             sem.clone(),
+            connect_err.clone(),
+            trace.clone(),
         ));
     }
 
@@ -228,17 +272,31 @@ where
             tx.clone(),
             connected.clone(),
             sem.clone(),
+            connect_err.clone(),
+            trace.clone(),
         ));
     }
 
+    // only the connect attempts can still send a connection
+    drop(tx);
+
     if let Some((stream, addr)) = rx.recv().await {
         connected.store(true, Ordering::Release);
         return Ok((stream, addr));
     }
 
-    Err(OpaqueError::from_display(format!(
-        "failed to connect to any resolved IP address for {domain} (port {port})"
-    )))
+    let msg = format!("failed to connect to any resolved IP address for {domain} (port {port})");
+    // preserve the (first) connector error, e.g. such that its type can be inspected
+    Err(
+        match connect_err
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(err) => err.context(msg),
+            None => OpaqueError::from_display(msg),
+        },
+    )
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -259,10 +317,13 @@ async fn tcp_connect_inner_branch<Dns, Connector>(
     tx: Sender<(TcpStream, SocketAddr)>,
     connected: Arc<AtomicBool>,
     sem: Arc<Semaphore>,
+    connect_err: Arc<Mutex<Option<OpaqueError>>>,
+    trace: Option<ConnectionTraceHandle>,
 ) where
     Dns: DnsResolver<Error: Into<BoxError>> + Clone,
     Connector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
 {
+    let lookup_start = Instant::now();
     let ip_it = match ip_kind {
         IpKind::Ipv4 => match dns.ipv4_lookup(domain).await {
             Ok(ips) => Either::A(ips.into_iter().map(IpAddr::V4)),
@@ -281,6 +342,9 @@ async fn tcp_connect_inner_branch<Dns, Connector>(
             }
         },
     };
+    if let Some(trace) = trace {
+        trace.record_dns(lookup_start.elapsed());
+    }
 
     let (ipv4_delay_scalar, ipv6_delay_scalar) = match dns_mode {
         DnsResolveIpMode::DualPreferIpV4 | DnsResolveIpMode::SingleIpV4 => (15 * 2, 21 * 2),
@@ -325,6 +389,7 @@ async fn tcp_connect_inner_branch<Dns, Connector>(
         }
 
         let connector = connector.clone();
+        let connect_err = connect_err.clone();
         tokio::spawn(async move {
             let _permit = sem.acquire().await.unwrap();
             if connected.load(Ordering::Acquire) {
@@ -346,6 +411,10 @@ async fn tcp_connect_inner_branch<Dns, Connector>(
                 Err(err) => {
                     let err = OpaqueError::from_boxed(err.into());
                     tracing::trace!(err = %err, "[{ip_kind:?}] #{index}: tcp connector failed to connect");
+                    connect_err
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get_or_insert(err);
                 }
             };
         });
@@ -0,0 +1,148 @@
+use super::TcpStreamConnector;
+use rama_core::error::BoxError;
+use rama_net::client::EgressPolicy;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::net::TcpStream;
+
+/// A [`TcpStreamConnector`] which only connects to addresses allowed by an [`EgressPolicy`],
+/// guarding against server-side request forgery (SSRF).
+///
+/// As the address is checked right before connecting, after the domain got resolved,
+/// this cannot be bypassed using DNS rebinding. Every connection is checked,
+/// so redirects followed by a client using this connector are checked as well.
+///
+/// Connections to denied addresses fail with an [`EgressDenied`] error,
+/// which can be found in the connector error using [`EgressDenied::find`].
+///
+/// Note that connections to (upstream) proxies are checked as well,
+/// use [`EgressPolicy::with_allow`] in case those live in a denied network.
+///
+/// [`EgressDenied`]: rama_net::client::EgressDenied
+/// [`EgressDenied::find`]: rama_net::client::EgressDenied::find
+///
+/// # Example
+///
+/// ```
+/// use rama_core::Context;
+/// use rama_dns::HickoryDns;
+/// use rama_net::client::{EgressDenied, EgressPolicy};
+/// use rama_tcp::client::{EgressGuardConnector, tcp_connect};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let connector = EgressGuardConnector::new(EgressPolicy::default());
+///
+/// let err = tcp_connect(
+///     &Context::<()>::default(),
+///     "127.0.0.1:8080".parse().unwrap(),
+///     false,
+///     HickoryDns::default(),
+///     connector,
+/// )
+/// .await
+/// .unwrap_err();
+/// assert!(EgressDenied::find(&err).is_some());
+/// # }
+/// ```
+///
+/// Use `TcpConnector::with_connector` to guard the connections
+/// made by a `TcpConnector` service.
+#[derive(Debug, Clone)]
+pub struct EgressGuardConnector<C = ()> {
+    policy: Arc<EgressPolicy>,
+    connector: C,
+}
+
+impl EgressGuardConnector {
+    /// Create a new [`EgressGuardConnector`] guarding the default [`TcpStreamConnector`].
+    pub fn new(policy: EgressPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            connector: (),
+        }
+    }
+}
+
+impl<C> EgressGuardConnector<C> {
+    /// Guard the given [`TcpStreamConnector`] instead of the default one.
+    pub fn with_connector<C2>(self, connector: C2) -> EgressGuardConnector<C2> {
+        EgressGuardConnector {
+            policy: self.policy,
+            connector,
+        }
+    }
+
+    /// The [`EgressPolicy`] used by this [`EgressGuardConnector`].
+    pub fn policy(&self) -> &EgressPolicy {
+        &self.policy
+    }
+}
+
+impl<C> TcpStreamConnector for EgressGuardConnector<C>
+where
+    C: TcpStreamConnector<Error: Into<BoxError>>,
+{
+    type Error = BoxError;
+
+    async fn connect(&self, addr: SocketAddr) -> Result<TcpStream, Self::Error> {
+        if let Err(err) = self.policy.check(addr.ip()) {
+            tracing::debug!(%addr, "egress guard: connection denied");
+            return Err(err.into());
+        }
+        self.connector.connect(addr).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tcp_connect;
+    use rama_core::Context;
+    use rama_dns::InMemoryDns;
+    use rama_net::address::{Authority, Domain};
+    use rama_net::client::EgressDenied;
+    use std::net::IpAddr;
+
+    #[tokio::test]
+    async fn test_egress_guard_denies_resolved_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut dns = InMemoryDns::new();
+        dns.insert_address(
+            Domain::from_static("rebind.example"),
+            IpAddr::from([127, 0, 0, 1]),
+        );
+
+        let ctx = Context::default();
+        let connector = EgressGuardConnector::new(EgressPolicy::default());
+
+        for authority in [
+            Authority::new(Domain::from_static("rebind.example").into(), port),
+            Authority::new(IpAddr::from([127, 0, 0, 1]).into(), port),
+        ] {
+            let err = tcp_connect(
+                &ctx,
+                authority.clone(),
+                false,
+                dns.clone(),
+                connector.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(
+                EgressDenied::find(&err).map(EgressDenied::ip),
+                Some(IpAddr::from([127, 0, 0, 1])),
+                "{authority}: {err}",
+            );
+        }
+
+        let connector = EgressGuardConnector::new(
+            EgressPolicy::default().with_allow(IpAddr::from([127, 0, 0, 1])),
+        );
+        let authority = Authority::new(Domain::from_static("rebind.example").into(), port);
+        tcp_connect(&ctx, authority, false, dns, connector)
+            .await
+            .unwrap();
+    }
+}
@@ -7,6 +7,10 @@ mod connect;
 #[doc(inline)]
 pub use connect::{TcpStreamConnector, default_tcp_connect, tcp_connect};
 
+mod egress;
+#[doc(inline)]
+pub use egress::EgressGuardConnector;
+
 #[cfg(feature = "http")]
 mod request;
 #[cfg(feature = "http")]
@@ -5,12 +5,13 @@ use private::{ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel};
 use rama_core::error::{BoxError, ErrorExt, OpaqueError};
 use rama_core::{Context, Layer, Service};
 use rama_net::address::Host;
-use rama_net::client::{ConnectorService, EstablishedClientConnection};
+use rama_net::client::{ConnectionTraceHandle, ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::tls::ApplicationProtocol;
 use rama_net::tls::client::NegotiatedTlsParameters;
 use rama_net::transport::TryRefIntoTransportContext;
 use std::fmt;
+use std::time::Instant;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_boring::SslStream;
 
@@ -262,7 +263,11 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get().cloned();
+        let handshake_start = Instant::now();
         let (stream, negotiated_params) = self.handshake(connector_data, host, conn).await?;
+        if let Some(trace) = ctx.get::<ConnectionTraceHandle>() {
+            trace.record_tls(handshake_start.elapsed(), negotiated_params.clone());
+        }
 
         tracing::trace!(
             authority = %transport_ctx.authority,
@@ -315,7 +320,11 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get().cloned();
+        let handshake_start = Instant::now();
         let (conn, negotiated_params) = self.handshake(connector_data, host, conn).await?;
+        if let Some(trace) = ctx.get::<ConnectionTraceHandle>() {
+            trace.record_tls(handshake_start.elapsed(), negotiated_params.clone());
+        }
         ctx.insert(negotiated_params);
 
         Ok(EstablishedClientConnection { ctx, req, conn })
@@ -1,11 +1,13 @@
 use super::{TlsAcceptorData, TlsAcceptorService};
 use rama_core::Layer;
+use rama_core::telemetry::prometheus::Registry;
 
 /// A [`Layer`] which wraps the given service with a [`TlsAcceptorService`].
 #[derive(Debug, Clone)]
 pub struct TlsAcceptorLayer {
     data: TlsAcceptorData,
     store_client_hello: bool,
+    registry: Option<Registry>,
 }
 
 impl TlsAcceptorLayer {
@@ -15,6 +17,7 @@ impl TlsAcceptorLayer {
         Self {
             data,
             store_client_hello: false,
+            registry: None,
         }
     }
 
@@ -29,12 +32,31 @@ impl TlsAcceptorLayer {
         self.store_client_hello = store;
         self
     }
+
+    /// Record failed handshakes in the given Prometheus [`Registry`].
+    ///
+    /// See [`metrics`](crate::metrics) for the recorded metrics.
+    pub fn with_prometheus_registry(mut self, registry: &Registry) -> Self {
+        self.registry = Some(registry.clone());
+        self
+    }
+
+    /// Same as [`Self::with_prometheus_registry`] but without consuming `self`.
+    pub fn set_prometheus_registry(&mut self, registry: &Registry) -> &mut Self {
+        self.registry = Some(registry.clone());
+        self
+    }
 }
 
 impl<S> Layer<S> for TlsAcceptorLayer {
     type Service = TlsAcceptorService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        TlsAcceptorService::new(self.data.clone(), inner, self.store_client_hello)
+        let mut service =
+            TlsAcceptorService::new(self.data.clone(), inner, self.store_client_hello);
+        if let Some(registry) = &self.registry {
+            service.set_prometheus_registry(registry);
+        }
+        service
     }
 }
@@ -11,6 +11,7 @@ use parking_lot::Mutex;
 use rama_core::{
     Context, Service,
     error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    telemetry::prometheus::{Counter, Registry},
 };
 use rama_net::{
     http::RequestContext,
@@ -27,6 +28,7 @@ use tracing::{debug, trace};
 pub struct TlsAcceptorService<S> {
     data: TlsAcceptorData,
     store_client_hello: bool,
+    handshake_failures: Option<Counter>,
     inner: S,
 }
 
@@ -36,10 +38,31 @@ impl<S> TlsAcceptorService<S> {
         Self {
             data,
             store_client_hello,
+            handshake_failures: None,
             inner,
         }
     }
 
+    /// Record failed handshakes in the given Prometheus [`Registry`].
+    ///
+    /// See [`metrics`](crate::metrics) for the recorded metrics.
+    pub fn with_prometheus_registry(mut self, registry: &Registry) -> Self {
+        self.handshake_failures = Some(crate::metrics::handshake_failures(registry));
+        self
+    }
+
+    /// Same as [`Self::with_prometheus_registry`] but without consuming `self`.
+    pub fn set_prometheus_registry(&mut self, registry: &Registry) -> &mut Self {
+        self.handshake_failures = Some(crate::metrics::handshake_failures(registry));
+        self
+    }
+
+    fn record_handshake_failure(&self) {
+        if let Some(counter) = &self.handshake_failures {
+            counter.inc(&["boring"]);
+        }
+    }
+
     define_inner_service_accessors!();
 }
 
@@ -48,6 +71,7 @@ impl<S: std::fmt::Debug> std::fmt::Debug for TlsAcceptorService<S> {
         f.debug_struct("TlsAcceptorService")
             .field("data", &self.data)
             .field("store_client_hello", &self.store_client_hello)
+            .field("handshake_failures", &self.handshake_failures)
             .field("inner", &self.inner)
             .finish()
     }
@@ -61,6 +85,7 @@ where
         Self {
             data: self.data.clone(),
             store_client_hello: self.store_client_hello,
+            handshake_failures: self.handshake_failures.clone(),
             inner: self.inner.clone(),
         }
     }
@@ -184,6 +209,7 @@ where
 
         let stream = tokio_boring::accept(&acceptor, stream)
             .await
+            .inspect_err(|_| self.record_handshake_failure())
             .map_err(|err| match err.as_io_error() {
                 Some(err) => OpaqueError::from_display(err.to_string())
                     .context("boring ssl acceptor: accept"),
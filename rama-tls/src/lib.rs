@@ -31,6 +31,8 @@ pub use boring as std;
 
 pub mod keylog;
 
+pub mod metrics;
+
 pub mod types {
     //! common tls types
     #[doc(inline)]
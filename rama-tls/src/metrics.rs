@@ -0,0 +1,22 @@
+//! Prometheus metrics recorded by the tls backends.
+//!
+//! The tls acceptor layers and services of each backend record the following metrics
+//! when configured with a Prometheus [`Registry`], e.g. using `with_prometheus_registry`:
+//!
+//! | name | type | labels | description |
+//! |-|-|-|-|
+//! | `rama_tls_handshake_failures` | counter | `backend` (`rustls` or `boring`) | Amount of inbound tls handshakes which failed. |
+
+use rama_core::telemetry::prometheus::{Counter, Registry};
+
+const TLS_HANDSHAKE_FAILURES: &str = "rama_tls_handshake_failures";
+
+/// The counter of failed server side tls handshakes, labeled by backend.
+#[cfg_attr(not(any(feature = "rustls", feature = "boring")), allow(dead_code))]
+pub(crate) fn handshake_failures(registry: &Registry) -> Counter {
+    registry.counter(
+        TLS_HANDSHAKE_FAILURES,
+        "Amount of inbound tls handshakes which failed.",
+        &["backend"],
+    )
+}
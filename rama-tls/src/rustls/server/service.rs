@@ -8,6 +8,7 @@ use crate::{
 use rama_core::{
     Context, Service,
     error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    telemetry::prometheus::{Counter, Registry},
 };
 use rama_net::{
     stream::Stream,
@@ -22,6 +23,7 @@ use super::TlsAcceptorData;
 pub struct TlsAcceptorService<S> {
     data: TlsAcceptorData,
     store_client_hello: bool,
+    handshake_failures: Option<Counter>,
     inner: S,
 }
 
@@ -31,10 +33,31 @@ impl<S> TlsAcceptorService<S> {
         Self {
             data,
             store_client_hello,
+            handshake_failures: None,
             inner,
         }
     }
 
+    /// Record failed handshakes in the given Prometheus [`Registry`].
+    ///
+    /// See [`metrics`](crate::metrics) for the recorded metrics.
+    pub fn with_prometheus_registry(mut self, registry: &Registry) -> Self {
+        self.handshake_failures = Some(crate::metrics::handshake_failures(registry));
+        self
+    }
+
+    /// Same as [`Self::with_prometheus_registry`] but without consuming `self`.
+    pub fn set_prometheus_registry(&mut self, registry: &Registry) -> &mut Self {
+        self.handshake_failures = Some(crate::metrics::handshake_failures(registry));
+        self
+    }
+
+    fn record_handshake_failure(&self) {
+        if let Some(counter) = &self.handshake_failures {
+            counter.inc(&["rustls"]);
+        }
+    }
+
     define_inner_service_accessors!();
 }
 
@@ -43,6 +66,7 @@ impl<S: std::fmt::Debug> std::fmt::Debug for TlsAcceptorService<S> {
         f.debug_struct("TlsAcceptorService")
             .field("data", &self.data)
             .field("store_client_hello", &self.store_client_hello)
+            .field("handshake_failures", &self.handshake_failures)
             .field("inner", &self.inner)
             .finish()
     }
@@ -56,6 +80,7 @@ where
         Self {
             data: self.data.clone(),
             store_client_hello: self.store_client_hello,
+            handshake_failures: self.handshake_failures.clone(),
             inner: self.inner.clone(),
         }
     }
@@ -75,7 +100,9 @@ where
 
         let acceptor = LazyConfigAcceptor::new(Acceptor::default(), stream);
 
-        let start = acceptor.await?;
+        let start = acceptor
+            .await
+            .inspect_err(|_| self.record_handshake_failure())?;
 
         let secure_transport = if self.store_client_hello {
             SecureTransport::with_client_hello(start.client_hello().into())
@@ -85,7 +112,8 @@ where
 
         let stream = start
             .into_stream(tls_acceptor_data.server_config.clone())
-            .await?;
+            .await
+            .inspect_err(|_| self.record_handshake_failure())?;
         let (_, conn_data_ref) = stream.get_ref();
         ctx.insert(NegotiatedTlsParameters {
             protocol_version: conn_data_ref